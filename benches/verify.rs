@@ -0,0 +1,38 @@
+use cacophoney_lib::crypto::{bench, hash, KeyPair, PublicKey, Signature, SignedHash};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn signed(len: usize) -> Vec<(PublicKey, SignedHash, Signature)> {
+    (0..len)
+        .map(|i| {
+            let pair = KeyPair::generate();
+            let msg = hash(i.to_be_bytes());
+            (pair.public, msg.into(), pair.sign(msg))
+        })
+        .collect()
+}
+
+fn verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify");
+
+    for len in [1, 16, 256] {
+        let items = signed(len);
+        let parsed = bench::Parsed::new(&items).unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("parse_per_call", len),
+            &items,
+            |b, items| b.iter(|| bench::parse_per_call(items)),
+        );
+        group.bench_with_input(BenchmarkId::new("parsed", len), &parsed, |b, parsed| {
+            b.iter(|| parsed.verify())
+        });
+        group.bench_with_input(BenchmarkId::new("batch", len), &items, |b, items| {
+            b.iter(|| bench::batch(items))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
@@ -0,0 +1,181 @@
+//! Two clients chatting through one server.
+//!
+//! Bob asks to be notified when Alice connects, and opens a stream to her once she identifies.
+//! The crate does not ship a network transport yet, so the clients and the server run in one
+//! process, and the streams are the in-memory streams of [`cacophoney_lib::mock`].
+//!
+//! Run with `cargo run --example chat`.
+
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use cacophoney_lib::crypto::{KeyPair, KeyTriad, PublicKey};
+use cacophoney_lib::mock::{stream_pair, MockRead, MockWrite};
+use cacophoney_lib::node::error::{StreamOpenError, StreamOpenErrorType};
+use cacophoney_lib::node::{InboundEndpoint, InboundHdl, Notify, OpenStream, ServerHandle};
+use cacophoney_lib::obj::{
+    CommunicationReq, EndpointInfo, Introduction, KeysExistsReq, Mail, PreIdentifyReq,
+    SignMessageType, SignedData, SignedFormat,
+};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tower_async::Service;
+
+/// The client a stream was opened to is gone.
+#[derive(Debug)]
+struct ClientGone;
+
+impl fmt::Display for ClientGone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the client is gone")
+    }
+}
+
+impl std::error::Error for ClientGone {}
+
+impl StreamOpenError for ClientGone {
+    fn error_type(&self) -> Option<StreamOpenErrorType> {
+        None
+    }
+}
+
+/// The side of the connection to a client that the server holds.
+#[derive(Debug)]
+struct ClientConn {
+    /// The public keys the client is notified of.
+    connected: mpsc::UnboundedSender<PublicKey>,
+    /// The streams other clients open to the client, along with their public keys.
+    streams: mpsc::UnboundedSender<(PublicKey, MockRead)>,
+}
+
+impl Notify for ClientConn {
+    type Err = ClientGone;
+
+    async fn notify_connected(&self, triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        self.connected
+            .send(triad.public_key)
+            .map_err(|_| ClientGone)
+    }
+    async fn notify_introduced(&self, _intro: &Introduction) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_revoked(&self, _revocation: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_mail(&self, _mail: &Mail) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+impl Service<PublicKey> for ClientConn {
+    type Response = MockWrite;
+    type Error = ClientGone;
+
+    async fn call(&self, from: PublicKey) -> Result<Self::Response, Self::Error> {
+        let (read, write) = stream_pair(16);
+        self.streams.send((from, read)).map_err(|_| ClientGone)?;
+        Ok(write)
+    }
+}
+
+impl OpenStream for ClientConn {
+    type Err = ClientGone;
+}
+
+/// A client connected to the server.
+struct Client {
+    name: &'static str,
+    pair: KeyPair,
+    hdl: InboundHdl<ClientConn>,
+    connected: mpsc::UnboundedReceiver<PublicKey>,
+    streams: mpsc::UnboundedReceiver<(PublicKey, MockRead)>,
+}
+
+impl Client {
+    fn connect(name: &'static str, id: u64, server: &Arc<ServerHandle<ClientConn>>) -> Self {
+        let (connected, connected_recv) = mpsc::unbounded_channel();
+        let (streams, streams_recv) = mpsc::unbounded_channel();
+        let info =
+            EndpointInfo::non_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 4000 + id as u16)));
+
+        Self {
+            name,
+            pair: KeyPair::generate(),
+            hdl: InboundEndpoint::server_hdl(
+                id,
+                info,
+                server.clone(),
+                ClientConn { connected, streams },
+            ),
+            connected: connected_recv,
+            streams: streams_recv,
+        }
+    }
+    async fn identify(&self) {
+        let identify = self.hdl.pre_identify(PreIdentifyReq {}).await;
+        let triad = KeyTriad::gen_signed(
+            &self.pair.private,
+            &identify,
+            SignMessageType::Identify,
+            SignedFormat::Cbor,
+        )
+        .unwrap();
+        self.hdl.identify(triad).await.unwrap();
+        println!(
+            "{} identified as {}",
+            self.name,
+            hex::encode(self.pair.public.0)
+        );
+    }
+    async fn send(&self, to: PublicKey, message: &str) {
+        let req = CommunicationReq {
+            from: self.pair.public,
+            to,
+            grant: None,
+            hops: 0,
+        };
+        let mut stream = self.hdl.communicate(req).await.unwrap().stream;
+        stream.write_all(message.as_bytes()).await.unwrap();
+        stream.write_all(b"\n").await.unwrap();
+    }
+    async fn receive(&mut self) -> (PublicKey, String) {
+        let (from, read) = self.streams.recv().await.unwrap();
+        let mut line = String::new();
+        BufReader::new(read).read_line(&mut line).await.unwrap();
+        println!("{} received {:?}", self.name, line.trim_end());
+
+        (from, line)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let server = ServerHandle::new_hdl();
+    let mut alice = Client::connect("alice", 0, &server);
+    let mut bob = Client::connect("bob", 1, &server);
+    bob.identify().await;
+
+    // bob watches for alice, who has not connected yet
+    let resp = bob
+        .hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![alice.pair.public],
+            notify: true,
+        })
+        .await
+        .unwrap();
+    assert!(resp.triads.is_empty());
+
+    alice.identify().await;
+    let connected = bob.connected.recv().await.unwrap();
+    assert_eq!(connected, alice.pair.public);
+    println!("bob was notified that alice connected");
+
+    bob.send(alice.pair.public, "hi alice").await;
+    let (from, _) = alice.receive().await;
+    assert_eq!(from, bob.pair.public);
+
+    alice.send(from, "hi bob").await;
+    bob.receive().await;
+}
@@ -0,0 +1,382 @@
+use std::error::Error as StdError;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use tokio::sync::{oneshot, Mutex};
+
+use super::error::{ClientError, DispatchError};
+use super::RetryPolicy;
+use crate::obj::{
+    Goodbye, GoodbyeCode, InvalidTypeError, NodeInfoResp, PingReq, PongResp, ReqMessage,
+    RespMessage, Tagged,
+};
+use crate::utils::{self, LockExt};
+use crate::CURRENT_VERSION;
+
+/// Sends requests on one connection and matches the responses to them by id, so many requests
+/// can be in flight at the same time.
+///
+/// The responses received on the connection must be handed to [`Dispatcher::dispatch`], or to
+/// [`Dispatcher::run`] running alongside the requests.
+#[derive(Debug)]
+pub struct Dispatcher<Si> {
+    sink: Mutex<Si>,
+    /// The requests waiting for a response, keyed by their ids.
+    pending: scc::HashMap<u64, oneshot::Sender<Tagged<RespMessage>>>,
+    next_id: AtomicU64,
+    /// The goodbye the node sent on its own, before closing the connection.
+    goodbye: std::sync::Mutex<Option<Goodbye>>,
+    /// Whether the responses ended, so that no request can be answered anymore.
+    closed: AtomicBool,
+}
+
+impl<Si> Dispatcher<Si> {
+    pub fn new(sink: Si) -> Self {
+        Self {
+            sink: Mutex::new(sink),
+            pending: Default::default(),
+            next_id: AtomicU64::new(0),
+            goodbye: Default::default(),
+            closed: AtomicBool::new(false),
+        }
+    }
+    /// Returns whether the responses handed to [`Dispatcher::run`] ended. Requests fail with
+    /// [`DispatchError::Closed`] from then on.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+    /// Returns the amount of requests waiting for a response.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+    /// Returns the goodbye the node sent before closing the connection, if it sent one without
+    /// being asked to.
+    pub fn goodbye(&self) -> Option<Goodbye> {
+        self.goodbye.locked().clone()
+    }
+    /// Hands `resp` to the request it answers. A goodbye no request is waiting for is kept, and
+    /// can be read with [`Dispatcher::goodbye`]. Returns any other response back if no request is
+    /// waiting for its id.
+    pub async fn dispatch(&self, resp: Tagged<RespMessage>) -> Result<(), Tagged<RespMessage>> {
+        match self.pending.remove_async(&resp.id).await {
+            Some((_, send)) => {
+                // the request may have been dropped while waiting
+                let _ = send.send(resp);
+                Ok(())
+            }
+            None => match resp.body {
+                RespMessage::Goodbye(goodbye) => {
+                    *self.goodbye.locked() = Some(goodbye);
+                    Ok(())
+                }
+                _ => Err(resp),
+            },
+        }
+    }
+    /// Dispatches every response of `responses`. Once it ends, the requests still waiting and
+    /// those sent afterwards fail with [`DispatchError::Closed`].
+    pub async fn run(&self, responses: impl Stream<Item = Tagged<RespMessage>>) {
+        let mut responses = std::pin::pin!(responses);
+        while let Some(resp) = responses.next().await {
+            let _ = self.dispatch(resp).await;
+        }
+
+        self.closed.store(true, Ordering::SeqCst);
+        self.pending.clear_async().await;
+    }
+}
+
+impl<Si: Sink<Tagged<ReqMessage>, Error: StdError> + Unpin> Dispatcher<Si> {
+    /// Sends `req` and waits for its response.
+    pub async fn request(
+        &self,
+        req: impl Into<ReqMessage>,
+    ) -> Result<RespMessage, DispatchError<Si::Error>> {
+        Ok(self.request_tagged(req).await?.body)
+    }
+    /// Sends `req` and waits for its response, in the envelope it was received in.
+    async fn request_tagged(
+        &self,
+        req: impl Into<ReqMessage>,
+    ) -> Result<Tagged<RespMessage>, DispatchError<Si::Error>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (send, recv) = oneshot::channel();
+        let _ = self.pending.insert_async(id, send).await;
+        // checked once the request is pending, so that either this or `run` drops it
+        if self.is_closed() {
+            self.pending.remove_async(&id).await;
+            return Err(DispatchError::Closed);
+        }
+
+        let sent = self
+            .sink
+            .lock()
+            .await
+            .send(Tagged::new(id, req.into()))
+            .await;
+        if let Err(err) = sent {
+            self.pending.remove_async(&id).await;
+            return Err(DispatchError::Send(err));
+        }
+
+        recv.await.map_err(|_| DispatchError::Closed)
+    }
+}
+
+impl<Si: Sink<Tagged<ReqMessage>, Error: StdError + Into<ClientError>> + Unpin> Dispatcher<Si> {
+    /// Sends `req` and waits for a response of the type `Resp`. An error sent by the node fails
+    /// with [`ClientError::Server`], and a node that does not speak the version of the client
+    /// fails with [`ClientError::IncompatibleVersion`], as does a response encoded with another
+    /// version of the protocol.
+    pub async fn call<Resp>(&self, req: impl Into<ReqMessage>) -> Result<Resp, ClientError>
+    where
+        Resp: TryFrom<RespMessage, Error = InvalidTypeError>,
+    {
+        let resp = self.request_tagged(req).await?;
+        if !resp.is_compatible() {
+            return Err(ClientError::IncompatibleVersion {
+                ours: CURRENT_VERSION,
+                theirs: resp.version.unwrap_or(0),
+            });
+        }
+        match resp.body {
+            RespMessage::Error(err) => Err(err.into()),
+            RespMessage::Connect(NodeInfoResp {
+                compatible: false,
+                info,
+                ..
+            }) => Err(ClientError::IncompatibleVersion {
+                ours: CURRENT_VERSION,
+                theirs: info.api_version,
+            }),
+            resp => Ok(resp.try_into()?),
+        }
+    }
+    /// Like [`Dispatcher::call`], but retries the request while `policy` allows it, waiting as
+    /// long as the node asked to.
+    pub async fn call_retrying<Resp>(
+        &self,
+        req: impl Into<ReqMessage>,
+        policy: &RetryPolicy,
+    ) -> Result<Resp, ClientError>
+    where
+        Resp: TryFrom<RespMessage, Error = InvalidTypeError>,
+    {
+        let req = req.into();
+        let mut attempt = 1;
+        loop {
+            let err = match self.call(req.clone()).await {
+                Ok(resp) => return Ok(resp),
+                Err(err) => err,
+            };
+            match policy.delay(attempt, &err) {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(err),
+            }
+            attempt += 1;
+        }
+    }
+    /// Pings the node, and returns the round trip time of the ping.
+    pub async fn ping(&self) -> Result<Duration, ClientError> {
+        let req = PingReq {
+            nonce: u64::from_be_bytes(utils::random_bytes()),
+            sent_time: utils::now(),
+        };
+        let pong: PongResp = self.call(req).await?;
+        Ok(pong.rtt(utils::now()))
+    }
+    /// Tells the node why the client is about to close the connection, and returns the goodbye
+    /// the node answered with.
+    pub async fn close(
+        &self,
+        code: GoodbyeCode,
+        reason: impl ToString,
+    ) -> Result<Goodbye, ClientError> {
+        self.call(Goodbye::new(code, reason)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use futures::channel::mpsc;
+
+    use super::*;
+    use crate::obj::{ErrorCode, ErrorResp, IdentifyData, PreIdentifyReq};
+
+    #[tokio::test]
+    async fn out_of_order() {
+        let (req_send, mut req_recv) = mpsc::unbounded();
+        let (resp_send, resp_recv) = mpsc::unbounded();
+        let dispatcher = Dispatcher::new(req_send);
+
+        // answers the second request before the first
+        let server = async move {
+            let first: Tagged<ReqMessage> = req_recv.next().await.unwrap();
+            let second = req_recv.next().await.unwrap();
+            for (id, compatible) in [(second.id, false), (first.id, true)] {
+                let body = RespMessage::Connect(NodeInfoResp {
+                    compatible,
+                    ..Default::default()
+                });
+                resp_send.unbounded_send(Tagged::new(id, body)).unwrap();
+            }
+        };
+
+        let requests = async {
+            let (first, second) = tokio::join!(
+                dispatcher.request(PreIdentifyReq {}),
+                dispatcher.request(PreIdentifyReq {}),
+            );
+            let compatible = |resp: RespMessage| match resp {
+                RespMessage::Connect(resp) => resp.compatible,
+                _ => unreachable!(),
+            };
+            assert!(compatible(first.unwrap()));
+            assert!(!compatible(second.unwrap()));
+        };
+
+        tokio::join!(server, requests, dispatcher.run(resp_recv));
+        assert_eq!(dispatcher.pending(), 0);
+
+        // requests fail once the responses end
+        let (req_send, _req_recv) = mpsc::unbounded();
+        let dispatcher = Dispatcher::new(req_send);
+        let (resp_send, resp_recv) = mpsc::unbounded::<Tagged<RespMessage>>();
+        drop(resp_send);
+        let (resp, _) = tokio::join!(
+            dispatcher.request(PreIdentifyReq {}),
+            // let the request be sent before the responses end
+            async {
+                tokio::task::yield_now().await;
+                dispatcher.run(resp_recv).await
+            }
+        );
+        assert!(matches!(resp, Err(DispatchError::Closed)));
+
+        // requests sent once the responses ended fail rather than wait forever
+        assert!(dispatcher.is_closed());
+        assert!(matches!(
+            dispatcher.request(PreIdentifyReq {}).await,
+            Err(DispatchError::Closed)
+        ));
+        assert_eq!(dispatcher.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn call() {
+        let (req_send, mut req_recv) = mpsc::unbounded();
+        let (resp_send, resp_recv) = mpsc::unbounded();
+        let req_send = req_send.sink_map_err(|_| std::io::Error::from(ErrorKind::BrokenPipe));
+        let dispatcher = Dispatcher::new(req_send);
+
+        let server = async move {
+            let bodies = [
+                RespMessage::Error(ErrorResp::new(ErrorCode::REVOKED, "revoked")),
+                RespMessage::Connect(NodeInfoResp::default()),
+                RespMessage::Connect(NodeInfoResp {
+                    compatible: true,
+                    ..Default::default()
+                }),
+                RespMessage::Connect(NodeInfoResp {
+                    compatible: true,
+                    ..Default::default()
+                }),
+            ];
+            for (i, body) in bodies.into_iter().enumerate() {
+                let req: Tagged<ReqMessage> = req_recv.next().await.unwrap();
+                let mut resp = Tagged::new(req.id, body);
+                // the last response is of a newer version
+                if i == 3 {
+                    resp.version = Some(CURRENT_VERSION + 1);
+                }
+                resp_send.unbounded_send(resp).unwrap();
+            }
+        };
+
+        let requests = async {
+            let resp = dispatcher.call::<IdentifyData>(PreIdentifyReq {}).await;
+            assert_eq!(resp.unwrap_err().code(), Some(ErrorCode::REVOKED));
+            let resp = dispatcher.call::<NodeInfoResp>(PreIdentifyReq {}).await;
+            assert!(matches!(resp, Err(ClientError::IncompatibleVersion { .. })));
+            let resp = dispatcher.call::<IdentifyData>(PreIdentifyReq {}).await;
+            assert!(matches!(resp, Err(ClientError::UnexpectedResponse(_))));
+            let resp = dispatcher.call::<NodeInfoResp>(PreIdentifyReq {}).await;
+            assert!(matches!(
+                resp,
+                Err(ClientError::IncompatibleVersion { theirs, .. }) if theirs == CURRENT_VERSION + 1
+            ));
+        };
+
+        tokio::join!(server, requests, dispatcher.run(resp_recv));
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        let (req_send, mut req_recv) = mpsc::unbounded();
+        let (resp_send, resp_recv) = mpsc::unbounded();
+        let req_send = req_send.sink_map_err(|_| std::io::Error::from(ErrorKind::BrokenPipe));
+        let dispatcher = Dispatcher::new(req_send);
+        let policy = RetryPolicy::default();
+
+        // rate limited twice, then answered
+        let limited = ErrorResp::new(ErrorCode::RATE_LIMITED, "rate limited")
+            .with_retry_after(Duration::from_millis(5));
+        let server = async move {
+            let bodies = [
+                RespMessage::Error(limited.clone()),
+                RespMessage::Error(limited),
+                RespMessage::Connect(NodeInfoResp {
+                    compatible: true,
+                    ..Default::default()
+                }),
+                RespMessage::Error(ErrorResp::new(ErrorCode::REVOKED, "revoked")),
+            ];
+            for body in bodies {
+                let req: Tagged<ReqMessage> = req_recv.next().await.unwrap();
+                resp_send.unbounded_send(Tagged::new(req.id, body)).unwrap();
+            }
+        };
+
+        let requests = async {
+            let resp = dispatcher
+                .call_retrying::<NodeInfoResp>(PreIdentifyReq {}, &policy)
+                .await;
+            assert!(resp.unwrap().compatible);
+
+            // errors that retrying cannot fix fail at once
+            let resp = dispatcher
+                .call_retrying::<NodeInfoResp>(PreIdentifyReq {}, &policy)
+                .await;
+            assert_eq!(resp.unwrap_err().code(), Some(ErrorCode::REVOKED));
+        };
+
+        tokio::join!(server, requests, dispatcher.run(resp_recv));
+
+        let limited = ClientError::from(ErrorResp::new(ErrorCode::RATE_LIMITED, ""));
+        assert_eq!(policy.delay(1, &limited), Some(policy.base_delay));
+        assert_eq!(policy.delay(2, &limited), Some(policy.base_delay * 2));
+        assert_eq!(policy.delay(3, &limited), None);
+        let hinted = ClientError::from(
+            ErrorResp::new(ErrorCode::RATE_LIMITED, "").with_retry_after(Duration::from_secs(60)),
+        );
+        assert_eq!(policy.delay(1, &hinted), None);
+    }
+
+    #[tokio::test]
+    async fn goodbye() {
+        let (req_send, _req_recv) = mpsc::unbounded::<Tagged<ReqMessage>>();
+        let dispatcher = Dispatcher::new(req_send);
+
+        let bye = Goodbye::new(GoodbyeCode::SHUTDOWN, "restarting");
+        let other = RespMessage::Connect(NodeInfoResp::default());
+        assert!(dispatcher.dispatch(Tagged::new(7, other)).await.is_err());
+        assert_eq!(dispatcher.goodbye(), None);
+
+        let body = RespMessage::Goodbye(bye.clone());
+        assert!(dispatcher.dispatch(Tagged::new(8, body)).await.is_ok());
+        assert_eq!(dispatcher.goodbye(), Some(bye));
+    }
+}
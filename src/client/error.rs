@@ -0,0 +1,153 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use std::error::Error as StdError;
+use std::io::Error as IoError;
+use std::time::Duration;
+
+use crate::codec::CodecError;
+use crate::crypto::PublicKey;
+use crate::obj::{ErrorCode, ErrorResp, InvalidTypeError};
+
+/// An error that can occur when checking the public key of a server against its pin.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum PinError<Err: StdError> {
+    /// The pin could not be read from or written to the store.
+    #[error("pin store error: {}", .0)]
+    Store(Err),
+    /// The server presented a different key than the pinned one, without a valid rotation record.
+    #[error("the server key does not match the pinned key")]
+    Mismatch {
+        pinned: PublicKey,
+        received: PublicKey,
+    },
+}
+
+/// An error that can occur when a [`Dispatcher`](super::Dispatcher) sends a request.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum DispatchError<Err: StdError> {
+    /// The request could not be sent.
+    #[error("failed to send request: {}", .0)]
+    Send(Err),
+    /// The connection closed before the response was received.
+    #[error("the connection closed before the response was received")]
+    Closed,
+}
+
+/// The broad category of a [`ClientError`], so applications can decide how to react to a failure
+/// without matching every variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Hash)]
+pub enum ErrorClass {
+    /// The connection failed or closed. The request may succeed on a new connection.
+    Transport,
+    /// The client and the node do not understand each other. Retrying does not help.
+    Protocol,
+    /// The node understood the request and refused it.
+    Application,
+}
+
+impl ErrorClass {
+    /// Returns the class of the errors the node sends with `code`.
+    pub const fn of(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::UNAVAILABLE => Self::Transport,
+            ErrorCode::NOT_SERVER | ErrorCode::UNSUPPORTED => Self::Protocol,
+            _ => Self::Application,
+        }
+    }
+}
+
+/// An error that can occur when talking to a node.
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("{}", .0)]
+    Io(#[from] IoError),
+    #[error("{}", .0)]
+    Codec(#[from] CodecError),
+    /// The node does not speak the version of the protocol of the client.
+    #[error("the node speaks version {theirs} of the protocol, expected version {ours}")]
+    IncompatibleVersion { ours: u32, theirs: u32 },
+    /// The connection closed before the response was received.
+    #[error("the connection closed before the response was received")]
+    Closed,
+    /// The node answered with a response of another type than the request expects.
+    #[error("unexpected response: {}", .0)]
+    UnexpectedResponse(#[from] InvalidTypeError),
+    /// The node answered with an error.
+    #[error("{}", .0)]
+    Server(#[from] ErrorResp),
+}
+
+impl ClientError {
+    /// Returns the class of this error.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::Io(_) | Self::Codec(CodecError::Io(_)) | Self::Closed => ErrorClass::Transport,
+            Self::Codec(_) | Self::IncompatibleVersion { .. } | Self::UnexpectedResponse(_) => {
+                ErrorClass::Protocol
+            }
+            Self::Server(resp) => ErrorClass::of(resp.code),
+        }
+    }
+    /// Returns the code the node sent, if the node answered with an error.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Server(resp) => Some(resp.code),
+            _ => None,
+        }
+    }
+    /// Returns how long the node asked to wait before retrying, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Server(resp) => resp.retry_after.map(Duration::from_millis),
+            _ => None,
+        }
+    }
+}
+
+impl<Err: StdError + Into<ClientError>> From<DispatchError<Err>> for ClientError {
+    fn from(value: DispatchError<Err>) -> Self {
+        match value {
+            DispatchError::Send(err) => err.into(),
+            DispatchError::Closed => Self::Closed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn class() {
+        let io = || IoError::from(ErrorKind::ConnectionReset);
+        assert_eq!(ClientError::from(io()).class(), ErrorClass::Transport);
+        assert_eq!(
+            ClientError::from(CodecError::from(io())).class(),
+            ErrorClass::Transport
+        );
+        assert_eq!(
+            ClientError::from(DispatchError::<IoError>::Closed).class(),
+            ErrorClass::Transport
+        );
+        assert_eq!(
+            ClientError::from(CodecError::FrameTooLarge { size: 2, max: 1 }).class(),
+            ErrorClass::Protocol
+        );
+        assert_eq!(
+            ClientError::IncompatibleVersion { ours: 0, theirs: 1 }.class(),
+            ErrorClass::Protocol
+        );
+
+        let server = |code| ClientError::from(ErrorResp::new(code, ""));
+        assert_eq!(
+            server(ErrorCode::UNAVAILABLE).class(),
+            ErrorClass::Transport
+        );
+        assert_eq!(server(ErrorCode::UNSUPPORTED).class(), ErrorClass::Protocol);
+        assert_eq!(server(ErrorCode::REVOKED).class(), ErrorClass::Application);
+        assert_eq!(server(ErrorCode::REVOKED).code(), Some(ErrorCode::REVOKED));
+    }
+}
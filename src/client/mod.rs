@@ -0,0 +1,11 @@
+mod dispatch;
+pub mod error;
+mod pin;
+mod receipt;
+mod reconnect;
+mod retry;
+
+pub use dispatch::*;
+pub use pin::*;
+pub use reconnect::*;
+pub use retry::*;
@@ -0,0 +1,223 @@
+use std::convert::Infallible;
+use std::error::Error as StdError;
+
+use arcstr::ArcStr;
+use futures::Future;
+use serde::{Deserialize, Serialize};
+
+use super::error::PinError;
+use crate::crypto::{HashMsg, KeyTriad, PrivateKey, PublicKey, ToHashMsg};
+
+/// Prefixes the hash of a [`KeyRotation`] before it is signed.
+const ROTATION_PREFIX: u8 = 5;
+
+/// A record in which a server hands its domain over from its old key to a new one. Is signed by
+/// the old key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyRotation {
+    /// The domain of the server.
+    pub domain: ArcStr,
+    /// The key that replaces the signer.
+    #[serde(rename = "newKey")]
+    pub new_key: PublicKey,
+    /// The time the rotation was signed.
+    pub time: u64,
+}
+
+impl ToHashMsg for &KeyRotation {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[ROTATION_PREFIX]);
+        hasher.update(&(self.domain.len() as u64).to_be_bytes());
+        hasher.update(self.domain.as_bytes());
+        hasher.update(&self.new_key.0);
+        hasher.update(&self.time.to_be_bytes());
+        HashMsg(hasher.finalize().into())
+    }
+}
+
+impl KeyTriad<KeyRotation> {
+    /// Signs a rotation of `domain` from `old` to `new_key` at the time `time`.
+    pub fn rotate(old: &PrivateKey, domain: ArcStr, new_key: PublicKey, time: u64) -> Self {
+        let rotation = KeyRotation {
+            domain,
+            new_key,
+            time,
+        };
+
+        KeyTriad {
+            public_key: old.derive_public(),
+            signature: old.sign(&rotation),
+            signed: rotation,
+        }
+    }
+    /// Returns whether this record rotates `domain` from `old` to `new`.
+    pub fn rotates(&self, domain: &str, old: &PublicKey, new: &PublicKey) -> bool {
+        self.signed.domain == domain
+            && self.public_key == *old
+            && self.signed.new_key == *new
+            && self.public_key.valid(&self.signed, &self.signature)
+    }
+}
+
+/// A store of the public keys pinned to the domains of servers.
+pub trait PinStore {
+    type Err: StdError;
+
+    /// Returns the key pinned to `domain`, if any.
+    fn get(&self, domain: &str) -> impl Future<Output = Result<Option<PublicKey>, Self::Err>>;
+
+    /// Pins `key` to `domain`, replacing the previous pin.
+    fn set(&self, domain: &str, key: PublicKey) -> impl Future<Output = Result<(), Self::Err>>;
+}
+
+/// A [`PinStore`] that keeps the pins in memory.
+#[derive(Debug, Default)]
+pub struct MemoryPinStore {
+    pins: scc::HashMap<ArcStr, PublicKey>,
+}
+
+impl PinStore for MemoryPinStore {
+    type Err = Infallible;
+
+    async fn get(&self, domain: &str) -> Result<Option<PublicKey>, Self::Err> {
+        Ok(self.pins.read_async(domain, |_, key| *key).await)
+    }
+    async fn set(&self, domain: &str, key: PublicKey) -> Result<(), Self::Err> {
+        self.pins
+            .entry_async(ArcStr::from(domain))
+            .await
+            .and_modify(|value| *value = key)
+            .or_insert(key);
+        Ok(())
+    }
+}
+
+/// What happens when a server presents a key other than its pin, without a valid rotation record.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PinPolicy {
+    /// The key is rejected.
+    #[default]
+    Enforce,
+    /// The key is accepted and pinned, and the change is reported.
+    Warn,
+}
+
+/// The outcome of checking the key of a server against its pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PinStatus {
+    /// The domain was not pinned before, and is now pinned to the key.
+    New,
+    /// The key is the pinned key.
+    Matched,
+    /// The pinned key signed a rotation to the key, which is now pinned.
+    Rotated { old: PublicKey },
+    /// The key is not the pinned key, and was pinned anyway because of [`PinPolicy::Warn`].
+    Changed { old: PublicKey },
+}
+
+/// Pins the public key of each server the first time it is seen, and checks that the server
+/// presents the same key on later connections.
+#[derive(Debug, Default)]
+pub struct KeyPins<S> {
+    store: S,
+    policy: PinPolicy,
+}
+
+impl<S: PinStore> KeyPins<S> {
+    pub fn new(store: S, policy: PinPolicy) -> Self {
+        Self { store, policy }
+    }
+    /// Returns the store of the pins.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+    /// Checks the key presented by the server of `domain` against its pin. `rotation` is the
+    /// rotation record the server presented along with its key, if any.
+    pub async fn check(
+        &self,
+        domain: &str,
+        key: PublicKey,
+        rotation: Option<&KeyTriad<KeyRotation>>,
+    ) -> Result<PinStatus, PinError<S::Err>> {
+        let pinned = match self.store.get(domain).await.map_err(PinError::Store)? {
+            Some(value) => value,
+            None => {
+                self.store.set(domain, key).await.map_err(PinError::Store)?;
+                return Ok(PinStatus::New);
+            }
+        };
+
+        if pinned == key {
+            return Ok(PinStatus::Matched);
+        }
+
+        let status = match rotation {
+            Some(rotation) if rotation.rotates(domain, &pinned, &key) => {
+                PinStatus::Rotated { old: pinned }
+            }
+            _ if self.policy == PinPolicy::Warn => PinStatus::Changed { old: pinned },
+            _ => {
+                return Err(PinError::Mismatch {
+                    pinned,
+                    received: key,
+                })
+            }
+        };
+        self.store.set(domain, key).await.map_err(PinError::Store)?;
+
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[tokio::test]
+    async fn pins() {
+        let old = KeyPair::generate();
+        let new = KeyPair::generate();
+        let pins = KeyPins::new(MemoryPinStore::default(), PinPolicy::Enforce);
+
+        assert_eq!(
+            pins.check("example.com", old.public, None).await,
+            Ok(PinStatus::New)
+        );
+        assert_eq!(
+            pins.check("example.com", old.public, None).await,
+            Ok(PinStatus::Matched)
+        );
+        assert_eq!(
+            pins.check("example.com", new.public, None).await,
+            Err(PinError::Mismatch {
+                pinned: old.public,
+                received: new.public
+            })
+        );
+
+        // a rotation for another domain is not accepted
+        let other = KeyTriad::rotate(&old.private, "other.com".into(), new.public, 0);
+        assert!(pins
+            .check("example.com", new.public, Some(&other))
+            .await
+            .is_err());
+
+        let rotation = KeyTriad::rotate(&old.private, "example.com".into(), new.public, 0);
+        assert_eq!(
+            pins.check("example.com", new.public, Some(&rotation)).await,
+            Ok(PinStatus::Rotated { old: old.public })
+        );
+        assert_eq!(pins.store().get("example.com").await, Ok(Some(new.public)));
+
+        let pins = KeyPins::new(MemoryPinStore::default(), PinPolicy::Warn);
+        pins.check("example.com", old.public, None).await.unwrap();
+        assert_eq!(
+            pins.check("example.com", new.public, None).await,
+            Ok(PinStatus::Changed { old: old.public })
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use crate::crypto::{KeyTriad, PrivateKey, PublicKey, ToHashMsg};
+use crate::obj::{Mail, MessageId, Receipt, ReceiptStatus, SendReceiptReq, SignedData};
+use crate::utils;
+
+impl Mail {
+    /// Returns whether the id of this message was derived from its sender, its `recipient` and
+    /// its signed payload.
+    pub fn id_valid(&self, recipient: &PublicKey) -> bool {
+        let message = self.payload.signed.to_hash_msg();
+        self.id == MessageId::new(&self.payload.public_key, recipient, &message)
+    }
+    /// Signs a receipt of this message as its recipient `key`. The request routes the receipt
+    /// back to the sender through the node the message was fetched from.
+    pub fn receipt(&self, key: &PrivateKey, status: ReceiptStatus) -> SendReceiptReq {
+        let message = self.payload.signed.to_hash_msg();
+
+        SendReceiptReq {
+            receipt: KeyTriad::receipt(key, self.id, message, status, utils::now()),
+        }
+    }
+}
+
+impl KeyTriad<Receipt> {
+    /// Returns whether this is a valid receipt by `to` of the message `id`, whose payload was
+    /// `payload`.
+    pub fn acknowledges(&self, to: &PublicKey, id: MessageId, payload: &SignedData) -> bool {
+        self.public_key == *to
+            && self.signed.id == id
+            && self.signed.message == payload.to_hash_msg()
+            && self.valid()
+    }
+}
@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::crypto::PublicKey;
+use crate::obj::{Goodbye, GoodbyeCode};
+use crate::utils;
+
+/// How a client waits before reconnecting to a node it lost its connection to.
+///
+/// Delays grow with every failed attempt, and are spread at random over the upper half of the
+/// backoff, so that the clients of a node that restarted do not all reconnect at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReconnectPolicy {
+    /// The delay before the first attempt, doubled for each attempt after it.
+    pub base_delay: Duration,
+    /// The longest delay of the backoff.
+    pub max_delay: Duration,
+    /// The longest [`KeepDown`](crate::obj::KeepDown) a node can ask for. Longer directives are
+    /// shortened to it.
+    pub max_keep_down: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+            max_keep_down: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the backoff before the attempt `attempt`, counting from 1, with jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let half = delay / 2;
+        half + Duration::from_millis(utils::rng().gen_range(0..=half.as_millis() as u64))
+    }
+    /// Returns how long the node that sent `goodbye` asked not to be reconnected to at `now`.
+    ///
+    /// The [`KeepDown`](crate::obj::KeepDown) of the goodbye is only honored if its signature is
+    /// valid, and, if `node` is known, it was signed by `node`.
+    pub fn keep_down(&self, goodbye: &Goodbye, node: Option<&PublicKey>, now: u64) -> Duration {
+        let Some(keep_down) = &goodbye.keep_down else {
+            return Duration::ZERO;
+        };
+        if !keep_down.valid() || node.is_some_and(|node| keep_down.public_key != *node) {
+            return Duration::ZERO;
+        }
+
+        keep_down.signed.remaining(now).min(self.max_keep_down)
+    }
+}
+
+/// Tracks the attempts to reconnect to a node, and how long to wait before each of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Reconnect {
+    pub policy: ReconnectPolicy,
+    /// The public key of the node, that signs the keep-down directives it sends.
+    node: Option<PublicKey>,
+    /// The amount of attempts since the client was last connected.
+    attempts: u32,
+}
+
+impl Reconnect {
+    /// Tracks the attempts to reconnect to the node of public key `node`, if known.
+    pub fn new(policy: ReconnectPolicy, node: Option<PublicKey>) -> Self {
+        Self {
+            policy,
+            node,
+            attempts: 0,
+        }
+    }
+    /// Returns the amount of attempts since the client was last connected.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+    /// Returns how long to wait before the next attempt, given the goodbye the node sent before
+    /// closing the connection, if any. Counts the attempt.
+    ///
+    /// A node that banned the client and sent no keep-down is not reconnected to, and [`None`] is
+    /// returned.
+    pub fn next_delay(&mut self, goodbye: Option<&Goodbye>) -> Option<Duration> {
+        let keep_down = match goodbye {
+            Some(goodbye) => {
+                let keep_down = self
+                    .policy
+                    .keep_down(goodbye, self.node.as_ref(), utils::now());
+                if goodbye.code == GoodbyeCode::BANNED && keep_down.is_zero() {
+                    return None;
+                }
+                keep_down
+            }
+            None => Duration::ZERO,
+        };
+
+        self.attempts = self.attempts.saturating_add(1);
+        Some(self.policy.backoff(self.attempts).max(keep_down))
+    }
+    /// Waits before the next attempt, as returned by [`Reconnect::next_delay`]. Returns `false`
+    /// right away if the node should not be reconnected to.
+    pub async fn wait(&mut self, goodbye: Option<&Goodbye>) -> bool {
+        match self.next_delay(goodbye) {
+            Some(delay) => {
+                tokio::time::sleep(delay).await;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Records that the client connected, so that the backoff starts over.
+    pub fn connected(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{KeyPair, KeyTriad};
+
+    #[test]
+    fn backoff() {
+        let policy = ReconnectPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(400),
+            ..Default::default()
+        };
+        for _ in 0..32 {
+            let delay = policy.backoff(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+            assert!(policy.backoff(10) <= Duration::from_millis(400));
+        }
+    }
+
+    #[test]
+    fn keep_down() {
+        let node = KeyPair::generate();
+        let other = KeyPair::generate();
+        let policy = ReconnectPolicy {
+            max_keep_down: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let now = utils::now();
+
+        let goodbye = Goodbye::new(GoodbyeCode::RATE_LIMITED, "slow down").with_keep_down(
+            KeyTriad::keep_down(&node.private, Duration::from_secs(30), now),
+        );
+        assert_eq!(
+            policy.keep_down(&goodbye, Some(&node.public), now),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            policy.keep_down(&goodbye, None, now),
+            Duration::from_secs(30)
+        );
+        // directives of another key are ignored
+        assert_eq!(
+            policy.keep_down(&goodbye, Some(&other.public), now),
+            Duration::ZERO
+        );
+
+        // forged directives are ignored
+        let mut forged = goodbye.clone();
+        if let Some(keep_down) = &mut forged.keep_down {
+            keep_down.signed.min_delay *= 2;
+        }
+        assert_eq!(policy.keep_down(&forged, None, now), Duration::ZERO);
+
+        // long directives are capped
+        let long = Goodbye::new(GoodbyeCode::BANNED, "banned").with_keep_down(KeyTriad::keep_down(
+            &node.private,
+            Duration::from_secs(3600),
+            now,
+        ));
+        assert_eq!(policy.keep_down(&long, None, now), Duration::from_secs(60));
+
+        let mut reconnect = Reconnect::new(policy, Some(node.public));
+        let delay = reconnect.next_delay(Some(&goodbye)).unwrap();
+        assert!(delay > Duration::from_secs(29));
+        assert_eq!(reconnect.attempts(), 1);
+        reconnect.connected();
+        assert_eq!(reconnect.attempts(), 0);
+
+        // a ban without a directive is final
+        let banned = Goodbye::new(GoodbyeCode::BANNED, "banned");
+        assert_eq!(reconnect.next_delay(Some(&banned)), None);
+        assert!(reconnect.next_delay(None).unwrap() < Duration::from_secs(1));
+    }
+}
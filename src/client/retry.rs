@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use super::error::ClientError;
+use crate::obj::ErrorCode;
+
+/// How a client retries the requests a node rejected for now, such as when it is rate limited.
+/// Errors that retrying cannot fix are never retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RetryPolicy {
+    /// The amount of times a request is sent, including the first time.
+    pub max_attempts: u32,
+    /// The delay before the first retry, doubled for each retry after it. Is only used if the
+    /// node gave no hint of when to retry.
+    pub base_delay: Duration,
+    /// The longest delay between two attempts. A node that asks to wait longer is not retried.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns how long to wait before retrying a request that failed with `err` on the attempt
+    /// `attempt`, counting from 1. Returns [`None`] if the request should not be retried.
+    pub fn delay(&self, attempt: u32, err: &ClientError) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        // the hint of the node wins over the backoff of the client
+        let delay = match err.retry_after() {
+            Some(hint) => hint,
+            None if matches!(
+                err.code(),
+                Some(ErrorCode::RATE_LIMITED | ErrorCode::UNAVAILABLE | ErrorCode::OVERLOADED)
+            ) =>
+            {
+                self.base_delay
+                    .saturating_mul(2u32.saturating_pow(attempt - 1))
+                    .min(self.max_delay)
+            }
+            None => return None,
+        };
+        (delay <= self.max_delay).then_some(delay)
+    }
+}
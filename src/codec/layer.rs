@@ -0,0 +1,260 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    KeyInit, XChaCha20Poly1305, XNonce,
+};
+
+use super::CodecError;
+use crate::crypto::ecdh::SharedSecret;
+use crate::obj::Features;
+use crate::utils;
+
+/// Marks a frame compressed with zstd, in its length prefix.
+pub(crate) const COMPRESSED: u32 = 1 << 31;
+/// Marks a frame encrypted with XChaCha20-Poly1305, in its length prefix.
+pub(crate) const ENCRYPTED: u32 = 1 << 29;
+
+const NONCE_SIZE: usize = 24;
+const TAG_SIZE: usize = 16;
+/// The size of the salt each end of an encrypted connection picks, in bytes.
+pub const SALT_SIZE: usize = 32;
+
+/// Clones boxed [`FrameLayer`]s, so that a [`MessageCodec`](super::MessageCodec) can be cloned
+/// along with its layers. Implemented for every layer that implements [`Clone`].
+pub trait CloneLayer {
+    fn clone_layer(&self) -> Box<dyn FrameLayer>;
+}
+
+impl<T: FrameLayer + Clone + 'static> CloneLayer for T {
+    fn clone_layer(&self) -> Box<dyn FrameLayer> {
+        Box::new(self.clone())
+    }
+}
+
+/// A stage of the pipeline of a [`MessageCodec`](super::MessageCodec). Outgoing frames pass
+/// through the layers of a codec in order after they are serialized, and incoming frames pass
+/// through them in reverse before they are deserialized.
+///
+/// Layers are cloned along with their codec, such as when the halves of a split connection each
+/// hold one, and clones must keep the state they share consistent.
+pub trait FrameLayer: Debug + Send + CloneLayer {
+    /// The bit of the length prefix that marks the frames this layer transformed. Every layer of
+    /// a codec must have its own bit.
+    fn flag(&self) -> u32;
+    /// Enables or disables the layer given the `features` agreed on when connecting. Layers that
+    /// do not depend on a feature ignore it.
+    fn negotiate(&mut self, features: &Features) {
+        let _ = features;
+    }
+    /// Returns whether this layer transforms the frames it encodes.
+    fn active(&self) -> bool {
+        true
+    }
+    /// Returns whether incoming frames must have been transformed by this layer. Frames that
+    /// were not are rejected, so that the other end cannot skip the layer.
+    fn required(&self) -> bool {
+        false
+    }
+    /// Returns the amount of bytes this layer can add to a frame.
+    fn overhead(&self) -> usize {
+        0
+    }
+    /// Returns whether this layer hides what frames hold. Frames are not compressed by a codec
+    /// that has such a layer, since the size of a compressed frame reveals what it holds.
+    fn conceals(&self) -> bool {
+        false
+    }
+    /// Transforms an outgoing frame. `flags` are the bits of the length prefix set by the format
+    /// of the frame and by the layers before this one. Returns [`None`] if the frame is sent as
+    /// it is.
+    fn encode(&mut self, frame: &[u8], flags: u32) -> Result<Option<Vec<u8>>, CodecError>;
+    /// Reverses [`FrameLayer::encode`] on an incoming frame marked with the flag of this layer,
+    /// given the same `flags`. Frames that would grow beyond `max` bytes are rejected.
+    fn decode(&mut self, frame: &[u8], flags: u32, max: usize) -> Result<Vec<u8>, CodecError>;
+}
+
+/// How a codec compresses the frames it encodes, once compression was negotiated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Compression {
+    /// Frames smaller than this many bytes are not compressed, since they would shrink little.
+    pub threshold: usize,
+    /// The zstd compression level.
+    pub level: i32,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            threshold: 1024,
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+/// A [`FrameLayer`] that compresses frames with zstd once both ends agreed on
+/// [`Features::COMPRESSION`]. Compressed frames are always decompressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Compressor {
+    pub compression: Compression,
+    /// Whether the other end agreed to receive compressed frames.
+    enabled: bool,
+}
+
+impl Compressor {
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            compression,
+            enabled: false,
+        }
+    }
+}
+
+impl FrameLayer for Compressor {
+    fn flag(&self) -> u32 {
+        COMPRESSED
+    }
+    fn negotiate(&mut self, features: &Features) {
+        self.enabled = features.contains(Features::COMPRESSION);
+    }
+    fn active(&self) -> bool {
+        self.enabled
+    }
+    fn encode(&mut self, frame: &[u8], _flags: u32) -> Result<Option<Vec<u8>>, CodecError> {
+        if !self.enabled || frame.len() < self.compression.threshold {
+            return Ok(None);
+        }
+
+        let compressed = zstd::bulk::compress(frame, self.compression.level)?;
+        // incompressible frames are sent as they are
+        Ok((compressed.len() < frame.len()).then_some(compressed))
+    }
+    fn decode(&mut self, frame: &[u8], _flags: u32, max: usize) -> Result<Vec<u8>, CodecError> {
+        // the decompressed frame is bound by the maximum as well
+        zstd::bulk::decompress(frame, max).map_err(CodecError::Decompress)
+    }
+}
+
+/// A [`FrameLayer`] that encrypts every frame with XChaCha20-Poly1305, under a key for each
+/// direction. Frames are numbered in each direction, so that frames that were dropped, replayed
+/// or reordered fail to decrypt, and frames that were not encrypted are rejected. The flags of
+/// the length prefix are authenticated along with the frame.
+///
+/// Clones share the numbering of the frames, so that a frame is never encrypted twice under the
+/// same nonce, even when the halves of a split connection each hold a clone.
+#[derive(Clone)]
+pub struct Encryption {
+    send: XChaCha20Poly1305,
+    recv: XChaCha20Poly1305,
+    /// The amount of frames encrypted so far.
+    sent: Arc<AtomicU64>,
+    /// The amount of frames decrypted so far.
+    received: Arc<AtomicU64>,
+}
+
+impl Debug for Encryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Encryption")
+            .field("sent", &self.sent)
+            .field("received", &self.received)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Encryption {
+    /// Encrypts outgoing frames with `send_key`, and decrypts incoming frames with `recv_key`.
+    /// The keys must not be used for another connection, since the frames of every connection
+    /// are numbered from 0.
+    pub fn new(send_key: [u8; 32], recv_key: [u8; 32]) -> Self {
+        Self {
+            send: XChaCha20Poly1305::new(&send_key.into()),
+            recv: XChaCha20Poly1305::new(&recv_key.into()),
+            sent: Default::default(),
+            received: Default::default(),
+        }
+    }
+    /// Returns a random salt, that this end sends to the other end of a connection before its
+    /// frames are encrypted. Refer to [`Encryption::from_secret`].
+    pub fn salt() -> [u8; SALT_SIZE] {
+        utils::random_bytes()
+    }
+    /// Derives the keys of both directions of a connection from `secret`, such as one computed
+    /// with [`PrivateKey::diffie_hellman`](crate::crypto::PrivateKey::diffie_hellman), and from
+    /// the salts both ends picked for the connection with [`Encryption::salt`], `ours` and
+    /// `theirs`. The keys of every connection differ even between the same two keys, as long as
+    /// either end picked a fresh salt. `initiator` is whether this end opened the connection, so
+    /// that both ends pair the keys the same way.
+    pub fn from_secret(
+        secret: &SharedSecret,
+        initiator: bool,
+        ours: &[u8; SALT_SIZE],
+        theirs: &[u8; SALT_SIZE],
+    ) -> Self {
+        let salt = match initiator {
+            true => [ours.as_slice(), theirs.as_slice()].concat(),
+            false => [theirs.as_slice(), ours.as_slice()].concat(),
+        };
+        let opener = secret.derive_key(&salt, b"cacophoney frames from opener");
+        let acceptor = secret.derive_key(&salt, b"cacophoney frames from acceptor");
+
+        match initiator {
+            true => Self::new(opener, acceptor),
+            false => Self::new(acceptor, opener),
+        }
+    }
+}
+
+/// Returns the nonce of the frame numbered `counter`.
+fn nonce(counter: u64) -> XNonce {
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce[NONCE_SIZE - 8..].copy_from_slice(&counter.to_be_bytes());
+    nonce.into()
+}
+
+/// Returns the length prefix of an encrypted frame of `size` bytes, that was marked with `flags`
+/// before it was encrypted, which is authenticated along with the frame.
+fn prefix(size: usize, flags: u32) -> [u8; 4] {
+    (size as u32 | flags | ENCRYPTED).to_be_bytes()
+}
+
+impl FrameLayer for Encryption {
+    fn flag(&self) -> u32 {
+        ENCRYPTED
+    }
+    fn required(&self) -> bool {
+        true
+    }
+    fn overhead(&self) -> usize {
+        TAG_SIZE
+    }
+    fn conceals(&self) -> bool {
+        true
+    }
+    fn encode(&mut self, frame: &[u8], flags: u32) -> Result<Option<Vec<u8>>, CodecError> {
+        let counter = self.sent.fetch_add(1, Ordering::Relaxed);
+        let payload = Payload {
+            msg: frame,
+            aad: &prefix(frame.len() + TAG_SIZE, flags),
+        };
+        let encrypted = self
+            .send
+            .encrypt(&nonce(counter), payload)
+            .map_err(|_| CodecError::Encrypt)?;
+        Ok(Some(encrypted))
+    }
+    fn decode(&mut self, frame: &[u8], flags: u32, _max: usize) -> Result<Vec<u8>, CodecError> {
+        let counter = self.received.load(Ordering::Relaxed);
+        let payload = Payload {
+            msg: frame,
+            aad: &prefix(frame.len(), flags),
+        };
+        let decrypted = self
+            .recv
+            .decrypt(&nonce(counter), payload)
+            .map_err(|_| CodecError::Decrypt)?;
+        self.received.store(counter + 1, Ordering::Relaxed);
+        Ok(decrypted)
+    }
+}
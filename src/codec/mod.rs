@@ -0,0 +1,864 @@
+//! Framing of protocol messages on a byte stream.
+//!
+//! Each message is encoded as CBOR, with keys named according to the [`NamingProfile`] of the
+//! codec, and prefixed with its length, as a 4-byte big-endian integer.
+//! Once both ends agreed on [`Features::COMPACT`], messages are encoded with postcard instead,
+//! which is much smaller and cheaper to decode on constrained devices, and which is marked by the
+//! second highest bit of the length prefix. With the `protobuf` feature, messages are encoded
+//! with protobuf once both ends agreed on [`Features::PROTOBUF`], which is marked by the fourth
+//! highest bit and takes precedence over postcard.
+//!
+//! Encoded messages then pass through the [`FrameLayer`]s of the codec, each of which marks the
+//! frames it transformed with its own bit of the length prefix. By default, a codec compresses
+//! large frames with zstd once both ends agreed on [`Features::COMPRESSION`], which is marked by
+//! the highest bit. Transports that agreed on a key can add [`Encryption`], which is marked by
+//! the third highest bit, and which turns compression off, since the size of compressed frames
+//! reveals what they hold.
+
+use std::io::Error as IoError;
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[cfg(feature = "protobuf")]
+use crate::obj::proto::{self, ProtoError};
+use crate::obj::{
+    from_postcard, to_postcard, Features, NamingProfile, PositionalTaggedReq, PositionalTaggedResp,
+    ProfileError, ReqMessage, RespMessage, Tagged,
+};
+use layer::{COMPRESSED, ENCRYPTED};
+
+mod layer;
+
+pub use layer::{CloneLayer, Compression, Compressor, Encryption, FrameLayer, SALT_SIZE};
+
+/// The size of the length prefix of a frame, in bytes.
+const LENGTH_SIZE: usize = 4;
+/// Marks a frame encoded with postcard, in its length prefix.
+const POSTCARD: u32 = 1 << 30;
+/// Marks a frame encoded with protobuf, in its length prefix.
+const PROTOBUF: u32 = 1 << 28;
+/// The bits of the length prefix that mark how a frame was encoded.
+const FLAGS: u32 = COMPRESSED | POSTCARD | ENCRYPTED | PROTOBUF;
+/// The bits of the length prefix that mark a format this codec decodes.
+#[cfg(feature = "protobuf")]
+const FORMATS: u32 = POSTCARD | PROTOBUF;
+#[cfg(not(feature = "protobuf"))]
+const FORMATS: u32 = POSTCARD;
+
+/// The largest message accepted by default, in bytes.
+pub const DEFAULT_MAX_FRAME: usize = 1 << 20;
+
+/// An error that can occur when encoding or decoding a message.
+#[derive(Error, Debug)]
+pub enum CodecError {
+    /// The message is larger than the maximum frame size of the codec.
+    #[error("frame of {size} bytes exceeds the maximum of {max} bytes")]
+    FrameTooLarge { size: usize, max: usize },
+    #[error("{}", .0)]
+    Cbor(#[from] serde_cbor::Error),
+    /// A CBOR frame does not follow the [`NamingProfile`] of the codec.
+    #[error("{}", .0)]
+    Profile(#[from] ProfileError),
+    #[error("{}", .0)]
+    Postcard(#[from] postcard::Error),
+    #[cfg(feature = "protobuf")]
+    #[error("{}", .0)]
+    Protobuf(#[from] ProtoError),
+    #[error("{}", .0)]
+    Io(#[from] IoError),
+    /// A compressed frame could not be decompressed, or decompresses to more than the maximum
+    /// frame size.
+    #[error("failed to decompress frame: {}", .0)]
+    Decompress(IoError),
+    /// A frame could not be encrypted.
+    #[error("failed to encrypt frame")]
+    Encrypt,
+    /// An encrypted frame failed to decrypt, such as when it was tampered with, replayed or
+    /// reordered.
+    #[error("failed to decrypt frame")]
+    Decrypt,
+    /// A frame was marked as transformed by a layer this codec does not have.
+    #[error("frame has flags {flags:#x} of no layer of the codec")]
+    UnknownLayer { flags: u32 },
+    /// A frame was not transformed by a layer the codec requires, such as a frame that was not
+    /// encrypted.
+    #[error("frame is missing the required layer {flag:#x}")]
+    MissingLayer { flag: u32 },
+}
+
+/// How messages are encoded in frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WireFormat {
+    /// CBOR, which every node decodes.
+    #[default]
+    Cbor,
+    /// postcard, a positional encoding that leaves the names of fields out. Is only sent to nodes
+    /// that agreed on [`Features::COMPACT`].
+    Postcard,
+    /// protobuf, as described by `proto/cacophoney.proto`. Is only sent to nodes that agreed on
+    /// [`Features::PROTOBUF`].
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+}
+
+impl WireFormat {
+    /// Returns the most compact format both ends agreed on in `features`.
+    pub fn negotiate(features: &Features) -> Self {
+        #[cfg(feature = "protobuf")]
+        if features.contains(Features::PROTOBUF) {
+            return Self::Protobuf;
+        }
+        match features.contains(Features::COMPACT) {
+            true => Self::Postcard,
+            false => Self::Cbor,
+        }
+    }
+}
+
+/// A message a [`MessageCodec`] encodes or decodes. Every message can be encoded with serde, by
+/// name or positionally; with the `protobuf` feature, it can also be converted to and from its
+/// protobuf form.
+pub trait WireMessage: Sized {
+    /// Encodes this message with postcard, in its positional shape.
+    fn to_postcard(&self) -> Result<Vec<u8>, postcard::Error>;
+    /// Decodes a message encoded with postcard.
+    fn from_postcard(bytes: &[u8]) -> Result<Self, postcard::Error>;
+    /// Encodes this message with protobuf.
+    #[cfg(feature = "protobuf")]
+    fn to_protobuf(self) -> Vec<u8>;
+    /// Decodes a message encoded with protobuf.
+    #[cfg(feature = "protobuf")]
+    fn from_protobuf(bytes: &[u8]) -> Result<Self, ProtoError>;
+}
+
+impl WireMessage for Tagged<ReqMessage> {
+    fn to_postcard(&self) -> Result<Vec<u8>, postcard::Error> {
+        to_postcard::<_, PositionalTaggedReq>(self)
+    }
+    fn from_postcard(bytes: &[u8]) -> Result<Self, postcard::Error> {
+        from_postcard::<_, PositionalTaggedReq>(bytes)
+    }
+    #[cfg(feature = "protobuf")]
+    fn to_protobuf(self) -> Vec<u8> {
+        prost::Message::encode_to_vec(&proto::TaggedReq::from(self))
+    }
+    #[cfg(feature = "protobuf")]
+    fn from_protobuf(bytes: &[u8]) -> Result<Self, ProtoError> {
+        <proto::TaggedReq as prost::Message>::decode(bytes)?.try_into()
+    }
+}
+
+impl WireMessage for Tagged<RespMessage> {
+    fn to_postcard(&self) -> Result<Vec<u8>, postcard::Error> {
+        to_postcard::<_, PositionalTaggedResp>(self)
+    }
+    fn from_postcard(bytes: &[u8]) -> Result<Self, postcard::Error> {
+        from_postcard::<_, PositionalTaggedResp>(bytes)
+    }
+    #[cfg(feature = "protobuf")]
+    fn to_protobuf(self) -> Vec<u8> {
+        prost::Message::encode_to_vec(&proto::TaggedResp::from(self))
+    }
+    #[cfg(feature = "protobuf")]
+    fn from_protobuf(bytes: &[u8]) -> Result<Self, ProtoError> {
+        <proto::TaggedResp as prost::Message>::decode(bytes)?.try_into()
+    }
+}
+
+/// Encodes messages of the type `Enc` and decodes messages of the type `Dec` as length-prefixed
+/// frames.
+pub struct MessageCodec<Enc, Dec> {
+    max_frame: usize,
+    /// The format of the frames this codec encodes.
+    format: WireFormat,
+    /// How the keys of CBOR frames are named.
+    profile: NamingProfile,
+    /// Compresses frames first, unless a layer conceals them.
+    compressor: Option<Compressor>,
+    /// The layers frames pass through after they are compressed, in order.
+    layers: Vec<Box<dyn FrameLayer>>,
+    _marker: PhantomData<fn(Enc) -> Dec>,
+}
+
+/// The codec of a client, which sends requests and receives responses, tagged with the ids that
+/// match them.
+pub type ClientCodec = MessageCodec<Tagged<ReqMessage>, Tagged<RespMessage>>;
+/// The codec of a node, which receives requests and sends responses, tagged with the ids that
+/// match them.
+pub type ServerCodec = MessageCodec<Tagged<RespMessage>, Tagged<ReqMessage>>;
+
+impl<Enc, Dec> std::fmt::Debug for MessageCodec<Enc, Dec> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageCodec")
+            .field("max_frame", &self.max_frame)
+            .field("format", &self.format)
+            .field("profile", &self.profile)
+            .field("compressor", &self.compressor)
+            .field("layers", &self.layers)
+            .finish()
+    }
+}
+
+impl<Enc, Dec> Clone for MessageCodec<Enc, Dec> {
+    fn clone(&self) -> Self {
+        Self {
+            max_frame: self.max_frame,
+            format: self.format,
+            profile: self.profile,
+            compressor: self.compressor,
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| layer.clone_layer())
+                .collect(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Enc, Dec> Default for MessageCodec<Enc, Dec> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds a [`MessageCodec`] out of its layers.
+pub struct CodecBuilder<Enc, Dec> {
+    max_frame: usize,
+    profile: NamingProfile,
+    compression: Option<Compression>,
+    layers: Vec<Box<dyn FrameLayer>>,
+    _marker: PhantomData<fn(Enc) -> Dec>,
+}
+
+impl<Enc, Dec> std::fmt::Debug for CodecBuilder<Enc, Dec> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CodecBuilder")
+            .field("max_frame", &self.max_frame)
+            .field("profile", &self.profile)
+            .field("compression", &self.compression)
+            .field("layers", &self.layers)
+            .finish()
+    }
+}
+
+impl<Enc, Dec> Default for CodecBuilder<Enc, Dec> {
+    fn default() -> Self {
+        Self {
+            max_frame: DEFAULT_MAX_FRAME,
+            profile: NamingProfile::default(),
+            compression: Some(Compression::default()),
+            layers: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Enc, Dec> CodecBuilder<Enc, Dec> {
+    /// Rejects messages larger than `max_frame` bytes, not counting the length prefix.
+    pub fn max_frame(mut self, max_frame: usize) -> Self {
+        self.max_frame = max_frame;
+        self
+    }
+    /// Names the keys of CBOR frames according to `profile`, such as the profile
+    /// [`NamingProfile::for_version`] returns for the version of the protocol both ends speak.
+    pub fn profile(mut self, profile: NamingProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+    /// Sets how frames are compressed once compression was negotiated, or leaves compression out
+    /// of the codec if `compression` is [`None`]. The codec compresses with the default settings
+    /// otherwise.
+    pub fn compression(mut self, compression: Option<Compression>) -> Self {
+        self.compression = compression;
+        self
+    }
+    /// Encrypts every frame. A codec that encrypts does not compress, since the size of
+    /// compressed frames reveals what they hold.
+    pub fn encryption(self, encryption: Encryption) -> Self {
+        self.layer(encryption)
+    }
+    /// Adds `layer` after the layers added before it. Compression always comes first, since
+    /// transformed frames rarely compress.
+    pub fn layer(mut self, layer: impl FrameLayer + 'static) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+    pub fn build(self) -> MessageCodec<Enc, Dec> {
+        MessageCodec {
+            max_frame: self.max_frame,
+            format: WireFormat::Cbor,
+            profile: self.profile,
+            compressor: self.compression.map(Compressor::new),
+            layers: self.layers,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Enc, Dec> MessageCodec<Enc, Dec> {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+    pub fn builder() -> CodecBuilder<Enc, Dec> {
+        CodecBuilder::default()
+    }
+    /// Creates a codec that rejects messages larger than `max_frame` bytes, not counting the
+    /// length prefix.
+    pub fn with_max_frame(max_frame: usize) -> Self {
+        Self::builder().max_frame(max_frame).build()
+    }
+    /// Returns the size of the largest message this codec accepts.
+    pub fn max_frame(&self) -> usize {
+        self.max_frame
+    }
+    /// Sets how frames are compressed once compression was negotiated. Compression is added to
+    /// a codec built without it, and starts once it is negotiated again.
+    pub fn set_compression(&mut self, compression: Compression) {
+        match &mut self.compressor {
+            Some(compressor) => compressor.compression = compression,
+            None => self.compressor = Some(Compressor::new(compression)),
+        }
+    }
+    /// Adds `layer` after the layers of this codec, such as [`Encryption`] once both ends agreed
+    /// on a key. Frames encoded or decoded before are not affected.
+    pub fn push_layer(&mut self, layer: impl FrameLayer + 'static) {
+        self.layers.push(Box::new(layer));
+    }
+    /// Encodes frames in the format returned by [`WireFormat::negotiate`], and enables the
+    /// layers that depend on the `features` agreed on when connecting, such as compression.
+    /// Frames of every format this codec supports are always decoded.
+    pub fn negotiate(&mut self, features: &Features) {
+        self.format = WireFormat::negotiate(features);
+        if let Some(compressor) = &mut self.compressor {
+            compressor.negotiate(features);
+        }
+        for layer in &mut self.layers {
+            layer.negotiate(features);
+        }
+    }
+    /// Returns whether this codec compresses the frames it encodes.
+    pub fn compresses(&self) -> bool {
+        self.compressor
+            .is_some_and(|compressor| compressor.active())
+            && !self.layers.iter().any(|layer| layer.conceals())
+    }
+    /// Returns the format of the frames this codec encodes.
+    pub fn format(&self) -> WireFormat {
+        self.format
+    }
+    /// Returns how the keys of CBOR frames are named.
+    pub fn profile(&self) -> NamingProfile {
+        self.profile
+    }
+    fn check(&self, size: usize, max: usize) -> Result<(), CodecError> {
+        if size > max || size >= PROTOBUF as usize {
+            return Err(CodecError::FrameTooLarge {
+                size,
+                max: self.max_frame,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<Enc: Serialize + WireMessage, Dec> Encoder<Enc> for MessageCodec<Enc, Dec> {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Enc, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (mut bytes, mut flags) = match self.format {
+            WireFormat::Cbor => (self.profile.to_cbor(&item)?, 0),
+            WireFormat::Postcard => (item.to_postcard()?, POSTCARD),
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => (item.to_protobuf(), PROTOBUF),
+        };
+        self.check(bytes.len(), self.max_frame)?;
+
+        if self.compresses() {
+            if let Some(compressor) = &mut self.compressor {
+                if let Some(compressed) = compressor.encode(&bytes, flags)? {
+                    bytes = compressed;
+                    flags |= COMPRESSED;
+                }
+            }
+        }
+        for layer in &mut self.layers {
+            if let Some(transformed) = layer.encode(&bytes, flags)? {
+                bytes = transformed;
+                flags |= layer.flag();
+            }
+        }
+
+        dst.reserve(LENGTH_SIZE + bytes.len());
+        dst.put_u32(bytes.len() as u32 | flags);
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+impl<Enc, Dec: DeserializeOwned + WireMessage> Decoder for MessageCodec<Enc, Dec> {
+    type Item = Dec;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < LENGTH_SIZE {
+            return Ok(None);
+        }
+
+        let prefix = (&src[..LENGTH_SIZE]).get_u32();
+        let size = (prefix & !FLAGS) as usize;
+        // reject the frame before buffering it
+        let overhead: usize = self.layers.iter().map(|layer| layer.overhead()).sum();
+        self.check(size, self.max_frame + overhead)?;
+
+        let compressed = self.compressor.map_or(0, |compressor| compressor.flag());
+        let unknown = prefix
+            & FLAGS
+            & !FORMATS
+            & !compressed
+            & !self
+                .layers
+                .iter()
+                .fold(0, |flags, layer| flags | layer.flag());
+        if unknown != 0 {
+            return Err(CodecError::UnknownLayer { flags: unknown });
+        }
+
+        if src.len() < LENGTH_SIZE + size {
+            src.reserve(LENGTH_SIZE + size - src.len());
+            return Ok(None);
+        }
+
+        src.advance(LENGTH_SIZE);
+        let frame = src.split_to(size);
+        // the flags of the prefix each layer saw when the frame was encoded
+        let mut flags = prefix & (FORMATS | COMPRESSED);
+        let mut seen = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            seen.push(flags);
+            flags |= prefix & layer.flag();
+        }
+
+        let mut decoded: Option<Vec<u8>> = None;
+        for (layer, flags) in self.layers.iter_mut().zip(seen).rev() {
+            let flag = layer.flag();
+            if prefix & flag != 0 {
+                let input = decoded.as_deref().unwrap_or(&frame);
+                decoded = Some(layer.decode(input, flags, self.max_frame)?);
+            } else if layer.required() {
+                return Err(CodecError::MissingLayer { flag });
+            }
+        }
+        if let Some(compressor) = self
+            .compressor
+            .as_mut()
+            .filter(|_| prefix & COMPRESSED != 0)
+        {
+            let input = decoded.as_deref().unwrap_or(&frame);
+            decoded = Some(compressor.decode(input, prefix & FORMATS, self.max_frame)?);
+        }
+        let frame = decoded.as_deref().unwrap_or(&frame);
+
+        #[cfg(feature = "protobuf")]
+        if prefix & PROTOBUF != 0 {
+            return Ok(Some(Dec::from_protobuf(frame)?));
+        }
+        Ok(Some(match prefix & POSTCARD {
+            0 => self.profile.from_cbor(frame)?,
+            _ => Dec::from_postcard(frame)?,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    use super::*;
+    use crate::crypto::keyset::{KeyRange, RangeItem, Reconciliation};
+    use crate::crypto::{HashMsg, KeyPair, KeyTriad};
+    use crate::mock::stream_pair;
+    use crate::obj::{
+        ConnectedServer, CrossSignResp, ErrorCode, ErrorResp, IdentifyData, IdentifyExtensions,
+        IdentifyReq, JournalEntry, JournalPosition, JournalRecord, JournalResp, KeyUsage,
+        KeysExistsResp, ListConnectedServersReq, ListConnectedServersResp, NodeInfo,
+        PreIdentifyReq, PresenceStatus, ReconcileReq, RevokeReq, ServerInfo, SALT_SIZE,
+    };
+
+    #[tokio::test]
+    async fn frames() {
+        let (read, write) = stream_pair(4);
+        let mut write = FramedWrite::new(write, ClientCodec::new());
+        let mut read = FramedRead::new(read, ServerCodec::new());
+
+        let pair = KeyPair::generate();
+        let messages = [
+            Tagged::new(0, ReqMessage::PreIdentify(PreIdentifyReq {})),
+            Tagged::new(
+                1,
+                ReqMessage::Revoke(RevokeReq {
+                    revocation: KeyTriad::revoke(&pair.private, 0),
+                }),
+            ),
+        ];
+        for message in messages.iter().cloned() {
+            write.send(message).await.unwrap();
+        }
+        for message in messages {
+            assert_eq!(read.next().await.unwrap().unwrap(), message);
+        }
+
+        // frames larger than the maximum are rejected on both ends
+        let mut codec = ClientCodec::with_max_frame(8);
+        let mut buf = BytesMut::new();
+        let revoke = Tagged::new(
+            0,
+            ReqMessage::Revoke(RevokeReq {
+                revocation: KeyTriad::revoke(&pair.private, 0),
+            }),
+        );
+        assert!(matches!(
+            codec.encode(revoke, &mut buf),
+            Err(CodecError::FrameTooLarge { max: 8, .. })
+        ));
+
+        let mut codec = ServerCodec::with_max_frame(8);
+        let mut buf = BytesMut::from(&[0, 0, 0, 9][..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(CodecError::FrameTooLarge { size: 9, max: 8 })
+        ));
+    }
+
+    #[test]
+    fn compression() {
+        let large = Tagged::new(
+            0,
+            RespMessage::Error(ErrorResp::new(ErrorCode::UNKNOWN, "a".repeat(4096))),
+        );
+        let small = Tagged::new(
+            1,
+            RespMessage::Error(ErrorResp::new(ErrorCode::UNKNOWN, "a")),
+        );
+        let mut encoder = ServerCodec::new();
+        let mut decoder = ClientCodec::new();
+
+        // frames are not compressed until compression is negotiated
+        let mut buf = BytesMut::new();
+        encoder.encode(large.clone(), &mut buf).unwrap();
+        assert_eq!(buf[0] & 0x80, 0);
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(large.clone()));
+
+        encoder.negotiate(&Features::from_iter([Features::COMPRESSION]));
+        assert!(encoder.compresses());
+        encoder.encode(large.clone(), &mut buf).unwrap();
+        assert_ne!(buf[0] & 0x80, 0);
+        assert!(buf.len() < 4096);
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(large.clone()));
+
+        // frames below the threshold are not compressed
+        encoder.encode(small.clone(), &mut buf).unwrap();
+        assert_eq!(buf[0] & 0x80, 0);
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(small));
+
+        // the threshold can be raised on a codec in use
+        let mut raised = encoder.clone();
+        raised.set_compression(Compression {
+            threshold: 1 << 16,
+            ..Default::default()
+        });
+        raised.encode(large.clone(), &mut buf).unwrap();
+        assert_eq!(buf[0] & 0x80, 0);
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(large.clone()));
+
+        // a frame that decompresses to more than the maximum is rejected
+        encoder.encode(large, &mut buf).unwrap();
+        let mut decoder = ClientCodec::with_max_frame(1024);
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Err(CodecError::Decompress(_))
+        ));
+    }
+
+    #[test]
+    fn postcard() {
+        let pair = KeyPair::generate();
+        let triad = KeyTriad::revoke(&pair.private, 0);
+        let requests = [
+            ReqMessage::Connect(NodeInfo {
+                api_version: crate::CURRENT_VERSION,
+                features: Features::from_iter([Features::COMPACT]),
+                load: None,
+            }),
+            ReqMessage::Identify(IdentifyReq {
+                keys: vec![triad.clone()],
+                compact: Vec::new(),
+                multi: Vec::new(),
+                delegated: Vec::new(),
+            }),
+            ReqMessage::Revoke(RevokeReq {
+                revocation: triad.clone(),
+            }),
+            ReqMessage::Reconcile(ReconcileReq {
+                round: Reconciliation {
+                    items: vec![
+                        RangeItem::Fingerprint {
+                            range: KeyRange {
+                                lower: Some(pair.public),
+                                upper: None,
+                            },
+                            count: 2,
+                            fingerprint: HashMsg([1; 32]),
+                        },
+                        RangeItem::Keys {
+                            range: KeyRange::default(),
+                            keys: vec![pair.public],
+                        },
+                    ],
+                    keys: Vec::new(),
+                },
+            }),
+        ];
+        let responses = [
+            RespMessage::Error(ErrorResp::new(ErrorCode::UNKNOWN, "a")),
+            RespMessage::KeysExists(KeysExistsResp {
+                triads: vec![triad],
+                presence: vec![KeyTriad::presence(
+                    &pair.private,
+                    PresenceStatus::Away,
+                    None,
+                    1,
+                )],
+            }),
+            RespMessage::PreIdentify(IdentifyData {
+                salt: [2; SALT_SIZE],
+                start_time: 0,
+                expire_time: 1,
+                extensions: IdentifyExtensions::default(),
+                difficulty: Some(3),
+                work: None,
+                usage: KeyUsage::default(),
+            }),
+            RespMessage::CrossSign(CrossSignResp::ProofRequired { from: 3 }),
+            RespMessage::Journal(JournalResp {
+                position: JournalPosition { epoch: 1, seq: 2 },
+                records: vec![JournalRecord {
+                    seq: 2,
+                    entry: JournalEntry::Fetched { to: pair.public },
+                }],
+                snapshot: false,
+            }),
+            RespMessage::ListConnectedServers(ListConnectedServersResp {
+                servers: vec![ConnectedServer {
+                    addr: None,
+                    server_info: ServerInfo::new(arcstr::literal!("a.example")),
+                    load: None,
+                    verified: true,
+                }],
+            }),
+        ];
+
+        let mut encoder = ClientCodec::new();
+        encoder.negotiate(&Features::from_iter([Features::COMPACT]));
+        assert_eq!(encoder.format(), WireFormat::Postcard);
+        // frames are decoded whatever the decoder negotiated
+        let mut decoder = ServerCodec::new();
+        let mut buf = BytesMut::new();
+        for (id, req) in requests.into_iter().enumerate() {
+            let req = Tagged::new(id as u64, req);
+            encoder.encode(req.clone(), &mut buf).unwrap();
+            assert_ne!(buf[0] & 0x40, 0);
+            assert!(buf.len() < serde_cbor::to_vec(&req).unwrap().len());
+            assert_eq!(decoder.decode(&mut buf).unwrap(), Some(req));
+        }
+
+        let mut encoder = ServerCodec::new();
+        encoder.negotiate(&Features::from_iter([
+            Features::COMPACT,
+            Features::COMPRESSION,
+        ]));
+        let mut decoder = ClientCodec::new();
+        for (id, resp) in responses.into_iter().enumerate() {
+            let resp = Tagged::new(id as u64, resp);
+            encoder.encode(resp.clone(), &mut buf).unwrap();
+            assert_eq!(decoder.decode(&mut buf).unwrap(), Some(resp));
+        }
+
+        // the named shapes are kept outside of postcard frames
+        encoder.negotiate(&Features::new());
+        let resp = Tagged::new(
+            0,
+            RespMessage::Error(ErrorResp::new(ErrorCode::UNKNOWN, "a")),
+        );
+        encoder.encode(resp.clone(), &mut buf).unwrap();
+        assert_eq!(buf[0] & 0x40, 0);
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(resp));
+    }
+
+    #[test]
+    fn profiles() {
+        let pair = KeyPair::generate();
+        let requests = [
+            ReqMessage::Connect(NodeInfo::default()),
+            ReqMessage::from(ListConnectedServersReq { max: Some(2) }),
+            ReqMessage::Revoke(RevokeReq {
+                revocation: KeyTriad::revoke(&pair.private, 0),
+            }),
+        ];
+        let mut strict = ClientCodec::builder()
+            .profile(NamingProfile::StrictCamel)
+            .build();
+        let mut canonical = ServerCodec::new();
+        let mut buf = BytesMut::new();
+        for (id, req) in requests.into_iter().enumerate() {
+            let req = Tagged::new(id as u64, req);
+            strict.encode(req.clone(), &mut buf).unwrap();
+            // the canonical types accept both spellings
+            assert_eq!(canonical.decode(&mut buf).unwrap(), Some(req));
+        }
+
+        let mut strict = ServerCodec::builder()
+            .profile(NamingProfile::StrictCamel)
+            .build();
+        let mut canonical = ClientCodec::new();
+        let req = Tagged::new(0, ReqMessage::from(ListConnectedServersReq { max: None }));
+        canonical.encode(req, &mut buf).unwrap();
+        assert!(matches!(
+            strict.decode(&mut buf),
+            Err(CodecError::Profile(ProfileError::InvalidKey(_)))
+        ));
+    }
+
+    #[test]
+    fn protobuf() {
+        let pair = KeyPair::generate();
+        let req = Tagged::new(
+            0,
+            ReqMessage::Revoke(RevokeReq {
+                revocation: KeyTriad::revoke(&pair.private, 0),
+            }),
+        );
+        let features = Features::from_iter([Features::COMPACT, Features::PROTOBUF]);
+        let mut encoder = ClientCodec::new();
+        encoder.negotiate(&features);
+        let mut decoder = ServerCodec::new();
+        let mut buf = BytesMut::new();
+
+        #[cfg(feature = "protobuf")]
+        {
+            // protobuf is preferred over postcard
+            assert_eq!(encoder.format(), WireFormat::Protobuf);
+            encoder.encode(req.clone(), &mut buf).unwrap();
+            assert_ne!(buf[0] & 0x10, 0);
+            assert_eq!(buf[0] & 0x40, 0);
+            assert_eq!(decoder.decode(&mut buf).unwrap(), Some(req));
+        }
+        #[cfg(not(feature = "protobuf"))]
+        {
+            assert_eq!(encoder.format(), WireFormat::Postcard);
+            encoder.encode(req, &mut buf).unwrap();
+            // protobuf frames are rejected without the feature
+            buf[0] |= 0x10;
+            assert!(matches!(
+                decoder.decode(&mut buf),
+                Err(CodecError::UnknownLayer { flags: PROTOBUF })
+            ));
+        }
+    }
+
+    #[test]
+    fn layers() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let secret = a.private.diffie_hellman(&b.public).unwrap();
+        let large = Tagged::new(
+            0,
+            RespMessage::Error(ErrorResp::new(ErrorCode::UNKNOWN, "a".repeat(4096))),
+        );
+        let (opener, acceptor) = (Encryption::salt(), Encryption::salt());
+        let mut encoder = ServerCodec::builder()
+            .encryption(Encryption::from_secret(&secret, true, &opener, &acceptor))
+            .build();
+        let mut decoder = ClientCodec::builder()
+            .encryption(Encryption::from_secret(&secret, false, &acceptor, &opener))
+            .build();
+        encoder.negotiate(&Features::from_iter([Features::COMPRESSION]));
+
+        // encrypted frames are not compressed, since their size would reveal what they hold
+        assert!(!encoder.compresses());
+        let mut buf = BytesMut::new();
+        encoder.encode(large.clone(), &mut buf).unwrap();
+        assert_eq!(buf[0] & 0xa0, 0x20);
+        assert!(buf.len() > 4096);
+        let frame = buf.clone();
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(large.clone()));
+
+        // the keys of every connection differ, even between the same keys
+        let mut other = ServerCodec::builder()
+            .encryption(Encryption::from_secret(
+                &secret,
+                true,
+                &Encryption::salt(),
+                &acceptor,
+            ))
+            .build();
+        let mut buf = BytesMut::new();
+        other.encode(large.clone(), &mut buf).unwrap();
+        assert_ne!(buf, frame);
+
+        // replayed and tampered frames fail to decrypt
+        assert!(matches!(
+            decoder.decode(&mut frame.clone()),
+            Err(CodecError::Decrypt)
+        ));
+        let mut buf = BytesMut::new();
+        encoder.encode(large.clone(), &mut buf).unwrap();
+        let last = buf.len() - 1;
+        buf[last] ^= 1;
+        assert!(matches!(decoder.decode(&mut buf), Err(CodecError::Decrypt)));
+        // so do frames whose flags were changed
+        let mut buf = BytesMut::new();
+        encoder.encode(large.clone(), &mut buf).unwrap();
+        buf[0] |= 0x40;
+        assert!(matches!(decoder.decode(&mut buf), Err(CodecError::Decrypt)));
+
+        // frames must be encrypted once encryption was added, and only by a codec that has it
+        let mut buf = BytesMut::new();
+        ServerCodec::new().encode(large.clone(), &mut buf).unwrap();
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Err(CodecError::MissingLayer { flag: 0x2000_0000 })
+        ));
+        let mut buf = frame;
+        assert!(matches!(
+            ClientCodec::new().decode(&mut buf),
+            Err(CodecError::UnknownLayer { flags: 0x2000_0000 })
+        ));
+
+        // encryption can be added to a codec that is in use
+        let mut encoder = ServerCodec::new();
+        let mut decoder = ClientCodec::new();
+        encoder.negotiate(&Features::from_iter([Features::COMPRESSION]));
+        assert!(encoder.compresses());
+        encoder.push_layer(Encryption::from_secret(&secret, true, &opener, &acceptor));
+        decoder.push_layer(Encryption::from_secret(&secret, false, &acceptor, &opener));
+        assert!(!encoder.compresses());
+        let mut buf = BytesMut::new();
+        encoder.encode(large.clone(), &mut buf).unwrap();
+        assert!(buf.len() > 4096);
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(large.clone()));
+
+        // clones of a codec number their frames together, so that no nonce is used twice
+        let mut clone = encoder.clone();
+        let mut buf = BytesMut::new();
+        clone.encode(large.clone(), &mut buf).unwrap();
+        encoder.encode(large.clone(), &mut buf).unwrap();
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(large.clone()));
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(large));
+    }
+}
@@ -0,0 +1,404 @@
+//! A conformance suite that drives a node through scripted scenarios over the wire, so that other
+//! implementations of the protocol can be checked against this crate.
+//!
+//! A node that rejects a request may answer with any other message, or not at all. Requests that
+//! receive no response within the timeout of the suite count as rejected.
+
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::time::Duration;
+
+use futures::{Future, Sink};
+
+use crate::client::error::DispatchError;
+use crate::client::Dispatcher;
+use crate::crypto::delegation::DelegationScope;
+use crate::crypto::{KeyPair, KeyTriad, PrivateKey, PublicKey};
+use crate::obj::{
+    DelegatedTriad, IdentifyData, IdentifyReq, KeysExistsReq, NodeInfo, PreIdentifyReq, ReqMessage,
+    RespMessage, RevokeReq, SignMessageType, SignedData, SignedFormat, Tagged,
+};
+use crate::{utils, CURRENT_VERSION};
+
+/// The time a node is given to answer each request by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A connection to the node under test.
+pub trait Connection {
+    type Err: StdError;
+
+    /// Sends `req` and waits for its response.
+    fn request(&self, req: ReqMessage) -> impl Future<Output = Result<RespMessage, Self::Err>>;
+}
+
+impl<Si: Sink<Tagged<ReqMessage>, Error: StdError> + Unpin> Connection for Dispatcher<Si> {
+    type Err = DispatchError<Si::Error>;
+
+    fn request(&self, req: ReqMessage) -> impl Future<Output = Result<RespMessage, Self::Err>> {
+        Dispatcher::request(self, req)
+    }
+}
+
+/// A scenario of the suite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Case {
+    /// The node accepts the current version of the protocol and rejects an unknown one.
+    VersionNegotiation,
+    /// The node identifies a key that signed the data it handed out.
+    Identify,
+    /// The node rejects a triad whose signature does not match.
+    BadSignature,
+    /// The node rejects a key delegated by an expired delegation.
+    ExpiredDelegation,
+    /// A key waited for with a [`KeysExistsReq`] is reported once it identifies.
+    Notifications,
+    /// The node accepts a [`RevokeReq`] of a key signed by the key itself, and no longer reports
+    /// the key.
+    Revocation,
+}
+
+impl Case {
+    pub const ALL: [Case; 6] = [
+        Case::VersionNegotiation,
+        Case::Identify,
+        Case::BadSignature,
+        Case::ExpiredDelegation,
+        Case::Notifications,
+        Case::Revocation,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::VersionNegotiation => "version negotiation",
+            Self::Identify => "identify",
+            Self::BadSignature => "bad signature",
+            Self::ExpiredDelegation => "expired delegation",
+            Self::Notifications => "notifications",
+            Self::Revocation => "revocation",
+        }
+    }
+}
+
+/// The outcome of every case of a run of the suite.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Report {
+    /// The cases in the order they ran, and the reasons of the ones that failed.
+    pub results: Vec<(Case, Result<(), String>)>,
+}
+
+impl Report {
+    /// Returns whether every case passed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+    /// Returns the cases that failed, along with the reasons.
+    pub fn failures(&self) -> impl Iterator<Item = (Case, &str)> {
+        self.results
+            .iter()
+            .filter_map(|(case, result)| result.as_ref().err().map(|err| (*case, err.as_str())))
+    }
+}
+
+/// Runs the cases of the conformance suite against a node. `connect` opens a new connection to
+/// the node, and is called at least once for each case.
+#[derive(Debug, Clone)]
+pub struct Suite<F> {
+    connect: F,
+    timeout: Duration,
+}
+
+impl<F, Fut, Co, E> Suite<F>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<Co, E>>,
+    Co: Connection,
+    E: Display,
+{
+    pub fn new(connect: F) -> Self {
+        Self {
+            connect,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+    /// Sets the time the node is given to answer each request.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+    /// Runs every case, one after the other.
+    pub async fn run(&self) -> Report {
+        let mut results = Vec::with_capacity(Case::ALL.len());
+        for case in Case::ALL {
+            results.push((case, self.run_case(case).await));
+        }
+
+        Report { results }
+    }
+    /// Runs one case.
+    pub async fn run_case(&self, case: Case) -> Result<(), String> {
+        match case {
+            Case::VersionNegotiation => self.version_negotiation().await,
+            Case::Identify => {
+                let conn = self.connect().await?;
+                self.identify(&conn, &KeyPair::generate().private).await
+            }
+            Case::BadSignature => self.bad_signature().await,
+            Case::ExpiredDelegation => self.expired_delegation().await,
+            Case::Notifications => self.notifications().await,
+            Case::Revocation => self.revocation().await,
+        }
+    }
+
+    async fn connect(&self) -> Result<Co, String> {
+        (self.connect)()
+            .await
+            .map_err(|err| format!("failed to connect: {err}"))
+    }
+    /// Sends `req`, returning [`None`] if the node rejected it or did not answer in time.
+    async fn request(&self, conn: &Co, req: impl Into<ReqMessage>) -> Option<RespMessage> {
+        match tokio::time::timeout(self.timeout, conn.request(req.into())).await {
+            Ok(Ok(resp)) => Some(resp),
+            _ => None,
+        }
+    }
+    async fn identify_data(&self, conn: &Co) -> Result<IdentifyData, String> {
+        match self.request(conn, PreIdentifyReq {}).await {
+            Some(RespMessage::PreIdentify(data)) => Ok(data),
+            _ => Err("no identify data was handed out".to_string()),
+        }
+    }
+    /// Sends `req`, returning whether the node identified the keys of it.
+    async fn send_identify(&self, conn: &Co, req: IdentifyReq) -> bool {
+        matches!(
+            self.request(conn, req).await,
+            Some(RespMessage::Identify(_))
+        )
+    }
+    async fn identify(&self, conn: &Co, key: &PrivateKey) -> Result<(), String> {
+        let data = self.identify_data(conn).await?;
+        let triad = KeyTriad::gen_signed(key, &data, SignMessageType::Identify, SignedFormat::Cbor)
+            .map_err(|err| err.to_string())?;
+
+        match self
+            .send_identify(conn, identify_req(vec![triad], Vec::new()))
+            .await
+        {
+            true => Ok(()),
+            false => Err("a valid triad was rejected".to_string()),
+        }
+    }
+
+    async fn key_exists(&self, conn: &Co, key: PublicKey, notify: bool) -> Result<bool, String> {
+        let req = KeysExistsReq {
+            keys: vec![key],
+            notify,
+        };
+
+        match self.request(conn, req).await {
+            Some(RespMessage::KeysExists(resp)) => {
+                Ok(resp.triads.iter().any(|triad| triad.public_key == key))
+            }
+            _ => Err("keys exists request was not answered".to_string()),
+        }
+    }
+
+    async fn version_negotiation(&self) -> Result<(), String> {
+        for (api_version, expected) in [(CURRENT_VERSION, true), (u32::MAX, false)] {
+            let conn = self.connect().await?;
+            let info = NodeInfo {
+                api_version,
+                features: Default::default(),
+                load: None,
+            };
+
+            match self.request(&conn, info).await {
+                Some(RespMessage::Connect(resp)) if resp.compatible == expected => {}
+                Some(RespMessage::Connect(_)) => {
+                    return Err(format!(
+                        "version {api_version} was reported as {}compatible",
+                        if expected { "in" } else { "" }
+                    ))
+                }
+                _ => return Err(format!("version {api_version} was not answered")),
+            }
+        }
+
+        Ok(())
+    }
+    async fn bad_signature(&self) -> Result<(), String> {
+        let conn = self.connect().await?;
+        let data = self.identify_data(&conn).await?;
+        let mut triad = KeyTriad::gen_signed(
+            &KeyPair::generate().private,
+            &data,
+            SignMessageType::Identify,
+            SignedFormat::Cbor,
+        )
+        .map_err(|err| err.to_string())?;
+        triad.signature.0[0] ^= 1;
+
+        if self
+            .send_identify(&conn, identify_req(vec![triad], Vec::new()))
+            .await
+        {
+            return Err("a triad with an invalid signature was accepted".to_string());
+        }
+        Ok(())
+    }
+    async fn expired_delegation(&self) -> Result<(), String> {
+        let device = KeyPair::generate();
+
+        let valid = utils::now() + 60 * 60 * 1000;
+        for (expire_time, expected) in [(valid, true), (0, false)] {
+            let conn = self.connect().await?;
+            let data = self.identify_data(&conn).await?;
+            let root = KeyPair::generate();
+            let delegated = DelegatedTriad {
+                triad: KeyTriad::gen_signed(
+                    &device.private,
+                    &data,
+                    SignMessageType::Identify,
+                    SignedFormat::Cbor,
+                )
+                .map_err(|err| err.to_string())?,
+                chain: vec![KeyTriad::delegate(
+                    &root.private,
+                    device.public,
+                    DelegationScope::IDENTIFY,
+                    expire_time,
+                )
+                .map_err(|err| err.to_string())?],
+            };
+
+            let accepted = self
+                .send_identify(&conn, identify_req(Vec::new(), vec![delegated]))
+                .await;
+            match (accepted, expected) {
+                (true, false) => return Err("an expired delegation was accepted".to_string()),
+                (false, true) => return Err("a valid delegation was rejected".to_string()),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+    async fn notifications(&self) -> Result<(), String> {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let a_conn = self.connect().await?;
+        self.identify(&a_conn, &a.private).await?;
+
+        if self.key_exists(&a_conn, b.public, true).await? {
+            return Err("a key was reported before it identified".to_string());
+        }
+        let b_conn = self.connect().await?;
+        self.identify(&b_conn, &b.private).await?;
+        if !self.key_exists(&a_conn, b.public, false).await? {
+            return Err("a key was not reported after it identified".to_string());
+        }
+
+        Ok(())
+    }
+    async fn revocation(&self) -> Result<(), String> {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let a_conn = self.connect().await?;
+        self.identify(&a_conn, &a.private).await?;
+        let b_conn = self.connect().await?;
+
+        // a key can only be revoked by itself
+        let mut forged = KeyTriad::revoke(&a.private, utils::now());
+        forged.public_key = b.public;
+        forged.signature = b.private.sign(&forged.signed);
+        if let Some(RespMessage::Ack(_)) = self
+            .request(&b_conn, RevokeReq { revocation: forged })
+            .await
+        {
+            return Err("a revocation signed by another key was accepted".to_string());
+        }
+
+        let revocation = KeyTriad::revoke(&a.private, utils::now());
+        match self.request(&b_conn, RevokeReq { revocation }).await {
+            Some(RespMessage::Ack(_)) => {}
+            _ => return Err("a valid revocation was not acknowledged".to_string()),
+        }
+        if self.key_exists(&b_conn, a.public, false).await? {
+            return Err("a revoked key was still reported".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+fn identify_req(keys: Vec<KeyTriad<SignedData>>, delegated: Vec<DelegatedTriad>) -> IdentifyReq {
+    IdentifyReq {
+        keys,
+        compact: Vec::new(),
+        multi: Vec::new(),
+        delegated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::node::{InboundEndpoint, InboundHdl, Notify, ServerHandle};
+    use crate::obj::{EndpointInfo, Introduction, Mail};
+
+    #[derive(Debug)]
+    struct NoNotify;
+
+    impl Notify for NoNotify {
+        type Err = Infallible;
+
+        async fn notify_connected(&self, _triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+            Ok(())
+        }
+        async fn notify_introduced(&self, _intro: &Introduction) -> Result<(), Self::Err> {
+            Ok(())
+        }
+        async fn notify_revoked(
+            &self,
+            _revocation: &KeyTriad<SignedData>,
+        ) -> Result<(), Self::Err> {
+            Ok(())
+        }
+        async fn notify_mail(&self, _mail: &Mail) -> Result<(), Self::Err> {
+            Ok(())
+        }
+    }
+
+    impl Connection for InboundHdl<NoNotify> {
+        type Err = Infallible;
+
+        async fn request(&self, req: ReqMessage) -> Result<RespMessage, Self::Err> {
+            Ok(self.respond(req).await)
+        }
+    }
+
+    #[tokio::test]
+    async fn reference_node() {
+        let server_hdl = ServerHandle::new_hdl();
+        let ids = AtomicU64::new(0);
+        let info = EndpointInfo::non_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)));
+        let connect = || async {
+            let id = ids.fetch_add(1, Ordering::Relaxed);
+            Ok::<_, Infallible>(InboundEndpoint::server_hdl(
+                id,
+                info.clone(),
+                Arc::clone(&server_hdl),
+                NoNotify,
+            ))
+        };
+
+        let report = Suite::new(connect).run().await;
+        assert!(
+            report.passed(),
+            "{:?}",
+            report.failures().collect::<Vec<_>>()
+        );
+        assert_eq!(report.results.len(), Case::ALL.len());
+    }
+}
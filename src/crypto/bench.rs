@@ -0,0 +1,55 @@
+//! The strategies for verifying many signatures on the hot path, exposed so that they can be
+//! compared by the benchmarks.
+
+use super::{
+    schnorr::SignatureScheme, verify_batch, PublicKey, Signature, SignatureParsing, SignedHash,
+};
+
+/// Parses the public key and signature of every item each time it is verified. This is what
+/// [`PublicKey::verify`] does.
+pub fn parse_per_call(batch: &[(PublicKey, SignedHash, Signature)]) -> bool {
+    batch
+        .iter()
+        .all(|(public_key, msg, signature)| public_key.valid(*msg, signature))
+}
+
+/// Verifies the items one after the other on the current thread, like [`parse_per_call`], but
+/// from values that were parsed ahead of time.
+pub struct Parsed(
+    Vec<(
+        libsecp256k1::PublicKey,
+        libsecp256k1::Message,
+        libsecp256k1::Signature,
+    )>,
+);
+
+impl Parsed {
+    /// Parses every item of `batch`, returning [`None`] if any of them fails to parse or is not
+    /// an ECDSA signature.
+    pub fn new(batch: &[(PublicKey, SignedHash, Signature)]) -> Option<Self> {
+        batch
+            .iter()
+            .map(|(public_key, msg, signature)| {
+                if msg.scheme != SignatureScheme::Ecdsa {
+                    return None;
+                }
+                Some((
+                    public_key.to_secp().ok()?,
+                    libsecp256k1::Message::parse(&msg.hash.0),
+                    signature.to_secp(SignatureParsing::Strict).ok()?,
+                ))
+            })
+            .collect::<Option<_>>()
+            .map(Self)
+    }
+    pub fn verify(&self) -> bool {
+        self.0
+            .iter()
+            .all(|(public_key, msg, signature)| libsecp256k1::verify(msg, signature, public_key))
+    }
+}
+
+/// Splits large batches across the available cores. This is what [`verify_batch`] does.
+pub fn batch(batch: &[(PublicKey, SignedHash, Signature)]) -> bool {
+    verify_batch(batch)
+}
@@ -0,0 +1,86 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use lru::LruCache;
+
+use super::{error::VerifyError, PublicKey, Signature, SignedHash, ToHashMsg};
+use crate::utils::LockExt;
+
+/// A signature over a hash, by a public key.
+pub type VerifyItem = (PublicKey, SignedHash, Signature);
+
+/// A cache of the signatures that were verified recently, so that triads that are verified again,
+/// such as triads gossiped between servers, skip the verification.
+///
+/// Only valid signatures are cached. The least recently used signatures are evicted first.
+#[derive(Debug)]
+pub struct VerifyCache {
+    verified: Mutex<LruCache<VerifyItem, ()>>,
+}
+
+impl VerifyCache {
+    /// Creates a cache that holds up to `capacity` signatures.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            verified: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+    /// Returns whether `item` was verified recently.
+    pub fn contains(&self, item: &VerifyItem) -> bool {
+        self.verified.locked().get(item).is_some()
+    }
+    /// Records that `item` is a valid signature.
+    pub fn insert(&self, item: VerifyItem) {
+        self.verified.locked().put(item, ());
+    }
+    /// Returns the amount of cached signatures.
+    pub fn len(&self) -> usize {
+        self.verified.locked().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Like [`PublicKey::verify`], but skips the verification if the signature was verified
+    /// recently.
+    pub fn verify(
+        &self,
+        public_key: &PublicKey,
+        msg: impl ToHashMsg,
+        signature: &Signature,
+    ) -> Result<(), VerifyError> {
+        let item = (*public_key, SignedHash::of(msg), *signature);
+        if self.contains(&item) {
+            return Ok(());
+        }
+
+        public_key.verify(item.1, signature)?;
+        self.insert(item);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{hash, KeyPair};
+
+    #[test]
+    fn cache() {
+        let cache = VerifyCache::new(NonZeroUsize::new(1).unwrap());
+        let pair = KeyPair::generate();
+        let (a, b) = (hash(b"a"), hash(b"b"));
+
+        assert!(cache.verify(&pair.public, a, &pair.sign(a)).is_ok());
+        assert!(cache.contains(&(pair.public, a.into(), pair.sign(a))));
+
+        // invalid signatures are not cached
+        assert!(cache.verify(&pair.public, b, &pair.sign(a)).is_err());
+        assert!(!cache.contains(&(pair.public, b.into(), pair.sign(a))));
+
+        // the least recently used signature is evicted
+        cache.verify(&pair.public, b, &pair.sign(b)).unwrap();
+        assert!(!cache.contains(&(pair.public, a.into(), pair.sign(a))));
+        assert_eq!(cache.len(), 1);
+    }
+}
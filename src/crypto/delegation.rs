@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    error::{DelegationError, SignError},
+    KeyTriad, PrivateKey, PublicKey,
+};
+use crate::obj::{SignMessageType, SignedData, SignedFormat};
+
+/// The maximum amount of delegations in a chain.
+pub const MAX_DELEGATION_DEPTH: usize = 8;
+
+/// A set of actions a delegated key may take on behalf of its parent.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, Hash,
+)]
+#[serde(transparent)]
+pub struct DelegationScope(pub u32);
+
+impl DelegationScope {
+    /// The key may identify as its parent.
+    pub const IDENTIFY: Self = Self(1 << 0);
+    /// The key may delegate to other keys, within its own scope.
+    pub const DELEGATE: Self = Self(1 << 1);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+    pub const fn all() -> Self {
+        Self(u32::MAX)
+    }
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// An authorization of a child key to act on behalf of the key that signed it, signed as a
+/// [`Signable`](crate::obj::Signable) of [`SignMessageType::Delegate`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Delegation {
+    /// The key that is authorized.
+    pub child: PublicKey,
+    pub scope: DelegationScope,
+    /// The expiration timestamp of the delegation.
+    #[serde(rename = "expireTime")]
+    pub expire_time: u64,
+}
+
+impl Delegation {
+    /// Reads the delegation signed in `signed`, which must be a signable of
+    /// [`SignMessageType::Delegate`], so that a signature made for another purpose cannot
+    /// delegate.
+    pub fn decode(signed: &SignedData) -> Result<Self, DelegationError> {
+        match signed.msg_type() {
+            Some(SignMessageType::Delegate) => {}
+            Some(found) => return Err(DelegationError::WrongMessageType(found)),
+            None => return Err(DelegationError::Malformed),
+        }
+
+        signed
+            .to_signable()
+            .map(|signable| signable.obj)
+            .map_err(|_| DelegationError::Malformed)
+    }
+}
+
+impl KeyTriad<SignedData> {
+    /// Authorizes `child` to take the actions in `scope` on behalf of `parent` until
+    /// `expire_time`.
+    pub fn delegate(
+        parent: &PrivateKey,
+        child: PublicKey,
+        scope: DelegationScope,
+        expire_time: u64,
+    ) -> Result<Self, SignError> {
+        let delegation = Delegation {
+            child,
+            scope,
+            expire_time,
+        };
+        Self::gen_signed(
+            parent,
+            &delegation,
+            SignMessageType::Delegate,
+            SignedFormat::Cbor,
+        )
+    }
+}
+
+/// Verifies a chain of delegations from a root key to `key` at the time `now`, returning the root
+/// key. Every delegation must be signed by the child of the previous one, and `key` must be
+/// allowed to take the actions in `required`.
+pub fn verify_chain(
+    chain: &[KeyTriad<SignedData>],
+    key: &PublicKey,
+    required: DelegationScope,
+    now: u64,
+) -> Result<PublicKey, DelegationError> {
+    let root = chain.first().ok_or(DelegationError::Empty)?.public_key;
+    if chain.len() > MAX_DELEGATION_DEPTH {
+        return Err(DelegationError::TooLong);
+    }
+
+    let mut parent = root;
+    let mut scope = DelegationScope::all();
+    for (index, link) in chain.iter().enumerate() {
+        if link.public_key != parent {
+            return Err(DelegationError::Broken);
+        }
+        let delegation = Delegation::decode(&link.signed)?;
+        link.verify()
+            .map_err(|_| DelegationError::SignatureInvalid)?;
+        if now > delegation.expire_time {
+            return Err(DelegationError::Expired);
+        }
+
+        // a key can only delegate what it was delegated
+        let delegates = index + 1 < chain.len();
+        if !scope.contains(delegation.scope)
+            || (delegates && !delegation.scope.contains(DelegationScope::DELEGATE))
+        {
+            return Err(DelegationError::ScopeExceeded);
+        }
+
+        parent = delegation.child;
+        scope = delegation.scope;
+    }
+
+    if parent != *key {
+        return Err(DelegationError::Broken);
+    }
+    if !scope.contains(required) {
+        return Err(DelegationError::ScopeExceeded);
+    }
+
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn chain() {
+        let root = KeyPair::generate();
+        let device = KeyPair::generate();
+        let app = KeyPair::generate();
+        let all = DelegationScope::IDENTIFY.union(DelegationScope::DELEGATE);
+
+        let chain = vec![
+            KeyTriad::delegate(&root.private, device.public, all, 100).unwrap(),
+            KeyTriad::delegate(&device.private, app.public, DelegationScope::IDENTIFY, 50).unwrap(),
+        ];
+        assert_eq!(
+            verify_chain(&chain, &app.public, DelegationScope::IDENTIFY, 10),
+            Ok(root.public)
+        );
+        assert_eq!(
+            verify_chain(&chain, &app.public, DelegationScope::IDENTIFY, 60),
+            Err(DelegationError::Expired)
+        );
+        assert_eq!(
+            verify_chain(&chain, &device.public, DelegationScope::IDENTIFY, 10),
+            Err(DelegationError::Broken)
+        );
+        assert_eq!(
+            verify_chain(&chain, &app.public, DelegationScope::DELEGATE, 10),
+            Err(DelegationError::ScopeExceeded)
+        );
+
+        // the device was not allowed to delegate
+        let chain = vec![
+            KeyTriad::delegate(&root.private, device.public, DelegationScope::IDENTIFY, 100)
+                .unwrap(),
+            KeyTriad::delegate(&device.private, app.public, DelegationScope::IDENTIFY, 50).unwrap(),
+        ];
+        assert_eq!(
+            verify_chain(&chain, &app.public, DelegationScope::IDENTIFY, 10),
+            Err(DelegationError::ScopeExceeded)
+        );
+
+        let delegation = KeyTriad::delegate(&root.private, app.public, all, 100).unwrap();
+        let forged = KeyTriad::gen_signed(
+            &app.private,
+            &Delegation {
+                expire_time: 200,
+                ..Delegation::decode(&delegation.signed).unwrap()
+            },
+            SignMessageType::Delegate,
+            SignedFormat::Cbor,
+        )
+        .unwrap();
+        let forged = KeyTriad {
+            public_key: root.public,
+            ..forged
+        };
+        assert_eq!(
+            verify_chain(&[forged], &app.public, DelegationScope::IDENTIFY, 10),
+            Err(DelegationError::SignatureInvalid)
+        );
+
+        // a signature made for another purpose does not delegate
+        let replayed = KeyTriad::gen_signed(
+            &root.private,
+            &Delegation::decode(&delegation.signed).unwrap(),
+            SignMessageType::AppMessage,
+            SignedFormat::Cbor,
+        )
+        .unwrap();
+        assert_eq!(
+            verify_chain(&[replayed], &app.public, DelegationScope::IDENTIFY, 10),
+            Err(DelegationError::WrongMessageType(
+                SignMessageType::AppMessage
+            ))
+        );
+        assert_eq!(
+            verify_chain(
+                &[KeyTriad {
+                    signed: delegation.signed.detach(),
+                    ..delegation
+                }],
+                &app.public,
+                DelegationScope::IDENTIFY,
+                10
+            ),
+            Err(DelegationError::Malformed)
+        );
+    }
+}
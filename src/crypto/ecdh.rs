@@ -0,0 +1,81 @@
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::{error::KeyParseError, PrivateKey, PublicKey};
+
+/// The size (in bytes) of a shared secret.
+pub const SHARED_SECRET_SIZE: usize = 32;
+
+/// A secret shared between two keypairs, computed using ECDH. Is the x coordinate of the shared
+/// point, and should not be used as a key directly. Use [`SharedSecret::derive_key`] instead.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct SharedSecret([u8; SHARED_SECRET_SIZE]);
+
+impl std::fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedSecret(..)")
+    }
+}
+
+impl SharedSecret {
+    /// Returns the raw bytes of this shared secret.
+    pub fn as_bytes(&self) -> &[u8; SHARED_SECRET_SIZE] {
+        &self.0
+    }
+    /// Derives key material from this secret using HKDF-SHA256, filling `okm`. The `info`
+    /// should differ for every purpose a key is derived for. Panics if `okm` is longer than
+    /// 8160 bytes.
+    pub fn expand(&self, salt: &[u8], info: &[u8], okm: &mut [u8]) {
+        Hkdf::<Sha256>::new(Some(salt), &self.0)
+            .expand(info, okm)
+            .expect("output key material is too long for HKDF-SHA256");
+    }
+    /// Derives a 32 byte key from this secret using HKDF-SHA256.
+    pub fn derive_key(&self, salt: &[u8], info: &[u8]) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        self.expand(salt, info, &mut key);
+        key
+    }
+}
+
+impl PrivateKey {
+    /// Computes the secret shared between this private key and `public`. Both sides of a
+    /// conversation compute the same secret.
+    pub fn diffie_hellman(&self, public: &PublicKey) -> Result<SharedSecret, KeyParseError> {
+        let mut point = public.to_secp()?;
+        point
+            .tweak_mul_assign(&self.0)
+            .map_err(|_| KeyParseError::InvalidPublicKey)?;
+
+        let mut secret = [0u8; SHARED_SECRET_SIZE];
+        secret.copy_from_slice(&point.serialize_compressed()[1..]);
+
+        Ok(SharedSecret(secret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn shared_secret() {
+        let (a, b, c) = (
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        let ab = a.private.diffie_hellman(&b.public).unwrap();
+        let ba = b.private.diffie_hellman(&a.public).unwrap();
+        let ac = a.private.diffie_hellman(&c.public).unwrap();
+
+        assert_eq!(ab, ba);
+        assert_ne!(ab, ac);
+        assert_eq!(
+            ab.derive_key(b"", b"session"),
+            ba.derive_key(b"", b"session")
+        );
+        assert_ne!(ab.derive_key(b"", b"session"), ab.derive_key(b"", b"other"));
+    }
+}
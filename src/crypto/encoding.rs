@@ -0,0 +1,181 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+use serde_with::{DeserializeAs, Same, SerializeAs};
+
+use super::{error::DecodeError, HashMsg, PublicKey, Signature};
+
+/// Serializes a byte array as a hex string when the format is human-readable, such as JSON, and
+/// as an array of bytes otherwise, such as CBOR.
+///
+/// Human-readable formats also accept an array of bytes, which older versions serialized.
+pub(crate) struct HexOrBytes;
+
+impl<const N: usize> SerializeAs<[u8; N]> for HexOrBytes {
+    fn serialize_as<S: Serializer>(source: &[u8; N], serializer: S) -> Result<S::Ok, S::Error> {
+        match serializer.is_human_readable() {
+            true => serializer.serialize_str(&hex::encode(source)),
+            false => <[Same; N]>::serialize_as(source, serializer),
+        }
+    }
+}
+impl<'de, const N: usize> DeserializeAs<'de, [u8; N]> for HexOrBytes {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<[u8; N], D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Hex(String),
+            Bytes(Vec<u8>),
+        }
+
+        if !deserializer.is_human_readable() {
+            return <[Same; N]>::deserialize_as(deserializer);
+        }
+
+        let bytes = match Repr::deserialize(deserializer)? {
+            Repr::Hex(hex) => hex::decode(hex).map_err(D::Error::custom)?,
+            Repr::Bytes(bytes) => bytes,
+        };
+        to_array(&bytes).map_err(D::Error::custom)
+    }
+}
+
+fn to_array<const N: usize>(bytes: &[u8]) -> Result<[u8; N], DecodeError> {
+    bytes.try_into().map_err(|_| DecodeError::InvalidLength {
+        expected: N,
+        received: bytes.len(),
+    })
+}
+
+macro_rules! encoding_impl {
+    ($for:ident, $parse:expr) => {
+        impl $for {
+            /// Encodes this value as lowercase hexadecimal.
+            pub fn to_hex(&self) -> String {
+                hex::encode(self.0)
+            }
+            /// Decodes a value from hexadecimal of either case.
+            pub fn from_hex(s: &str) -> Result<Self, DecodeError> {
+                let bytes = hex::decode(s).map_err(|_| DecodeError::InvalidHex)?;
+                $parse(&bytes)
+            }
+            /// Encodes this value as base58, followed by a 4 byte checksum.
+            pub fn to_base58check(&self) -> String {
+                bs58::encode(self.0).with_check().into_string()
+            }
+            /// Decodes a value encoded with a checksum by
+            #[doc = concat!("[`", stringify!($for), "::to_base58check`].")]
+            pub fn from_base58check(s: &str) -> Result<Self, DecodeError> {
+                let bytes =
+                    bs58::decode(s)
+                        .with_check(None)
+                        .into_vec()
+                        .map_err(|err| match err {
+                            bs58::decode::Error::InvalidChecksum { .. } => {
+                                DecodeError::InvalidChecksum
+                            }
+                            _ => DecodeError::InvalidBase58,
+                        })?;
+                $parse(&bytes)
+            }
+        }
+
+        impl fmt::Display for $for {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.to_hex())
+            }
+        }
+        impl FromStr for $for {
+            type Err = DecodeError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::from_hex(s)
+            }
+        }
+    };
+}
+
+encoding_impl!(PublicKey, |bytes: &[u8]| Ok(PublicKey::parse(bytes)?));
+encoding_impl!(Signature, |bytes: &[u8]| Ok(Signature(to_array(bytes)?)));
+encoding_impl!(HashMsg, |bytes: &[u8]| Ok(HashMsg(to_array(bytes)?)));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{hash, KeyPair, KeyParseError, HASH_SIZE, PUBLIC_KEY_SIZE};
+
+    #[test]
+    fn round_trip() {
+        let pair = KeyPair::generate();
+        let signature = pair.sign(b"msg");
+        let msg = hash(b"msg");
+
+        assert_eq!(pair.public.to_string().parse(), Ok(pair.public));
+        assert_eq!(signature.to_string().parse(), Ok(signature));
+        assert_eq!(msg.to_string().to_uppercase().parse(), Ok(msg));
+
+        let encoded = pair.public.to_base58check();
+        assert_eq!(PublicKey::from_base58check(&encoded), Ok(pair.public));
+        assert_eq!(
+            Signature::from_base58check(&signature.to_base58check()),
+            Ok(signature)
+        );
+    }
+
+    #[test]
+    fn serde() {
+        let pair = KeyPair::generate();
+
+        let json = serde_json::to_string(&pair.public).unwrap();
+        assert_eq!(json, format!("\"{}\"", pair.public.to_hex()));
+        assert_eq!(
+            serde_json::from_str::<PublicKey>(&json).unwrap(),
+            pair.public
+        );
+
+        // arrays of bytes are still accepted
+        let array = serde_json::to_string(&pair.public.0.to_vec()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PublicKey>(&array).unwrap(),
+            pair.public
+        );
+
+        // CBOR keeps the compact representation
+        let cbor = serde_cbor::to_vec(&pair.public).unwrap();
+        assert_eq!(cbor, serde_cbor::to_vec(&pair.public.0.to_vec()).unwrap());
+        assert_eq!(
+            serde_cbor::from_slice::<PublicKey>(&cbor).unwrap(),
+            pair.public
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let msg = hash(b"msg");
+
+        assert_eq!("zz".parse::<HashMsg>(), Err(DecodeError::InvalidHex));
+        assert_eq!(
+            "abcd".parse::<HashMsg>(),
+            Err(DecodeError::InvalidLength {
+                expected: HASH_SIZE,
+                received: 2
+            })
+        );
+        assert_eq!(
+            PublicKey::from_hex(&hex::encode([9u8; PUBLIC_KEY_SIZE])),
+            Err(DecodeError::InvalidKey(KeyParseError::InvalidPublicKey))
+        );
+
+        // change the first character, which changes the contents but not the checksum
+        let mut encoded = msg.to_base58check().into_bytes();
+        encoded[0] = if encoded[0] == b'2' { b'3' } else { b'2' };
+        assert_eq!(
+            HashMsg::from_base58check(std::str::from_utf8(&encoded).unwrap()),
+            Err(DecodeError::InvalidChecksum)
+        );
+        assert_eq!(
+            HashMsg::from_base58check("0OIl"),
+            Err(DecodeError::InvalidBase58)
+        );
+    }
+}
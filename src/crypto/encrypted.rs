@@ -0,0 +1,225 @@
+//! Password-based encryption of private keys.
+//!
+//! The container format (version 1) is laid out as follows, with integers in big endian:
+//!
+//! | Size | Field                                    |
+//! |------|------------------------------------------|
+//! | 1    | version                                  |
+//! | 4    | Argon2id memory cost (KiB)               |
+//! | 4    | Argon2id iterations                      |
+//! | 4    | Argon2id parallelism                     |
+//! | 16   | salt                                     |
+//! | 24   | XChaCha20-Poly1305 nonce                 |
+//! | 48   | encrypted private key and tag            |
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+use super::{error::EncryptedKeyError, KeyPair, PrivateKey, PRIVATE_KEY_SIZE};
+
+/// The current version of the container format.
+pub const ENCRYPTED_KEY_VERSION: u8 = 1;
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 24;
+const TAG_SIZE: usize = 16;
+const HEADER_SIZE: usize = 1 + 12 + SALT_SIZE + NONCE_SIZE;
+
+/// The size (in bytes) of an encrypted private key.
+pub const ENCRYPTED_KEY_SIZE: usize = HEADER_SIZE + PRIVATE_KEY_SIZE + TAG_SIZE;
+
+/// The Argon2id parameters used to derive the encryption key from a password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KdfParams {
+    /// Memory cost in KiB.
+    pub m_cost: u32,
+    /// Number of iterations.
+    pub t_cost: u32,
+    /// Degree of parallelism.
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    /// The most costly parameters a container may ask for: 1 GiB of memory, 16 iterations and 16
+    /// lanes. Containers are untrusted input, and deriving their key must not exhaust the memory
+    /// or the time of the machine.
+    pub const MAX: Self = Self {
+        m_cost: 1024 * 1024,
+        t_cost: 16,
+        p_cost: 16,
+    };
+
+    fn derive(
+        &self,
+        password: &[u8],
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; 32]>, EncryptedKeyError> {
+        if self.m_cost > Self::MAX.m_cost
+            || self.t_cost > Self::MAX.t_cost
+            || self.p_cost > Self::MAX.p_cost
+        {
+            return Err(EncryptedKeyError::ExcessiveParams);
+        }
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|_| EncryptedKeyError::InvalidParams)?;
+        let mut key = Zeroizing::new([0u8; 32]);
+
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password, salt, &mut key[..])
+            .map_err(|_| EncryptedKeyError::InvalidParams)?;
+
+        Ok(key)
+    }
+}
+
+impl PrivateKey {
+    /// Encrypts this private key with a password, using the default [`KdfParams`].
+    pub fn to_encrypted_bytes(&self, password: &[u8]) -> Result<Vec<u8>, EncryptedKeyError> {
+        self.to_encrypted_bytes_with(&mut crate::utils::rng(), password, KdfParams::default())
+    }
+    /// Encrypts this private key with a password.
+    pub fn to_encrypted_bytes_with(
+        &self,
+        rng: &mut (impl RngCore + CryptoRng),
+        password: &[u8],
+        params: KdfParams,
+    ) -> Result<Vec<u8>, EncryptedKeyError> {
+        let mut salt = [0u8; SALT_SIZE];
+        let mut nonce = [0u8; NONCE_SIZE];
+        rng.fill_bytes(&mut salt);
+        rng.fill_bytes(&mut nonce);
+
+        let key = params.derive(password, &salt)?;
+        let plaintext = Zeroizing::new(self.0.serialize());
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(XNonce::from_slice(&nonce), &plaintext[..])
+            .map_err(|_| EncryptedKeyError::InvalidParams)?;
+
+        let mut bytes = Vec::with_capacity(ENCRYPTED_KEY_SIZE);
+        bytes.push(ENCRYPTED_KEY_VERSION);
+        bytes.extend_from_slice(&params.m_cost.to_be_bytes());
+        bytes.extend_from_slice(&params.t_cost.to_be_bytes());
+        bytes.extend_from_slice(&params.p_cost.to_be_bytes());
+        bytes.extend_from_slice(&salt);
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&ciphertext);
+
+        Ok(bytes)
+    }
+    /// Decrypts a private key encrypted with [`PrivateKey::to_encrypted_bytes`].
+    pub fn from_encrypted_bytes(bytes: &[u8], password: &[u8]) -> Result<Self, EncryptedKeyError> {
+        match bytes.first() {
+            Some(&ENCRYPTED_KEY_VERSION) => {}
+            Some(&version) => return Err(EncryptedKeyError::UnsupportedVersion(version)),
+            None => return Err(EncryptedKeyError::Truncated),
+        }
+        if bytes.len() != ENCRYPTED_KEY_SIZE {
+            return Err(EncryptedKeyError::Truncated);
+        }
+
+        let u32_at = |index: usize| {
+            u32::from_be_bytes([
+                bytes[index],
+                bytes[index + 1],
+                bytes[index + 2],
+                bytes[index + 3],
+            ])
+        };
+        let params = KdfParams {
+            m_cost: u32_at(1),
+            t_cost: u32_at(5),
+            p_cost: u32_at(9),
+        };
+        let salt = &bytes[13..13 + SALT_SIZE];
+        let nonce = &bytes[13 + SALT_SIZE..HEADER_SIZE];
+
+        let key = params.derive(password, salt)?;
+        let plaintext = Zeroizing::new(
+            XChaCha20Poly1305::new(key.as_ref().into())
+                .decrypt(XNonce::from_slice(nonce), &bytes[HEADER_SIZE..])
+                .map_err(|_| EncryptedKeyError::DecryptionFailed)?,
+        );
+
+        Self::parse(&plaintext).map_err(|_| EncryptedKeyError::InvalidKey)
+    }
+}
+
+impl KeyPair {
+    /// Encrypts the private key of this keypair with a password. Refer to
+    /// [`PrivateKey::to_encrypted_bytes`].
+    pub fn to_encrypted_bytes(&self, password: &[u8]) -> Result<Vec<u8>, EncryptedKeyError> {
+        self.private.to_encrypted_bytes(password)
+    }
+    /// Decrypts a keypair encrypted with [`KeyPair::to_encrypted_bytes`].
+    pub fn from_encrypted_bytes(bytes: &[u8], password: &[u8]) -> Result<Self, EncryptedKeyError> {
+        Ok(PrivateKey::from_encrypted_bytes(bytes, password)?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// Cheap parameters, so the tests run quickly.
+    const PARAMS: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn round_trip() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let pair = KeyPair::generate_with(&mut rng);
+        let bytes = pair
+            .private
+            .to_encrypted_bytes_with(&mut rng, b"hunter2", PARAMS)
+            .unwrap();
+
+        assert_eq!(bytes.len(), ENCRYPTED_KEY_SIZE);
+        assert_eq!(KeyPair::from_encrypted_bytes(&bytes, b"hunter2"), Ok(pair));
+        assert_eq!(
+            KeyPair::from_encrypted_bytes(&bytes, b"hunter3"),
+            Err(EncryptedKeyError::DecryptionFailed)
+        );
+
+        let mut tampered = bytes.clone();
+        tampered[0] = 9;
+        assert_eq!(
+            KeyPair::from_encrypted_bytes(&tampered, b"hunter2"),
+            Err(EncryptedKeyError::UnsupportedVersion(9))
+        );
+        assert_eq!(
+            KeyPair::from_encrypted_bytes(&bytes[..20], b"hunter2"),
+            Err(EncryptedKeyError::Truncated)
+        );
+
+        // a crafted container cannot demand unbounded memory or time
+        let mut costly = bytes.clone();
+        costly[1..5].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(
+            KeyPair::from_encrypted_bytes(&costly, b"hunter2"),
+            Err(EncryptedKeyError::ExcessiveParams)
+        );
+        let mut costly = bytes;
+        costly[5..9].copy_from_slice(&(KdfParams::MAX.t_cost + 1).to_be_bytes());
+        assert_eq!(
+            KeyPair::from_encrypted_bytes(&costly, b"hunter2"),
+            Err(EncryptedKeyError::ExcessiveParams)
+        );
+    }
+}
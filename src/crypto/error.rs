@@ -0,0 +1,249 @@
+use std::convert::Infallible;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::obj::SignMessageType;
+
+/// This error happens when bytes cannot be parsed into a key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum KeyParseError {
+    /// The amount of bytes provided does not match the size of the key.
+    #[error("expected {expected} bytes however received {received}")]
+    InvalidLength { expected: usize, received: usize },
+    /// The bytes are not a valid compressed secp256k1 point.
+    #[error("invalid public key")]
+    InvalidPublicKey,
+    /// The bytes are not a valid secp256k1 scalar.
+    #[error("invalid private key")]
+    InvalidPrivateKey,
+}
+
+/// This error happens when a signature fails to verify.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum VerifyError {
+    /// The public key is not a valid compressed secp256k1 point.
+    #[error("invalid public key")]
+    InvalidPublicKey,
+    /// The signature is not a valid encoding of a signature.
+    #[error("invalid signature encoding")]
+    InvalidSignature,
+    /// The signature has a high `s` value. Its canonical encoding has a low `s` value.
+    #[error("signature is not canonical")]
+    NonCanonical,
+    /// The signature is well formed, but was not created by the key over the message.
+    #[error("signature does not match the public key and message")]
+    Mismatch,
+}
+
+/// An error that can occur when deriving a key from a mnemonic phrase.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum MnemonicError {
+    /// The phrase is not a valid BIP39 mnemonic.
+    #[error("{}", .0)]
+    InvalidPhrase(#[from] bip39::Error),
+    /// The seed derived an invalid private key.
+    #[error("the seed does not derive a valid private key")]
+    InvalidSeed,
+}
+
+/// An error that can occur when deriving a child key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum DeriveError {
+    /// The derivation path could not be parsed.
+    #[error("invalid derivation path")]
+    InvalidPath,
+    /// The child index derives an invalid key. The next index should be used instead.
+    #[error("child {} derives an invalid key", .0)]
+    InvalidChild(u32),
+}
+
+/// An error that can occur when encrypting or decrypting a private key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum EncryptedKeyError {
+    /// The container is shorter than its header says it should be.
+    #[error("encrypted key is truncated")]
+    Truncated,
+    /// The container was created by an unsupported version of the format.
+    #[error("unsupported encrypted key version {}", .0)]
+    UnsupportedVersion(u8),
+    /// The key derivation parameters are invalid.
+    #[error("invalid key derivation parameters")]
+    InvalidParams,
+    /// The key derivation parameters cost more than [`KdfParams::MAX`](super::encrypted::KdfParams::MAX)
+    /// allows.
+    #[error("key derivation parameters exceed the maximum costs")]
+    ExcessiveParams,
+    /// The password is incorrect, or the container was tampered with.
+    #[error("failed to decrypt key")]
+    DecryptionFailed,
+    /// The decrypted bytes are not a valid private key.
+    #[error("invalid private key")]
+    InvalidKey,
+}
+
+/// This error happens when a signature cannot be used to recover a public key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum RecoverError {
+    /// The recovery id is not between 0 and 3.
+    #[error("invalid recovery id")]
+    InvalidRecoveryId,
+    /// The signature does not recover a public key.
+    #[error("invalid signature")]
+    InvalidSignature,
+}
+
+/// This error happens when a string cannot be decoded into a key, signature or hash.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum DecodeError {
+    /// The string is not valid hexadecimal.
+    #[error("invalid hex")]
+    InvalidHex,
+    /// The string is not valid base58.
+    #[error("invalid base58")]
+    InvalidBase58,
+    /// The checksum of a base58check string does not match its contents.
+    #[error("invalid checksum")]
+    InvalidChecksum,
+    /// The amount of decoded bytes does not match the size of the value.
+    #[error("expected {expected} bytes however received {received}")]
+    InvalidLength { expected: usize, received: usize },
+    /// The decoded bytes are not a valid key.
+    #[error("{}", .0)]
+    InvalidKey(#[from] KeyParseError),
+}
+
+/// An error that can occur when generating or signing with a threshold key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum ThresholdError {
+    /// The parameters are invalid, such as a threshold larger than the amount of participants,
+    /// or less signers than the threshold.
+    #[error("invalid threshold parameters")]
+    InvalidParams,
+    /// A participant did not send its commitment or share.
+    #[error("missing participant {}", .0)]
+    MissingParticipant(u16),
+    /// The commitment of a participant is malformed, or its proof is invalid.
+    #[error("invalid commitment from participant {}", .0)]
+    InvalidCommitment(u16),
+    /// The share sent by a participant does not match its commitment.
+    #[error("invalid share from participant {}", .0)]
+    InvalidShare(u16),
+    /// The aggregated signature is invalid, because a signer sent an invalid share.
+    #[error("invalid signature")]
+    InvalidSignature,
+}
+
+/// This error happens when a chain of delegations does not authorize a key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum DelegationError {
+    /// The chain has no delegations.
+    #[error("empty delegation chain")]
+    Empty,
+    /// The chain has more delegations than allowed.
+    #[error("delegation chain is too long")]
+    TooLong,
+    /// A delegation is not signed by the child of the previous one, or the chain does not end at
+    /// the key.
+    #[error("broken delegation chain")]
+    Broken,
+    /// The signature of a delegation is invalid.
+    #[error("delegation signature invalid")]
+    SignatureInvalid,
+    /// A delegation is signed for another purpose.
+    #[error("expected a delegation, found {0:?}")]
+    WrongMessageType(SignMessageType),
+    /// A delegation cannot be read, such as when it is detached or encrypted.
+    #[error("malformed delegation")]
+    Malformed,
+    #[error("delegation expired")]
+    Expired,
+    /// A delegation grants more than its signer was granted.
+    #[error("delegation scope exceeded")]
+    ScopeExceeded,
+}
+
+/// This error happens when a [`MultiKeyTriad`](super::multi::MultiKeyTriad) does not claim the
+/// identity of its group.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum MultiKeyError {
+    /// The group is not signed by the identity it claims.
+    #[error("group signature invalid")]
+    GroupSignatureInvalid,
+    /// The group is signed for another purpose.
+    #[error("expected a key group, found {0:?}")]
+    WrongMessageType(SignMessageType),
+    /// The group cannot be read, such as when it is detached or encrypted.
+    #[error("malformed key group")]
+    Malformed,
+    #[error("key group expired")]
+    Expired,
+    /// The threshold of the group is zero, or larger than the group.
+    #[error("invalid group threshold")]
+    InvalidThreshold,
+    /// A signer is not a key of the group.
+    #[error("signer is not in the group")]
+    UnknownSigner,
+    /// A key of the group signed more than once.
+    #[error("duplicate signer")]
+    DuplicateSigner,
+    /// Less distinct keys of the group signed than its threshold.
+    #[error("{signers} signers are below the threshold of {threshold}")]
+    BelowThreshold { threshold: u16, signers: usize },
+    /// The signature of a signer failed to verify.
+    #[error("{}", .0)]
+    Verify(#[from] VerifyError),
+}
+
+/// This error happens when a [`Token`](super::token::Token) is not accepted.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum TokenError {
+    /// The token was not issued with the key.
+    #[error("invalid token code")]
+    InvalidMac,
+    /// The token was issued for another scope.
+    #[error("token issued for another scope")]
+    WrongScope,
+    #[error("token expired")]
+    Expired,
+}
+
+/// This error happens when a job is submitted to a [`CryptoPool`](super::pool::CryptoPool) whose
+/// workers have stopped, or the job panicked.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+#[error("the crypto pool is closed")]
+pub struct PoolClosedError;
+
+/// An error that can occur when signing a signable.
+#[derive(Error, Debug)]
+pub enum SignError<E = Infallible> {
+    /// The signable could not be serialized to canonical CBOR.
+    #[error("failed to serialize signable: {}", .0)]
+    Serialize(#[from] serde_cbor::Error),
+    /// The signable could not be serialized to JSON.
+    #[error("failed to serialize signable: {}", .0)]
+    SerializeJson(#[from] serde_json::Error),
+    /// The signer failed to sign.
+    #[error("{0}")]
+    Signer(E),
+}
+
+/// An error that can occur when sealing or opening a sealed box.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum SealError {
+    /// The public key of the recipient or the ephemeral public key is invalid.
+    #[error("invalid public key")]
+    InvalidPublicKey,
+    /// The box is shorter than its overhead.
+    #[error("sealed box is truncated")]
+    Truncated,
+    /// The box was not sealed to the key, or was tampered with.
+    #[error("failed to open sealed box")]
+    DecryptionFailed,
+    /// The data is not encrypted.
+    #[error("data is not encrypted")]
+    NotEncrypted,
+    /// The opened payload is not valid signed data.
+    #[error("invalid sealed payload")]
+    InvalidPayload,
+}
@@ -0,0 +1,319 @@
+use core::{fmt, str::FromStr};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use zeroize::Zeroizing;
+
+use super::{
+    error::{DeriveError, MnemonicError},
+    mnemonic, KeyPair, PrivateKey, PRIVATE_KEY_SIZE,
+};
+
+/// The size (in bytes) of a chain code.
+pub const CHAIN_CODE_SIZE: usize = 32;
+
+/// The HMAC key used to derive the master key from a seed, as specified by BIP32.
+const MASTER_KEY_DOMAIN: &[u8] = b"Bitcoin seed";
+
+/// The HMAC key used to derive the chain code of a [`KeyPair`] from its private key.
+const CHAIN_CODE_DOMAIN: &[u8] = b"cacophoney/chain-code";
+
+/// The bit that marks a child index as hardened.
+const HARDENED_BIT: u32 = 1 << 31;
+
+/// The index of a child key. Hardened children cannot be linked to their parent using only the
+/// parent's public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChildNumber(pub u32);
+
+impl ChildNumber {
+    pub const fn normal(index: u32) -> Self {
+        Self(index & !HARDENED_BIT)
+    }
+    pub const fn hardened(index: u32) -> Self {
+        Self(index | HARDENED_BIT)
+    }
+    pub const fn is_hardened(&self) -> bool {
+        self.0 & HARDENED_BIT != 0
+    }
+}
+impl fmt::Display for ChildNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_hardened() {
+            true => write!(f, "{}'", self.0 & !HARDENED_BIT),
+            false => write!(f, "{}", self.0),
+        }
+    }
+}
+impl FromStr for ChildNumber {
+    type Err = DeriveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, hardened) = match s.strip_suffix(['\'', 'h', 'H']) {
+            Some(index) => (index, true),
+            None => (s, false),
+        };
+        let index: u32 = index.parse().map_err(|_| DeriveError::InvalidPath)?;
+
+        if index & HARDENED_BIT != 0 {
+            return Err(DeriveError::InvalidPath);
+        }
+
+        Ok(match hardened {
+            true => Self::hardened(index),
+            false => Self::normal(index),
+        })
+    }
+}
+
+/// A BIP32 derivation path, such as `m/44'/0'/1`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct DerivationPath(pub Vec<ChildNumber>);
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("m")?;
+        for child in self.0.iter() {
+            write!(f, "/{child}")?;
+        }
+        Ok(())
+    }
+}
+impl FromStr for DerivationPath {
+    type Err = DeriveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(DeriveError::InvalidPath);
+        }
+
+        Ok(Self(parts.map(str::parse).collect::<Result<_, _>>()?))
+    }
+}
+
+/// A private key together with a chain code, which child keys can be derived from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExtendedKey {
+    pub key: PrivateKey,
+    pub chain_code: [u8; CHAIN_CODE_SIZE],
+}
+
+impl ExtendedKey {
+    /// Derives the master key from a seed.
+    pub fn master(seed: &[u8]) -> Result<Self, MnemonicError> {
+        let (key, chain_code) = split(hmac_sha512(MASTER_KEY_DOMAIN, &[seed]));
+        let key = PrivateKey::try_from(*key).map_err(|_| MnemonicError::InvalidSeed)?;
+
+        Ok(Self { key, chain_code })
+    }
+    /// Derives the master key from a BIP39 mnemonic phrase and a passphrase.
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self, MnemonicError> {
+        Self::master(&mnemonic::to_seed(phrase, passphrase)?[..])
+    }
+    /// Returns the keypair of this extended key.
+    pub fn key_pair(&self) -> KeyPair {
+        self.key.clone().into()
+    }
+    /// Derives the direct child of this key with the index `child`.
+    pub fn child(&self, child: ChildNumber) -> Result<Self, DeriveError> {
+        let index = child.0.to_be_bytes();
+        let out = match child.is_hardened() {
+            true => {
+                let key = Zeroizing::new(self.key.0.serialize());
+                hmac_sha512(&self.chain_code, &[&[0u8], &key[..], &index])
+            }
+            false => {
+                let public = self.key.derive_public();
+                hmac_sha512(&self.chain_code, &[&public.0, &index])
+            }
+        };
+        let (tweak, chain_code) = split(out);
+
+        let tweak = libsecp256k1::SecretKey::parse(&tweak)
+            .map_err(|_| DeriveError::InvalidChild(child.0))?;
+        let mut key = self.key.0;
+        key.tweak_add_assign(&tweak)
+            .map_err(|_| DeriveError::InvalidChild(child.0))?;
+
+        Ok(Self {
+            key: PrivateKey(key),
+            chain_code,
+        })
+    }
+    /// Derives the descendant of this key at `path`, relative to this key.
+    pub fn derive_child(&self, path: &DerivationPath) -> Result<Self, DeriveError> {
+        path.0
+            .iter()
+            .try_fold(self.clone(), |key, child| key.child(*child))
+    }
+}
+
+impl KeyPair {
+    /// Returns the extended key of this pair, whose chain code is derived from the private key,
+    /// so that the private key alone backs up every key derived from it.
+    pub fn extended(&self) -> ExtendedKey {
+        let key = Zeroizing::new(self.private.0.serialize());
+        let (_, chain_code) = split(hmac_sha512(CHAIN_CODE_DOMAIN, &[&key[..]]));
+
+        ExtendedKey {
+            key: self.private.clone(),
+            chain_code,
+        }
+    }
+    /// Derives the descendant of this pair at `path`, such as a key for each device or node, that
+    /// cannot be linked to this pair by anyone who does not hold its private key.
+    pub fn derive_child(&self, path: &DerivationPath) -> Result<KeyPair, DeriveError> {
+        Ok(self.extended().derive_child(path)?.key_pair())
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> Zeroizing<[u8; 64]> {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for data in data {
+        mac.update(data);
+    }
+
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+fn split(out: Zeroizing<[u8; 64]>) -> (Zeroizing<[u8; PRIVATE_KEY_SIZE]>, [u8; CHAIN_CODE_SIZE]) {
+    let mut key = Zeroizing::new([0u8; PRIVATE_KEY_SIZE]);
+    let mut chain_code = [0u8; CHAIN_CODE_SIZE];
+    key.copy_from_slice(&out[..32]);
+    chain_code.copy_from_slice(&out[32..]);
+
+    (key, chain_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn bip32_vector() {
+        // test vector 1 of BIP32
+        let master = ExtendedKey::master(&hex("000102030405060708090a0b0c0d0e0f")).unwrap();
+        assert_eq!(
+            master.chain_code.to_vec(),
+            hex("873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508")
+        );
+
+        let child = master.derive_child(&"m/0'".parse().unwrap()).unwrap();
+        assert_eq!(
+            child.key.0.serialize().to_vec(),
+            hex("edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea")
+        );
+        assert_eq!(
+            child.key_pair().public.0.to_vec(),
+            hex("035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56")
+        );
+    }
+
+    #[test]
+    fn bip32_deep_vector() {
+        // test vector 1 of BIP32, through normal and hardened children
+        let master = ExtendedKey::master(&hex("000102030405060708090a0b0c0d0e0f")).unwrap();
+
+        let child = master.derive_child(&"m/0'/1".parse().unwrap()).unwrap();
+        assert_eq!(
+            child.key.0.serialize().to_vec(),
+            hex("3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368")
+        );
+        assert_eq!(
+            child.chain_code.to_vec(),
+            hex("2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19")
+        );
+        assert_eq!(
+            child.key_pair().public.0.to_vec(),
+            hex("03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c")
+        );
+
+        let child = master
+            .derive_child(&"m/0'/1/2'/2/1000000000".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            child.key.0.serialize().to_vec(),
+            hex("471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8")
+        );
+        assert_eq!(
+            child.chain_code.to_vec(),
+            hex("c783e67b921d2beb8f6b389cc646d7263b4145701dadd2161548a8b078e65e9e")
+        );
+        assert_eq!(
+            child.key_pair().public.0.to_vec(),
+            hex("022a471424da5e657499d1ff51cb43c47481a03b1e77f951fe64cec9f5a48f7011")
+        );
+    }
+
+    #[test]
+    fn bip32_normal_vector() {
+        // test vector 2 of BIP32, whose first child is normal
+        let master = ExtendedKey::master(&hex(
+            "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a2\
+             9f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542",
+        ))
+        .unwrap();
+
+        let child = master.derive_child(&"m/0".parse().unwrap()).unwrap();
+        assert_eq!(
+            child.key.0.serialize().to_vec(),
+            hex("abe74a98f6c7eabee0428f53798f0ab8aa1bd37873999041703c742f15ac7e1e")
+        );
+        assert_eq!(
+            child.chain_code.to_vec(),
+            hex("f0909affaa7ee7abe5dd4e100598d4dc53cd709d5a5c2cac40e7412f232f7c9c")
+        );
+        assert_eq!(
+            child.key_pair().public.0.to_vec(),
+            hex("02fc9e5af0ac8d9b3cecfe2a888e2117ba3d089d8585886c9c826b6b22a98d12ea")
+        );
+    }
+
+    #[test]
+    fn key_pair() {
+        let pair = KeyPair::generate();
+        let path: DerivationPath = "m/1'/7".parse().unwrap();
+
+        let child = pair.derive_child(&path).unwrap();
+        assert_eq!(child, pair.derive_child(&path).unwrap());
+        assert_eq!(
+            child,
+            pair.extended().derive_child(&path).unwrap().key_pair()
+        );
+        assert_ne!(child.public, pair.public);
+        assert_ne!(
+            child.public,
+            pair.derive_child(&"m/1'/8".parse().unwrap())
+                .unwrap()
+                .public
+        );
+        // the empty path is the pair itself
+        assert_eq!(pair.derive_child(&DerivationPath::default()).unwrap(), pair);
+    }
+
+    #[test]
+    fn parse_path() {
+        let path: DerivationPath = "m/44'/0h/1".parse().unwrap();
+
+        assert_eq!(
+            path.0,
+            vec![
+                ChildNumber::hardened(44),
+                ChildNumber::hardened(0),
+                ChildNumber::normal(1)
+            ]
+        );
+        assert_eq!(path.to_string(), "m/44'/0'/1");
+        assert!("44/1".parse::<DerivationPath>().is_err());
+        assert!("m/x".parse::<DerivationPath>().is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+
+use super::merkle::{hash_leaf, MerkleProof, MerkleTree};
+use super::{HashMsg, KeyTriad, PrivateKey, PublicKey, ToHashMsg};
+
+/// Prefixes the hash of a [`KeysRoot`] before it is signed.
+const KEYS_ROOT_PREFIX: u8 = 7;
+
+/// A set of public keys, committed to by a [`MerkleTree`] over the keys in ascending order.
+///
+/// Because the leaves are sorted, the tree can prove that a key is absent as well as present: a
+/// key is absent if the two adjacent leaves around where it would be are both included.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeySet {
+    keys: Vec<PublicKey>,
+    tree: MerkleTree,
+}
+
+impl KeySet {
+    pub fn new(keys: impl IntoIterator<Item = PublicKey>) -> Self {
+        let mut keys: Vec<_> = keys.into_iter().collect();
+        keys.sort_unstable();
+        keys.dedup();
+        let tree = MerkleTree::from_objects(keys.iter().map(|key| key.0));
+
+        Self { keys, tree }
+    }
+    /// Returns the amount of keys in the set.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+    pub fn contains(&self, key: &PublicKey) -> bool {
+        self.keys.binary_search(key).is_ok()
+    }
+    /// Returns the root of the tree over the keys.
+    pub fn root(&self) -> HashMsg {
+        self.tree.root()
+    }
+    /// Signs the root of the set at the time `time`.
+    pub fn sign(&self, key: &PrivateKey, time: u64) -> KeyTriad<KeysRoot> {
+        let root = KeysRoot {
+            size: self.len() as u64,
+            root: self.root(),
+            time,
+        };
+        KeyTriad::<KeysRoot>::signed_by(key, root)
+    }
+    /// Returns the keys of the set within `range`, in ascending order.
+    pub fn range(&self, range: &KeyRange) -> &[PublicKey] {
+        let start = match &range.lower {
+            Some(lower) => self.keys.partition_point(|key| key < lower),
+            None => 0,
+        };
+        let end = match &range.upper {
+            Some(upper) => self.keys.partition_point(|key| key < upper),
+            None => self.len(),
+        };
+        &self.keys[start..end.max(start)]
+    }
+    /// Returns the fingerprint of the keys of the set within `range`, that is equal for two sets
+    /// if they hold the same keys within it.
+    pub fn fingerprint(&self, range: &KeyRange) -> RangeItem {
+        let keys = self.range(range);
+        RangeItem::Fingerprint {
+            range: *range,
+            count: keys.len() as u64,
+            fingerprint: fingerprint(keys),
+        }
+    }
+    /// Starts reconciling this set with the set of another party. The message is handed to the
+    /// other party, which answers with [`KeySet::reconcile`].
+    pub fn start_reconcile(&self) -> Reconciliation {
+        Reconciliation {
+            items: vec![self.fingerprint(&KeyRange::default())],
+            keys: Vec::new(),
+        }
+    }
+    /// Answers a message of a reconciliation. Returns the answer and the keys the other party
+    /// has that this set lacks. The reconciliation is done once the answer holds no items,
+    /// though the keys of the answer must still be handed to the other party.
+    ///
+    /// Ranges that differ are split in two until they hold at most `leaf_size` keys, after which
+    /// the keys are exchanged. The amount of messages grows with the logarithm of the size of the
+    /// sets, and the size of the messages with the size of their difference.
+    pub fn reconcile(
+        &self,
+        msg: &Reconciliation,
+        leaf_size: usize,
+    ) -> (Reconciliation, Vec<PublicKey>) {
+        let mut learned: Vec<_> = msg
+            .keys
+            .iter()
+            .filter(|key| !self.contains(key))
+            .copied()
+            .collect();
+        let mut answer = Reconciliation::default();
+
+        for item in &msg.items {
+            match item {
+                RangeItem::Fingerprint {
+                    range,
+                    count,
+                    fingerprint: theirs,
+                } => {
+                    let keys = self.range(range);
+                    if keys.len() as u64 == *count && fingerprint(keys) == *theirs {
+                        continue;
+                    }
+
+                    // the other party has nothing to compare the keys to
+                    if keys.len() <= leaf_size.max(1) || *count == 0 {
+                        answer.items.push(RangeItem::Keys {
+                            range: *range,
+                            keys: keys.to_vec(),
+                        });
+                        continue;
+                    }
+
+                    let middle = Some(keys[keys.len() / 2]);
+                    let lower = KeyRange {
+                        upper: middle,
+                        ..*range
+                    };
+                    let upper = KeyRange {
+                        lower: middle,
+                        ..*range
+                    };
+                    answer.items.push(self.fingerprint(&lower));
+                    answer.items.push(self.fingerprint(&upper));
+                }
+                RangeItem::Keys { range, keys } => {
+                    learned.extend(keys.iter().filter(|key| !self.contains(key)));
+                    answer.keys.extend(
+                        self.range(range)
+                            .iter()
+                            .filter(|key| keys.binary_search(key).is_err()),
+                    );
+                }
+            }
+        }
+
+        learned.sort_unstable();
+        learned.dedup();
+        (answer, learned)
+    }
+    /// Returns the proof that `key` is or is not in the set.
+    pub fn prove(&self, key: &PublicKey) -> KeyProof {
+        let neighbor = |index: usize| Neighbor {
+            key: self.keys[index],
+            proof: self.tree.proof(index).expect("neighbors are in the set"),
+        };
+
+        match self.keys.binary_search(key) {
+            Ok(index) => KeyProof::Present {
+                proof: self.tree.proof(index).expect("the key is in the set"),
+            },
+            Err(index) => KeyProof::Absent {
+                lower: index.checked_sub(1).map(neighbor),
+                upper: (index < self.len()).then(|| neighbor(index)),
+            },
+        }
+    }
+}
+
+/// XORs the hashes of `keys`, so that the fingerprint does not depend on how the keys are split
+/// into ranges.
+fn fingerprint(keys: &[PublicKey]) -> HashMsg {
+    let mut fingerprint = [0u8; 32];
+    for key in keys {
+        let hash = hash_leaf(key.0);
+        for (byte, other) in fingerprint.iter_mut().zip(hash.0) {
+            *byte ^= other;
+        }
+    }
+    HashMsg(fingerprint)
+}
+
+/// A range of public keys. Both bounds are unbounded if [`None`].
+#[derive(
+    Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct KeyRange {
+    /// The smallest key in the range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lower: Option<PublicKey>,
+    /// The smallest key after the range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upper: Option<PublicKey>,
+}
+
+/// What a party of a reconciliation holds within a range of keys.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(tag = "type")]
+pub enum RangeItem {
+    /// The amount of keys within the range, and their fingerprint.
+    #[serde(rename = "FINGERPRINT", alias = "fingerprint")]
+    Fingerprint {
+        range: KeyRange,
+        count: u64,
+        fingerprint: HashMsg,
+    },
+    /// Every key within the range, in ascending order.
+    #[serde(rename = "KEYS", alias = "keys")]
+    Keys {
+        range: KeyRange,
+        keys: Vec<PublicKey>,
+    },
+}
+
+/// A message of a reconciliation of two [`KeySet`]s, that leaves both parties knowing the union
+/// of the sets.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Reconciliation {
+    /// The ranges the receiver must compare to its own set.
+    pub items: Vec<RangeItem>,
+    /// The keys the receiver lacks, as found by comparing the keys of a range it sent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<PublicKey>,
+}
+
+impl Reconciliation {
+    /// Returns the amount of ranges and keys this message holds, counting the keys within the
+    /// ranges.
+    pub fn size(&self) -> usize {
+        let keys: usize = self
+            .items
+            .iter()
+            .map(|item| match item {
+                RangeItem::Fingerprint { .. } => 0,
+                RangeItem::Keys { keys, .. } => keys.len(),
+            })
+            .sum();
+        self.items.len() + keys + self.keys.len()
+    }
+}
+
+/// A commitment to the contents of a [`KeySet`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeysRoot {
+    /// The amount of keys in the set.
+    pub size: u64,
+    pub root: HashMsg,
+    /// The time the root was signed.
+    pub time: u64,
+}
+
+impl ToHashMsg for &KeysRoot {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[KEYS_ROOT_PREFIX]);
+        hasher.update(&self.size.to_be_bytes());
+        hasher.update(&self.root.0);
+        hasher.update(&self.time.to_be_bytes());
+        HashMsg(hasher.finalize().into())
+    }
+}
+
+/// A key of a [`KeySet`] next to where an absent key would be, and the proof that it is included.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Neighbor {
+    pub key: PublicKey,
+    pub proof: MerkleProof,
+}
+
+impl Neighbor {
+    fn included(&self, root: &KeysRoot) -> bool {
+        self.proof.leaves == root.size && self.proof.root(hash_leaf(self.key.0)) == Some(root.root)
+    }
+}
+
+/// A proof that a key is or is not in a [`KeySet`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(tag = "type")]
+pub enum KeyProof {
+    #[serde(rename = "PRESENT", alias = "present")]
+    Present { proof: MerkleProof },
+    /// The key is absent. `lower` and `upper` are the keys right before and after where the key
+    /// would be, if there are any.
+    #[serde(rename = "ABSENT", alias = "absent")]
+    Absent {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lower: Option<Neighbor>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        upper: Option<Neighbor>,
+    },
+}
+
+impl KeyProof {
+    /// Checks the proof for `key` against `root`. Returns whether the key is in the set, or
+    /// [`None`] if the proof is invalid.
+    ///
+    /// The proof relies on the signer of the root having sorted the keys, so it is only as
+    /// trustworthy as the signer.
+    pub fn verify(&self, key: &PublicKey, root: &KeysRoot) -> Option<bool> {
+        match self {
+            Self::Present { proof } => {
+                let valid =
+                    proof.leaves == root.size && proof.root(hash_leaf(key.0)) == Some(root.root);
+                valid.then_some(true)
+            }
+            Self::Absent { lower, upper } => {
+                let valid = match (lower, upper) {
+                    (None, None) => root.size == 0,
+                    (Some(lower), None) => {
+                        lower.proof.index + 1 == root.size
+                            && lower.key < *key
+                            && lower.included(root)
+                    }
+                    (None, Some(upper)) => {
+                        upper.proof.index == 0 && *key < upper.key && upper.included(root)
+                    }
+                    (Some(lower), Some(upper)) => {
+                        lower.proof.index + 1 == upper.proof.index
+                            && lower.key < *key
+                            && *key < upper.key
+                            && lower.included(root)
+                            && upper.included(root)
+                    }
+                };
+                valid.then_some(false)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn reconcile() {
+        let keys: Vec<_> = (0..300).map(|_| KeyPair::generate().public).collect();
+        let a = KeySet::new(keys[..250].iter().copied());
+        let b = KeySet::new(keys[10..260].iter().chain(&keys[270..]).copied());
+
+        let (mut a_learned, mut b_learned) = (Vec::new(), Vec::new());
+        let mut msg = a.start_reconcile();
+        let mut rounds = 0;
+        loop {
+            let (answer, learned) = b.reconcile(&msg, 8);
+            b_learned.extend(learned);
+            let (next, learned) = a.reconcile(&answer, 8);
+            a_learned.extend(learned);
+            rounds += 1;
+            if next.items.is_empty() {
+                b_learned.extend(b.reconcile(&next, 8).1);
+                break;
+            }
+            msg = next;
+        }
+        assert!(rounds < 10);
+
+        a_learned.sort();
+        b_learned.sort();
+        let mut expected: Vec<_> = keys[250..260].iter().chain(&keys[270..]).copied().collect();
+        expected.sort();
+        assert_eq!(a_learned, expected);
+        let mut expected = keys[..10].to_vec();
+        expected.sort();
+        assert_eq!(b_learned, expected);
+
+        // equal sets agree after one message
+        let (answer, learned) = a.reconcile(&a.start_reconcile(), 8);
+        assert_eq!((answer, learned), (Reconciliation::default(), Vec::new()));
+    }
+
+    #[test]
+    fn key_proofs() {
+        let keys: Vec<_> = (0..7).map(|_| KeyPair::generate().public).collect();
+        let set = KeySet::new(keys[..5].iter().copied());
+        let signer = KeyPair::generate();
+        let root = set.sign(&signer.private, 0);
+        assert!(root.valid());
+
+        for key in &keys[..5] {
+            assert_eq!(set.prove(key).verify(key, &root.signed), Some(true));
+        }
+        for key in &keys[5..] {
+            assert_eq!(set.prove(key).verify(key, &root.signed), Some(false));
+            // a proof of absence does not hold for another key
+            assert_eq!(set.prove(key).verify(&keys[0], &root.signed), None);
+        }
+        // a proof of presence does not hold for a key that is absent
+        assert_eq!(set.prove(&keys[0]).verify(&keys[5], &root.signed), None);
+
+        // the smallest and largest keys only have one neighbor
+        let mut sorted = keys[..5].to_vec();
+        sorted.sort();
+        let mut below = sorted[0];
+        below.0[1..].fill(0);
+        let mut above = sorted[4];
+        above.0[1..].fill(u8::MAX);
+        for key in [below, above] {
+            if !set.contains(&key) {
+                assert_eq!(set.prove(&key).verify(&key, &root.signed), Some(false));
+            }
+        }
+
+        let empty = KeySet::new([]);
+        let root = empty.sign(&signer.private, 0);
+        assert_eq!(
+            empty.prove(&keys[0]).verify(&keys[0], &root.signed),
+            Some(false)
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+use super::{HashMsg, KeyTriad, PrivateKey, ToHashMsg, HASH_SIZE};
+
+/// Prefixes the hash of a link of the chain.
+const CHAIN_PREFIX: u8 = 2;
+/// Prefixes the hash of a [`LogHead`] before it is signed.
+const HEAD_PREFIX: u8 = 3;
+/// Prefixes the hash of a signed [`LogHead`] before it is cosigned.
+const COSIGN_PREFIX: u8 = 4;
+
+/// The head of an empty log.
+pub const EMPTY_HEAD: HashMsg = HashMsg([0u8; HASH_SIZE]);
+
+fn chain(head: &HashMsg, leaf: &HashMsg) -> HashMsg {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[CHAIN_PREFIX]);
+    hasher.update(&head.0);
+    hasher.update(&leaf.0);
+    HashMsg(hasher.finalize().into())
+}
+
+/// An append-only log of leaf hashes, where every head commits to all the leaves before it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TransparencyLog {
+    leaves: Vec<HashMsg>,
+    /// The head of the log after each amount of leaves, starting with [`EMPTY_HEAD`].
+    heads: Vec<HashMsg>,
+}
+
+impl Default for TransparencyLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransparencyLog {
+    pub fn new() -> Self {
+        Self {
+            leaves: Vec::new(),
+            heads: vec![EMPTY_HEAD],
+        }
+    }
+    /// Returns the amount of leaves in the log.
+    pub fn len(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+    /// Returns the current head of the log.
+    pub fn head(&self) -> HashMsg {
+        *self
+            .heads
+            .last()
+            .expect("the log holds the head of the empty log")
+    }
+    /// Returns the head of the log when it had `size` leaves.
+    pub fn head_at(&self, size: u64) -> Option<HashMsg> {
+        self.heads.get(usize::try_from(size).ok()?).copied()
+    }
+    /// Appends a leaf to the log, returning its index.
+    pub fn append(&mut self, leaf: HashMsg) -> u64 {
+        let head = chain(&self.head(), &leaf);
+        self.leaves.push(leaf);
+        self.heads.push(head);
+
+        self.len() - 1
+    }
+    /// Returns the proof that the log grew from `from` leaves to `to` leaves by appending only.
+    ///
+    /// The first leaf of the proof is the leaf at index `from`, so this is also the proof that the
+    /// leaf is included in the log of size `to`.
+    pub fn proof(&self, from: u64, to: u64) -> Option<LogProof> {
+        if from > to || to > self.len() {
+            return None;
+        }
+
+        Some(LogProof {
+            from,
+            start: self.head_at(from)?,
+            leaves: self.leaves[from as usize..to as usize].to_vec(),
+        })
+    }
+    /// Signs the current head of the log at the time `time`.
+    pub fn sign(&self, key: &PrivateKey, time: u64) -> KeyTriad<LogHead> {
+        let head = LogHead {
+            size: self.len(),
+            head: self.head(),
+            time,
+        };
+        KeyTriad::<LogHead>::signed_by(key, head)
+    }
+    /// Signs the head of the log when it had `size` leaves at the time `time`, if it ever had
+    /// that many.
+    pub fn sign_at(&self, size: u64, key: &PrivateKey, time: u64) -> Option<KeyTriad<LogHead>> {
+        let head = LogHead {
+            size,
+            head: self.head_at(size)?,
+            time,
+        };
+
+        Some(KeyTriad {
+            public_key: key.derive_public(),
+            signature: key.sign(&head),
+            signed: head,
+        })
+    }
+}
+
+/// A commitment to the contents of a [`TransparencyLog`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LogHead {
+    /// The amount of leaves in the log.
+    pub size: u64,
+    pub head: HashMsg,
+    /// The time the head was signed.
+    pub time: u64,
+}
+
+impl ToHashMsg for &LogHead {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[HEAD_PREFIX]);
+        hasher.update(&self.size.to_be_bytes());
+        hasher.update(&self.head.0);
+        hasher.update(&self.time.to_be_bytes());
+        HashMsg(hasher.finalize().into())
+    }
+}
+
+impl KeyTriad<LogHead> {
+    /// Countersigns this head, attesting that the signer has seen no head of the same log that
+    /// this head does not extend.
+    pub fn cosign(&self, key: &PrivateKey) -> KeyTriad<Self> {
+        KeyTriad::<Self>::signed_by(key, *self)
+    }
+}
+
+impl ToHashMsg for &KeyTriad<LogHead> {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[COSIGN_PREFIX]);
+        hasher.update(&self.public_key.0);
+        hasher.update(&self.signature.0);
+        hasher.update(&(&self.signed).to_hash_msg().0);
+        HashMsg(hasher.finalize().into())
+    }
+}
+
+impl KeyTriad<KeyTriad<LogHead>> {
+    /// Returns whether both the head and the cosignature over it are valid. [`KeyTriad::valid`]
+    /// only checks the cosignature.
+    pub fn valid_cosigned(&self) -> bool {
+        self.signed.valid() && self.valid()
+    }
+}
+
+/// A proof that a [`TransparencyLog`] only had leaves appended between two of its sizes.
+///
+/// The proof contains every leaf appended in between, so its size grows with the distance between
+/// the two heads.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LogProof {
+    /// The size of the log the proof starts at.
+    pub from: u64,
+    /// The head of the log at the size `from`.
+    pub start: HashMsg,
+    /// The leaves appended after `from`.
+    pub leaves: Vec<HashMsg>,
+}
+
+impl LogProof {
+    /// Returns the size of the log the proof ends at.
+    pub fn to(&self) -> u64 {
+        self.from + self.leaves.len() as u64
+    }
+    /// Computes the head of the log the proof ends at.
+    pub fn end(&self) -> HashMsg {
+        self.leaves
+            .iter()
+            .fold(self.start, |head, leaf| chain(&head, leaf))
+    }
+    /// Computes the head of the log when it had `size` leaves, if the proof covers it.
+    pub fn head_at(&self, size: u64) -> Option<HashMsg> {
+        if size < self.from || size > self.to() {
+            return None;
+        }
+
+        Some(
+            self.leaves[..(size - self.from) as usize]
+                .iter()
+                .fold(self.start, |head, leaf| chain(&head, leaf)),
+        )
+    }
+    /// Returns whether `new` is the head of a log that `old` is a prefix of.
+    pub fn extends(&self, old: &LogHead, new: &LogHead) -> bool {
+        self.from == old.size
+            && self.start == old.head
+            && self.to() == new.size
+            && self.end() == new.head
+    }
+    /// Returns whether `leaf` is the leaf at index `from` of the log with the head `head`.
+    pub fn includes(&self, leaf: &HashMsg, head: &LogHead) -> bool {
+        self.leaves.first() == Some(leaf) && self.to() == head.size && self.end() == head.head
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{merkle::hash_leaf, KeyPair};
+
+    #[test]
+    fn proofs() {
+        let pair = KeyPair::generate();
+        let mut log = TransparencyLog::new();
+
+        for i in 0u8..4 {
+            log.append(hash_leaf([i]));
+        }
+        let old = log.sign(&pair.private, 0);
+        for i in 4u8..9 {
+            log.append(hash_leaf([i]));
+        }
+        let new = log.sign(&pair.private, 1);
+        assert!(new.public_key.valid(&new.signed, &new.signature));
+
+        let proof = log.proof(old.signed.size, new.signed.size).unwrap();
+        assert!(proof.extends(&old.signed, &new.signed));
+        assert!(proof.includes(&hash_leaf([4]), &new.signed));
+        assert!(!proof.includes(&hash_leaf([5]), &new.signed));
+
+        // a log that rewrote an old leaf does not extend the old head
+        let mut forked = TransparencyLog::new();
+        for i in 0u8..9 {
+            forked.append(hash_leaf([i + (i == 2) as u8]));
+        }
+        let forked_head = forked.sign(&pair.private, 1);
+        let proof = forked
+            .proof(old.signed.size, forked_head.signed.size)
+            .unwrap();
+        assert!(!proof.extends(&old.signed, &forked_head.signed));
+        assert!(log.proof(3, 10).is_none());
+
+        // a witness can tell whether the head it cosigned before is part of the log
+        let proof = log.proof(2, new.signed.size).unwrap();
+        assert_eq!(proof.head_at(old.signed.size), Some(old.signed.head));
+        assert_ne!(
+            forked.proof(2, 9).unwrap().head_at(old.signed.size),
+            Some(old.signed.head)
+        );
+
+        let witness = KeyPair::generate();
+        let cosigned = new.cosign(&witness.private);
+        assert!(cosigned.valid_cosigned());
+        assert!(!KeyTriad {
+            signed: old,
+            ..cosigned
+        }
+        .valid_cosigned());
+    }
+}
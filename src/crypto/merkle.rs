@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+use super::{HashMsg, KeyTriad, PrivateKey, PublicKey, Signature, HASH_SIZE};
+
+/// Prefixes the hash of a leaf, so a leaf can never be confused with an inner node.
+const LEAF_PREFIX: u8 = 0;
+/// Prefixes the hash of an inner node.
+const NODE_PREFIX: u8 = 1;
+
+/// Hashes a leaf of a Merkle tree.
+pub fn hash_leaf(bytes: impl AsRef<[u8]>) -> HashMsg {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(bytes.as_ref());
+    HashMsg(hasher.finalize().into())
+}
+
+fn hash_node(left: &HashMsg, right: &HashMsg) -> HashMsg {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(&left.0);
+    hasher.update(&right.0);
+    HashMsg(hasher.finalize().into())
+}
+
+/// A Merkle tree over a list of leaf hashes. A node without a sibling is carried up to the next
+/// level unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MerkleTree {
+    /// The levels of the tree, from the leaves to the root.
+    levels: Vec<Vec<HashMsg>>,
+}
+
+impl MerkleTree {
+    /// Builds a tree from leaf hashes, which should be created using [`hash_leaf`].
+    pub fn new(leaves: Vec<HashMsg>) -> Self {
+        let mut levels = vec![leaves];
+
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Self { levels }
+    }
+    /// Builds a tree over objects, hashing each one as a leaf.
+    pub fn from_objects<T: AsRef<[u8]>>(objects: impl IntoIterator<Item = T>) -> Self {
+        Self::new(objects.into_iter().map(hash_leaf).collect())
+    }
+    /// Returns the amount of leaves in this tree.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the root of this tree. The root of an empty tree is all zeroes.
+    pub fn root(&self) -> HashMsg {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or(HashMsg([0u8; HASH_SIZE]))
+    }
+    /// Returns the proof that the leaf at `index` is included in this tree.
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut siblings = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                siblings.push(*hash);
+            }
+            position /= 2;
+        }
+
+        Some(MerkleProof {
+            index: index as u64,
+            leaves: self.len() as u64,
+            siblings,
+        })
+    }
+    /// Signs the root of this tree, so that every leaf can be attested with one signature.
+    pub fn sign(&self, key: &PrivateKey) -> KeyTriad<HashMsg> {
+        let root = self.root();
+
+        KeyTriad {
+            public_key: key.derive_public(),
+            signature: key.sign(root),
+            signed: root,
+        }
+    }
+}
+
+/// A proof that a leaf is included in a [`MerkleTree`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MerkleProof {
+    /// The index of the leaf.
+    pub index: u64,
+    /// The amount of leaves in the tree.
+    pub leaves: u64,
+    /// The hashes of the siblings on the path from the leaf to the root.
+    pub siblings: Vec<HashMsg>,
+}
+
+impl MerkleProof {
+    /// Computes the root of the tree from the hash of the leaf. Returns [`None`] if the proof is
+    /// malformed.
+    pub fn root(&self, leaf: HashMsg) -> Option<HashMsg> {
+        if self.index >= self.leaves {
+            return None;
+        }
+
+        let mut siblings = self.siblings.iter();
+        let (mut hash, mut position, mut width) = (leaf, self.index, self.leaves);
+
+        while width > 1 {
+            let sibling = position ^ 1;
+            if sibling < width {
+                let sibling = siblings.next()?;
+                hash = match position % 2 {
+                    0 => hash_node(&hash, sibling),
+                    _ => hash_node(sibling, &hash),
+                };
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        match siblings.next() {
+            Some(_) => None,
+            None => Some(hash),
+        }
+    }
+    /// Returns whether the leaf is included in the tree whose root was signed by `public_key`.
+    pub fn valid(&self, leaf: HashMsg, public_key: &PublicKey, signature: &Signature) -> bool {
+        match self.root(leaf) {
+            Some(root) => public_key.valid(root, signature),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn batch_proofs() {
+        let objects: Vec<_> = (0u8..7).map(|i| vec![i; 4]).collect();
+        let tree = MerkleTree::from_objects(&objects);
+        let pair = KeyPair::generate();
+        let triad = tree.sign(&pair.private);
+
+        for (index, object) in objects.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            assert!(proof.valid(hash_leaf(object), &triad.public_key, &triad.signature));
+            assert!(!proof.valid(hash_leaf(b"other"), &triad.public_key, &triad.signature));
+        }
+        assert!(tree.proof(7).is_none());
+    }
+}
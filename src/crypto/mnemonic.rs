@@ -0,0 +1,71 @@
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+pub use bip39::{Language, Mnemonic};
+
+use super::{error::MnemonicError, hd::ExtendedKey, KeyPair};
+
+/// The size (in bytes) of a seed derived from a mnemonic phrase.
+pub const SEED_SIZE: usize = 64;
+
+/// Generates a new English mnemonic phrase with `word_count` words (12, 15, 18, 21 or 24).
+pub fn generate(
+    rng: &mut (impl RngCore + CryptoRng),
+    word_count: usize,
+) -> Result<Mnemonic, MnemonicError> {
+    let mut entropy = Zeroizing::new([0u8; 32]);
+    rng.fill_bytes(&mut entropy[..]);
+
+    // every 3 words hold 32 bits of entropy
+    let len = (word_count / 3) * 4;
+    if !word_count.is_multiple_of(3) || !(16..=32).contains(&len) {
+        return Err(bip39::Error::BadWordCount(word_count).into());
+    }
+
+    Ok(Mnemonic::from_entropy(&entropy[..len])?)
+}
+
+/// Parses a mnemonic phrase, and derives a seed from it and the passphrase.
+pub fn to_seed(
+    phrase: &str,
+    passphrase: &str,
+) -> Result<Zeroizing<[u8; SEED_SIZE]>, MnemonicError> {
+    let mnemonic = Mnemonic::parse(phrase)?;
+
+    Ok(Zeroizing::new(mnemonic.to_seed(passphrase)))
+}
+
+impl KeyPair {
+    /// Derives a keypair from a BIP39 mnemonic phrase and a passphrase. The same phrase and
+    /// passphrase always derive the same keypair.
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self, MnemonicError> {
+        Ok(ExtendedKey::from_mnemonic(phrase, passphrase)?.key_pair())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mnemonic = generate(&mut StdRng::seed_from_u64(3), 24).unwrap();
+        let phrase = mnemonic.to_string();
+
+        let first = KeyPair::from_mnemonic(&phrase, "passphrase").unwrap();
+        let second = KeyPair::from_mnemonic(&phrase, "passphrase").unwrap();
+        let other = KeyPair::from_mnemonic(&phrase, "").unwrap();
+
+        assert_eq!(mnemonic.word_count(), 24);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn invalid_phrase() {
+        assert!(KeyPair::from_mnemonic("not a mnemonic", "").is_err());
+        assert!(generate(&mut StdRng::seed_from_u64(3), 13).is_err());
+    }
+}
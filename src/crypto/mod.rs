@@ -1,9 +1,43 @@
 use std::sync::Arc;
 
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-
-use crate::obj::{IdentifyData, SignMessageType, Signable, SignedData};
+use sha2::Digest;
+use subtle::{Choice, ConstantTimeEq};
+
+#[cfg(feature = "bench-internals")]
+#[doc(hidden)]
+pub mod bench;
+pub mod cache;
+pub mod delegation;
+pub mod ecdh;
+mod encoding;
+pub mod encrypted;
+pub mod error;
+pub mod hd;
+pub mod keyset;
+pub mod log;
+pub mod merkle;
+pub mod mnemonic;
+pub mod multi;
+pub mod pool;
+pub mod recover;
+pub mod schnorr;
+pub mod sealed;
+pub mod signer;
+pub mod stream;
+pub mod threshold;
+pub mod token;
+
+use crate::obj::{
+    to_canonical_cbor, Features, Revocation, SignMessageType, Signable, SignedData, SignedFormat,
+};
+pub(crate) use encoding::HexOrBytes;
+use error::*;
+use schnorr::{SchnorrSignature, SignatureScheme};
+pub use sealed::{seal, unseal};
+use signer::Signer;
 
 /// The size (in bytes) of a public key.
 pub const PUBLIC_KEY_SIZE: usize = 33;
@@ -17,45 +51,265 @@ pub const HASH_SIZE: usize = 32;
 /// The size (in bytes) of a signature.
 pub const SIGNATURE_SIZE: usize = 64;
 
+/// The size (in bytes) of the key of a keyed hash.
+pub const MAC_KEY_SIZE: usize = 32;
+
+/// The size (in bytes) of a message authentication code.
+pub const MAC_SIZE: usize = 32;
+
 /// Computes the hash of a value
 pub fn hash(bytes: impl AsRef<[u8]>) -> HashMsg {
     HashMsg(blake3::hash(bytes.as_ref()).into())
 }
 
+/// Computes the hash of a value keyed with `key`. Only holders of the key can compute or check the
+/// hash.
+pub fn keyed_hash(key: &[u8; MAC_KEY_SIZE], bytes: impl AsRef<[u8]>) -> HashMsg {
+    HashMsg(blake3::keyed_hash(key, bytes.as_ref()).into())
+}
+
+/// A message authentication code, that authenticates a message much more cheaply than a signature
+/// when both sides share a key.
+///
+/// Codes are compared in constant time.
+#[repr(transparent)]
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct Mac(#[serde_as(as = "HexOrBytes")] pub [u8; MAC_SIZE]);
+
+impl std::hash::Hash for Mac {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+impl PartialEq for Mac {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl Eq for Mac {}
+
+impl Mac {
+    /// Computes the code of `bytes` with `key`.
+    pub fn new(key: &[u8; MAC_KEY_SIZE], bytes: impl AsRef<[u8]>) -> Self {
+        Self(keyed_hash(key, bytes).0)
+    }
+    /// Returns whether this is the code of `bytes` with `key`. The comparison takes constant time.
+    pub fn verify(&self, key: &[u8; MAC_KEY_SIZE], bytes: impl AsRef<[u8]>) -> bool {
+        *self == Self::new(key, bytes)
+    }
+}
+
+/// Implements [`ConstantTimeEq`] for a wrapper of a byte array.
+macro_rules! ct_eq_impl {
+    ($for:ty) => {
+        impl ConstantTimeEq for $for {
+            fn ct_eq(&self, other: &Self) -> Choice {
+                self.0.ct_eq(&other.0)
+            }
+        }
+    };
+}
+ct_eq_impl!(Mac);
+ct_eq_impl!(Signature);
+ct_eq_impl!(PublicKey);
+ct_eq_impl!(HashMsg);
+
+/// The digest a signed message is hashed with before it is signed.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum HashAlgorithm {
+    #[default]
+    #[serde(rename = "BLAKE3", alias = "blake3")]
+    Blake3,
+    /// SHA-256, for deployments that must use FIPS-approved hashes.
+    #[serde(rename = "SHA256", alias = "sha256")]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Computes the hash of a value with this algorithm.
+    pub fn hash(self, bytes: impl AsRef<[u8]>) -> HashMsg {
+        match self {
+            HashAlgorithm::Blake3 => hash(bytes),
+            HashAlgorithm::Sha256 => HashMsg(sha2::Sha256::digest(bytes.as_ref()).into()),
+        }
+    }
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Batches smaller than this are verified on the calling thread.
+const BATCH_PARALLEL_THRESHOLD: usize = 8;
+
+/// Verifies a batch of signatures, returning `true` only if every signature is valid.
+///
+/// Large batches are split across the available cores instead of being verified one after the
+/// other. This blocks the calling thread until every signature is verified; async code should
+/// call [`spawn_verify_batch`](pool::spawn_verify_batch) instead.
+pub fn verify_batch(batch: &[(PublicKey, SignedHash, Signature)]) -> bool {
+    let verify = |items: &[(PublicKey, SignedHash, Signature)]| {
+        items
+            .iter()
+            .all(|(public_key, msg, signature)| public_key.valid(*msg, signature))
+    };
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if batch.len() < BATCH_PARALLEL_THRESHOLD || threads == 1 {
+        return verify(batch);
+    }
+
+    let chunk_size = batch.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = batch
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || verify(chunk)))
+            .collect();
+
+        handles
+            .into_iter()
+            .all(|handle| handle.join().unwrap_or(false))
+    })
+}
+
 /// A signature.
 #[repr(transparent)]
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(transparent)]
-pub struct Signature(#[serde_as(as = "[_; SIGNATURE_SIZE]")] pub [u8; SIGNATURE_SIZE]);
+pub struct Signature(#[serde_as(as = "HexOrBytes")] pub [u8; SIGNATURE_SIZE]);
+
+/// How strictly signatures are parsed before they are verified.
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SignatureParsing {
+    /// Only the canonical encoding of a signature is accepted, so that every message signed by
+    /// a key has a single valid signature. Signatures can then be used as unique identifiers.
+    #[default]
+    Strict,
+    /// Signatures whose values overflow the curve order, or whose `s` value is high, are also
+    /// accepted.
+    Lax,
+}
+
+impl Signature {
+    /// Converts this signature to a [`libsecp256k1::Signature`], parsed according to `parsing`.
+    pub(crate) fn to_secp(
+        self,
+        parsing: SignatureParsing,
+    ) -> Result<libsecp256k1::Signature, VerifyError> {
+        let signature = match parsing {
+            SignatureParsing::Strict => libsecp256k1::Signature::parse_standard(&self.0)
+                .map_err(|_| VerifyError::InvalidSignature)?,
+            SignatureParsing::Lax => libsecp256k1::Signature::parse_overflowing(&self.0),
+        };
+
+        if signature.r.is_zero() || signature.s.is_zero() {
+            return Err(VerifyError::InvalidSignature);
+        }
+        if parsing == SignatureParsing::Strict && signature.s.is_high() {
+            return Err(VerifyError::NonCanonical);
+        }
+
+        Ok(signature)
+    }
+    /// Returns whether this is the canonical encoding of a signature.
+    pub fn is_canonical(&self) -> bool {
+        self.to_secp(SignatureParsing::Strict).is_ok()
+    }
+    /// Returns the canonical encoding of this signature, which verifies for the same keys and
+    /// messages.
+    pub fn normalize(&self) -> Self {
+        let mut signature = libsecp256k1::Signature::parse_overflowing(&self.0);
+        signature.normalize_s();
+
+        Self(signature.serialize())
+    }
+}
 
 /// A public key.
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(transparent)]
-pub struct PublicKey(#[serde_as(as = "[_; PUBLIC_KEY_SIZE]")] pub [u8; PUBLIC_KEY_SIZE]);
+pub struct PublicKey(#[serde_as(as = "HexOrBytes")] pub [u8; PUBLIC_KEY_SIZE]);
 
 impl PublicKey {
+    /// Parses a compressed public key, checking that it is a valid point on the curve.
+    pub fn parse(bytes: &[u8]) -> Result<Self, KeyParseError> {
+        let bytes: [u8; PUBLIC_KEY_SIZE] =
+            bytes.try_into().map_err(|_| KeyParseError::InvalidLength {
+                expected: PUBLIC_KEY_SIZE,
+                received: bytes.len(),
+            })?;
+        let key = Self(bytes);
+        key.to_secp()?;
+
+        Ok(key)
+    }
+    /// Converts this public key to a [`libsecp256k1::PublicKey`].
+    pub(crate) fn to_secp(self) -> Result<libsecp256k1::PublicKey, KeyParseError> {
+        libsecp256k1::PublicKey::parse_compressed(&self.0)
+            .map_err(|_| KeyParseError::InvalidPublicKey)
+    }
     pub fn valid(&self, msg: impl ToHashMsg, signature: &Signature) -> bool {
-        let pubkey = match libsecp256k1::PublicKey::parse_compressed(&self.0) {
-            Ok(value) => value,
-            _ => return false,
-        };
-
+        self.verify(msg, signature).is_ok()
+    }
+    /// Like [`PublicKey::valid`], but returns why the signature failed to verify.
+    pub fn verify(&self, msg: impl ToHashMsg, signature: &Signature) -> Result<(), VerifyError> {
+        self.verify_with(msg, signature, SignatureParsing::Strict)
+    }
+    /// Like [`PublicKey::verify`], but parses the signature according to `parsing`.
+    pub fn verify_with(
+        &self,
+        msg: impl ToHashMsg,
+        signature: &Signature,
+        parsing: SignatureParsing,
+    ) -> Result<(), VerifyError> {
+        let scheme = msg.signature_scheme();
         let hashmsg = msg.to_hash_msg();
+        if scheme == SignatureScheme::Schnorr {
+            return self.verify_schnorr(*hashmsg.as_ref(), &SchnorrSignature(signature.0));
+        }
+
+        let pubkey = self.to_secp().map_err(|_| VerifyError::InvalidPublicKey)?;
         let msg = libsecp256k1::Message::parse(&hashmsg.as_ref().0);
-        let signature = libsecp256k1::Signature::parse_overflowing(&signature.0);
+        let signature = signature.to_secp(parsing)?;
 
-        libsecp256k1::verify(&msg, &signature, &pubkey)
+        match libsecp256k1::verify(&msg, &signature, &pubkey) {
+            true => Ok(()),
+            false => Err(VerifyError::Mismatch),
+        }
     }
 }
 
 /// A private key.
+///
+/// Keys are compared in constant time.
 #[repr(transparent)]
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(try_from = "[u8; PRIVATE_KEY_SIZE]", into = "[u8; PRIVATE_KEY_SIZE]")]
 pub struct PrivateKey(pub libsecp256k1::SecretKey);
 
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PrivateKey(..)")
+    }
+}
+
+impl ConstantTimeEq for PrivateKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.serialize().ct_eq(&other.0.serialize())
+    }
+}
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl Eq for PrivateKey {}
+
 impl std::hash::Hash for PrivateKey {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.0.serialize().hash(state);
@@ -75,8 +329,22 @@ impl From<PrivateKey> for [u8; PRIVATE_KEY_SIZE] {
 }
 
 impl PrivateKey {
-    pub fn new(bytes: [u8; PRIVATE_KEY_SIZE]) -> Self {
-        Self::try_from(bytes).unwrap()
+    pub fn new(bytes: [u8; PRIVATE_KEY_SIZE]) -> Result<Self, KeyParseError> {
+        Self::try_from(bytes).map_err(|_| KeyParseError::InvalidPrivateKey)
+    }
+    /// Generates a new private key using the provided cryptographically secure RNG.
+    pub fn generate(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        Self(libsecp256k1::SecretKey::random(rng))
+    }
+    /// Parses a private key, checking that it is within the range of the curve order.
+    pub fn parse(bytes: &[u8]) -> Result<Self, KeyParseError> {
+        let bytes: [u8; PRIVATE_KEY_SIZE] =
+            bytes.try_into().map_err(|_| KeyParseError::InvalidLength {
+                expected: PRIVATE_KEY_SIZE,
+                received: bytes.len(),
+            })?;
+
+        Self::try_from(bytes).map_err(|_| KeyParseError::InvalidPrivateKey)
     }
     pub fn derive_public(&self) -> PublicKey {
         PublicKey(libsecp256k1::PublicKey::from_secret_key(&self.0).serialize_compressed())
@@ -94,20 +362,37 @@ pub struct KeyPair {
     pub public: PublicKey,
     pub private: PrivateKey,
 }
+impl From<PrivateKey> for KeyPair {
+    fn from(private: PrivateKey) -> Self {
+        Self {
+            public: private.derive_public(),
+            private,
+        }
+    }
+}
 impl KeyPair {
+    /// Generates a new keypair using the thread-local RNG.
+    pub fn generate() -> Self {
+        Self::generate_with(&mut crate::utils::rng())
+    }
+    /// Generates a new keypair using the provided cryptographically secure RNG.
+    pub fn generate_with(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        PrivateKey::generate(rng).into()
+    }
     pub fn derive_public(&self) -> PublicKey {
         self.public
     }
     pub fn sign(&self, msg: impl ToHashMsg) -> Signature {
-        (&self.private).sign(msg)
+        self.private.sign(msg)
     }
 }
 
 /// A message that can be signed, or verified. Is typically a hash of a value.
 #[repr(transparent)]
+#[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(transparent)]
-pub struct HashMsg(pub [u8; HASH_SIZE]);
+pub struct HashMsg(#[serde_as(as = "HexOrBytes")] pub [u8; HASH_SIZE]);
 
 impl AsRef<HashMsg> for HashMsg {
     fn as_ref(&self) -> &HashMsg {
@@ -119,6 +404,53 @@ pub trait ToHashMsg {
     type Output: AsRef<HashMsg>;
 
     fn to_hash_msg(self) -> Self::Output;
+    /// Returns the scheme the message is signed with. Only signed payloads declare another
+    /// scheme than ECDSA.
+    fn signature_scheme(&self) -> SignatureScheme {
+        SignatureScheme::Ecdsa
+    }
+}
+
+/// A hash, and the scheme of the signature over it.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SignedHash {
+    pub hash: HashMsg,
+    pub scheme: SignatureScheme,
+}
+
+impl SignedHash {
+    /// Hashes `msg`, keeping the scheme it is signed with.
+    pub fn of(msg: impl ToHashMsg) -> Self {
+        let scheme = msg.signature_scheme();
+        Self {
+            hash: *msg.to_hash_msg().as_ref(),
+            scheme,
+        }
+    }
+}
+
+impl From<HashMsg> for SignedHash {
+    fn from(hash: HashMsg) -> Self {
+        Self {
+            hash,
+            scheme: SignatureScheme::Ecdsa,
+        }
+    }
+}
+impl AsRef<HashMsg> for SignedHash {
+    fn as_ref(&self) -> &HashMsg {
+        &self.hash
+    }
+}
+impl ToHashMsg for SignedHash {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        self.hash
+    }
+    fn signature_scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
 }
 
 impl ToHashMsg for HashMsg {
@@ -137,6 +469,9 @@ where
     fn to_hash_msg(self) -> Self::Output {
         (*self).to_hash_msg()
     }
+    fn signature_scheme(&self) -> SignatureScheme {
+        (**self).signature_scheme()
+    }
 }
 impl<'a> ToHashMsg for &'a HashMsg {
     type Output = &'a HashMsg;
@@ -199,22 +534,444 @@ impl<T> KeyTriad<T> {
     }
 }
 
+impl<T> KeyTriad<T>
+where
+    for<'a> &'a T: ToHashMsg,
+{
+    /// Signs `signed` with `key`.
+    pub fn signed_by(key: &PrivateKey, signed: T) -> Self {
+        KeyTriad {
+            public_key: key.derive_public(),
+            signature: key.sign(&signed),
+            signed,
+        }
+    }
+    /// Returns whether the signature over the signed value is valid.
+    pub fn valid(&self) -> bool {
+        self.public_key.valid(&self.signed, &self.signature)
+    }
+    /// Verifies the signature over the signed value, returning why it failed if it did.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        self.public_key.verify(&self.signed, &self.signature)
+    }
+    /// Signs `signed` with `signer`.
+    pub async fn sign<S: Signer>(signer: &S, signed: T) -> Result<Self, S::Err> {
+        let msg = *(&signed).to_hash_msg().as_ref();
+
+        Ok(KeyTriad {
+            public_key: signer.public_key(),
+            signature: signer.sign(msg).await?,
+            signed,
+        })
+    }
+}
+
 impl KeyTriad<SignedData> {
-    pub fn gen_signed(
-        key: &PrivateKey,
-        identify: &IdentifyData,
-        msg_type: SignMessageType,
-    ) -> Self {
-        let signable = Signable {
-            msg_type,
-            obj: identify,
+    /// Signs a [`Revocation`] of the public key of `key` at the time `time`.
+    pub fn revoke(key: &PrivateKey, time: u64) -> Self {
+        let revocation = Revocation {
+            key: key.derive_public(),
+            time,
         };
-        let ser = serde_cbor::to_vec(&signable).unwrap();
+
+        Self::gen_signed(
+            key,
+            &revocation,
+            SignMessageType::Revoke,
+            SignedFormat::Cbor,
+        )
+        .expect("revocations serialize to CBOR")
+    }
+    /// Signs a payload that is sent or stored separately, such as a large file. Only the hash of
+    /// the payload is kept in the triad.
+    pub fn sign_detached(key: &PrivateKey, payload: &[u8]) -> Self {
+        let hash = hash(payload);
 
         KeyTriad {
             public_key: key.derive_public(),
-            signature: key.sign(&ser),
-            signed: SignedData::Cbor(Arc::from(ser)),
+            signature: key.sign(hash),
+            signed: SignedData::Detached(hash),
+        }
+    }
+    /// Like [`KeyTriad::sign_detached`], but reads the payload from `reader` in chunks instead of
+    /// holding it in memory.
+    pub async fn sign_detached_reader(
+        key: &PrivateKey,
+        reader: &mut (impl tokio::io::AsyncRead + Unpin),
+    ) -> std::io::Result<Self> {
+        let hash = HashAlgorithm::Blake3.hash_reader(reader).await?;
+
+        Ok(KeyTriad {
+            public_key: key.derive_public(),
+            signature: key.sign(hash),
+            signed: SignedData::Detached(hash),
+        })
+    }
+    /// Like [`KeyTriad::valid_detached`], but reads the payload from `reader` in chunks instead
+    /// of holding it in memory.
+    pub async fn valid_detached_reader(
+        &self,
+        reader: &mut (impl tokio::io::AsyncRead + Unpin),
+    ) -> std::io::Result<bool> {
+        let hash = HashAlgorithm::Blake3.hash_reader(reader).await?;
+
+        Ok((&self.signed).to_hash_msg() == hash
+            && self.public_key.valid(&self.signed, &self.signature))
+    }
+    /// Returns whether `payload` is the payload referenced by this triad, and the signature over
+    /// it is valid.
+    pub fn valid_detached(&self, payload: &[u8]) -> bool {
+        (&self.signed).to_hash_msg() == hash(payload)
+            && self.public_key.valid(&self.signed, &self.signature)
+    }
+    /// Signs `obj` as a [`Signable`] of the type `msg_type`, serialized to `format`, such as
+    /// identify data, a revocation or a message of an application.
+    pub fn gen_signed<T: Serialize + ?Sized>(
+        key: &PrivateKey,
+        obj: &T,
+        msg_type: SignMessageType,
+        format: SignedFormat,
+    ) -> Result<Self, SignError> {
+        Self::gen_signed_with(key, obj, msg_type, format, HashAlgorithm::default())
+    }
+    /// Like [`KeyTriad::gen_signed`], but signs with `signer`, whose key may not live in this
+    /// process.
+    pub async fn gen_signed_by<S: Signer, T: Serialize + ?Sized>(
+        signer: &S,
+        obj: &T,
+        msg_type: SignMessageType,
+        format: SignedFormat,
+        hash: HashAlgorithm,
+    ) -> Result<Self, SignError<S::Err>> {
+        let signed = to_signed(obj, msg_type, format, hash, SignatureScheme::Ecdsa)?;
+
+        Ok(KeyTriad {
+            public_key: signer.public_key(),
+            signature: signer
+                .sign(signed.hash_with(hash))
+                .await
+                .map_err(SignError::Signer)?,
+            signed,
+        })
+    }
+    /// Like [`KeyTriad::gen_signed`], but hashes the signable with `hash`.
+    pub fn gen_signed_with<T: Serialize + ?Sized>(
+        key: &PrivateKey,
+        obj: &T,
+        msg_type: SignMessageType,
+        format: SignedFormat,
+        hash: HashAlgorithm,
+    ) -> Result<Self, SignError> {
+        let signed = to_signed(obj, msg_type, format, hash, SignatureScheme::Ecdsa)?;
+
+        Ok(KeyTriad {
+            public_key: key.derive_public(),
+            signature: key.sign(signed.hash_with(hash)),
+            signed,
+        })
+    }
+    /// Like [`KeyTriad::gen_signed`], but hashes and signs the signable with the algorithm and
+    /// the scheme of `features`, as negotiated with a node.
+    pub fn gen_signed_negotiated<T: Serialize + ?Sized>(
+        key: &PrivateKey,
+        obj: &T,
+        msg_type: SignMessageType,
+        format: SignedFormat,
+        features: &Features,
+    ) -> Result<Self, SignError> {
+        let scheme = features.signature_scheme();
+        let signed = to_signed(obj, msg_type, format, features.hash_algorithm(), scheme)?;
+        let (public_key, signature) = match scheme {
+            SignatureScheme::Ecdsa => (key.derive_public(), key.sign(&signed)),
+            SignatureScheme::Schnorr => (
+                key.derive_schnorr_public(),
+                key.sign_schnorr(&signed).into(),
+            ),
+        };
+
+        Ok(KeyTriad {
+            public_key,
+            signature,
+            signed,
+        })
+    }
+}
+
+/// Serializes the signable of `obj` to `format`. CBOR is serialized in canonical form.
+pub(crate) fn to_signed<T: Serialize + ?Sized, E>(
+    obj: &T,
+    msg_type: SignMessageType,
+    format: SignedFormat,
+    hash: HashAlgorithm,
+    scheme: SignatureScheme,
+) -> Result<SignedData, SignError<E>> {
+    let signable = Signable {
+        msg_type,
+        obj,
+        hash,
+        scheme,
+    };
+
+    Ok(match format {
+        SignedFormat::Json => SignedData::Json(serde_json::to_string(&signable)?.into()),
+        SignedFormat::Cbor => SignedData::Cbor(Arc::from(to_canonical_cbor(&signable)?)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::obj::IdentifyData;
+
+    #[test]
+    fn generate_sign_verify() {
+        let pair = KeyPair::generate_with(&mut StdRng::seed_from_u64(7));
+        let signature = pair.sign(b"msg");
+
+        assert_eq!(pair.public, pair.private.derive_public());
+        assert!(pair.public.valid(b"msg", &signature));
+        assert!(!pair.public.valid(b"other", &signature));
+    }
+
+    #[test]
+    fn mac() {
+        let key = [7u8; MAC_KEY_SIZE];
+        let mac = Mac::new(&key, b"frame");
+
+        assert!(mac.verify(&key, b"frame"));
+        assert!(!mac.verify(&key, b"other frame"));
+        assert!(!mac.verify(&[8u8; MAC_KEY_SIZE], b"frame"));
+        assert_ne!(keyed_hash(&key, b"frame"), hash(b"frame"));
+    }
+
+    #[test]
+    fn ct_eq() {
+        let pair = KeyPair::generate();
+        let other = KeyPair::generate();
+
+        assert!(bool::from(pair.private.ct_eq(&pair.private.clone())));
+        assert_ne!(pair.private, other.private);
+        assert!(bool::from(pair.public.ct_eq(&pair.public)));
+        assert!(!bool::from(pair.public.ct_eq(&other.public)));
+    }
+
+    #[test]
+    fn batch() {
+        let batch: Vec<_> = (0..32u8)
+            .map(|i| {
+                let pair = KeyPair::generate();
+                let msg = hash([i]);
+                (pair.public, msg.into(), pair.sign(msg))
+            })
+            .collect();
+        assert!(verify_batch(&batch));
+
+        let mut invalid = batch.clone();
+        invalid[20].1 = hash(b"other").into();
+        assert!(!verify_batch(&invalid));
+    }
+
+    #[tokio::test]
+    async fn signer() {
+        let pair = KeyPair::generate();
+        let identify = IdentifyData {
+            salt: [0u8; 16],
+            start_time: 0,
+            expire_time: 0,
+            extensions: Default::default(),
+            difficulty: None,
+            work: None,
+            usage: Default::default(),
+        };
+
+        let triad = KeyTriad::gen_signed_by(
+            &pair,
+            &identify,
+            SignMessageType::Identify,
+            SignedFormat::Cbor,
+            HashAlgorithm::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            triad,
+            KeyTriad::gen_signed(
+                &pair.private,
+                &identify,
+                SignMessageType::Identify,
+                SignedFormat::Cbor
+            )
+            .unwrap()
+        );
+
+        let triad = KeyTriad::<HashMsg>::sign(&pair.private, hash(b"message"))
+            .await
+            .unwrap();
+        assert!(triad.verify().is_ok());
+    }
+
+    #[test]
+    fn gen_signed_formats() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Chat {
+            text: String,
         }
+
+        let pair = KeyPair::generate();
+        let chat = Chat {
+            text: "hello".into(),
+        };
+        for format in [SignedFormat::Json, SignedFormat::Cbor] {
+            let triad =
+                KeyTriad::gen_signed(&pair.private, &chat, SignMessageType::AppMessage, format)
+                    .unwrap();
+            assert!(triad.verify().is_ok());
+            assert_eq!(
+                matches!(triad.signed, SignedData::Json(_)),
+                format == SignedFormat::Json
+            );
+
+            let signable: Signable<Chat> = triad.signed.to_signable().unwrap();
+            assert_eq!(signable.msg_type, SignMessageType::AppMessage);
+            assert_eq!(signable.obj, chat);
+        }
+
+        // revocations sign the same way
+        let revocation = Revocation {
+            key: pair.public,
+            time: 1,
+        };
+        let triad = KeyTriad::gen_signed(
+            &pair.private,
+            &revocation,
+            SignMessageType::Revoke,
+            SignedFormat::Cbor,
+        )
+        .unwrap();
+        assert_eq!(triad, KeyTriad::revoke(&pair.private, 1));
+    }
+
+    #[tokio::test]
+    async fn detached_reader() {
+        let pair = KeyPair::generate();
+        let triad = KeyTriad::sign_detached_reader(&pair.private, &mut &b"large file"[..])
+            .await
+            .unwrap();
+
+        assert_eq!(triad, KeyTriad::sign_detached(&pair.private, b"large file"));
+        assert!(triad
+            .valid_detached_reader(&mut &b"large file"[..])
+            .await
+            .unwrap());
+        assert!(!triad
+            .valid_detached_reader(&mut &b"other file"[..])
+            .await
+            .unwrap());
+    }
+
+    #[test]
+    fn detached() {
+        let pair = KeyPair::generate();
+        let triad = KeyTriad::sign_detached(&pair.private, b"large file");
+
+        assert!(triad.valid_detached(b"large file"));
+        assert!(!triad.valid_detached(b"other file"));
+
+        // detaching an attached payload keeps the signature valid
+        let attached = KeyTriad::gen_signed(
+            &pair.private,
+            &IdentifyData {
+                salt: [0u8; 16],
+                start_time: 0,
+                expire_time: 0,
+                extensions: Default::default(),
+                difficulty: None,
+                work: None,
+                usage: Default::default(),
+            },
+            SignMessageType::Identify,
+            SignedFormat::Cbor,
+        )
+        .unwrap();
+        let detached = attached.signed.detach();
+        assert!(pair.public.valid(&detached, &attached.signature));
+        assert_eq!(
+            detached.attach(attached.signed.clone()),
+            Ok(attached.signed)
+        );
+    }
+
+    #[test]
+    fn verify() {
+        let pair = KeyPair::generate();
+        let signature = pair.sign(b"msg");
+
+        assert_eq!(pair.public.verify(b"msg", &signature), Ok(()));
+        assert_eq!(
+            pair.public.verify(b"other", &signature),
+            Err(VerifyError::Mismatch)
+        );
+        assert_eq!(
+            pair.public
+                .verify(b"msg", &Signature([0u8; SIGNATURE_SIZE])),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_eq!(
+            PublicKey([9u8; PUBLIC_KEY_SIZE]).verify(b"msg", &signature),
+            Err(VerifyError::InvalidPublicKey)
+        );
+    }
+
+    #[test]
+    fn malleability() {
+        let pair = KeyPair::generate();
+        let signature = pair.sign(b"msg");
+        assert!(signature.is_canonical());
+
+        // negating `s` yields another valid signature over the same message
+        let mut high = libsecp256k1::Signature::parse_standard(&signature.0).unwrap();
+        high.s = -high.s;
+        let high = Signature(high.serialize());
+
+        assert_eq!(
+            pair.public.verify(b"msg", &high),
+            Err(VerifyError::NonCanonical)
+        );
+        assert_eq!(
+            pair.public
+                .verify_with(b"msg", &high, SignatureParsing::Lax),
+            Ok(())
+        );
+        assert_eq!(high.normalize(), signature);
+
+        let overflowing = Signature([0xff; SIGNATURE_SIZE]);
+        assert_eq!(
+            pair.public.verify(b"msg", &overflowing),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn parse_public_key() {
+        let pair = KeyPair::generate();
+
+        assert_eq!(PublicKey::parse(&pair.public.0), Ok(pair.public));
+        assert_eq!(
+            PublicKey::parse(&[2u8; 12]),
+            Err(KeyParseError::InvalidLength {
+                expected: PUBLIC_KEY_SIZE,
+                received: 12
+            })
+        );
+        assert_eq!(
+            PublicKey::parse(&[9u8; PUBLIC_KEY_SIZE]),
+            Err(KeyParseError::InvalidPublicKey)
+        );
+        assert_eq!(
+            PrivateKey::parse(&[0u8; PRIVATE_KEY_SIZE]),
+            Err(KeyParseError::InvalidPrivateKey)
+        );
     }
 }
@@ -0,0 +1,289 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    error::{MultiKeyError, SignError},
+    to_signed, KeyTriad, PrivateKey, PublicKey, Signature, SignedHash, ToHashMsg,
+};
+use crate::obj::{SignMessageType, SignedData, SignedFormat};
+
+/// A public key and its signature over the value of a [`MultiKeyTriad`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct KeySignature {
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKey,
+    pub signature: Signature,
+}
+
+/// The keys that may claim the identity of the key that signed the group, such as the device keys
+/// of an organization, and how many of them must sign together. Signed as a
+/// [`Signable`](crate::obj::Signable) of [`SignMessageType::KeyGroup`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct KeyGroup {
+    pub keys: Vec<PublicKey>,
+    /// The amount of distinct keys of the group that must sign to claim the identity.
+    pub threshold: u16,
+    /// The expiration timestamp of the group.
+    #[serde(rename = "expireTime")]
+    pub expire_time: u64,
+}
+
+impl KeyGroup {
+    /// Reads the group signed in `signed`, which must be a signable of
+    /// [`SignMessageType::KeyGroup`], so that a signature made for another purpose cannot declare
+    /// a group.
+    pub fn decode(signed: &SignedData) -> Result<Self, MultiKeyError> {
+        match signed.msg_type() {
+            Some(SignMessageType::KeyGroup) => {}
+            Some(found) => return Err(MultiKeyError::WrongMessageType(found)),
+            None => return Err(MultiKeyError::Malformed),
+        }
+
+        signed
+            .to_signable()
+            .map(|signable| signable.obj)
+            .map_err(|_| MultiKeyError::Malformed)
+    }
+}
+
+impl KeyTriad<SignedData> {
+    /// Declares that any `threshold` distinct keys of `keys` may claim the identity of
+    /// `identity` until `expire_time`.
+    pub fn group(
+        identity: &PrivateKey,
+        keys: Vec<PublicKey>,
+        threshold: u16,
+        expire_time: u64,
+    ) -> Result<Self, SignError> {
+        let group = KeyGroup {
+            keys,
+            threshold,
+            expire_time,
+        };
+        Self::gen_signed(
+            identity,
+            &group,
+            SignMessageType::KeyGroup,
+            SignedFormat::Cbor,
+        )
+    }
+}
+
+/// A value signed by at least the threshold of a [`KeyGroup`], which claims the identity that
+/// signed the group. The signatures are accepted or rejected together.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct MultiKeyTriad<T> {
+    /// The [`KeyGroup`] of the signers, signed by the identity they claim.
+    pub group: KeyTriad<SignedData>,
+    pub signers: Vec<KeySignature>,
+    pub signed: T,
+}
+
+impl<T> MultiKeyTriad<T> {
+    /// Returns the public key the signers claim.
+    pub fn identity(&self) -> PublicKey {
+        self.group.public_key
+    }
+    /// Reads the group of the signers.
+    pub fn key_group(&self) -> Result<KeyGroup, MultiKeyError> {
+        KeyGroup::decode(&self.group.signed)
+    }
+    /// Returns the public keys that signed the value.
+    pub fn public_keys(&self) -> impl Iterator<Item = PublicKey> + '_ {
+        self.signers.iter().map(|signer| signer.public_key)
+    }
+    /// Checks everything but the signatures of the signers: that the group is signed by the
+    /// identity and has not expired at the time `now`, and that at least the threshold of
+    /// distinct keys of the group signed. Returns the identity.
+    pub fn check(&self, now: u64) -> Result<PublicKey, MultiKeyError> {
+        let group = self.key_group()?;
+        self.group
+            .verify()
+            .map_err(|_| MultiKeyError::GroupSignatureInvalid)?;
+        if now > group.expire_time {
+            return Err(MultiKeyError::Expired);
+        }
+
+        let threshold = usize::from(group.threshold);
+        if threshold == 0 || threshold > group.keys.len() {
+            return Err(MultiKeyError::InvalidThreshold);
+        }
+
+        let mut signers = HashSet::with_capacity(self.signers.len());
+        for signer in self.signers.iter() {
+            if !group.keys.contains(&signer.public_key) {
+                return Err(MultiKeyError::UnknownSigner);
+            }
+            if !signers.insert(signer.public_key) {
+                return Err(MultiKeyError::DuplicateSigner);
+            }
+        }
+        if signers.len() < threshold {
+            return Err(MultiKeyError::BelowThreshold {
+                threshold: group.threshold,
+                signers: signers.len(),
+            });
+        }
+
+        Ok(self.identity())
+    }
+}
+
+impl<T> MultiKeyTriad<T>
+where
+    for<'a> &'a T: ToHashMsg,
+{
+    /// Verifies that the signers claim the identity of the group at the time `now`, returning
+    /// it, or why the triad failed to verify.
+    pub fn verify(&self, now: u64) -> Result<PublicKey, MultiKeyError> {
+        let identity = self.check(now)?;
+        let msg = SignedHash::of(&self.signed);
+
+        self.signers
+            .iter()
+            .try_for_each(|signer| signer.public_key.verify(msg, &signer.signature))?;
+        Ok(identity)
+    }
+}
+
+impl MultiKeyTriad<SignedData> {
+    /// Like [`KeyTriad::gen_signed`], but signs with every key in `keys`, which claim the identity
+    /// of `group`.
+    pub fn gen_signed<T: Serialize + ?Sized>(
+        group: KeyTriad<SignedData>,
+        keys: &[PrivateKey],
+        obj: &T,
+        msg_type: SignMessageType,
+        format: SignedFormat,
+    ) -> Result<Self, SignError> {
+        let signed = to_signed(
+            obj,
+            msg_type,
+            format,
+            Default::default(),
+            Default::default(),
+        )?;
+
+        Ok(MultiKeyTriad {
+            group,
+            signers: keys
+                .iter()
+                .map(|key| KeySignature {
+                    public_key: key.derive_public(),
+                    signature: key.sign(&signed),
+                })
+                .collect(),
+            signed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{error::VerifyError, KeyPair};
+    use crate::obj::IdentifyData;
+
+    #[test]
+    fn verify() {
+        let org = KeyPair::generate();
+        let devices: Vec<_> = (0..3).map(|_| KeyPair::generate()).collect();
+        let keys: Vec<_> = devices.iter().map(|pair| pair.public).collect();
+        let group = KeyTriad::group(&org.private, keys.clone(), 2, u64::MAX).unwrap();
+        let identify = IdentifyData {
+            salt: [0u8; 16],
+            start_time: 0,
+            expire_time: 0,
+            extensions: Default::default(),
+            difficulty: None,
+            work: None,
+            usage: Default::default(),
+        };
+        let sign = |keys: &[KeyPair]| {
+            let keys: Vec<_> = keys.iter().map(|pair| pair.private.clone()).collect();
+            MultiKeyTriad::gen_signed(
+                group.clone(),
+                &keys,
+                &identify,
+                SignMessageType::Identify,
+                SignedFormat::Cbor,
+            )
+            .unwrap()
+        };
+
+        // k - 1 signers cannot claim the identity
+        assert_eq!(
+            sign(&devices[..1]).verify(0),
+            Err(MultiKeyError::BelowThreshold {
+                threshold: 2,
+                signers: 1
+            })
+        );
+        // neither can a signer counted twice
+        let mut twice = sign(&devices[..1]);
+        twice.signers.push(twice.signers[0]);
+        assert_eq!(twice.verify(0), Err(MultiKeyError::DuplicateSigner));
+
+        // k signers can
+        let triad = sign(&devices[1..]);
+        assert_eq!(triad.verify(0), Ok(org.public));
+
+        let mut bad = triad.clone();
+        bad.signers[1].signature = bad.signers[0].signature;
+        assert_eq!(
+            bad.verify(0),
+            Err(MultiKeyError::Verify(VerifyError::Mismatch))
+        );
+
+        // keys outside of the group do not count
+        let mut outsider = triad.clone();
+        outsider.signers[1].public_key = org.public;
+        assert_eq!(outsider.verify(0), Err(MultiKeyError::UnknownSigner));
+
+        // and the group cannot be changed without the identity
+        let mut lowered = triad.clone();
+        lowered.group = KeyTriad {
+            signature: triad.group.signature,
+            ..KeyTriad::group(&org.private, keys.clone(), 1, u64::MAX).unwrap()
+        };
+        lowered.signers.pop();
+        assert_eq!(lowered.verify(0), Err(MultiKeyError::GroupSignatureInvalid));
+
+        // a group stops claiming the identity once it expires
+        let expiring = MultiKeyTriad {
+            group: KeyTriad::group(&org.private, keys.clone(), 2, 10).unwrap(),
+            ..triad.clone()
+        };
+        assert_eq!(expiring.verify(10), Ok(org.public));
+        assert_eq!(expiring.verify(11), Err(MultiKeyError::Expired));
+
+        // a signature made for another purpose does not declare a group
+        let group = KeyGroup {
+            keys,
+            threshold: 2,
+            expire_time: u64::MAX,
+        };
+        let replayed = MultiKeyTriad {
+            group: KeyTriad::gen_signed(
+                &org.private,
+                &group,
+                SignMessageType::Delegate,
+                SignedFormat::Cbor,
+            )
+            .unwrap(),
+            ..triad
+        };
+        assert_eq!(
+            replayed.verify(0),
+            Err(MultiKeyError::WrongMessageType(SignMessageType::Delegate))
+        );
+
+        let empty = KeyTriad::group(&org.private, Vec::new(), 0, u64::MAX).unwrap();
+        let triad = MultiKeyTriad {
+            group: empty,
+            ..sign(&devices)
+        };
+        assert_eq!(triad.verify(0), Err(MultiKeyError::InvalidThreshold));
+    }
+}
@@ -0,0 +1,163 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use tokio::sync::oneshot;
+
+use super::{
+    error::{PoolClosedError, VerifyError},
+    verify_batch, HashMsg, PrivateKey, PublicKey, Signature, SignedHash,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of worker threads dedicated to signing and verification, so that busy servers can tune
+/// their crypto load independently of the blocking pool of the runtime.
+///
+/// Cloning the pool yields another handle to the same workers. The workers stop once every handle
+/// is dropped.
+#[derive(Debug, Clone)]
+pub struct CryptoPool {
+    jobs: mpsc::Sender<Job>,
+    threads: usize,
+}
+
+impl CryptoPool {
+    /// Starts a pool of `threads` workers. At least one worker is started. Fails if a worker
+    /// cannot be spawned, in which case the workers already started stop.
+    pub fn new(threads: usize) -> std::io::Result<Self> {
+        let threads = threads.max(1);
+        let (jobs, recv) = mpsc::channel::<Job>();
+        let recv = Arc::new(Mutex::new(recv));
+
+        for i in 0..threads {
+            let recv = recv.clone();
+            thread::Builder::new()
+                .name(format!("crypto-{i}"))
+                .spawn(move || loop {
+                    let job = match recv.lock() {
+                        Ok(recv) => recv.recv(),
+                        Err(_) => return,
+                    };
+                    match job {
+                        // a panicking job drops its sender, which is reported to the caller
+                        Ok(job) => {
+                            let _ = std::panic::catch_unwind(AssertUnwindSafe(job));
+                        }
+                        Err(_) => return,
+                    }
+                })?;
+        }
+
+        Ok(Self { jobs, threads })
+    }
+    /// Returns the amount of workers of the pool.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+    /// Runs `f` on a worker, and waits for its result.
+    pub async fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> R + Send + 'static,
+    ) -> Result<R, PoolClosedError> {
+        let (send, recv) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move || {
+                let _ = send.send(f());
+            }))
+            .map_err(|_| PoolClosedError)?;
+
+        recv.await.map_err(|_| PoolClosedError)
+    }
+    /// Signs `msg` with `key` on a worker.
+    pub async fn sign(&self, key: PrivateKey, msg: HashMsg) -> Result<Signature, PoolClosedError> {
+        self.run(move || key.sign(msg)).await
+    }
+    /// Verifies `signature` of `msg` by `public_key` on a worker. Refer to [`PublicKey::verify`].
+    pub async fn verify(
+        &self,
+        public_key: PublicKey,
+        msg: SignedHash,
+        signature: Signature,
+    ) -> Result<Result<(), VerifyError>, PoolClosedError> {
+        self.run(move || public_key.verify(msg, &signature)).await
+    }
+    /// Verifies `batch` on a worker. Refer to [`verify_batch`].
+    pub async fn verify_batch(
+        &self,
+        batch: Arc<[(PublicKey, SignedHash, Signature)]>,
+    ) -> Result<bool, PoolClosedError> {
+        self.run(move || verify_batch(&batch)).await
+    }
+}
+
+/// Verifies `batch` without blocking the async runtime: on `pool` if there is one, or on the
+/// blocking threads of tokio otherwise. Refer to [`verify_batch`].
+pub async fn spawn_verify_batch(
+    pool: Option<&CryptoPool>,
+    batch: Arc<[(PublicKey, SignedHash, Signature)]>,
+) -> bool {
+    if let Some(pool) = pool {
+        if let Ok(valid) = pool.verify_batch(batch.clone()).await {
+            return valid;
+        }
+    }
+    tokio::task::spawn_blocking(move || verify_batch(&batch))
+        .await
+        .unwrap_or(false)
+}
+
+/// Verifies `signature` of `msg` by `public_key` without blocking the async runtime, like
+/// [`spawn_verify_batch`]. Refer to [`PublicKey::verify`].
+pub async fn spawn_verify(
+    pool: Option<&CryptoPool>,
+    public_key: PublicKey,
+    msg: SignedHash,
+    signature: Signature,
+) -> Result<(), VerifyError> {
+    if let Some(pool) = pool {
+        if let Ok(result) = pool.verify(public_key, msg, signature).await {
+            return result;
+        }
+    }
+    tokio::task::spawn_blocking(move || public_key.verify(msg, &signature))
+        .await
+        .unwrap_or(Err(VerifyError::Mismatch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{hash, KeyPair};
+
+    #[tokio::test]
+    async fn pool() {
+        let pool = CryptoPool::new(2).unwrap();
+        let pair = KeyPair::generate();
+        let msg = hash(b"message");
+
+        let signature = pool.sign(pair.private.clone(), msg).await.unwrap();
+        assert!(pair.public.valid(msg, &signature));
+
+        let batch: Arc<[_]> = Arc::from(vec![(pair.public, msg.into(), signature)]);
+        assert_eq!(pool.verify_batch(batch.clone()).await, Ok(true));
+        assert!(spawn_verify_batch(Some(&pool), batch.clone()).await);
+        assert!(spawn_verify_batch(None, batch).await);
+        assert_eq!(
+            spawn_verify(Some(&pool), pair.public, msg.into(), signature).await,
+            Ok(())
+        );
+        assert!(
+            spawn_verify(None, pair.public, hash(b"other").into(), signature)
+                .await
+                .is_err()
+        );
+
+        // a panicking job does not take its worker down
+        assert_eq!(
+            pool.run(|| panic!("job panicked")).await,
+            Err::<(), _>(PoolClosedError)
+        );
+        assert_eq!(pool.clone().run(|| 1 + 1).await, Ok(2));
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::{
+    error::{RecoverError, SignError},
+    to_signed, HexOrBytes, KeyTriad, PrivateKey, PublicKey, Signature, SignatureParsing, ToHashMsg,
+    SIGNATURE_SIZE,
+};
+use crate::obj::{SignMessageType, SignedData, SignedFormat};
+
+/// The size (in bytes) of a recoverable signature.
+pub const RECOVERABLE_SIGNATURE_SIZE: usize = SIGNATURE_SIZE + 1;
+
+/// A signature followed by a recovery id, which the public key of the signer can be recovered
+/// from.
+#[repr(transparent)]
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct RecoverableSignature(
+    #[serde_as(as = "HexOrBytes")] pub [u8; RECOVERABLE_SIGNATURE_SIZE],
+);
+
+impl RecoverableSignature {
+    /// Returns the signature without the recovery id.
+    pub fn signature(&self) -> Signature {
+        let mut signature = [0u8; SIGNATURE_SIZE];
+        signature.copy_from_slice(&self.0[..SIGNATURE_SIZE]);
+        Signature(signature)
+    }
+    /// Returns the recovery id.
+    pub fn recovery_id(&self) -> u8 {
+        self.0[SIGNATURE_SIZE]
+    }
+}
+
+impl PrivateKey {
+    /// Signs a message, producing a signature that the public key can be recovered from.
+    pub fn sign_recoverable(&self, msg: impl ToHashMsg) -> RecoverableSignature {
+        let hashmsg = msg.to_hash_msg();
+        let msg = libsecp256k1::Message::parse(&hashmsg.as_ref().0);
+        let (signature, recovery_id) = libsecp256k1::sign(&msg, &self.0);
+
+        let mut bytes = [0u8; RECOVERABLE_SIGNATURE_SIZE];
+        bytes[..SIGNATURE_SIZE].copy_from_slice(&signature.serialize());
+        bytes[SIGNATURE_SIZE] = recovery_id.serialize();
+        RecoverableSignature(bytes)
+    }
+}
+
+impl PublicKey {
+    /// Recovers the public key that signed `msg`.
+    pub fn recover(
+        msg: impl ToHashMsg,
+        signature: &RecoverableSignature,
+    ) -> Result<Self, RecoverError> {
+        let hashmsg = msg.to_hash_msg();
+        let msg = libsecp256k1::Message::parse(&hashmsg.as_ref().0);
+        let recovery_id = libsecp256k1::RecoveryId::parse(signature.recovery_id())
+            .map_err(|_| RecoverError::InvalidRecoveryId)?;
+        let signature = signature
+            .signature()
+            .to_secp(SignatureParsing::Strict)
+            .map_err(|_| RecoverError::InvalidSignature)?;
+
+        let key = libsecp256k1::recover(&msg, &signature, &recovery_id)
+            .map_err(|_| RecoverError::InvalidSignature)?;
+        Ok(Self(key.serialize_compressed()))
+    }
+}
+
+/// A [`KeyTriad`] without the public key, which is recovered from the signature instead.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct RecoverableTriad<T> {
+    pub signature: RecoverableSignature,
+    pub signed: T,
+}
+
+impl<T> RecoverableTriad<T>
+where
+    for<'a> &'a T: ToHashMsg,
+{
+    /// Recovers the public key of this triad, converting it to a [`KeyTriad`].
+    pub fn recover(self) -> Result<KeyTriad<T>, RecoverError> {
+        Ok(KeyTriad {
+            public_key: PublicKey::recover(&self.signed, &self.signature)?,
+            signature: self.signature.signature(),
+            signed: self.signed,
+        })
+    }
+}
+
+impl RecoverableTriad<SignedData> {
+    /// Like [`KeyTriad::gen_signed`], but signs with a recoverable signature.
+    pub fn gen_signed<T: Serialize + ?Sized>(
+        key: &PrivateKey,
+        obj: &T,
+        msg_type: SignMessageType,
+        format: SignedFormat,
+    ) -> Result<Self, SignError> {
+        let signed = to_signed(
+            obj,
+            msg_type,
+            format,
+            Default::default(),
+            Default::default(),
+        )?;
+
+        Ok(RecoverableTriad {
+            signature: key.sign_recoverable(&signed),
+            signed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn recover() {
+        let pair = KeyPair::generate();
+        let signature = pair.private.sign_recoverable(b"msg");
+
+        assert_eq!(PublicKey::recover(b"msg", &signature), Ok(pair.public));
+        assert_ne!(PublicKey::recover(b"other", &signature), Ok(pair.public));
+        assert!(pair.public.valid(b"msg", &signature.signature()));
+
+        let mut invalid = signature;
+        invalid.0[SIGNATURE_SIZE] = 9;
+        assert_eq!(
+            PublicKey::recover(b"msg", &invalid),
+            Err(RecoverError::InvalidRecoveryId)
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use k256::schnorr::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::{
+    error::{SignError, VerifyError},
+    to_signed, HashAlgorithm, HexOrBytes, KeyTriad, PrivateKey, PublicKey, Signature, ToHashMsg,
+    SIGNATURE_SIZE,
+};
+use crate::obj::{SignMessageType, SignedData, SignedFormat};
+use crate::utils;
+
+/// A BIP340 Schnorr signature. Is carried in the [`Signature`] of a triad whose signed data
+/// declares [`SignatureScheme::Schnorr`].
+#[repr(transparent)]
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct SchnorrSignature(#[serde_as(as = "HexOrBytes")] pub [u8; SIGNATURE_SIZE]);
+
+impl From<SchnorrSignature> for Signature {
+    fn from(value: SchnorrSignature) -> Self {
+        Signature(value.0)
+    }
+}
+
+/// The signature schemes a [`PublicKey`] can sign with. A [`Signable`](crate::obj::Signable)
+/// declares the scheme it is signed with, which the signature is verified with.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum SignatureScheme {
+    #[default]
+    #[serde(rename = "ECDSA")]
+    Ecdsa,
+    #[serde(rename = "SCHNORR")]
+    Schnorr,
+}
+
+impl SignatureScheme {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The prefix of a compressed public key whose point has an even y.
+const EVEN_PREFIX: u8 = 0x02;
+
+impl PrivateKey {
+    /// Derives the public key that BIP340 Schnorr signatures of this key verify against: the
+    /// point of the x coordinate with an even y. Is [`PrivateKey::derive_public`] for half of
+    /// the keys.
+    pub fn derive_schnorr_public(&self) -> PublicKey {
+        let mut public_key = self.derive_public();
+        public_key.0[0] = EVEN_PREFIX;
+        public_key
+    }
+    /// Signs a message using BIP340 Schnorr. The message is the 32 byte hash of `msg`.
+    pub fn sign_schnorr(&self, msg: impl ToHashMsg) -> SchnorrSignature {
+        let hashmsg = msg.to_hash_msg();
+        let key = SigningKey::from_bytes(&self.0.serialize())
+            .expect("valid secp256k1 private keys are valid schnorr signing keys");
+        let aux_rand: [u8; 32] = utils::random_bytes();
+
+        let signature = key
+            .sign_raw(&hashmsg.as_ref().0, &aux_rand)
+            .expect("signing a 32 byte message never fails");
+        SchnorrSignature(signature.to_bytes())
+    }
+}
+
+impl PublicKey {
+    /// Checks a BIP340 Schnorr signature created by [`PrivateKey::sign_schnorr`]. This public key
+    /// must be the point with an even y, as returned by [`PrivateKey::derive_schnorr_public`], so
+    /// that a signature verifies against a single key.
+    pub fn valid_schnorr(&self, msg: impl ToHashMsg, signature: &SchnorrSignature) -> bool {
+        self.verify_schnorr(msg, signature).is_ok()
+    }
+    /// Like [`PublicKey::valid_schnorr`], but returns why the signature failed to verify.
+    pub fn verify_schnorr(
+        &self,
+        msg: impl ToHashMsg,
+        signature: &SchnorrSignature,
+    ) -> Result<(), VerifyError> {
+        // BIP340 only uses the x coordinate, so the prefix must be pinned to one value
+        if self.0[0] != EVEN_PREFIX {
+            return Err(VerifyError::InvalidPublicKey);
+        }
+        let key =
+            VerifyingKey::from_bytes(&self.0[1..]).map_err(|_| VerifyError::InvalidPublicKey)?;
+        let signature = k256::schnorr::Signature::try_from(&signature.0[..])
+            .map_err(|_| VerifyError::InvalidSignature)?;
+
+        let hashmsg = msg.to_hash_msg();
+        key.verify_raw(&hashmsg.as_ref().0, &signature)
+            .map_err(|_| VerifyError::Mismatch)
+    }
+}
+
+impl SignedData {
+    /// Serializes `obj` as a [`Signable`](crate::obj::Signable) of the type `msg_type` to be
+    /// signed with BIP340 Schnorr by a key that does not live in one place, such as a
+    /// [threshold](super::threshold) group key. The signature verifies with [`PublicKey::valid`].
+    pub fn for_schnorr<T: Serialize + ?Sized>(
+        obj: &T,
+        msg_type: SignMessageType,
+        format: SignedFormat,
+    ) -> Result<Self, SignError> {
+        to_signed(
+            obj,
+            msg_type,
+            format,
+            HashAlgorithm::default(),
+            SignatureScheme::Schnorr,
+        )
+    }
+}
+
+impl KeyTriad<SignedData> {
+    /// Like [`KeyTriad::gen_signed`], but signs with BIP340 Schnorr, as the key of
+    /// [`PrivateKey::derive_schnorr_public`].
+    pub fn gen_signed_schnorr<T: Serialize + ?Sized>(
+        key: &PrivateKey,
+        obj: &T,
+        msg_type: SignMessageType,
+        format: SignedFormat,
+    ) -> Result<Self, SignError> {
+        let signed = SignedData::for_schnorr(obj, msg_type, format)?;
+
+        Ok(KeyTriad {
+            public_key: key.derive_schnorr_public(),
+            signature: key.sign_schnorr(&signed).into(),
+            signed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignatureScheme;
+    use crate::crypto::{error::VerifyError, KeyPair, KeyTriad};
+    use crate::obj::{Features, NodeInfo, SignMessageType, SignedFormat};
+
+    #[test]
+    fn negotiate() {
+        let schnorr = NodeInfo {
+            api_version: 0,
+            features: Features::from_iter([Features::SCHNORR]),
+            ..Default::default()
+        };
+        let legacy = NodeInfo::default();
+
+        assert_eq!(
+            schnorr.negotiate(&schnorr).signature_scheme(),
+            SignatureScheme::Schnorr
+        );
+        assert_eq!(
+            schnorr.negotiate(&legacy).signature_scheme(),
+            SignatureScheme::Ecdsa
+        );
+    }
+
+    #[test]
+    fn sign_verify() {
+        // check keys with both an even and odd y coordinate
+        for _ in 0..4 {
+            let pair = KeyPair::generate();
+            let public_key = pair.private.derive_schnorr_public();
+            let signature = pair.private.sign_schnorr(b"msg");
+
+            assert!(public_key.valid_schnorr(b"msg", &signature));
+            assert!(!public_key.valid_schnorr(b"other", &signature));
+        }
+    }
+
+    #[test]
+    fn prefix() {
+        let pair = KeyPair::generate();
+        let public_key = pair.private.derive_schnorr_public();
+        let signature = pair.private.sign_schnorr(b"msg");
+        assert_eq!(public_key.verify_schnorr(b"msg", &signature), Ok(()));
+
+        // the same x coordinate under any other prefix is another key, which the signature does
+        // not verify against
+        for prefix in [0x00, 0x03, 0x04, 0x07, 0xff] {
+            let mut rewritten = public_key;
+            rewritten.0[0] = prefix;
+            assert_eq!(
+                rewritten.verify_schnorr(b"msg", &signature),
+                Err(VerifyError::InvalidPublicKey)
+            );
+        }
+    }
+
+    #[test]
+    fn signed_data() {
+        let pair = KeyPair::generate();
+        let triad = KeyTriad::gen_signed_schnorr(
+            &pair.private,
+            "hello",
+            SignMessageType::AppMessage,
+            SignedFormat::Cbor,
+        )
+        .unwrap();
+
+        // the payload declares the scheme, so the signature verifies like any other
+        assert_eq!(triad.signed.signature_scheme(), SignatureScheme::Schnorr);
+        assert!(triad.valid());
+
+        // and is not mistaken for an ECDSA signature
+        let ecdsa = KeyTriad::gen_signed(
+            &pair.private,
+            "hello",
+            SignMessageType::AppMessage,
+            SignedFormat::Cbor,
+        )
+        .unwrap();
+        let forged = KeyTriad {
+            signature: triad.signature,
+            ..ecdsa.clone()
+        };
+        assert!(!forged.valid());
+
+        // a node that negotiated schnorr is sent schnorr signatures
+        let features = Features::from_iter([Features::SCHNORR]);
+        let negotiated = KeyTriad::gen_signed_negotiated(
+            &pair.private,
+            "hello",
+            SignMessageType::AppMessage,
+            SignedFormat::Cbor,
+            &features,
+        )
+        .unwrap();
+        assert_eq!(
+            negotiated.signed.signature_scheme(),
+            SignatureScheme::Schnorr
+        );
+        assert_eq!(negotiated.verify(), Ok(()));
+        let mut tampered = negotiated;
+        tampered.signature.0[40] ^= 1;
+        assert_eq!(tampered.verify(), Err(VerifyError::Mismatch));
+    }
+}
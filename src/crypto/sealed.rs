@@ -0,0 +1,125 @@
+//! Sealed-box encryption to a public key (ECIES over secp256k1).
+//!
+//! A sealed box is laid out as an ephemeral public key (33 bytes), followed by the payload
+//! encrypted with XChaCha20-Poly1305 under a key derived from the ECDH secret between the
+//! ephemeral key and the recipient. Only the holder of the private key of the recipient can open
+//! it, and the sender stays anonymous.
+
+use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+use rand::{CryptoRng, RngCore};
+
+use super::{error::SealError, KeyPair, PrivateKey, PublicKey, PUBLIC_KEY_SIZE};
+
+const NONCE_SIZE: usize = 24;
+const TAG_SIZE: usize = 16;
+
+/// The amount of bytes a sealed box adds to its payload.
+pub const SEAL_OVERHEAD: usize = PUBLIC_KEY_SIZE + TAG_SIZE;
+
+/// Derives the key of the box between the ephemeral key and the recipient.
+fn box_key(
+    private: &PrivateKey,
+    public: &PublicKey,
+    ephemeral: &PublicKey,
+    to: &PublicKey,
+) -> Result<[u8; 32], SealError> {
+    let secret = private
+        .diffie_hellman(public)
+        .map_err(|_| SealError::InvalidPublicKey)?;
+
+    let mut salt = [0u8; PUBLIC_KEY_SIZE * 2];
+    salt[..PUBLIC_KEY_SIZE].copy_from_slice(&ephemeral.0);
+    salt[PUBLIC_KEY_SIZE..].copy_from_slice(&to.0);
+    Ok(secret.derive_key(&salt, b"cacophoney sealed box"))
+}
+
+/// Encrypts `bytes` so that only the holder of the private key of `to` can read them.
+pub fn seal(to: &PublicKey, bytes: &[u8]) -> Result<Vec<u8>, SealError> {
+    seal_with(&mut crate::utils::rng(), to, bytes)
+}
+
+/// Like [`seal`], but generates the ephemeral key with `rng`.
+pub fn seal_with(
+    rng: &mut (impl RngCore + CryptoRng),
+    to: &PublicKey,
+    bytes: &[u8],
+) -> Result<Vec<u8>, SealError> {
+    let ephemeral = KeyPair::generate_with(rng);
+    let key = box_key(&ephemeral.private, to, &ephemeral.public, to)?;
+
+    // every box has its own key, so the nonce can be fixed
+    let ciphertext = XChaCha20Poly1305::new(&key.into())
+        .encrypt(XNonce::from_slice(&[0u8; NONCE_SIZE]), bytes)
+        .map_err(|_| SealError::DecryptionFailed)?;
+
+    let mut sealed = Vec::with_capacity(PUBLIC_KEY_SIZE + ciphertext.len());
+    sealed.extend_from_slice(&ephemeral.public.0);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts a box sealed to the public key of `key` with [`seal`].
+pub fn unseal(key: &PrivateKey, sealed: &[u8]) -> Result<Vec<u8>, SealError> {
+    if sealed.len() < SEAL_OVERHEAD {
+        return Err(SealError::Truncated);
+    }
+
+    let mut ephemeral = PublicKey([0u8; PUBLIC_KEY_SIZE]);
+    ephemeral.0.copy_from_slice(&sealed[..PUBLIC_KEY_SIZE]);
+    let box_key = box_key(key, &ephemeral, &ephemeral, &key.derive_public())?;
+
+    XChaCha20Poly1305::new(&box_key.into())
+        .decrypt(
+            XNonce::from_slice(&[0u8; NONCE_SIZE]),
+            &sealed[PUBLIC_KEY_SIZE..],
+        )
+        .map_err(|_| SealError::DecryptionFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyTriad;
+    use crate::obj::SignedData;
+
+    #[test]
+    fn seal_unseal() {
+        let (to, other) = (KeyPair::generate(), KeyPair::generate());
+        let sealed = seal(&to.public, b"secret").unwrap();
+
+        assert_eq!(sealed.len(), b"secret".len() + SEAL_OVERHEAD);
+        assert_eq!(unseal(&to.private, &sealed).unwrap(), b"secret");
+        assert_eq!(
+            unseal(&other.private, &sealed),
+            Err(SealError::DecryptionFailed)
+        );
+        assert_eq!(
+            unseal(&to.private, &sealed[..10]),
+            Err(SealError::Truncated)
+        );
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            unseal(&to.private, &tampered),
+            Err(SealError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn signed_data() {
+        let (from, to) = (KeyPair::generate(), KeyPair::generate());
+        let data = SignedData::Json("{}".into());
+        let sealed = data.seal(&to.public).unwrap();
+
+        // relays can check the signature without reading the payload
+        let triad = KeyTriad {
+            public_key: from.public,
+            signature: from.sign(&sealed),
+            signed: sealed,
+        };
+        assert!(triad.verify().is_ok());
+        assert_eq!(triad.signed.unseal(&to.private), Ok(data.clone()));
+        assert_eq!(data.unseal(&to.private), Err(SealError::NotEncrypted));
+    }
+}
@@ -0,0 +1,51 @@
+use std::convert::Infallible;
+use std::error::Error as StdError;
+
+use futures::Future;
+
+use super::{HashMsg, KeyPair, PrivateKey, PublicKey, Signature};
+
+/// Something that signs messages with a private key, that may not live in this process, such as
+/// a key in a hardware security module, the keychain of the OS, or a remote signing service.
+pub trait Signer {
+    type Err: StdError;
+
+    /// Returns the public key of the signer.
+    fn public_key(&self) -> PublicKey;
+
+    /// Signs the hash of a message.
+    fn sign(&self, msg: HashMsg) -> impl Future<Output = Result<Signature, Self::Err>> + Send;
+}
+
+impl Signer for PrivateKey {
+    type Err = Infallible;
+
+    fn public_key(&self) -> PublicKey {
+        self.derive_public()
+    }
+    fn sign(&self, msg: HashMsg) -> impl Future<Output = Result<Signature, Self::Err>> + Send {
+        futures::future::ready(Ok(PrivateKey::sign(self, msg)))
+    }
+}
+
+impl Signer for KeyPair {
+    type Err = Infallible;
+
+    fn public_key(&self) -> PublicKey {
+        self.public
+    }
+    fn sign(&self, msg: HashMsg) -> impl Future<Output = Result<Signature, Self::Err>> + Send {
+        Signer::sign(&self.private, msg)
+    }
+}
+
+impl<S: Signer + ?Sized + Sync> Signer for &S {
+    type Err = S::Err;
+
+    fn public_key(&self) -> PublicKey {
+        (**self).public_key()
+    }
+    fn sign(&self, msg: HashMsg) -> impl Future<Output = Result<Signature, Self::Err>> + Send {
+        (**self).sign(msg)
+    }
+}
@@ -0,0 +1,95 @@
+use sha2::Digest;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::{HashAlgorithm, HashMsg};
+
+/// The size (in bytes) of the chunks read by [`HashStream::read_from`].
+pub const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+}
+
+/// Computes a hash incrementally, so that large payloads, such as files sent over a stream, can be
+/// hashed without holding them in memory. Produces the same hash as [`HashAlgorithm::hash`] over
+/// the whole payload.
+#[derive(Debug, Clone)]
+pub struct HashStream {
+    hasher: Hasher,
+}
+
+impl HashStream {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        let hasher = match algorithm {
+            HashAlgorithm::Blake3 => Hasher::Blake3(Default::default()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Default::default()),
+        };
+
+        Self { hasher }
+    }
+    /// Hashes the next bytes of the payload.
+    pub fn update(&mut self, bytes: &[u8]) {
+        match &mut self.hasher {
+            Hasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+    /// Hashes everything `reader` yields until it ends, returning the amount of bytes read.
+    pub async fn read_from(
+        &mut self,
+        reader: &mut (impl AsyncRead + Unpin),
+    ) -> std::io::Result<u64> {
+        let mut buf = vec![0u8; HASH_CHUNK_SIZE];
+        let mut read = 0;
+
+        loop {
+            match reader.read(&mut buf).await? {
+                0 => return Ok(read),
+                len => {
+                    self.update(&buf[..len]);
+                    read += len as u64;
+                }
+            }
+        }
+    }
+    /// Returns the hash of the bytes hashed so far.
+    pub fn finalize(self) -> HashMsg {
+        match self.hasher {
+            Hasher::Blake3(hasher) => HashMsg(hasher.finalize().into()),
+            Hasher::Sha256(hasher) => HashMsg(hasher.finalize().into()),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    /// Computes the hash of everything `reader` yields with this algorithm.
+    pub async fn hash_reader(
+        self,
+        reader: &mut (impl AsyncRead + Unpin),
+    ) -> std::io::Result<HashMsg> {
+        let mut stream = HashStream::new(self);
+        stream.read_from(reader).await?;
+        Ok(stream.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stream() {
+        let payload: Vec<u8> = (0..HASH_CHUNK_SIZE * 2 + 7).map(|i| i as u8).collect();
+
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            assert_eq!(
+                algorithm.hash_reader(&mut &payload[..]).await.unwrap(),
+                algorithm.hash(&payload)
+            );
+        }
+    }
+}
@@ -0,0 +1,571 @@
+//! FROST threshold signatures, where any `threshold` of the holders of a shared key can sign
+//! together, while fewer holders learn nothing about the key.
+//!
+//! The key is generated with a distributed key generation, so that no party ever holds the whole
+//! private key. The signatures are BIP340 Schnorr signatures. The signers sign a payload prepared
+//! with [`SignedData::for_schnorr`](crate::obj::SignedData::for_schnorr), so that a
+//! [`KeyTriad`](super::KeyTriad) of the group key over it verifies with [`PublicKey::valid`], and
+//! identifies to a node, like any other triad.
+
+use k256::elliptic_curve::{
+    ops::Reduce, point::AffineCoordinates, sec1::ToEncodedPoint, Field, PrimeField,
+};
+use k256::{ProjectivePoint, Scalar, U256};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    error::ThresholdError,
+    schnorr::{SchnorrSignature, SignatureScheme},
+    HashMsg, PrivateKey, PublicKey, Signature, SignedHash, ToHashMsg, PRIVATE_KEY_SIZE,
+    SIGNATURE_SIZE,
+};
+
+/// The index of a participant, starting at 1.
+pub type ParticipantId = u16;
+
+/// Tags the hash a participant proves it knows its secret over.
+const PROOF_TAG: &[u8] = b"cacophoney/frost/proof";
+/// Tags the hash the binding factors of the signers are derived from.
+const BINDING_TAG: &[u8] = b"cacophoney/frost/binding";
+/// Tags the hash of the challenge, as specified by BIP340.
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+fn tagged_hash(tag: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let tag = Sha256::digest(tag);
+    let mut hasher = Sha256::new();
+    hasher.update(tag);
+    hasher.update(tag);
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+fn hash_to_scalar(hash: [u8; 32]) -> Scalar {
+    <Scalar as Reduce<U256>>::reduce_bytes(&hash.into())
+}
+
+fn to_public(point: &ProjectivePoint) -> Option<PublicKey> {
+    let encoded = point.to_affine().to_encoded_point(true);
+    Some(PublicKey(encoded.as_bytes().try_into().ok()?))
+}
+
+/// Returns the key of the x coordinate of `key` with an even y, that BIP340 signatures of its
+/// secret verify against.
+fn to_even(key: &PublicKey) -> PublicKey {
+    let mut key = *key;
+    key.0[0] = 0x02;
+    key
+}
+
+fn to_point(key: &PublicKey) -> Option<ProjectivePoint> {
+    k256::PublicKey::from_sec1_bytes(&key.0)
+        .ok()
+        .map(|key| key.to_projective())
+}
+
+fn to_scalar(bytes: &[u8; PRIVATE_KEY_SIZE]) -> Option<Scalar> {
+    Scalar::from_repr((*bytes).into()).into()
+}
+
+/// Evaluates the polynomial with the coefficients `coefficients` at `x`.
+fn evaluate(coefficients: &[Scalar], x: ParticipantId) -> Scalar {
+    let x = Scalar::from(x as u64);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Scalar::ZERO, |acc, coefficient| acc * x + coefficient)
+}
+
+/// Returns the Lagrange coefficient of `id` when interpolating at zero over `ids`.
+fn lagrange(id: ParticipantId, ids: impl Iterator<Item = ParticipantId>) -> Scalar {
+    let x = Scalar::from(id as u64);
+    let (num, den) = ids
+        .filter(|other| *other != id)
+        .map(|other| Scalar::from(other as u64))
+        .fold((Scalar::ONE, Scalar::ONE), |(num, den), other| {
+            (num * other, den * (other - x))
+        });
+
+    num * den.invert().unwrap()
+}
+
+fn proof_msg(id: ParticipantId, commitment: &PublicKey) -> HashMsg {
+    HashMsg(tagged_hash(PROOF_TAG, &[&id.to_be_bytes(), &commitment.0]))
+}
+
+/// The commitments to the polynomial of a participant, broadcast to every other participant in
+/// the first round of the key generation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DkgCommitment {
+    pub id: ParticipantId,
+    /// The commitments to the coefficients of the polynomial, starting with the secret.
+    pub commitments: Vec<PublicKey>,
+    /// The proof that the participant knows the secret committed to.
+    pub proof: SchnorrSignature,
+}
+
+/// The share of the secret of a participant, sent privately to another participant in the second
+/// round of the key generation.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DkgShare {
+    pub from: ParticipantId,
+    pub to: ParticipantId,
+    pub value: [u8; PRIVATE_KEY_SIZE],
+}
+
+impl std::fmt::Debug for DkgShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DkgShare")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state of a participant during the distributed key generation.
+#[derive(Clone)]
+pub struct Dkg {
+    id: ParticipantId,
+    threshold: u16,
+    participants: u16,
+    coefficients: Vec<Scalar>,
+}
+
+impl std::fmt::Debug for Dkg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dkg")
+            .field("id", &self.id)
+            .field("threshold", &self.threshold)
+            .field("participants", &self.participants)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Dkg {
+    /// Starts the key generation as the participant `id` of `participants`, of which `threshold`
+    /// are needed to sign. The commitment must be broadcast to every other participant.
+    pub fn new(
+        id: ParticipantId,
+        threshold: u16,
+        participants: u16,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<(Self, DkgCommitment), ThresholdError> {
+        if threshold == 0 || threshold > participants || id == 0 || id > participants {
+            return Err(ThresholdError::InvalidParams);
+        }
+
+        let coefficients: Vec<_> = (0..threshold).map(|_| Scalar::random(&mut *rng)).collect();
+        let commitments = coefficients
+            .iter()
+            .map(|coefficient| to_public(&(ProjectivePoint::GENERATOR * coefficient)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ThresholdError::InvalidParams)?;
+
+        let secret = PrivateKey::parse(&coefficients[0].to_repr())
+            .map_err(|_| ThresholdError::InvalidParams)?;
+        let proof = secret.sign_schnorr(proof_msg(id, &commitments[0]));
+
+        let dkg = Self {
+            id,
+            threshold,
+            participants,
+            coefficients,
+        };
+        Ok((
+            dkg,
+            DkgCommitment {
+                id,
+                commitments,
+                proof,
+            },
+        ))
+    }
+    /// Returns the share of the secret of this participant for the participant `to`, which must
+    /// be sent to it privately. Fails if `to` is not one of the participants: the polynomial
+    /// evaluated at 0 is the secret itself.
+    pub fn share(&self, to: ParticipantId) -> Result<DkgShare, ThresholdError> {
+        if to == 0 || to > self.participants {
+            return Err(ThresholdError::InvalidParams);
+        }
+
+        Ok(DkgShare {
+            from: self.id,
+            to,
+            value: evaluate(&self.coefficients, to).to_repr().into(),
+        })
+    }
+    /// Finishes the key generation, given the commitments of every participant and the shares the
+    /// other participants sent to this one.
+    pub fn finish(
+        self,
+        commitments: &[DkgCommitment],
+        shares: &[DkgShare],
+    ) -> Result<KeyShare, ThresholdError> {
+        let mut group = ProjectivePoint::IDENTITY;
+        let mut secret = Scalar::ZERO;
+
+        for from in 1..=self.participants {
+            let commitment = commitments
+                .iter()
+                .find(|commitment| commitment.id == from)
+                .ok_or(ThresholdError::MissingParticipant(from))?;
+
+            let points = commitment
+                .commitments
+                .iter()
+                .map(to_point)
+                .collect::<Option<Vec<_>>>()
+                .filter(|points| points.len() == self.threshold as usize)
+                .ok_or(ThresholdError::InvalidCommitment(from))?;
+            if !to_even(&commitment.commitments[0]).valid_schnorr(
+                proof_msg(from, &commitment.commitments[0]),
+                &commitment.proof,
+            ) {
+                return Err(ThresholdError::InvalidCommitment(from));
+            }
+
+            let share = match from == self.id {
+                true => evaluate(&self.coefficients, self.id),
+                false => shares
+                    .iter()
+                    .find(|share| share.from == from && share.to == self.id)
+                    .ok_or(ThresholdError::MissingParticipant(from))
+                    .and_then(|share| {
+                        to_scalar(&share.value).ok_or(ThresholdError::InvalidShare(from))
+                    })?,
+            };
+
+            // the share must lie on the committed polynomial
+            let x = Scalar::from(self.id as u64);
+            let expected = points
+                .iter()
+                .rev()
+                .fold(ProjectivePoint::IDENTITY, |acc, point| acc * x + point);
+            if ProjectivePoint::GENERATOR * share != expected {
+                return Err(ThresholdError::InvalidShare(from));
+            }
+
+            group += points[0];
+            secret += share;
+        }
+
+        // BIP340 signatures only verify against keys with an even y, so every participant negates
+        // the group key and its share of the secret if the group key has an odd y
+        let (group, secret) = match bool::from(group.to_affine().y_is_odd()) {
+            true => (-group, -secret),
+            false => (group, secret),
+        };
+
+        Ok(KeyShare {
+            id: self.id,
+            threshold: self.threshold,
+            secret,
+            group_key: to_public(&group).ok_or(ThresholdError::InvalidParams)?,
+        })
+    }
+}
+
+/// The share of a group key held by a participant, produced by [`Dkg::finish`].
+#[derive(Clone)]
+pub struct KeyShare {
+    id: ParticipantId,
+    threshold: u16,
+    secret: Scalar,
+    group_key: PublicKey,
+}
+
+impl std::fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("id", &self.id)
+            .field("threshold", &self.threshold)
+            .field("group_key", &self.group_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The nonces of a signer for a single signature. Must never be reused.
+pub struct SigningNonces {
+    id: ParticipantId,
+    hiding: Scalar,
+    binding: Scalar,
+}
+
+impl std::fmt::Debug for SigningNonces {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningNonces")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The commitments to the [`SigningNonces`] of a signer, sent to every other signer before
+/// signing.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NonceCommitment {
+    pub id: ParticipantId,
+    pub hiding: PublicKey,
+    pub binding: PublicKey,
+}
+
+/// The share of a signature created by a signer, which are aggregated into the signature.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SignatureShare {
+    pub id: ParticipantId,
+    pub value: [u8; PRIVATE_KEY_SIZE],
+}
+
+/// The values every signer derives from the commitments of the signers.
+struct SigningPackage {
+    /// The x coordinate of the nonce of the signature.
+    nonce_x: [u8; 32],
+    /// Whether the nonces must be negated, so that the nonce of the signature has an even y.
+    negate_nonces: bool,
+    challenge: Scalar,
+    /// The binding factor of each signer.
+    binding: Vec<(ParticipantId, Scalar)>,
+}
+
+impl SigningPackage {
+    fn new(
+        group_key: &PublicKey,
+        msg: &HashMsg,
+        commitments: &[NonceCommitment],
+    ) -> Result<Self, ThresholdError> {
+        let mut encoded = Vec::with_capacity(commitments.len() * 68);
+        for (index, commitment) in commitments.iter().enumerate() {
+            // the commitments must be sorted, so that every signer derives the same values
+            if index > 0 && commitments[index - 1].id >= commitment.id {
+                return Err(ThresholdError::InvalidParams);
+            }
+            encoded.extend_from_slice(&commitment.id.to_be_bytes());
+            encoded.extend_from_slice(&commitment.hiding.0);
+            encoded.extend_from_slice(&commitment.binding.0);
+        }
+
+        let mut nonce = ProjectivePoint::IDENTITY;
+        let mut binding = Vec::with_capacity(commitments.len());
+        for commitment in commitments {
+            let factor = hash_to_scalar(tagged_hash(
+                BINDING_TAG,
+                &[&group_key.0, &msg.0, &encoded, &commitment.id.to_be_bytes()],
+            ));
+            let hiding = to_point(&commitment.hiding);
+            let binding_point = to_point(&commitment.binding);
+            let (hiding, binding_point) = hiding
+                .zip(binding_point)
+                .ok_or(ThresholdError::InvalidCommitment(commitment.id))?;
+
+            nonce += hiding + binding_point * factor;
+            binding.push((commitment.id, factor));
+        }
+
+        let nonce = nonce.to_affine();
+        let nonce_x: [u8; 32] = nonce.x().into();
+        let challenge = hash_to_scalar(tagged_hash(
+            CHALLENGE_TAG,
+            &[&nonce_x, &group_key.0[1..], &msg.0],
+        ));
+
+        Ok(Self {
+            nonce_x,
+            negate_nonces: nonce.y_is_odd().into(),
+            challenge,
+            binding,
+        })
+    }
+}
+
+impl KeyShare {
+    /// Returns the id of the participant holding this share.
+    pub fn id(&self) -> ParticipantId {
+        self.id
+    }
+    /// Returns the amount of participants needed to sign.
+    pub fn threshold(&self) -> u16 {
+        self.threshold
+    }
+    /// Returns the public key of the group, that signatures verify against. Always has an even y.
+    pub fn group_key(&self) -> PublicKey {
+        self.group_key
+    }
+    /// Generates the nonces of this signer for a single signature. The commitment must be sent to
+    /// every other signer.
+    pub fn commit(&self, rng: &mut (impl RngCore + CryptoRng)) -> (SigningNonces, NonceCommitment) {
+        let nonces = SigningNonces {
+            id: self.id,
+            hiding: Scalar::random(&mut *rng),
+            binding: Scalar::random(&mut *rng),
+        };
+        let commitment = NonceCommitment {
+            id: self.id,
+            hiding: to_public(&(ProjectivePoint::GENERATOR * nonces.hiding))
+                .expect("random nonces are not zero"),
+            binding: to_public(&(ProjectivePoint::GENERATOR * nonces.binding))
+                .expect("random nonces are not zero"),
+        };
+
+        (nonces, commitment)
+    }
+    /// Creates the share of this signer of the signature over `msg`. `commitments` are the
+    /// commitments of every signer sorted by their ids, including this one.
+    pub fn sign(
+        &self,
+        nonces: SigningNonces,
+        msg: impl ToHashMsg,
+        commitments: &[NonceCommitment],
+    ) -> Result<SignatureShare, ThresholdError> {
+        if nonces.id != self.id || commitments.len() < self.threshold as usize {
+            return Err(ThresholdError::InvalidParams);
+        }
+
+        let msg = *msg.to_hash_msg().as_ref();
+        let package = SigningPackage::new(&self.group_key, &msg, commitments)?;
+        let binding = package
+            .binding
+            .iter()
+            .find(|(id, _)| *id == self.id)
+            .ok_or(ThresholdError::MissingParticipant(self.id))?
+            .1;
+
+        // BIP340 signs with the keys and nonces whose points have an even y
+        let (hiding, binding_nonce) = match package.negate_nonces {
+            true => (-nonces.hiding, -nonces.binding),
+            false => (nonces.hiding, nonces.binding),
+        };
+        let lambda = lagrange(self.id, commitments.iter().map(|commitment| commitment.id));
+
+        let value = hiding + binding_nonce * binding + lambda * package.challenge * self.secret;
+        Ok(SignatureShare {
+            id: self.id,
+            value: value.to_repr().into(),
+        })
+    }
+}
+
+/// Aggregates the shares of the signers into a signature over `msg`, that verifies against
+/// `group_key` as a [`SignatureScheme::Schnorr`] signature.
+pub fn aggregate(
+    group_key: &PublicKey,
+    msg: impl ToHashMsg,
+    commitments: &[NonceCommitment],
+    shares: &[SignatureShare],
+) -> Result<SchnorrSignature, ThresholdError> {
+    let msg = *msg.to_hash_msg().as_ref();
+    let package = SigningPackage::new(group_key, &msg, commitments)?;
+
+    let mut value = Scalar::ZERO;
+    for (id, _) in package.binding.iter() {
+        let share = shares
+            .iter()
+            .find(|share| share.id == *id)
+            .ok_or(ThresholdError::MissingParticipant(*id))?;
+        value += to_scalar(&share.value).ok_or(ThresholdError::InvalidShare(*id))?;
+    }
+
+    let mut signature = [0u8; SIGNATURE_SIZE];
+    signature[..32].copy_from_slice(&package.nonce_x);
+    signature[32..].copy_from_slice(&value.to_repr());
+    let signature = SchnorrSignature(signature);
+
+    let signed = SignedHash {
+        hash: msg,
+        scheme: SignatureScheme::Schnorr,
+    };
+    match group_key.valid(signed, &Signature::from(signature)) {
+        true => Ok(signature),
+        false => Err(ThresholdError::InvalidSignature),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+
+    fn generate(threshold: u16, participants: u16) -> Vec<KeyShare> {
+        let (dkgs, commitments): (Vec<_>, Vec<_>) = (1..=participants)
+            .map(|id| Dkg::new(id, threshold, participants, &mut thread_rng()).unwrap())
+            .unzip();
+        let shares: Vec<_> = dkgs
+            .iter()
+            .flat_map(|dkg| (1..=participants).map(|to| dkg.share(to).unwrap()))
+            .collect();
+
+        dkgs.into_iter()
+            .map(|dkg| dkg.finish(&commitments, &shares).unwrap())
+            .collect()
+    }
+
+    fn sign(signers: &[&KeyShare], msg: &[u8]) -> Result<SchnorrSignature, ThresholdError> {
+        let (nonces, commitments): (Vec<_>, Vec<_>) = signers
+            .iter()
+            .map(|share| share.commit(&mut thread_rng()))
+            .unzip();
+        let shares = signers
+            .iter()
+            .zip(nonces)
+            .map(|(share, nonces)| share.sign(nonces, msg, &commitments))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        aggregate(&signers[0].group_key(), msg, &commitments, &shares)
+    }
+
+    #[test]
+    fn threshold() {
+        // generate several keys, so that groups with both an even and odd y are checked
+        for _ in 0..4 {
+            let shares = generate(2, 3);
+            let group_key = shares[0].group_key();
+            assert!(shares.iter().all(|share| share.group_key() == group_key));
+            assert_eq!(group_key.0[0], 0x02);
+
+            let signature = sign(&[&shares[0], &shares[2]], b"msg").unwrap();
+            assert!(group_key.valid_schnorr(b"msg", &signature));
+            assert!(!group_key.valid_schnorr(b"other", &signature));
+
+            // the signature does not verify against the x coordinate of the group under any other
+            // prefix
+            for prefix in [0x03, 0x07, 0xff] {
+                let mut rewritten = group_key;
+                rewritten.0[0] = prefix;
+                assert!(!rewritten.valid_schnorr(b"msg", &signature));
+            }
+
+            let signature = sign(&[&shares[1], &shares[2]], b"msg").unwrap();
+            assert!(group_key.valid_schnorr(b"msg", &signature));
+
+            // too few signers
+            assert_eq!(
+                sign(&[&shares[1]], b"msg"),
+                Err(ThresholdError::InvalidParams)
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_share() {
+        let (dkgs, commitments): (Vec<_>, Vec<_>) = (1..=2)
+            .map(|id| Dkg::new(id, 2, 2, &mut thread_rng()).unwrap())
+            .unzip();
+        let mut share = dkgs[1].share(1).unwrap();
+        share.value = dkgs[1].share(2).unwrap().value;
+        // the secret itself is never handed out, nor shares for strangers
+        assert_eq!(dkgs[1].share(0), Err(ThresholdError::InvalidParams));
+        assert_eq!(dkgs[1].share(3), Err(ThresholdError::InvalidParams));
+        assert!(!format!("{:?}", dkgs[1]).contains("coefficients"));
+
+        assert_eq!(
+            dkgs.into_iter()
+                .next()
+                .unwrap()
+                .finish(&commitments, &[share])
+                .unwrap_err(),
+            ThresholdError::InvalidShare(2)
+        );
+    }
+}
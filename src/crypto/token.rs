@@ -0,0 +1,160 @@
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use subtle::{Choice, ConstantTimeEq};
+
+use super::{error::TokenError, HexOrBytes, Mac, MAC_KEY_SIZE};
+
+/// The size (in bytes) of the random nonce of a token.
+pub const TOKEN_NONCE_SIZE: usize = 16;
+
+/// What a [`Token`] was issued for. A token issued for one subsystem is never accepted by another.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TokenScope {
+    Resumption,
+    Dial,
+    Invite,
+}
+
+impl TokenScope {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Resumption => 0,
+            Self::Dial => 1,
+            Self::Invite => 2,
+        }
+    }
+}
+
+/// A bearer token authenticated with a keyed hash, that only the holder of the key it was issued
+/// with can issue or check. The expiry and scope of the token are covered by its code.
+///
+/// Tokens are compared in constant time.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialOrd, Ord)]
+pub struct Token {
+    pub scope: TokenScope,
+    /// The expiration timestamp of the token.
+    #[serde(rename = "expireTime")]
+    pub expire_time: u64,
+    #[serde_as(as = "HexOrBytes")]
+    pub nonce: [u8; TOKEN_NONCE_SIZE],
+    pub mac: Mac,
+}
+
+impl Token {
+    /// Issues a token for `scope` that expires at `expire_time`.
+    pub fn issue(key: &[u8; MAC_KEY_SIZE], scope: TokenScope, expire_time: u64) -> Self {
+        Self::issue_with(&mut crate::utils::rng(), key, scope, expire_time)
+    }
+    /// Issues a token for `scope` that expires at `expire_time`, using `rng` for the nonce.
+    pub fn issue_with(
+        rng: &mut (impl RngCore + CryptoRng),
+        key: &[u8; MAC_KEY_SIZE],
+        scope: TokenScope,
+        expire_time: u64,
+    ) -> Self {
+        let mut nonce = [0u8; TOKEN_NONCE_SIZE];
+        rng.fill_bytes(&mut nonce);
+
+        Self {
+            scope,
+            expire_time,
+            nonce,
+            mac: Mac::new(key, Self::message(scope, expire_time, &nonce)),
+        }
+    }
+    /// Checks that this token was issued with `key` for `scope`, and has not expired at the time
+    /// `now`.
+    pub fn verify(
+        &self,
+        key: &[u8; MAC_KEY_SIZE],
+        scope: TokenScope,
+        now: u64,
+    ) -> Result<(), TokenError> {
+        if !self.mac.verify(
+            key,
+            Self::message(self.scope, self.expire_time, &self.nonce),
+        ) {
+            return Err(TokenError::InvalidMac);
+        }
+        if self.scope != scope {
+            return Err(TokenError::WrongScope);
+        }
+        if now > self.expire_time {
+            return Err(TokenError::Expired);
+        }
+
+        Ok(())
+    }
+
+    fn message(
+        scope: TokenScope,
+        expire_time: u64,
+        nonce: &[u8; TOKEN_NONCE_SIZE],
+    ) -> [u8; 1 + 8 + TOKEN_NONCE_SIZE] {
+        let mut message = [0u8; 1 + 8 + TOKEN_NONCE_SIZE];
+        message[0] = scope.to_byte();
+        message[1..9].copy_from_slice(&expire_time.to_be_bytes());
+        message[9..].copy_from_slice(nonce);
+        message
+    }
+}
+
+impl ConstantTimeEq for Token {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.scope.to_byte().ct_eq(&other.scope.to_byte())
+            & self.expire_time.ct_eq(&other.expire_time)
+            & self.nonce.ct_eq(&other.nonce)
+            & self.mac.ct_eq(&other.mac)
+    }
+}
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl Eq for Token {}
+impl std::hash::Hash for Token {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.scope.hash(state);
+        self.expire_time.hash(state);
+        self.nonce.hash(state);
+        self.mac.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify() {
+        let key = [3u8; MAC_KEY_SIZE];
+        let token = Token::issue(&key, TokenScope::Dial, 100);
+
+        assert_eq!(token.verify(&key, TokenScope::Dial, 50), Ok(()));
+        assert_eq!(
+            token.verify(&key, TokenScope::Dial, 150),
+            Err(TokenError::Expired)
+        );
+        assert_eq!(
+            token.verify(&key, TokenScope::Resumption, 50),
+            Err(TokenError::WrongScope)
+        );
+        assert_eq!(
+            token.verify(&[4u8; MAC_KEY_SIZE], TokenScope::Dial, 50),
+            Err(TokenError::InvalidMac)
+        );
+
+        // the expiry cannot be extended without the key
+        let mut extended = token;
+        extended.expire_time = 200;
+        assert_eq!(
+            extended.verify(&key, TokenScope::Dial, 150),
+            Err(TokenError::InvalidMac)
+        );
+        assert_ne!(token, Token::issue(&key, TokenScope::Dial, 100));
+    }
+}
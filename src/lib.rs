@@ -1,9 +1,24 @@
 #![allow(unreachable_patterns)]
 
+// The modules that handle the messages of peers must not panic on them. Unwraps that cannot fail
+// are spelled out as `expect`, with the reason they cannot.
+#[deny(clippy::unwrap_used)]
+pub mod client;
+#[deny(clippy::unwrap_used)]
+pub mod codec;
+pub mod conformance;
+#[deny(clippy::unwrap_used)]
 pub mod crypto;
 pub mod mock;
+#[deny(clippy::unwrap_used)]
 pub mod node;
+#[deny(clippy::unwrap_used)]
 pub mod obj;
+pub mod resolve;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
 #[cfg(test)]
 mod tests;
 mod utils;
@@ -0,0 +1,171 @@
+use std::error::Error as StdError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::Future;
+use thiserror::Error;
+use tower_async::Service;
+
+use crate::{
+    crypto::KeyTriad,
+    node::{error::StreamOpenError, error::StreamOpenErrorType, Notify, OpenStream},
+    obj::{Introduction, Mail, Receipt, SignedData},
+};
+
+/// The faults injected into the requests sent through a [`Chaos`] connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Faults {
+    /// Whether the server is unreachable. Requests fail with [`ChaosError::Dropped`].
+    pub dropped: bool,
+    /// How long each request is held before it is sent.
+    pub delay: Duration,
+    /// The amount of extra copies of each request that are sent before it. Their responses are
+    /// discarded.
+    pub duplicates: u32,
+}
+
+/// Changes the faults of a [`Chaos`] connection while it is in use.
+#[derive(Debug, Clone, Default)]
+pub struct ChaosControl {
+    faults: Arc<Mutex<Faults>>,
+    delivered: Arc<AtomicU64>,
+}
+
+impl ChaosControl {
+    /// Returns the current faults.
+    pub fn faults(&self) -> Faults {
+        *self.faults.lock().unwrap()
+    }
+    pub fn set(&self, faults: Faults) {
+        *self.faults.lock().unwrap() = faults;
+    }
+    /// Makes the server unreachable.
+    pub fn drop_peer(&self) {
+        self.faults.lock().unwrap().dropped = true;
+    }
+    pub fn delay(&self, delay: Duration) {
+        self.faults.lock().unwrap().delay = delay;
+    }
+    pub fn duplicate(&self, duplicates: u32) {
+        self.faults.lock().unwrap().duplicates = duplicates;
+    }
+    /// Removes every fault.
+    pub fn restore(&self) {
+        self.set(Faults::default());
+    }
+    /// Returns the amount of requests that reached the server, including duplicates.
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+}
+
+/// An error returned by a [`Chaos`] connection.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChaosError<E: StdError> {
+    /// The server was made unreachable by [`ChaosControl::drop_peer`].
+    #[error("the server is unreachable")]
+    Dropped,
+    #[error("{}", .0)]
+    Inner(E),
+}
+
+impl<E: StreamOpenError> StreamOpenError for ChaosError<E> {
+    fn error_type(&self) -> Option<StreamOpenErrorType> {
+        match self {
+            Self::Dropped => None,
+            Self::Inner(err) => err.error_type(),
+        }
+    }
+}
+
+/// Wraps the connection to a federated server, and injects the faults set through its
+/// [`ChaosControl`] into the requests sent to it. Notifications are passed through unchanged.
+#[derive(Debug)]
+pub struct Chaos<C> {
+    inner: C,
+    control: ChaosControl,
+}
+
+impl<C> Chaos<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            control: Default::default(),
+        }
+    }
+    /// Returns the control of the faults of this connection.
+    pub fn control(&self) -> &ChaosControl {
+        &self.control
+    }
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C: Service<Req, Error: StdError>, Req: Clone> Service<Req> for Chaos<C> {
+    type Response = C::Response;
+    type Error = ChaosError<C::Error>;
+
+    async fn call(&self, req: Req) -> Result<Self::Response, Self::Error> {
+        let faults = self.control.faults();
+        if !faults.delay.is_zero() {
+            tokio::time::sleep(faults.delay).await;
+        }
+        if faults.dropped {
+            return Err(ChaosError::Dropped);
+        }
+
+        for _ in 0..faults.duplicates {
+            self.control.delivered.fetch_add(1, Ordering::Relaxed);
+            let _ = self.inner.call(req.clone()).await;
+        }
+        self.control.delivered.fetch_add(1, Ordering::Relaxed);
+        self.inner.call(req).await.map_err(ChaosError::Inner)
+    }
+}
+
+impl<C: OpenStream> OpenStream for Chaos<C> {
+    type Err = ChaosError<<C as OpenStream>::Err>;
+}
+
+impl<C: Notify> Notify for Chaos<C> {
+    type Err = C::Err;
+
+    fn notify_connected(
+        &self,
+        triad: &KeyTriad<SignedData>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.inner.notify_connected(triad)
+    }
+    fn notify_introduced(
+        &self,
+        intro: &Introduction,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.inner.notify_introduced(intro)
+    }
+    fn notify_revoked(
+        &self,
+        revocation: &KeyTriad<SignedData>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.inner.notify_revoked(revocation)
+    }
+    fn notify_receipt(
+        &self,
+        receipt: &KeyTriad<Receipt>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.inner.notify_receipt(receipt)
+    }
+    fn notify_mail(
+        &self,
+        mail: &Mail,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.inner.notify_mail(mail)
+    }
+    fn notify_broadcast(
+        &self,
+        payload: &KeyTriad<SignedData>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.inner.notify_broadcast(payload)
+    }
+}
@@ -1,30 +1,77 @@
+mod chaos;
 mod stream;
 
+pub use chaos::*;
 use futures::Future;
 pub use stream::*;
 use tokio::sync::mpsc;
 
-use crate::{crypto::{KeyTriad, PublicKey}, node::Notify, obj::SignedData};
+use crate::{
+    crypto::{KeyTriad, PublicKey},
+    node::Notify,
+    obj::{Introduction, Mail, Receipt, SignedData},
+};
+
+/// A message pushed by the node to a [`MockNotify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockPush {
+    Connected(KeyTriad<SignedData>),
+    Introduced(Introduction),
+    Revoked(KeyTriad<SignedData>),
+    Receipt(KeyTriad<Receipt>),
+    Mail(Mail),
+    Broadcast(KeyTriad<SignedData>),
+}
 
 #[derive(Clone, Debug)]
 pub struct MockNotify {
-    send: mpsc::Sender<KeyTriad<SignedData>>,
+    send: mpsc::Sender<MockPush>,
 }
 
 impl Notify for MockNotify {
-    type Err = mpsc::error::SendError<KeyTriad<SignedData>>;
+    type Err = mpsc::error::SendError<MockPush>;
 
     fn notify_connected(
         &self,
         triad: &KeyTriad<SignedData>,
     ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
-        self.send.send(triad.clone())
+        self.send.send(MockPush::Connected(triad.clone()))
+    }
+    fn notify_introduced(
+        &self,
+        intro: &Introduction,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.send.send(MockPush::Introduced(*intro))
+    }
+    fn notify_revoked(
+        &self,
+        revocation: &KeyTriad<SignedData>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.send.send(MockPush::Revoked(revocation.clone()))
+    }
+    fn notify_receipt(
+        &self,
+        receipt: &KeyTriad<Receipt>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.send.send(MockPush::Receipt(*receipt))
+    }
+    fn notify_mail(
+        &self,
+        mail: &Mail,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.send.send(MockPush::Mail(mail.clone()))
+    }
+    fn notify_broadcast(
+        &self,
+        payload: &KeyTriad<SignedData>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        self.send.send(MockPush::Broadcast(payload.clone()))
     }
 }
 
+#[allow(dead_code)]
 pub struct MockConnection {
     notify: MockNotify,
-    notify_recv: mpsc::Receiver<KeyTriad<SignedData>>,
+    notify_recv: mpsc::Receiver<MockPush>,
     stream_opener: mpsc::Sender<(PublicKey, MockWrite, MockRead)>,
-    
-}
\ No newline at end of file
+}
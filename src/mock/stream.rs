@@ -84,7 +84,7 @@ impl AsyncRead for MockRead {
     ) -> Poll<IoResult<()>> {
         if self.to_read() == 0 {
             let bytes = match self.recv.poll_recv(cx) {
-                Poll::Ready(bytes) => bytes.ok_or_else(|| shutdown_err())?,
+                Poll::Ready(bytes) => bytes.ok_or_else(shutdown_err)?,
                 Poll::Pending => return Poll::Pending,
             };
 
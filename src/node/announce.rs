@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use tower_async::Service;
+
+use super::*;
+
+impl<C: Notify + Send + Sync + 'static + ?Sized> ServerHandle<C> {
+    /// Pushes `payload` to every endpoint identified to this node, or only to the endpoints that
+    /// are servers of `domain` if set. Returns the amount of endpoints it was pushed to.
+    pub async fn broadcast(&self, payload: &KeyTriad<SignedData>, domain: Option<&str>) -> usize {
+        // an endpoint identified as several keys receives the payload once
+        let mut endpoints = HashMap::new();
+        self.key_to_endpoint
+            .scan_async(|_, endpoint| {
+                endpoints.insert(endpoint.id, endpoint.clone());
+            })
+            .await;
+        if let Some(domain) = domain {
+            endpoints.retain(|_, endpoint| {
+                endpoint
+                    .info
+                    .server_info
+                    .as_ref()
+                    .is_some_and(|info| info.domain == domain)
+            });
+        }
+
+        let recipients = endpoints.len();
+        for (id, endpoint) in endpoints {
+            let payload = payload.clone();
+            // Fire and forget the notification
+            self.fanout.submit(id, async move {
+                let _ = endpoint.conn.notify_broadcast(&payload).await;
+            });
+        }
+
+        recipients
+    }
+}
+
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<BroadcastReq> for InboundEndpoint<C> {
+    type Response = BroadcastResp;
+    type Error = BroadcastReqError;
+
+    async fn call(&self, req: BroadcastReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // only federated servers can reach every endpoint at once
+        let Some(server_info) = &self.info.server_info else {
+            return Err(BroadcastReqError::NotPeer);
+        };
+        // and only in their own name, with the node key they identified as
+        let signer = &req.payload.public_key;
+        if server_info.public_key.is_some_and(|key| key != *signer)
+            || !self.identities.contains_async(signer).await
+        {
+            return Err(BroadcastReqError::InvalidPublicKey);
+        }
+        server_hdl
+            .config
+            .size_limits
+            .check_payload(&req.payload.signed)?;
+        WrongMessageTypeError::check(SignMessageType::AppMessage, &req.payload.signed)?;
+        if !req
+            .payload
+            .public_key
+            .valid(&req.payload.signed, &req.payload.signature)
+        {
+            return Err(BroadcastReqError::SignatureInvalid);
+        }
+
+        let recipients = server_hdl
+            .broadcast(&req.payload, req.domain.as_deref())
+            .await;
+        Ok(BroadcastResp {
+            recipients: recipients as u32,
+        })
+    }
+}
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<BroadcastReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<BroadcastReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<BroadcastReq>>::Error;
+
+    fn call(&self, req: BroadcastReq) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
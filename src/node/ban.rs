@@ -0,0 +1,230 @@
+use std::net::IpAddr;
+
+use super::*;
+
+/// A range of IP addresses that share their first `prefix` bits, such as `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    /// Creates the range of the addresses that share the first `prefix` bits of `addr`. Returns
+    /// [`None`] if `prefix` is longer than the addresses of the family of `addr`.
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<Self> {
+        let addr = match addr {
+            IpAddr::V4(addr) if prefix <= 32 => {
+                IpAddr::V4((u32::from(addr) & mask(prefix, 32) as u32).into())
+            }
+            IpAddr::V6(addr) if prefix <= 128 => {
+                IpAddr::V6((u128::from(addr) & mask(prefix, 128)).into())
+            }
+            _ => return None,
+        };
+        Some(Self { addr, prefix })
+    }
+    /// Returns the first address of this range.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+    /// Returns the amount of bits the addresses of this range share.
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+    /// Returns whether `ip` is in this range. IPv4 addresses mapped to IPv6 are compared as IPv4
+    /// addresses.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                u32::from(ip) & mask(self.prefix, 32) as u32 == u32::from(addr)
+            }
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                u128::from(ip) & mask(self.prefix, 128) == u128::from(addr)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns the mask of the first `prefix` bits of an address of `bits` bits.
+fn mask(prefix: u8, bits: u32) -> u128 {
+    match prefix {
+        0 => 0,
+        prefix => (u128::MAX << (128 - prefix as u32)) >> (128 - bits),
+    }
+}
+
+/// What a [`Ban`] applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+    /// A public key, that cannot identify or communicate while banned.
+    Key(PublicKey),
+    /// An IP address, that cannot connect, identify or communicate while banned.
+    Ip(IpAddr),
+    /// Every IP address of a range.
+    Range(IpRange),
+}
+
+/// A ban of a [`BanList`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ban {
+    pub target: BanTarget,
+    /// When the ban is lifted, in milliseconds since the unix epoch. Is [`None`] if the ban lasts
+    /// until it is removed.
+    pub expire_time: Option<u64>,
+}
+
+impl Ban {
+    fn is_active(expire_time: Option<u64>, now: u64) -> bool {
+        expire_time.is_none_or(|expire_time| now <= expire_time)
+    }
+}
+
+/// The public keys, IP addresses and ranges of IP addresses a node refuses to serve, so that
+/// operators can act on abuse. Refer to [`ServerHandle::bans`].
+///
+/// Bans that expired are ignored, and cleared by [`ServerHandle::reap`].
+#[derive(Debug, Default)]
+pub struct BanList {
+    keys: scc::HashMap<PublicKey, Option<u64>>,
+    ips: scc::HashMap<IpAddr, Option<u64>>,
+    ranges: std::sync::Mutex<Vec<(IpRange, Option<u64>)>>,
+}
+
+impl BanList {
+    /// Bans `target` until `expire_time`, or until it is removed if [`None`]. Replaces the
+    /// expiry of a ban of the same target.
+    pub async fn insert(&self, target: BanTarget, expire_time: Option<u64>) {
+        match target {
+            BanTarget::Key(key) => {
+                *self.keys.entry_async(key).await.or_default().get_mut() = expire_time;
+            }
+            BanTarget::Ip(ip) => {
+                *self
+                    .ips
+                    .entry_async(ip.to_canonical())
+                    .await
+                    .or_default()
+                    .get_mut() = expire_time;
+            }
+            BanTarget::Range(range) => {
+                let mut ranges = self.ranges.locked();
+                match ranges.iter_mut().find(|(banned, _)| *banned == range) {
+                    Some((_, expiry)) => *expiry = expire_time,
+                    None => ranges.push((range, expire_time)),
+                }
+            }
+        }
+    }
+    /// Lifts the ban of `target`. Returns whether it was banned.
+    pub async fn remove(&self, target: &BanTarget) -> bool {
+        match target {
+            BanTarget::Key(key) => self.keys.remove_async(key).await.is_some(),
+            BanTarget::Ip(ip) => self.ips.remove_async(&ip.to_canonical()).await.is_some(),
+            BanTarget::Range(range) => {
+                let mut ranges = self.ranges.locked();
+                let len = ranges.len();
+                ranges.retain(|(banned, _)| banned != range);
+                ranges.len() != len
+            }
+        }
+    }
+    /// Returns the bans that did not expire.
+    pub async fn list(&self) -> Vec<Ban> {
+        let now = utils::now();
+        let mut bans = Vec::new();
+        let mut push = |target, expire_time| {
+            if Ban::is_active(expire_time, now) {
+                bans.push(Ban {
+                    target,
+                    expire_time,
+                });
+            }
+        };
+
+        self.keys
+            .scan_async(|key, expire_time| push(BanTarget::Key(*key), *expire_time))
+            .await;
+        self.ips
+            .scan_async(|ip, expire_time| push(BanTarget::Ip(*ip), *expire_time))
+            .await;
+        for (range, expire_time) in self.ranges.locked().iter() {
+            push(BanTarget::Range(*range), *expire_time);
+        }
+        bans
+    }
+    /// Returns whether `key` is banned.
+    pub async fn is_key_banned(&self, key: &PublicKey) -> bool {
+        let now = utils::now();
+        self.keys
+            .read_async(key, |_, expire_time| Ban::is_active(*expire_time, now))
+            .await
+            .unwrap_or(false)
+    }
+    /// Returns whether `ip` is banned, by itself or by a range.
+    pub async fn is_ip_banned(&self, ip: IpAddr) -> bool {
+        let now = utils::now();
+        let ip = ip.to_canonical();
+        if self
+            .ips
+            .read_async(&ip, |_, expire_time| Ban::is_active(*expire_time, now))
+            .await
+            .unwrap_or(false)
+        {
+            return true;
+        }
+        self.ranges
+            .locked()
+            .iter()
+            .any(|(range, expire_time)| range.contains(ip) && Ban::is_active(*expire_time, now))
+    }
+    /// Clears the bans that expired before `now`. Returns how many were cleared.
+    pub(crate) async fn reap(&self, now: u64) -> usize {
+        let mut reaped = 0;
+        let mut active = |expire_time: &Option<u64>| {
+            let active = Ban::is_active(*expire_time, now);
+            reaped += !active as usize;
+            active
+        };
+        self.keys
+            .retain_async(|_, expire_time| active(expire_time))
+            .await;
+        self.ips
+            .retain_async(|_, expire_time| active(expire_time))
+            .await;
+        self.ranges
+            .locked()
+            .retain(|(_, expire_time)| active(expire_time));
+        reaped
+    }
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the bans of this node, that operators add to and remove from.
+    pub fn bans(&self) -> &BanList {
+        &self.bans
+    }
+    /// Checks that an endpoint connecting from `ip` may be served. Transports call this before
+    /// they create the [`InboundEndpoint`] of a connection, and close the connection if it fails.
+    pub async fn admit(&self, ip: IpAddr) -> Result<(), BannedError> {
+        match self.bans.is_ip_banned(ip).await {
+            true => Err(BannedError),
+            false => Ok(()),
+        }
+    }
+    /// Checks that neither the endpoint `info` nor any of `keys` is banned.
+    pub(crate) async fn check_bans(
+        &self,
+        info: &EndpointInfo,
+        keys: impl IntoIterator<Item = &PublicKey>,
+    ) -> Result<(), BannedError> {
+        self.admit(info.endpoint.ip()).await?;
+        for key in keys {
+            if self.bans.is_key_banned(key).await {
+                return Err(BannedError);
+            }
+        }
+        Ok(())
+    }
+}
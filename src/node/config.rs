@@ -0,0 +1,420 @@
+use std::time::Duration;
+
+use super::error::{TooLargeError, ValidityError};
+use super::{PoolConfig, RateLimit, RequestLimits, RetentionPolicy};
+use crate::crypto::PublicKey;
+use crate::obj::{IdentifyExtensions, SignedData};
+
+/// Configuration of a node, shared by every endpoint connected to a [`ServerHandle`](super::ServerHandle).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodeConfig {
+    /// How long a [`CommunicationReq`](crate::obj::CommunicationReq) to a recently seen public key
+    /// waits for the key to identify again. Is [`None`] if requests should fail immediately.
+    pub park_timeout: Option<Duration>,
+    /// How recently a public key must have identified for requests to it to be parked.
+    pub recently_seen: Duration,
+    /// The amount of forwarded requests a connected server can fail in a row before it is
+    /// demoted.
+    pub peer_demote_errors: u32,
+    /// How long a demoted server is not forwarded requests for.
+    pub peer_demote_duration: Duration,
+    /// How long a push to every connected server, such as
+    /// [`ServerHandle::propagate_revocation`](super::ServerHandle::propagate_revocation), waits
+    /// for each server before counting it as failed.
+    pub push_timeout: Duration,
+    /// The extensions attached to the identify data handed out by the node, that endpoints must
+    /// sign over to identify.
+    pub identify_extensions: IdentifyExtensions,
+    /// The amount of leading zero bits of the proof of work endpoints must attach to the identify
+    /// data to identify. Is [`None`] if no proof is required.
+    pub identify_difficulty: Option<u8>,
+    /// The amount of worker threads verifying the signatures of identify, revoke and cross-sign
+    /// requests. Is [`None`] if they are verified on the blocking threads of the runtime.
+    pub crypto_threads: Option<usize>,
+    /// The amount of requests on one connection that are processed at the same time. Refer to
+    /// [`Pipeline`](super::Pipeline).
+    pub pipeline_limit: usize,
+    /// How long a public key that a connected server reported as connected to it is remembered.
+    /// While it is, a [`CommunicationReq`](crate::obj::CommunicationReq) to the key opens a stream
+    /// to the server directly. Is [`None`] if reports should not be remembered.
+    pub remote_key_ttl: Option<Duration>,
+    /// The amount of recently verified signatures that are not verified again. Is 0 if
+    /// signatures should always be verified.
+    pub verify_cache_size: usize,
+    /// The limits of the streams pre-established for each key over a link to a connected server.
+    /// Refer to [`ServerHandle::prepare_streams`](super::ServerHandle::prepare_streams).
+    pub peer_streams: PoolConfig,
+    /// The amount of workers that push notifications to endpoints. Endpoints are served in turn,
+    /// so that a slow endpoint cannot hold up the notifications of the others.
+    pub fanout_workers: usize,
+    /// The limits on the messages held for each public key, unless overridden with
+    /// [`ServerHandle::set_retention`](super::ServerHandle::set_retention).
+    pub mail_retention: RetentionPolicy,
+    /// How long the id of an accepted message is remembered. A retry of the message within this
+    /// window is acknowledged without delivering the message again.
+    pub dedupe_window: Duration,
+    /// Whether signed CBOR payloads must be in canonical form. Payloads signed by older clients
+    /// are not, so this is off by default.
+    pub require_canonical: bool,
+    /// The limits on the time windows of the signed objects the node accepts.
+    pub validity: ValidityLimits,
+    /// The limit on the rate of the requests each connection sends on the wire. Requests over
+    /// the limit are rejected with a hint of when to retry. Is [`None`] if not limited.
+    pub rate_limit: Option<RateLimit>,
+    /// The limits on the rate of each type of request of one endpoint. Unlike
+    /// [`NodeConfig::rate_limit`], they also apply to requests that are not received on the wire.
+    pub request_limits: RequestLimits,
+    /// The most public keys one endpoint may identify as. Is [`None`] if an endpoint may identify
+    /// as any amount of keys.
+    pub max_identities: Option<usize>,
+    /// The most public keys the node holds messages for at once. Messages to a key the node holds
+    /// no messages for are rejected once it is reached. Is [`None`] if any amount of keys may be
+    /// sent messages.
+    pub max_mailboxes: Option<usize>,
+    /// The amount of recent changes to the set of identified public keys that are held for
+    /// federated servers to catch up on. A server further behind fetches the whole set.
+    pub gossip_history: usize,
+    /// The amount of keys within a range at which a reconciliation with a connected server sends
+    /// the keys, rather than splitting the range further.
+    pub reconcile_leaf_size: usize,
+    /// What a [`ListConnectedServersReq`](crate::obj::ListConnectedServersReq) reveals about the
+    /// connected servers, depending on who asks.
+    pub list_disclosure: DisclosurePolicy,
+    /// The greatest depth of a [`KeysExistsRReq`](crate::obj::KeysExistsRReq) the node accepts,
+    /// and the most servers a [`CommunicationReq`](crate::obj::CommunicationReq) may be relayed
+    /// through. Deeper requests are rejected, so that a server cannot make a request travel
+    /// indefinitely.
+    pub max_forward_depth: u32,
+    /// The limits on the size of the requests the node accepts.
+    pub size_limits: SizeLimits,
+    /// The estimated memory above which the node sheds work that would hold more. Refer to
+    /// [`ServerHandle::memory_usage`](super::ServerHandle::memory_usage).
+    pub memory_watermarks: MemoryWatermarks,
+    /// How long endpoints the node disconnects are asked not to reconnect for. Refer to
+    /// [`ServerHandle::dismiss`](super::ServerHandle::dismiss).
+    pub keep_down: KeepDownDelays,
+    /// How the counts the node advertises to anyone who connects are blurred, so that the
+    /// activity of a small node cannot be followed user by user. Is [`None`] if they are exact.
+    /// The exact counts remain available through [`ServerHandle::load`](super::ServerHandle::load).
+    pub public_counts: Option<CountPrivacy>,
+    /// How the node replicates its state to warm standbys, or whether it is one.
+    pub replication: ReplicationConfig,
+    /// How often the node clears expired state, such as identify data handed out to endpoints
+    /// that never identified. Refer to [`ServerHandle::start_reaper`](super::ServerHandle::start_reaper).
+    /// Is [`None`] if expired state is only cleared by calling
+    /// [`ServerHandle::reap`](super::ServerHandle::reap).
+    pub reap_interval: Option<Duration>,
+    /// How long an endpoint stays subscribed to a key with a
+    /// [`KeysExistsReq`](crate::obj::KeysExistsReq) before it must subscribe again. Is [`None`]
+    /// if subscriptions do not expire.
+    pub subscription_ttl: Option<Duration>,
+    /// How long the resumption token handed out to an endpoint stays valid after the endpoint
+    /// connected. Tokens are also invalidated once used.
+    pub resumption_ttl: Duration,
+    /// How long the receipts of a message held for a public key are routed back to its sender
+    /// after the message was stored. Delivered messages are still tracked until then, so that
+    /// the recipient can sign a receipt once it read them.
+    pub receipt_ttl: Duration,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            park_timeout: None,
+            recently_seen: Duration::from_secs(60),
+            peer_demote_errors: 3,
+            peer_demote_duration: Duration::from_secs(30),
+            push_timeout: Duration::from_secs(5),
+            identify_extensions: Default::default(),
+            identify_difficulty: None,
+            crypto_threads: None,
+            pipeline_limit: 16,
+            remote_key_ttl: None,
+            verify_cache_size: 4096,
+            peer_streams: Default::default(),
+            fanout_workers: 4,
+            mail_retention: Default::default(),
+            dedupe_window: Duration::from_secs(10 * 60),
+            require_canonical: false,
+            validity: Default::default(),
+            rate_limit: None,
+            request_limits: Default::default(),
+            max_identities: Some(64),
+            max_mailboxes: Some(1 << 16),
+            gossip_history: 1024,
+            reconcile_leaf_size: 16,
+            list_disclosure: Default::default(),
+            max_forward_depth: 4,
+            size_limits: Default::default(),
+            memory_watermarks: Default::default(),
+            keep_down: Default::default(),
+            public_counts: None,
+            replication: Default::default(),
+            reap_interval: Some(Duration::from_secs(30)),
+            subscription_ttl: None,
+            resumption_ttl: Duration::from_secs(24 * 60 * 60),
+            receipt_ttl: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// What is revealed about a connected server when it is listed. Its domain is always revealed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Disclosure {
+    /// Whether the address of the server is revealed.
+    pub ip: bool,
+    /// Whether the ports the server listens on are revealed. The address of the server is only
+    /// revealed along with its port.
+    pub ports: bool,
+}
+
+impl Disclosure {
+    /// Reveals everything known about the server.
+    pub const ALL: Self = Self {
+        ip: true,
+        ports: true,
+    };
+    /// Reveals only the domain of the server, and what it advertised about itself other than its
+    /// ports.
+    pub const DOMAIN_ONLY: Self = Self {
+        ip: false,
+        ports: false,
+    };
+}
+
+/// What is revealed about connected servers to each kind of endpoint that lists them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DisclosurePolicy {
+    /// What connected servers are shown.
+    pub peers: Disclosure,
+    /// What endpoints that identified as a public key are shown.
+    pub identified: Disclosure,
+    /// What endpoints that did not identify are shown.
+    pub anonymous: Disclosure,
+}
+
+impl Default for DisclosurePolicy {
+    fn default() -> Self {
+        Self {
+            peers: Disclosure::ALL,
+            identified: Disclosure::ALL,
+            anonymous: Disclosure::ALL,
+        }
+    }
+}
+
+/// Limits on the time windows of signed objects, such as identify data, delegations and
+/// revocations. Keeps clients from minting objects that stay valid for implausibly long, or that
+/// claim to have been signed in the future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValidityLimits {
+    /// How long after it is checked an object may stay valid. Is [`None`] if not limited.
+    pub max_validity: Option<Duration>,
+    /// How far in the future an object may claim to have been signed, to allow for the clocks of
+    /// the signer and the node drifting apart.
+    pub max_drift: Duration,
+}
+
+impl Default for ValidityLimits {
+    fn default() -> Self {
+        Self {
+            max_validity: Some(Duration::from_secs(365 * 24 * 60 * 60)),
+            max_drift: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+impl ValidityLimits {
+    /// Checks that an object signed at `time` was not signed further in the future than `now`
+    /// allows.
+    pub fn check_time(&self, time: u64, now: u64) -> Result<(), ValidityError> {
+        let ahead = time.saturating_sub(now);
+        let max = self.max_drift.as_millis() as u64;
+        if ahead > max {
+            return Err(ValidityError::FutureDated { ahead, max });
+        }
+        Ok(())
+    }
+    /// Checks that an object that expires at `expire_time` does not stay valid for longer than
+    /// allowed after `now`.
+    pub fn check_expiry(&self, expire_time: u64, now: u64) -> Result<(), ValidityError> {
+        let validity = expire_time.saturating_sub(now);
+        match self.max_validity.map(|max| max.as_millis() as u64) {
+            Some(max) if validity > max => Err(ValidityError::TooLong { validity, max }),
+            _ => Ok(()),
+        }
+    }
+    /// Checks both ends of an object valid from `start_time` until `expire_time`.
+    pub fn check_window(
+        &self,
+        start_time: u64,
+        expire_time: u64,
+        now: u64,
+    ) -> Result<(), ValidityError> {
+        self.check_time(start_time, now)?;
+        self.check_expiry(expire_time, now)
+    }
+}
+
+/// Limits on the size of requests, so that a single request cannot make the node allocate an
+/// unbounded amount of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SizeLimits {
+    /// The most public keys a request may list, such as the keys a
+    /// [`KeysExistsReq`](crate::obj::KeysExistsReq) asks about.
+    pub max_keys: usize,
+    /// The most triads an [`IdentifyReq`](crate::obj::IdentifyReq) may identify at once, counting
+    /// each signer of a triad signed by several keys.
+    pub max_batch: usize,
+    /// The largest signed payload a request may carry, in bytes.
+    pub max_payload: usize,
+    /// The most entries of a transparency log a proof may cover, such as the proof of a
+    /// [`GetLogProofReq`](crate::obj::GetLogProofReq).
+    pub max_proof: usize,
+    /// The most entries a response may list, such as the servers of a
+    /// [`ListConnectedServersResp`](crate::obj::ListConnectedServersResp) or the changes of a
+    /// [`KeyChangesResp`](crate::obj::KeyChangesResp).
+    pub max_entries: usize,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            max_keys: 1024,
+            max_batch: 256,
+            max_payload: 1 << 18,
+            max_proof: 4096,
+            max_entries: 1024,
+        }
+    }
+}
+
+impl SizeLimits {
+    /// Checks that a request listing `len` public keys is within the limits.
+    pub fn check_keys(&self, len: usize) -> Result<(), TooLargeError> {
+        check_size(len, self.max_keys)
+    }
+    /// Checks that a request identifying `len` triads is within the limits.
+    pub fn check_batch(&self, len: usize) -> Result<(), TooLargeError> {
+        check_size(len, self.max_batch)
+    }
+    /// Checks that a proof covering `len` entries of a log is within the limits.
+    pub fn check_proof(&self, len: usize) -> Result<(), TooLargeError> {
+        check_size(len, self.max_proof)
+    }
+    /// Checks that the signed payload `data` is within the limits.
+    pub fn check_payload(&self, data: &SignedData) -> Result<(), TooLargeError> {
+        check_size(data.size(), self.max_payload)
+    }
+}
+
+fn check_size(size: usize, max: usize) -> Result<(), TooLargeError> {
+    if size > max {
+        return Err(TooLargeError { size, max });
+    }
+    Ok(())
+}
+
+/// The estimated memory, in bytes, at which a node sheds each kind of work that would hold more.
+/// Work is rejected with [`ErrorCode::OVERLOADED`](crate::obj::ErrorCode::OVERLOADED) until the
+/// node holds less. Watermarks that are [`None`] are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MemoryWatermarks {
+    /// The watermark at which messages are no longer stored for public keys.
+    pub mail: Option<usize>,
+    /// The watermark at which endpoints can no longer subscribe to public keys connecting.
+    pub subscriptions: Option<usize>,
+}
+
+/// How long an endpoint disconnected by the node should wait before reconnecting, depending on why
+/// it was disconnected. Endpoints disconnected for other reasons are not kept down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeepDownDelays {
+    /// The delay of an endpoint that sent more requests than the node allows.
+    pub rate_limited: Duration,
+    /// The delay of an endpoint that was banned.
+    pub banned: Duration,
+}
+
+impl Default for KeepDownDelays {
+    fn default() -> Self {
+        Self {
+            rate_limited: Duration::from_secs(30),
+            banned: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// How a count a node advertises publicly, such as the amount of identified public keys, is
+/// blurred. Noise is added first, then the count is rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CountPrivacy {
+    /// The multiple counts are rounded to. Counts are not rounded if it is 0 or 1.
+    pub granularity: u32,
+    /// The most a count is moved up or down at random.
+    pub noise: u32,
+}
+
+/// How a node replicates its state to warm standbys, that can take over if it fails.
+///
+/// The primary records the writes to its state that outlive connections in a journal: the public
+/// keys that identified or were revoked, the subscriptions of identified endpoints, and the
+/// writes to mailboxes. A standby connects to the primary as a federated server, identifies as
+/// one of [`ReplicationConfig::followers`], and tails the journal with
+/// [`ServerHandle::follow`](super::ServerHandle::follow). Once the primary fails, the standby is
+/// promoted with [`ServerHandle::promote`](super::ServerHandle::promote), and starts listening.
+///
+/// Replication is asynchronous. Entries are applied in the order the primary recorded them, each
+/// exactly once, so a standby always holds the state of the primary as of some earlier point, and
+/// writes the primary acknowledged after the last time the standby followed are lost on failover.
+/// Senders that retry a lost message are stored again, while retries of replicated messages are
+/// still deduplicated. Connections are not replicated: endpoints
+/// reconnect and identify again, and the subscriptions they made as the same public key are
+/// restored then. Requests waiting for a key, introductions, resumption tokens and the
+/// transparency log are lost, and the sequence of key changes starts a new epoch, so federated
+/// servers fetch the whole set of keys again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReplicationConfig {
+    /// Whether the node starts as a standby, until it is promoted.
+    pub standby: bool,
+    /// The public keys of the standbys allowed to tail the journal of the node.
+    pub followers: Vec<PublicKey>,
+    /// The amount of recent journal entries held for standbys to catch up on. A standby further
+    /// behind is sent a snapshot of the whole state.
+    pub history: usize,
+    /// The most journal entries sent to a standby at once.
+    pub batch: usize,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            standby: false,
+            followers: Vec::new(),
+            history: 4096,
+            batch: 512,
+        }
+    }
+}
+
+impl Default for CountPrivacy {
+    fn default() -> Self {
+        Self {
+            granularity: 10,
+            noise: 5,
+        }
+    }
+}
+
+impl CountPrivacy {
+    /// Blurs `count`, with `draw` picking the noise. The same draw always blurs a count the same
+    /// way.
+    pub fn blur(&self, count: u32, draw: u64) -> u32 {
+        let noise = self.noise as u64;
+        let noisy = (count as u64 + draw % (2 * noise + 1)).saturating_sub(noise);
+
+        let granularity = self.granularity.max(1) as u64;
+        let rounded = (noisy + granularity / 2) / granularity * granularity;
+        rounded.min(u32::MAX as u64) as u32
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::obj::MessageId;
+use crate::utils::LockExt;
+
+/// The ids of the messages a node accepted recently, so that retries of a message are not
+/// delivered twice.
+#[derive(Debug)]
+pub(crate) struct DedupeWindow {
+    window: Duration,
+    seen: Mutex<Seen>,
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    /// The ids in the order they were accepted, along with the times they were accepted.
+    order: VecDeque<(u64, MessageId)>,
+    ids: HashSet<MessageId>,
+}
+
+impl DedupeWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Default::default(),
+        }
+    }
+    /// Records `id` at the time `now`. Returns `false` if it was recorded within the window
+    /// already.
+    pub fn insert(&self, id: MessageId, now: u64) -> bool {
+        let window = self.window.as_millis() as u64;
+        let mut seen = self.seen.locked();
+
+        while let Some((time, old)) = seen.order.front().copied() {
+            if now.saturating_sub(time) <= window {
+                break;
+            }
+            seen.order.pop_front();
+            seen.ids.remove(&old);
+        }
+
+        if !seen.ids.insert(id) {
+            return false;
+        }
+        seen.order.push_back((now, id));
+        true
+    }
+    /// Returns the amount of ids recorded.
+    pub fn len(&self) -> usize {
+        self.seen.locked().order.len()
+    }
+}
@@ -0,0 +1,155 @@
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::net::TcpListener;
+
+use super::*;
+
+/// The earliest time the clock of a node can read, in milliseconds since January 1 1970. A clock
+/// behind it was never set, and every identify data the node issues would have expired already.
+pub const MIN_CLOCK_TIME: u64 = 1_704_067_200_000;
+
+/// What a [`Check`] of [`ServerHandle::diagnose`] verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CheckKind {
+    /// The system clock is set.
+    Clock,
+    /// The random number generator returns random bytes.
+    Entropy,
+    /// Signatures made by a fresh key and by the key of the node verify, and signatures of other
+    /// messages do not.
+    Crypto,
+    /// The address can be listened on.
+    Listener(SocketAddr),
+    /// The [`GossipStore`] of the node can be read.
+    Persistence,
+}
+
+/// How a [`Check`] went.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CheckStatus {
+    Passed,
+    /// The check failed, for the reason it holds.
+    Failed(String),
+    /// The node has nothing to check, such as when it has no [`GossipStore`].
+    Skipped,
+}
+
+/// A check run by [`ServerHandle::diagnose`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Check {
+    pub kind: CheckKind,
+    pub status: CheckStatus,
+}
+
+/// The report of [`ServerHandle::diagnose`], with a check for every part of the environment the
+/// node depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnosis {
+    pub checks: Vec<Check>,
+}
+
+impl Diagnosis {
+    /// Returns whether no check failed.
+    pub fn healthy(&self) -> bool {
+        self.failures().next().is_none()
+    }
+    /// Returns the checks that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks
+            .iter()
+            .filter(|check| matches!(check.status, CheckStatus::Failed(_)))
+    }
+}
+
+fn check_clock() -> CheckStatus {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(time) if time.as_millis() as u64 >= MIN_CLOCK_TIME => CheckStatus::Passed,
+        Ok(time) => CheckStatus::Failed(format!(
+            "clock reads {} ms since the epoch, which is before {MIN_CLOCK_TIME}",
+            time.as_millis()
+        )),
+        Err(_) => CheckStatus::Failed("clock reads before the epoch".into()),
+    }
+}
+
+fn check_entropy() -> CheckStatus {
+    let a: [u8; 32] = utils::random_bytes();
+    let b: [u8; 32] = utils::random_bytes();
+    if a == b || a == [0; 32] {
+        return CheckStatus::Failed("random number generator repeats its output".into());
+    }
+
+    CheckStatus::Passed
+}
+
+fn check_signing(key: &PrivateKey) -> Result<(), String> {
+    let public_key = key.derive_public();
+    let msg = crate::crypto::hash(b"cacophoney self-test");
+    let signature = key.sign(msg);
+
+    if !public_key.valid(msg, &signature) {
+        return Err("signature does not verify".into());
+    }
+    if public_key.valid(crate::crypto::hash(b"another message"), &signature) {
+        return Err("signature verifies another message".into());
+    }
+
+    Ok(())
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Checks the environment this node runs in: whether the clock is set, whether the random
+    /// number generator works, whether signatures round trip, whether every address of `listen`
+    /// can be bound, and whether the [`GossipStore`] of the node can be read.
+    ///
+    /// Addresses are bound and released right away, so this is meant to run before the node
+    /// starts listening on them.
+    pub async fn diagnose(&self, listen: &[SocketAddr]) -> Diagnosis {
+        let mut checks = vec![
+            Check {
+                kind: CheckKind::Clock,
+                status: check_clock(),
+            },
+            Check {
+                kind: CheckKind::Entropy,
+                status: check_entropy(),
+            },
+        ];
+
+        let fresh = PrivateKey::generate(&mut utils::rng());
+        checks.push(Check {
+            kind: CheckKind::Crypto,
+            status: match check_signing(&fresh).and_then(|_| check_signing(&self.key)) {
+                Ok(()) => CheckStatus::Passed,
+                Err(err) => CheckStatus::Failed(err),
+            },
+        });
+
+        for addr in listen {
+            checks.push(Check {
+                kind: CheckKind::Listener(*addr),
+                status: match TcpListener::bind(addr).await {
+                    Ok(_) => CheckStatus::Passed,
+                    Err(err) => CheckStatus::Failed(err.to_string()),
+                },
+            });
+        }
+
+        let store = self.gossip.locked().store();
+        let status = match store {
+            Some(store) => match tokio::task::spawn_blocking(move || store.load()).await {
+                Ok(Ok(_)) => CheckStatus::Passed,
+                Ok(Err(err)) => CheckStatus::Failed(err.to_string()),
+                Err(err) => CheckStatus::Failed(err.to_string()),
+            },
+            None => CheckStatus::Skipped,
+        };
+        checks.push(Check {
+            kind: CheckKind::Persistence,
+            status,
+        });
+
+        Diagnosis { checks }
+    }
+}
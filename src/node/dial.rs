@@ -0,0 +1,126 @@
+use tower_async::Service;
+
+use super::*;
+use crate::crypto::error::TokenError;
+
+/// The amount of milliseconds a [`DialToken`] is valid for after it was issued.
+pub const DIAL_TOKEN_LIFETIME: u64 = 30_000;
+
+/// An issued [`Introduction`] and the handles of both sides at the time it was issued.
+#[derive(Debug)]
+pub(crate) struct DialEntry<C: ?Sized> {
+    pub(crate) intro: Introduction,
+    pub(crate) from: InboundHdl<C>,
+    pub(crate) to: InboundHdl<C>,
+}
+
+impl<C: Notify + ?Sized> Service<IntroductionReq> for InboundHdl<C> {
+    type Response = Introduction;
+    type Error = IntroductionReqError;
+
+    async fn call(&self, req: IntroductionReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // check if this endpoint identified as the public key
+        if !self.identities.contains_async(&req.from).await {
+            return Err(Self::Error::InvalidPublicKey);
+        }
+        // introductions carry no contact grant, so unlisted keys cannot be introduced to
+        if !server_hdl.may_see(&req.from, &req.to, None).await {
+            return Err(Self::Error::CannotFindKey);
+        }
+        if !server_hdl.may_reach(&req.from, &req.to).await {
+            return Err(Self::Error::UsageDenied);
+        }
+
+        let to_hdl = match server_hdl.key_to_endpoint.get_async(&req.to).await {
+            Some(value) => value.clone(),
+            None => return Err(Self::Error::CannotFindKey),
+        };
+
+        let expire_time = utils::now() + DIAL_TOKEN_LIFETIME;
+        let token = DialToken(Token::issue(
+            &server_hdl.token_key,
+            TokenScope::Dial,
+            expire_time,
+        ));
+        let intro = Introduction {
+            token,
+            from: req.from,
+            to: req.to,
+            expire_time,
+        };
+
+        // push the introduction to the other side before handing out the token
+        if to_hdl.conn.notify_introduced(&intro).await.is_err() {
+            return Err(Self::Error::NotifyFailed);
+        }
+
+        let entry = DialEntry {
+            intro,
+            from: self.clone(),
+            to: to_hdl,
+        };
+        let _ = server_hdl.dial_tokens.insert_async(token, entry).await;
+
+        Ok(intro)
+    }
+}
+impl<C: OpenStream + ?Sized> Service<DialReq> for InboundEndpoint<C> {
+    type Response = C::Response;
+    type Error = DialReqError<C::Err>;
+
+    async fn call(&self, req: DialReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // reject forged tokens without touching the map
+        match req
+            .token
+            .0
+            .verify(&server_hdl.token_key, TokenScope::Dial, utils::now())
+        {
+            Ok(()) => {}
+            Err(TokenError::Expired) => {
+                let _ = server_hdl.dial_tokens.remove_async(&req.token).await;
+                return Err(Self::Error::Expired);
+            }
+            Err(_) => return Err(Self::Error::InvalidToken),
+        }
+
+        // tokens are single use, so remove it only if it belongs to this endpoint
+        let entry = server_hdl
+            .dial_tokens
+            .remove_if_async(&req.token, |entry| {
+                entry.from.id == self.id || entry.to.id == self.id
+            })
+            .await
+            .ok_or(Self::Error::InvalidToken)?
+            .1;
+
+        let (key, other) = if entry.from.id == self.id {
+            (entry.intro.from, entry.to)
+        } else {
+            (entry.intro.to, entry.from)
+        };
+
+        Ok(other.conn.open_stream(key).await?)
+    }
+}
+impl<C: OpenStream + ?Sized> Service<DialReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<DialReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<DialReq>>::Error;
+
+    fn call(&self, req: DialReq) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
@@ -0,0 +1,169 @@
+use tower_async::Service;
+
+use super::*;
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Forgets `hdl` once its connection dropped. Returns `false` if it was already forgotten.
+    ///
+    /// Refer to [`InboundEndpoint::disconnect`].
+    pub async fn remove_endpoint(&self, hdl: &InboundHdl<C>) -> bool {
+        self.forget(hdl).await
+    }
+    async fn forget(&self, endpoint: &InboundEndpoint<C>) -> bool {
+        if endpoint.disconnected.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        let id = endpoint.id;
+
+        // the keys are no longer reachable through the endpoint, but may still be parked on until
+        // they identify again
+        let mut keys = Vec::new();
+        self.key_to_endpoint
+            .retain_async(|key, hdl| {
+                if hdl.id != id {
+                    return true;
+                }
+                keys.push(*key);
+                false
+            })
+            .await;
+        let now = utils::now();
+        for key in &keys {
+            *self
+                .last_seen
+                .entry_async(*key)
+                .await
+                .or_default()
+                .get_mut() = now;
+            // the presence was published through the connection
+            self.clear_presence(key).await;
+        }
+        if !keys.is_empty() {
+            self.invalidate_key_set().await;
+        }
+
+        self.connected_servers.write().await.remove(endpoint);
+        let mut subscribed = Vec::new();
+        self.notifications
+            .retain_async(|key, endpoints| {
+                if endpoints.remove(endpoint) {
+                    subscribed.push(*key);
+                    self.gauges.subscribers.update(1, 0);
+                }
+                !endpoints.is_empty()
+            })
+            .await;
+        self.journal_subscriptions(endpoint, &subscribed, false)
+            .await;
+        self.dial_tokens
+            .retain_async(|_, entry| entry.from.id != id && entry.to.id != id)
+            .await;
+        self.parked
+            .retain_async(|_, senders| {
+                senders.retain(|(parked, _)| *parked != id);
+                !senders.is_empty()
+            })
+            .await;
+        self.remote_keys
+            .retain_async(|_, peers| {
+                let len = peers.len();
+                peers.retain(|remote| remote.peer.id != id);
+                self.gauges.routes.update(len, peers.len());
+                !peers.is_empty()
+            })
+            .await;
+        self.peer_health.remove_async(&id).await;
+        self.challenges.remove_async(&id).await;
+
+        let _ = self.events.send(NodeEvent::Disconnected {
+            id,
+            server_info: endpoint.info.server_info.clone(),
+            public_keys: keys,
+        });
+
+        true
+    }
+}
+
+impl<C: Service<DisconnectNoticeReq> + ?Sized> ServerHandle<C> {
+    /// Tells every connected server that `keys` disconnected from this node, so that they stop
+    /// routing them to it rather than waiting for their reports to expire. Returns the amount of
+    /// servers that accepted it.
+    ///
+    /// A server only forgets the routes it learned before the keys disconnected, so a notice that
+    /// arrives late or more than once does not undo a newer report.
+    pub async fn propagate_disconnect(&self, keys: &[PublicKey]) -> usize {
+        self.push_peers(DisconnectNoticeReq {
+            keys: keys.to_vec(),
+            time: utils::now(),
+        })
+        .await
+    }
+}
+
+impl<C: ?Sized> Service<DisconnectNoticeReq> for InboundHdl<C> {
+    type Response = ();
+    type Error = DisconnectNoticeReqError;
+
+    async fn call(&self, req: DisconnectNoticeReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        if self.info.server_info.is_none() {
+            return Err(DisconnectNoticeReqError::NotPeer);
+        }
+        server_hdl.config.size_limits.check_keys(req.keys.len())?;
+
+        let ttl = server_hdl
+            .config
+            .remote_key_ttl
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut forgotten = false;
+        for key in &req.keys {
+            server_hdl
+                .remote_keys
+                .remove_if_async(key, |paths| {
+                    let len = paths.len();
+                    // a route reported after the keys disconnected is newer than the notice
+                    paths.retain(|remote| {
+                        remote.peer.id != self.id
+                            || remote.expire_time.saturating_sub(ttl) > req.time
+                    });
+                    forgotten |= paths.len() != len;
+                    server_hdl.gauges.routes.update(len, paths.len());
+                    paths.is_empty()
+                })
+                .await;
+        }
+        if forgotten {
+            server_hdl.invalidate_known_keys().await;
+        }
+        Ok(())
+    }
+}
+
+impl<C: ?Sized> InboundEndpoint<C> {
+    /// Forgets this endpoint once its connection dropped. Returns `false` if it was already
+    /// forgotten.
+    ///
+    /// The public keys the endpoint identified as are no longer routed to it, its subscriptions
+    /// and introductions are dropped, it is no longer listed as a connected server, and
+    /// [`NodeEvent::Disconnected`] is sent to the subscribers of the node. Requests parked on the
+    /// keys keep waiting for them to identify again, and the resumption token of the endpoint
+    /// stays valid, so that a new connection can take over its identities.
+    pub async fn disconnect(&self) -> bool {
+        match self.server_hdl.as_ref().and_then(Weak::upgrade) {
+            Some(server_hdl) => server_hdl.forget(self).await,
+            None => !self.disconnected.swap(true, Ordering::SeqCst),
+        }
+    }
+    /// Returns whether this endpoint was forgotten with [`InboundEndpoint::disconnect`].
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::{Arc, Weak};
+
+use futures::{future, Sink, SinkExt, Stream, StreamExt};
+use tower_async::Service;
+
+use super::{InboundEndpoint, IncompatibleVersionError, NodeConfig, Notify};
+use crate::obj::{ErrorResp, ReqMessage, RespMessage, Tagged};
+
+/// Drives the requests received on one connection through a service.
+///
+/// Requests are processed concurrently, up to a limit, so clients can pipeline requests without
+/// waiting for each response. Responses are always delivered in the order the requests were
+/// received, tagged with the ids of their requests. A slow request holds back the responses of
+/// the requests received after it, but not their processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pipeline {
+    limit: usize,
+}
+
+impl Pipeline {
+    /// Creates a pipeline that processes up to `limit` requests at the same time. A limit of 1
+    /// processes requests one after the other.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+        }
+    }
+    /// Creates a pipeline with the limit of `config`.
+    pub fn from_config(config: &NodeConfig) -> Self {
+        Self::new(config.pipeline_limit)
+    }
+    /// Returns the amount of requests processed at the same time.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+    /// Calls `service` with every request of `requests`, and sends the responses to `responses`
+    /// in order. Returns once `requests` ends, or sending a response fails.
+    ///
+    /// Requests encoded with another version of the protocol are not passed to `service`, and
+    /// are answered with an [`IncompatibleVersionError`].
+    pub async fn drive<S, Req, St, Si>(
+        &self,
+        service: &S,
+        requests: St,
+        responses: Si,
+    ) -> Result<(), Si::Error>
+    where
+        S: Service<Req>,
+        S::Error: From<IncompatibleVersionError>,
+        St: Stream<Item = Tagged<Req>>,
+        Si: Sink<Tagged<Result<S::Response, S::Error>>>,
+    {
+        requests
+            .map(|req| async move {
+                let resp = match req.is_compatible() {
+                    true => service.call(req.body).await,
+                    false => Err(IncompatibleVersionError {
+                        version: req.version.unwrap_or(0),
+                    }
+                    .into()),
+                };
+                Ok::<_, Si::Error>(Tagged::new(req.id, resp))
+            })
+            .buffered(self.limit)
+            .forward(responses)
+            .await
+    }
+}
+
+impl<C: Notify + Send + Sync + 'static + ?Sized> InboundEndpoint<C> {
+    /// Answers the requests received on the connection of this endpoint until `requests` ends,
+    /// with the [`Pipeline`] of the [`NodeConfig::pipeline_limit`] of its node. Failed requests
+    /// are answered with an [`ErrorResp`], as with [`InboundEndpoint::respond`].
+    pub async fn serve<St, Si>(
+        self: &Arc<Self>,
+        requests: St,
+        responses: Si,
+    ) -> Result<(), Si::Error>
+    where
+        St: Stream<Item = Tagged<ReqMessage>>,
+        Si: Sink<Tagged<RespMessage>>,
+    {
+        let pipeline = match self.server_hdl.as_ref().and_then(Weak::upgrade) {
+            Some(server_hdl) => Pipeline::from_config(&server_hdl.config),
+            None => Pipeline::from_config(&NodeConfig::default()),
+        };
+        let responses = responses.with(|resp: Tagged<Result<RespMessage, _>>| {
+            let body = resp.body.unwrap_or_else(|err| ErrorResp::from(err).into());
+            future::ok(Tagged::new(resp.id, body))
+        });
+
+        pipeline.drive(self, requests, responses).await
+    }
+}
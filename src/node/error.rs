@@ -1,9 +1,15 @@
 use serde::Serialize;
 use thiserror::Error;
 
+use std::convert::Infallible;
 use std::error::Error as StdError;
+use std::time::Duration;
 
-use crate::obj::{InvalidTypeError, SignedConvertError};
+use crate::crypto::error::{DelegationError, MultiKeyError, VerifyError};
+use crate::crypto::schnorr::SignatureScheme;
+use crate::obj::{
+    ErrorCode, ErrorResp, InvalidTypeError, SignMessageType, SignedConvertError, SignedData,
+};
 
 /// This error happens when an endpoint starts a request that only a server can fulfill.
 #[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
@@ -16,6 +22,78 @@ pub struct NotServerError;
 #[error("all instances of the node handle were dropped")]
 pub struct ServerHdlDroppedError;
 
+/// This error happens when a request lists more items, or carries a larger payload, than the
+/// [`SizeLimits`](super::SizeLimits) of the node allow.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+#[error("size {size} exceeds the maximum of {max}")]
+pub struct TooLargeError {
+    pub size: usize,
+    pub max: usize,
+}
+
+/// This error happens when a signed payload is of a type other than the operation it is used
+/// for, such as a revocation sent as identify data.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+#[error("expected a signable of type {expected:?}, found {found:?}")]
+pub struct WrongMessageTypeError {
+    pub expected: SignMessageType,
+    pub found: SignMessageType,
+}
+
+impl WrongMessageTypeError {
+    /// Checks that the signable in `data` is of the type `expected`, before it is parsed any
+    /// further. Payloads the node cannot read, such as detached or encrypted payloads, pass.
+    pub fn check(expected: SignMessageType, data: &SignedData) -> Result<(), Self> {
+        match data.msg_type() {
+            Some(found) if found != expected => Err(Self { expected, found }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// This error happens when the node holds more memory than a
+/// [`MemoryWatermarks`](super::MemoryWatermarks) of the node allows for the request.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+#[error("estimated memory usage of {usage} bytes exceeds the watermark of {watermark}")]
+pub struct OverloadedError {
+    pub usage: usize,
+    pub watermark: usize,
+}
+
+/// This error happens when an endpoint sent more requests of a type than the
+/// [`RequestLimits`](super::RequestLimits) of the node allow.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+#[error("rate limited, retry after {} ms", .retry_after.as_millis())]
+pub struct RateLimitedError {
+    pub retry_after: Duration,
+}
+
+/// This error happens when an endpoint, or a public key it acts as, is on the
+/// [`BanList`](super::BanList) of the node.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+#[error("banned from the node")]
+pub struct BannedError;
+
+/// This error happens when a request is encoded with a version of the protocol other than the
+/// [`CURRENT_VERSION`](crate::CURRENT_VERSION) of the node.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+#[error("version {version} of the protocol is not supported")]
+pub struct IncompatibleVersionError {
+    pub version: u32,
+}
+
+/// This error happens when the time window of a signed object is outside the
+/// [`ValidityLimits`](super::ValidityLimits) of the node. Times are in milliseconds.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum ValidityError {
+    /// The object stays valid for longer than allowed.
+    #[error("valid for {validity} ms, exceeding the maximum of {max} ms")]
+    TooLong { validity: u64, max: u64 },
+    /// The object claims to have been signed further in the future than the clocks can drift.
+    #[error("signed {ahead} ms in the future, exceeding the maximum drift of {max} ms")]
+    FutureDated { ahead: u64, max: u64 },
+}
+
 #[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
 pub enum ConnError<Conn: StdError, Req: StdError> {
     #[error("cannot connect to endpoint with error: {}", .0)]
@@ -36,14 +114,66 @@ pub enum IdentifyReqError {
     /// A digital signature was invalid.
     #[error("signature invalid")]
     SignatureInvalid,
+    /// The signature of a triad failed to verify.
+    #[error("{}", .0)]
+    Verify(#[from] VerifyError),
+    /// A triad is signed with another scheme than the one negotiated with the endpoint.
+    #[error("expected a signature of {expected:?}, found {found:?}")]
+    SchemeMismatch {
+        expected: SignatureScheme,
+        found: SignatureScheme,
+    },
+    /// The delegation chain of a triad does not authorize its key.
+    #[error("{}", .0)]
+    Delegation(#[from] DelegationError),
+    /// The signers of a multi-key triad do not claim the identity of their group.
+    #[error("{}", .0)]
+    MultiKey(#[from] MultiKeyError),
     #[error("identify data invalid")]
     IdentifyDataInvalid,
+    /// The signed identify data does not carry the extensions the node attached to it.
+    #[error("identify extensions do not match")]
+    ExtensionsMismatch,
+    /// The proof of work attached to the identify data does not meet the difficulty.
+    #[error("insufficient proof of work")]
+    InsufficientWork,
     #[error("identify data expired")]
     Expired,
+    /// The identify data or a delegation is valid for an implausible time window.
+    #[error("{}", .0)]
+    Validity(#[from] ValidityError),
+    /// The signed payload is not in canonical form, which the node requires.
+    #[error("payload not canonical")]
+    NonCanonical,
     #[error("already identified key")]
     AlreadyIdentified,
+    /// The key declared that it can only identify on a connection from a server.
+    #[error("server-only key")]
+    ServerOnly,
+    /// The public key was revoked.
+    #[error("revoked key")]
+    Revoked,
+    /// The endpoint disconnected before it identified.
+    #[error("endpoint disconnected")]
+    Disconnected,
     #[error("{}", .0)]
     ConvertErr(#[from] SignedConvertError),
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+    /// Refer to [`WrongMessageTypeError`].
+    #[error("{}", .0)]
+    WrongMessageType(#[from] WrongMessageTypeError),
+    /// Refer to [`RateLimitedError`].
+    #[error("{}", .0)]
+    RateLimited(#[from] RateLimitedError),
+    /// The endpoint would identify as more public keys than
+    /// [`NodeConfig::max_identities`](super::NodeConfig::max_identities) allows.
+    #[error("cannot identify as more than {max} keys")]
+    TooManyIdentities { max: usize },
+    /// Refer to [`BannedError`].
+    #[error("{}", .0)]
+    Banned(#[from] BannedError),
 }
 
 #[derive(Error, Debug)]
@@ -54,6 +184,25 @@ pub enum KeysExistsReqError {
     /// Refer to [`ServerHdlDroppedError`].
     #[error("{}", .0)]
     ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+    /// Refer to [`OverloadedError`].
+    #[error("{}", .0)]
+    Overloaded(#[from] OverloadedError),
+    /// Refer to [`RateLimitedError`].
+    #[error("{}", .0)]
+    RateLimited(#[from] RateLimitedError),
+}
+
+#[derive(Error, Debug)]
+pub enum KeysRootReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
 }
 
 /// An error type corresponding to a stream being opened to a connection.
@@ -83,8 +232,20 @@ pub enum CommunicationReqError<Err: StreamOpenError> {
     InvalidPublicKey,
     #[error("the initiator did not ")]
     CannotFindKey,
+    /// A key declared that it cannot be used this way.
+    #[error("the usage of a key does not allow the communication")]
+    UsageDenied,
+    /// The request was relayed through more servers than the node allows.
+    #[error("request relayed {hops} times exceeds the maximum of {max}")]
+    TtlExceeded { hops: u32, max: u32 },
     #[error("{}", .0)]
     StreamOpenErr(#[from] Err),
+    /// Refer to [`RateLimitedError`].
+    #[error("{}", .0)]
+    RateLimited(#[from] RateLimitedError),
+    /// Refer to [`BannedError`].
+    #[error("{}", .0)]
+    Banned(#[from] BannedError),
 }
 
 /// A minimal error that can occur when doing a server-only request.
@@ -96,4 +257,710 @@ pub enum ServerReqError {
     /// Refer to [`ServerHdlDroppedError`].
     #[error("{}", .0)]
     ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+}
+
+/// An error that can occur when a federated server asks whether public keys are connected.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum KeysExistsRReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The request is deeper than the node allows.
+    #[error("request depth {depth} exceeds the maximum of {max}")]
+    TtlExceeded { depth: u32, max: u32 },
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+}
+
+/// An error that can occur when an endpoint asks to be introduced to another public key.
+#[derive(Error, Debug)]
+pub enum IntroductionReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    #[error("the endpoint did not identify as the public key")]
+    InvalidPublicKey,
+    #[error("the public key is not connected to the node")]
+    CannotFindKey,
+    /// A key declared that it cannot be used this way.
+    #[error("the usage of a key does not allow the introduction")]
+    UsageDenied,
+    /// The introduction could not be pushed to the endpoint identified as the public key.
+    #[error("failed to notify the endpoint of the introduction")]
+    NotifyFailed,
+}
+
+/// An error that can occur when an endpoint presents a [`DialToken`](crate::obj::DialToken).
+#[derive(Error, Debug)]
+pub enum DialReqError<Err: StreamOpenError> {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The token does not exist, or was not issued to this endpoint.
+    #[error("invalid dial token")]
+    InvalidToken,
+    #[error("dial token expired")]
+    Expired,
+    #[error("{}", .0)]
+    StreamOpenErr(#[from] Err),
+}
+
+/// An error that can occur when an endpoint presents a [`ResumptionToken`](crate::obj::ResumptionToken).
+#[derive(Error, Debug)]
+pub enum ResumeReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    #[error("invalid resumption token")]
+    InvalidToken,
+}
+
+/// An error that can occur when revoking a public key.
+#[derive(Error, Debug)]
+pub enum RevokeReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The revocation is not a revocation of the key that signed it.
+    #[error("invalid revocation")]
+    InvalidRevocation,
+    /// The revocation claims to have been signed in the future.
+    #[error("{}", .0)]
+    Validity(#[from] ValidityError),
+    /// The signed payload is not in canonical form, which the node requires.
+    #[error("payload not canonical")]
+    NonCanonical,
+    /// The signature of the revocation failed to verify.
+    #[error("{}", .0)]
+    Verify(#[from] VerifyError),
+    #[error("{}", .0)]
+    ConvertErr(#[from] SignedConvertError),
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+    /// Refer to [`WrongMessageTypeError`].
+    #[error("{}", .0)]
+    WrongMessageType(#[from] WrongMessageTypeError),
+}
+
+/// An error that can occur when requesting a proof from the transparency log of a node.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum LogProofReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The log has less entries than requested.
+    #[error("log has less entries than requested")]
+    InvalidRange,
+}
+
+/// An error that can occur when a standby tails the journal of a node.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum JournalReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The endpoint did not identify as one of the followers of the node.
+    #[error("endpoint is not a follower")]
+    NotFollower,
+}
+
+/// An error that can occur when a federated server asks this node to cosign the head of its
+/// transparency log.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum CrossSignReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The endpoint that sent the request is not a federated server.
+    #[error("endpoint is not a server")]
+    NotPeer,
+    /// A digital signature was invalid.
+    #[error("signature invalid")]
+    SignatureInvalid,
+    /// The proof does not prove the head.
+    #[error("proof invalid")]
+    ProofInvalid,
+    /// The head does not extend a head of the same log this node cosigned before.
+    #[error("head is inconsistent with a cosigned head")]
+    Inconsistent,
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+}
+
+/// An error that can occur when a federated server reconciles the public keys it knows of with
+/// this node.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum ReconcileReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+    /// The endpoint that sent the request is not a federated server.
+    #[error("endpoint is not a server")]
+    NotPeer,
+}
+
+/// An error that can occur when a message is stored for a public key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum MailboxError {
+    /// The signature of the message failed to verify.
+    #[error("signature invalid")]
+    SignatureInvalid,
+    /// The recipient was revoked.
+    #[error("revoked key")]
+    Revoked,
+    /// The payload of the message is larger than the node accepts, or than the mailbox of the
+    /// recipient can hold.
+    #[error("message of {} bytes exceeds the limit of {} bytes", .size, .max)]
+    TooLarge { size: usize, max: usize },
+    /// A key declared that it cannot be used this way.
+    #[error("the usage of a key does not allow the message")]
+    UsageDenied,
+    /// Refer to [`OverloadedError`].
+    #[error("{}", .0)]
+    Overloaded(#[from] OverloadedError),
+    /// Refer to [`WrongMessageTypeError`].
+    #[error("{}", .0)]
+    WrongMessageType(#[from] WrongMessageTypeError),
+    /// The node holds messages for as many public keys as it can.
+    #[error("messages are held for the maximum of {} keys", .max)]
+    TooManyMailboxes { max: usize },
+}
+
+/// An error that can occur when an endpoint fetches the messages held for a public key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum FetchMailReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    #[error("the endpoint did not identify as the public key")]
+    InvalidPublicKey,
+}
+
+/// An error that can occur when broadcasting a payload to the endpoints of a node.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum BroadcastReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The endpoint that sent the request is not a federated server.
+    #[error("endpoint is not a server")]
+    NotPeer,
+    /// The payload is not signed by the node key the server identified as.
+    #[error("the payload is not signed by the server")]
+    InvalidPublicKey,
+    /// The signature of the payload failed to verify.
+    #[error("signature invalid")]
+    SignatureInvalid,
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+    /// Refer to [`WrongMessageTypeError`].
+    #[error("{}", .0)]
+    WrongMessageType(#[from] WrongMessageTypeError),
+}
+
+/// An error that can occur when relaying a message to a public key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum RelayReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The endpoint did not identify as the public key that signed the message.
+    #[error("the endpoint did not identify as the public key")]
+    InvalidPublicKey,
+    /// Refer to [`MailboxError`].
+    #[error("{}", .0)]
+    Mailbox(#[from] MailboxError),
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+}
+
+/// An error that can occur when publishing the presence of a public key.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum PresenceReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The endpoint did not identify as the public key that signed the update.
+    #[error("the endpoint did not identify as the public key")]
+    InvalidPublicKey,
+    /// The signature of the update failed to verify.
+    #[error("signature invalid")]
+    InvalidSignature,
+    /// The status message is longer than allowed.
+    #[error("status message of {} bytes exceeds the limit of {} bytes", .size, .max)]
+    TooLarge { size: usize, max: usize },
+    /// Refer to [`ValidityError`].
+    #[error("{}", .0)]
+    Validity(#[from] ValidityError),
+}
+
+/// An error that can occur when routing or following the receipts of a message.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Hash)]
+pub enum ReceiptReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The endpoint did not identify as the recipient that signed the receipt, or as the sender
+    /// of the message.
+    #[error("the endpoint did not identify as the public key")]
+    InvalidPublicKey,
+    /// The node holds no record of the message, because it was never stored, was evicted, or
+    /// its read receipt was already fetched.
+    #[error("unknown message")]
+    UnknownMessage,
+    /// The receipt is not signed by the recipient of the message, or does not match the message.
+    #[error("invalid receipt")]
+    InvalidReceipt,
+}
+
+/// An error that can occur when a federated server reports keys that disconnected from it.
+#[derive(Error, Debug)]
+pub enum DisconnectNoticeReqError {
+    /// Refer to [`NotServerError`].
+    #[error("{}", .0)]
+    NotServer(#[from] NotServerError),
+    /// Refer to [`ServerHdlDroppedError`].
+    #[error("{}", .0)]
+    ServerHdlDropped(#[from] ServerHdlDroppedError),
+    /// The endpoint that sent the request is not a federated server.
+    #[error("endpoint is not a server")]
+    NotPeer,
+    /// Refer to [`TooLargeError`].
+    #[error("{}", .0)]
+    TooLarge(#[from] TooLargeError),
+}
+
+/// An error that can occur when answering a request received on the wire.
+#[derive(Error, Debug)]
+pub enum WireReqError {
+    #[error("{}", .0)]
+    Identify(#[from] IdentifyReqError),
+    #[error("{}", .0)]
+    KeysExists(#[from] KeysExistsReqError),
+    #[error("{}", .0)]
+    Revoke(#[from] RevokeReqError),
+    #[error("{}", .0)]
+    Server(#[from] ServerReqError),
+    #[error("{}", .0)]
+    Broadcast(#[from] BroadcastReqError),
+    #[error("{}", .0)]
+    Relay(#[from] RelayReqError),
+    #[error("{}", .0)]
+    FetchMail(#[from] FetchMailReqError),
+    #[error("{}", .0)]
+    Receipt(#[from] ReceiptReqError),
+    #[error("{}", .0)]
+    Presence(#[from] PresenceReqError),
+    #[error("{}", .0)]
+    CrossSign(#[from] CrossSignReqError),
+    #[error("{}", .0)]
+    Journal(#[from] JournalReqError),
+    #[error("{}", .0)]
+    DisconnectNotice(#[from] DisconnectNoticeReqError),
+    /// The request has no response message.
+    #[error("request {} is not supported on the wire", .0)]
+    Unsupported(&'static str),
+    /// The connection sent more requests than the node allows.
+    #[error("{}", .0)]
+    RateLimited(#[from] RateLimitedError),
+    #[error("{}", .0)]
+    IncompatibleVersion(#[from] IncompatibleVersionError),
+}
+
+impl From<Infallible> for ErrorResp {
+    fn from(value: Infallible) -> Self {
+        match value {}
+    }
+}
+impl From<IncompatibleVersionError> for ErrorResp {
+    fn from(value: IncompatibleVersionError) -> Self {
+        ErrorResp::new(ErrorCode::INCOMPATIBLE_VERSION, value)
+    }
+}
+impl From<NotServerError> for ErrorResp {
+    fn from(value: NotServerError) -> Self {
+        ErrorResp::new(ErrorCode::NOT_SERVER, value)
+    }
+}
+impl From<TooLargeError> for ErrorResp {
+    fn from(value: TooLargeError) -> Self {
+        ErrorResp::new(ErrorCode::TOO_LARGE, value)
+    }
+}
+impl From<WrongMessageTypeError> for ErrorResp {
+    fn from(value: WrongMessageTypeError) -> Self {
+        ErrorResp::new(ErrorCode::WRONG_MESSAGE_TYPE, value)
+    }
+}
+impl From<OverloadedError> for ErrorResp {
+    fn from(value: OverloadedError) -> Self {
+        ErrorResp::new(ErrorCode::OVERLOADED, value)
+    }
+}
+impl From<BannedError> for ErrorResp {
+    fn from(value: BannedError) -> Self {
+        ErrorResp::new(ErrorCode::BANNED, value)
+    }
+}
+impl From<RateLimitedError> for ErrorResp {
+    fn from(value: RateLimitedError) -> Self {
+        ErrorResp::new(ErrorCode::RATE_LIMITED, value).with_retry_after(value.retry_after)
+    }
+}
+impl From<ServerHdlDroppedError> for ErrorResp {
+    fn from(value: ServerHdlDroppedError) -> Self {
+        ErrorResp::new(ErrorCode::UNAVAILABLE, value)
+    }
+}
+impl From<IdentifyReqError> for ErrorResp {
+    fn from(value: IdentifyReqError) -> Self {
+        let code = match &value {
+            IdentifyReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            IdentifyReqError::SignatureInvalid
+            | IdentifyReqError::Verify(_)
+            | IdentifyReqError::SchemeMismatch { .. } => ErrorCode::INVALID_SIGNATURE,
+            IdentifyReqError::Delegation(DelegationError::Expired) => ErrorCode::EXPIRED,
+            IdentifyReqError::Delegation(_) => ErrorCode::INVALID_DELEGATION,
+            IdentifyReqError::MultiKey(
+                MultiKeyError::GroupSignatureInvalid | MultiKeyError::Verify(_),
+            ) => ErrorCode::INVALID_SIGNATURE,
+            IdentifyReqError::MultiKey(MultiKeyError::Expired) => ErrorCode::EXPIRED,
+            IdentifyReqError::MultiKey(_) => ErrorCode::INVALID_GROUP,
+            IdentifyReqError::IdentifyDataInvalid => ErrorCode::INVALID_IDENTIFY_DATA,
+            IdentifyReqError::ExtensionsMismatch => ErrorCode::EXTENSIONS_MISMATCH,
+            IdentifyReqError::InsufficientWork => ErrorCode::INSUFFICIENT_WORK,
+            IdentifyReqError::Expired => ErrorCode::EXPIRED,
+            IdentifyReqError::Validity(_) => ErrorCode::IMPLAUSIBLE_TIME,
+            IdentifyReqError::NonCanonical => ErrorCode::NON_CANONICAL,
+            IdentifyReqError::AlreadyIdentified => ErrorCode::ALREADY_IDENTIFIED,
+            IdentifyReqError::ServerOnly => ErrorCode::USAGE_DENIED,
+            IdentifyReqError::Revoked => ErrorCode::REVOKED,
+            IdentifyReqError::Disconnected => ErrorCode::UNAVAILABLE,
+            IdentifyReqError::ConvertErr(_) => ErrorCode::INVALID_PAYLOAD,
+            IdentifyReqError::TooLarge(_) => ErrorCode::TOO_LARGE,
+            IdentifyReqError::WrongMessageType(_) => ErrorCode::WRONG_MESSAGE_TYPE,
+            IdentifyReqError::RateLimited(err) => return (*err).into(),
+            IdentifyReqError::TooManyIdentities { .. } => ErrorCode::TOO_MANY_IDENTITIES,
+            IdentifyReqError::Banned(_) => ErrorCode::BANNED,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<KeysExistsReqError> for ErrorResp {
+    fn from(value: KeysExistsReqError) -> Self {
+        match value {
+            KeysExistsReqError::NotServer(err) => err.into(),
+            KeysExistsReqError::ServerHdlDropped(err) => err.into(),
+            KeysExistsReqError::TooLarge(err) => err.into(),
+            KeysExistsReqError::Overloaded(err) => err.into(),
+            KeysExistsReqError::RateLimited(err) => err.into(),
+        }
+    }
+}
+impl From<KeysRootReqError> for ErrorResp {
+    fn from(value: KeysRootReqError) -> Self {
+        match value {
+            KeysRootReqError::NotServer(err) => err.into(),
+            KeysRootReqError::ServerHdlDropped(err) => err.into(),
+        }
+    }
+}
+impl From<KeysExistsRReqError> for ErrorResp {
+    fn from(value: KeysExistsRReqError) -> Self {
+        let code = match value {
+            KeysExistsRReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            KeysExistsRReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            KeysExistsRReqError::TtlExceeded { .. } => ErrorCode::TTL_EXCEEDED,
+            KeysExistsRReqError::TooLarge(_) => ErrorCode::TOO_LARGE,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<ServerReqError> for ErrorResp {
+    fn from(value: ServerReqError) -> Self {
+        match value {
+            ServerReqError::NotServer(err) => err.into(),
+            ServerReqError::ServerHdlDropped(err) => err.into(),
+            ServerReqError::TooLarge(err) => err.into(),
+        }
+    }
+}
+
+/// Returns the code of an error that occurred when opening a stream.
+fn stream_open_code(err: &impl StreamOpenError) -> ErrorCode {
+    match err.error_type() {
+        Some(StreamOpenErrorType::EndpointDeclined) => ErrorCode::ENDPOINT_DECLINED,
+        None => ErrorCode::STREAM_FAILED,
+    }
+}
+
+impl<Err: StreamOpenError> From<CommunicationReqError<Err>> for ErrorResp {
+    fn from(value: CommunicationReqError<Err>) -> Self {
+        let code = match &value {
+            CommunicationReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            CommunicationReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            CommunicationReqError::InvalidPublicKey => ErrorCode::INVALID_PUBLIC_KEY,
+            CommunicationReqError::CannotFindKey => ErrorCode::KEY_NOT_FOUND,
+            CommunicationReqError::UsageDenied => ErrorCode::USAGE_DENIED,
+            CommunicationReqError::TtlExceeded { .. } => ErrorCode::TTL_EXCEEDED,
+            CommunicationReqError::StreamOpenErr(err) => stream_open_code(err),
+            CommunicationReqError::RateLimited(err) => return (*err).into(),
+            CommunicationReqError::Banned(_) => ErrorCode::BANNED,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<IntroductionReqError> for ErrorResp {
+    fn from(value: IntroductionReqError) -> Self {
+        let code = match &value {
+            IntroductionReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            IntroductionReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            IntroductionReqError::InvalidPublicKey => ErrorCode::INVALID_PUBLIC_KEY,
+            IntroductionReqError::CannotFindKey => ErrorCode::KEY_NOT_FOUND,
+            IntroductionReqError::UsageDenied => ErrorCode::USAGE_DENIED,
+            IntroductionReqError::NotifyFailed => ErrorCode::NOTIFY_FAILED,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl<Err: StreamOpenError> From<DialReqError<Err>> for ErrorResp {
+    fn from(value: DialReqError<Err>) -> Self {
+        let code = match &value {
+            DialReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            DialReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            DialReqError::InvalidToken => ErrorCode::INVALID_TOKEN,
+            DialReqError::Expired => ErrorCode::EXPIRED,
+            DialReqError::StreamOpenErr(err) => stream_open_code(err),
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<ResumeReqError> for ErrorResp {
+    fn from(value: ResumeReqError) -> Self {
+        let code = match &value {
+            ResumeReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            ResumeReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            ResumeReqError::InvalidToken => ErrorCode::INVALID_TOKEN,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<RevokeReqError> for ErrorResp {
+    fn from(value: RevokeReqError) -> Self {
+        let code = match &value {
+            RevokeReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            RevokeReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            RevokeReqError::InvalidRevocation => ErrorCode::INVALID_REVOCATION,
+            RevokeReqError::Validity(_) => ErrorCode::IMPLAUSIBLE_TIME,
+            RevokeReqError::NonCanonical => ErrorCode::NON_CANONICAL,
+            RevokeReqError::Verify(_) => ErrorCode::INVALID_SIGNATURE,
+            RevokeReqError::ConvertErr(_) => ErrorCode::INVALID_PAYLOAD,
+            RevokeReqError::TooLarge(_) => ErrorCode::TOO_LARGE,
+            RevokeReqError::WrongMessageType(_) => ErrorCode::WRONG_MESSAGE_TYPE,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<LogProofReqError> for ErrorResp {
+    fn from(value: LogProofReqError) -> Self {
+        let code = match value {
+            LogProofReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            LogProofReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            LogProofReqError::InvalidRange => ErrorCode::INVALID_RANGE,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<ReconcileReqError> for ErrorResp {
+    fn from(value: ReconcileReqError) -> Self {
+        match value {
+            ReconcileReqError::NotServer(err) => err.into(),
+            ReconcileReqError::ServerHdlDropped(err) => err.into(),
+            ReconcileReqError::TooLarge(err) => err.into(),
+            ReconcileReqError::NotPeer => ErrorResp::new(ErrorCode::NOT_PEER, value),
+        }
+    }
+}
+impl From<CrossSignReqError> for ErrorResp {
+    fn from(value: CrossSignReqError) -> Self {
+        let code = match value {
+            CrossSignReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            CrossSignReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            CrossSignReqError::NotPeer => ErrorCode::NOT_PEER,
+            CrossSignReqError::SignatureInvalid => ErrorCode::INVALID_SIGNATURE,
+            CrossSignReqError::ProofInvalid => ErrorCode::INVALID_PROOF,
+            CrossSignReqError::Inconsistent => ErrorCode::INCONSISTENT,
+            CrossSignReqError::TooLarge(_) => ErrorCode::TOO_LARGE,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<JournalReqError> for ErrorResp {
+    fn from(value: JournalReqError) -> Self {
+        let code = match value {
+            JournalReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            JournalReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            JournalReqError::NotFollower => ErrorCode::NOT_PEER,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<MailboxError> for ErrorResp {
+    fn from(value: MailboxError) -> Self {
+        let code = match value {
+            MailboxError::SignatureInvalid => ErrorCode::INVALID_SIGNATURE,
+            MailboxError::Revoked => ErrorCode::REVOKED,
+            MailboxError::TooLarge { .. } => ErrorCode::TOO_LARGE,
+            MailboxError::UsageDenied => ErrorCode::USAGE_DENIED,
+            MailboxError::Overloaded(_) => ErrorCode::OVERLOADED,
+            MailboxError::WrongMessageType(_) => ErrorCode::WRONG_MESSAGE_TYPE,
+            MailboxError::TooManyMailboxes { .. } => ErrorCode::OVERLOADED,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<FetchMailReqError> for ErrorResp {
+    fn from(value: FetchMailReqError) -> Self {
+        let code = match value {
+            FetchMailReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            FetchMailReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            FetchMailReqError::InvalidPublicKey => ErrorCode::INVALID_PUBLIC_KEY,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<BroadcastReqError> for ErrorResp {
+    fn from(value: BroadcastReqError) -> Self {
+        let code = match value {
+            BroadcastReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            BroadcastReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            BroadcastReqError::NotPeer => ErrorCode::NOT_PEER,
+            BroadcastReqError::InvalidPublicKey => ErrorCode::INVALID_PUBLIC_KEY,
+            BroadcastReqError::SignatureInvalid => ErrorCode::INVALID_SIGNATURE,
+            BroadcastReqError::TooLarge(_) => ErrorCode::TOO_LARGE,
+            BroadcastReqError::WrongMessageType(_) => ErrorCode::WRONG_MESSAGE_TYPE,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<RelayReqError> for ErrorResp {
+    fn from(value: RelayReqError) -> Self {
+        match value {
+            RelayReqError::NotServer(err) => err.into(),
+            RelayReqError::ServerHdlDropped(err) => err.into(),
+            RelayReqError::InvalidPublicKey => ErrorResp::new(ErrorCode::INVALID_PUBLIC_KEY, value),
+            RelayReqError::Mailbox(err) => err.into(),
+            RelayReqError::TooLarge(err) => err.into(),
+        }
+    }
+}
+impl From<PresenceReqError> for ErrorResp {
+    fn from(value: PresenceReqError) -> Self {
+        let code = match value {
+            PresenceReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            PresenceReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            PresenceReqError::InvalidPublicKey => ErrorCode::INVALID_PUBLIC_KEY,
+            PresenceReqError::InvalidSignature => ErrorCode::INVALID_SIGNATURE,
+            PresenceReqError::TooLarge { .. } => ErrorCode::TOO_LARGE,
+            PresenceReqError::Validity(_) => ErrorCode::IMPLAUSIBLE_TIME,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<ReceiptReqError> for ErrorResp {
+    fn from(value: ReceiptReqError) -> Self {
+        let code = match value {
+            ReceiptReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            ReceiptReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            ReceiptReqError::InvalidPublicKey => ErrorCode::INVALID_PUBLIC_KEY,
+            ReceiptReqError::UnknownMessage => ErrorCode::UNKNOWN_MESSAGE,
+            ReceiptReqError::InvalidReceipt => ErrorCode::INVALID_RECEIPT,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<DisconnectNoticeReqError> for ErrorResp {
+    fn from(value: DisconnectNoticeReqError) -> Self {
+        let code = match &value {
+            DisconnectNoticeReqError::NotServer(_) => ErrorCode::NOT_SERVER,
+            DisconnectNoticeReqError::ServerHdlDropped(_) => ErrorCode::UNAVAILABLE,
+            DisconnectNoticeReqError::NotPeer => ErrorCode::NOT_PEER,
+            DisconnectNoticeReqError::TooLarge(_) => ErrorCode::TOO_LARGE,
+        };
+        ErrorResp::new(code, value)
+    }
+}
+impl From<WireReqError> for ErrorResp {
+    fn from(value: WireReqError) -> Self {
+        match value {
+            WireReqError::Identify(err) => err.into(),
+            WireReqError::KeysExists(err) => err.into(),
+            WireReqError::Revoke(err) => err.into(),
+            WireReqError::Server(err) => err.into(),
+            WireReqError::Broadcast(err) => err.into(),
+            WireReqError::Relay(err) => err.into(),
+            WireReqError::FetchMail(err) => err.into(),
+            WireReqError::Receipt(err) => err.into(),
+            WireReqError::Presence(err) => err.into(),
+            WireReqError::CrossSign(err) => err.into(),
+            WireReqError::Journal(err) => err.into(),
+            WireReqError::DisconnectNotice(err) => err.into(),
+            WireReqError::Unsupported(_) => ErrorResp::new(ErrorCode::UNSUPPORTED, value),
+            WireReqError::RateLimited(err) => err.into(),
+            WireReqError::IncompatibleVersion(err) => err.into(),
+        }
+    }
 }
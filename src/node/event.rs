@@ -0,0 +1,50 @@
+use crate::crypto::{KeyTriad, PublicKey};
+use crate::obj::{Goodbye, JournalPosition, ServerInfo, SignedData};
+
+use super::MemoryUsage;
+
+/// An event that happened on a node, exposed to the operator through
+/// [`ServerHandle::subscribe`](super::ServerHandle::subscribe).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NodeEvent {
+    /// A connected server failed too many requests in a row, and will not be forwarded requests
+    /// until its demotion expires.
+    PeerDemoted { id: u64, server_info: ServerInfo },
+    /// A demoted server fulfilled a request again.
+    PeerRestored { id: u64, server_info: ServerInfo },
+    /// A connected server asked for a head of its transparency log to be cosigned, that does not
+    /// extend a head of the same log this node cosigned before.
+    LogEquivocation {
+        id: u64,
+        server_info: ServerInfo,
+        public_key: PublicKey,
+    },
+    /// Work the node ran on behalf of an endpoint, such as a notification, panicked.
+    TaskPanicked { id: u64, message: String },
+    /// An endpoint said why it is about to close its connection.
+    Goodbye {
+        id: u64,
+        server_info: Option<ServerInfo>,
+        goodbye: Goodbye,
+    },
+    /// An endpoint was forgotten once its connection dropped, along with the public keys that
+    /// were routed to it. The keys can be handed to
+    /// [`ServerHandle::propagate_disconnect`](super::ServerHandle::propagate_disconnect), so that
+    /// other servers stop routing them to this node.
+    Disconnected {
+        id: u64,
+        server_info: Option<ServerInfo>,
+        public_keys: Vec<PublicKey>,
+    },
+    /// This standby was promoted to a primary, having replicated the journal of its primary up to
+    /// `position`.
+    Promoted { position: JournalPosition },
+    /// A public key was revoked. The revocation can be handed to
+    /// [`ServerHandle::propagate_revocation`](super::ServerHandle::propagate_revocation), so that
+    /// it also reaches those waiting for the key on other servers.
+    Revoked { revocation: KeyTriad<SignedData> },
+    /// The memory the node held as the reaper last ran. Refer to
+    /// [`ServerHandle::memory_usage`](super::ServerHandle::memory_usage).
+    MemoryUsage { usage: MemoryUsage },
+}
@@ -0,0 +1,151 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use futures::{Future, FutureExt};
+use tokio::sync::broadcast;
+use tokio::task::JoinSet;
+
+use super::NodeEvent;
+use crate::utils::LockExt;
+
+#[derive(Default)]
+struct State {
+    /// The endpoints that have queued work, in the order they are served.
+    order: VecDeque<u64>,
+    /// The queued work of each endpoint.
+    queues: HashMap<u64, VecDeque<BoxFuture<'static, ()>>>,
+    /// The amount of running workers.
+    active: usize,
+    /// Whether the scheduler was shut down, after which work is no longer queued.
+    closed: bool,
+}
+
+/// Runs work the node initiates on behalf of endpoints, such as notifications, on a bounded
+/// amount of workers. The endpoints with queued work are served one job at a time in turn, so that
+/// an endpoint with a lot of work cannot hold up the work of the others.
+///
+/// Workers are spawned when work is queued, and stop once every queue is empty. The workers are
+/// owned by the scheduler, and are cancelled when it is shut down or dropped. A job that panics is
+/// reported as a [`NodeEvent::TaskPanicked`], and does not take its worker down with it.
+pub(crate) struct FairScheduler {
+    state: Arc<Mutex<State>>,
+    tasks: Mutex<JoinSet<()>>,
+    events: broadcast::Sender<NodeEvent>,
+    workers: usize,
+}
+
+impl std::fmt::Debug for FairScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FairScheduler")
+            .field("workers", &self.workers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FairScheduler {
+    pub(crate) fn new(workers: usize, events: broadcast::Sender<NodeEvent>) -> Self {
+        Self {
+            state: Default::default(),
+            tasks: Default::default(),
+            events,
+            workers: workers.max(1),
+        }
+    }
+    /// Queues `job` as work of the endpoint `id`. Must be called within a tokio runtime. The job
+    /// is dropped if the scheduler was shut down.
+    pub(crate) fn submit(&self, id: u64, job: impl Future<Output = ()> + Send + 'static) {
+        let mut state = self.state.locked();
+        if state.closed {
+            return;
+        }
+
+        let queue = state.queues.entry(id).or_default();
+        queue.push_back(Box::pin(job));
+        if queue.len() == 1 {
+            state.order.push_back(id);
+        }
+
+        if state.active < self.workers {
+            state.active += 1;
+
+            let mut tasks = self.tasks.locked();
+            // forget the workers that already stopped
+            while tasks.try_join_next().is_some() {}
+            tasks.spawn(Self::work(self.state.clone(), self.events.clone()));
+        }
+    }
+    /// Returns the amount of jobs queued as work of the endpoint `id`, not counting a job that is
+    /// running.
+    pub(crate) fn queued(&self, id: u64) -> usize {
+        let state = self.state.locked();
+        state.queues.get(&id).map_or(0, VecDeque::len)
+    }
+    /// Drops the jobs queued as work of the endpoint `id`. A job that is running is left to finish.
+    /// Returns the amount of jobs dropped.
+    pub(crate) fn cancel(&self, id: u64) -> usize {
+        let mut state = self.state.locked();
+        let Some(queue) = state.queues.remove(&id) else {
+            return 0;
+        };
+        state.order.retain(|queued| *queued != id);
+        queue.len()
+    }
+    /// Cancels the running workers and drops the queued work.
+    pub(crate) fn shutdown(&self) {
+        let mut state = self.state.locked();
+        state.closed = true;
+        state.order.clear();
+        state.queues.clear();
+        state.active = 0;
+
+        self.tasks.locked().abort_all();
+    }
+    async fn work(state: Arc<Mutex<State>>, events: broadcast::Sender<NodeEvent>) {
+        loop {
+            let (id, job) = {
+                let mut state = state.locked();
+                let id = match state.order.pop_front() {
+                    Some(value) => value,
+                    None => {
+                        state.active -= 1;
+                        return;
+                    }
+                };
+
+                let queue = state
+                    .queues
+                    .get_mut(&id)
+                    .expect("queued endpoints have work");
+                let job = queue.pop_front().expect("queued endpoints have work");
+                // the endpoint goes to the back of the line if it has more work
+                if queue.is_empty() {
+                    state.queues.remove(&id);
+                } else {
+                    state.order.push_back(id);
+                }
+                (id, job)
+            };
+
+            if let Err(payload) = AssertUnwindSafe(job).catch_unwind().await {
+                let _ = events.send(NodeEvent::TaskPanicked {
+                    id,
+                    message: panic_message(payload.as_ref()),
+                });
+            }
+        }
+    }
+}
+
+/// Returns the message a panic was raised with.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io;
+use std::sync::Mutex;
+
+use tower_async::Service;
+
+use super::*;
+
+/// Persists the position of the sequence of key changes of a node, so that the sequence
+/// continues where it left off after a restart.
+///
+/// The methods may block, such as on file I/O. Positions are saved on the blocking threads of the
+/// tokio runtime, outside of the locks of the node, one at a time and in order.
+pub trait GossipStore: Debug + Send + Sync {
+    /// Returns the position that was saved last, if any.
+    fn load(&self) -> io::Result<Option<GossipPosition>>;
+
+    /// Saves `position`, replacing the previous position.
+    fn save(&self, position: GossipPosition) -> io::Result<()>;
+}
+
+/// A [`GossipStore`] that keeps the position in memory.
+#[derive(Debug, Default)]
+pub struct MemoryGossipStore {
+    position: Mutex<Option<GossipPosition>>,
+}
+
+impl GossipStore for MemoryGossipStore {
+    fn load(&self) -> io::Result<Option<GossipPosition>> {
+        Ok(*self.position.locked())
+    }
+    fn save(&self, position: GossipPosition) -> io::Result<()> {
+        *self.position.locked() = Some(position);
+        Ok(())
+    }
+}
+
+/// Saves the positions of a [`GossipLog`] in its store, skipping to the latest position when
+/// saving falls behind.
+#[derive(Debug)]
+struct GossipWriter {
+    store: Arc<dyn GossipStore>,
+    /// The position to save next, and whether a task is saving positions.
+    pending: Mutex<(Option<GossipPosition>, bool)>,
+}
+
+impl GossipWriter {
+    /// Saves `position` on a blocking thread, unless a later position is saved first.
+    fn save(self: &Arc<Self>, position: GossipPosition) {
+        {
+            let mut pending = self.pending.locked();
+            if pending.0.is_none_or(|pending| pending.seq < position.seq) {
+                pending.0 = Some(position);
+            }
+            if pending.1 {
+                return;
+            }
+            pending.1 = true;
+        }
+
+        let writer = self.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(move || writer.drain());
+            }
+            Err(_) => writer.drain(),
+        }
+    }
+    fn drain(&self) {
+        loop {
+            let position = {
+                let mut pending = self.pending.locked();
+                match pending.0.take() {
+                    Some(position) => position,
+                    None => {
+                        pending.1 = false;
+                        return;
+                    }
+                }
+            };
+            // a position that failed to save is saved along with the next change
+            let _ = self.store.save(position);
+        }
+    }
+}
+
+/// The recent changes to the set of identified public keys, numbered in sequence.
+#[derive(Debug)]
+pub(crate) struct GossipLog {
+    position: GossipPosition,
+    /// The sequence number after which every change is held.
+    start: u64,
+    changes: VecDeque<KeyChange>,
+    writer: Option<Arc<GossipWriter>>,
+}
+
+impl Default for GossipLog {
+    fn default() -> Self {
+        Self {
+            position: GossipPosition {
+                epoch: u64::from_be_bytes(utils::random_bytes()),
+                seq: 0,
+            },
+            start: 0,
+            changes: VecDeque::new(),
+            writer: None,
+        }
+    }
+}
+
+impl GossipLog {
+    /// Continues the sequence saved in `store`, or starts a new one if it holds none.
+    ///
+    /// The set of keys does not survive a restart, so the restart itself takes a sequence number,
+    /// and peers that last saw a position before it must fetch the whole set. Sequence numbers
+    /// are never reused, so positions taken after the restart stay comparable.
+    ///
+    /// Blocks on the store, which is only read when the node is built.
+    fn restore(store: Arc<dyn GossipStore>) -> io::Result<Self> {
+        let mut log = Self::default();
+        if let Some(position) = store.load()? {
+            log.position = GossipPosition {
+                seq: position.seq + 1,
+                ..position
+            };
+        }
+        store.save(log.position)?;
+        log.start = log.position.seq;
+        log.writer = Some(Arc::new(GossipWriter {
+            store,
+            pending: Default::default(),
+        }));
+
+        Ok(log)
+    }
+    /// Records a change, returning the position to save once the log is unlocked, if the log is
+    /// persisted.
+    fn record(
+        &mut self,
+        key: PublicKey,
+        kind: KeyChangeKind,
+        history: usize,
+    ) -> Option<(Arc<GossipWriter>, GossipPosition)> {
+        self.position.seq += 1;
+        self.changes.push_back(KeyChange {
+            seq: self.position.seq,
+            key,
+            kind,
+        });
+        while self.changes.len() > history {
+            if let Some(change) = self.changes.pop_front() {
+                self.start = change.seq;
+            }
+        }
+
+        self.writer
+            .as_ref()
+            .map(|writer| (writer.clone(), self.position))
+    }
+    /// Returns the amount of changes held.
+    pub(crate) fn len(&self) -> usize {
+        self.changes.len()
+    }
+    /// Returns the store the position is persisted in, if any.
+    pub(crate) fn store(&self) -> Option<Arc<dyn GossipStore>> {
+        self.writer.as_ref().map(|writer| writer.store.clone())
+    }
+    fn since(&self, since: GossipPosition) -> Option<Vec<KeyChange>> {
+        if since.epoch != self.position.epoch
+            || since.seq < self.start
+            || since.seq > self.position.seq
+        {
+            return None;
+        }
+
+        Some(
+            self.changes
+                .iter()
+                .filter(|change| change.seq > since.seq)
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Persists the sequence of key changes of this node in `store`, continuing the sequence
+    /// saved in it by a previous run.
+    pub fn with_gossip_store(mut self, store: Arc<dyn GossipStore>) -> io::Result<Self> {
+        self.gossip = Mutex::new(GossipLog::restore(store)?);
+        Ok(self)
+    }
+    /// Returns the position of the latest change to the set of identified public keys.
+    pub fn gossip_position(&self) -> GossipPosition {
+        self.gossip.locked().position
+    }
+    /// Records that the set of identified public keys changed.
+    pub(crate) fn record_change(&self, key: PublicKey, kind: KeyChangeKind) {
+        let save = self
+            .gossip
+            .locked()
+            .record(key, kind, self.config.gossip_history);
+        if let Some((writer, position)) = save {
+            writer.save(position);
+        }
+    }
+    /// Returns the changes to the set of identified public keys since `since`, along with the
+    /// position of the last of them. Returns at most [`SizeLimits::max_entries`] changes, and the
+    /// rest from the returned position.
+    pub fn key_changes(&self, since: GossipPosition) -> KeyChangesResp {
+        let gossip = self.gossip.locked();
+        let mut position = gossip.position;
+        let mut changes = gossip.since(since);
+        if let Some(changes) = &mut changes {
+            let max = self.config.size_limits.max_entries;
+            if changes.len() > max {
+                changes.truncate(max);
+                position.seq = changes.last().map_or(since.seq, |change| change.seq);
+            }
+        }
+        KeyChangesResp { position, changes }
+    }
+}
+
+impl<C: ?Sized> Service<KeyChangesReq> for InboundEndpoint<C> {
+    type Response = KeyChangesResp;
+    type Error = KeysRootReqError;
+
+    async fn call(&self, req: KeyChangesReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        Ok(server_hdl.key_changes(req.since))
+    }
+}
+impl<C: ?Sized> Service<KeyChangesReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<KeyChangesReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<KeyChangesReq>>::Error;
+
+    fn call(
+        &self,
+        req: KeyChangesReq,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
@@ -0,0 +1,226 @@
+use tower_async::Service;
+
+use super::*;
+
+/// The weight given to the newest round trip time when smoothing, out of 8.
+const RTT_WEIGHT: u64 = 2;
+/// The round trip time servers are ranked by until a request forwarded to them succeeds, in
+/// milliseconds.
+const UNMEASURED_RTT: u64 = 5_000;
+
+/// The health of a connected server, measured from the requests forwarded to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PeerHealth {
+    /// The smoothed round trip time of successful requests, in milliseconds.
+    pub rtt: u64,
+    /// The amount of requests forwarded to the server.
+    pub requests: u64,
+    /// The amount of forwarded requests that failed.
+    pub errors: u64,
+    /// The amount of forwarded requests that failed since the last one that succeeded.
+    pub consecutive_errors: u32,
+    /// The timestamp until which the server is demoted, if it is.
+    pub demoted_until: Option<u64>,
+    /// The load the server advertised the last time it connected, if it did.
+    pub load: Option<Load>,
+}
+
+impl PeerHealth {
+    /// Returns whether requests should be forwarded to the server at the time `now`.
+    pub fn available(&self, now: u64) -> bool {
+        self.demoted_until.is_none_or(|until| now >= until)
+    }
+    /// Returns the round trip time the server is ranked by. Is pessimistic while no request
+    /// forwarded to the server succeeded, so that measured servers are preferred.
+    pub fn ranked_rtt(&self) -> u64 {
+        match self.requests == self.errors {
+            true => UNMEASURED_RTT,
+            false => self.rtt,
+        }
+    }
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the exact current load of this node.
+    pub fn load(&self) -> Load {
+        Load {
+            relay_saturation: self.relay_saturation.load(Ordering::Relaxed),
+            connections: self.connections.load(Ordering::Relaxed),
+            identities: self.key_to_endpoint.len() as u32,
+        }
+    }
+    /// Returns the load this node advertises in its [`NodeInfo`], with the counts blurred as
+    /// configured in [`NodeConfig::public_counts`].
+    pub fn public_load(&self) -> Load {
+        let load = self.load();
+        let Some(privacy) = self.config.public_counts else {
+            return load;
+        };
+
+        Load {
+            connections: privacy.blur(
+                load.connections,
+                self.count_draw(b"connections", load.connections),
+            ),
+            identities: privacy.blur(
+                load.identities,
+                self.count_draw(b"identities", load.identities),
+            ),
+            ..load
+        }
+    }
+    /// Returns the draw that picks the noise of the count `count` of `label`. Is derived from a
+    /// secret of the node and the count, so that asking for the same count again returns the
+    /// same noise, rather than noise that averages out.
+    fn count_draw(&self, label: &[u8], count: u32) -> u64 {
+        let mut hasher = blake3::Hasher::new_keyed(&self.token_key);
+        hasher.update(b"cacophoney public count");
+        hasher.update(&(label.len() as u64).to_be_bytes());
+        hasher.update(label);
+        hasher.update(&count.to_be_bytes());
+
+        let mut draw = [0u8; 8];
+        draw.copy_from_slice(&hasher.finalize().as_bytes()[..8]);
+        u64::from_be_bytes(draw)
+    }
+    /// Reports how many of the streams relayed to other servers are in use, in percent. The node
+    /// measures the streams it pre-establishes to connected servers itself as it relays, so this
+    /// is only needed by transports that pool streams of their own, such as with
+    /// [`StreamPool::saturation`]. The next relay replaces the reported value.
+    pub fn set_relay_saturation(&self, percent: u8) {
+        self.relay_saturation
+            .store(percent.min(100), Ordering::Relaxed);
+    }
+    /// Measures how many of the streams pre-established to connected servers were taken by the
+    /// communications relayed to them, and reports it as the relay saturation of the node.
+    pub(crate) async fn measure_relay_saturation(&self) {
+        let peers: Vec<_> = self
+            .connected_servers
+            .read()
+            .await
+            .iter()
+            .cloned()
+            .collect();
+        let (mut depleted, mut ready) = (0, 0);
+        for peer in peers {
+            let (peer_depleted, peer_ready) = peer.streams.depletion().await;
+            depleted += peer_depleted;
+            ready += peer_ready;
+        }
+        if let Some(percent) = (depleted * 100).checked_div(ready) {
+            self.set_relay_saturation(percent as u8);
+        }
+    }
+    /// Returns the health of the connected server with the endpoint id `id`.
+    pub async fn peer_health(&self, id: u64) -> Option<PeerHealth> {
+        self.peer_health.read_async(&id, |_, v| *v).await
+    }
+    /// Returns the connected servers requests should be forwarded to, healthiest first. Servers
+    /// that are as fast are ordered by their load.
+    pub(crate) async fn forward_peers(&self) -> Vec<InboundHdl<C>> {
+        let now = utils::now();
+        let mut peers = Vec::new();
+
+        for peer in self.connected_servers.read().await.iter() {
+            let health = self.peer_health(peer.id).await.unwrap_or_default();
+            if health.available(now) {
+                peers.push(((health.ranked_rtt(), health.load), peer.clone()));
+            }
+        }
+        peers.sort_by_key(|(key, _)| *key);
+
+        peers.into_iter().map(|(_, peer)| peer).collect()
+    }
+    /// Records the load `peer` advertised, if it is a server.
+    pub(crate) async fn record_peer_load(&self, peer: &InboundEndpoint<C>, load: Load) {
+        if peer.info.server_info.is_none() {
+            return;
+        }
+        let mut entry = self.peer_health.entry_async(peer.id).await.or_default();
+        entry.get_mut().load = Some(load);
+    }
+    /// Sends `req` to every connected server at once, including demoted ones, waiting at most
+    /// [`NodeConfig::push_timeout`] for each. Returns the amount of servers that accepted it.
+    pub(crate) async fn push_peers<Req: Clone>(&self, req: Req) -> usize
+    where
+        C: Service<Req>,
+    {
+        let peers: Vec<_> = self
+            .connected_servers
+            .read()
+            .await
+            .iter()
+            .cloned()
+            .collect();
+
+        let pushes = peers.iter().map(|peer| {
+            let req = req.clone();
+            async move {
+                let start = utils::now();
+                let accepted = matches!(
+                    tokio::time::timeout(self.config.push_timeout, peer.conn.call(req)).await,
+                    Ok(Ok(_))
+                );
+                let rtt = accepted.then(|| utils::now().saturating_sub(start));
+                self.record_peer(peer, rtt).await;
+                accepted
+            }
+        });
+        futures::future::join_all(pushes)
+            .await
+            .into_iter()
+            .filter(|accepted| *accepted)
+            .count()
+    }
+    /// Records the outcome of a request forwarded to `peer`. `rtt` is [`None`] if the request
+    /// failed.
+    pub(crate) async fn record_peer(&self, peer: &InboundHdl<C>, rtt: Option<u64>) {
+        let server_info = match &peer.info.server_info {
+            Some(value) => value.clone(),
+            None => return,
+        };
+        let now = utils::now();
+
+        let mut entry = self.peer_health.entry_async(peer.id).await.or_default();
+        let health = entry.get_mut();
+        health.requests += 1;
+
+        let event = match rtt {
+            Some(rtt) => {
+                health.rtt = match health.requests - health.errors {
+                    1 => rtt,
+                    _ => (health.rtt * (8 - RTT_WEIGHT) + rtt * RTT_WEIGHT) / 8,
+                };
+                health.consecutive_errors = 0;
+
+                health
+                    .demoted_until
+                    .take()
+                    .map(|_| NodeEvent::PeerRestored {
+                        id: peer.id,
+                        server_info,
+                    })
+            }
+            None => {
+                health.errors += 1;
+                health.consecutive_errors += 1;
+
+                let demote = health.consecutive_errors >= self.config.peer_demote_errors
+                    && health.available(now);
+                demote.then(|| {
+                    health.demoted_until =
+                        Some(now + self.config.peer_demote_duration.as_millis() as u64);
+                    NodeEvent::PeerDemoted {
+                        id: peer.id,
+                        server_info,
+                    }
+                })
+            }
+        };
+        drop(entry);
+
+        if let Some(event) = event {
+            let _ = self.events.send(event);
+        }
+    }
+}
@@ -0,0 +1,537 @@
+use tower_async::Service;
+
+use super::*;
+use crate::crypto::delegation::{verify_chain, Delegation, DelegationScope};
+use crate::crypto::schnorr::SignatureScheme;
+
+impl<C: ?Sized> InboundEndpoint<C> {
+    async fn current_identify_data(&self) -> Result<IdentifyData, IdentifyReqError> {
+        let identify_data = match self.server_hdl.as_ref().and_then(Weak::upgrade) {
+            Some(server_hdl) => {
+                server_hdl
+                    .challenges
+                    .read_async(&self.id, |_, data| data.clone())
+                    .await
+            }
+            None => self.identify_data.read().await.clone(),
+        };
+        match identify_data {
+            Some(value) => Ok(value),
+            None => Err(IdentifyReqError::IdentifyDataInvalid),
+        }
+    }
+}
+
+/// Decodes the signable of an identify triad and checks its message type.
+fn decode(triad: &KeyTriad<SignedData>) -> Result<CachedSigned<IdentifyData>, IdentifyReqError> {
+    WrongMessageTypeError::check(SignMessageType::Identify, &triad.signed)?;
+
+    Ok(triad.signed.clone().to_cached::<IdentifyData>()?)
+}
+
+/// Returns the validity limits of the node of `server_hdl`, or the default limits if the endpoint
+/// is not connected to a node.
+fn limits<C: ?Sized>(server_hdl: Option<&Arc<ServerHandle<C>>>) -> ValidityLimits {
+    server_hdl
+        .map(|hdl| hdl.config.validity)
+        .unwrap_or_default()
+}
+
+/// Returns the size limits of the node of `server_hdl`, or the default limits if the endpoint is
+/// not connected to a node.
+fn size_limits<C: ?Sized>(server_hdl: Option<&Arc<ServerHandle<C>>>) -> SizeLimits {
+    server_hdl
+        .map(|hdl| hdl.config.size_limits)
+        .unwrap_or_default()
+}
+
+/// Checks that `hdl` may identify as `count` more public keys, within the
+/// [`NodeConfig::max_identities`] of its node.
+fn check_identities<C: ?Sized>(
+    hdl: &InboundEndpoint<C>,
+    server_hdl: Option<&Arc<ServerHandle<C>>>,
+    count: usize,
+) -> Result<(), IdentifyReqError> {
+    match server_hdl.and_then(|hdl| hdl.config.max_identities) {
+        Some(max) if hdl.identities.len() + count > max => {
+            Err(IdentifyReqError::TooManyIdentities { max })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the signed identify data carries a proof of work by `public_key` that meets the
+/// difficulty handed out to the endpoint. Is checked before the signature, which costs more to
+/// verify than the proof.
+fn check_work(
+    identify_data: &IdentifyData,
+    public_key: &PublicKey,
+    cached: &CachedSigned<IdentifyData>,
+) -> Result<(), IdentifyReqError> {
+    let value = IdentifyData {
+        difficulty: identify_data.difficulty,
+        ..cached.signable.obj.clone()
+    };
+    match value.work_valid(public_key) {
+        true => Ok(()),
+        false => Err(IdentifyReqError::InsufficientWork),
+    }
+}
+
+/// Checks that the signed identify data is the data handed out to the endpoint with a proof of
+/// work by `public_key`, that it has not expired, and that the endpoint `info` may use the key.
+fn check(
+    identify_data: &IdentifyData,
+    public_key: &PublicKey,
+    cached: &CachedSigned<IdentifyData>,
+    limits: &ValidityLimits,
+    info: &EndpointInfo,
+) -> Result<(), IdentifyReqError> {
+    let value = &cached.signable;
+
+    // Check if the signer accepted the policy of the node.
+    if value.obj.extensions != identify_data.extensions {
+        return Err(IdentifyReqError::ExtensionsMismatch);
+    }
+
+    // Check if the identify data is the same, apart from what the signer fills in.
+    let handed_out = IdentifyData {
+        work: None,
+        usage: KeyUsage::empty(),
+        ..value.obj.clone()
+    };
+    if handed_out != *identify_data {
+        return Err(IdentifyReqError::IdentifyDataInvalid);
+    }
+    check_work(identify_data, public_key, cached)?;
+
+    let now = utils::now();
+    if now > value.obj.expire_time {
+        return Err(IdentifyReqError::Expired);
+    }
+    limits.check_window(value.obj.start_time, value.obj.expire_time, now)?;
+
+    if value.obj.usage.contains(KeyUsage::SERVER_ONLY) && info.server_info.is_none() {
+        return Err(IdentifyReqError::ServerOnly);
+    }
+
+    Ok(())
+}
+
+/// Checks that the payload of `triad` is in canonical form, if the node of `server_hdl` requires
+/// it.
+fn check_canonical<C: ?Sized>(
+    server_hdl: Option<&Arc<ServerHandle<C>>>,
+    triad: &KeyTriad<SignedData>,
+) -> Result<(), IdentifyReqError> {
+    match server_hdl {
+        Some(hdl) if hdl.config.require_canonical && !triad.signed.is_canonical() => {
+            Err(IdentifyReqError::NonCanonical)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `triad` is signed with `scheme`, the scheme negotiated with the endpoint, so that
+/// the payload does not choose how its signature is verified.
+fn check_scheme(
+    scheme: SignatureScheme,
+    triad: &KeyTriad<SignedData>,
+) -> Result<(), IdentifyReqError> {
+    match triad.signed.signature_scheme() {
+        found if found == scheme => Ok(()),
+        found => Err(IdentifyReqError::SchemeMismatch {
+            expected: scheme,
+            found,
+        }),
+    }
+}
+
+/// Checks that none of `keys` is revoked or banned by the node of `server_hdl`.
+async fn check_keys<C: ?Sized>(
+    server_hdl: &ServerHandle<C>,
+    info: &EndpointInfo,
+    keys: &[&PublicKey],
+) -> Result<(), IdentifyReqError> {
+    for key in keys {
+        if server_hdl.revoked.contains_async(*key).await {
+            return Err(IdentifyReqError::Revoked);
+        }
+    }
+    server_hdl.check_bans(info, keys.iter().copied()).await?;
+
+    Ok(())
+}
+
+/// A triad whose signature has already been verified, waiting to be registered.
+pub(super) struct Pending {
+    /// The key the triad identifies as, which is the key of the triad or the root of its
+    /// delegation chain.
+    pub(super) public_key: PublicKey,
+    pub(super) triad: KeyTriad<SignedData>,
+    /// The other keys the identity rests on, such as the cosigners of a group or the delegates of
+    /// a chain.
+    pub(super) signers: Vec<PublicKey>,
+    pub(super) cached: CachedSigned<IdentifyData>,
+}
+
+/// Registers a batch of verified triads. Either every triad of the batch is registered or none of
+/// them are: every key is claimed and published before any other trace of the batch is left.
+pub(super) async fn register<C: Notify + Send + Sync + 'static + ?Sized>(
+    hdl: &InboundHdl<C>,
+    pending: Vec<Pending>,
+) -> Result<(), IdentifyReqError> {
+    let server_hdl = match &hdl.server_hdl {
+        Some(weak) => Some(weak.upgrade().ok_or(ServerHdlDroppedError)?),
+        None => None,
+    };
+    if let Some(server_hdl) = &server_hdl {
+        for pending in pending.iter() {
+            let mut keys = vec![&pending.public_key, &pending.triad.public_key];
+            keys.extend(pending.signers.iter());
+            check_keys(server_hdl, &hdl.info, &keys).await?;
+        }
+    }
+
+    // Claim the keys before anything else, so that an endpoint identifying as a key it already
+    // identified as leaves no trace
+    let mut registered = Vec::with_capacity(pending.len());
+    for pending in pending {
+        let cached_triad = KeyTriad {
+            public_key: pending.triad.public_key,
+            signature: pending.triad.signature,
+            signed: pending.cached,
+        };
+        let usage = cached_triad.signed.signable.obj.usage;
+        if hdl
+            .identities
+            .insert_async(pending.public_key, cached_triad)
+            .await
+            .is_err()
+        {
+            for (public_key, _, _) in registered {
+                hdl.identities.remove_async(&public_key).await;
+            }
+            return Err(IdentifyReqError::AlreadyIdentified);
+        }
+        registered.push((pending.public_key, pending.triad, usage));
+    }
+
+    if let Some(server_hdl) = server_hdl {
+        // the most recent endpoint that identified as the key receives its requests
+        for (public_key, _, _) in registered.iter() {
+            server_hdl
+                .key_to_endpoint
+                .entry_async(*public_key)
+                .await
+                .and_modify(|endpoint| *endpoint = hdl.clone())
+                .or_insert_with(|| hdl.clone());
+        }
+        // the endpoint was forgotten while it identified
+        if hdl.is_disconnected() {
+            for (public_key, _, _) in registered {
+                server_hdl
+                    .key_to_endpoint
+                    .remove_if_async(&public_key, |endpoint| endpoint == hdl)
+                    .await;
+                hdl.identities.remove_async(&public_key).await;
+            }
+            return Err(IdentifyReqError::Disconnected);
+        }
+
+        for (public_key, triad, usage) in registered.iter() {
+            let (public_key, usage) = (*public_key, *usage);
+            server_hdl.set_usage(public_key, usage).await;
+            server_hdl.invalidate_key_set().await;
+            if !usage.contains(KeyUsage::UNLISTED) {
+                server_hdl.record_change(public_key, KeyChangeKind::Joined);
+            }
+            server_hdl.unpark(public_key, hdl).await;
+            server_hdl.journal(JournalEntry::Identified {
+                public_key,
+                usage,
+                time: utils::now(),
+            });
+            server_hdl.restore_subscriptions(&public_key, hdl).await;
+            let _ = server_hdl
+                .resumptions
+                .insert_async(hdl.resumption_token, hdl.clone())
+                .await;
+
+            server_hdl
+                .attest(LogEntry::Identified {
+                    public_key,
+                    time: utils::now(),
+                })
+                .await;
+
+            // Notify endpoints that wanted to be notified when this public key connected. Unlisted
+            // keys are not announced, so the endpoints keep waiting.
+            let notifications = match usage.contains(KeyUsage::UNLISTED) {
+                true => None,
+                false => server_hdl.notifications.remove_async(&public_key).await,
+            };
+            if let Some((_, endpoints)) = notifications {
+                server_hdl.gauges.subscribers.update(endpoints.len(), 0);
+                for endpoint in endpoints.into_iter() {
+                    let triad = triad.clone();
+                    // Fire and forget the notification
+                    server_hdl.fanout.submit(endpoint.id, async move {
+                        let _ = endpoint.conn.notify_connected(&triad).await;
+                    });
+                }
+            }
+
+            // deliver the messages relayed to the key while it was offline
+            server_hdl.deliver_mail(public_key, hdl.clone()).await;
+        }
+    }
+
+    // Add to vector for enumeration
+    let mut public_keys = hdl.public_keys.write().await;
+    public_keys.extend(registered.into_iter().map(|(public_key, _, _)| public_key));
+
+    Ok(())
+}
+
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<KeyTriad<SignedData>> for InboundHdl<C> {
+    type Response = IdentifyResp;
+    type Error = IdentifyReqError;
+
+    async fn call(&self, triad: KeyTriad<SignedData>) -> Result<Self::Response, Self::Error> {
+        limit::acquire(&self.request_limiters.identify, utils::now())?;
+        let identify_data = self.current_identify_data().await?;
+        let server_hdl = self.server_hdl.as_ref().and_then(Weak::upgrade);
+        size_limits(server_hdl.as_ref()).check_payload(&triad.signed)?;
+        check_identities(self, server_hdl.as_ref(), 1)?;
+        check_canonical(server_hdl.as_ref(), &triad)?;
+        check_scheme(self.features.read().await.signature_scheme(), &triad)?;
+        let cached = decode(&triad)?;
+        check_work(&identify_data, &triad.public_key, &cached)?;
+
+        // Check the validity of the signature
+        match &server_hdl {
+            Some(hdl) => {
+                hdl.verify(&triad.public_key, &cached, &triad.signature)
+                    .await?
+            }
+            None => {
+                let msg = SignedHash::of(&cached);
+                spawn_verify(None, triad.public_key, msg, triad.signature).await?
+            }
+        }
+
+        check(
+            &identify_data,
+            &triad.public_key,
+            &cached,
+            &limits(server_hdl.as_ref()),
+            &self.info,
+        )?;
+        let pending = Pending {
+            public_key: triad.public_key,
+            triad,
+            signers: Vec::new(),
+            cached,
+        };
+        register(self, vec![pending]).await?;
+
+        Ok(IdentifyResp {
+            resumption_token: self.resumption_token,
+        })
+    }
+}
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<RecoverableTriad<SignedData>>
+    for InboundHdl<C>
+{
+    type Response = IdentifyResp;
+    type Error = IdentifyReqError;
+
+    async fn call(
+        &self,
+        triad: RecoverableTriad<SignedData>,
+    ) -> Result<Self::Response, Self::Error> {
+        let triad = triad
+            .recover()
+            .map_err(|_| IdentifyReqError::SignatureInvalid)?;
+
+        self.call(triad).await
+    }
+}
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<IdentifyReq> for InboundHdl<C> {
+    type Response = IdentifyResp;
+    type Error = IdentifyReqError;
+
+    async fn call(&self, req: IdentifyReq) -> Result<Self::Response, Self::Error> {
+        limit::acquire(&self.request_limiters.identify, utils::now())?;
+        let identify_data = self.current_identify_data().await?;
+        let server_hdl = self.server_hdl.as_ref().and_then(Weak::upgrade);
+
+        // reject oversized batches before recovering or verifying any of them
+        let size_limits = size_limits(server_hdl.as_ref());
+        let signers: usize = req.multi.iter().map(|multi| multi.signers.len()).sum();
+        size_limits
+            .check_batch(req.keys.len() + req.compact.len() + signers + req.delegated.len())?;
+
+        let mut triads = req.keys;
+        for compact in req.compact {
+            triads.push(
+                compact
+                    .recover()
+                    .map_err(|_| IdentifyReqError::SignatureInvalid)?,
+            );
+        }
+
+        // pair every triad with the key it identifies as, and the other keys the identity rests on
+        let mut triads: Vec<_> = triads
+            .into_iter()
+            .map(|triad| (triad.public_key, triad, Vec::new()))
+            .collect();
+
+        // A multi-key triad registers its identity with the triad of its first signer. The
+        // signatures of the other signers are verified with the batch, and their keys are checked
+        // like the keys of the triads.
+        let limits = limits(server_hdl.as_ref());
+        let now = utils::now();
+        let mut cosigned = Vec::new();
+        for multi in req.multi {
+            size_limits.check_payload(&multi.group.signed)?;
+            let group = multi.key_group()?;
+            size_limits.check_keys(group.keys.len())?;
+            limits.check_expiry(group.expire_time, now)?;
+            let identity = multi.check(now)?;
+            let msg = SignedHash::of(&multi.signed);
+            let mut signers = multi.signers.into_iter();
+            let first = signers.next().ok_or(IdentifyReqError::SignatureInvalid)?;
+            let mut cosigners = Vec::new();
+            for signer in signers {
+                cosigners.push(signer.public_key);
+                cosigned.push((signer.public_key, msg, signer.signature));
+            }
+            let triad = KeyTriad {
+                public_key: first.public_key,
+                signature: first.signature,
+                signed: multi.signed,
+            };
+            triads.push((identity, triad, cosigners));
+        }
+
+        for delegated in req.delegated {
+            let root = verify_chain(
+                &delegated.chain,
+                &delegated.triad.public_key,
+                DelegationScope::IDENTIFY,
+                now,
+            )?;
+            for link in delegated.chain.iter() {
+                limits.check_expiry(Delegation::decode(&link.signed)?.expire_time, now)?;
+            }
+            // a delegate that was revoked cannot vouch for the keys it delegated to
+            let delegates = delegated.chain.iter().map(|link| link.public_key).collect();
+            triads.push((root, delegated.triad, delegates));
+        }
+
+        // Reject keys that cannot be registered up front, so that either every key is
+        // registered or none of them are
+        check_identities(self, server_hdl.as_ref(), triads.len())?;
+        let scheme = self.features.read().await.signature_scheme();
+        let mut keys = HashSet::with_capacity(triads.len());
+        for (public_key, triad, signers) in triads.iter() {
+            if !keys.insert(*public_key) || self.identities.contains_async(public_key).await {
+                return Err(IdentifyReqError::AlreadyIdentified);
+            }
+            check_canonical(server_hdl.as_ref(), triad)?;
+            check_scheme(scheme, triad)?;
+            if let Some(server_hdl) = &server_hdl {
+                let mut keys = vec![public_key, &triad.public_key];
+                keys.extend(signers);
+                check_keys(server_hdl, &self.info, &keys).await?;
+            }
+        }
+
+        let mut pending = Vec::with_capacity(triads.len());
+        let mut batch = Vec::with_capacity(triads.len());
+        for (public_key, triad, signers) in triads {
+            size_limits.check_payload(&triad.signed)?;
+            let cached = decode(&triad)?;
+            check_work(&identify_data, &triad.public_key, &cached)?;
+            batch.push((triad.public_key, SignedHash::of(&cached), triad.signature));
+            pending.push(Pending {
+                public_key,
+                triad,
+                signers,
+                cached,
+            });
+        }
+        batch.extend(cosigned);
+
+        // skip the signatures that were verified recently
+        let verify_cache = server_hdl
+            .as_ref()
+            .and_then(|hdl| hdl.verify_cache.as_ref());
+        if let Some(cache) = verify_cache {
+            batch.retain(|item| !cache.contains(item));
+        }
+
+        // Verify every signature up front, so that no key is registered if any of them is invalid
+        let batch: Arc<[_]> = Arc::from(batch);
+        let pool = server_hdl.as_ref().and_then(|hdl| hdl.crypto_pool.as_ref());
+        let valid = spawn_verify_batch(pool, batch.clone()).await;
+        if !valid {
+            // find out why the batch failed
+            for (public_key, msg, signature) in batch.iter() {
+                public_key.verify(*msg, signature)?;
+            }
+            return Err(IdentifyReqError::SignatureInvalid);
+        }
+        if let Some(cache) = verify_cache {
+            batch.iter().for_each(|item| cache.insert(*item));
+        }
+
+        for pending in pending.iter() {
+            check(
+                &identify_data,
+                &pending.triad.public_key,
+                &pending.cached,
+                &limits,
+                &self.info,
+            )?;
+        }
+
+        register(self, pending).await?;
+
+        Ok(IdentifyResp {
+            resumption_token: self.resumption_token,
+        })
+    }
+}
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<MultiKeyTriad<SignedData>>
+    for InboundHdl<C>
+{
+    type Response = IdentifyResp;
+    type Error = IdentifyReqError;
+
+    async fn call(&self, triad: MultiKeyTriad<SignedData>) -> Result<Self::Response, Self::Error> {
+        self.call(IdentifyReq {
+            keys: Vec::new(),
+            compact: Vec::new(),
+            multi: vec![triad],
+            delegated: Vec::new(),
+        })
+        .await
+    }
+}
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<DelegatedTriad> for InboundHdl<C> {
+    type Response = IdentifyResp;
+    type Error = IdentifyReqError;
+
+    async fn call(&self, triad: DelegatedTriad) -> Result<Self::Response, Self::Error> {
+        self.call(IdentifyReq {
+            keys: Vec::new(),
+            compact: Vec::new(),
+            multi: Vec::new(),
+            delegated: vec![triad],
+        })
+        .await
+    }
+}
@@ -0,0 +1,69 @@
+use tower_async::Service;
+
+use super::*;
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the set of public keys identified to this node. The set is rebuilt when a key
+    /// identifies or is revoked.
+    pub async fn key_set(&self) -> Arc<KeySet> {
+        if let Some(set) = &*self.key_set.read().await {
+            return set.clone();
+        }
+
+        let mut cached = self.key_set.write().await;
+        if let Some(set) = &*cached {
+            return set.clone();
+        }
+
+        // unlisted keys cannot be proven to be identified
+        let unlisted = self.unlisted().await;
+        let mut keys = Vec::new();
+        self.key_to_endpoint
+            .scan_async(|key, _| {
+                if !unlisted.contains(key) {
+                    keys.push(*key);
+                }
+            })
+            .await;
+        let set = Arc::new(KeySet::new(keys));
+        *cached = Some(set.clone());
+
+        set
+    }
+    /// Discards the cached set of identified keys, and the set of known keys built from it. Must
+    /// be called after a key is added to or removed from `key_to_endpoint`.
+    pub(crate) async fn invalidate_key_set(&self) {
+        *self.key_set.write().await = None;
+        self.invalidate_known_keys().await;
+    }
+}
+
+impl<C: ?Sized> Service<KeysRootReq> for InboundEndpoint<C> {
+    type Response = KeysRootResp;
+    type Error = KeysRootReqError;
+
+    async fn call(&self, req: KeysRootReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // sign and prove against the same set
+        let set = server_hdl.key_set().await;
+
+        Ok(KeysRootResp {
+            root: set.sign(&server_hdl.key, utils::now()),
+            proofs: req.keys.iter().map(|key| set.prove(key)).collect(),
+        })
+    }
+}
+impl<C: ?Sized> Service<KeysRootReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<KeysRootReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<KeysRootReq>>::Error;
+
+    fn call(&self, req: KeysRootReq) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::error::RateLimitedError;
+use crate::utils::LockExt;
+
+/// A limit on the rate of the requests received on one connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RateLimit {
+    /// The amount of requests that can be sent at once after the connection was idle.
+    pub burst: u32,
+    /// The amount of requests allowed per second once the burst is spent.
+    pub per_second: u32,
+}
+
+/// The limits on the rate of each type of request of one endpoint, on top of the limit on every
+/// request received on the wire. Is [`None`] for a type of request that is not limited on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RequestLimits {
+    pub pre_identify: Option<RateLimit>,
+    /// Applies to each identify request, whatever the amount of keys it identifies.
+    pub identify: Option<RateLimit>,
+    pub keys_exists: Option<RateLimit>,
+    pub communication: Option<RateLimit>,
+}
+
+/// The token buckets enforcing the [`RequestLimits`] of one endpoint.
+#[derive(Debug, Default)]
+pub(crate) struct RequestLimiters {
+    pub(crate) pre_identify: Option<RateLimiter>,
+    pub(crate) identify: Option<RateLimiter>,
+    pub(crate) keys_exists: Option<RateLimiter>,
+    pub(crate) communication: Option<RateLimiter>,
+}
+
+impl RequestLimiters {
+    pub(crate) fn new(limits: &RequestLimits, now: u64) -> Self {
+        let limiter = |limit: Option<RateLimit>| limit.map(|limit| RateLimiter::new(limit, now));
+        Self {
+            pre_identify: limiter(limits.pre_identify),
+            identify: limiter(limits.identify),
+            keys_exists: limiter(limits.keys_exists),
+            communication: limiter(limits.communication),
+        }
+    }
+}
+
+/// Takes the token of a request received at `now` from `limiter`, if the request is limited.
+pub(crate) fn acquire(limiter: &Option<RateLimiter>, now: u64) -> Result<(), RateLimitedError> {
+    match limiter {
+        Some(limiter) => limiter
+            .acquire(now)
+            .map_err(|retry_after| RateLimitedError { retry_after }),
+        None => Ok(()),
+    }
+}
+
+/// A token bucket enforcing a [`RateLimit`]. Tokens are counted in thousandths of a request, so
+/// that they refill every millisecond.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    /// The tokens left and the time they were counted at.
+    state: Mutex<(u64, u64)>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit, now: u64) -> Self {
+        Self {
+            limit,
+            state: Mutex::new((limit.burst as u64 * 1000, now)),
+        }
+    }
+    /// Takes the token of a request received at `now`. Returns how long until a token is
+    /// available if there is none.
+    pub(crate) fn acquire(&self, now: u64) -> Result<(), Duration> {
+        let per_second = self.limit.per_second.max(1) as u64;
+        let mut state = self.state.locked();
+        let (tokens, since) = &mut *state;
+
+        let elapsed = now.saturating_sub(*since);
+        *tokens = (*tokens + elapsed * per_second).min(self.limit.burst as u64 * 1000);
+        *since = (*since).max(now);
+
+        if *tokens >= 1000 {
+            *tokens -= 1000;
+            return Ok(());
+        }
+        Err(Duration::from_millis((1000 - *tokens).div_ceil(per_second)))
+    }
+}
@@ -0,0 +1,202 @@
+use tower_async::Service;
+
+use super::*;
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Records an attestation in the transparency log of this node, returning its index.
+    pub(crate) async fn attest(&self, entry: LogEntry) -> Option<u64> {
+        let leaf = entry.leaf().ok()?;
+        Some(self.log.write().await.append(leaf))
+    }
+    /// Returns the current head of the transparency log of this node, signed by the node.
+    pub async fn log_head(&self) -> KeyTriad<LogHead> {
+        self.log.read().await.sign(&self.key, utils::now())
+    }
+    /// Returns the latest cosignatures of the head of the log of this node.
+    pub async fn cosignatures(&self) -> Vec<KeyTriad<KeyTriad<LogHead>>> {
+        let mut cosignatures = Vec::new();
+        self.cosignatures
+            .scan_async(|_, cosignature| cosignatures.push(*cosignature))
+            .await;
+
+        cosignatures
+    }
+}
+
+impl<C: Service<CrossSignReq, Response = CrossSignResp> + ?Sized> ServerHandle<C> {
+    /// Asks every connected server to cosign the current head of the log of this node, returning
+    /// the amount of cosignatures received.
+    ///
+    /// Should be called periodically, so that the heads clients receive are recent enough to be
+    /// checked against the witnesses.
+    pub async fn cross_sign_peers(&self) -> usize {
+        let head = self.log_head().await;
+        let size = head.signed.size;
+        let mut received = 0;
+
+        for peer in self.forward_peers().await {
+            let proof = match self.log.read().await.proof(size, size) {
+                Some(value) => value,
+                None => continue,
+            };
+            let resp = match peer.conn.call(CrossSignReq { head, proof }).await {
+                Ok(CrossSignResp::ProofRequired { from }) => {
+                    // the log is append-only, so a proof up to `head` can still be built
+                    let proof = match self.log.read().await.proof(from, size) {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    peer.conn.call(CrossSignReq { head, proof }).await
+                }
+                resp => resp,
+            };
+
+            if let Ok(CrossSignResp::Cosigned { cosignature }) = resp {
+                // the head is our own, so only the cosignature is left to verify
+                if cosignature.signed != head
+                    || self
+                        .verify(
+                            &cosignature.public_key,
+                            &cosignature.signed,
+                            &cosignature.signature,
+                        )
+                        .await
+                        .is_err()
+                {
+                    continue;
+                }
+
+                self.cosignatures
+                    .entry_async(cosignature.public_key)
+                    .await
+                    .and_modify(|value| *value = *cosignature)
+                    .or_insert(*cosignature);
+                received += 1;
+            }
+        }
+
+        received
+    }
+}
+
+impl<C: ?Sized> Service<GetLogProofReq> for InboundEndpoint<C> {
+    type Response = LogProofResp;
+    type Error = LogProofReqError;
+
+    async fn call(&self, req: GetLogProofReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // sign and prove against the same state of the log, up to as many entries as a proof may
+        // cover
+        let log = server_hdl.log.read().await;
+        let max = server_hdl.config.size_limits.max_proof as u64;
+        let to = log.len().min(req.from.saturating_add(max));
+        let proof = log
+            .proof(req.from, to)
+            .ok_or(LogProofReqError::InvalidRange)?;
+        let head = log
+            .sign_at(to, &server_hdl.key, utils::now())
+            .ok_or(LogProofReqError::InvalidRange)?;
+
+        let mut witnesses = server_hdl.cosignatures().await;
+        witnesses.retain(|witness| (req.from..=to).contains(&witness.signed.signed.size));
+
+        Ok(LogProofResp {
+            head,
+            proof,
+            witnesses,
+        })
+    }
+}
+impl<C: ?Sized> Service<GetLogProofReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<GetLogProofReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<GetLogProofReq>>::Error;
+
+    fn call(
+        &self,
+        req: GetLogProofReq,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
+
+impl<C: ?Sized> Service<CrossSignReq> for InboundEndpoint<C> {
+    type Response = CrossSignResp;
+    type Error = CrossSignReqError;
+
+    async fn call(&self, req: CrossSignReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        let server_info = self
+            .info
+            .server_info
+            .as_ref()
+            .ok_or(CrossSignReqError::NotPeer)?;
+        server_hdl
+            .config
+            .size_limits
+            .check_proof(req.proof.leaves.len())?;
+
+        let head = req.head.signed;
+        server_hdl
+            .verify(&req.head.public_key, &head, &req.head.signature)
+            .await
+            .map_err(|_| CrossSignReqError::SignatureInvalid)?;
+        if req.proof.to() != head.size || req.proof.end() != head.head {
+            return Err(CrossSignReqError::ProofInvalid);
+        }
+
+        let public_key = req.head.public_key;
+        let witnessed = server_hdl
+            .witnessed
+            .read_async(&public_key, |_, head| *head)
+            .await;
+
+        if let Some(witnessed) = witnessed {
+            if req.proof.from > witnessed.size {
+                return Ok(CrossSignResp::ProofRequired {
+                    from: witnessed.size,
+                });
+            }
+
+            // the server rewrote or rolled back a head this node already vouched for
+            if req.proof.head_at(witnessed.size) != Some(witnessed.head) {
+                let _ = server_hdl.events.send(NodeEvent::LogEquivocation {
+                    id: self.id,
+                    server_info: server_info.clone(),
+                    public_key,
+                });
+                return Err(CrossSignReqError::Inconsistent);
+            }
+        }
+
+        server_hdl
+            .witnessed
+            .entry_async(public_key)
+            .await
+            .and_modify(|value| *value = head)
+            .or_insert(head);
+
+        Ok(CrossSignResp::Cosigned {
+            cosignature: Box::new(req.head.cosign(&server_hdl.key)),
+        })
+    }
+}
+impl<C: ?Sized> Service<CrossSignReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<CrossSignReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<CrossSignReq>>::Error;
+
+    fn call(&self, req: CrossSignReq) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use tower_async::Service;
+
+use super::*;
+
+/// The amount of eviction notices a mailbox keeps until its owner fetches them. Older notices are
+/// dropped first.
+pub const MAX_EVICTION_NOTICES: usize = 256;
+
+/// Limits on the messages a node holds for a public key. Limits that are [`None`] are not
+/// enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RetentionPolicy {
+    /// The amount of messages held at once.
+    pub max_messages: Option<usize>,
+    /// The total size of the payloads of the messages held at once. A single message larger than
+    /// this is rejected.
+    pub max_bytes: Option<usize>,
+    /// How long a message is held.
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_messages: Some(256),
+            max_bytes: Some(1 << 20),
+            max_age: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+        }
+    }
+}
+
+/// The messages held for a public key, oldest first.
+#[derive(Debug, Default)]
+pub(crate) struct Mailbox {
+    mail: VecDeque<Mail>,
+    bytes: usize,
+    evicted: VecDeque<EvictionNotice>,
+    /// The messages being delivered to an endpoint of the owner, that are not delivered again
+    /// until that delivery ends.
+    delivering: HashSet<MessageId>,
+}
+
+impl Mailbox {
+    /// Evicts messages until this mailbox is within `policy`. Messages past the time their
+    /// sender set are evicted first, then expired messages, then the oldest messages until the
+    /// mailbox is within the count and the size, so that the same messages are always evicted in
+    /// the same order. Returns the ids of the evicted messages.
+    fn enforce(&mut self, policy: &RetentionPolicy, now: u64) -> Vec<MessageId> {
+        let expired = |mail: &Mail| {
+            policy
+                .max_age
+                .is_some_and(|age| now.saturating_sub(mail.time) > age.as_millis() as u64)
+        };
+
+        let mut evicted = Vec::new();
+        // messages with their own expiry can expire out of order
+        let past = |mail: &Mail| mail.expire_time.is_some_and(|time| now > time);
+        if self.mail.iter().any(past) {
+            for mail in std::mem::take(&mut self.mail) {
+                match past(&mail) {
+                    true => evicted.push(self.note_eviction(mail, EvictionReason::Expired, now)),
+                    false => self.mail.push_back(mail),
+                }
+            }
+        }
+        loop {
+            let reason = match self.mail.front() {
+                Some(mail) if expired(mail) => EvictionReason::Expired,
+                Some(_) if policy.max_messages.is_some_and(|max| self.mail.len() > max) => {
+                    EvictionReason::MaxMessages
+                }
+                Some(_) if policy.max_bytes.is_some_and(|max| self.bytes > max) => {
+                    EvictionReason::MaxBytes
+                }
+                _ => return evicted,
+            };
+            evicted.extend(self.evict(0, reason, now));
+        }
+    }
+    fn evict(&mut self, index: usize, reason: EvictionReason, now: u64) -> Option<MessageId> {
+        let mail = self.mail.remove(index)?;
+        Some(self.note_eviction(mail, reason, now))
+    }
+    /// Records that `mail`, that was removed from this mailbox, was evicted.
+    fn note_eviction(&mut self, mail: Mail, reason: EvictionReason, now: u64) -> MessageId {
+        self.bytes -= mail.payload.signed.size();
+        self.delivering.remove(&mail.id);
+
+        if self.evicted.len() == MAX_EVICTION_NOTICES {
+            self.evicted.pop_front();
+        }
+        self.evicted.push_back(EvictionNotice {
+            id: mail.id,
+            from: mail.payload.public_key,
+            reason,
+            time: now,
+        });
+        mail.id
+    }
+    /// Returns the amount of messages and eviction notices held, and their approximate size.
+    pub(crate) fn usage(&self) -> Usage {
+        Usage::of::<Mail>(self.mail.len())
+            + Usage::bytes(self.bytes)
+            + Usage::of::<EvictionNotice>(self.evicted.len())
+            + Usage::of::<MessageId>(self.delivering.len())
+    }
+    /// Removes the messages `ids`, that were delivered to the owner of this mailbox.
+    fn remove(&mut self, ids: &[MessageId]) {
+        self.mail.retain(|mail| {
+            let delivered = ids.contains(&mail.id);
+            if delivered {
+                self.bytes -= mail.payload.signed.size();
+                self.delivering.remove(&mail.id);
+            }
+            !delivered
+        });
+    }
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the retention policy of the mailbox of `key`.
+    pub async fn retention(&self, key: &PublicKey) -> RetentionPolicy {
+        self.retention
+            .read_async(key, |_, policy| *policy)
+            .await
+            .unwrap_or(self.config.mail_retention)
+    }
+    /// Overrides the retention policy of the mailbox of `key`, or restores the policy of the
+    /// configuration if `policy` is [`None`]. Messages outside the new policy are evicted
+    /// immediately.
+    pub async fn set_retention(&self, key: PublicKey, policy: Option<RetentionPolicy>) {
+        match policy {
+            Some(policy) => {
+                self.retention
+                    .entry_async(key)
+                    .await
+                    .and_modify(|value| *value = policy)
+                    .or_insert(policy);
+            }
+            None => {
+                self.retention.remove_async(&key).await;
+            }
+        }
+
+        let policy = self.retention(&key).await;
+        let evicted = match self.mailboxes.get_async(&key).await {
+            Some(mut mailbox) => self.update_mailbox(mailbox.get_mut(), |mailbox| {
+                mailbox.enforce(&policy, utils::now())
+            }),
+            None => return,
+        };
+        self.untrack(&evicted).await;
+    }
+    /// Runs `f` on `mailbox`, accounting for what it holds afterwards.
+    fn update_mailbox<R>(&self, mailbox: &mut Mailbox, f: impl FnOnce(&mut Mailbox) -> R) -> R {
+        let before = mailbox.usage();
+        let result = f(mailbox);
+        self.gauges.update_mail(before, mailbox.usage());
+        result
+    }
+    /// Holds `payload` for `to` until the key fetches it with a [`FetchMailReq`]. Returns the id
+    /// of the message, that the receipts of the message refer to.
+    ///
+    /// A message with the same sender, recipient and signed payload as a message accepted within
+    /// the dedupe window is a retry, and is not stored again.
+    pub async fn deposit(
+        &self,
+        to: PublicKey,
+        payload: KeyTriad<SignedData>,
+    ) -> Result<MessageId, MailboxError> {
+        self.store_mail(to, payload, None).await
+    }
+    /// Like [`ServerHandle::deposit`], but the message is no longer delivered after
+    /// `expire_time`, if set.
+    async fn store_mail(
+        &self,
+        to: PublicKey,
+        payload: KeyTriad<SignedData>,
+        expire_time: Option<u64>,
+    ) -> Result<MessageId, MailboxError> {
+        let size = payload.signed.size();
+        let max = self.config.size_limits.max_payload;
+        if size > max {
+            return Err(MailboxError::TooLarge { size, max });
+        }
+        if self.revoked.contains_async(&to).await {
+            return Err(MailboxError::Revoked);
+        }
+        if !self.may_reach(&payload.public_key, &to).await {
+            return Err(MailboxError::UsageDenied);
+        }
+        if !payload
+            .public_key
+            .valid(&payload.signed, &payload.signature)
+        {
+            return Err(MailboxError::SignatureInvalid);
+        }
+        WrongMessageTypeError::check(SignMessageType::AppMessage, &payload.signed)?;
+        self.check_watermark(self.config.memory_watermarks.mail)
+            .await?;
+
+        let policy = self.retention(&to).await;
+        if let Some(max) = policy.max_bytes.filter(|max| size > *max) {
+            return Err(MailboxError::TooLarge { size, max });
+        }
+
+        // the cap is checked as the mailbox is created, so concurrent deposits cannot exceed it
+        let mut mailbox = match self.mailboxes.entry_async(to).await {
+            scc::hash_map::Entry::Occupied(entry) => entry,
+            scc::hash_map::Entry::Vacant(entry) => {
+                let max = self.config.max_mailboxes;
+                if let Some(max) = max.filter(|max| self.mailboxes.len() >= *max) {
+                    return Err(MailboxError::TooManyMailboxes { max });
+                }
+                entry.insert_entry(Mailbox::default())
+            }
+        };
+
+        let from = payload.public_key;
+        let message = payload.signed.to_hash_msg();
+        let id = MessageId::new(&from, &to, &message);
+        let time = utils::now();
+        if !self.dedupe.insert(id, time) {
+            if mailbox.get().mail.is_empty() && mailbox.get().evicted.is_empty() {
+                self.gauges
+                    .update_mail(mailbox.remove().usage(), Usage::default());
+            }
+            return Ok(id);
+        }
+
+        let mail = Mail {
+            id,
+            time,
+            expire_time,
+            payload,
+        };
+        self.journal(JournalEntry::Deposited {
+            to,
+            mail: mail.clone(),
+        });
+        let evicted = self.update_mailbox(mailbox.get_mut(), |mailbox| {
+            mailbox.bytes += size;
+            mailbox.mail.push_back(mail);
+            mailbox.enforce(&policy, time)
+        });
+        drop(mailbox);
+
+        self.track(id, from, to, message).await;
+        self.untrack(&evicted).await;
+        Ok(id)
+    }
+    /// Pushes the messages held for `key` to `endpoint`, which identified as the key. Messages
+    /// are dropped from the mailbox as they are delivered, and those that could not be are kept
+    /// until the key fetches them or identifies again.
+    pub(crate) async fn deliver_mail(self: &Arc<Self>, key: PublicKey, endpoint: InboundHdl<C>)
+    where
+        C: Notify + Send + Sync + 'static,
+    {
+        let policy = self.retention(&key).await;
+        let (mail, evicted) = match self.mailboxes.get_async(&key).await {
+            Some(mut mailbox) => self.update_mailbox(mailbox.get_mut(), |mailbox| {
+                let evicted = mailbox.enforce(&policy, utils::now());
+                // messages another delivery holds are left to it
+                let mut mail = Vec::new();
+                for held in &mailbox.mail {
+                    if mailbox.delivering.insert(held.id) {
+                        mail.push(held.clone());
+                    }
+                }
+                (mail, evicted)
+            }),
+            None => return,
+        };
+        self.untrack(&evicted).await;
+        if mail.is_empty() {
+            return;
+        }
+
+        let server_hdl = self.clone();
+        self.fanout.submit(endpoint.id, async move {
+            let mut delivered = Vec::new();
+            for mail in &mail {
+                if endpoint.conn.notify_mail(mail).await.is_err() {
+                    break;
+                }
+                delivered.push(mail.id);
+            }
+
+            server_hdl.remove_mail(&key, &delivered).await;
+            // messages that were not delivered can be delivered again
+            if let Some(mut mailbox) = server_hdl.mailboxes.get_async(&key).await {
+                server_hdl.update_mailbox(mailbox.get_mut(), |mailbox| {
+                    for mail in &mail {
+                        mailbox.delivering.remove(&mail.id);
+                    }
+                });
+            }
+            server_hdl.journal(JournalEntry::Delivered {
+                to: key,
+                ids: delivered,
+            });
+        });
+    }
+    /// Drops the messages held for `key`, without notifying anyone.
+    pub(crate) async fn drop_mailbox(&self, key: &PublicKey) {
+        if let Some((_, mailbox)) = self.mailboxes.remove_async(key).await {
+            self.gauges.update_mail(mailbox.usage(), Usage::default());
+            let ids: Vec<_> = mailbox.mail.iter().map(|mail| mail.id).collect();
+            self.untrack(&ids).await;
+        }
+    }
+    /// Stores `mail` for `to` as a primary stored it, unless it is stored already. Retention is
+    /// enforced once the mailbox is delivered or fetched.
+    pub(crate) async fn restore_mail(&self, to: PublicKey, mail: Mail) {
+        let (id, from) = (mail.id, mail.payload.public_key);
+        let message = mail.payload.signed.to_hash_msg();
+        {
+            let mut mailbox = self.mailboxes.entry_async(to).await.or_default();
+            let mailbox = mailbox.get_mut();
+            if mailbox.mail.iter().any(|held| held.id == id) {
+                return;
+            }
+
+            self.dedupe.insert(id, mail.time);
+            self.update_mailbox(mailbox, |mailbox| {
+                mailbox.bytes += mail.payload.signed.size();
+                mailbox.mail.push_back(mail);
+            });
+        }
+        self.track(id, from, to, message).await;
+    }
+    /// Removes the messages `ids` from the mailbox of `to`, that were delivered. The mailbox is
+    /// dropped once it holds neither messages nor eviction notices, so that it no longer counts
+    /// toward [`NodeConfig::max_mailboxes`].
+    pub(crate) async fn remove_mail(&self, to: &PublicKey, ids: &[MessageId]) {
+        self.mailboxes
+            .remove_if_async(to, |mailbox| {
+                self.update_mailbox(mailbox, |mailbox| mailbox.remove(ids));
+                let empty = mailbox.mail.is_empty() && mailbox.evicted.is_empty();
+                if empty {
+                    self.gauges.update_mail(mailbox.usage(), Usage::default());
+                }
+                empty
+            })
+            .await;
+    }
+    /// Returns the messages held, along with the keys they are held for.
+    pub(crate) async fn held_mail(&self) -> Vec<(PublicKey, Mail)> {
+        let mut held = Vec::new();
+        self.mailboxes
+            .scan_async(|key, mailbox| {
+                held.extend(mailbox.mail.iter().map(|mail| (*key, mail.clone())));
+            })
+            .await;
+        held
+    }
+    /// Removes the messages held for `key` and the notices of the messages that were evicted.
+    pub(crate) async fn take_mail(&self, key: &PublicKey) -> FetchMailResp {
+        let policy = self.retention(key).await;
+        let Some((_, mut mailbox)) = self.mailboxes.remove_async(key).await else {
+            return FetchMailResp {
+                mail: Vec::new(),
+                evicted: Vec::new(),
+            };
+        };
+        self.gauges.update_mail(mailbox.usage(), Usage::default());
+        self.journal(JournalEntry::Fetched { to: *key });
+
+        // expired messages are reported rather than delivered
+        let evicted = mailbox.enforce(&policy, utils::now());
+        self.untrack(&evicted).await;
+        FetchMailResp {
+            mail: mailbox.mail.into(),
+            evicted: mailbox.evicted.into(),
+        }
+    }
+}
+
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<RelayMessage> for InboundEndpoint<C> {
+    type Response = RelayResp;
+    type Error = RelayReqError;
+
+    async fn call(&self, req: RelayMessage) -> Result<Self::Response, Self::Error> {
+        let server_hdl = self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // only the sender can relay its message
+        if !self
+            .identities
+            .contains_async(&req.payload.public_key)
+            .await
+        {
+            return Err(RelayReqError::InvalidPublicKey);
+        }
+        server_hdl
+            .config
+            .size_limits
+            .check_payload(&req.payload.signed)?;
+
+        let expire_time = req.ttl.map(|ttl| utils::now().saturating_add(ttl));
+        let id = server_hdl
+            .store_mail(req.to, req.payload, expire_time)
+            .await?;
+
+        // deliver it right away if the key is identified already
+        let endpoint = server_hdl
+            .key_to_endpoint
+            .get_async(&req.to)
+            .await
+            .map(|entry| entry.clone());
+        if let Some(endpoint) = endpoint {
+            server_hdl.deliver_mail(req.to, endpoint).await;
+        }
+
+        Ok(RelayResp { id })
+    }
+}
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<RelayMessage> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<RelayMessage>>::Response;
+    type Error = <InboundEndpoint<C> as Service<RelayMessage>>::Error;
+
+    fn call(&self, req: RelayMessage) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
+
+impl<C: ?Sized> Service<FetchMailReq> for InboundEndpoint<C> {
+    type Response = FetchMailResp;
+    type Error = FetchMailReqError;
+
+    async fn call(&self, req: FetchMailReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // only the owner of a mailbox can fetch it
+        if !self.identities.contains_async(&req.key).await {
+            return Err(FetchMailReqError::InvalidPublicKey);
+        }
+
+        Ok(server_hdl.take_mail(&req.key).await)
+    }
+}
+impl<C: ?Sized> Service<FetchMailReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<FetchMailReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<FetchMailReq>>::Error;
+
+    fn call(&self, req: FetchMailReq) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
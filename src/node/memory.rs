@@ -0,0 +1,183 @@
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::*;
+
+/// The approximate memory a part of a node holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Usage {
+    /// The amount of entries held.
+    pub entries: usize,
+    /// The approximate size of the entries, in bytes.
+    pub bytes: usize,
+}
+
+impl Usage {
+    /// Returns the usage of `entries` entries of `T`.
+    pub(crate) fn of<T>(entries: usize) -> Self {
+        Self {
+            entries,
+            bytes: entries * size_of::<T>(),
+        }
+    }
+    /// Returns `bytes` bytes held by entries counted elsewhere.
+    pub(crate) fn bytes(bytes: usize) -> Self {
+        Self { entries: 0, bytes }
+    }
+}
+
+impl std::ops::Add for Usage {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            entries: self.entries + rhs.entries,
+            bytes: self.bytes + rhs.bytes,
+        }
+    }
+}
+
+/// The approximate memory each subsystem of a node holds, as reported by
+/// [`ServerHandle::memory_usage`]. Sizes are estimated from the amount of entries and their
+/// sizes, along with the payloads and the messages they hold, without the overhead of the maps
+/// that hold them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MemoryUsage {
+    /// The identified public keys, along with when they were last seen, their usage, their
+    /// presence, and the identities the endpoints they identified on hold.
+    pub identities: Usage,
+    /// The endpoints waiting to be notified when public keys connect.
+    pub subscriptions: Usage,
+    /// The public keys connected servers reported, and the introductions handed out that were
+    /// not used yet.
+    pub routes: Usage,
+    /// The messages held for public keys until they fetch them, the receipts tracked for them,
+    /// and the ids of the messages accepted recently.
+    pub mailboxes: Usage,
+    /// The leaves of the transparency log, the recent changes to the set of identified public
+    /// keys, and the recent writes standbys replicate.
+    pub journals: Usage,
+}
+
+impl MemoryUsage {
+    /// Returns the approximate memory held by every subsystem together.
+    pub fn total(&self) -> Usage {
+        self.identities + self.subscriptions + self.routes + self.mailboxes + self.journals
+    }
+}
+
+/// An amount kept up to date as what it counts changes, so that it can be read without visiting
+/// every entry.
+#[derive(Debug, Default)]
+pub(crate) struct Gauge(AtomicUsize);
+
+impl Gauge {
+    pub(crate) fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+    /// Accounts for something counted that went from `before` to `after`.
+    pub(crate) fn update(&self, before: usize, after: usize) {
+        match after >= before {
+            true => self.0.fetch_add(after - before, Ordering::Relaxed),
+            false => self.0.fetch_sub(before - after, Ordering::Relaxed),
+        };
+    }
+}
+
+/// The gauges of the parts of a node whose entries vary in size.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryGauges {
+    /// The amount of endpoints subscribed to public keys, counted once per key.
+    pub(crate) subscribers: Gauge,
+    /// The amount of connected servers public keys were reported on, counted once per key.
+    pub(crate) routes: Gauge,
+    /// The amount of messages, eviction notices and deliveries held by mailboxes.
+    pub(crate) mail: Gauge,
+    /// The approximate size of what mailboxes hold, in bytes.
+    pub(crate) mail_bytes: Gauge,
+    /// The size of the status messages of presences, in bytes.
+    pub(crate) presence_bytes: Gauge,
+}
+
+impl MemoryGauges {
+    /// Accounts for a mailbox that held `before` and now holds `after`.
+    pub(crate) fn update_mail(&self, before: Usage, after: Usage) {
+        self.mail.update(before.entries, after.entries);
+        self.mail_bytes.update(before.bytes, after.bytes);
+    }
+}
+
+/// Returns the size of the status message of `presence`, that is held apart from it.
+pub(crate) fn presence_bytes(presence: &KeyTriad<PresenceUpdate>) -> usize {
+    presence.signed.message.as_ref().map_or(0, String::len)
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Estimates the memory held by the identity maps, the subscriptions, the routes, the
+    /// mailboxes and the journals of this node, so that operators can see what grows on a large
+    /// node. The reaper also reports it as a [`NodeEvent::MemoryUsage`] every
+    /// [`NodeConfig::reap_interval`].
+    ///
+    /// The amounts are kept up to date as they change, so this does not visit every mailbox and
+    /// subscription.
+    pub async fn memory_usage(&self) -> MemoryUsage {
+        let keys = self.key_to_endpoint.len();
+        let identities = Usage::of::<(PublicKey, InboundHdl<C>)>(keys)
+            // the identity the endpoint holds for each key it identified as
+            + Usage::bytes(keys * size_of::<(PublicKey, KeyTriad<CachedSigned<IdentifyData>>)>())
+
+            + Usage::of::<(PublicKey, u64)>(self.last_seen.len())
+            + Usage::of::<(PublicKey, KeyUsage)>(self.usage.len())
+            + Usage::of::<(PublicKey, KeyTriad<PresenceUpdate>)>(self.presence.len())
+            + Usage::bytes(self.gauges.presence_bytes.get());
+
+        let subscriptions =
+            Usage::of::<(PublicKey, HashSet<InboundHdl<C>>)>(self.notifications.len())
+                + Usage::of::<InboundHdl<C>>(self.gauges.subscribers.get())
+                + Usage::of::<((PublicKey, u64), u64)>(self.subscription_expiry.len());
+
+        let routes = Usage::of::<(PublicKey, Vec<RemoteKey<C>>)>(self.remote_keys.len())
+            + Usage::of::<RemoteKey<C>>(self.gauges.routes.get())
+            + Usage::of::<(DialToken, DialEntry<C>)>(self.dial_tokens.len());
+
+        let mailboxes = Usage::of::<(PublicKey, Mailbox)>(self.mailboxes.len())
+            + Usage {
+                entries: self.gauges.mail.get(),
+                bytes: self.gauges.mail_bytes.get(),
+            }
+            + Usage::of::<(MessageId, Tracked)>(self.receipts.len())
+            // each id is held in the order of the window and in its set
+            + Usage::of::<(u64, MessageId, MessageId)>(self.dedupe.len());
+
+        // every leaf of the log is held along with the head after it
+        let leaves = self.log.read().await.len() as usize;
+        let changes = self.gossip.locked().len();
+        let journals = Usage::of::<(HashMsg, HashMsg)>(leaves)
+            + Usage::of::<KeyChange>(changes)
+            + self.journal.locked().usage();
+
+        MemoryUsage {
+            identities,
+            subscriptions,
+            routes,
+            mailboxes,
+            journals,
+        }
+    }
+    /// Checks that the memory this node holds is below `watermark`, if set, so that work that
+    /// would hold more is shed while it is not.
+    pub(crate) async fn check_watermark(
+        &self,
+        watermark: Option<usize>,
+    ) -> Result<(), OverloadedError> {
+        let Some(watermark) = watermark else {
+            return Ok(());
+        };
+
+        let usage = self.memory_usage().await.total().bytes;
+        if usage >= watermark {
+            return Err(OverloadedError { usage, watermark });
+        }
+        Ok(())
+    }
+}
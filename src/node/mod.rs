@@ -4,19 +4,94 @@ use std::{
     collections::HashSet,
     convert::Infallible,
     error::Error as StdError,
-    sync::{Arc, Weak},
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
+        Arc, Weak,
+    },
 };
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, watch, RwLock};
 use tower_async::Service;
 
+mod announce;
+mod ban;
+mod config;
+mod dedupe;
+mod diagnose;
+mod dial;
+mod disconnect;
+mod driver;
 pub mod error;
+mod event;
+mod fair;
+mod gossip;
+mod health;
+mod identify;
+mod keys;
+mod limit;
+mod log;
+mod mailbox;
+mod memory;
+mod park;
+mod pending;
+mod pool;
+mod presence;
+mod reap;
+mod receipt;
+mod reconcile;
+mod remote;
+mod replica;
+mod resume;
+mod revoke;
+mod router;
 #[cfg(test)]
 mod tests;
-
+mod usage;
+mod wire;
+
+use crate::crypto::cache::VerifyCache;
+use crate::crypto::error::VerifyError;
+use crate::crypto::keyset::KeySet;
+use crate::crypto::log::{LogHead, TransparencyLog};
+use crate::crypto::multi::MultiKeyTriad;
+use crate::crypto::pool::{spawn_verify, spawn_verify_batch, CryptoPool};
+use crate::crypto::recover::RecoverableTriad;
+use crate::crypto::token::{Token, TokenScope};
 use crate::crypto::*;
 use crate::obj::*;
-use crate::utils;
+use crate::utils::{self, LockExt, RandomState};
+pub use ban::{Ban, BanList, BanTarget, IpRange};
+pub use config::*;
+use dedupe::DedupeWindow;
+pub use diagnose::*;
+use dial::DialEntry;
+pub use dial::DIAL_TOKEN_LIFETIME;
+pub use driver::*;
 use error::*;
+pub use event::*;
+use fair::FairScheduler;
+use gossip::GossipLog;
+pub use gossip::{GossipStore, MemoryGossipStore};
+pub use health::*;
+pub use limit::{RateLimit, RequestLimits};
+use limit::{RateLimiter, RequestLimiters};
+use mailbox::Mailbox;
+pub use mailbox::{RetentionPolicy, MAX_EVICTION_NOTICES};
+pub use memory::*;
+use park::Parked;
+pub use pending::PendingWork;
+use pool::PeerStreams;
+pub use pool::*;
+pub use reap::Reaped;
+use receipt::Tracked;
+pub use reconcile::MAX_RECONCILE_ROUNDS;
+use remote::RemoteKey;
+pub use remote::{Established, RelayPath};
+use replica::Journal;
+pub use replica::ReplicaRole;
+pub use router::*;
+pub use wire::*;
 
 pub trait OpenStream: Service<PublicKey, Error = <Self as OpenStream>::Err> {
     type Err: StreamOpenError;
@@ -27,6 +102,16 @@ pub trait OpenStream: Service<PublicKey, Error = <Self as OpenStream>::Err> {
     ) -> impl Future<Output = Result<Self::Response, Self::Err>> {
         self.call(key)
     }
+    /// Opens a stream for `req.to` over the connection to a server the node relays `req`
+    /// through. `req.hops` already counts the node, so that a transport that carries the request
+    /// lets the next server bound how far it travels. Calls [`OpenStream::open_stream`] by
+    /// default.
+    fn relay_stream(
+        &self,
+        req: &CommunicationReq,
+    ) -> impl Future<Output = Result<Self::Response, Self::Err>> {
+        self.open_stream(req.to)
+    }
 }
 
 pub trait Notify {
@@ -37,28 +122,278 @@ pub trait Notify {
         &self,
         triad: &KeyTriad<SignedData>,
     ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync;
+
+    /// Notify this client that another public key asked to be introduced to it.
+    fn notify_introduced(
+        &self,
+        intro: &Introduction,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync;
+
+    /// Notify this client that a public key it is waiting for was revoked.
+    fn notify_revoked(
+        &self,
+        revocation: &KeyTriad<SignedData>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync;
+
+    /// Notify this client that the recipient of a message it sent signed a receipt of it. Does
+    /// nothing by default.
+    fn notify_receipt(
+        &self,
+        _receipt: &KeyTriad<Receipt>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        async { Ok(()) }
+    }
+
+    /// Deliver a message that was relayed to this client while it was offline. The message is
+    /// dropped from the mailbox once this succeeds.
+    fn notify_mail(&self, mail: &Mail)
+        -> impl Future<Output = Result<(), Self::Err>> + Send + Sync;
+
+    /// Notify this client of a payload broadcast to every endpoint of the node. Does nothing by
+    /// default.
+    fn notify_broadcast(
+        &self,
+        _payload: &KeyTriad<SignedData>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
+        async { Ok(()) }
+    }
 }
 
+/// The amount of events buffered for each subscriber of [`ServerHandle::subscribe`].
+pub const EVENT_CAPACITY: usize = 256;
+
 #[derive(Debug)]
 pub struct ServerHandle<C: ?Sized> {
     /// A map from a public key to a handle.
     key_to_endpoint: scc::HashMap<PublicKey, InboundHdl<C>>,
     /// Nodes connected to this endpoint that are also servers.
-    connected_servers: RwLock<HashSet<InboundHdl<C>>>,
+    connected_servers: RwLock<HashSet<InboundHdl<C>, RandomState>>,
     /// Client handles that requested that they be notified when a public key connects to the node.
-    notifications: scc::HashMap<PublicKey, HashSet<InboundHdl<C>>>,
+    notifications: scc::HashMap<PublicKey, HashSet<InboundHdl<C>, RandomState>>,
+    /// Introductions that were issued and have not been used yet.
+    dial_tokens: scc::HashMap<DialToken, DialEntry<C>>,
+    /// A map from a resumption token to the identified endpoint it was issued to.
+    resumptions: scc::HashMap<ResumptionToken, InboundHdl<C>>,
+    /// The set of identified public keys, built when it is first requested after a change.
+    key_set: RwLock<Option<Arc<KeySet>>>,
+    /// The set of identified public keys and of those connected servers reported, built when it
+    /// is first requested after a change, along with when it expires.
+    known_keys: RwLock<Option<(Arc<KeySet>, u64)>>,
+    /// Public keys that were revoked, and can never identify again.
+    revoked: scc::HashSet<PublicKey>,
+    /// The usage public keys declared the last time they identified, if they declared any.
+    usage: scc::HashMap<PublicKey, KeyUsage>,
+    /// The latest presence identified public keys published.
+    presence: scc::HashMap<PublicKey, KeyTriad<PresenceUpdate>>,
+    /// A map from a public key to the last time it identified.
+    last_seen: scc::HashMap<PublicKey, u64>,
+    /// Public keys connected servers reported as connected to them.
+    remote_keys: scc::HashMap<PublicKey, Vec<RemoteKey<C>>>,
+    /// Requests waiting for a public key to identify again.
+    parked: scc::HashMap<PublicKey, Vec<Parked<C>>>,
+    /// What cancels the requests forwarded to other servers on behalf of an endpoint, keyed by
+    /// the id of the endpoint.
+    forwards: scc::HashMap<u64, Arc<tokio::sync::Notify>>,
+    /// Messages held for public keys until they fetch them.
+    mailboxes: scc::HashMap<PublicKey, Mailbox>,
+    /// Retention policies that override the policy of the configuration for some mailboxes.
+    retention: scc::HashMap<PublicKey, RetentionPolicy>,
+    /// The ids of the messages accepted recently.
+    dedupe: DedupeWindow,
+    /// The senders and the latest receipts of stored messages, keyed by the ids of the messages.
+    receipts: scc::HashMap<MessageId, Tracked>,
+    /// The health of connected servers, keyed by their endpoint id.
+    peer_health: scc::HashMap<u64, PeerHealth>,
+    /// The amount of endpoints connected to this node.
+    connections: AtomicU32,
+    /// How many of the streams relayed to other servers are in use, in percent, as last
+    /// reported with [`ServerHandle::set_relay_saturation`].
+    relay_saturation: AtomicU8,
+    events: broadcast::Sender<NodeEvent>,
+    config: NodeConfig,
+    /// The key this node signs its attestations with.
+    key: PrivateKey,
+    /// Pushes notifications to endpoints.
+    fanout: FairScheduler,
+    /// The signatures that were verified recently, if configured.
+    verify_cache: Option<VerifyCache>,
+    /// The workers that verify signatures, if configured.
+    crypto_pool: Option<CryptoPool>,
+    /// The key of the resumption and dial tokens this node issues.
+    token_key: [u8; MAC_KEY_SIZE],
+    /// The attestations this node issued.
+    log: RwLock<TransparencyLog>,
+    /// The latest heads of the logs of federated servers this node cosigned, keyed by the public
+    /// keys of the servers.
+    witnessed: scc::HashMap<PublicKey, LogHead>,
+    /// The latest cosignatures of the head of the log of this node, keyed by the public keys of
+    /// the witnesses.
+    cosignatures: scc::HashMap<PublicKey, KeyTriad<KeyTriad<LogHead>>, RandomState>,
+    /// The recent changes to the set of identified public keys, that federated servers catch up
+    /// on with a [`KeyChangesReq`].
+    gossip: std::sync::Mutex<GossipLog>,
+    /// The recent writes to the state of this node, that standbys replicate.
+    journal: std::sync::Mutex<Journal>,
+    /// Whether this node serves endpoints, or follows a primary.
+    role: watch::Sender<ReplicaRole>,
+    /// The subscriptions replicated from a primary, keyed by the public key that made them, until
+    /// an endpoint identifies as the key.
+    replicated_subscriptions: scc::HashMap<PublicKey, HashSet<PublicKey>>,
+    /// The identify data handed out to each endpoint with a [`PreIdentifyReq`], keyed by the ids
+    /// of the endpoints, until it expires.
+    challenges: scc::HashMap<u64, IdentifyData>,
+    /// When the subscriptions of endpoints to public keys expire, keyed by the public keys and
+    /// the ids of the endpoints, if [`NodeConfig::subscription_ttl`] is set.
+    subscription_expiry: scc::HashMap<(PublicKey, u64), u64>,
+    /// The task that clears expired state, once started.
+    reaper: std::sync::Mutex<Option<tokio::task::AbortHandle>>,
+    /// The public keys and IP addresses this node refuses to serve.
+    bans: BanList,
+    /// The amounts the memory of this node is estimated from.
+    gauges: MemoryGauges,
+}
+
+impl<C: ?Sized> Default for ServerHandle<C> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<C: ?Sized> ServerHandle<C> {
     pub fn new() -> Self {
+        Self::build(
+            Default::default(),
+            PrivateKey::generate(&mut utils::rng()),
+            None,
+        )
+    }
+    pub fn new_hdl() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+    /// Creates a node with `config`. Fails if the workers of its crypto pool cannot be spawned.
+    pub fn with_config(config: NodeConfig) -> std::io::Result<Self> {
+        Self::with_key(config, PrivateKey::generate(&mut utils::rng()))
+    }
+    /// Creates a node that signs its attestations with `key`. Fails if the workers of its crypto
+    /// pool cannot be spawned.
+    pub fn with_key(config: NodeConfig, key: PrivateKey) -> std::io::Result<Self> {
+        let crypto_pool = config.crypto_threads.map(CryptoPool::new).transpose()?;
+        Ok(Self::build(config, key, crypto_pool))
+    }
+    fn build(config: NodeConfig, key: PrivateKey, crypto_pool: Option<CryptoPool>) -> Self {
+        let events = broadcast::channel(EVENT_CAPACITY).0;
+        let role = watch::channel(match config.replication.standby {
+            true => ReplicaRole::Standby,
+            false => ReplicaRole::Primary,
+        })
+        .0;
+
         Self {
             connected_servers: Default::default(),
             key_to_endpoint: Default::default(),
             notifications: Default::default(),
+            dial_tokens: Default::default(),
+            resumptions: Default::default(),
+            key_set: Default::default(),
+            known_keys: Default::default(),
+            revoked: Default::default(),
+            usage: Default::default(),
+            presence: Default::default(),
+            last_seen: Default::default(),
+            parked: Default::default(),
+            forwards: Default::default(),
+            remote_keys: Default::default(),
+            mailboxes: Default::default(),
+            retention: Default::default(),
+            dedupe: DedupeWindow::new(config.dedupe_window),
+            receipts: Default::default(),
+            peer_health: Default::default(),
+            connections: Default::default(),
+            relay_saturation: Default::default(),
+            crypto_pool,
+            fanout: FairScheduler::new(config.fanout_workers, events.clone()),
+            events,
+            verify_cache: NonZeroUsize::new(config.verify_cache_size).map(VerifyCache::new),
+            config,
+            key,
+            token_key: utils::random_bytes(),
+            log: Default::default(),
+            witnessed: Default::default(),
+            cosignatures: Default::default(),
+            gossip: Default::default(),
+            journal: Default::default(),
+            role,
+            replicated_subscriptions: Default::default(),
+            challenges: Default::default(),
+            subscription_expiry: Default::default(),
+            reaper: Default::default(),
+            bans: Default::default(),
+            gauges: Default::default(),
         }
     }
-    pub fn new_hdl() -> Arc<Self> {
-        Arc::new(Self::new())
+    /// Returns the public key this node signs its attestations with.
+    pub fn public_key(&self) -> PublicKey {
+        self.key.derive_public()
+    }
+    /// Returns the configuration of this node.
+    pub fn config(&self) -> &NodeConfig {
+        &self.config
+    }
+    /// Verifies `signature` of `msg` by `public_key` on the crypto pool of this node, skipping
+    /// the signatures verified recently.
+    pub(crate) async fn verify(
+        &self,
+        public_key: &PublicKey,
+        msg: impl ToHashMsg,
+        signature: &Signature,
+    ) -> Result<(), VerifyError> {
+        let item = (*public_key, SignedHash::of(msg), *signature);
+        if let Some(cache) = &self.verify_cache {
+            if cache.contains(&item) {
+                return Ok(());
+            }
+        }
+
+        spawn_verify(self.crypto_pool.as_ref(), item.0, item.1, item.2).await?;
+        if let Some(cache) = &self.verify_cache {
+            cache.insert(item);
+        }
+        Ok(())
+    }
+    /// Subscribes to the events of this node. Events are dropped for receivers that fall more than
+    /// [`EVENT_CAPACITY`] events behind.
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.events.subscribe()
+    }
+    /// Cancels the background work of this node, such as pending notifications. Work submitted
+    /// afterwards is dropped.
+    pub fn shutdown(&self) {
+        self.fanout.shutdown();
+        if let Some(reaper) = self.reaper.locked().take() {
+            reaper.abort();
+        }
+    }
+    /// Stops notifying `hdl` when any of `keys` connects. Returns the keys `hdl` was subscribed
+    /// to.
+    pub async fn unsubscribe(&self, hdl: &InboundHdl<C>, keys: &[PublicKey]) -> Vec<PublicKey> {
+        let mut removed = Vec::new();
+        for key in keys {
+            let mut entry = match self.notifications.get_async(key).await {
+                Some(value) => value,
+                None => continue,
+            };
+            if entry.get_mut().remove(hdl) {
+                removed.push(*key);
+                self.gauges.subscribers.update(1, 0);
+            }
+            // nobody is waiting for the key anymore
+            if entry.get().is_empty() {
+                let _ = entry.remove();
+            }
+        }
+        self.journal_subscriptions(hdl, &removed, false).await;
+
+        removed
     }
     pub async fn connect_server(&self, server_hdl: InboundHdl<C>) -> Result<(), InboundHdl<C>> {
         if server_hdl.info.server_info.is_none() {
@@ -84,10 +419,24 @@ pub type InboundHdl<C> = Arc<InboundEndpoint<C>>;
 pub struct InboundEndpoint<C: ?Sized> {
     id: u64,
     server_hdl: Option<Weak<ServerHandle<C>>>,
+    /// The identify data handed out to this endpoint, if it is not connected to a node. The node
+    /// holds the identify data of its endpoints, so that it can clear it once it expires.
     identify_data: RwLock<Option<IdentifyData>>,
     public_keys: RwLock<Vec<PublicKey>>,
     identities: scc::HashMap<PublicKey, KeyTriad<CachedSigned<IdentifyData>>>,
+    resumption_token: ResumptionToken,
     info: EndpointInfo,
+    /// Limits the rate of the requests received on the wire, if the node is configured to.
+    limiter: Option<RateLimiter>,
+    /// Limits the rate of each type of request, if the node is configured to.
+    request_limiters: RequestLimiters,
+    /// The features negotiated with the endpoint. Its identify triads must be signed with the
+    /// signature scheme of these features.
+    features: RwLock<Features>,
+    /// Whether the endpoint was forgotten once its connection dropped.
+    disconnected: AtomicBool,
+    /// The streams pre-established over the connection, if the endpoint is a connected server.
+    streams: PeerStreams,
     conn: C,
 }
 
@@ -97,6 +446,14 @@ impl<C: ?Sized> PartialEq for InboundEndpoint<C> {
     }
 }
 impl<C: ?Sized> Eq for InboundEndpoint<C> {}
+impl<C: ?Sized> Drop for InboundEndpoint<C> {
+    fn drop(&mut self) {
+        if let Some(server_hdl) = self.server_hdl.as_ref().and_then(Weak::upgrade) {
+            server_hdl.connections.fetch_sub(1, Ordering::Relaxed);
+            server_hdl.challenges.remove(&self.id);
+        }
+    }
+}
 impl<C: ?Sized> std::hash::Hash for InboundEndpoint<C> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.id.hash(state);
@@ -142,12 +499,30 @@ impl<C> InboundEndpoint<C> {
             identify_data: Default::default(),
             public_keys: Default::default(),
             identities: Default::default(),
+            limiter: None,
+            request_limiters: Default::default(),
+            features: Default::default(),
+            disconnected: AtomicBool::new(false),
+            streams: Default::default(),
+            // clients never accept resumptions, so the key is thrown away
+            resumption_token: ResumptionToken(Token::issue(
+                &utils::random_bytes(),
+                TokenScope::Resumption,
+                u64::MAX,
+            )),
         }
     }
     pub fn client_hdl(id: u64, info: EndpointInfo, conn: C) -> Arc<Self> {
         Arc::new(Self::client(id, info, conn))
     }
     pub fn server(id: u64, info: EndpointInfo, server_hdl: Arc<ServerHandle<C>>, conn: C) -> Self {
+        let resumption_token = ResumptionToken(Token::issue(
+            &server_hdl.token_key,
+            TokenScope::Resumption,
+            utils::now().saturating_add(server_hdl.config.resumption_ttl.as_millis() as u64),
+        ));
+        server_hdl.connections.fetch_add(1, Ordering::Relaxed);
+
         Self {
             id,
             info,
@@ -155,6 +530,15 @@ impl<C> InboundEndpoint<C> {
             identify_data: Default::default(),
             public_keys: Default::default(),
             identities: Default::default(),
+            resumption_token,
+            limiter: server_hdl
+                .config
+                .rate_limit
+                .map(|limit| RateLimiter::new(limit, utils::now())),
+            request_limiters: RequestLimiters::new(&server_hdl.config.request_limits, utils::now()),
+            features: Default::default(),
+            disconnected: AtomicBool::new(false),
+            streams: Default::default(),
             conn,
         }
     }
@@ -177,32 +561,71 @@ impl<C> InboundEndpoint<C> {
     }
 
     // service related functions:
-    pub async fn pre_identify(&self, req: PreIdentifyReq) -> IdentifyData {
-        self.call(req).await.unwrap()
+    pub async fn ping(&self, req: PingReq) -> PongResp {
+        let Ok(resp) = self.call(req).await;
+        resp
+    }
+    pub async fn goodbye(&self, req: Goodbye) {
+        let Ok(()) = self.call(req).await;
     }
+    service_fn!(pre_identify, PreIdentifyReq);
     service_fn!(list_connected, ListConnectedServersReq);
     service_fn!(communicate, CommunicationReq);
+    service_fn!(dial, DialReq);
+    service_fn!(log_proof, GetLogProofReq);
+    service_fn!(keys_root, KeysRootReq);
+    service_fn!(cross_sign, CrossSignReq);
+    service_fn!(fetch_mail, FetchMailReq);
+    service_fn!(relay, RelayMessage);
+    service_fn!(broadcast, BroadcastReq);
+    service_fn!(receipt, ReceiptReq);
+    service_fn!(publish_presence, PublishPresenceReq);
+    service_fn_hdl!(introduce, IntroductionReq);
+    service_fn_hdl!(resume, ResumeReq);
+    service_fn_hdl!(revoke, RevokeReq);
+    service_fn_hdl!(disconnect_notice, DisconnectNoticeReq);
     service_fn_hdl!(identify, KeyTriad<SignedData>);
+    service_fn_hdl!(identify_recoverable, RecoverableTriad<SignedData>);
+    service_fn_hdl!(identify_batch, IdentifyReq);
+    service_fn_hdl!(identify_multi, MultiKeyTriad<SignedData>);
+    service_fn_hdl!(identify_delegated, DelegatedTriad);
     service_fn_hdl!(keys_exists, KeysExistsReq);
+    service_fn_hdl!(unsubscribe_keys, UnsubscribeKeysReq);
+    service_fn_hdl!(reconcile, ReconcileReq);
+    service_fn_hdl!(send_receipt, SendReceiptReq);
 }
 
-impl<C: Service<KeysExistsRReq, Response = KeysExistsRResp> + ?Sized> Service<KeysExistsRReq> for InboundEndpoint<C> {
+impl<C: Service<KeysExistsRReq, Response = KeysExistsRResp> + ?Sized> Service<KeysExistsRReq>
+    for InboundEndpoint<C>
+{
     type Response = KeysExistsRResp;
-    type Error = ServerReqError;
+    type Error = KeysExistsRReqError;
 
     async fn call(&self, mut req: KeysExistsRReq) -> Result<Self::Response, Self::Error> {
-        let ref server_hdl = *self
+        let server_hdl = &*self
             .server_hdl
             .as_ref()
             .ok_or(NotServerError)?
             .upgrade()
             .ok_or(ServerHdlDroppedError)?;
 
+        server_hdl.config.size_limits.check_keys(req.keys.len())?;
+        let max = server_hdl.config.max_forward_depth;
+        if req.depth > max {
+            return Err(KeysExistsRReqError::TtlExceeded {
+                depth: req.depth,
+                max,
+            });
+        }
+
         let mut keys: Vec<_> = req.keys.iter().cloned().collect();
         let mut triads = Vec::new();
         let mut offset = 0;
 
         for (index, key) in req.keys.iter().enumerate() {
+            if server_hdl.is_unlisted(key).await {
+                continue;
+            }
             let hdl = match server_hdl.key_to_endpoint.get_async(key).await {
                 Some(value) => value.clone(),
                 None => continue,
@@ -213,32 +636,59 @@ impl<C: Service<KeysExistsRReq, Response = KeysExistsRResp> + ?Sized> Service<Ke
                 None => continue,
             };
 
-            let triad = KeyConnectedTo { triad: triad.map(|value| value.value), connected_to: vec![] };
+            let triad = KeyConnectedTo {
+                triad: triad.map(|value| value.value),
+                connected_to: vec![],
+            };
             triads.push(triad);
             keys.remove(index - offset);
             offset += 1;
         }
 
         // If all the public keys were connected to this server OR the request depth is 0, return
-        if req.depth == 0 || req.keys.len() == 0 {
-            return Ok(KeysExistsRResp { triads })
+        if req.depth == 0 || keys.is_empty() {
+            return Ok(KeysExistsRResp { triads });
         }
 
         req.keys = keys.into();
         req.depth -= 1;
 
-        // ask other nodes for the triads corresponding to the remaining public keys
-        for node in server_hdl.connected_servers.read().await.iter() {
-            let req = KeysExistsRReq {
+        // ask other nodes for the triads corresponding to the remaining public keys, except the
+        // one that asked, which would only ask this node again
+        for node in server_hdl.forward_peers().await {
+            if node.id == self.id {
+                continue;
+            }
+            let forwarded = KeysExistsRReq {
                 keys: req.keys.clone(),
-                depth: req.depth
+                depth: req.depth,
             };
-            let resp = match node.conn.call(req).await {
-                Ok(resp) => resp,
-                Err(_) => continue,
+            let start = utils::now();
+            let resp = match server_hdl.forward(self.id, node.conn.call(forwarded)).await {
+                Some(Ok(resp)) => resp,
+                // the endpoint that asked is gone
+                None => break,
+                Some(Err(_)) => {
+                    server_hdl.record_peer(&node, None).await;
+                    continue;
+                }
             };
+            server_hdl
+                .record_peer(&node, Some(utils::now().saturating_sub(start)))
+                .await;
 
-            triads.extend(resp.triads.into_iter().map(|mut value| { value.connected_to.push(node.info.server_info.as_ref().unwrap().clone()); value }))
+            let Some(server_info) = &node.info.server_info else {
+                continue;
+            };
+            for mut value in resp.triads {
+                // the peer has to prove the key identified, rather than claim it did
+                if !server_hdl.verify_remote(&value.triad, &req.keys).await {
+                    continue;
+                }
+                server_hdl.learn_remote(value.triad.public_key, &node).await;
+                value.connected_to.push(server_info.clone());
+                triads.push(value);
+            }
         }
 
         Ok(KeysExistsRResp { triads })
@@ -249,29 +699,60 @@ impl<C: ?Sized> Service<ListConnectedServersReq> for InboundEndpoint<C> {
     type Error = ServerReqError;
 
     async fn call(&self, req: ListConnectedServersReq) -> Result<Self::Response, Self::Error> {
-        let ref server_hdl = *self
+        let server_hdl = &*self
             .server_hdl
             .as_ref()
             .ok_or(NotServerError)?
             .upgrade()
             .ok_or(ServerHdlDroppedError)?;
 
+        let disclosure = if self.info.server_info.is_some() {
+            server_hdl.config.list_disclosure.peers
+        } else if !self.identities.is_empty() {
+            server_hdl.config.list_disclosure.identified
+        } else {
+            server_hdl.config.list_disclosure.anonymous
+        };
+
         let connected_servers = server_hdl.connected_servers.read().await;
-        let mut servers = Vec::with_capacity(
-            req.max
-                .map(|value| value as usize)
-                .unwrap_or(connected_servers.len()),
-        );
-
-        for (index, server) in connected_servers.iter().enumerate() {
-            if Some(index as u32 + 1) == req.max {
+        let max = req
+            .max
+            .map_or(usize::MAX, |value| value as usize)
+            .min(server_hdl.config.size_limits.max_entries);
+        let mut servers = Vec::with_capacity(max.min(connected_servers.len()));
+
+        for server in connected_servers.iter() {
+            if servers.len() >= max {
                 break;
             }
 
             let info = &server.info;
+            let load = server_hdl
+                .peer_health(server.id)
+                .await
+                .and_then(|health| health.load);
+            let Some(mut server_info) = info.server_info.clone() else {
+                continue;
+            };
+            let verified = match &server_info.public_key {
+                Some(key) => server.identities.contains_async(key).await,
+                None => false,
+            };
+            let port = match disclosure.ports {
+                true => server_info.ports.first().copied(),
+                false => None,
+            };
+            if !disclosure.ports {
+                server_info.ports.clear();
+            }
+
             servers.push(ConnectedServer {
-                ip: info.endpoint.ip(),
-                domain: info.server_info.as_ref().unwrap().domain.clone(),
+                addr: port
+                    .filter(|_| disclosure.ip)
+                    .map(|port| SocketAddr::new(info.endpoint.ip(), port)),
+                server_info,
+                load,
+                verified,
             })
         }
 
@@ -286,37 +767,94 @@ impl<C: ?Sized> Service<ListConnectedServersReq> for InboundHdl<C> {
         &self,
         req: ListConnectedServersReq,
     ) -> impl Future<Output = Result<Self::Response, Self::Error>> {
-        (&**self).call(req)
+        (**self).call(req)
     }
 }
-impl<C: OpenStream + ?Sized> Service<CommunicationReq> for InboundEndpoint<C> {
-    type Response = C::Response;
+impl<C: OpenStream<Response: Send + 'static> + ?Sized> Service<CommunicationReq>
+    for InboundEndpoint<C>
+{
+    type Response = Established<C::Response>;
     type Error = CommunicationReqError<C::Err>;
 
     async fn call(&self, req: CommunicationReq) -> Result<Self::Response, Self::Error> {
-        let ref server_hdl = *self
+        limit::acquire(&self.request_limiters.communication, utils::now())?;
+        let server_hdl = &*self
             .server_hdl
             .as_ref()
             .ok_or(NotServerError)?
             .upgrade()
             .ok_or(ServerHdlDroppedError)?;
 
-        // check if this endpoint identified as the public key
-        if !self.identities.contains_async(&req.from).await {
+        // a relayed request was checked by the server the initiator sent it to, and only servers
+        // relay requests
+        let max = server_hdl.config.max_forward_depth;
+        if req.hops > 0 {
+            if self.info.server_info.is_none() {
+                return Err(Self::Error::InvalidPublicKey);
+            }
+            if req.hops > max {
+                return Err(Self::Error::TtlExceeded {
+                    hops: req.hops,
+                    max,
+                });
+            }
+        } else if !self.identities.contains_async(&req.from).await {
             return Err(Self::Error::InvalidPublicKey);
         }
+        server_hdl.check_bans(&self.info, [&req.from]).await?;
+        // an unlisted key is not found by those it did not grant to reach it
+        if !server_hdl
+            .may_see(&req.from, &req.to, req.grant.as_ref())
+            .await
+        {
+            return Err(Self::Error::CannotFindKey);
+        }
+        if !server_hdl.may_reach(&req.from, &req.to).await {
+            return Err(Self::Error::UsageDenied);
+        }
 
-        // get the handle that the initiator will communicate with
+        // get the handle that the initiator will communicate with, then the healthiest server
+        // the key was reported on, then wait for it to identify again if it was connected recently
         let to_hdl = match server_hdl.key_to_endpoint.get_async(&req.to).await {
-            Some(value) => value,
-            None => return Err(Self::Error::CannotFindKey),
+            Some(value) => value.clone(),
+            None => {
+                // a request is not relayed further than allowed, nor back to where it came from
+                let route = match req.hops < max {
+                    true => server_hdl.remote_route(&req.to, Some(self.id)).await,
+                    false => None,
+                };
+                if let Some((peer, path)) = route {
+                    let relayed = CommunicationReq {
+                        hops: req.hops + 1,
+                        ..req.clone()
+                    };
+                    // streams opened to a server are for the key on that server
+                    let stream = server_hdl
+                        .forward(self.id, peer.open_pooled(&relayed))
+                        .await
+                        .ok_or(Self::Error::CannotFindKey)??;
+                    server_hdl.measure_relay_saturation().await;
+                    return Ok(Established {
+                        stream,
+                        relay: Some(path),
+                    });
+                }
+
+                server_hdl
+                    .park(req.to, self.id)
+                    .await
+                    .ok_or(Self::Error::CannotFindKey)?
+            }
         };
 
         // open a stream to the endpoint
-        Ok(to_hdl.conn.open_stream(req.from).await?)
+        Ok(Established {
+            stream: to_hdl.conn.open_stream(req.from).await?,
+            relay: None,
+        })
     }
 }
-impl<C: OpenStream + ?Sized> Service<CommunicationReq> for InboundHdl<C> {
+impl<C: OpenStream<Response: Send + 'static> + ?Sized> Service<CommunicationReq> for InboundHdl<C> {
     type Response = <InboundEndpoint<C> as Service<CommunicationReq>>::Response;
     type Error = <InboundEndpoint<C> as Service<CommunicationReq>>::Error;
 
@@ -324,7 +862,7 @@ impl<C: OpenStream + ?Sized> Service<CommunicationReq> for InboundHdl<C> {
         &self,
         req: CommunicationReq,
     ) -> impl Future<Output = Result<Self::Response, Self::Error>> {
-        (&**self).call(req)
+        (**self).call(req)
     }
 }
 impl<C: ?Sized> Service<KeysExistsReq> for InboundHdl<C> {
@@ -332,26 +870,48 @@ impl<C: ?Sized> Service<KeysExistsReq> for InboundHdl<C> {
     type Error = KeysExistsReqError;
 
     async fn call(&self, req: KeysExistsReq) -> Result<Self::Response, Self::Error> {
-        let mut triads = Vec::with_capacity(req.keys.len());
-        let ref server_hdl = *self
+        limit::acquire(&self.request_limiters.keys_exists, utils::now())?;
+        let server_hdl = &*self
             .server_hdl
             .as_ref()
             .ok_or(NotServerError)?
             .upgrade()
             .ok_or(ServerHdlDroppedError)?;
+        server_hdl.config.size_limits.check_keys(req.keys.len())?;
+        if req.notify {
+            server_hdl
+                .check_watermark(server_hdl.config.memory_watermarks.subscriptions)
+                .await?;
+        }
+
+        let mut triads = Vec::with_capacity(req.keys.len());
+        let mut presence = Vec::new();
 
         let notify_when_left = |key: PublicKey| async move {
             if !req.notify {
                 return;
             }
 
-            let entry = &mut *server_hdl.notifications.entry_async(key).await.or_default();
-            // Add this handle to the notifiations map.
-            entry.insert(self.clone());
+            {
+                // endpoints are hashed by their id, which never changes
+                #[allow(clippy::mutable_key_type)]
+                let entry = &mut *server_hdl.notifications.entry_async(key).await.or_default();
+                // Add this handle to the notifiations map.
+                if entry.insert(self.clone()) {
+                    server_hdl.gauges.subscribers.update(0, 1);
+                }
+            }
+            server_hdl.expire_subscription(key, self.id).await;
+            server_hdl.journal_subscriptions(self, &[key], true).await;
         };
 
         for key in req.keys {
+            // unlisted keys are answered as if they were not identified
             let hdl = match server_hdl.key_to_endpoint.get_async(&key).await {
+                Some(_) if server_hdl.is_unlisted(&key).await => {
+                    notify_when_left(key).await;
+                    continue;
+                }
                 Some(value) => value.clone(),
                 None => {
                     notify_when_left(key).await;
@@ -369,22 +929,49 @@ impl<C: ?Sized> Service<KeysExistsReq> for InboundHdl<C> {
 
             // map from KeyTriad<CachedSigned<IdentifyData>> to KeyTriad<SignedData>
             let triad = triad.map(|value| value.value);
-            triads.push(triad)
+            triads.push(triad);
+            presence.extend(server_hdl.presence(&key).await);
         }
 
-        Ok(KeysExistsResp { triads })
+        Ok(KeysExistsResp { triads, presence })
+    }
+}
+impl<C: ?Sized> Service<UnsubscribeKeysReq> for InboundHdl<C> {
+    type Response = UnsubscribeKeysResp;
+    type Error = KeysExistsReqError;
+
+    async fn call(&self, req: UnsubscribeKeysReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+        server_hdl.config.size_limits.check_keys(req.keys.len())?;
+
+        Ok(UnsubscribeKeysResp {
+            keys: server_hdl.unsubscribe(self, &req.keys).await,
+        })
     }
 }
 impl<C: ?Sized> Service<PreIdentifyReq> for InboundEndpoint<C> {
     type Response = IdentifyData;
-    type Error = Infallible;
+    type Error = RateLimitedError;
 
     async fn call(&self, _req: PreIdentifyReq) -> Result<Self::Response, Self::Error> {
+        limit::acquire(&self.request_limiters.pre_identify, utils::now())?;
+
         // generate salt using RNG
         let mut salt = [0u8; SALT_SIZE];
-        let mut rng = rand::thread_rng();
-        rng.fill_bytes(&mut salt);
-        drop(rng);
+        utils::rng().fill_bytes(&mut salt);
+
+        let (extensions, difficulty) = match self.server_hdl.as_ref().and_then(Weak::upgrade) {
+            Some(server_hdl) => (
+                server_hdl.config.identify_extensions.clone(),
+                server_hdl.config.identify_difficulty,
+            ),
+            None => Default::default(),
+        };
 
         let start_time = utils::now();
         let identify_data = IdentifyData {
@@ -392,14 +979,70 @@ impl<C: ?Sized> Service<PreIdentifyReq> for InboundEndpoint<C> {
             start_time,
             // this expires in 5 seconds. add 5000 milliseconds.
             expire_time: start_time + 5000,
+            extensions,
+            difficulty,
+            work: None,
+            usage: KeyUsage::empty(),
         };
 
-        let mut identify_data_w = self.identify_data.write().await;
-        *identify_data_w = Some(identify_data.clone());
+        match self.server_hdl.as_ref().and_then(Weak::upgrade) {
+            Some(server_hdl) => {
+                *server_hdl
+                    .challenges
+                    .entry_async(self.id)
+                    .await
+                    .or_insert(identify_data.clone())
+                    .get_mut() = identify_data.clone();
+            }
+            None => *self.identify_data.write().await = Some(identify_data.clone()),
+        }
 
         Ok(identify_data)
     }
 }
+impl<C: ?Sized> Service<PingReq> for InboundEndpoint<C> {
+    type Response = PongResp;
+    type Error = Infallible;
+
+    async fn call(&self, req: PingReq) -> Result<Self::Response, Self::Error> {
+        Ok(PongResp {
+            nonce: req.nonce,
+            sent_time: req.sent_time,
+            received_time: utils::now(),
+        })
+    }
+}
+impl<C: ?Sized> Service<PingReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<PingReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<PingReq>>::Error;
+
+    fn call(&self, req: PingReq) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
+impl<C: ?Sized> Service<Goodbye> for InboundEndpoint<C> {
+    type Response = ();
+    type Error = Infallible;
+
+    async fn call(&self, req: Goodbye) -> Result<Self::Response, Self::Error> {
+        if let Some(server_hdl) = self.server_hdl.as_ref().and_then(Weak::upgrade) {
+            let _ = server_hdl.events.send(NodeEvent::Goodbye {
+                id: self.id,
+                server_info: self.info.server_info.clone(),
+                goodbye: req,
+            });
+        }
+        Ok(())
+    }
+}
+impl<C: ?Sized> Service<Goodbye> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<Goodbye>>::Response;
+    type Error = <InboundEndpoint<C> as Service<Goodbye>>::Error;
+
+    fn call(&self, req: Goodbye) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
 impl<C: ?Sized> Service<PreIdentifyReq> for InboundHdl<C> {
     type Response = <InboundEndpoint<C> as Service<PreIdentifyReq>>::Response;
     type Error = <InboundEndpoint<C> as Service<PreIdentifyReq>>::Error;
@@ -411,95 +1054,3 @@ impl<C: ?Sized> Service<PreIdentifyReq> for InboundHdl<C> {
         (**self).call(req)
     }
 }
-impl<C: Notify + Send + Sync + 'static + ?Sized> Service<KeyTriad<SignedData>> for InboundHdl<C> {
-    type Response = IdentifyResp;
-    type Error = IdentifyReqError;
-
-    async fn call(&self, triad: KeyTriad<SignedData>) -> Result<Self::Response, Self::Error> {
-        let identify_data_r = self.identify_data.read().await;
-
-        let identify_data = match *identify_data_r {
-            Some(value) => value,
-            None => return Err(IdentifyReqError::IdentifyDataInvalid),
-        };
-
-        let cached = triad.signed.clone().to_cached::<IdentifyData>()?;
-        let value = &cached.signable;
-
-        // Check the validity of the signature and the message type
-        if value.msg_type != SignMessageType::Identify
-            || !triad.public_key.valid(&cached.value, &triad.signature)
-        {
-            return Err(IdentifyReqError::SignatureInvalid);
-        }
-
-        // Check if the identify data is the same.
-        if value.obj != identify_data {
-            return Err(IdentifyReqError::IdentifyDataInvalid);
-        }
-
-        if utils::now() > value.obj.expire_time {
-            return Err(IdentifyReqError::Expired);
-        }
-
-        let public_key = triad.public_key;
-        let cached_triad = KeyTriad {
-            public_key,
-            signature: triad.signature,
-            signed: cached,
-        };
-
-        let server_hdl = match &self.server_hdl {
-            Some(weak) => {
-                let server_hdl = match weak.upgrade() {
-                    Some(value) => value,
-                    None => return Err(ServerHdlDroppedError.into()),
-                };
-
-                let _ = server_hdl
-                    .key_to_endpoint
-                    .insert_async(public_key, self.clone())
-                    .await;
-
-                Some(server_hdl)
-            }
-            None => None,
-        };
-
-        // Add to identities
-        match self
-            .identities
-            .insert_async(public_key, cached_triad.clone())
-            .await
-        {
-            Ok(_) => {}
-            Err(_) => return Err(IdentifyReqError::AlreadyIdentified),
-        }
-
-        // Notify endpoints that wanted to be notified when this public key connected.
-        match server_hdl {
-            Some(server_hdl) => {
-                tokio::spawn(async move {
-                    let endpoints =
-                        match server_hdl.notifications.remove_async(&public_key).await {
-                            Some(value) => value,
-                            None => return,
-                        }
-                        .1;
-
-                    for endpoint in endpoints.into_iter() {
-                        // Fire and forget the notification
-                        let _ = endpoint.conn.notify_connected(&triad).await;
-                    }
-                });
-            }
-            None => {}
-        }
-
-        // Add to vector for enumeration
-        let mut public_keys = self.public_keys.write().await;
-        public_keys.push(public_key);
-
-        Ok(IdentifyResp {})
-    }
-}
@@ -0,0 +1,57 @@
+use tokio::sync::oneshot;
+
+use super::*;
+
+/// A request waiting for a public key to identify again, along with the id of the endpoint that
+/// sent it.
+pub(crate) type Parked<C> = (u64, oneshot::Sender<InboundHdl<C>>);
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Waits for `key` to identify again on behalf of the endpoint `id`, if the key identified
+    /// recently and parking is enabled. Returns [`None`] if the key did not identify in time, or
+    /// if the wait was cancelled.
+    pub(crate) async fn park(&self, key: PublicKey, id: u64) -> Option<InboundHdl<C>> {
+        let timeout = self.config.park_timeout?;
+        let last_seen = self.last_seen.read_async(&key, |_, v| *v).await?;
+
+        if utils::now().saturating_sub(last_seen) > self.config.recently_seen.as_millis() as u64 {
+            return None;
+        }
+
+        let (send, recv) = oneshot::channel();
+        self.parked
+            .entry_async(key)
+            .await
+            .or_default()
+            .push((id, send));
+
+        // the key might have identified before the request was parked
+        if let Some(hdl) = self.key_to_endpoint.get_async(&key).await {
+            return Some(hdl.clone());
+        }
+
+        match tokio::time::timeout(timeout, recv).await {
+            Ok(Ok(hdl)) => Some(hdl),
+            _ => {
+                // drop the senders of requests that stopped waiting
+                self.parked
+                    .remove_if_async(&key, |senders| {
+                        senders.retain(|(_, send)| !send.is_closed());
+                        senders.is_empty()
+                    })
+                    .await;
+                None
+            }
+        }
+    }
+    /// Records that `key` identified as `hdl`, and completes the requests parked on it.
+    pub(crate) async fn unpark(&self, key: PublicKey, hdl: &InboundHdl<C>) {
+        *self.last_seen.entry_async(key).await.or_default().get_mut() = utils::now();
+
+        if let Some((_, senders)) = self.parked.remove_async(&key).await {
+            for (_, send) in senders {
+                let _ = send.send(hdl.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::*;
+
+/// The work a node holds on behalf of an endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PendingWork {
+    /// Communication requests of the endpoint waiting for a public key to identify again.
+    pub parked: usize,
+    /// Notifications queued to be pushed to the endpoint.
+    pub notifications: usize,
+    /// Requests forwarded to other servers on behalf of the endpoint that did not complete.
+    pub forwards: usize,
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the work this node holds on behalf of the endpoint `id`.
+    pub async fn pending_work(&self, id: u64) -> PendingWork {
+        let mut parked = 0;
+        self.parked
+            .scan_async(|_, senders| {
+                parked += senders
+                    .iter()
+                    .filter(|(parked_id, send)| *parked_id == id && !send.is_closed())
+                    .count();
+            })
+            .await;
+
+        let forwards = self
+            .forwards
+            .read_async(&id, |_, cancel| Arc::strong_count(cancel) - 1)
+            .await
+            .unwrap_or(0);
+
+        PendingWork {
+            parked,
+            notifications: self.fanout.queued(id),
+            forwards,
+        }
+    }
+    /// Cancels the work this node holds on behalf of the endpoint `id`, such as when its
+    /// connection is closing or it stopped responding. Parked requests fail as if the key they
+    /// wait for did not identify in time, requests forwarded to other servers are abandoned, and
+    /// queued notifications are dropped. A notification that is being pushed is left to finish.
+    /// Returns the work that was cancelled.
+    pub async fn cancel_pending(&self, id: u64) -> PendingWork {
+        let mut parked = 0;
+        self.parked
+            .retain_async(|_, senders| {
+                senders.retain(|(parked_id, send)| {
+                    let cancelled = *parked_id == id;
+                    if cancelled && !send.is_closed() {
+                        parked += 1;
+                    }
+                    !cancelled
+                });
+                !senders.is_empty()
+            })
+            .await;
+
+        let forwards = match self.forwards.remove_async(&id).await {
+            Some((_, cancel)) => {
+                let forwards = Arc::strong_count(&cancel) - 1;
+                cancel.notify_waiters();
+                forwards
+            }
+            None => 0,
+        };
+
+        PendingWork {
+            parked,
+            notifications: self.fanout.cancel(id),
+            forwards,
+        }
+    }
+    /// Runs `forward`, a request forwarded to another server on behalf of the endpoint `id`,
+    /// until it completes or [`ServerHandle::cancel_pending`] cancels the work of the endpoint.
+    /// Returns [`None`] if it was cancelled.
+    pub(crate) async fn forward<F: Future>(&self, id: u64, forward: F) -> Option<F::Output> {
+        let entry = self.forwards.entry_async(id).await.or_default();
+        let cancel = entry.get().clone();
+        // waits for the cancellation before it can be removed
+        let cancelled = cancel.notified();
+        drop(entry);
+        let output = tokio::select! {
+            output = forward => Some(output),
+            _ = cancelled => None,
+        };
+
+        // the last forward of the endpoint drops what cancels them
+        self.forwards
+            .remove_if_async(&id, |held| {
+                Arc::ptr_eq(held, &cancel) && Arc::strong_count(held) == 2
+            })
+            .await;
+        output
+    }
+}
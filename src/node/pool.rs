@@ -0,0 +1,285 @@
+use std::any::Any;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::Future;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::crypto::PublicKey;
+use crate::utils::{self, LockExt};
+
+/// Limits of the streams pooled over one server-to-server link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PoolConfig {
+    /// The amount of idle streams kept open when reaping.
+    pub min_streams: usize,
+    /// The maximum amount of streams open at the same time. Requests for a stream wait for one to
+    /// be returned once the maximum is reached.
+    pub max_streams: usize,
+    /// How long a stream can stay idle before it is reaped.
+    pub idle_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            min_streams: 1,
+            max_streams: 16,
+            idle_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Idle<S> {
+    stream: S,
+    since: u64,
+}
+
+/// A pool of the streams opened over a link to another server, so that relayed and forwarded
+/// requests reuse streams instead of opening a new one each.
+#[derive(Debug)]
+pub struct StreamPool<S> {
+    idle: Mutex<Vec<Idle<S>>>,
+    /// Permits for the streams in use.
+    in_use: Arc<Semaphore>,
+    config: PoolConfig,
+}
+
+impl<S> StreamPool<S> {
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            idle: Default::default(),
+            in_use: Arc::new(Semaphore::new(config.max_streams.max(1))),
+            config,
+        }
+    }
+    /// Returns the limits of the pool.
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+    /// Returns the amount of idle streams.
+    pub fn idle(&self) -> usize {
+        self.idle.locked().len()
+    }
+    /// Returns how many of the streams the pool can hold are in use, in percent.
+    pub fn saturation(&self) -> u8 {
+        let max = self.config.max_streams.max(1);
+        let in_use = max - self.in_use.available_permits();
+        (in_use * 100 / max) as u8
+    }
+    /// Takes an idle stream, or opens one with `open` if there is none. The stream returns to the
+    /// pool when the returned guard is dropped.
+    pub async fn get<E, Fut>(&self, open: impl FnOnce() -> Fut) -> Result<Pooled<'_, S>, E>
+    where
+        Fut: Future<Output = Result<S, E>>,
+    {
+        let permit = self
+            .in_use
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+
+        // the most recently used stream is the least likely to have been closed by the server
+        let stream = self.idle.locked().pop().map(|idle| idle.stream);
+        let stream = match stream {
+            Some(value) => value,
+            None => open().await?,
+        };
+
+        Ok(Pooled {
+            pool: self,
+            stream: Some(stream),
+            _permit: permit,
+        })
+    }
+    /// Returns how many of the [`PoolConfig::min_streams`] streams kept ready were taken or are in
+    /// use, along with that minimum.
+    pub fn depletion(&self) -> (usize, usize) {
+        let min = self.config.min_streams.min(self.config.max_streams.max(1));
+        (min.saturating_sub(self.idle()), min)
+    }
+    /// Takes an idle stream for good, such as to hand it to the endpoint that asked for it.
+    pub fn take(&self) -> Option<S> {
+        self.idle.locked().pop().map(|idle| idle.stream)
+    }
+    /// Opens streams with `open` until [`PoolConfig::min_streams`] streams are idle, without
+    /// exceeding [`PoolConfig::max_streams`] streams along with those in use.
+    pub async fn fill<E, Fut>(&self, mut open: impl FnMut() -> Fut) -> Result<(), E>
+    where
+        Fut: Future<Output = Result<S, E>>,
+    {
+        let max = self.config.max_streams.max(1);
+        let min = self.config.min_streams.min(max);
+        loop {
+            let in_use = max - self.in_use.available_permits();
+            let idle = self.idle();
+            if idle >= min || idle + in_use >= max {
+                break;
+            }
+            let stream = open().await?;
+            self.release(stream);
+        }
+
+        Ok(())
+    }
+    /// Closes the streams that have been idle for longer than [`PoolConfig::idle_timeout`],
+    /// keeping [`PoolConfig::min_streams`] open. Returns the amount of closed streams.
+    pub fn reap(&self) -> usize {
+        let now = utils::now();
+        let timeout = self.config.idle_timeout.as_millis() as u64;
+        let mut idle = self.idle.locked();
+
+        // streams are returned in order, so the least recently used streams come first
+        let expired = idle
+            .iter()
+            .take_while(|stream| now.saturating_sub(stream.since) >= timeout)
+            .count();
+        let reaped = expired.min(idle.len().saturating_sub(self.config.min_streams));
+        idle.drain(..reaped);
+
+        reaped
+    }
+    fn release(&self, stream: S) {
+        self.idle.locked().push(Idle {
+            stream,
+            since: utils::now(),
+        });
+    }
+}
+
+/// A stream taken from a [`StreamPool`]. Returns to the pool when dropped.
+#[derive(Debug)]
+pub struct Pooled<'a, S> {
+    pool: &'a StreamPool<S>,
+    stream: Option<S>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<S> Pooled<'_, S> {
+    /// Closes the stream instead of returning it to the pool, such as when it broke.
+    pub fn discard(mut self) {
+        self.stream = None;
+    }
+}
+
+impl<S> Deref for Pooled<'_, S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        self.stream.as_ref().expect("the stream is taken on drop")
+    }
+}
+impl<S> DerefMut for Pooled<'_, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.stream.as_mut().expect("the stream is taken on drop")
+    }
+}
+impl<S> Drop for Pooled<'_, S> {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            self.pool.release(stream);
+        }
+    }
+}
+
+/// A [`StreamPool`] whose stream type is erased, so that endpoints can hold the pools of their
+/// connection whatever its stream type is.
+trait ErasedPool: Any + Send + Sync {
+    fn idle(&self) -> usize;
+    fn depletion(&self) -> (usize, usize);
+    fn reap(&self) -> usize;
+}
+
+impl<S: Send + 'static> ErasedPool for StreamPool<S> {
+    fn idle(&self) -> usize {
+        StreamPool::idle(self)
+    }
+    fn depletion(&self) -> (usize, usize) {
+        StreamPool::depletion(self)
+    }
+    fn reap(&self) -> usize {
+        StreamPool::reap(self)
+    }
+}
+
+/// The streams pre-established over the link to a server, pooled by the public key they are for,
+/// since a stream opened to a server is for one key on that server.
+#[derive(Default)]
+pub(crate) struct PeerStreams {
+    pools: scc::HashMap<PublicKey, Arc<dyn ErasedPool>>,
+}
+
+impl std::fmt::Debug for PeerStreams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeerStreams")
+            .field("keys", &self.pools.len())
+            .finish()
+    }
+}
+
+impl PeerStreams {
+    /// Returns the pool of the streams for `key`, creating it with `config` if there is none.
+    pub(crate) async fn pool<S: Send + 'static>(
+        &self,
+        key: PublicKey,
+        config: PoolConfig,
+    ) -> Option<Arc<StreamPool<S>>> {
+        let pool = self
+            .pools
+            .entry_async(key)
+            .await
+            .or_insert_with(|| Arc::new(StreamPool::<S>::new(config)))
+            .get()
+            .clone();
+        // the streams of a connection are all of the same type
+        let pool: Arc<dyn Any + Send + Sync> = pool;
+        pool.downcast().ok()
+    }
+    /// Returns the pool of the streams for `key`, if streams were pooled for it.
+    pub(crate) async fn get<S: Send + 'static>(
+        &self,
+        key: &PublicKey,
+    ) -> Option<Arc<StreamPool<S>>> {
+        let pool: Arc<dyn Any + Send + Sync> =
+            self.pools.read_async(key, |_, pool| pool.clone()).await?;
+        pool.downcast().ok()
+    }
+    /// Returns the keys streams are pooled for.
+    pub(crate) async fn keys(&self) -> Vec<PublicKey> {
+        let mut keys = Vec::new();
+        self.pools.scan_async(|key, _| keys.push(*key)).await;
+        keys
+    }
+    /// Returns how many of the streams kept ready for every key were taken or are in use, along
+    /// with how many are kept ready. Refer to [`StreamPool::depletion`].
+    pub(crate) async fn depletion(&self) -> (usize, usize) {
+        let (mut depleted, mut ready) = (0, 0);
+        self.pools
+            .scan_async(|_, pool| {
+                let (pool_depleted, pool_ready) = pool.depletion();
+                depleted += pool_depleted;
+                ready += pool_ready;
+            })
+            .await;
+        (depleted, ready)
+    }
+    /// Closes the streams of the keys that `keep` rejects, and the streams of the other keys that
+    /// idled for too long. Returns the amount of closed streams.
+    pub(crate) async fn reap(&self, mut keep: impl FnMut(&PublicKey) -> bool) -> usize {
+        let mut reaped = 0;
+        self.pools
+            .retain_async(|key, pool| {
+                if keep(key) {
+                    reaped += pool.reap();
+                    return true;
+                }
+                reaped += pool.idle();
+                false
+            })
+            .await;
+        reaped
+    }
+}
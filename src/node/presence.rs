@@ -0,0 +1,85 @@
+use tower_async::Service;
+
+use super::*;
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the latest presence `key` published, if any.
+    pub async fn presence(&self, key: &PublicKey) -> Option<KeyTriad<PresenceUpdate>> {
+        self.presence
+            .read_async(key, |_, presence| presence.clone())
+            .await
+    }
+    /// Stores `presence` as the presence of its key, unless the key published a later one.
+    pub(crate) async fn set_presence(&self, presence: KeyTriad<PresenceUpdate>) {
+        let gauge = &self.gauges.presence_bytes;
+        self.presence
+            .entry_async(presence.public_key)
+            .await
+            .and_modify(|value| {
+                // updates can arrive out of order
+                if value.signed.time <= presence.signed.time {
+                    gauge.update(presence_bytes(value), presence_bytes(&presence));
+                    *value = presence.clone();
+                }
+            })
+            .or_insert_with(|| {
+                gauge.update(0, presence_bytes(&presence));
+                presence.clone()
+            });
+    }
+    /// Forgets the presence `key` published, if any.
+    pub(crate) async fn clear_presence(&self, key: &PublicKey) {
+        if let Some((_, presence)) = self.presence.remove_async(key).await {
+            self.gauges
+                .presence_bytes
+                .update(presence_bytes(&presence), 0);
+        }
+    }
+}
+
+impl<C: ?Sized> Service<PublishPresenceReq> for InboundEndpoint<C> {
+    type Response = ();
+    type Error = PresenceReqError;
+
+    async fn call(&self, req: PublishPresenceReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        let presence = req.presence;
+        if !self.identities.contains_async(&presence.public_key).await {
+            return Err(PresenceReqError::InvalidPublicKey);
+        }
+        let size = presence.signed.message.as_ref().map_or(0, String::len);
+        if size > MAX_PRESENCE_MESSAGE {
+            return Err(PresenceReqError::TooLarge {
+                size,
+                max: MAX_PRESENCE_MESSAGE,
+            });
+        }
+        server_hdl
+            .config
+            .validity
+            .check_time(presence.signed.time, utils::now())?;
+        if !presence.valid() {
+            return Err(PresenceReqError::InvalidSignature);
+        }
+
+        server_hdl.set_presence(presence).await;
+        Ok(())
+    }
+}
+impl<C: ?Sized> Service<PublishPresenceReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<PublishPresenceReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<PublishPresenceReq>>::Error;
+
+    fn call(
+        &self,
+        req: PublishPresenceReq,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+
+/// The expired state a node cleared. Refer to [`ServerHandle::reap`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Reaped {
+    /// Identify data handed out to endpoints that did not identify before it expired.
+    pub challenges: usize,
+    /// Subscriptions of endpoints to public keys that were not renewed in time.
+    pub subscriptions: usize,
+    /// Bans of the [`BanList`] that expired.
+    pub bans: usize,
+    /// Resumption tokens that expired before they were used.
+    pub resumptions: usize,
+    /// Streams pre-established to connected servers that idled for too long, or whose key is no
+    /// longer reported on the server.
+    pub streams: usize,
+    /// Messages whose receipts were routed back to their sender for longer than
+    /// [`NodeConfig::receipt_ttl`].
+    pub receipts: usize,
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Clears the identify data handed out with a [`PreIdentifyReq`] that expired, and the
+    /// subscriptions older than [`NodeConfig::subscription_ttl`], the bans that expired, and the
+    /// resumption tokens older than [`NodeConfig::resumption_ttl`], the streams to connected
+    /// servers that idled for longer than [`NodeConfig::peer_streams`] allows, and the receipts
+    /// tracked for longer than [`NodeConfig::receipt_ttl`]. Returns what was cleared.
+    ///
+    /// This is called every [`NodeConfig::reap_interval`] once [`ServerHandle::start_reaper`] was
+    /// called.
+    pub async fn reap(&self) -> Reaped {
+        let now = utils::now();
+
+        let mut challenges = 0;
+        self.challenges
+            .retain_async(|_, data| {
+                let expired = now > data.expire_time;
+                challenges += expired as usize;
+                !expired
+            })
+            .await;
+
+        let mut expired = Vec::new();
+        self.subscription_expiry
+            .retain_async(|subscription, expire_time| {
+                if now <= *expire_time {
+                    return true;
+                }
+                expired.push(*subscription);
+                false
+            })
+            .await;
+        let mut subscriptions = 0;
+        for (key, id) in expired {
+            self.notifications
+                .remove_if_async(&key, |endpoints| {
+                    let len = endpoints.len();
+                    endpoints.retain(|endpoint| endpoint.id != id);
+                    subscriptions += len - endpoints.len();
+                    self.gauges.subscribers.update(len, endpoints.len());
+                    endpoints.is_empty()
+                })
+                .await;
+        }
+
+        let mut resumptions = 0;
+        self.resumptions
+            .retain_async(|token, _| {
+                let expired = now > token.0.expire_time;
+                resumptions += expired as usize;
+                !expired
+            })
+            .await;
+
+        let peers: Vec<_> = self
+            .connected_servers
+            .read()
+            .await
+            .iter()
+            .cloned()
+            .collect();
+        let mut streams = 0;
+        for peer in peers {
+            let mut routed = HashSet::new();
+            for key in peer.streams.keys().await {
+                if self.routes_through(&key, peer.id, now).await {
+                    routed.insert(key);
+                }
+            }
+            streams += peer.streams.reap(|key| routed.contains(key)).await;
+        }
+        self.measure_relay_saturation().await;
+
+        Reaped {
+            challenges,
+            subscriptions,
+            bans: self.bans.reap(now).await,
+            resumptions,
+            streams,
+            receipts: self.reap_receipts(now).await,
+        }
+    }
+    /// Sets when the subscription of the endpoint `id` to `key` expires, if subscriptions expire.
+    pub(crate) async fn expire_subscription(&self, key: PublicKey, id: u64) {
+        let Some(ttl) = self.config.subscription_ttl else {
+            return;
+        };
+        *self
+            .subscription_expiry
+            .entry_async((key, id))
+            .await
+            .or_default()
+            .get_mut() = utils::now() + ttl.as_millis() as u64;
+    }
+}
+
+impl<C: ?Sized + Send + Sync + 'static> ServerHandle<C> {
+    /// Starts clearing expired state every [`NodeConfig::reap_interval`], replacing the task
+    /// started before, if any. Must be called within a tokio runtime. Does nothing if the
+    /// interval is [`None`].
+    ///
+    /// The task stops once the node is shut down with [`ServerHandle::shutdown`] or dropped.
+    pub fn start_reaper(self: &Arc<Self>) {
+        let Some(interval) = self.config.reap_interval else {
+            return;
+        };
+        let server_hdl = Arc::downgrade(self);
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            // the first tick completes at once
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                let Some(server_hdl) = server_hdl.upgrade() else {
+                    return;
+                };
+                server_hdl.reap().await;
+                let usage = server_hdl.memory_usage().await;
+                let _ = server_hdl.events.send(NodeEvent::MemoryUsage { usage });
+            }
+        });
+
+        if let Some(reaper) = self.reaper.locked().replace(task.abort_handle()) {
+            reaper.abort();
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use tower_async::Service;
+
+use super::*;
+
+/// A message stored for a public key, whose receipts are routed back to its sender.
+#[derive(Debug)]
+pub(crate) struct Tracked {
+    from: PublicKey,
+    to: PublicKey,
+    /// The hash of the signed payload of the message.
+    message: HashMsg,
+    receipt: Option<KeyTriad<Receipt>>,
+    /// When receipts of the message stop being routed, in milliseconds since the unix epoch.
+    expire_time: u64,
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Starts routing the receipts of the message `id` from `to` back to `from`.
+    pub(crate) async fn track(
+        &self,
+        id: MessageId,
+        from: PublicKey,
+        to: PublicKey,
+        message: HashMsg,
+    ) {
+        let tracked = Tracked {
+            from,
+            to,
+            message,
+            receipt: None,
+            expire_time: utils::now().saturating_add(self.config.receipt_ttl.as_millis() as u64),
+        };
+        let _ = self.receipts.insert_async(id, tracked).await;
+    }
+    /// Stops routing the receipts of the messages `ids`, once they can no longer be delivered.
+    pub(crate) async fn untrack(&self, ids: &[MessageId]) {
+        for id in ids {
+            self.receipts.remove_async(id).await;
+        }
+    }
+    /// Stops routing the receipts of the messages tracked for longer than
+    /// [`NodeConfig::receipt_ttl`]. Returns how many were dropped.
+    pub(crate) async fn reap_receipts(&self, now: u64) -> usize {
+        let mut reaped = 0;
+        self.receipts
+            .retain_async(|_, tracked| {
+                let expired = now > tracked.expire_time;
+                reaped += expired as usize;
+                !expired
+            })
+            .await;
+        reaped
+    }
+}
+
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<SendReceiptReq> for InboundHdl<C> {
+    type Response = ();
+    type Error = ReceiptReqError;
+
+    async fn call(&self, req: SendReceiptReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        let receipt = req.receipt;
+        if !self.identities.contains_async(&receipt.public_key).await {
+            return Err(ReceiptReqError::InvalidPublicKey);
+        }
+        if !receipt.valid() {
+            return Err(ReceiptReqError::InvalidReceipt);
+        }
+
+        let from = {
+            let mut entry = server_hdl
+                .receipts
+                .get_async(&receipt.signed.id)
+                .await
+                .ok_or(ReceiptReqError::UnknownMessage)?;
+            let tracked = entry.get_mut();
+
+            // only the recipient can acknowledge the message
+            if tracked.to != receipt.public_key || tracked.message != receipt.signed.message {
+                return Err(ReceiptReqError::InvalidReceipt);
+            }
+            // a message that was read is not reported as only delivered again
+            if tracked
+                .receipt
+                .is_some_and(|latest| latest.signed.status >= receipt.signed.status)
+            {
+                return Ok(());
+            }
+
+            tracked.receipt = Some(receipt);
+            tracked.from
+        };
+
+        // Push the receipt to the sender if it is connected. Otherwise, it can ask for it later.
+        let endpoint = server_hdl
+            .key_to_endpoint
+            .get_async(&from)
+            .await
+            .map(|entry| entry.clone());
+        if let Some(endpoint) = endpoint {
+            // Fire and forget the notification
+            server_hdl.fanout.submit(endpoint.id, async move {
+                let _ = endpoint.conn.notify_receipt(&receipt).await;
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<C: ?Sized> Service<ReceiptReq> for InboundEndpoint<C> {
+    type Response = ReceiptResp;
+    type Error = ReceiptReqError;
+
+    async fn call(&self, req: ReceiptReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        let entry = server_hdl
+            .receipts
+            .get_async(&req.id)
+            .await
+            .ok_or(ReceiptReqError::UnknownMessage)?;
+
+        // only the sender can follow the message
+        if !self.identities.contains_async(&entry.from).await {
+            return Err(ReceiptReqError::InvalidPublicKey);
+        }
+
+        // the record is dropped once the sender learned that the message was read
+        let receipt = entry.receipt;
+        if receipt.is_some_and(|receipt| receipt.signed.status == ReceiptStatus::Read) {
+            let _ = entry.remove();
+        }
+
+        Ok(ReceiptResp { receipt })
+    }
+}
+impl<C: ?Sized> Service<ReceiptReq> for InboundHdl<C> {
+    type Response = <InboundEndpoint<C> as Service<ReceiptReq>>::Response;
+    type Error = <InboundEndpoint<C> as Service<ReceiptReq>>::Error;
+
+    fn call(&self, req: ReceiptReq) -> impl Future<Output = Result<Self::Response, Self::Error>> {
+        (**self).call(req)
+    }
+}
@@ -0,0 +1,159 @@
+use tower_async::Service;
+
+use super::*;
+
+/// The most messages a reconciliation with a peer is allowed to take, in case the peer never
+/// agrees.
+pub const MAX_RECONCILE_ROUNDS: usize = 64;
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the public keys identified to this node, along with those connected servers
+    /// reported as connected to them, unless the reports expired. The set is rebuilt when a key
+    /// identifies or is revoked, when a key is first reported, or once a report expires.
+    pub async fn known_keys(&self) -> Arc<KeySet> {
+        let now = utils::now();
+        if let Some((set, expire_time)) = &*self.known_keys.read().await {
+            if now <= *expire_time {
+                return set.clone();
+            }
+        }
+
+        let mut cached = self.known_keys.write().await;
+        if let Some((set, expire_time)) = &*cached {
+            if now <= *expire_time {
+                return set.clone();
+            }
+        }
+
+        let unlisted = self.unlisted().await;
+        let mut keys = Vec::new();
+        self.key_to_endpoint
+            .scan_async(|key, _| {
+                if !unlisted.contains(key) {
+                    keys.push(*key);
+                }
+            })
+            .await;
+        // the set is valid until the first of the reports it holds expires
+        let mut expire_time = u64::MAX;
+        self.remote_keys
+            .scan_async(|key, paths| {
+                let live = paths
+                    .iter()
+                    .map(|remote| remote.expire_time)
+                    .filter(|time| now <= *time)
+                    .max();
+                if let Some(time) = live {
+                    keys.push(*key);
+                    expire_time = expire_time.min(time);
+                }
+            })
+            .await;
+
+        let set = Arc::new(KeySet::new(keys));
+        *cached = Some((set.clone(), expire_time));
+        set
+    }
+    /// Discards the cached set of known keys. Must be called after a key is first reported by a
+    /// connected server, or its reports are dropped.
+    pub(crate) async fn invalidate_known_keys(&self) {
+        *self.known_keys.write().await = None;
+    }
+}
+
+impl<C: Service<KeysExistsRReq, Response = KeysExistsRResp> + ?Sized> ServerHandle<C> {
+    /// Remembers the keys a reconciliation with `peer` found to be connected to it, once the
+    /// peer proved that they identified to it, as when it answers a [`KeysExistsRReq`]. Returns
+    /// the amount of keys learned.
+    ///
+    /// A reconciliation only carries the keys, which a peer could make up.
+    async fn learn_reconciled(&self, keys: Vec<PublicKey>, peer: &InboundHdl<C>) -> usize {
+        let mut learned = 0;
+        for keys in keys.chunks(self.config.size_limits.max_keys.max(1)) {
+            let req = KeysExistsRReq {
+                keys: keys.into(),
+                depth: 0,
+            };
+            let Ok(resp) = peer.conn.call(req).await else {
+                continue;
+            };
+            for value in resp.triads {
+                if self.verify_remote(&value.triad, keys).await {
+                    self.learn_remote(value.triad.public_key, peer).await;
+                    learned += 1;
+                }
+            }
+        }
+        learned
+    }
+}
+
+impl<
+        C: Service<ReconcileReq, Response = ReconcileResp>
+            + Service<KeysExistsRReq, Response = KeysExistsRResp>
+            + ?Sized,
+    > ServerHandle<C>
+{
+    /// Reconciles the public keys this node knows of with those every connected server knows of,
+    /// so that servers that were apart for a while converge without exchanging their whole
+    /// sets. Returns the amount of keys learned.
+    ///
+    /// Keys are remembered as in [`NodeConfig::remote_key_ttl`], so this should be called more
+    /// often than the keys expire.
+    pub async fn reconcile_peers(&self) -> usize {
+        let mut learned = 0;
+
+        for peer in self.forward_peers().await {
+            let known = self.known_keys().await;
+            let mut round = known.start_reconcile();
+
+            for _ in 0..MAX_RECONCILE_ROUNDS {
+                let resp = match peer.conn.call(ReconcileReq { round }).await {
+                    Ok(value) => value,
+                    Err(_) => break,
+                };
+                let (answer, keys) = known.reconcile(&resp.round, self.config.reconcile_leaf_size);
+                learned += self.learn_reconciled(keys, &peer).await;
+
+                if answer.items.is_empty() {
+                    // the peer still has to learn the keys it lacks
+                    if !answer.keys.is_empty() {
+                        let _ = peer.conn.call(ReconcileReq { round: answer }).await;
+                    }
+                    break;
+                }
+                round = answer;
+            }
+        }
+
+        learned
+    }
+}
+
+impl<C: Service<KeysExistsRReq, Response = KeysExistsRResp> + ?Sized> Service<ReconcileReq>
+    for InboundHdl<C>
+{
+    type Response = ReconcileResp;
+    type Error = ReconcileReqError;
+
+    async fn call(&self, req: ReconcileReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // only federated servers route keys
+        if self.info.server_info.is_none() {
+            return Err(ReconcileReqError::NotPeer);
+        }
+        server_hdl.config.size_limits.check_keys(req.round.size())?;
+
+        let known = server_hdl.known_keys().await;
+        let (round, keys) = known.reconcile(&req.round, server_hdl.config.reconcile_leaf_size);
+        server_hdl.learn_reconciled(keys, self).await;
+
+        Ok(ReconcileResp { round })
+    }
+}
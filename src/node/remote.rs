@@ -0,0 +1,197 @@
+use super::*;
+
+/// A connected server that reported a public key as connected to it.
+#[derive(Debug)]
+pub(crate) struct RemoteKey<C: ?Sized> {
+    pub(crate) peer: InboundHdl<C>,
+    pub(crate) expire_time: u64,
+}
+
+/// The connected server a communication was relayed through, and how healthy it was when it was
+/// chosen.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RelayPath {
+    /// The endpoint id of the server.
+    pub id: u64,
+    pub server_info: ServerInfo,
+    /// The smoothed round trip time of the server, in milliseconds. Is pessimistic if no
+    /// request forwarded to the server succeeded yet.
+    pub rtt: u64,
+    /// The load the server advertised, if it did.
+    pub load: Option<Load>,
+}
+
+/// A stream opened for a [`CommunicationReq`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Established<S> {
+    pub stream: S,
+    /// The server the stream was relayed through, or [`None`] if the key is identified to this
+    /// node.
+    pub relay: Option<RelayPath>,
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Remembers that `key` is connected to `peer`, so that communication requests to it can be
+    /// routed to the peer without looking the key up again. A key can be connected to several
+    /// servers at once. Does nothing if [`NodeConfig::remote_key_ttl`] is [`None`].
+    pub(crate) async fn learn_remote(&self, key: PublicKey, peer: &InboundHdl<C>) {
+        let ttl = match self.config.remote_key_ttl {
+            Some(value) => value,
+            None => return,
+        };
+        let now = utils::now();
+        let expire_time = now + ttl.as_millis() as u64;
+
+        let known = {
+            let mut entry = self.remote_keys.entry_async(key).await.or_default();
+            let paths = entry.get_mut();
+            let known = paths.iter().any(|remote| now <= remote.expire_time);
+            match paths.iter_mut().find(|remote| remote.peer == *peer) {
+                Some(remote) => remote.expire_time = expire_time,
+                None => {
+                    paths.push(RemoteKey {
+                        peer: peer.clone(),
+                        expire_time,
+                    });
+                    self.gauges.routes.update(0, 1);
+                }
+            }
+            known
+        };
+        if !known {
+            self.invalidate_known_keys().await;
+        }
+    }
+    /// Checks that `triad`, which a connected server reported as connected to it, is a validly
+    /// signed identify triad of one of `keys`, so that a server cannot have this node route keys
+    /// it was never asked about, or that never identified, to it.
+    pub(crate) async fn verify_remote(
+        &self,
+        triad: &KeyTriad<SignedData>,
+        keys: &[PublicKey],
+    ) -> bool {
+        if !keys.contains(&triad.public_key)
+            || WrongMessageTypeError::check(SignMessageType::Identify, &triad.signed).is_err()
+        {
+            return false;
+        }
+        let Ok(cached) = triad.signed.clone().to_cached::<IdentifyData>() else {
+            return false;
+        };
+        self.verify(&triad.public_key, &cached, &triad.signature)
+            .await
+            .is_ok()
+    }
+    /// Returns whether `key` was reported on `peer`, and the report did not expire.
+    pub(crate) async fn routes_through(&self, key: &PublicKey, peer: u64, now: u64) -> bool {
+        self.remote_keys
+            .read_async(key, |_, paths| {
+                paths
+                    .iter()
+                    .any(|remote| remote.peer.id == peer && now <= remote.expire_time)
+            })
+            .await
+            .unwrap_or(false)
+    }
+    /// Returns the healthiest connected server `key` was reported on other than the endpoint
+    /// `except`, along with its health. Servers whose reports expired, that disconnected or that
+    /// are demoted are skipped, and the reports of those that are gone are forgotten.
+    pub(crate) async fn remote_route(
+        &self,
+        key: &PublicKey,
+        except: Option<u64>,
+    ) -> Option<(InboundHdl<C>, RelayPath)> {
+        let now = utils::now();
+        let paths = self
+            .remote_keys
+            .read_async(key, |_, paths| {
+                paths
+                    .iter()
+                    .filter(|remote| now <= remote.expire_time)
+                    .map(|remote| remote.peer.clone())
+                    .collect::<Vec<_>>()
+            })
+            .await?;
+
+        let mut best = None;
+        let mut live = Vec::new();
+        for peer in paths {
+            if !self.connected_servers.read().await.contains(&peer) {
+                continue;
+            }
+            live.push(peer.clone());
+            if Some(peer.id) == except {
+                continue;
+            }
+
+            let health = self.peer_health(peer.id).await.unwrap_or_default();
+            if !health.available(now) {
+                continue;
+            }
+            let score = (health.ranked_rtt(), health.load);
+            if best.as_ref().is_none_or(|(best, _, _)| score < *best) {
+                best = Some((score, peer, health));
+            }
+        }
+
+        if live.is_empty() {
+            if let Some((_, paths)) = self.remote_keys.remove_async(key).await {
+                self.gauges.routes.update(paths.len(), 0);
+            }
+            self.invalidate_known_keys().await;
+            return None;
+        }
+        if let Some(mut entry) = self.remote_keys.get_async(key).await {
+            let paths = entry.get_mut();
+            let len = paths.len();
+            paths.retain(|remote| now <= remote.expire_time && live.contains(&remote.peer));
+            self.gauges.routes.update(len, paths.len());
+        }
+
+        let (_, peer, health) = best?;
+        let path = RelayPath {
+            id: peer.id,
+            server_info: peer.info.server_info.clone()?,
+            rtt: health.ranked_rtt(),
+            load: health.load,
+        };
+        Some((peer, path))
+    }
+}
+
+impl<C: OpenStream<Response: Send + 'static> + ?Sized> ServerHandle<C> {
+    /// Pre-establishes streams for `key` to the healthiest connected server it was reported on,
+    /// up to the [`PoolConfig::min_streams`] of [`NodeConfig::peer_streams`], so that a
+    /// [`CommunicationReq`] to the key does not wait for a stream to open. Returns whether the key
+    /// was reported on a server.
+    ///
+    /// Should be called when gossip or a lookup reports a key that endpoints are expected to
+    /// reach soon. Streams that are not used are closed by [`ServerHandle::reap`].
+    pub async fn prepare_streams(&self, key: PublicKey) -> Result<bool, C::Err> {
+        let Some((peer, _)) = self.remote_route(&key, None).await else {
+            return Ok(false);
+        };
+        if let Some(pool) = peer.streams.pool(key, self.config.peer_streams).await {
+            pool.fill(|| peer.conn.open_stream(key)).await?;
+        }
+        self.measure_relay_saturation().await;
+        Ok(true)
+    }
+}
+
+impl<C: OpenStream<Response: Send + 'static> + ?Sized> InboundEndpoint<C> {
+    /// Opens a stream to relay `req` over the connection to this server, taking one that was
+    /// pre-established by [`ServerHandle::prepare_streams`] if there is one. Pre-established
+    /// streams carry no request, so they are only taken for requests the initiator sent to this
+    /// node itself.
+    pub(crate) async fn open_pooled(&self, req: &CommunicationReq) -> Result<C::Response, C::Err> {
+        let pooled = match (req.hops, self.streams.get::<C::Response>(&req.to).await) {
+            (1, Some(pool)) => pool.take(),
+            _ => None,
+        };
+        match pooled {
+            Some(stream) => Ok(stream),
+            None => self.conn.relay_stream(req).await,
+        }
+    }
+}
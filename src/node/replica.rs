@@ -0,0 +1,419 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::mem::size_of;
+
+use tokio::sync::watch;
+use tower_async::Service;
+
+use super::*;
+
+/// Whether a node serves endpoints, or follows a primary as a warm standby. Refer to
+/// [`ReplicationConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplicaRole {
+    Primary,
+    Standby,
+}
+
+/// The recent writes to the state of a node that standbys replicate, numbered in sequence.
+#[derive(Debug)]
+pub(crate) struct Journal {
+    position: JournalPosition,
+    /// The sequence number after which every record is held.
+    start: u64,
+    records: VecDeque<JournalRecord>,
+    /// The approximate size of the records held, in bytes.
+    bytes: usize,
+    /// The position in the journal of the primary this node replicated up to, if it is a
+    /// standby.
+    following: JournalPosition,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self {
+            position: JournalPosition {
+                epoch: u64::from_be_bytes(utils::random_bytes()),
+                seq: 0,
+            },
+            start: 0,
+            records: VecDeque::new(),
+            bytes: 0,
+            following: JournalPosition::default(),
+        }
+    }
+}
+
+impl Journal {
+    fn record(&mut self, entry: JournalEntry, history: usize) {
+        self.position.seq += 1;
+        let record = JournalRecord {
+            seq: self.position.seq,
+            entry,
+        };
+        self.bytes += record_size(&record);
+        self.records.push_back(record);
+        while self.records.len() > history {
+            if let Some(record) = self.records.pop_front() {
+                self.bytes -= record_size(&record);
+                self.start = record.seq;
+            }
+        }
+    }
+    /// Returns the amount of records held, and their approximate size.
+    pub(crate) fn usage(&self) -> Usage {
+        Usage {
+            entries: self.records.len(),
+            bytes: self.bytes,
+        }
+    }
+    /// Returns at most `max` records after `since`, or [`None`] if they are no longer held.
+    fn since(&self, since: JournalPosition, max: usize) -> Option<Vec<JournalRecord>> {
+        if since.epoch != self.position.epoch
+            || since.seq < self.start
+            || since.seq > self.position.seq
+        {
+            return None;
+        }
+
+        Some(
+            self.records
+                .iter()
+                .filter(|record| record.seq > since.seq)
+                .take(max)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// Returns the approximate size of `record`, including the keys, ids and payloads it holds.
+fn record_size(record: &JournalRecord) -> usize {
+    let held = match &record.entry {
+        JournalEntry::Subscribed { keys, .. } | JournalEntry::Unsubscribed { keys, .. } => {
+            keys.len() * size_of::<PublicKey>()
+        }
+        JournalEntry::Deposited { mail, .. } => mail.payload.signed.size(),
+        JournalEntry::Delivered { ids, .. } => ids.len() * size_of::<MessageId>(),
+        JournalEntry::Identified { .. }
+        | JournalEntry::Revoked { .. }
+        | JournalEntry::Fetched { .. } => 0,
+    };
+    size_of::<JournalRecord>() + held
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns whether this node serves endpoints, or follows a primary.
+    pub fn role(&self) -> ReplicaRole {
+        *self.role.borrow()
+    }
+    /// Watches the role of this node, so that a standby starts listening once it is promoted.
+    pub fn watch_role(&self) -> watch::Receiver<ReplicaRole> {
+        self.role.subscribe()
+    }
+    /// Promotes this standby to a primary, once its primary failed. Returns the position in the
+    /// journal of the primary it replicated up to, or [`None`] if it was a primary already.
+    ///
+    /// The node starts a journal of its own, that its own standbys follow from a snapshot.
+    pub fn promote(&self) -> Option<JournalPosition> {
+        if !self.role.send_if_modified(|role| {
+            let standby = *role == ReplicaRole::Standby;
+            *role = ReplicaRole::Primary;
+            standby
+        }) {
+            return None;
+        }
+
+        let position = std::mem::take(&mut *self.journal.locked()).following;
+        let _ = self.events.send(NodeEvent::Promoted { position });
+        Some(position)
+    }
+    /// Returns the position of the latest entry of the journal of this node.
+    pub fn journal_position(&self) -> JournalPosition {
+        self.journal.locked().position
+    }
+    /// Records a write to the state of this node, for standbys to replicate.
+    pub(crate) fn journal(&self, entry: JournalEntry) {
+        self.journal
+            .locked()
+            .record(entry, self.config.replication.history);
+    }
+    /// Records that `endpoint` subscribed to or unsubscribed from `keys`, as each of the public
+    /// keys it identified as. Subscriptions of endpoints that did not identify are not replicated.
+    pub(crate) async fn journal_subscriptions(
+        &self,
+        endpoint: &InboundEndpoint<C>,
+        keys: &[PublicKey],
+        subscribed: bool,
+    ) {
+        if keys.is_empty() {
+            return;
+        }
+        for subscriber in endpoint.public_keys.read().await.iter().copied() {
+            let keys = keys.to_vec();
+            self.journal(match subscribed {
+                true => JournalEntry::Subscribed { subscriber, keys },
+                false => JournalEntry::Unsubscribed { subscriber, keys },
+            });
+        }
+    }
+    /// Returns at most `max` records of the journal after `since`, or a snapshot of the whole
+    /// state if they are no longer held.
+    pub async fn journal_since(&self, since: JournalPosition, max: usize) -> JournalResp {
+        let (position, records) = {
+            let journal = self.journal.locked();
+            (journal.position, journal.since(since, max))
+        };
+        if let Some(records) = records {
+            return JournalResp {
+                position: records.last().map_or(since, |record| JournalPosition {
+                    seq: record.seq,
+                    ..position
+                }),
+                records,
+                snapshot: false,
+            };
+        }
+
+        // writes made while the snapshot is taken are replayed on top of it, which converges
+        // since every entry can be applied again
+        let records = self
+            .snapshot()
+            .await
+            .into_iter()
+            .map(|entry| JournalRecord {
+                seq: position.seq,
+                entry,
+            })
+            .collect();
+        JournalResp {
+            position,
+            records,
+            snapshot: true,
+        }
+    }
+    /// Returns the entries that rebuild the state of this node from nothing.
+    async fn snapshot(&self) -> Vec<JournalEntry> {
+        let mut seen = Vec::new();
+        self.last_seen
+            .scan_async(|key, time| seen.push((*key, *time)))
+            .await;
+        let mut entries = Vec::new();
+        for (public_key, time) in seen {
+            entries.push(JournalEntry::Identified {
+                public_key,
+                usage: self.key_usage(&public_key).await,
+                time,
+            });
+        }
+        self.revoked
+            .scan_async(|public_key| {
+                entries.push(JournalEntry::Revoked {
+                    public_key: *public_key,
+                })
+            })
+            .await;
+
+        let mut subscriptions = BTreeMap::<PublicKey, Vec<PublicKey>>::new();
+        let mut waiting = Vec::new();
+        self.notifications
+            .scan_async(|key, endpoints| waiting.push((*key, endpoints.clone())))
+            .await;
+        for (key, endpoints) in waiting {
+            for endpoint in endpoints {
+                for subscriber in endpoint.public_keys.read().await.iter() {
+                    subscriptions.entry(*subscriber).or_default().push(key);
+                }
+            }
+        }
+        self.replicated_subscriptions
+            .scan_async(|subscriber, keys| {
+                subscriptions
+                    .entry(*subscriber)
+                    .or_default()
+                    .extend(keys.iter().copied())
+            })
+            .await;
+        entries.extend(
+            subscriptions
+                .into_iter()
+                .map(|(subscriber, keys)| JournalEntry::Subscribed { subscriber, keys }),
+        );
+
+        entries.extend(
+            self.held_mail()
+                .await
+                .into_iter()
+                .map(|(to, mail)| JournalEntry::Deposited { to, mail }),
+        );
+        entries
+    }
+    /// Applies the records of the journal of the primary this node follows. Returns the amount
+    /// of records applied.
+    ///
+    /// Records this node replicated already are skipped, and a snapshot replaces the state
+    /// replicated before.
+    pub async fn apply_journal(&self, resp: JournalResp) -> usize {
+        let following = self.journal.locked().following;
+        if resp.snapshot {
+            self.last_seen.clear_async().await;
+            self.usage.clear_async().await;
+            self.revoked.clear_async().await;
+            self.replicated_subscriptions.clear_async().await;
+            let mut keys = Vec::new();
+            self.mailboxes.scan_async(|key, _| keys.push(*key)).await;
+            for key in keys {
+                self.drop_mailbox(&key).await;
+            }
+        }
+
+        let mut applied = 0;
+        for record in resp.records {
+            let replicated = !resp.snapshot
+                && following.epoch == resp.position.epoch
+                && record.seq <= following.seq;
+            if replicated {
+                continue;
+            }
+            self.apply(record.entry).await;
+            applied += 1;
+        }
+
+        self.journal.locked().following = resp.position;
+        applied
+    }
+    async fn apply(&self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Identified {
+                public_key,
+                usage,
+                time,
+            } => {
+                *self
+                    .last_seen
+                    .entry_async(public_key)
+                    .await
+                    .or_default()
+                    .get_mut() = time;
+                self.set_usage(public_key, usage).await;
+                // the primary notified those waiting for the key
+                if !usage.contains(KeyUsage::UNLISTED) {
+                    self.replicated_subscriptions
+                        .retain_async(|_, keys| {
+                            keys.remove(&public_key);
+                            !keys.is_empty()
+                        })
+                        .await;
+                }
+            }
+            JournalEntry::Revoked { public_key } => {
+                let _ = self.revoked.insert_async(public_key).await;
+                self.usage.remove_async(&public_key).await;
+                self.clear_presence(&public_key).await;
+                self.drop_mailbox(&public_key).await;
+                self.replicated_subscriptions
+                    .remove_async(&public_key)
+                    .await;
+                self.replicated_subscriptions
+                    .retain_async(|_, keys| {
+                        keys.remove(&public_key);
+                        !keys.is_empty()
+                    })
+                    .await;
+            }
+            JournalEntry::Subscribed { subscriber, keys } => {
+                self.replicated_subscriptions
+                    .entry_async(subscriber)
+                    .await
+                    .or_default()
+                    .get_mut()
+                    .extend(keys);
+            }
+            JournalEntry::Unsubscribed { subscriber, keys } => {
+                self.replicated_subscriptions
+                    .remove_if_async(&subscriber, |subscribed| {
+                        for key in &keys {
+                            subscribed.remove(key);
+                        }
+                        subscribed.is_empty()
+                    })
+                    .await;
+            }
+            JournalEntry::Deposited { to, mail } => self.restore_mail(to, mail).await,
+            JournalEntry::Delivered { to, ids } => self.remove_mail(&to, &ids).await,
+            JournalEntry::Fetched { to } => self.drop_mailbox(&to).await,
+        }
+    }
+    /// Restores the subscriptions `key` made on the primary this node replicated, now that `hdl`
+    /// identified as it.
+    pub(crate) async fn restore_subscriptions(&self, key: &PublicKey, hdl: &InboundHdl<C>) {
+        let Some((_, keys)) = self.replicated_subscriptions.remove_async(key).await else {
+            return;
+        };
+        for key in keys {
+            if self
+                .notifications
+                .entry_async(key)
+                .await
+                .or_default()
+                .get_mut()
+                .insert(hdl.clone())
+            {
+                self.gauges.subscribers.update(0, 1);
+            }
+        }
+    }
+}
+
+impl<C: Service<JournalReq, Response = JournalResp> + ?Sized> ServerHandle<C> {
+    /// Replicates the journal of `primary` until this node caught up with it, or is promoted.
+    /// Returns the amount of records applied.
+    ///
+    /// This should be called periodically while this node is a standby. Refer to
+    /// [`ReplicationConfig`] for what is replicated.
+    pub async fn follow(&self, primary: &InboundHdl<C>) -> Result<usize, C::Error> {
+        let mut applied = 0;
+
+        while self.role() == ReplicaRole::Standby {
+            let since = self.journal.locked().following;
+            let resp = primary.conn.call(JournalReq { since, max: None }).await?;
+            let done = resp.records.is_empty() || resp.snapshot;
+            applied += self.apply_journal(resp).await;
+            if done {
+                break;
+            }
+        }
+
+        Ok(applied)
+    }
+}
+
+impl<C: ?Sized> Service<JournalReq> for InboundHdl<C> {
+    type Response = JournalResp;
+    type Error = JournalReqError;
+
+    async fn call(&self, req: JournalReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        // the journal holds the mail of every key, so only the standbys of the node can read it
+        let followers = &server_hdl.config.replication.followers;
+        let follower = self.info.server_info.is_some()
+            && self
+                .public_keys
+                .read()
+                .await
+                .iter()
+                .any(|key| followers.contains(key));
+        if !follower {
+            return Err(JournalReqError::NotFollower);
+        }
+
+        let batch = server_hdl.config.replication.batch;
+        let max = req.max.map_or(batch, |max| (max as usize).min(batch));
+        Ok(server_hdl.journal_since(req.since, max).await)
+    }
+}
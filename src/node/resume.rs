@@ -0,0 +1,88 @@
+use tower_async::Service;
+
+use super::*;
+
+impl<C: ?Sized> Service<ResumeReq> for InboundHdl<C> {
+    type Response = ResumeResp;
+    type Error = ResumeReqError;
+
+    async fn call(&self, req: ResumeReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = &*self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        req.token
+            .0
+            .verify(&server_hdl.token_key, TokenScope::Resumption, utils::now())
+            .map_err(|_| ResumeReqError::InvalidToken)?;
+
+        let old = server_hdl
+            .resumptions
+            .remove_async(&req.token)
+            .await
+            .ok_or(ResumeReqError::InvalidToken)?
+            .1;
+
+        // take over the identities of the old endpoint
+        let mut keys = Vec::new();
+        let mut entry = old.identities.first_entry_async().await;
+        while let Some(current) = entry {
+            keys.push((*current.key(), current.get().clone()));
+            entry = current.next_async().await;
+        }
+
+        for (key, triad) in keys.iter() {
+            let _ = self.identities.insert_async(*key, triad.clone()).await;
+            server_hdl
+                .key_to_endpoint
+                .entry_async(*key)
+                .await
+                .and_modify(|hdl| *hdl = self.clone())
+                .or_insert_with(|| self.clone());
+            server_hdl.invalidate_key_set().await;
+            server_hdl.unpark(*key, self).await;
+        }
+        let keys: Vec<_> = keys.into_iter().map(|(key, _)| key).collect();
+        self.public_keys.write().await.extend(keys.iter().copied());
+
+        // the old endpoint can no longer act as the keys
+        old.identities.clear_async().await;
+        old.public_keys.write().await.clear();
+
+        // take over the subscriptions and introductions of the old endpoint
+        server_hdl
+            .notifications
+            .retain_async(|_, endpoints| {
+                if endpoints.remove(&old) && !endpoints.insert(self.clone()) {
+                    server_hdl.gauges.subscribers.update(1, 0);
+                }
+                true
+            })
+            .await;
+        server_hdl
+            .dial_tokens
+            .retain_async(|_, entry| {
+                if entry.from == old {
+                    entry.from = self.clone();
+                }
+                if entry.to == old {
+                    entry.to = self.clone();
+                }
+                true
+            })
+            .await;
+
+        let _ = server_hdl
+            .resumptions
+            .insert_async(self.resumption_token, self.clone())
+            .await;
+
+        Ok(ResumeResp {
+            keys,
+            resumption_token: self.resumption_token,
+        })
+    }
+}
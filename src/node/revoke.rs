@@ -0,0 +1,106 @@
+use tower_async::Service;
+
+use super::*;
+
+impl<C: Service<RevokeReq> + ?Sized> ServerHandle<C> {
+    /// Sends `revocation` to every connected server, including demoted ones, so that endpoints
+    /// waiting for the key on them are notified even if some of the paths between the servers
+    /// failed. Returns the amount of servers that accepted it.
+    ///
+    /// A server that learns of a revocation it already knows of ignores it, so the same
+    /// revocation can arrive over several paths and is only pushed to its subscribers once.
+    pub async fn propagate_revocation(&self, revocation: &KeyTriad<SignedData>) -> usize {
+        self.push_peers(RevokeReq {
+            revocation: revocation.clone(),
+        })
+        .await
+    }
+}
+
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<RevokeReq> for InboundHdl<C> {
+    type Response = ();
+    type Error = RevokeReqError;
+
+    async fn call(&self, req: RevokeReq) -> Result<Self::Response, Self::Error> {
+        let server_hdl = self
+            .server_hdl
+            .as_ref()
+            .ok_or(NotServerError)?
+            .upgrade()
+            .ok_or(ServerHdlDroppedError)?;
+
+        let revocation = req.revocation;
+        let public_key = revocation.public_key;
+        server_hdl
+            .config
+            .size_limits
+            .check_payload(&revocation.signed)?;
+        if server_hdl.config.require_canonical && !revocation.signed.is_canonical() {
+            return Err(RevokeReqError::NonCanonical);
+        }
+        WrongMessageTypeError::check(SignMessageType::Revoke, &revocation.signed)?;
+        let cached = revocation.signed.clone().to_cached::<Revocation>()?;
+
+        // a key can only be revoked by itself
+        if cached.signable.obj.key != public_key {
+            return Err(RevokeReqError::InvalidRevocation);
+        }
+        server_hdl
+            .config
+            .validity
+            .check_time(cached.signable.obj.time, utils::now())?;
+        server_hdl
+            .verify(&public_key, &cached, &revocation.signature)
+            .await?;
+
+        if server_hdl.revoked.insert_async(public_key).await.is_err() {
+            // already revoked
+            return Ok(());
+        }
+        server_hdl.journal(JournalEntry::Revoked { public_key });
+
+        // the endpoint identified as the key can no longer act as it
+        if let Some((_, endpoint)) = server_hdl.key_to_endpoint.remove_async(&public_key).await {
+            endpoint.identities.remove_async(&public_key).await;
+            endpoint
+                .public_keys
+                .write()
+                .await
+                .retain(|key| *key != public_key);
+            server_hdl.invalidate_key_set().await;
+            if !server_hdl.is_unlisted(&public_key).await {
+                server_hdl.record_change(public_key, KeyChangeKind::Revoked);
+            }
+        }
+        // requests waiting for the key fail, and messages held for it are dropped
+        server_hdl.parked.remove_async(&public_key).await;
+        server_hdl.usage.remove_async(&public_key).await;
+        server_hdl.clear_presence(&public_key).await;
+        server_hdl.drop_mailbox(&public_key).await;
+
+        server_hdl
+            .attest(LogEntry::Revoked {
+                public_key,
+                time: utils::now(),
+            })
+            .await;
+
+        let _ = server_hdl.events.send(NodeEvent::Revoked {
+            revocation: revocation.clone(),
+        });
+
+        // Notify endpoints that wanted to be notified when this public key connected.
+        if let Some((_, endpoints)) = server_hdl.notifications.remove_async(&public_key).await {
+            server_hdl.gauges.subscribers.update(endpoints.len(), 0);
+            for endpoint in endpoints.into_iter() {
+                let revocation = revocation.clone();
+                // Fire and forget the notification
+                server_hdl.fanout.submit(endpoint.id, async move {
+                    let _ = endpoint.conn.notify_revoked(&revocation).await;
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
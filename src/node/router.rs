@@ -0,0 +1,188 @@
+use std::marker::PhantomData;
+
+use tower_async::Service;
+
+use super::*;
+
+/// A request that is sent on the wire as a variant of [`ReqMessage`].
+pub trait WireReq: Into<ReqMessage> + TryFrom<ReqMessage> {
+    /// The object type of the variant, as returned by [`ObjectType::object_type`].
+    const OBJECT_TYPE: &'static str;
+}
+
+macro_rules! wire_req {
+    ($($for:ty => $name:expr),* $(,)?) => {
+        $(
+            impl WireReq for $for {
+                const OBJECT_TYPE: &'static str = $name;
+            }
+        )*
+    };
+}
+
+wire_req!(
+    NodeInfo => "NODE_INFO",
+    PreIdentifyReq => "PRE_IDENTIFY",
+    IdentifyReq => "IDENTIFY",
+    RevokeReq => "REVOKE",
+    KeysExistsReq => "KEYS_EXISTS",
+    UnsubscribeKeysReq => "UNSUBSCRIBE_KEYS",
+    CommunicationReq => "COMMUNICATION",
+    ListConnectedServersReq => "LIST_CONNECTED_SERVERS",
+    PingReq => "PING",
+    Goodbye => "GOODBYE",
+    BroadcastReq => "BROADCAST",
+    RelayMessage => "RELAY",
+    ReconcileReq => "RECONCILE",
+    FetchMailReq => "FETCH_MAIL",
+    SendReceiptReq => "SEND_RECEIPT",
+    ReceiptReq => "RECEIPT",
+    PublishPresenceReq => "PUBLISH_PRESENCE",
+    CrossSignReq => "CROSS_SIGN",
+    JournalReq => "JOURNAL",
+    DisconnectNoticeReq => "DISCONNECT_NOTICE",
+);
+
+/// The routes of a [`Router`] without any route, which reject every request as unsupported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Unrouted;
+
+impl Service<ReqMessage> for Unrouted {
+    type Response = RespMessage;
+    type Error = ErrorResp;
+
+    async fn call(&self, req: ReqMessage) -> Result<Self::Response, Self::Error> {
+        Err(WireReqError::Unsupported(req.object_type()).into())
+    }
+}
+
+/// The routes of a [`Router`] that send every request to a service of [`ReqMessage`], such as an
+/// [`InboundHdl`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Fallback<S>(S);
+
+impl<S> Service<ReqMessage> for Fallback<S>
+where
+    S: Service<ReqMessage, Response: Into<RespMessage>, Error: Into<ErrorResp>>,
+{
+    type Response = RespMessage;
+    type Error = ErrorResp;
+
+    async fn call(&self, req: ReqMessage) -> Result<Self::Response, Self::Error> {
+        self.0.call(req).await.map(Into::into).map_err(Into::into)
+    }
+}
+
+/// The routes of a [`Router`] that send the requests of type `Req` to `service`, and the other
+/// requests to `next`.
+pub struct Route<Req, S, Next> {
+    service: S,
+    next: Next,
+    _req: PhantomData<fn(Req)>,
+}
+
+impl<Req, S: std::fmt::Debug, Next: std::fmt::Debug> std::fmt::Debug for Route<Req, S, Next> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Route")
+            .field("req", &std::any::type_name::<Req>())
+            .field("service", &self.service)
+            .field("next", &self.next)
+            .finish()
+    }
+}
+impl<Req, S: Clone, Next: Clone> Clone for Route<Req, S, Next> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            next: self.next.clone(),
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<Req, S, Next> Service<ReqMessage> for Route<Req, S, Next>
+where
+    Req: WireReq,
+    S: Service<Req, Response: Into<RespMessage>, Error: Into<ErrorResp>>,
+    Next: Service<ReqMessage, Response = RespMessage, Error = ErrorResp>,
+{
+    type Response = RespMessage;
+    type Error = ErrorResp;
+
+    async fn call(&self, req: ReqMessage) -> Result<Self::Response, Self::Error> {
+        if req.object_type() != Req::OBJECT_TYPE {
+            return self.next.call(req).await;
+        }
+        match Req::try_from(req) {
+            Ok(req) => self
+                .service
+                .call(req)
+                .await
+                .map(Into::into)
+                .map_err(Into::into),
+            Err(_) => Err(WireReqError::Unsupported(Req::OBJECT_TYPE).into()),
+        }
+    }
+}
+
+/// Dispatches the requests decoded from the wire to the service of their message type, so that
+/// the services of the node can be mounted among the services of an application.
+///
+/// Each route is a [`Service`] of one request type, whose response is sent as a [`RespMessage`]
+/// and whose error is sent as an [`ErrorResp`]. The routes added last are matched first, and
+/// requests without a route are passed to the fallback, or rejected with
+/// [`ErrorCode::UNSUPPORTED`]. The router never fails: errors are answered with
+/// [`RespMessage::Error`].
+///
+/// An [`InboundHdl`] answers every request on its own, so a router that falls back to it only
+/// needs routes for the requests the application handles differently. Requests sent to a route
+/// skip the rate limit of the endpoint, which the route applies if it needs one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Router<R = Unrouted> {
+    routes: R,
+}
+
+impl Router {
+    /// Creates a router without any route.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S> Router<Fallback<S>> {
+    /// Creates a router that passes the requests without a route to `service`.
+    pub fn with_fallback(service: S) -> Self {
+        Self {
+            routes: Fallback(service),
+        }
+    }
+}
+
+impl<R> Router<R> {
+    /// Sends the requests of type `Req` to `service`.
+    pub fn route<Req, S>(self, service: S) -> Router<Route<Req, S, R>>
+    where
+        Req: WireReq,
+        S: Service<Req>,
+    {
+        Router {
+            routes: Route {
+                service,
+                next: self.routes,
+                _req: PhantomData,
+            },
+        }
+    }
+}
+
+impl<R> Service<ReqMessage> for Router<R>
+where
+    R: Service<ReqMessage, Response = RespMessage, Error = ErrorResp>,
+{
+    type Response = RespMessage;
+    type Error = Infallible;
+
+    async fn call(&self, req: ReqMessage) -> Result<Self::Response, Self::Error> {
+        Ok(self.routes.call(req).await.unwrap_or_else(Into::into))
+    }
+}
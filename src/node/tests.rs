@@ -1,15 +1,57 @@
 use core::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::convert::Infallible;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use futures::Future;
+use rand::thread_rng;
+use tower_async::Service;
 
-use crate::crypto::PrivateKey;
+use crate::crypto::{
+    delegation::{Delegation, DelegationScope},
+    error::{DelegationError, MultiKeyError, VerifyError},
+    log::TransparencyLog,
+    multi::MultiKeyTriad,
+    recover::RecoverableTriad,
+    schnorr::SignatureScheme,
+    threshold::{aggregate, Dkg},
+    HashAlgorithm, KeyPair, PrivateKey, PublicKey, ToHashMsg,
+};
+use crate::mock::{Chaos, MockPush};
 use crate::node::{KeyTriad, ServerHandle};
-use crate::obj::{KeysExistsReq, SignMessageType, Signable, SignedData};
-use crate::{node::InboundEndpoint, obj::PreIdentifyReq};
+use crate::obj::{
+    BroadcastReq, CommunicationReq, CrossSignReq, CrossSignResp, DelegatedTriad, DialReq,
+    DisconnectNoticeReq, ErrorCode, ErrorResp, EvictionReason, Features, FetchMailReq,
+    FetchMailResp, GetLogProofReq, Goodbye, GoodbyeCode, IdentifyData, IdentifyExtensions,
+    IdentifyReq, Introduction, IntroductionReq, JournalReq, JournalResp, KeyChangeKind,
+    KeyChangesReq, KeyConnectedTo, KeyUsage, KeysExistsRReq, KeysExistsRResp, KeysExistsReq,
+    KeysRootReq, ListConnectedServersReq, Load, LogEntry, Mail, MessageId, NodeInfo, PingReq,
+    PongResp, PresenceStatus, PublishPresenceReq, Receipt, ReceiptReq, ReceiptStatus, ReconcileReq,
+    ReconcileResp, RelayMessage, ReqMessage, RespMessage, ResumeReq, RevokeReq, SignMessageType,
+    Signable, SignedData, SignedFormat, Tagged, Transport, UnsubscribeKeysReq, UnsubscribeKeysResp,
+    MAX_PRESENCE_MESSAGE,
+};
+use crate::testkit::{
+    identify, identify_triad, DeclinedError, Scenario, ScenarioError, Step, ENDPOINT_INFO,
+};
+use crate::{node::InboundEndpoint, obj::PreIdentifyReq, CURRENT_VERSION};
 
-use super::{ConnectedServer, EndpointInfo, Notify, PRIVATE_KEY_SIZE};
+use super::error::{
+    BroadcastReqError, CommunicationReqError, CrossSignReqError, DialReqError,
+    DisconnectNoticeReqError, FetchMailReqError, IdentifyReqError, IncompatibleVersionError,
+    IntroductionReqError, JournalReqError, KeysExistsRReqError, KeysExistsReqError,
+    LogProofReqError, MailboxError, OverloadedError, PresenceReqError, ReceiptReqError,
+    ReconcileReqError, RelayReqError, ResumeReqError, RevokeReqError, ServerHdlDroppedError,
+    TooLargeError, ValidityError, WrongMessageTypeError,
+};
+use super::fair::FairScheduler;
+use super::identify::{register, Pending};
+use super::{
+    Ban, BanTarget, Check, CheckKind, CheckStatus, ConnectedServer, CountPrivacy, Disclosure,
+    DisclosurePolicy, EndpointInfo, GossipStore, InboundHdl, IpRange, MemoryGossipStore,
+    MemoryUsage, MemoryWatermarks, NodeConfig, NodeEvent, Notify, OpenStream, PendingWork,
+    Pipeline, PoolConfig, RateLimit, Reaped, ReplicaRole, ReplicationConfig, RequestLimits,
+    RetentionPolicy, Router, ServerInfo, SizeLimits, StreamPool, PRIVATE_KEY_SIZE,
+};
 
 /// The private key used for the unit tests.
 /// I do *NOT* recommend using this for anything other than tests.
@@ -18,38 +60,102 @@ const PRIVATE_KEY: [u8; PRIVATE_KEY_SIZE] = [
     169, 115, 232, 229, 225, 77, 170, 4, 162, 75,
 ];
 
-/// Endpoint info used for the unit tests.
-const ENDPOINT_INFO: EndpointInfo = EndpointInfo::non_server(SocketAddr::new(
-    IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
-    51763,
-));
-
+#[derive(Debug)]
 struct DummyNotify;
 
 impl Notify for DummyNotify {
     type Err = Infallible;
 
-    fn notify_connected(
-        &self,
-        _triad: &KeyTriad<SignedData>,
-    ) -> impl Future<Output = Result<(), Self::Err>> + Send + Sync {
-        async { unimplemented!() }
+    async fn notify_connected(&self, _triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        unimplemented!()
+    }
+    async fn notify_introduced(&self, _intro: &Introduction) -> Result<(), Self::Err> {
+        unimplemented!()
+    }
+    async fn notify_revoked(&self, _revocation: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        unimplemented!()
+    }
+    async fn notify_mail(&self, _mail: &Mail) -> Result<(), Self::Err> {
+        unimplemented!()
+    }
+}
+
+/// A connection that records the notifications pushed to it. Opening a stream to it yields the
+/// public key of the initiator.
+#[derive(Default)]
+struct RecordConn {
+    connected: Mutex<Vec<PublicKey>>,
+    intros: Mutex<Vec<Introduction>>,
+    revoked: Mutex<Vec<PublicKey>>,
+    receipts: Mutex<Vec<KeyTriad<Receipt>>>,
+    mail: Mutex<Vec<Mail>>,
+    broadcasts: Mutex<Vec<KeyTriad<SignedData>>>,
+}
+
+impl Notify for RecordConn {
+    type Err = Infallible;
+
+    async fn notify_connected(&self, triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        self.connected.lock().unwrap().push(triad.public_key);
+        Ok(())
+    }
+    async fn notify_introduced(&self, intro: &Introduction) -> Result<(), Self::Err> {
+        self.intros.lock().unwrap().push(*intro);
+        Ok(())
+    }
+    async fn notify_revoked(&self, revocation: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        self.revoked.lock().unwrap().push(revocation.public_key);
+        Ok(())
+    }
+    async fn notify_receipt(&self, receipt: &KeyTriad<Receipt>) -> Result<(), Self::Err> {
+        self.receipts.lock().unwrap().push(*receipt);
+        Ok(())
+    }
+    async fn notify_mail(&self, mail: &Mail) -> Result<(), Self::Err> {
+        self.mail.lock().unwrap().push(mail.clone());
+        Ok(())
+    }
+    async fn notify_broadcast(&self, payload: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        self.broadcasts.lock().unwrap().push(payload.clone());
+        Ok(())
+    }
+}
+impl Service<PublicKey> for RecordConn {
+    type Response = PublicKey;
+    type Error = DeclinedError;
+
+    async fn call(&self, key: PublicKey) -> Result<Self::Response, Self::Error> {
+        Ok(key)
     }
 }
+impl OpenStream for RecordConn {
+    type Err = DeclinedError;
+}
 
 #[allow(unused)]
 fn dummy_info() -> ConnectedServer {
-    ConnectedServer { ip: "127.0.0.1".parse().unwrap(), domain: arcstr::literal!("") }
+    ConnectedServer {
+        addr: Some("127.0.0.1:0".parse().unwrap()),
+        server_info: ServerInfo::new(arcstr::literal!("")),
+        load: None,
+        verified: false,
+    }
 }
 
 #[tokio::test]
 async fn keys_exists() {
-    let key = PrivateKey::new(PRIVATE_KEY);
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
     let server_hdl = ServerHandle::new_hdl();
     let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
 
-    let identify = hdl.pre_identify(PreIdentifyReq {}).await;
-    let triad = KeyTriad::gen_signed(&key, &identify, SignMessageType::Identify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triad = KeyTriad::gen_signed(
+        &key,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
 
     hdl.identify(triad.clone()).await.unwrap();
 
@@ -67,15 +173,17 @@ async fn keys_exists() {
 
 #[tokio::test]
 async fn fake_signature() {
-    let key = PrivateKey::new(PRIVATE_KEY);
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
     let server_hdl = ServerHandle::new_hdl();
     let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
 
-    let identify = hdl.pre_identify(PreIdentifyReq {}).await;
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
 
     let signable = Signable {
         msg_type: SignMessageType::Identify,
         obj: identify,
+        hash: Default::default(),
+        scheme: Default::default(),
     };
     let data = serde_cbor::to_vec(&signable).unwrap();
     let ser = SignedData::Cbor(Arc::from(data));
@@ -88,3 +196,4209 @@ async fn fake_signature() {
 
     assert!(hdl.identify(triad).await.is_err())
 }
+
+#[tokio::test]
+async fn canonical_payloads() {
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            require_canonical: true,
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+
+    // payloads signed before signables were serialized canonically still verify
+    let signable = Signable {
+        msg_type: SignMessageType::Identify,
+        obj: identify.clone(),
+        hash: Default::default(),
+        scheme: Default::default(),
+    };
+    let data = serde_cbor::to_vec(&signable).unwrap();
+    let signature = key.sign(signable.hash.hash(&data));
+    let legacy = KeyTriad {
+        public_key: key.derive_public(),
+        signed: SignedData::Cbor(Arc::from(data)),
+        signature,
+    };
+    assert!(legacy.public_key.valid(&legacy.signed, &legacy.signature));
+    assert!(!legacy.signed.is_canonical());
+
+    // unless the node requires canonical payloads
+    assert!(matches!(
+        hdl.identify(legacy).await,
+        Err(IdentifyReqError::NonCanonical)
+    ));
+
+    let triad = KeyTriad::gen_signed(
+        &key,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(triad.signed.is_canonical());
+    hdl.identify(triad).await.unwrap();
+}
+
+#[tokio::test]
+async fn canonical_batch() {
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            require_canonical: true,
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+
+    let keys: Vec<_> = (0..3).map(|_| KeyPair::generate()).collect();
+    let mut triads: Vec<_> = keys[..2]
+        .iter()
+        .map(|pair| {
+            KeyTriad::gen_signed(
+                &pair.private,
+                &identify,
+                SignMessageType::Identify,
+                SignedFormat::Cbor,
+            )
+            .unwrap()
+        })
+        .collect();
+    let signable = Signable {
+        msg_type: SignMessageType::Identify,
+        obj: identify,
+        hash: Default::default(),
+        scheme: Default::default(),
+    };
+    let data = serde_cbor::to_vec(&signable).unwrap();
+    triads.push(KeyTriad {
+        public_key: keys[2].public,
+        signature: keys[2].private.sign(signable.hash.hash(&data)),
+        signed: SignedData::Cbor(Arc::from(data)),
+    });
+
+    // the last triad of the batch is not canonical, so none of the keys are registered
+    let resp = hdl
+        .identify_batch(IdentifyReq {
+            keys: triads,
+            compact: Vec::new(),
+            multi: Vec::new(),
+            delegated: Vec::new(),
+        })
+        .await;
+    assert!(matches!(resp, Err(IdentifyReqError::NonCanonical)));
+    assert!(hdl.public_keys.read().await.is_empty());
+    for pair in keys.iter() {
+        assert!(!hdl.identities.contains_async(&pair.public).await);
+        assert!(
+            !server_hdl
+                .key_to_endpoint
+                .contains_async(&pair.public)
+                .await
+        );
+    }
+}
+
+#[tokio::test]
+async fn key_usage() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+
+    // a server-only key cannot identify on a connection from a client
+    let b_key = &b.private;
+    let identify_usage = |usage| {
+        let b_hdl = b_hdl.clone();
+        async move {
+            let identify = b_hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+            let signed = IdentifyData { usage, ..identify };
+            let triad = KeyTriad::gen_signed(
+                b_key,
+                &signed,
+                SignMessageType::Identify,
+                SignedFormat::Cbor,
+            )
+            .unwrap();
+            b_hdl.identify(triad).await
+        }
+    };
+    assert!(matches!(
+        identify_usage(KeyUsage::SERVER_ONLY).await,
+        Err(IdentifyReqError::ServerOnly)
+    ));
+
+    identify_usage(KeyUsage::NO_INCOMING).await.unwrap();
+    assert_eq!(server_hdl.key_usage(&b.public).await, KeyUsage::NO_INCOMING);
+
+    // the key cannot be reached, but can reach others
+    let to_b = IntroductionReq {
+        from: a.public,
+        to: b.public,
+    };
+    assert!(matches!(
+        a_hdl.introduce(to_b).await,
+        Err(IntroductionReqError::UsageDenied)
+    ));
+    let payload = KeyTriad::sign_detached(&a.private, b"hello");
+    assert_eq!(
+        server_hdl.deposit(b.public, payload).await,
+        Err(MailboxError::UsageDenied)
+    );
+    b_hdl
+        .introduce(IntroductionReq {
+            from: b.public,
+            to: a.public,
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn unlisted() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+    let position = server_hdl.gossip_position();
+
+    let identify = b_hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let signed = IdentifyData {
+        usage: KeyUsage::UNLISTED,
+        ..identify
+    };
+    let triad = KeyTriad::gen_signed(
+        &b.private,
+        &signed,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    b_hdl.identify(triad).await.unwrap();
+    assert!(server_hdl.is_unlisted(&b.public).await);
+
+    // the key is left out of lookups, the key set and the gossip
+    let resp = a_hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public, b.public],
+            notify: false,
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.triads.len(), 1);
+    assert_eq!(resp.triads[0].public_key, a.public);
+    let set = server_hdl.key_set().await;
+    assert!(set.contains(&a.public) && !set.contains(&b.public));
+    assert!(!server_hdl.known_keys().await.contains(&b.public));
+    assert_eq!(server_hdl.key_changes(position).changes, Some(vec![]));
+
+    // the key cannot be found without a grant
+    let req = CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: None,
+        hops: 0,
+    };
+    assert!(matches!(
+        a_hdl.communicate(req).await,
+        Err(CommunicationReqError::CannotFindKey)
+    ));
+    let to_b = IntroductionReq {
+        from: a.public,
+        to: b.public,
+    };
+    assert!(matches!(
+        a_hdl.introduce(to_b).await,
+        Err(IntroductionReqError::CannotFindKey)
+    ));
+
+    // nor with a grant to another key
+    let grant = KeyTriad::grant(&b.private, b.public, crate::utils::now() + 60_000);
+    let req = CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: Some(grant),
+        hops: 0,
+    };
+    assert!(matches!(
+        a_hdl.communicate(req).await,
+        Err(CommunicationReqError::CannotFindKey)
+    ));
+
+    let grant = KeyTriad::grant(&b.private, a.public, crate::utils::now() + 60_000);
+    let req = CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: Some(grant),
+        hops: 0,
+    };
+    assert_eq!(a_hdl.communicate(req).await.unwrap().stream, a.public);
+}
+
+#[tokio::test]
+async fn dial_token() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    identify(&a_hdl, &a.private).await;
+    identify(&b_hdl, &b.private).await;
+
+    let intro = a_hdl
+        .introduce(IntroductionReq {
+            from: a.public,
+            to: b.public,
+        })
+        .await
+        .unwrap();
+    assert_eq!(*b_hdl.conn.intros.lock().unwrap(), vec![intro]);
+
+    // a token with a forged expiry is rejected
+    let mut forged = intro.token;
+    forged.0.expire_time += 1;
+    assert!(matches!(
+        b_hdl.dial(DialReq { token: forged }).await,
+        Err(DialReqError::InvalidToken)
+    ));
+
+    // the token is single use
+    let req = DialReq { token: intro.token };
+    assert_eq!(b_hdl.dial(req).await.unwrap(), b.public);
+    assert!(matches!(
+        a_hdl.dial(req).await,
+        Err(DialReqError::InvalidToken)
+    ));
+}
+
+#[tokio::test]
+async fn park_communication() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            park_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    identify(&a_hdl, &a.private).await;
+    identify(&b_hdl, &b.private).await;
+
+    // simulate the endpoint of `b` dropping
+    server_hdl.key_to_endpoint.remove_async(&b.public).await;
+
+    let req = CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: None,
+        hops: 0,
+    };
+    let parked = tokio::spawn(async move { a_hdl.communicate(req).await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let b_hdl =
+        InboundEndpoint::server_hdl(2, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&b_hdl, &b.private).await;
+
+    assert_eq!(parked.await.unwrap().unwrap().stream, a.public);
+}
+
+#[tokio::test]
+async fn cancel_pending() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            park_timeout: Some(Duration::from_secs(5)),
+            fanout_workers: 1,
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+    identify(&b_hdl, &b.private).await;
+    server_hdl.key_to_endpoint.remove_async(&b.public).await;
+
+    let req = CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: None,
+        hops: 0,
+    };
+    let parked = tokio::spawn({
+        let a_hdl = a_hdl.clone();
+        async move { a_hdl.communicate(req).await }
+    });
+    // the only worker is busy, so the notifications of the endpoint stay queued
+    let (gate_send, gate) = tokio::sync::oneshot::channel::<()>();
+    server_hdl.fanout.submit(2, async move {
+        let _ = gate.await;
+    });
+    for _ in 0..3 {
+        server_hdl.fanout.submit(0, async {});
+    }
+    // a server the request was forwarded to does not answer
+    let forwarded = tokio::spawn({
+        let server_hdl = server_hdl.clone();
+        async move { server_hdl.forward(0, std::future::pending::<()>()).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let pending = PendingWork {
+        parked: 1,
+        notifications: 3,
+        forwards: 1,
+    };
+    assert_eq!(server_hdl.pending_work(0).await, pending);
+    assert_eq!(server_hdl.pending_work(1).await, PendingWork::default());
+
+    assert_eq!(server_hdl.cancel_pending(0).await, pending);
+    assert_eq!(server_hdl.pending_work(0).await, PendingWork::default());
+    assert!(matches!(
+        parked.await.unwrap(),
+        Err(CommunicationReqError::CannotFindKey)
+    ));
+    assert_eq!(forwarded.await.unwrap(), None);
+    assert!(server_hdl.forwards.is_empty());
+    let _ = gate_send.send(());
+}
+
+#[tokio::test]
+async fn resume() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let old =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    let token = identify(&old, &a.private).await.resumption_token;
+    old.keys_exists(KeysExistsReq {
+        keys: vec![b.public],
+        notify: true,
+    })
+    .await
+    .unwrap();
+
+    let new =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let resp = new.resume(ResumeReq { token }).await.unwrap();
+    assert_eq!(resp.keys, vec![a.public]);
+    assert!(new.resume(ResumeReq { token }).await.is_err());
+    assert!(resp.resumption_token.0.expire_time < u64::MAX);
+
+    // the subscription moved to the new endpoint
+    let b_hdl =
+        InboundEndpoint::server_hdl(2, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&b_hdl, &b.private).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(*new.conn.connected.lock().unwrap(), vec![b.public]);
+    assert!(old.conn.connected.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn resumption_expires() {
+    let a = KeyPair::generate();
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            resumption_ttl: Duration::ZERO,
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let old =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let token = identify(&old, &a.private).await.resumption_token;
+    tokio::time::sleep(Duration::from_millis(2)).await;
+
+    let new =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    assert!(matches!(
+        new.resume(ResumeReq { token }).await,
+        Err(ResumeReqError::InvalidToken)
+    ));
+    assert_eq!(server_hdl.reap().await.resumptions, 1);
+}
+
+#[tokio::test]
+async fn presence() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+    identify(&b_hdl, &b.private).await;
+
+    let now = crate::utils::now();
+    let away = KeyTriad::presence(&a.private, PresenceStatus::Away, None, now);
+    let publish = |presence| PublishPresenceReq { presence };
+
+    // only the key itself can publish its presence
+    assert_eq!(
+        b_hdl.publish_presence(publish(away.clone())).await,
+        Err(PresenceReqError::InvalidPublicKey)
+    );
+    let long = KeyTriad::presence(
+        &a.private,
+        PresenceStatus::Online,
+        Some("a".repeat(MAX_PRESENCE_MESSAGE + 1)),
+        now,
+    );
+    assert!(matches!(
+        a_hdl.publish_presence(publish(long)).await,
+        Err(PresenceReqError::TooLarge { .. })
+    ));
+    let mut forged = away.clone();
+    forged.signed.status = PresenceStatus::DoNotDisturb;
+    assert_eq!(
+        a_hdl.publish_presence(publish(forged)).await,
+        Err(PresenceReqError::InvalidSignature)
+    );
+
+    a_hdl.publish_presence(publish(away.clone())).await.unwrap();
+    // an older update does not replace a later one
+    let stale = KeyTriad::presence(&a.private, PresenceStatus::Online, None, now - 1);
+    a_hdl.publish_presence(publish(stale)).await.unwrap();
+
+    let resp = b_hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public, b.public],
+            notify: false,
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.triads.len(), 2);
+    assert_eq!(resp.presence, vec![away]);
+
+    // the presence is dropped with the connection it was published through
+    let b_presence = KeyTriad::presence(&b.private, PresenceStatus::Online, None, now);
+    b_hdl.publish_presence(publish(b_presence)).await.unwrap();
+    b_hdl.disconnect().await;
+    assert_eq!(server_hdl.presence(&b.public).await, None);
+
+    a_hdl
+        .revoke(RevokeReq {
+            revocation: KeyTriad::revoke(&a.private, 0),
+        })
+        .await
+        .unwrap();
+    assert_eq!(server_hdl.presence(&a.public).await, None);
+}
+
+#[tokio::test]
+async fn unsubscribe_keys() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    hdl.keys_exists(KeysExistsReq {
+        keys: vec![a.public, b.public],
+        notify: true,
+    })
+    .await
+    .unwrap();
+    let resp = hdl
+        .respond(
+            UnsubscribeKeysReq {
+                keys: vec![a.public, a.public],
+            }
+            .into(),
+        )
+        .await;
+    assert_eq!(
+        resp,
+        RespMessage::UnsubscribeKeys(UnsubscribeKeysResp {
+            keys: vec![a.public]
+        })
+    );
+    assert!(!server_hdl.notifications.contains_async(&a.public).await);
+
+    // only the keys still subscribed to are notified
+    for key in [&a.private, &b.private] {
+        let other = InboundEndpoint::server_hdl(
+            1,
+            ENDPOINT_INFO,
+            server_hdl.clone(),
+            RecordConn::default(),
+        );
+        identify(&other, key).await;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(*hdl.conn.connected.lock().unwrap(), vec![b.public]);
+}
+
+#[tokio::test]
+async fn peer_demotion() {
+    let server_hdl = ServerHandle::new_hdl();
+    let mut events = server_hdl.subscribe();
+    let server_info = ServerInfo::new(arcstr::literal!("peer.example"));
+    let peer = InboundEndpoint::server_hdl(
+        0,
+        EndpointInfo {
+            server_info: Some(server_info.clone()),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        DummyNotify,
+    );
+    server_hdl.connect_server(peer.clone()).await.unwrap();
+
+    for _ in 0..server_hdl.config().peer_demote_errors {
+        server_hdl.record_peer(&peer, None).await;
+    }
+    assert!(server_hdl.forward_peers().await.is_empty());
+    assert_eq!(
+        events.try_recv().unwrap(),
+        NodeEvent::PeerDemoted {
+            id: 0,
+            server_info: server_info.clone()
+        }
+    );
+
+    server_hdl.record_peer(&peer, Some(12)).await;
+    assert_eq!(server_hdl.forward_peers().await, vec![peer]);
+    assert_eq!(server_hdl.peer_health(0).await.unwrap().rtt, 12);
+    assert_eq!(
+        events.try_recv().unwrap(),
+        NodeEvent::PeerRestored { id: 0, server_info }
+    );
+}
+
+#[tokio::test]
+async fn identify_recoverable() {
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triad = RecoverableTriad::gen_signed(
+        &key,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    hdl.identify_recoverable(triad).await.unwrap();
+
+    assert!(
+        server_hdl
+            .key_to_endpoint
+            .contains_async(&key.derive_public())
+            .await
+    );
+}
+
+#[tokio::test]
+async fn identify_batch() {
+    // verify the batches on dedicated workers
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            crypto_threads: Some(2),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+
+    let keys: Vec<_> = (0..16).map(|_| KeyPair::generate()).collect();
+    let mut triads: Vec<_> = keys
+        .iter()
+        .map(|pair| {
+            KeyTriad::gen_signed(
+                &pair.private,
+                &identify,
+                SignMessageType::Identify,
+                SignedFormat::Cbor,
+            )
+            .unwrap()
+        })
+        .collect();
+
+    // a single bad signature rejects the whole request
+    let mut bad = triads.clone();
+    bad[7].signature = bad[0].signature;
+    let resp = hdl
+        .identify_batch(IdentifyReq {
+            keys: bad,
+            compact: Vec::new(),
+            multi: Vec::new(),
+            delegated: Vec::new(),
+        })
+        .await;
+    assert!(matches!(
+        resp,
+        Err(IdentifyReqError::Verify(VerifyError::Mismatch))
+    ));
+    assert!(hdl.public_keys.read().await.is_empty());
+
+    let compact = RecoverableTriad::gen_signed(
+        &keys[0].private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    triads.remove(0);
+    hdl.identify_batch(IdentifyReq {
+        keys: triads,
+        compact: vec![compact],
+        multi: Vec::new(),
+        delegated: Vec::new(),
+    })
+    .await
+    .unwrap();
+
+    for pair in keys.iter() {
+        assert!(
+            server_hdl
+                .key_to_endpoint
+                .contains_async(&pair.public)
+                .await
+        );
+    }
+}
+
+#[tokio::test]
+async fn register_atomic() {
+    let server_hdl = Arc::new(ServerHandle::new());
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+
+    let pending = |pair: &KeyPair| {
+        let triad = KeyTriad::gen_signed(
+            &pair.private,
+            &identify,
+            SignMessageType::Identify,
+            SignedFormat::Cbor,
+        )
+        .unwrap();
+        Pending {
+            public_key: pair.public,
+            cached: triad.signed.clone().to_cached::<IdentifyData>().unwrap(),
+            triad,
+            signers: Vec::new(),
+        }
+    };
+    let (first, second) = (KeyPair::generate(), KeyPair::generate());
+
+    // the second key is claimed after the batch was checked, so the first key is not registered
+    // either
+    register(&hdl, vec![pending(&second)]).await.unwrap();
+    let resp = register(&hdl, vec![pending(&first), pending(&second)]).await;
+    assert!(matches!(resp, Err(IdentifyReqError::AlreadyIdentified)));
+    assert!(!hdl.identities.contains_async(&first.public).await);
+    assert!(
+        !server_hdl
+            .key_to_endpoint
+            .contains_async(&first.public)
+            .await
+    );
+    assert_eq!(*hdl.public_keys.read().await, vec![second.public]);
+
+    register(&hdl, vec![pending(&first)]).await.unwrap();
+    assert!(
+        server_hdl
+            .key_to_endpoint
+            .contains_async(&first.public)
+            .await
+    );
+}
+
+#[tokio::test]
+async fn log_proof() {
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            size_limits: SizeLimits {
+                max_proof: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    let first = KeyPair::generate();
+    identify(&hdl, &first.private).await;
+    let old = server_hdl.log_head().await;
+    assert_eq!(old.signed.size, 1);
+
+    let second = KeyPair::generate();
+    identify(&hdl, &second.private).await;
+
+    let resp = hdl.log_proof(GetLogProofReq { from: 1 }).await.unwrap();
+    assert_eq!(resp.head.public_key, server_hdl.public_key());
+    assert!(resp
+        .head
+        .public_key
+        .valid(&resp.head.signed, &resp.head.signature));
+    assert!(resp.proof.extends(&old.signed, &resp.head.signed));
+    assert_eq!(resp.proof.leaves.len(), 1);
+
+    // proofs cover at most as many entries as the limits allow, up to an older head
+    let resp = hdl.log_proof(GetLogProofReq { from: 0 }).await.unwrap();
+    assert_eq!(resp.proof.leaves.len(), 1);
+    assert_eq!(resp.head.signed.size, 1);
+    assert_eq!(resp.head.signed.head, old.signed.head);
+    assert!(resp.head.valid());
+
+    assert_eq!(
+        hdl.log_proof(GetLogProofReq { from: 3 }).await,
+        Err(LogProofReqError::InvalidRange)
+    );
+
+    // identifying as a key again is rejected before it is attested
+    let identify_data = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triad = KeyTriad::gen_signed(
+        &first.private,
+        &identify_data,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        hdl.identify(triad).await,
+        Err(IdentifyReqError::AlreadyIdentified)
+    ));
+    assert_eq!(server_hdl.log_head().await.signed.size, 2);
+}
+
+#[tokio::test]
+async fn wire_errors() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
+
+    let identify = match hdl.respond(PreIdentifyReq {}.into()).await {
+        RespMessage::PreIdentify(value) => value,
+        resp => panic!("unexpected response {resp:?}"),
+    };
+    let mut triad = KeyTriad::gen_signed(
+        &key,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    triad.signature.0[0] ^= 1;
+    let req = IdentifyReq {
+        keys: vec![triad],
+        compact: Vec::new(),
+        multi: Vec::new(),
+        delegated: Vec::new(),
+    };
+    assert!(matches!(
+        hdl.respond(req.into()).await,
+        RespMessage::Error(ErrorResp {
+            code: ErrorCode::INVALID_SIGNATURE,
+            ..
+        })
+    ));
+
+    // a revocation has no response message, and is acknowledged
+    let req = RevokeReq {
+        revocation: KeyTriad::revoke(&key, crate::utils::now()),
+    };
+    assert!(matches!(hdl.respond(req.into()).await, RespMessage::Ack(_)));
+    assert!(
+        server_hdl
+            .revoked
+            .contains_async(&key.derive_public())
+            .await
+    );
+
+    let req = CommunicationReq {
+        from: key.derive_public(),
+        to: key.derive_public(),
+        grant: None,
+        hops: 0,
+    };
+    assert!(matches!(
+        hdl.respond(req.into()).await,
+        RespMessage::Error(ErrorResp {
+            code: ErrorCode::UNSUPPORTED,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn ping() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl, DummyNotify);
+
+    let req = PingReq {
+        nonce: 7,
+        sent_time: crate::utils::now(),
+    };
+    let pong = match hdl.respond(req.into()).await {
+        RespMessage::Pong(value) => value,
+        resp => panic!("unexpected response {resp:?}"),
+    };
+    assert_eq!((pong.nonce, pong.sent_time), (req.nonce, req.sent_time));
+    assert!(pong.received_time >= req.sent_time);
+    assert_eq!(pong.rtt(req.sent_time + 20), Duration::from_millis(20));
+}
+
+#[tokio::test]
+async fn goodbye() {
+    let server_hdl = ServerHandle::new_hdl();
+    let mut events = server_hdl.subscribe();
+    let hdl = InboundEndpoint::server_hdl(3, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    let goodbye = Goodbye::new(GoodbyeCode::BANNED, "spam");
+    match hdl.respond(goodbye.clone().into()).await {
+        RespMessage::Goodbye(resp) => assert_eq!(resp.code, GoodbyeCode::NORMAL),
+        resp => panic!("unexpected response {resp:?}"),
+    }
+    assert_eq!(
+        events.try_recv().unwrap(),
+        NodeEvent::Goodbye {
+            id: 3,
+            server_info: None,
+            goodbye
+        }
+    );
+}
+
+#[tokio::test]
+async fn dismiss() {
+    let server_hdl = ServerHandle::<DummyNotify>::new_hdl();
+
+    let goodbye = server_hdl.dismiss(GoodbyeCode::RATE_LIMITED, "slow down");
+    let keep_down = goodbye
+        .keep_down
+        .expect("rate limited endpoints are kept down");
+    assert!(keep_down.valid());
+    assert_eq!(keep_down.public_key, server_hdl.public_key());
+    assert_eq!(keep_down.signed.min_delay, 30_000);
+
+    let goodbye = server_hdl.dismiss(GoodbyeCode::SHUTDOWN, "restarting");
+    assert_eq!(goodbye.keep_down, None);
+}
+
+#[tokio::test]
+async fn rate_limit() {
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            rate_limit: Some(RateLimit {
+                burst: 2,
+                per_second: 1,
+            }),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl, DummyNotify);
+    let ping = || PingReq {
+        nonce: 0,
+        sent_time: 0,
+    };
+
+    for _ in 0..2 {
+        assert!(matches!(
+            hdl.respond(ping().into()).await,
+            RespMessage::Pong(_)
+        ));
+    }
+    // the burst is spent, and the next request is allowed in about a second
+    match hdl.respond(ping().into()).await {
+        RespMessage::Error(resp) => {
+            assert_eq!(resp.code, ErrorCode::RATE_LIMITED);
+            assert!(resp.retry_after.is_some_and(|ms| ms > 0 && ms <= 1000));
+        }
+        resp => panic!("unexpected response {resp:?}"),
+    }
+}
+
+#[tokio::test]
+async fn request_limits() {
+    let once = Some(RateLimit {
+        burst: 1,
+        per_second: 1,
+    });
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            request_limits: RequestLimits {
+                pre_identify: Some(RateLimit {
+                    burst: 2,
+                    per_second: 1,
+                }),
+                identify: once,
+                keys_exists: once,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    identify(&hdl, &a.private).await;
+    // the second identify request is limited, even with fresh identify data
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triad = KeyTriad::gen_signed(
+        &b.private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        hdl.identify(triad).await,
+        Err(IdentifyReqError::RateLimited(_))
+    ));
+    // as is the third request for identify data, on the wire too
+    assert!(hdl.pre_identify(PreIdentifyReq {}).await.is_err());
+    match hdl.respond(PreIdentifyReq {}.into()).await {
+        RespMessage::Error(resp) => {
+            assert_eq!(resp.code, ErrorCode::RATE_LIMITED);
+            assert!(resp.retry_after.is_some_and(|ms| ms > 0 && ms <= 1000));
+        }
+        resp => panic!("unexpected response {resp:?}"),
+    }
+
+    let keys_exists = || KeysExistsReq {
+        keys: vec![a.public],
+        notify: false,
+    };
+    hdl.keys_exists(keys_exists()).await.unwrap();
+    assert!(matches!(
+        hdl.keys_exists(keys_exists()).await,
+        Err(KeysExistsReqError::RateLimited(_))
+    ));
+    // each endpoint has limits of its own
+    let other =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    other.keys_exists(keys_exists()).await.unwrap();
+}
+
+#[tokio::test]
+async fn max_identities() {
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            max_identities: Some(2),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let keys: Vec<_> = (0..4).map(|_| KeyPair::generate()).collect();
+    let hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&hdl, &keys[0].private).await;
+
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triads: Vec<_> = keys[1..]
+        .iter()
+        .map(|pair| {
+            KeyTriad::gen_signed(
+                &pair.private,
+                &identify,
+                SignMessageType::Identify,
+                SignedFormat::Cbor,
+            )
+            .unwrap()
+        })
+        .collect();
+
+    // a batch that would exceed the cap registers none of its keys
+    let resp = hdl
+        .identify_batch(IdentifyReq {
+            keys: triads[..2].to_vec(),
+            compact: Vec::new(),
+            multi: Vec::new(),
+            delegated: Vec::new(),
+        })
+        .await;
+    assert!(matches!(
+        resp,
+        Err(IdentifyReqError::TooManyIdentities { max: 2 })
+    ));
+    assert_eq!(hdl.identities.len(), 1);
+
+    hdl.identify(triads[0].clone()).await.unwrap();
+    assert!(matches!(
+        hdl.identify(triads[2].clone()).await,
+        Err(IdentifyReqError::TooManyIdentities { max: 2 })
+    ));
+    let req = IdentifyReq {
+        keys: vec![triads[1].clone()],
+        compact: Vec::new(),
+        multi: Vec::new(),
+        delegated: Vec::new(),
+    };
+    match hdl.respond(req.into()).await {
+        RespMessage::Error(resp) => assert_eq!(resp.code, ErrorCode::TOO_MANY_IDENTITIES),
+        resp => panic!("unexpected response {resp:?}"),
+    }
+    assert!(
+        !server_hdl
+            .key_to_endpoint
+            .contains_async(&keys[2].public)
+            .await
+    );
+}
+
+#[tokio::test]
+async fn public_counts() {
+    let privacy = CountPrivacy {
+        granularity: 10,
+        noise: 5,
+    };
+    for draw in 0..11 {
+        let blurred = privacy.blur(23, draw);
+        assert!((20..=30).contains(&blurred) && blurred.is_multiple_of(10));
+    }
+    assert_eq!(privacy.blur(0, 0), 0);
+    assert_eq!(
+        CountPrivacy {
+            granularity: 0,
+            noise: 0
+        }
+        .blur(23, 7),
+        23
+    );
+
+    let server_hdl = Arc::new(
+        ServerHandle::<DummyNotify>::with_config(NodeConfig {
+            public_counts: Some(CountPrivacy {
+                granularity: 100,
+                noise: 0,
+            }),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    identify(&hdl, &PrivateKey::new(PRIVATE_KEY).unwrap()).await;
+
+    // the operator sees the exact counts, anyone who connects sees them rounded
+    assert_eq!(server_hdl.load().identities, 1);
+    let load = server_hdl.node_info().load.unwrap();
+    assert_eq!((load.connections, load.identities), (0, 0));
+    // the same counts are always blurred the same way
+    assert_eq!(server_hdl.public_load(), server_hdl.public_load());
+}
+
+#[tokio::test]
+async fn load() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let other = InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    identify(&hdl, &PrivateKey::new(PRIVATE_KEY).unwrap()).await;
+    drop(other);
+
+    server_hdl.set_relay_saturation(150);
+    let load = Load {
+        relay_saturation: 100,
+        connections: 1,
+        identities: 1,
+    };
+    assert_eq!(server_hdl.node_info().load, Some(load));
+
+    // a server advertises its load when it connects
+    let peer = InboundEndpoint::server_hdl(
+        2,
+        EndpointInfo {
+            server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        DummyNotify,
+    );
+    server_hdl.connect_server(peer.clone()).await.unwrap();
+    let info = NodeInfo {
+        api_version: crate::CURRENT_VERSION,
+        load: Some(Load::default()),
+        ..Default::default()
+    };
+    peer.respond(info.into()).await;
+
+    let resp = hdl
+        .list_connected(ListConnectedServersReq { max: None })
+        .await
+        .unwrap();
+    assert_eq!(resp.servers[0].load, Some(Load::default()));
+    assert_eq!(resp.least_loaded(), Some(&resp.servers[0]));
+}
+
+#[tokio::test]
+async fn list_connected_servers() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let server_info = ServerInfo {
+        ports: vec![443, 8443],
+        transports: vec![Transport::Quic, Transport::Ws],
+        api_version: Some(crate::CURRENT_VERSION),
+        public_key: Some(PrivateKey::new(PRIVATE_KEY).unwrap().derive_public()),
+        ..ServerInfo::new(arcstr::literal!("peer.example"))
+    };
+    let peer = InboundEndpoint::server_hdl(
+        1,
+        EndpointInfo {
+            server_info: Some(server_info.clone()),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        DummyNotify,
+    );
+    server_hdl.connect_server(peer.clone()).await.unwrap();
+
+    let list = || hdl.list_connected(ListConnectedServersReq { max: None });
+    let resp = list().await.unwrap();
+    assert_eq!(resp.servers[0].server_info, server_info);
+    // the server is dialed at the first port it advertised
+    assert_eq!(
+        resp.servers[0].addr,
+        Some(SocketAddr::new(ENDPOINT_INFO.endpoint.ip(), 443))
+    );
+    assert!(!resp.servers[0].verified);
+
+    // until it identifies as the key it advertised, the key is only claimed
+    identify(&peer, &PrivateKey::new(PRIVATE_KEY).unwrap()).await;
+    let resp = list().await.unwrap();
+    assert!(resp.servers[0].verified);
+
+    // the server info is flattened into the listed server
+    let value = serde_json::to_value(&resp.servers[0]).unwrap();
+    assert_eq!(value["domain"], "peer.example");
+    assert_eq!(value["transports"], serde_json::json!(["QUIC", "WS"]));
+    let decoded: ConnectedServer = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(decoded, resp.servers[0]);
+    // listed servers serialized before the address was renamed still decode
+    let mut value = value;
+    let addr = value.as_object_mut().unwrap().remove("addr").unwrap();
+    value["ip"] = addr;
+    let decoded: ConnectedServer = serde_json::from_value(value).unwrap();
+    assert_eq!(decoded, resp.servers[0]);
+
+    // a server that advertised no port has no address it can be dialed at
+    let quiet = InboundEndpoint::server_hdl(
+        2,
+        EndpointInfo {
+            server_info: Some(ServerInfo::new(arcstr::literal!("quiet.example"))),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        DummyNotify,
+    );
+    server_hdl.connect_server(quiet).await.unwrap();
+    let resp = list().await.unwrap();
+    let quiet = resp
+        .servers
+        .iter()
+        .find(|server| server.server_info.domain == "quiet.example")
+        .unwrap();
+    assert_eq!(quiet.addr, None);
+
+    // the limit counts the servers listed
+    let resp = hdl
+        .list_connected(ListConnectedServersReq { max: Some(1) })
+        .await
+        .unwrap();
+    assert_eq!(resp.servers.len(), 1);
+}
+
+#[tokio::test]
+async fn list_disclosure() {
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            list_disclosure: DisclosurePolicy {
+                anonymous: Disclosure::DOMAIN_ONLY,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let anonymous = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identified = InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    identify(&identified, &PrivateKey::new(PRIVATE_KEY).unwrap()).await;
+    let server_info = ServerInfo {
+        ports: vec![443],
+        ..ServerInfo::new(arcstr::literal!("peer.example"))
+    };
+    let peer = InboundEndpoint::server_hdl(
+        2,
+        EndpointInfo {
+            server_info: Some(server_info.clone()),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        DummyNotify,
+    );
+    server_hdl.connect_server(peer).await.unwrap();
+
+    let req = || ListConnectedServersReq { max: None };
+    let resp = identified.list_connected(req()).await.unwrap();
+    let server = &resp.servers[0];
+    assert_eq!(
+        server.addr,
+        Some(SocketAddr::new(ENDPOINT_INFO.endpoint.ip(), 443))
+    );
+    assert_eq!(server.server_info, server_info);
+
+    // only the domain is revealed to endpoints that did not identify
+    let resp = anonymous.list_connected(req()).await.unwrap();
+    let server = &resp.servers[0];
+    assert_eq!(server.addr, None);
+    assert_eq!(
+        server.server_info,
+        ServerInfo::new(arcstr::literal!("peer.example"))
+    );
+}
+
+#[tokio::test]
+async fn negotiate_features() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    // features the node does not know are ignored
+    let info = NodeInfo {
+        api_version: crate::CURRENT_VERSION,
+        features: Features::from_iter([Features::SCHNORR, "future-feature"]),
+        ..Default::default()
+    };
+    let resp = match hdl.respond(info.clone().into()).await {
+        RespMessage::Connect(resp) => resp,
+        resp => panic!("unexpected response {resp:?}"),
+    };
+    assert!(resp.compatible);
+    assert_eq!(resp.info, server_hdl.node_info());
+    assert_eq!(resp.features, Features::from_iter([Features::SCHNORR]));
+
+    // and are kept when the info is decoded
+    let decoded: NodeInfo = serde_json::from_value(serde_json::to_value(&info).unwrap()).unwrap();
+    assert!(decoded.features.contains("future-feature"));
+
+    // but a peer cannot advertise an unbounded set
+    let names: Vec<_> = (0..=Features::MAX_FEATURES)
+        .map(|i| format!("feature-{i}"))
+        .collect();
+    let info = serde_json::json!({ "apiVersion": 0, "features": names });
+    assert!(serde_json::from_value::<NodeInfo>(info).is_err());
+    let info = serde_json::json!({ "apiVersion": 0, "features": ["a".repeat(65)] });
+    assert!(serde_json::from_value::<NodeInfo>(info).is_err());
+}
+
+#[tokio::test]
+async fn keys_root() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    identify(&hdl, &a.private).await;
+
+    let resp = hdl
+        .keys_root(KeysRootReq {
+            keys: vec![a.public, b.public],
+        })
+        .await
+        .unwrap();
+    assert!(resp.root.valid());
+    assert_eq!(resp.root.public_key, server_hdl.public_key());
+    assert_eq!(resp.root.signed.size, 1);
+    assert_eq!(
+        resp.proofs[0].verify(&a.public, &resp.root.signed),
+        Some(true)
+    );
+    assert_eq!(
+        resp.proofs[1].verify(&b.public, &resp.root.signed),
+        Some(false)
+    );
+
+    // the set is rebuilt once another key identifies
+    identify(&hdl, &b.private).await;
+    let resp = hdl
+        .keys_root(KeysRootReq {
+            keys: vec![b.public],
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.root.signed.size, 2);
+    assert_eq!(
+        resp.proofs[0].verify(&b.public, &resp.root.signed),
+        Some(true)
+    );
+}
+
+/// Forwards the cross-signing requests of a server to the endpoint another server has for it.
+#[derive(Debug)]
+struct Witness(InboundHdl<DummyNotify>);
+
+impl Service<CrossSignReq> for Witness {
+    type Response = CrossSignResp;
+    type Error = CrossSignReqError;
+
+    async fn call(&self, req: CrossSignReq) -> Result<Self::Response, Self::Error> {
+        self.0.call(req).await
+    }
+}
+
+/// Forwards the requests of a server to the endpoint another server has for it, once linked.
+#[derive(Debug, Default)]
+struct Link(std::sync::OnceLock<InboundHdl<Link>>);
+
+impl Link {
+    fn peer(&self) -> Result<&InboundHdl<Link>, ServerHdlDroppedError> {
+        self.0.get().ok_or(ServerHdlDroppedError)
+    }
+}
+
+impl Notify for Link {
+    type Err = Infallible;
+
+    async fn notify_connected(&self, _triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_introduced(&self, _intro: &Introduction) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_revoked(&self, _revocation: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_mail(&self, _mail: &Mail) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+impl Service<ReconcileReq> for Link {
+    type Response = ReconcileResp;
+    type Error = ReconcileReqError;
+
+    async fn call(&self, req: ReconcileReq) -> Result<Self::Response, Self::Error> {
+        Box::pin(self.peer()?.call(req)).await
+    }
+}
+
+impl Service<KeysExistsRReq> for Link {
+    type Response = KeysExistsRResp;
+    type Error = KeysExistsRReqError;
+
+    async fn call(&self, req: KeysExistsRReq) -> Result<Self::Response, Self::Error> {
+        Box::pin((**self.peer()?).call(req)).await
+    }
+}
+
+impl Service<DisconnectNoticeReq> for Link {
+    type Response = ();
+    type Error = DisconnectNoticeReqError;
+
+    async fn call(&self, req: DisconnectNoticeReq) -> Result<Self::Response, Self::Error> {
+        match self.0.get() {
+            Some(peer) => peer.disconnect_notice(req).await,
+            // like a server that stopped answering
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[tokio::test]
+async fn reconcile_peers() {
+    let config = NodeConfig {
+        remote_key_ttl: Some(Duration::from_secs(60)),
+        reconcile_leaf_size: 2,
+        ..Default::default()
+    };
+    let peer_info = EndpointInfo {
+        server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+        ..ENDPOINT_INFO
+    };
+
+    let b_server = Arc::new(ServerHandle::with_config(config.clone()).unwrap());
+    let a_on_b =
+        InboundEndpoint::server_hdl(0, peer_info.clone(), b_server.clone(), Link::default());
+    b_server.connect_server(a_on_b.clone()).await.unwrap();
+    let keys: Vec<_> = (0..8).map(|_| KeyPair::generate()).collect();
+    for (i, key) in keys[..6].iter().enumerate() {
+        let hdl = InboundEndpoint::server_hdl(
+            i as u64 + 1,
+            ENDPOINT_INFO,
+            b_server.clone(),
+            Link::default(),
+        );
+        identify(&hdl, &key.private).await;
+    }
+
+    let a_server = Arc::new(ServerHandle::with_config(config).unwrap());
+    let b_on_a =
+        InboundEndpoint::server_hdl(0, peer_info.clone(), a_server.clone(), Link::default());
+    a_server.connect_server(b_on_a.clone()).await.unwrap();
+    a_on_b.conn.0.set(b_on_a.clone()).unwrap();
+    b_on_a.conn.0.set(a_on_b.clone()).unwrap();
+    // A shares some of the keys of B, has one of its own, and was told of one it cannot prove
+    for key in &keys[..3] {
+        a_server.learn_remote(key.public, &b_on_a).await;
+    }
+    let hdl = InboundEndpoint::server_hdl(1, ENDPOINT_INFO, a_server.clone(), Link::default());
+    identify(&hdl, &keys[7].private).await;
+    let c_on_a = InboundEndpoint::server_hdl(2, peer_info, a_server.clone(), Link::default());
+    a_server.learn_remote(keys[6].public, &c_on_a).await;
+
+    assert_eq!(a_server.reconcile_peers().await, 3);
+    for key in &keys[3..6] {
+        assert_eq!(
+            a_server.remote_route(&key.public, None).await.unwrap().0,
+            b_on_a
+        );
+    }
+    assert_eq!(
+        b_server
+            .remote_route(&keys[7].public, None)
+            .await
+            .unwrap()
+            .0,
+        a_on_b
+    );
+    // a key the peer did not prove identified is not learned
+    assert!(b_server.remote_route(&keys[6].public, None).await.is_none());
+    assert_eq!(b_server.known_keys().await.len(), 7);
+
+    // only servers reconcile
+    let client = InboundEndpoint::server_hdl(3, ENDPOINT_INFO, b_server.clone(), Link::default());
+    let round = b_server.known_keys().await.start_reconcile();
+    assert_eq!(
+        client.call(ReconcileReq { round }).await,
+        Err(ReconcileReqError::NotPeer)
+    );
+}
+
+#[tokio::test]
+async fn propagate_disconnect() {
+    let config = NodeConfig {
+        remote_key_ttl: Some(Duration::from_secs(60)),
+        push_timeout: Duration::from_millis(50),
+        ..Default::default()
+    };
+    let peer_info = EndpointInfo {
+        server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+        ..ENDPOINT_INFO
+    };
+    let key = KeyPair::generate();
+
+    let b_server = Arc::new(ServerHandle::with_config(config.clone()).unwrap());
+    let a_on_b =
+        InboundEndpoint::server_hdl(0, peer_info.clone(), b_server.clone(), Link::default());
+    b_server.connect_server(a_on_b.clone()).await.unwrap();
+    // C never answers
+    let c_on_b =
+        InboundEndpoint::server_hdl(1, peer_info.clone(), b_server.clone(), Link::default());
+    b_server.connect_server(c_on_b).await.unwrap();
+    let hdl = InboundEndpoint::server_hdl(2, ENDPOINT_INFO, b_server.clone(), Link::default());
+    identify(&hdl, &key.private).await;
+
+    let a_server = Arc::new(ServerHandle::with_config(config).unwrap());
+    let b_on_a = InboundEndpoint::server_hdl(0, peer_info, a_server.clone(), Link::default());
+    a_server.connect_server(b_on_a.clone()).await.unwrap();
+    a_on_b.conn.0.set(b_on_a.clone()).unwrap();
+    a_server.learn_remote(key.public, &b_on_a).await;
+
+    assert!(hdl.disconnect().await);
+    assert_eq!(b_server.propagate_disconnect(&[key.public]).await, 1);
+    assert!(a_server.remote_route(&key.public, None).await.is_none());
+
+    // a notice older than the route does not drop it
+    a_server.learn_remote(key.public, &b_on_a).await;
+    b_on_a
+        .disconnect_notice(DisconnectNoticeReq {
+            keys: vec![key.public],
+            time: 0,
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        a_server.remote_route(&key.public, None).await.unwrap().0,
+        b_on_a
+    );
+
+    // only servers send notices
+    let client = InboundEndpoint::server_hdl(1, ENDPOINT_INFO, a_server.clone(), Link::default());
+    assert!(matches!(
+        client
+            .disconnect_notice(DisconnectNoticeReq {
+                keys: vec![key.public],
+                time: crate::utils::now(),
+            })
+            .await,
+        Err(DisconnectNoticeReqError::NotPeer)
+    ));
+    assert_eq!(
+        a_server.remote_route(&key.public, None).await.unwrap().0,
+        b_on_a
+    );
+}
+
+#[tokio::test]
+async fn cross_sign() {
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
+    let server_info = ServerInfo::new(arcstr::literal!("peer.example"));
+    let peer_info = EndpointInfo {
+        server_info: Some(server_info.clone()),
+        ..ENDPOINT_INFO
+    };
+
+    let b_server = ServerHandle::new_hdl();
+    let mut events = b_server.subscribe();
+    let a_on_b = InboundEndpoint::server_hdl(0, peer_info.clone(), b_server.clone(), DummyNotify);
+
+    let a_server = Arc::new(ServerHandle::with_key(Default::default(), key.clone()).unwrap());
+    let b_on_a =
+        InboundEndpoint::server_hdl(0, peer_info, a_server.clone(), Witness(a_on_b.clone()));
+    a_server.connect_server(b_on_a).await.unwrap();
+
+    let attest = |i: u64| LogEntry::Identified {
+        public_key: key.derive_public(),
+        time: i,
+    };
+    a_server.attest(attest(0)).await;
+    assert_eq!(a_server.cross_sign_peers().await, 1);
+
+    // the witness asks for a proof from the head it cosigned before
+    a_server.attest(attest(1)).await;
+    a_server.attest(attest(2)).await;
+    assert_eq!(a_server.cross_sign_peers().await, 1);
+
+    let witnesses = a_server.cosignatures().await;
+    assert_eq!(witnesses.len(), 1);
+    assert_eq!(witnesses[0].public_key, b_server.public_key());
+    assert_eq!(witnesses[0].signed.signed.size, 3);
+    assert!(witnesses[0].valid_cosigned());
+
+    // a forked log signed by the same key is rejected
+    let mut forked = TransparencyLog::new();
+    for i in 0..4 {
+        forked.append(attest(i + 10).leaf().unwrap());
+    }
+    let head = forked.sign(&key, 0);
+    let resp = a_on_b
+        .cross_sign(CrossSignReq {
+            head,
+            proof: forked.proof(0, 4).unwrap(),
+        })
+        .await;
+    assert_eq!(resp, Err(CrossSignReqError::Inconsistent));
+    assert_eq!(
+        events.try_recv().unwrap(),
+        NodeEvent::LogEquivocation {
+            id: 0,
+            server_info,
+            public_key: key.derive_public(),
+        }
+    );
+}
+
+#[tokio::test]
+async fn identify_sha256() {
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let hash = Features::from_iter([Features::SHA256]).hash_algorithm();
+    let triad = KeyTriad::gen_signed_with(
+        &key,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+        hash,
+    )
+    .unwrap();
+    assert_eq!(triad.signed.hash_algorithm(), HashAlgorithm::Sha256);
+    assert!(triad.public_key.valid(&triad.signed, &triad.signature));
+
+    hdl.identify(triad).await.unwrap();
+}
+
+#[tokio::test]
+async fn identify_schnorr() {
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            crypto_threads: Some(1),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    // the client signs with the scheme negotiated with the node
+    let client = NodeInfo {
+        api_version: CURRENT_VERSION,
+        features: Features::from_iter([Features::SCHNORR]),
+        load: None,
+    };
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+
+    // the payload cannot pick a scheme the endpoint did not negotiate
+    let schnorr = KeyTriad::gen_signed_schnorr(
+        &b.private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    let resp = hdl.identify(schnorr).await;
+    assert!(matches!(
+        resp,
+        Err(IdentifyReqError::SchemeMismatch {
+            expected: SignatureScheme::Ecdsa,
+            found: SignatureScheme::Schnorr,
+        })
+    ));
+    assert_eq!(
+        ErrorResp::from(resp.unwrap_err()).code,
+        ErrorCode::INVALID_SIGNATURE
+    );
+
+    let Ok(resp) = hdl.call(client).await;
+    let features = resp.features;
+    assert_eq!(features.signature_scheme(), SignatureScheme::Schnorr);
+
+    // and once it negotiated schnorr, ECDSA signatures are rejected
+    let ecdsa = KeyTriad::gen_signed(
+        &b.private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        hdl.identify_batch(IdentifyReq {
+            keys: vec![ecdsa],
+            compact: Vec::new(),
+            multi: Vec::new(),
+            delegated: Vec::new(),
+        })
+        .await,
+        Err(IdentifyReqError::SchemeMismatch {
+            expected: SignatureScheme::Schnorr,
+            found: SignatureScheme::Ecdsa,
+        })
+    ));
+
+    let sign = |key: &PrivateKey| {
+        KeyTriad::gen_signed_negotiated(
+            key,
+            &identify,
+            SignMessageType::Identify,
+            SignedFormat::Cbor,
+            &features,
+        )
+        .unwrap()
+    };
+
+    let triad = sign(&a.private);
+    let mut forged = triad.clone();
+    forged.signature = a.private.sign(&triad.signed);
+    assert!(matches!(
+        hdl.identify(forged).await,
+        Err(IdentifyReqError::Verify(_))
+    ));
+    hdl.identify(triad.clone()).await.unwrap();
+
+    hdl.identify_batch(IdentifyReq {
+        keys: vec![sign(&b.private)],
+        compact: Vec::new(),
+        multi: Vec::new(),
+        delegated: Vec::new(),
+    })
+    .await
+    .unwrap();
+
+    // endpoints that look the key up verify the triad like any other
+    let resp = hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.private.derive_schnorr_public()],
+            notify: false,
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.triads, vec![triad]);
+    assert!(resp.triads[0].valid());
+}
+
+#[tokio::test]
+async fn identify_threshold() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+
+    // three parties generate a group key, any two of which can sign
+    let (dkgs, commitments): (Vec<_>, Vec<_>) = (1..=3)
+        .map(|id| Dkg::new(id, 2, 3, &mut thread_rng()).unwrap())
+        .unzip();
+    let shares: Vec<_> = dkgs
+        .iter()
+        .flat_map(|dkg| (1..=3).map(|to| dkg.share(to).unwrap()))
+        .collect();
+    let keys: Vec<_> = dkgs
+        .into_iter()
+        .map(|dkg| dkg.finish(&commitments, &shares).unwrap())
+        .collect();
+    let group_key = keys[0].group_key();
+
+    let client = NodeInfo {
+        api_version: CURRENT_VERSION,
+        features: Features::from_iter([Features::SCHNORR]),
+        load: None,
+    };
+    let Ok(_) = hdl.call(client).await;
+
+    let signed =
+        SignedData::for_schnorr(&identify, SignMessageType::Identify, SignedFormat::Cbor).unwrap();
+    let signers = [&keys[0], &keys[2]];
+    let (nonces, commitments): (Vec<_>, Vec<_>) = signers
+        .iter()
+        .map(|key| key.commit(&mut thread_rng()))
+        .unzip();
+    let shares: Vec<_> = signers
+        .iter()
+        .zip(nonces)
+        .map(|(key, nonces)| key.sign(nonces, &signed, &commitments).unwrap())
+        .collect();
+    let signature = aggregate(&group_key, &signed, &commitments, &shares).unwrap();
+
+    let triad = KeyTriad {
+        public_key: group_key,
+        signature: signature.into(),
+        signed,
+    };
+    assert!(triad.public_key.valid(&triad.signed, &triad.signature));
+
+    // the group key cannot be claimed under another prefix with the same signature
+    let mut rewritten = triad.clone();
+    rewritten.public_key.0[0] = 0x03;
+    assert!(matches!(
+        hdl.identify(rewritten).await,
+        Err(IdentifyReqError::Verify(VerifyError::InvalidPublicKey))
+    ));
+    assert!(hdl.public_keys.read().await.is_empty());
+
+    hdl.identify(triad).await.unwrap();
+    assert!(server_hdl.key_to_endpoint.contains_async(&group_key).await);
+}
+
+#[tokio::test]
+async fn identify_extensions() {
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            identify_extensions: IdentifyExtensions {
+                tos_hash: Some(crate::crypto::hash(b"terms of service")),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    assert_eq!(identify.extensions, server_hdl.config().identify_extensions);
+
+    // a signer that strips the extensions did not accept the policy of the node
+    let stripped = IdentifyData {
+        extensions: Default::default(),
+        ..identify
+    };
+    let triad = KeyTriad::gen_signed(
+        &key,
+        &stripped,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        hdl.identify(triad).await,
+        Err(IdentifyReqError::ExtensionsMismatch)
+    ));
+
+    let triad = KeyTriad::gen_signed(
+        &key,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    hdl.identify(triad).await.unwrap();
+}
+
+#[tokio::test]
+async fn identify_multi() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+
+    // any two of three devices may claim the identity of the organization
+    let org = KeyPair::generate();
+    let devices: Vec<_> = (0..3).map(|_| KeyPair::generate()).collect();
+    let group = KeyTriad::group(
+        &org.private,
+        devices.iter().map(|pair| pair.public).collect(),
+        2,
+        identify.expire_time,
+    )
+    .unwrap();
+    let sign = |keys: &[KeyPair]| {
+        let keys: Vec<_> = keys.iter().map(|pair| pair.private.clone()).collect();
+        MultiKeyTriad::gen_signed(
+            group.clone(),
+            &keys,
+            &identify,
+            SignMessageType::Identify,
+            SignedFormat::Cbor,
+        )
+        .unwrap()
+    };
+
+    // an expired group cannot claim the identity, and neither can one that never expires
+    let keys: Vec<_> = devices.iter().map(|pair| pair.public).collect();
+    let expired = MultiKeyTriad {
+        group: KeyTriad::group(&org.private, keys.clone(), 2, 0).unwrap(),
+        ..sign(&devices[1..])
+    };
+    let resp = hdl.identify_multi(expired).await;
+    assert!(matches!(
+        resp,
+        Err(IdentifyReqError::MultiKey(MultiKeyError::Expired))
+    ));
+    assert_eq!(ErrorResp::from(resp.unwrap_err()).code, ErrorCode::EXPIRED);
+    let forever = MultiKeyTriad {
+        group: KeyTriad::group(&org.private, keys, 2, u64::MAX).unwrap(),
+        ..sign(&devices[1..])
+    };
+    assert!(matches!(
+        hdl.identify_multi(forever).await,
+        Err(IdentifyReqError::Validity(ValidityError::TooLong { .. }))
+    ));
+
+    // k - 1 devices cannot
+    assert!(matches!(
+        hdl.identify_multi(sign(&devices[..1])).await,
+        Err(IdentifyReqError::MultiKey(MultiKeyError::BelowThreshold {
+            threshold: 2,
+            signers: 1
+        }))
+    ));
+
+    // a single bad signature rejects the claim
+    let mut bad = sign(&devices[..2]);
+    bad.signers[1].signature = bad.signers[0].signature;
+    assert!(matches!(
+        hdl.identify_multi(bad).await,
+        Err(IdentifyReqError::Verify(VerifyError::Mismatch))
+    ));
+    assert!(hdl.public_keys.read().await.is_empty());
+
+    // k devices can, and only the identity of the group is registered
+    hdl.identify_multi(sign(&devices[1..])).await.unwrap();
+    assert_eq!(*hdl.public_keys.read().await, vec![org.public]);
+    assert!(server_hdl.key_to_endpoint.contains_async(&org.public).await);
+    for device in devices.iter() {
+        assert!(!hdl.identities.contains_async(&device.public).await);
+    }
+
+    // the identity can only be claimed once
+    assert!(matches!(
+        hdl.identify_multi(sign(&devices)).await,
+        Err(IdentifyReqError::AlreadyIdentified)
+    ));
+}
+
+#[tokio::test]
+async fn identify_delegated() {
+    let root = PrivateKey::new(PRIVATE_KEY).unwrap();
+    let device = KeyPair::generate();
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+
+    let triad = KeyTriad::gen_signed(
+        &device.private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    let expired = KeyTriad::delegate(&root, device.public, DelegationScope::IDENTIFY, 0).unwrap();
+    assert!(matches!(
+        hdl.identify_delegated(DelegatedTriad {
+            triad: triad.clone(),
+            chain: vec![expired],
+        })
+        .await,
+        Err(IdentifyReqError::Delegation(DelegationError::Expired))
+    ));
+
+    // a delegation that never expires is implausible
+    let forever =
+        KeyTriad::delegate(&root, device.public, DelegationScope::IDENTIFY, u64::MAX).unwrap();
+    assert!(matches!(
+        hdl.identify_delegated(DelegatedTriad {
+            triad: triad.clone(),
+            chain: vec![forever],
+        })
+        .await,
+        Err(IdentifyReqError::Validity(ValidityError::TooLong { .. }))
+    ));
+
+    let expire_time = crate::utils::now() + 60 * 60 * 1000;
+    let delegation =
+        KeyTriad::delegate(&root, device.public, DelegationScope::IDENTIFY, expire_time).unwrap();
+
+    // a signature made for another purpose does not delegate
+    let replayed = KeyTriad::gen_signed(
+        &root,
+        &Delegation::decode(&delegation.signed).unwrap(),
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        hdl.identify_delegated(DelegatedTriad {
+            triad: triad.clone(),
+            chain: vec![replayed],
+        })
+        .await,
+        Err(IdentifyReqError::Delegation(
+            DelegationError::WrongMessageType(SignMessageType::Identify)
+        ))
+    ));
+
+    hdl.identify_delegated(DelegatedTriad {
+        triad,
+        chain: vec![delegation],
+    })
+    .await
+    .unwrap();
+
+    // the device identified as the root key
+    assert!(
+        server_hdl
+            .key_to_endpoint
+            .contains_async(&root.derive_public())
+            .await
+    );
+    assert!(
+        !server_hdl
+            .key_to_endpoint
+            .contains_async(&device.public)
+            .await
+    );
+}
+
+#[tokio::test]
+async fn revoked_delegate() {
+    let (root, device, app) = (
+        KeyPair::generate(),
+        KeyPair::generate(),
+        KeyPair::generate(),
+    );
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+
+    let expire_time = crate::utils::now() + 60 * 60 * 1000;
+    let scope = DelegationScope::IDENTIFY.union(DelegationScope::DELEGATE);
+    let delegated = DelegatedTriad {
+        triad: KeyTriad::gen_signed(
+            &app.private,
+            &identify,
+            SignMessageType::Identify,
+            SignedFormat::Cbor,
+        )
+        .unwrap(),
+        chain: vec![
+            KeyTriad::delegate(&root.private, device.public, scope, expire_time).unwrap(),
+            KeyTriad::delegate(
+                &device.private,
+                app.public,
+                DelegationScope::IDENTIFY,
+                expire_time,
+            )
+            .unwrap(),
+        ],
+    };
+
+    // the device in the middle of the chain was revoked, so it no longer vouches for the app
+    hdl.revoke(RevokeReq {
+        revocation: KeyTriad::revoke(&device.private, 0),
+    })
+    .await
+    .unwrap();
+    assert!(matches!(
+        hdl.identify_delegated(delegated).await,
+        Err(IdentifyReqError::Revoked)
+    ));
+    assert!(
+        !server_hdl
+            .key_to_endpoint
+            .contains_async(&root.public)
+            .await
+    );
+}
+
+#[tokio::test]
+async fn revoke() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    identify(&a_hdl, &a.private).await;
+
+    // only the revoked key can sign its revocation
+    let mut forged = KeyTriad::revoke(&b.private, 0);
+    forged.public_key = a.public;
+    assert!(b_hdl
+        .revoke(RevokeReq { revocation: forged })
+        .await
+        .is_err());
+
+    // a revocation signed in the future is rejected
+    let future = crate::utils::now() + 60 * 60 * 1000;
+    assert!(matches!(
+        b_hdl
+            .revoke(RevokeReq {
+                revocation: KeyTriad::revoke(&a.private, future),
+            })
+            .await,
+        Err(RevokeReqError::Validity(ValidityError::FutureDated { .. }))
+    ));
+
+    b_hdl
+        .revoke(RevokeReq {
+            revocation: KeyTriad::revoke(&a.private, 0),
+        })
+        .await
+        .unwrap();
+    assert!(!server_hdl.key_to_endpoint.contains_async(&a.public).await);
+    assert!(!a_hdl.identities.contains_async(&a.public).await);
+
+    // the key can never identify again
+    let identify = a_hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triad = KeyTriad::gen_signed(
+        &a.private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        a_hdl.identify(triad).await,
+        Err(IdentifyReqError::Revoked)
+    ));
+}
+
+#[tokio::test]
+async fn key_changes() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let store = Arc::new(MemoryGossipStore::default());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            gossip_history: 2,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_gossip_store(store.clone())
+        .unwrap(),
+    );
+    let hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let start = server_hdl.gossip_position();
+    assert_eq!(start.seq, 0);
+
+    identify(&hdl, &a.private).await;
+    identify(&hdl, &b.private).await;
+    let resp = hdl.call(KeyChangesReq { since: start }).await.unwrap();
+    let changes: Vec<_> = resp
+        .changes
+        .unwrap()
+        .into_iter()
+        .map(|change| (change.key, change.kind))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (a.public, KeyChangeKind::Joined),
+            (b.public, KeyChangeKind::Joined)
+        ]
+    );
+    assert_eq!(resp.position.seq, 2);
+
+    // a peer that is up to date receives nothing
+    let resp = hdl
+        .call(KeyChangesReq {
+            since: resp.position,
+        })
+        .await;
+    assert_eq!(resp.unwrap().changes, Some(Vec::new()));
+
+    hdl.revoke(RevokeReq {
+        revocation: KeyTriad::revoke(&a.private, 0),
+    })
+    .await
+    .unwrap();
+    // the first change is no longer held
+    let resp = hdl.call(KeyChangesReq { since: start }).await.unwrap();
+    assert_eq!(resp.changes, None);
+    // the position is saved in the background
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(store.load().unwrap(), Some(resp.position));
+
+    // the sequence continues after a restart, but the changes before it are lost
+    let restarted = ServerHandle::<RecordConn>::new()
+        .with_gossip_store(store.clone())
+        .unwrap();
+    let position = restarted.gossip_position();
+    assert_eq!(position.epoch, resp.position.epoch);
+    assert_eq!(position.seq, resp.position.seq + 1);
+    assert_eq!(restarted.key_changes(resp.position).changes, None);
+    assert_eq!(restarted.key_changes(position).changes, Some(Vec::new()));
+
+    // positions of another epoch are never compared
+    let other = ServerHandle::<RecordConn>::new();
+    assert_eq!(other.key_changes(position).changes, None);
+}
+
+#[tokio::test]
+async fn key_changes_paged() {
+    let keys: Vec<_> = (0..3).map(|_| KeyPair::generate()).collect();
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            size_limits: SizeLimits {
+                max_entries: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let start = server_hdl.gossip_position();
+    for key in &keys {
+        identify(&hdl, &key.private).await;
+    }
+
+    // the changes that do not fit are fetched from the position of the last one returned
+    let resp = server_hdl.key_changes(start);
+    assert_eq!(resp.changes.unwrap().len(), 2);
+    assert_eq!(resp.position.seq, 2);
+    let resp = server_hdl.key_changes(resp.position);
+    let changes = resp.changes.unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].key, keys[2].public);
+    assert_eq!(resp.position, server_hdl.gossip_position());
+}
+
+#[tokio::test]
+async fn wrong_message_type() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    // a revocation cannot identify
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    assert!(matches!(
+        hdl.identify(KeyTriad::revoke(&a.private, 0)).await,
+        Err(IdentifyReqError::WrongMessageType(WrongMessageTypeError {
+            expected: SignMessageType::Identify,
+            found: SignMessageType::Revoke,
+        }))
+    ));
+
+    // identify data cannot revoke, nor be held as mail
+    let triad = KeyTriad::gen_signed(
+        &a.private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    let err = hdl
+        .revoke(RevokeReq {
+            revocation: triad.clone(),
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RevokeReqError::WrongMessageType(_)));
+    assert_eq!(ErrorResp::from(err).code, ErrorCode::WRONG_MESSAGE_TYPE);
+    assert!(matches!(
+        server_hdl.deposit(b.public, triad).await,
+        Err(MailboxError::WrongMessageType(_))
+    ));
+
+    // messages of applications can
+    let message = KeyTriad::gen_signed(
+        &a.private,
+        "hello",
+        SignMessageType::AppMessage,
+        SignedFormat::Json,
+    )
+    .unwrap();
+    assert!(server_hdl.deposit(b.public, message).await.is_ok());
+}
+
+#[tokio::test]
+async fn revoke_notifies() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    identify(&b_hdl, &b.private).await;
+    b_hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public],
+            notify: true,
+        })
+        .await
+        .unwrap();
+
+    b_hdl
+        .revoke(RevokeReq {
+            revocation: KeyTriad::revoke(&a.private, 0),
+        })
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(*b_hdl.conn.revoked.lock().unwrap(), vec![a.public]);
+}
+
+/// A connection that records the revocations pushed to it, and hands the revocations it is sent to
+/// an endpoint of another server, if any.
+#[derive(Default)]
+struct RevokeRelay {
+    peer: Option<InboundHdl<RecordConn>>,
+    revoked: Mutex<Vec<PublicKey>>,
+}
+
+impl Notify for RevokeRelay {
+    type Err = Infallible;
+
+    async fn notify_connected(&self, _triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_introduced(&self, _intro: &Introduction) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_revoked(&self, revocation: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        self.revoked.lock().unwrap().push(revocation.public_key);
+        Ok(())
+    }
+    async fn notify_mail(&self, _mail: &Mail) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+impl Service<RevokeReq> for RevokeRelay {
+    type Response = ();
+    type Error = RevokeReqError;
+
+    async fn call(&self, req: RevokeReq) -> Result<Self::Response, Self::Error> {
+        match &self.peer {
+            Some(peer) => peer.revoke(req).await,
+            None => Err(RevokeReqError::InvalidRevocation),
+        }
+    }
+}
+
+#[tokio::test]
+async fn propagate_revocation() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let peer_info = EndpointInfo {
+        server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+        ..ENDPOINT_INFO
+    };
+
+    // b waits for a on both servers
+    let a_server = Arc::new(ServerHandle::new());
+    let b_server = ServerHandle::new_hdl();
+    let b_on_a =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, a_server.clone(), RevokeRelay::default());
+    identify(&b_on_a, &b.private).await;
+    b_on_a
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public],
+            notify: true,
+        })
+        .await
+        .unwrap();
+    let b_on_b =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, b_server.clone(), RecordConn::default());
+    identify(&b_on_b, &b.private).await;
+    b_on_b
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public],
+            notify: true,
+        })
+        .await
+        .unwrap();
+
+    let a_on_b = InboundEndpoint::server_hdl(
+        1,
+        peer_info.clone(),
+        b_server.clone(),
+        RecordConn::default(),
+    );
+    let b_on_a_peer = InboundEndpoint::server_hdl(
+        1,
+        peer_info,
+        a_server.clone(),
+        RevokeRelay {
+            peer: Some(a_on_b),
+            ..Default::default()
+        },
+    );
+    assert!(a_server.connect_server(b_on_a_peer).await.is_ok());
+
+    let mut events = a_server.subscribe();
+    b_on_a
+        .revoke(RevokeReq {
+            revocation: KeyTriad::revoke(&a.private, 0),
+        })
+        .await
+        .unwrap();
+    let revocation = match events.recv().await.unwrap() {
+        NodeEvent::Revoked { revocation } => revocation,
+        event => panic!("unexpected event {event:?}"),
+    };
+    assert_eq!(a_server.propagate_revocation(&revocation).await, 1);
+    // a revocation that arrives over another path again is not pushed twice
+    assert_eq!(a_server.propagate_revocation(&revocation).await, 1);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(*b_on_a.conn.revoked.lock().unwrap(), vec![a.public]);
+    assert_eq!(*b_on_b.conn.revoked.lock().unwrap(), vec![a.public]);
+}
+
+/// A service that answers with its request after sleeping for that many milliseconds.
+struct SleepService;
+
+impl Service<u64> for SleepService {
+    type Response = u64;
+    type Error = IncompatibleVersionError;
+
+    async fn call(&self, millis: u64) -> Result<Self::Response, Self::Error> {
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        Ok(millis)
+    }
+}
+
+#[tokio::test]
+async fn pipeline() {
+    let requests = [200, 10, 100, 0]
+        .into_iter()
+        .enumerate()
+        .map(|(id, body)| Tagged::new(id as u64, body));
+    let (send, recv) = futures::channel::mpsc::unbounded();
+
+    let start = std::time::Instant::now();
+    Pipeline::new(4)
+        .drive(&SleepService, futures::stream::iter(requests), send)
+        .await
+        .unwrap();
+    // the requests were processed at the same time
+    assert!(start.elapsed() < Duration::from_millis(300));
+
+    // the responses are in the order of the requests
+    let responses: Vec<_> = futures::StreamExt::collect(recv).await;
+    let ids: Vec<_> = responses.iter().map(|resp| resp.id).collect();
+    assert_eq!(ids, vec![0, 1, 2, 3]);
+    assert_eq!(responses[0].body, Ok(200));
+
+    // requests of another version are rejected without being processed
+    let mut req = Tagged::new(0, 10_000);
+    req.version = Some(CURRENT_VERSION + 1);
+    let (send, recv) = futures::channel::mpsc::unbounded();
+    Pipeline::new(4)
+        .drive(&SleepService, futures::stream::iter([req]), send)
+        .await
+        .unwrap();
+    let responses: Vec<_> = futures::StreamExt::collect(recv).await;
+    assert_eq!(
+        responses[0].body,
+        Err(IncompatibleVersionError {
+            version: CURRENT_VERSION + 1
+        })
+    );
+}
+
+#[tokio::test]
+async fn serve() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl, RecordConn::default());
+    let requests = [
+        ReqMessage::from(PreIdentifyReq {}),
+        ReqMessage::from(CommunicationReq {
+            from: KeyPair::generate().public,
+            to: KeyPair::generate().public,
+            grant: None,
+            hops: 0,
+        }),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(id, body)| Tagged::new(id as u64, body));
+    let (send, recv) = futures::channel::mpsc::unbounded();
+
+    hdl.serve(futures::stream::iter(requests), send)
+        .await
+        .unwrap();
+    let responses: Vec<_> = futures::StreamExt::collect(recv).await;
+    assert_eq!(responses.len(), 2);
+    assert!(matches!(responses[0].body, RespMessage::PreIdentify(_)));
+    // failed requests are answered with an error
+    assert_eq!(responses[1].id, 1);
+    assert!(matches!(responses[1].body, RespMessage::Error(_)));
+}
+
+/// A connection of a server that reports `remote` as connected to it. Opening a stream to it
+/// yields the public key the stream is for.
+#[derive(Debug, Default)]
+struct FederatedConn {
+    remote: Option<KeyTriad<SignedData>>,
+}
+
+impl Notify for FederatedConn {
+    type Err = Infallible;
+
+    async fn notify_connected(&self, _triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_introduced(&self, _intro: &Introduction) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_revoked(&self, _revocation: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_mail(&self, _mail: &Mail) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+impl Service<PublicKey> for FederatedConn {
+    type Response = PublicKey;
+    type Error = DeclinedError;
+
+    async fn call(&self, key: PublicKey) -> Result<Self::Response, Self::Error> {
+        Ok(key)
+    }
+}
+impl OpenStream for FederatedConn {
+    type Err = DeclinedError;
+}
+impl Service<KeysExistsRReq> for FederatedConn {
+    type Response = KeysExistsRResp;
+    type Error = DeclinedError;
+
+    async fn call(&self, _req: KeysExistsRReq) -> Result<Self::Response, Self::Error> {
+        let triads = self.remote.iter().map(|triad| KeyConnectedTo {
+            triad: triad.clone(),
+            connected_to: Vec::new(),
+        });
+
+        Ok(KeysExistsRResp {
+            triads: triads.collect(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn remote_keys() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            remote_key_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let a_hdl = InboundEndpoint::server_hdl(
+        0,
+        ENDPOINT_INFO,
+        server_hdl.clone(),
+        FederatedConn::default(),
+    );
+    identify(&a_hdl, &a.private).await;
+
+    let req = CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: None,
+        hops: 0,
+    };
+    assert!(a_hdl.communicate(req.clone()).await.is_err());
+
+    // a connected server claims the key, without an identify triad to prove it
+    let lookup = KeysExistsRReq {
+        keys: Arc::from([b.public]),
+        depth: 1,
+    };
+    let forger = InboundEndpoint::server_hdl(
+        1,
+        EndpointInfo {
+            server_info: Some(ServerInfo::new(arcstr::literal!("forger.example"))),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        FederatedConn {
+            remote: Some(KeyTriad::sign_detached(&b.private, b"triad")),
+        },
+    );
+    server_hdl.connect_server(forger).await.unwrap();
+    let resp = (*a_hdl).call(lookup.clone()).await.unwrap();
+    assert!(resp.triads.is_empty());
+    assert!(a_hdl.communicate(req.clone()).await.is_err());
+
+    // a connected server reports the key
+    let peer = InboundEndpoint::server_hdl(
+        2,
+        EndpointInfo {
+            server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        FederatedConn {
+            remote: Some(identify_triad(&a_hdl, &b.private).await),
+        },
+    );
+    server_hdl.connect_server(peer.clone()).await.unwrap();
+    (*a_hdl).call(lookup).await.unwrap();
+
+    // a stream to the key is opened ahead of the request
+    assert_eq!(server_hdl.prepare_streams(b.public).await, Ok(true));
+    let pool = peer.streams.get::<PublicKey>(&b.public).await.unwrap();
+    assert_eq!(pool.idle(), 1);
+    assert_eq!(server_hdl.load().relay_saturation, 0);
+
+    // the request is routed to the server without looking the key up again, over the stream
+    let established = a_hdl.communicate(req).await.unwrap();
+    assert_eq!(established.stream, b.public);
+    assert_eq!(established.relay.unwrap().id, 2);
+    assert_eq!(pool.idle(), 0);
+    // which leaves no stream ready for the next request
+    assert_eq!(server_hdl.load().relay_saturation, 100);
+
+    // streams of keys the server no longer reports are closed
+    assert_eq!(server_hdl.prepare_streams(b.public).await, Ok(true));
+    server_hdl.remote_keys.clear_async().await;
+    assert_eq!(server_hdl.reap().await.streams, 1);
+    assert!(peer.streams.keys().await.is_empty());
+}
+
+#[tokio::test]
+async fn multi_homed() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            remote_key_ttl: Some(Duration::from_secs(60)),
+            peer_demote_errors: 1,
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let a_hdl = InboundEndpoint::server_hdl(
+        0,
+        ENDPOINT_INFO,
+        server_hdl.clone(),
+        FederatedConn::default(),
+    );
+    identify(&a_hdl, &a.private).await;
+
+    // the key is connected to three servers, one of which was never measured
+    let mut peers = Vec::new();
+    for id in 1..=3 {
+        let peer = InboundEndpoint::server_hdl(
+            id,
+            EndpointInfo {
+                server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+                ..ENDPOINT_INFO
+            },
+            server_hdl.clone(),
+            FederatedConn::default(),
+        );
+        server_hdl.connect_server(peer.clone()).await.unwrap();
+        server_hdl.learn_remote(b.public, &peer).await;
+        peers.push(peer);
+    }
+    server_hdl.record_peer(&peers[0], Some(80)).await;
+    server_hdl.record_peer(&peers[1], Some(20)).await;
+
+    let req = CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: None,
+        hops: 0,
+    };
+    let relay = a_hdl.communicate(req.clone()).await.unwrap().relay.unwrap();
+    assert_eq!((relay.id, relay.rtt), (2, 20));
+
+    // a demoted server is passed over
+    server_hdl.record_peer(&peers[1], None).await;
+    let relay = a_hdl.communicate(req).await.unwrap().relay.unwrap();
+    assert_eq!((relay.id, relay.rtt), (1, 80));
+}
+
+#[tokio::test]
+async fn relay_hops() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            remote_key_ttl: Some(Duration::from_secs(60)),
+            max_forward_depth: 2,
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let mut peers = Vec::new();
+    for id in 1..=2 {
+        let peer = InboundEndpoint::server_hdl(
+            id,
+            EndpointInfo {
+                server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+                ..ENDPOINT_INFO
+            },
+            server_hdl.clone(),
+            FederatedConn::default(),
+        );
+        server_hdl.connect_server(peer.clone()).await.unwrap();
+        server_hdl.learn_remote(b.public, &peer).await;
+        peers.push(peer);
+    }
+
+    // a relayed request is not sent back to the server it came from
+    let req = CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: None,
+        hops: 1,
+    };
+    let relay = peers[0].communicate(req.clone()).await.unwrap().relay;
+    assert_eq!(relay.unwrap().id, 2);
+
+    // nor relayed further than allowed
+    assert!(matches!(
+        peers[0]
+            .communicate(CommunicationReq {
+                hops: 2,
+                ..req.clone()
+            })
+            .await,
+        Err(CommunicationReqError::CannotFindKey)
+    ));
+    assert!(matches!(
+        peers[0]
+            .communicate(CommunicationReq {
+                hops: 3,
+                ..req.clone()
+            })
+            .await,
+        Err(CommunicationReqError::TtlExceeded { hops: 3, max: 2 })
+    ));
+
+    // only servers relay requests
+    let client = InboundEndpoint::server_hdl(
+        0,
+        ENDPOINT_INFO,
+        server_hdl.clone(),
+        FederatedConn::default(),
+    );
+    identify(&client, &a.private).await;
+    assert!(matches!(
+        client.communicate(req).await,
+        Err(CommunicationReqError::InvalidPublicKey)
+    ));
+}
+
+#[tokio::test]
+async fn chaos_peer() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            remote_key_ttl: Some(Duration::from_secs(60)),
+            peer_demote_errors: 2,
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let a_hdl = InboundEndpoint::server_hdl(
+        0,
+        ENDPOINT_INFO,
+        server_hdl.clone(),
+        Chaos::new(FederatedConn::default()),
+    );
+    identify(&a_hdl, &a.private).await;
+
+    let peer = InboundEndpoint::server_hdl(
+        1,
+        EndpointInfo {
+            server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        Chaos::new(FederatedConn {
+            remote: Some(identify_triad(&a_hdl, &b.private).await),
+        }),
+    );
+    let control = peer.conn.control().clone();
+    server_hdl.connect_server(peer).await.unwrap();
+    let req = KeysExistsRReq {
+        keys: Arc::from([b.public]),
+        depth: 1,
+    };
+
+    // an unreachable peer is counted as an error, and the key is not found
+    control.drop_peer();
+    let resp = (*a_hdl).call(req.clone()).await.unwrap();
+    assert!(resp.triads.is_empty());
+    assert_eq!(
+        server_hdl.peer_health(1).await.unwrap().consecutive_errors,
+        1
+    );
+    assert_eq!(control.delivered(), 0);
+
+    // duplicated requests do not duplicate the results, and reset the errors
+    control.restore();
+    control.duplicate(2);
+    let resp = (*a_hdl).call(req.clone()).await.unwrap();
+    assert_eq!(resp.triads.len(), 1);
+    assert_eq!(control.delivered(), 3);
+    assert_eq!(
+        server_hdl.peer_health(1).await.unwrap().consecutive_errors,
+        0
+    );
+
+    // delays raise the smoothed round trip time of the peer
+    control.restore();
+    control.delay(Duration::from_millis(20));
+    (*a_hdl).call(req).await.unwrap();
+    assert!(server_hdl.peer_health(1).await.unwrap().rtt >= 20 * 2 / 8);
+}
+
+#[tokio::test]
+async fn forward_depth() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl = InboundEndpoint::server_hdl(
+        0,
+        ENDPOINT_INFO,
+        server_hdl.clone(),
+        FederatedConn::default(),
+    );
+    identify(&a_hdl, &a.private).await;
+    let peer = InboundEndpoint::server_hdl(
+        1,
+        EndpointInfo {
+            server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        FederatedConn {
+            remote: Some(identify_triad(&a_hdl, &b.private).await),
+        },
+    );
+    server_hdl.connect_server(peer.clone()).await.unwrap();
+    let req = |depth| KeysExistsRReq {
+        keys: Arc::from([b.public]),
+        depth,
+    };
+
+    // a request deeper than the node allows is dropped
+    assert_eq!(
+        (*a_hdl).call(req(5)).await,
+        Err(KeysExistsRReqError::TtlExceeded { depth: 5, max: 4 })
+    );
+    assert_eq!((*a_hdl).call(req(4)).await.unwrap().triads.len(), 1);
+
+    // a request is not forwarded back to the server that sent it
+    assert!((*peer).call(req(4)).await.unwrap().triads.is_empty());
+}
+
+#[tokio::test]
+async fn forward_resolved() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl = InboundEndpoint::server_hdl(
+        0,
+        ENDPOINT_INFO,
+        server_hdl.clone(),
+        Chaos::new(FederatedConn::default()),
+    );
+    identify(&a_hdl, &a.private).await;
+    let peer = InboundEndpoint::server_hdl(
+        1,
+        EndpointInfo {
+            server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        Chaos::new(FederatedConn {
+            remote: Some(identify_triad(&a_hdl, &b.private).await),
+        }),
+    );
+    let control = peer.conn.control().clone();
+    server_hdl.connect_server(peer).await.unwrap();
+    let req = |keys: &[PublicKey]| KeysExistsRReq {
+        keys: Arc::from(keys),
+        depth: 1,
+    };
+
+    // a request without keys is answered without asking the peers
+    let resp = (*a_hdl).call(req(&[])).await.unwrap();
+    assert!(resp.triads.is_empty());
+    assert_eq!(control.delivered(), 0);
+
+    // and so is one whose keys all identified to this server
+    let resp = (*a_hdl).call(req(&[a.public])).await.unwrap();
+    assert_eq!(resp.triads.len(), 1);
+    assert_eq!(control.delivered(), 0);
+
+    // only the keys left unresolved are forwarded
+    let resp = (*a_hdl).call(req(&[a.public, b.public])).await.unwrap();
+    assert_eq!(resp.triads.len(), 2);
+    assert_eq!(control.delivered(), 1);
+}
+
+#[tokio::test]
+async fn size_limits() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            size_limits: SizeLimits {
+                max_keys: 2,
+                max_batch: 1,
+                max_payload: 8,
+                max_proof: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    let resp = hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public, b.public, a.public],
+            notify: false,
+        })
+        .await;
+    assert!(matches!(
+        resp,
+        Err(KeysExistsReqError::TooLarge(TooLargeError {
+            size: 3,
+            max: 2
+        }))
+    ));
+
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triads: Vec<_> = [&a, &b]
+        .iter()
+        .map(|pair| {
+            KeyTriad::gen_signed(
+                &pair.private,
+                &identify,
+                SignMessageType::Identify,
+                SignedFormat::Cbor,
+            )
+            .unwrap()
+        })
+        .collect();
+    let resp = hdl
+        .identify_batch(IdentifyReq {
+            keys: triads,
+            compact: Vec::new(),
+            multi: Vec::new(),
+            delegated: Vec::new(),
+        })
+        .await;
+    let err = ErrorResp::from(resp.unwrap_err());
+    assert_eq!(err.code, ErrorCode::TOO_LARGE);
+    assert!(hdl.public_keys.read().await.is_empty());
+
+    // the identify data itself is larger than the payload limit
+    let triad = KeyTriad::gen_signed(
+        &a.private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        hdl.identify(triad).await,
+        Err(IdentifyReqError::TooLarge(_))
+    ));
+
+    let message = KeyTriad::gen_signed(
+        &a.private,
+        "too long to hold",
+        SignMessageType::AppMessage,
+        SignedFormat::Json,
+    )
+    .unwrap();
+    assert!(matches!(
+        server_hdl.deposit(b.public, message).await,
+        Err(MailboxError::TooLarge { max: 8, .. })
+    ));
+
+    // a proof covering more entries than allowed is not checked
+    let peer = InboundEndpoint::server_hdl(
+        1,
+        EndpointInfo {
+            server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        RecordConn::default(),
+    );
+    let mut log = TransparencyLog::default();
+    for _ in 0..3 {
+        log.append((&a.public.0[..]).to_hash_msg());
+    }
+    let resp = peer
+        .cross_sign(CrossSignReq {
+            head: log.sign(&a.private, 0),
+            proof: log.proof(0, 3).unwrap(),
+        })
+        .await;
+    assert!(matches!(resp, Err(CrossSignReqError::TooLarge(_))));
+}
+
+#[tokio::test]
+async fn stream_pool() {
+    let pool = StreamPool::new(PoolConfig {
+        min_streams: 1,
+        max_streams: 2,
+        idle_timeout: Duration::ZERO,
+    });
+    let opened = Mutex::new(0);
+    let open = || async {
+        let mut opened = opened.lock().unwrap();
+        *opened += 1;
+        Ok::<_, Infallible>(*opened)
+    };
+
+    let a = pool.get(open).await.unwrap();
+    let b = pool.get(open).await.unwrap();
+    assert_eq!((*a, *b), (1, 2));
+
+    // the pool is full, so the next request waits for a stream to be returned
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), pool.get(open))
+            .await
+            .is_err()
+    );
+    drop(a);
+    assert_eq!(*pool.get(open).await.unwrap(), 1);
+
+    // broken streams are not reused
+    b.discard();
+    assert_eq!(pool.idle(), 1);
+
+    // reaping keeps the minimum open
+    assert_eq!(pool.reap(), 0);
+    pool.fill(open).await.unwrap();
+    assert_eq!(pool.idle(), 1);
+
+    // filling counts the streams in use toward the maximum
+    let pool = StreamPool::new(PoolConfig {
+        min_streams: 2,
+        max_streams: 2,
+        idle_timeout: Duration::ZERO,
+    });
+    let used = pool.get(open).await.unwrap();
+    pool.fill(open).await.unwrap();
+    assert_eq!(pool.idle(), 1);
+    drop(used);
+    assert_eq!(pool.idle(), 2);
+}
+
+#[tokio::test]
+async fn identify_work() {
+    let key = PrivateKey::new(PRIVATE_KEY).unwrap();
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            identify_difficulty: Some(8),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+
+    let identify = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    assert_eq!(identify.difficulty, Some(8));
+
+    let triad = KeyTriad::gen_signed(
+        &key,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        hdl.identify(triad.clone()).await,
+        Err(IdentifyReqError::InsufficientWork)
+    ));
+    // the proof is checked before the signature
+    let mut forged = triad;
+    forged.signature = KeyTriad::sign_detached(&key, b"other").signature;
+    assert!(matches!(
+        hdl.identify(forged).await,
+        Err(IdentifyReqError::InsufficientWork)
+    ));
+
+    // a proof for another key does not count
+    let other_key = KeyPair::generate().public;
+    let mut other = identify.clone();
+    for work in 0.. {
+        other.work = Some(work);
+        if other.work_valid(&other_key) && !other.work_valid(&key.derive_public()) {
+            break;
+        }
+    }
+    let triad =
+        KeyTriad::gen_signed(&key, &other, SignMessageType::Identify, SignedFormat::Cbor).unwrap();
+    assert!(matches!(
+        hdl.identify(triad).await,
+        Err(IdentifyReqError::InsufficientWork)
+    ));
+
+    let solved = identify.solve(&key.derive_public());
+    let triad =
+        KeyTriad::gen_signed(&key, &solved, SignMessageType::Identify, SignedFormat::Cbor).unwrap();
+    hdl.identify(triad).await.unwrap();
+}
+
+#[tokio::test]
+async fn fair_scheduler() {
+    let scheduler = FairScheduler::new(1, tokio::sync::broadcast::channel(1).0);
+    let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
+
+    // a busy endpoint queues a lot of work before another endpoint queues any
+    for i in 0..8 {
+        let send = send.clone();
+        scheduler.submit(0, async move { send.send((0, i)).unwrap() });
+    }
+    let other = send.clone();
+    scheduler.submit(1, async move { other.send((1, 0)).unwrap() });
+    drop(send);
+
+    let mut order = Vec::new();
+    while let Some(value) = recv.recv().await {
+        order.push(value);
+    }
+    assert_eq!(order.len(), 9);
+    // the other endpoint is served after one job of the busy endpoint
+    assert_eq!(order[1], (1, 0));
+}
+
+#[tokio::test]
+async fn fair_scheduler_panics() {
+    let (events, mut event_recv) = tokio::sync::broadcast::channel(8);
+    let scheduler = FairScheduler::new(1, events);
+    let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
+
+    scheduler.submit(0, async { panic!("notification failed") });
+    let other = send.clone();
+    scheduler.submit(1, async move { other.send(1).unwrap() });
+
+    assert_eq!(
+        event_recv.recv().await.unwrap(),
+        NodeEvent::TaskPanicked {
+            id: 0,
+            message: "notification failed".to_string()
+        }
+    );
+    // the worker keeps serving the other endpoints
+    assert_eq!(recv.recv().await, Some(1));
+
+    // work is cancelled and dropped after a shutdown
+    let (_gate_send, gate) = tokio::sync::oneshot::channel::<()>();
+    let other = send.clone();
+    scheduler.submit(0, async move {
+        let _ = gate.await;
+        other.send(0).unwrap();
+    });
+    let other = send.clone();
+    scheduler.submit(1, async move { other.send(1).unwrap() });
+    scheduler.shutdown();
+    scheduler.submit(2, async move { send.send(2).unwrap() });
+
+    assert_eq!(recv.recv().await, None);
+}
+
+#[tokio::test]
+async fn memory_usage() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            memory_watermarks: MemoryWatermarks {
+                mail: Some(1),
+                subscriptions: Some(1),
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let a_hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    assert_eq!(server_hdl.memory_usage().await, MemoryUsage::default());
+
+    server_hdl
+        .deposit(a.public, KeyTriad::sign_detached(&b.private, b"0"))
+        .await
+        .unwrap();
+    identify(&a_hdl, &b.private).await;
+    let usage = server_hdl.memory_usage().await;
+    assert_eq!(usage.identities.entries, 2);
+    // the mailbox and its message, the receipt tracked for it and its id
+    assert_eq!(usage.mailboxes.entries, 4);
+    assert!(usage.mailboxes.bytes > 32);
+    // the attestation of the identity, the change to the set of keys, and the deposit and the
+    // identity replicated to standbys
+    assert_eq!(usage.journals.entries, 4);
+    assert_eq!(usage.total().entries, 10);
+
+    // work that would hold more is shed above the watermarks
+    assert!(matches!(
+        server_hdl
+            .deposit(a.public, KeyTriad::sign_detached(&b.private, b"1"))
+            .await,
+        Err(MailboxError::Overloaded(OverloadedError {
+            watermark: 1,
+            ..
+        }))
+    ));
+    let err = a_hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public],
+            notify: true,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorResp::from(err).code, ErrorCode::OVERLOADED);
+    assert!(a_hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public],
+            notify: false,
+        })
+        .await
+        .is_ok());
+    assert_eq!(server_hdl.memory_usage().await.subscriptions.entries, 0);
+
+    // what a mailbox held is no longer counted once it is fetched
+    server_hdl.take_mail(&a.public).await;
+    assert_eq!(server_hdl.memory_usage().await.mailboxes.entries, 2);
+    assert_eq!(server_hdl.gauges.mail_bytes.get(), 0);
+}
+
+#[tokio::test]
+async fn mailbox_retention() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            mail_retention: RetentionPolicy {
+                max_messages: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let a_hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let mail = |msg: &[u8]| KeyTriad::sign_detached(&b.private, msg);
+
+    // the oldest message is evicted first
+    let mut ids = Vec::new();
+    for msg in [b"0", b"1", b"2"] {
+        ids.push(server_hdl.deposit(a.public, mail(msg)).await.unwrap());
+    }
+
+    // a detached payload is the size of its hash
+    server_hdl
+        .set_retention(
+            a.public,
+            Some(RetentionPolicy {
+                max_bytes: Some(32),
+                ..Default::default()
+            }),
+        )
+        .await;
+    server_hdl
+        .deposit(a.public, KeyTriad::sign_detached(&a.private, b"x"))
+        .await
+        .unwrap();
+    let mut forged = mail(b"3");
+    forged.public_key = a.public;
+    assert_eq!(
+        server_hdl.deposit(a.public, forged).await,
+        Err(MailboxError::SignatureInvalid)
+    );
+
+    // only the owner can fetch the mailbox
+    assert_eq!(
+        a_hdl.fetch_mail(FetchMailReq { key: a.public }).await,
+        Err(FetchMailReqError::InvalidPublicKey)
+    );
+    identify(&a_hdl, &a.private).await;
+    let resp = a_hdl
+        .fetch_mail(FetchMailReq { key: a.public })
+        .await
+        .unwrap();
+    assert_eq!(resp.mail.len(), 1);
+    assert_eq!(resp.mail[0].payload.public_key, a.public);
+    let evicted: Vec<_> = resp
+        .evicted
+        .iter()
+        .map(|notice| (notice.id, notice.from, notice.reason))
+        .collect();
+    assert_eq!(
+        evicted,
+        vec![
+            (ids[0], b.public, EvictionReason::MaxMessages),
+            (ids[1], b.public, EvictionReason::MaxBytes),
+            (ids[2], b.public, EvictionReason::MaxBytes),
+        ]
+    );
+
+    // fetched messages are removed, and old messages expire
+    server_hdl
+        .set_retention(
+            a.public,
+            Some(RetentionPolicy {
+                max_age: Some(Duration::ZERO),
+                ..Default::default()
+            }),
+        )
+        .await;
+    let id = server_hdl.deposit(a.public, mail(b"4")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let resp = a_hdl
+        .fetch_mail(FetchMailReq { key: a.public })
+        .await
+        .unwrap();
+    assert!(resp.mail.is_empty());
+    assert_eq!(resp.evicted.len(), 1);
+    assert_eq!(resp.evicted[0].id, id);
+    assert_eq!(resp.evicted[0].reason, EvictionReason::Expired);
+}
+
+#[tokio::test]
+async fn receipts() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+
+    let payload = KeyTriad::sign_detached(&a.private, b"hello");
+    let id = server_hdl.deposit(b.public, payload).await.unwrap();
+    assert_eq!(
+        a_hdl.receipt(ReceiptReq { id }).await.unwrap().receipt,
+        None
+    );
+    // only the sender can follow the message
+    assert_eq!(
+        b_hdl.receipt(ReceiptReq { id }).await,
+        Err(ReceiptReqError::InvalidPublicKey)
+    );
+
+    identify(&b_hdl, &b.private).await;
+    let mail = b_hdl
+        .fetch_mail(FetchMailReq { key: b.public })
+        .await
+        .unwrap()
+        .mail
+        .remove(0);
+
+    // only the recipient can acknowledge the message
+    assert_eq!(
+        a_hdl
+            .send_receipt(mail.receipt(&a.private, ReceiptStatus::Read))
+            .await,
+        Err(ReceiptReqError::InvalidReceipt)
+    );
+
+    b_hdl
+        .send_receipt(mail.receipt(&b.private, ReceiptStatus::Delivered))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let pushed = a_hdl.conn.receipts.lock().unwrap().clone();
+    assert_eq!(pushed.len(), 1);
+    assert!(pushed[0].acknowledges(&b.public, id, &mail.payload.signed));
+
+    b_hdl
+        .send_receipt(mail.receipt(&b.private, ReceiptStatus::Read))
+        .await
+        .unwrap();
+    // a late delivery receipt does not replace the read receipt
+    b_hdl
+        .send_receipt(mail.receipt(&b.private, ReceiptStatus::Delivered))
+        .await
+        .unwrap();
+    let receipt = a_hdl.receipt(ReceiptReq { id }).await.unwrap().receipt;
+    assert_eq!(
+        receipt.map(|receipt| receipt.signed.status),
+        Some(ReceiptStatus::Read)
+    );
+
+    // the record is dropped once the read receipt was fetched
+    assert_eq!(
+        a_hdl.receipt(ReceiptReq { id }).await,
+        Err(ReceiptReqError::UnknownMessage)
+    );
+}
+
+#[tokio::test]
+async fn receipts_expire() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            receipt_ttl: Duration::ZERO,
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let a_hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    identify(&a_hdl, &a.private).await;
+
+    let payload = KeyTriad::sign_detached(&a.private, b"hello");
+    let id = server_hdl.deposit(b.public, payload).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(2)).await;
+    assert_eq!(
+        server_hdl.reap().await,
+        Reaped {
+            receipts: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        a_hdl.receipt(ReceiptReq { id }).await,
+        Err(ReceiptReqError::UnknownMessage)
+    );
+}
+
+#[tokio::test]
+async fn mail_dedupe() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    identify(&a_hdl, &a.private).await;
+
+    let payload = KeyTriad::sign_detached(&b.private, b"hello");
+    let id = server_hdl.deposit(a.public, payload.clone()).await.unwrap();
+    let message = payload.signed.to_hash_msg();
+    assert_eq!(id, MessageId::new(&b.public, &a.public, &message));
+
+    // a retry has the same id, and is not stored again
+    let retry = server_hdl.deposit(a.public, payload.clone()).await;
+    assert_eq!(retry, Ok(id));
+    // another payload is another message
+    let other = server_hdl
+        .deposit(
+            a.public,
+            KeyTriad::sign_detached(&b.private, b"hello again"),
+        )
+        .await
+        .unwrap();
+    assert_ne!(other, id);
+
+    let resp = a_hdl
+        .fetch_mail(FetchMailReq { key: a.public })
+        .await
+        .unwrap();
+    let ids: Vec<_> = resp.mail.iter().map(|mail| mail.id).collect();
+    assert_eq!(ids, vec![id, other]);
+    assert!(resp.mail.iter().all(|mail| mail.id_valid(&a.public)));
+
+    // nor is it delivered again once fetched, nor does it leave a mailbox behind
+    server_hdl.deposit(a.public, payload).await.unwrap();
+    assert!(!server_hdl.mailboxes.contains_async(&a.public).await);
+    let resp = a_hdl.respond(FetchMailReq { key: a.public }.into()).await;
+    assert_eq!(
+        resp,
+        RespMessage::FetchMail(FetchMailResp {
+            mail: Vec::new(),
+            evicted: Vec::new(),
+        })
+    );
+}
+
+#[tokio::test]
+async fn mail_delivered_once() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let payload = KeyTriad::sign_detached(&b.private, b"hello");
+    let id = server_hdl.deposit(a.public, payload).await.unwrap();
+
+    // delivering again while the mail is being delivered does not deliver it twice
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+    server_hdl.deliver_mail(a.public, a_hdl.clone()).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let delivered: Vec<_> = a_hdl
+        .conn
+        .mail
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|mail| mail.id)
+        .collect();
+    assert_eq!(delivered, vec![id]);
+    assert!(!server_hdl.mailboxes.contains_async(&a.public).await);
+}
+
+#[tokio::test]
+async fn max_mailboxes() {
+    let (a, b, c) = (
+        KeyPair::generate(),
+        KeyPair::generate(),
+        KeyPair::generate(),
+    );
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            max_mailboxes: Some(1),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let mail = |nonce| KeyTriad::sign_detached(&c.private, &[nonce]);
+
+    server_hdl.deposit(a.public, mail(0)).await.unwrap();
+    // the mailbox of another key would exceed the cap, but the existing one can still grow
+    assert_eq!(
+        server_hdl.deposit(b.public, mail(1)).await,
+        Err(MailboxError::TooManyMailboxes { max: 1 })
+    );
+    server_hdl.deposit(a.public, mail(2)).await.unwrap();
+
+    // delivered mailboxes no longer count
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    server_hdl.deposit(b.public, mail(1)).await.unwrap();
+}
+
+#[tokio::test]
+async fn relay_message() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+
+    let relay = |nonce: u8, ttl| RelayMessage {
+        to: b.public,
+        payload: KeyTriad::sign_detached(&a.private, &[nonce]),
+        ttl,
+    };
+    // only the sender can relay its message
+    let forged = RelayMessage {
+        payload: KeyTriad::sign_detached(&b.private, b"forged"),
+        ..relay(0, None)
+    };
+    assert_eq!(
+        a_hdl.relay(forged).await,
+        Err(RelayReqError::InvalidPublicKey)
+    );
+
+    let id = a_hdl.relay(relay(1, None)).await.unwrap().id;
+    a_hdl.relay(relay(2, Some(0))).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    // the messages are delivered once the key identifies, except the one that expired
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&b_hdl, &b.private).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let delivered: Vec<_> = b_hdl
+        .conn
+        .mail
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|mail| mail.id)
+        .collect();
+    assert_eq!(delivered, vec![id]);
+
+    let resp = b_hdl
+        .fetch_mail(FetchMailReq { key: b.public })
+        .await
+        .unwrap();
+    assert!(resp.mail.is_empty());
+    assert_eq!(resp.evicted.len(), 1);
+    assert_eq!(resp.evicted[0].reason, EvictionReason::Expired);
+
+    // a message to a key that is identified is pushed right away
+    let id = a_hdl.relay(relay(3, None)).await.unwrap().id;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(b_hdl.conn.mail.lock().unwrap().last().unwrap().id, id);
+}
+
+#[tokio::test]
+async fn broadcast() {
+    let (a, b, admin) = (
+        KeyPair::generate(),
+        KeyPair::generate(),
+        KeyPair::generate(),
+    );
+    let peer_info = |domain| EndpointInfo {
+        server_info: Some(ServerInfo {
+            public_key: Some(a.public),
+            ..ServerInfo::new(domain)
+        }),
+        ..ENDPOINT_INFO
+    };
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl = InboundEndpoint::server_hdl(
+        0,
+        peer_info(arcstr::literal!("a.example")),
+        server_hdl.clone(),
+        RecordConn::default(),
+    );
+    identify(&a_hdl, &a.private).await;
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&b_hdl, &b.private).await;
+
+    let req = |domain| BroadcastReq {
+        payload: KeyTriad::sign_detached(&a.private, b"maintenance at 02:00"),
+        domain,
+    };
+    // only federated servers can broadcast, and only in their own name
+    assert_eq!(
+        b_hdl.broadcast(req(None)).await,
+        Err(BroadcastReqError::NotPeer)
+    );
+    let unbound = BroadcastReq {
+        payload: KeyTriad::sign_detached(&admin.private, b"maintenance at 02:00"),
+        domain: None,
+    };
+    assert_eq!(
+        a_hdl.broadcast(unbound.clone()).await,
+        Err(BroadcastReqError::InvalidPublicKey)
+    );
+    let mut forged = unbound;
+    forged.payload.public_key = a.public;
+    assert_eq!(
+        a_hdl.broadcast(forged).await,
+        Err(BroadcastReqError::SignatureInvalid)
+    );
+
+    assert_eq!(a_hdl.broadcast(req(None)).await.unwrap().recipients, 2);
+    let resp = a_hdl
+        .broadcast(req(Some(arcstr::literal!("a.example"))))
+        .await
+        .unwrap();
+    assert_eq!(resp.recipients, 1);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(a_hdl.conn.broadcasts.lock().unwrap().len(), 2);
+    assert_eq!(
+        *b_hdl.conn.broadcasts.lock().unwrap(),
+        vec![req(None).payload]
+    );
+}
+
+#[tokio::test]
+async fn diagnose() {
+    let hdl = ServerHandle::<DummyNotify>::new();
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let taken = taken.local_addr().unwrap();
+    let free = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let diagnosis = hdl.diagnose(&[free, taken]).await;
+    let status = |kind| {
+        diagnosis
+            .checks
+            .iter()
+            .find(|check| check.kind == kind)
+            .map(|check| check.status.clone())
+            .unwrap()
+    };
+    assert_eq!(status(CheckKind::Clock), CheckStatus::Passed);
+    assert_eq!(status(CheckKind::Entropy), CheckStatus::Passed);
+    assert_eq!(status(CheckKind::Crypto), CheckStatus::Passed);
+    assert_eq!(status(CheckKind::Listener(free)), CheckStatus::Passed);
+    assert!(matches!(
+        status(CheckKind::Listener(taken)),
+        CheckStatus::Failed(_)
+    ));
+    // the node has no store to check
+    assert_eq!(status(CheckKind::Persistence), CheckStatus::Skipped);
+    assert!(!diagnosis.healthy());
+    assert_eq!(diagnosis.failures().count(), 1);
+
+    let hdl = ServerHandle::<DummyNotify>::new()
+        .with_gossip_store(Arc::new(MemoryGossipStore::default()))
+        .unwrap();
+    let diagnosis = hdl.diagnose(&[]).await;
+    assert!(diagnosis.healthy());
+    assert!(diagnosis.checks.contains(&Check {
+        kind: CheckKind::Persistence,
+        status: CheckStatus::Passed,
+    }));
+}
+
+#[tokio::test]
+async fn disconnect() {
+    let (a, b) = (KeyPair::generate(), KeyPair::generate());
+    let server_hdl = ServerHandle::new_hdl();
+    let mut events = server_hdl.subscribe();
+    let a_hdl = InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    let server_info = ServerInfo::new(arcstr::literal!("peer.example"));
+    let peer = InboundEndpoint::server_hdl(
+        1,
+        EndpointInfo {
+            server_info: Some(server_info.clone()),
+            ..ENDPOINT_INFO
+        },
+        server_hdl.clone(),
+        DummyNotify,
+    );
+    server_hdl.connect_server(peer.clone()).await.unwrap();
+    let token = identify(&peer, &a.private).await.resumption_token;
+    // the peer waits for `b` to connect
+    peer.keys_exists(KeysExistsReq {
+        keys: vec![b.public],
+        notify: true,
+    })
+    .await
+    .unwrap();
+
+    assert!(peer.disconnect().await);
+    assert!(peer.is_disconnected());
+    assert!(!server_hdl.key_to_endpoint.contains_async(&a.public).await);
+    assert!(!server_hdl.notifications.contains_async(&b.public).await);
+    assert!(server_hdl.connected_servers.read().await.is_empty());
+    assert_eq!(
+        events.recv().await.unwrap(),
+        NodeEvent::Disconnected {
+            id: 1,
+            server_info: Some(server_info),
+            public_keys: vec![a.public],
+        }
+    );
+    let resp = a_hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![a.public],
+            notify: false,
+        })
+        .await
+        .unwrap();
+    assert!(resp.triads.is_empty());
+
+    // forgetting the endpoint again does nothing
+    assert!(!server_hdl.remove_endpoint(&peer).await);
+    assert!(events.try_recv().is_err());
+
+    // the endpoint can no longer identify, but its identities can be resumed
+    let identify = peer.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triad = KeyTriad::gen_signed(
+        &b.private,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        peer.identify(triad).await,
+        Err(IdentifyReqError::Disconnected)
+    ));
+    assert!(!server_hdl.key_to_endpoint.contains_async(&b.public).await);
+    let new = InboundEndpoint::server_hdl(2, ENDPOINT_INFO, server_hdl.clone(), DummyNotify);
+    assert_eq!(
+        new.resume(ResumeReq { token }).await.unwrap().keys,
+        vec![a.public]
+    );
+}
+
+/// The connection of a standby to its primary, that asks for the journal through the endpoint of
+/// the standby on the primary.
+struct Follower(InboundHdl<RecordConn>);
+
+impl Notify for Follower {
+    type Err = Infallible;
+
+    async fn notify_connected(&self, _triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_introduced(&self, _intro: &Introduction) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_revoked(&self, _revocation: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        Ok(())
+    }
+    async fn notify_mail(&self, _mail: &Mail) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+impl Service<JournalReq> for Follower {
+    type Response = JournalResp;
+    type Error = JournalReqError;
+
+    async fn call(&self, req: JournalReq) -> Result<Self::Response, Self::Error> {
+        self.0.call(req).await
+    }
+}
+
+#[tokio::test]
+async fn standby() {
+    let (a, b, c, s) = (
+        KeyPair::generate(),
+        KeyPair::generate(),
+        KeyPair::generate(),
+        KeyPair::generate(),
+    );
+    let peer_info = EndpointInfo {
+        server_info: Some(ServerInfo::new(arcstr::literal!("peer.example"))),
+        ..ENDPOINT_INFO
+    };
+    let primary = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            replication: ReplicationConfig {
+                followers: vec![s.public],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let link =
+        InboundEndpoint::server_hdl(0, peer_info.clone(), primary.clone(), RecordConn::default());
+    let since = JournalReq {
+        since: Default::default(),
+        max: None,
+    };
+    // only the standbys of the node can read its journal
+    assert!(matches!(
+        link.call(since).await,
+        Err(JournalReqError::NotFollower)
+    ));
+    identify(&link, &s.private).await;
+
+    let a_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, primary.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+    a_hdl
+        .keys_exists(KeysExistsReq {
+            keys: vec![b.public],
+            notify: true,
+        })
+        .await
+        .unwrap();
+    let message = KeyTriad::gen_signed(
+        &a.private,
+        "hello",
+        SignMessageType::AppMessage,
+        SignedFormat::Json,
+    )
+    .unwrap();
+    let id = primary.deposit(b.public, message).await.unwrap();
+
+    let standby = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            replication: ReplicationConfig {
+                standby: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    assert_eq!(standby.role(), ReplicaRole::Standby);
+    let upstream =
+        InboundEndpoint::server_hdl(0, peer_info, standby.clone(), Follower(link.clone()));
+
+    // a new standby starts from a snapshot
+    assert_eq!(standby.follow(&upstream).await.unwrap(), 4);
+    assert!(standby.last_seen.contains_async(&a.public).await);
+    assert!(standby.last_seen.contains_async(&s.public).await);
+    // then replicates the writes made since
+    identify(
+        &InboundEndpoint::server_hdl(2, ENDPOINT_INFO, primary.clone(), RecordConn::default()),
+        &c.private,
+    )
+    .await;
+    a_hdl
+        .revoke(RevokeReq {
+            revocation: KeyTriad::revoke(&c.private, 0),
+        })
+        .await
+        .unwrap();
+    assert_eq!(standby.follow(&upstream).await.unwrap(), 2);
+    assert!(standby.revoked.contains_async(&c.public).await);
+    assert_eq!(standby.follow(&upstream).await.unwrap(), 0);
+
+    let mut role = standby.watch_role();
+    let mut events = standby.subscribe();
+    let position = primary.journal_position();
+    assert_eq!(standby.promote(), Some(position));
+    assert_eq!(standby.promote(), None);
+    assert!(role.has_changed().unwrap());
+    assert_eq!(*role.borrow_and_update(), ReplicaRole::Primary);
+    assert!(matches!(
+        events.try_recv(),
+        Ok(NodeEvent::Promoted { position: promoted }) if promoted == position
+    ));
+    // a promoted standby no longer follows
+    assert_eq!(standby.follow(&upstream).await.unwrap(), 0);
+
+    // subscriptions are restored once the subscriber identifies again
+    let new_a =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, standby.clone(), Follower(link.clone()));
+    identify(&new_a, &a.private).await;
+    assert!(standby
+        .notifications
+        .get_async(&b.public)
+        .await
+        .is_some_and(|endpoints| endpoints.get().contains(&new_a)));
+    let mail = standby.take_mail(&b.public).await.mail;
+    assert_eq!(mail.len(), 1);
+    assert_eq!(mail[0].id, id);
+}
+
+#[tokio::test]
+async fn reap() {
+    let a = KeyPair::generate();
+    let server_hdl = Arc::new(
+        ServerHandle::with_config(NodeConfig {
+            reap_interval: Some(Duration::from_millis(10)),
+            subscription_ttl: Some(Duration::ZERO),
+            ..Default::default()
+        })
+        .unwrap(),
+    );
+    let hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let other =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+
+    let stale = hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    other.pre_identify(PreIdentifyReq {}).await.unwrap();
+    server_hdl
+        .challenges
+        .get_async(&0)
+        .await
+        .unwrap()
+        .get_mut()
+        .expire_time = 0;
+    hdl.keys_exists(KeysExistsReq {
+        keys: vec![a.public],
+        notify: true,
+    })
+    .await
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(2)).await;
+
+    assert_eq!(
+        server_hdl.reap().await,
+        Reaped {
+            challenges: 1,
+            subscriptions: 1,
+            ..Default::default()
+        }
+    );
+    assert!(!server_hdl.notifications.contains_async(&a.public).await);
+    assert_eq!(server_hdl.reap().await, Reaped::default());
+    // the endpoint has to ask for new identify data
+    let triad = KeyTriad::gen_signed(
+        &a.private,
+        &stale,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        hdl.identify(triad).await,
+        Err(IdentifyReqError::IdentifyDataInvalid)
+    ));
+    identify(&hdl, &a.private).await;
+
+    // the identify data of dropped endpoints is forgotten
+    drop(other);
+    assert!(!server_hdl.challenges.contains_async(&1).await);
+
+    // the reaper clears the rest on its own
+    server_hdl.start_reaper();
+    server_hdl
+        .challenges
+        .get_async(&0)
+        .await
+        .unwrap()
+        .get_mut()
+        .expire_time = 0;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(server_hdl.challenges.is_empty());
+    server_hdl.shutdown();
+}
+
+#[tokio::test]
+async fn scenario() {
+    let topology = Scenario::new()
+        .server("a")
+        .server("b")
+        .link("a", "b")
+        .client("alice", "a")
+        .client("bob", "a")
+        .client("carol", "b")
+        .step(Step::identify("alice"))
+        .step(Step::subscribe("alice", ["bob", "carol"]))
+        .step(Step::identify("bob"))
+        .step(Step::identify("carol"))
+        .run()
+        .await
+        .unwrap();
+
+    let (a, b) = (topology.server("a"), topology.server("b"));
+    let peer = a.peer("b").unwrap();
+    assert_eq!(peer.server_info().unwrap().domain, "b.test");
+    assert!(a.hdl().connected_servers.read().await.contains(peer));
+    assert!(b
+        .hdl()
+        .connected_servers
+        .read()
+        .await
+        .contains(b.peer("a").unwrap()));
+    assert!(
+        a.hdl()
+            .key_to_endpoint
+            .contains_async(&b.keys().public)
+            .await
+    );
+
+    // only the key identified on the same server is reported
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let bob = topology.client("bob");
+    assert!(matches!(
+        &topology.client("alice").conn().take_pushes()[..],
+        [MockPush::Connected(triad)] if triad.public_key == bob.public_key()
+    ));
+    assert!(
+        a.hdl()
+            .notifications
+            .contains_async(&topology.client("carol").public_key())
+            .await
+    );
+
+    let err = Scenario::new()
+        .server("a")
+        .client("alice", "a")
+        .step(Step::identify("alice"))
+        .step(Step::revoke("alice"))
+        .step(Step::identify("alice"))
+        .run()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ScenarioError::Rejected { step: 2, .. }));
+    assert_eq!(
+        Scenario::new()
+            .client("alice", "a")
+            .run()
+            .await
+            .unwrap_err(),
+        ScenarioError::UnknownServer("a".into())
+    );
+}
+
+/// Answers pings with a fixed time, and rejects everyone's unsubscriptions.
+struct FixedClock;
+
+impl Service<PingReq> for FixedClock {
+    type Response = PongResp;
+    type Error = Infallible;
+
+    async fn call(&self, req: PingReq) -> Result<Self::Response, Self::Error> {
+        Ok(PongResp {
+            nonce: req.nonce,
+            sent_time: req.sent_time,
+            received_time: 42,
+        })
+    }
+}
+impl Service<UnsubscribeKeysReq> for FixedClock {
+    type Response = UnsubscribeKeysResp;
+    type Error = ErrorResp;
+
+    async fn call(&self, _req: UnsubscribeKeysReq) -> Result<Self::Response, Self::Error> {
+        Err(ErrorResp::new(ErrorCode::USAGE_DENIED, "no"))
+    }
+}
+
+#[tokio::test]
+async fn router() {
+    let server_hdl = ServerHandle::new_hdl();
+    let hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let ping = PingReq {
+        nonce: 7,
+        sent_time: 1,
+    };
+
+    // requests without a route are unsupported
+    assert!(matches!(
+        Router::new().call(ping.into()).await,
+        Ok(RespMessage::Error(ErrorResp {
+            code: ErrorCode::UNSUPPORTED,
+            ..
+        }))
+    ));
+
+    let router = Router::with_fallback(hdl.clone())
+        .route::<PingReq, _>(FixedClock)
+        .route::<UnsubscribeKeysReq, _>(FixedClock)
+        .route::<NodeInfo, _>(hdl.clone());
+    assert_eq!(
+        router.call(ping.into()).await.unwrap(),
+        RespMessage::Pong(PongResp {
+            nonce: 7,
+            sent_time: 1,
+            received_time: 42,
+        })
+    );
+    assert!(matches!(
+        router
+            .call(UnsubscribeKeysReq { keys: Vec::new() }.into())
+            .await,
+        Ok(RespMessage::Error(ErrorResp {
+            code: ErrorCode::USAGE_DENIED,
+            ..
+        }))
+    ));
+    assert!(matches!(
+        router.call(NodeInfo::default().into()).await,
+        Ok(RespMessage::Connect(resp)) if resp.compatible
+    ));
+    // the other requests are answered by the endpoint
+    assert!(matches!(
+        router.call(PreIdentifyReq {}.into()).await,
+        Ok(RespMessage::PreIdentify(_))
+    ));
+    assert!(matches!(
+        router
+            .call(
+                KeysExistsReq {
+                    keys: Vec::new(),
+                    notify: false,
+                }
+                .into()
+            )
+            .await,
+        Ok(RespMessage::KeysExists(_))
+    ));
+}
+
+#[tokio::test]
+async fn bans() {
+    let (a, b, c) = (
+        KeyPair::generate(),
+        KeyPair::generate(),
+        KeyPair::generate(),
+    );
+    let server_hdl = ServerHandle::new_hdl();
+    let a_hdl =
+        InboundEndpoint::server_hdl(0, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    let b_hdl =
+        InboundEndpoint::server_hdl(1, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    identify(&a_hdl, &a.private).await;
+    identify(&b_hdl, &b.private).await;
+    let bans = server_hdl.bans();
+
+    // a banned key can neither communicate nor identify again
+    bans.insert(BanTarget::Key(a.public), None).await;
+    let req = || CommunicationReq {
+        from: a.public,
+        to: b.public,
+        grant: None,
+        hops: 0,
+    };
+    let err = a_hdl.communicate(req()).await.unwrap_err();
+    assert!(matches!(err, CommunicationReqError::Banned(_)));
+    assert_eq!(ErrorResp::from(err).code, ErrorCode::BANNED);
+    let c_hdl =
+        InboundEndpoint::server_hdl(2, ENDPOINT_INFO, server_hdl.clone(), RecordConn::default());
+    bans.insert(BanTarget::Key(c.public), None).await;
+    let identify_data = c_hdl.pre_identify(PreIdentifyReq {}).await.unwrap();
+    let triad = KeyTriad::gen_signed(
+        &c.private,
+        &identify_data,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .unwrap();
+    assert!(matches!(
+        c_hdl.identify(triad.clone()).await,
+        Err(IdentifyReqError::Banned(_))
+    ));
+    assert!(bans.remove(&BanTarget::Key(a.public)).await);
+    assert!(!bans.remove(&BanTarget::Key(a.public)).await);
+    assert_eq!(a_hdl.communicate(req()).await.unwrap().stream, a.public);
+
+    // ranges ban the addresses they contain, including ones mapped to IPv6
+    let loopback = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let range = IpRange::new(loopback, 8).unwrap();
+    assert_eq!(range.addr(), IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)));
+    assert!(IpRange::new(loopback, 33).is_none());
+    bans.insert(BanTarget::Range(range), Some(crate::utils::now() + 60_000))
+        .await;
+    assert!(server_hdl.admit(ENDPOINT_INFO.endpoint.ip()).await.is_err());
+    assert!(server_hdl
+        .admit(Ipv4Addr::LOCALHOST.to_ipv6_mapped().into())
+        .await
+        .is_err());
+    assert!(server_hdl
+        .admit(Ipv4Addr::new(10, 0, 0, 1).into())
+        .await
+        .is_ok());
+    assert!(matches!(
+        a_hdl.communicate(req()).await,
+        Err(CommunicationReqError::Banned(_))
+    ));
+
+    // expired bans are ignored until they are cleared
+    let expired = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+    bans.insert(BanTarget::Ip(expired), Some(0)).await;
+    assert!(server_hdl.admit(expired).await.is_ok());
+    let listed = bans.list().await;
+    assert_eq!(listed.len(), 2);
+    assert!(listed.contains(&Ban {
+        target: BanTarget::Key(c.public),
+        expire_time: None,
+    }));
+    assert_eq!(server_hdl.reap().await.bans, 1);
+    assert_eq!(bans.list().await.len(), 2);
+}
@@ -0,0 +1,55 @@
+use super::*;
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the usage `key` declared the last time it identified.
+    pub async fn key_usage(&self, key: &PublicKey) -> KeyUsage {
+        self.usage
+            .read_async(key, |_, usage| *usage)
+            .await
+            .unwrap_or_default()
+    }
+    /// Records the usage `key` declared when it identified, replacing the usage it declared
+    /// before.
+    pub(crate) async fn set_usage(&self, key: PublicKey, usage: KeyUsage) {
+        if usage.is_empty() {
+            self.usage.remove_async(&key).await;
+            return;
+        }
+        self.usage
+            .entry_async(key)
+            .await
+            .and_modify(|value| *value = usage)
+            .or_insert(usage);
+    }
+    /// Returns whether `key` identified as [`KeyUsage::UNLISTED`] the last time it identified.
+    pub async fn is_unlisted(&self, key: &PublicKey) -> bool {
+        self.key_usage(key).await.contains(KeyUsage::UNLISTED)
+    }
+    /// Returns the keys that identified as [`KeyUsage::UNLISTED`].
+    pub(crate) async fn unlisted(&self) -> HashSet<PublicKey> {
+        let mut unlisted = HashSet::new();
+        self.usage
+            .scan_async(|key, usage| {
+                if usage.contains(KeyUsage::UNLISTED) {
+                    unlisted.insert(*key);
+                }
+            })
+            .await;
+        unlisted
+    }
+    /// Returns whether `from` may learn that `to` is identified: `to` is listed, or `grant` allows
+    /// `from` to reach it.
+    pub(crate) async fn may_see(
+        &self,
+        from: &PublicKey,
+        to: &PublicKey,
+        grant: Option<&KeyTriad<ContactGrant>>,
+    ) -> bool {
+        !self.is_unlisted(to).await
+            || grant.is_some_and(|grant| grant.allows(from, to, utils::now()))
+    }
+    /// Returns whether the usage of `from` and `to` allows `from` to reach `to`.
+    pub(crate) async fn may_reach(&self, from: &PublicKey, to: &PublicKey) -> bool {
+        self.key_usage(from).await.can_initiate() && self.key_usage(to).await.accepts_incoming()
+    }
+}
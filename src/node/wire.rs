@@ -0,0 +1,136 @@
+use tower_async::Service;
+
+use super::*;
+use crate::CURRENT_VERSION;
+
+/// Returns the features this node supports.
+pub fn supported_features() -> Features {
+    let mut features = Features::from_iter([
+        Features::COMPRESSION,
+        Features::COMPACT,
+        Features::SCHNORR,
+        Features::SHA256,
+        Features::FEDERATED_LOOKUP,
+        Features::MAILBOX,
+        Features::RECEIPTS,
+    ]);
+    if cfg!(feature = "protobuf") {
+        features.insert(Features::PROTOBUF);
+    }
+    features
+}
+
+impl<C: ?Sized> ServerHandle<C> {
+    /// Returns the info this node answers [`ReqMessage::Connect`] with.
+    pub fn node_info(&self) -> NodeInfo {
+        NodeInfo {
+            api_version: CURRENT_VERSION,
+            features: supported_features(),
+            load: Some(self.public_load()),
+        }
+    }
+    /// Returns the goodbye to send an endpoint before closing its connection for `code`.
+    ///
+    /// Endpoints that are rate limited or banned are sent a [`KeepDown`] signed by the node, with
+    /// the delay of [`NodeConfig::keep_down`], so that clients do not reconnect right away.
+    pub fn dismiss(&self, code: GoodbyeCode, reason: impl ToString) -> Goodbye {
+        let goodbye = Goodbye::new(code, reason);
+        let delay = match code {
+            GoodbyeCode::RATE_LIMITED => self.config.keep_down.rate_limited,
+            GoodbyeCode::BANNED => self.config.keep_down.banned,
+            _ => return goodbye,
+        };
+
+        goodbye.with_keep_down(KeyTriad::keep_down(&self.key, delay, utils::now()))
+    }
+}
+
+impl<C: Notify + Send + Sync + 'static + ?Sized> InboundEndpoint<C> {
+    /// Answers `req`, with an [`ErrorResp`] if it failed.
+    pub async fn respond(self: &Arc<Self>, req: ReqMessage) -> RespMessage {
+        self.call(req)
+            .await
+            .unwrap_or_else(|err| ErrorResp::from(err).into())
+    }
+}
+
+/// Answers the [`ReqMessage::Connect`] of an endpoint with the info of this node, and the features
+/// both ends support.
+impl<C: ?Sized> Service<NodeInfo> for InboundHdl<C> {
+    type Response = NodeInfoResp;
+    type Error = Infallible;
+
+    async fn call(&self, peer: NodeInfo) -> Result<Self::Response, Self::Error> {
+        let compatible = peer.api_version == CURRENT_VERSION;
+        let info = match self.server_hdl.as_ref().and_then(Weak::upgrade) {
+            Some(server_hdl) => {
+                if let Some(load) = peer.load {
+                    server_hdl.record_peer_load(self, load).await;
+                }
+                server_hdl.node_info()
+            }
+            None => NodeInfo {
+                api_version: CURRENT_VERSION,
+                features: supported_features(),
+                load: None,
+            },
+        };
+        let features = info.negotiate(&peer);
+        *self.features.write().await = features.clone();
+        Ok(NodeInfoResp {
+            compatible,
+            info,
+            features,
+        })
+    }
+}
+
+/// Answers the requests received on the wire, by calling the service of the request.
+///
+/// Requests whose services respond with nothing, such as [`ReqMessage::Revoke`], are answered with
+/// [`RespMessage::Ack`]. [`ReqMessage::Communication`] needs a connection that can open streams,
+/// and [`ReqMessage::Reconcile`] one that can look keys up on the peer; they are rejected with
+/// [`WireReqError::Unsupported`], and are served by the routes of a [`Router`].
+impl<C: Notify + Send + Sync + 'static + ?Sized> Service<ReqMessage> for InboundHdl<C> {
+    type Response = RespMessage;
+    type Error = WireReqError;
+
+    async fn call(&self, req: ReqMessage) -> Result<Self::Response, Self::Error> {
+        if let Some(limiter) = &self.limiter {
+            limiter
+                .acquire(utils::now())
+                .map_err(|retry_after| RateLimitedError { retry_after })?;
+        }
+
+        Ok(match req {
+            ReqMessage::Connect(req) => {
+                let Ok(resp) = self.call(req).await;
+                resp.into()
+            }
+            ReqMessage::PreIdentify(req) => (**self).call(req).await?.into(),
+            ReqMessage::Identify(req) => self.call(req).await?.into(),
+            ReqMessage::Revoke(req) => self.call(req).await?.into(),
+            ReqMessage::KeysExists(req) => self.call(req).await?.into(),
+            ReqMessage::UnsubscribeKeys(req) => self.call(req).await?.into(),
+            ReqMessage::ListConnectedServers(req) => self.call(req).await?.into(),
+            ReqMessage::Ping(req) => {
+                let Ok(resp) = (**self).call(req).await;
+                resp.into()
+            }
+            ReqMessage::Goodbye(req) => {
+                let Ok(()) = (**self).call(req).await;
+                Goodbye::new(GoodbyeCode::NORMAL, "goodbye").into()
+            }
+            ReqMessage::Broadcast(req) => self.call(req).await?.into(),
+            ReqMessage::Relay(req) => self.call(req).await?.into(),
+            ReqMessage::FetchMail(req) => self.call(req).await?.into(),
+            ReqMessage::SendReceipt(req) => self.call(req).await?.into(),
+            ReqMessage::Receipt(req) => self.call(req).await?.into(),
+            ReqMessage::PublishPresence(req) => self.call(req).await?.into(),
+            ReqMessage::CrossSign(req) => self.call(req).await?.into(),
+            ReqMessage::Journal(req) => self.call(req).await?.into(),
+            ReqMessage::DisconnectNotice(req) => self.call(req).await?.into(),
+            req => return Err(WireReqError::Unsupported(req.object_type())),
+        })
+    }
+}
@@ -0,0 +1,73 @@
+//! Canonical CBOR, in which every value has exactly one encoding: map keys are sorted, lengths
+//! are definite, and integers and floats take as few bytes as possible. Signables are serialized
+//! canonically, so that another implementation serializing the same signable produces the same
+//! bytes.
+
+use serde::Serialize;
+use serde_cbor::Value;
+
+/// Serializes `value` to canonical CBOR. Maps are sorted by the length of their keys first, and
+/// then by the keys themselves, as specified by RFC 7049.
+pub fn to_canonical_cbor<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, serde_cbor::Error> {
+    // values hold maps sorted in canonical order, and know the lengths of their collections
+    serde_cbor::to_vec(&serde_cbor::value::to_value(value)?)
+}
+
+/// Returns whether `bytes` is a single value in canonical CBOR.
+pub fn is_canonical_cbor(bytes: &[u8]) -> bool {
+    serde_cbor::from_slice::<Value>(bytes)
+        .ok()
+        .and_then(|value| serde_cbor::to_vec(&value).ok())
+        .is_some_and(|canonical| canonical == bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Unsorted {
+        zz: u8,
+        a: u64,
+        bb: Vec<u8>,
+    }
+
+    #[test]
+    fn canonical() {
+        let value = Unsorted {
+            zz: 1,
+            a: 2,
+            bb: vec![3],
+        };
+        let bytes = to_canonical_cbor(&value).unwrap();
+        assert!(is_canonical_cbor(&bytes));
+
+        // keys are sorted by length, then lexically
+        let keys: Vec<_> = match serde_cbor::from_slice::<Value>(&bytes).unwrap() {
+            Value::Map(map) => map.into_keys().collect(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            keys,
+            vec![
+                Value::Text("a".into()),
+                Value::Text("bb".into()),
+                Value::Text("zz".into())
+            ]
+        );
+        // unlike in the field order serde_cbor writes structs in
+        let legacy = serde_cbor::to_vec(&value).unwrap();
+        assert_ne!(legacy, bytes);
+        assert!(!is_canonical_cbor(&legacy));
+
+        // the order of a map does not change its encoding
+        let a: HashMap<_, _> = (0..32u8).map(|i| (i, i)).collect();
+        let b: HashMap<_, _> = (0..32u8).rev().map(|i| (i, i)).collect();
+        assert_eq!(
+            to_canonical_cbor(&a).unwrap(),
+            to_canonical_cbor(&b).unwrap()
+        );
+    }
+}
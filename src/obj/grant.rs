@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{HashMsg, KeyTriad, PrivateKey, PublicKey, ToHashMsg};
+
+/// Prefixes the hash of a [`ContactGrant`] before it is signed.
+const CONTACT_GRANT_PREFIX: u8 = 12;
+
+/// A permission a key that identified as [`KeyUsage::UNLISTED`](super::KeyUsage::UNLISTED) gives
+/// another key to reach it. Is signed by the unlisted key, and presented along with a
+/// [`CommunicationReq`](super::CommunicationReq).
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct ContactGrant {
+    /// The key allowed to reach the signer.
+    pub grantee: PublicKey,
+    /// The time after which the grant is no longer honored.
+    #[serde(rename = "expireTime")]
+    pub expire_time: u64,
+}
+
+impl ToHashMsg for &ContactGrant {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[CONTACT_GRANT_PREFIX]);
+        hasher.update(&self.grantee.0);
+        hasher.update(&self.expire_time.to_be_bytes());
+        HashMsg(hasher.finalize().into())
+    }
+}
+
+impl KeyTriad<ContactGrant> {
+    /// Signs a grant of `key` allowing `grantee` to reach it until `expire_time`.
+    pub fn grant(key: &PrivateKey, grantee: PublicKey, expire_time: u64) -> Self {
+        let grant = ContactGrant {
+            grantee,
+            expire_time,
+        };
+        Self::signed_by(key, grant)
+    }
+    /// Returns whether this grant allows `from` to reach `to` at `now`.
+    pub fn allows(&self, from: &PublicKey, to: &PublicKey, now: u64) -> bool {
+        self.public_key == *to
+            && self.signed.grantee == *from
+            && now <= self.signed.expire_time
+            && self.valid()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn grant() {
+        let owner = KeyPair::generate();
+        let grantee = KeyPair::generate();
+        let other = KeyPair::generate();
+        let grant = KeyTriad::grant(&owner.private, grantee.public, 1000);
+
+        assert!(grant.allows(&grantee.public, &owner.public, 1000));
+        assert!(!grant.allows(&grantee.public, &owner.public, 1001));
+        assert!(!grant.allows(&other.public, &owner.public, 0));
+        assert!(!grant.allows(&grantee.public, &other.public, 0));
+
+        // the grantee is signed over
+        let mut forged = grant;
+        forged.signed.grantee = other.public;
+        assert!(!forged.allows(&other.public, &owner.public, 0));
+    }
+}
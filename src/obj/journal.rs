@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use super::{KeyUsage, Mail, MessageId};
+use crate::crypto::PublicKey;
+
+/// A point in the journal of a node. The epoch is drawn at random when the journal starts, such
+/// as when the node starts or a standby is promoted, so that positions of different journals are
+/// never compared.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Hash,
+)]
+pub struct JournalPosition {
+    pub epoch: u64,
+    /// The sequence number of the latest entry, or 0 if there was none.
+    pub seq: u64,
+}
+
+/// A write to the state of a node that a standby replicates.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[serde(tag = "type")]
+pub enum JournalEntry {
+    /// A public key identified to the node.
+    #[serde(rename = "IDENTIFIED", alias = "identified")]
+    Identified {
+        #[serde(rename = "publicKey")]
+        public_key: PublicKey,
+        usage: KeyUsage,
+        time: u64,
+    },
+    /// A public key was revoked.
+    #[serde(rename = "REVOKED", alias = "revoked")]
+    Revoked {
+        #[serde(rename = "publicKey")]
+        public_key: PublicKey,
+    },
+    /// An endpoint identified as `subscriber` asked to be notified when `keys` connect.
+    #[serde(rename = "SUBSCRIBED", alias = "subscribed")]
+    Subscribed {
+        subscriber: PublicKey,
+        keys: Vec<PublicKey>,
+    },
+    /// An endpoint identified as `subscriber` no longer waits for `keys`.
+    #[serde(rename = "UNSUBSCRIBED", alias = "unsubscribed")]
+    Unsubscribed {
+        subscriber: PublicKey,
+        keys: Vec<PublicKey>,
+    },
+    /// A message was stored in the mailbox of `to`.
+    #[serde(rename = "DEPOSITED", alias = "deposited")]
+    Deposited { to: PublicKey, mail: Mail },
+    /// Messages of the mailbox of `to` were delivered, and dropped from it.
+    #[serde(rename = "DELIVERED", alias = "delivered")]
+    Delivered { to: PublicKey, ids: Vec<MessageId> },
+    /// The mailbox of `to` was fetched, and emptied.
+    #[serde(rename = "FETCHED", alias = "fetched")]
+    Fetched { to: PublicKey },
+}
+
+/// An entry of the journal of a node, along with its sequence number.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct JournalRecord {
+    pub seq: u64,
+    pub entry: JournalEntry,
+}
+
+/// A request of a standby for the entries of the journal of its primary after `since`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct JournalReq {
+    pub since: JournalPosition,
+    /// The most entries to return. Is [`None`] to let the primary decide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<u32>,
+}
+
+/// A response to a [`JournalReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct JournalResp {
+    /// The position of the last record returned, which the next request continues from.
+    pub position: JournalPosition,
+    /// The records after the requested position, oldest first.
+    pub records: Vec<JournalRecord>,
+    /// Whether the records are a snapshot of the whole state of the primary, rather than the
+    /// records after the requested position. A snapshot is sent when the primary no longer holds
+    /// those records, or the position is of another journal, and replaces the state the standby
+    /// replicated so far.
+    #[serde(default)]
+    pub snapshot: bool,
+}
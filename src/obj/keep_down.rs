@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{HashMsg, KeyTriad, PrivateKey, ToHashMsg};
+
+/// Prefixes the hash of a [`KeepDown`] before it is signed.
+const KEEP_DOWN_PREFIX: u8 = 11;
+
+/// A directive from a node to an endpoint it disconnects, not to reconnect before some delay
+/// passed. Is signed by the node, and attached to a [`Goodbye`](super::Goodbye).
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct KeepDown {
+    /// How long the endpoint should wait before reconnecting, in milliseconds since `time`.
+    #[serde(rename = "minDelay")]
+    pub min_delay: u64,
+    /// The time the directive was signed.
+    pub time: u64,
+}
+
+impl KeepDown {
+    /// Returns the time before which the endpoint should not reconnect.
+    pub fn until(&self) -> u64 {
+        self.time.saturating_add(self.min_delay)
+    }
+    /// Returns how long the endpoint should still wait at `now`.
+    pub fn remaining(&self, now: u64) -> Duration {
+        Duration::from_millis(self.until().saturating_sub(now))
+    }
+}
+
+impl ToHashMsg for &KeepDown {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[KEEP_DOWN_PREFIX]);
+        hasher.update(&self.min_delay.to_be_bytes());
+        hasher.update(&self.time.to_be_bytes());
+        HashMsg(hasher.finalize().into())
+    }
+}
+
+impl KeyTriad<KeepDown> {
+    /// Signs a directive of `key` not to reconnect for `min_delay` after the time `time`.
+    pub fn keep_down(key: &PrivateKey, min_delay: Duration, time: u64) -> Self {
+        let keep_down = KeepDown {
+            min_delay: min_delay.as_millis() as u64,
+            time,
+        };
+        Self::signed_by(key, keep_down)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn keep_down() {
+        let key = KeyPair::generate();
+        let keep_down = KeyTriad::keep_down(&key.private, Duration::from_secs(30), 1000);
+        assert!(keep_down.valid());
+        assert_eq!(keep_down.signed.until(), 31_000);
+        assert_eq!(keep_down.signed.remaining(21_000), Duration::from_secs(10));
+        assert_eq!(keep_down.signed.remaining(40_000), Duration::ZERO);
+
+        // the delay is signed over
+        let mut forged = keep_down;
+        forged.signed.min_delay = 0;
+        assert!(!forged.valid());
+    }
+}
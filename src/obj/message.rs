@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use super::*;
+use crate::CURRENT_VERSION;
 
 pub trait ObjectType {
     fn object_type(&self) -> &'static str;
@@ -18,9 +19,9 @@ macro_rules! convert_impl {
         convert_impl!($for, $name, $msg, $variant, no_obj_impl);
     };
     ($for:ty, $name: expr, $msg:ident, $variant:ident, no_obj_impl) => {
-        impl Into<$msg> for $for {
-            fn into(self) -> $msg {
-                $msg::$variant(self)
+        impl From<$for> for $msg {
+            fn from(value: $for) -> $msg {
+                $msg::$variant(value)
             }
         }
         impl TryFrom<$msg> for $for {
@@ -29,7 +30,7 @@ macro_rules! convert_impl {
             fn try_from(value: $msg) -> Result<Self, Self::Error> {
                 match value {
                     $msg::$variant(v) => Ok(v),
-                    value @ _ => Err(InvalidTypeError {
+                    value => Err(InvalidTypeError {
                         expected: $name,
                         received: value.object_type(),
                     }),
@@ -48,12 +49,48 @@ pub struct InvalidTypeError {
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub enum ReqMessage {
-    #[serde(rename = "NODE_INFO")]
+    #[serde(rename = "NODE_INFO", alias = "nodeInfo")]
     Connect(NodeInfo),
-    #[serde(rename = "PRE_IDENTIFY")]
+    #[serde(rename = "PRE_IDENTIFY", alias = "preIdentify")]
     PreIdentify(PreIdentifyReq),
-    #[serde(rename = "IDENTIFY")]
+    #[serde(rename = "IDENTIFY", alias = "identify")]
     Identify(IdentifyReq),
+    #[serde(rename = "REVOKE", alias = "revoke")]
+    Revoke(RevokeReq),
+    #[serde(rename = "KEYS_EXISTS", alias = "keysExists")]
+    KeysExists(KeysExistsReq),
+    #[serde(rename = "UNSUBSCRIBE_KEYS", alias = "unsubscribeKeys")]
+    UnsubscribeKeys(UnsubscribeKeysReq),
+    /// Asks to open a stream to another public key. The stream is the response.
+    #[serde(rename = "COMMUNICATION", alias = "communication")]
+    Communication(CommunicationReq),
+    #[serde(rename = "LIST_CONNECTED_SERVERS", alias = "listConnectedServers")]
+    ListConnectedServers(ListConnectedServersReq),
+    #[serde(rename = "PING", alias = "ping")]
+    Ping(PingReq),
+    /// The endpoint is about to close the connection.
+    #[serde(rename = "GOODBYE", alias = "goodbye")]
+    Goodbye(Goodbye),
+    #[serde(rename = "BROADCAST", alias = "broadcast")]
+    Broadcast(BroadcastReq),
+    #[serde(rename = "RELAY", alias = "relay")]
+    Relay(RelayMessage),
+    #[serde(rename = "RECONCILE", alias = "reconcile")]
+    Reconcile(ReconcileReq),
+    #[serde(rename = "FETCH_MAIL", alias = "fetchMail")]
+    FetchMail(FetchMailReq),
+    #[serde(rename = "SEND_RECEIPT", alias = "sendReceipt")]
+    SendReceipt(SendReceiptReq),
+    #[serde(rename = "RECEIPT", alias = "receipt")]
+    Receipt(ReceiptReq),
+    #[serde(rename = "PUBLISH_PRESENCE", alias = "publishPresence")]
+    PublishPresence(PublishPresenceReq),
+    #[serde(rename = "CROSS_SIGN", alias = "crossSign")]
+    CrossSign(CrossSignReq),
+    #[serde(rename = "JOURNAL", alias = "journal")]
+    Journal(JournalReq),
+    #[serde(rename = "DISCONNECT_NOTICE", alias = "disconnectNotice")]
+    DisconnectNotice(DisconnectNoticeReq),
 }
 
 impl ObjectType for ReqMessage {
@@ -62,28 +99,196 @@ impl ObjectType for ReqMessage {
             Self::Connect(v) => v.object_type(),
             Self::Identify(v) => v.object_type(),
             Self::PreIdentify(v) => v.object_type(),
+            Self::Revoke(v) => v.object_type(),
+            Self::KeysExists(v) => v.object_type(),
+            Self::UnsubscribeKeys(v) => v.object_type(),
+            Self::Communication(v) => v.object_type(),
+            Self::ListConnectedServers(v) => v.object_type(),
+            Self::Ping(v) => v.object_type(),
+            Self::Goodbye(v) => v.object_type(),
+            Self::Broadcast(v) => v.object_type(),
+            Self::Relay(v) => v.object_type(),
+            Self::Reconcile(v) => v.object_type(),
+            Self::FetchMail(v) => v.object_type(),
+            Self::SendReceipt(v) => v.object_type(),
+            Self::Receipt(v) => v.object_type(),
+            Self::PublishPresence(v) => v.object_type(),
+            Self::CrossSign(v) => v.object_type(),
+            Self::Journal(v) => v.object_type(),
+            Self::DisconnectNotice(v) => v.object_type(),
         }
     }
 }
 convert_impl!(NodeInfo, "NODE_INFO", ReqMessage, Connect);
 convert_impl!(IdentifyReq, "IDENTIFY", ReqMessage, Identify);
 convert_impl!(PreIdentifyReq, "PRE_IDENTIFY", ReqMessage, PreIdentify);
+convert_impl!(RevokeReq, "REVOKE", ReqMessage, Revoke);
+convert_impl!(KeysExistsReq, "KEYS_EXISTS", ReqMessage, KeysExists);
+convert_impl!(
+    UnsubscribeKeysReq,
+    "UNSUBSCRIBE_KEYS",
+    ReqMessage,
+    UnsubscribeKeys
+);
+convert_impl!(CommunicationReq, "COMMUNICATION", ReqMessage, Communication);
+convert_impl!(
+    ListConnectedServersReq,
+    "LIST_CONNECTED_SERVERS",
+    ReqMessage,
+    ListConnectedServers
+);
+convert_impl!(PingReq, "PING", ReqMessage, Ping);
+convert_impl!(Goodbye, "GOODBYE", ReqMessage, Goodbye);
+convert_impl!(BroadcastReq, "BROADCAST", ReqMessage, Broadcast);
+convert_impl!(RelayMessage, "RELAY", ReqMessage, Relay);
+convert_impl!(ReconcileReq, "RECONCILE", ReqMessage, Reconcile);
+convert_impl!(FetchMailReq, "FETCH_MAIL", ReqMessage, FetchMail);
+convert_impl!(SendReceiptReq, "SEND_RECEIPT", ReqMessage, SendReceipt);
+convert_impl!(ReceiptReq, "RECEIPT", ReqMessage, Receipt);
+convert_impl!(
+    PublishPresenceReq,
+    "PUBLISH_PRESENCE",
+    ReqMessage,
+    PublishPresence
+);
+convert_impl!(CrossSignReq, "CROSS_SIGN", ReqMessage, CrossSign);
+convert_impl!(JournalReq, "JOURNAL", ReqMessage, Journal);
+convert_impl!(
+    DisconnectNoticeReq,
+    "DISCONNECT_NOTICE",
+    ReqMessage,
+    DisconnectNotice
+);
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub enum RespMessage {
-    #[serde(rename = "NODE_INFO")]
+    #[serde(rename = "NODE_INFO", alias = "nodeInfo")]
     Connect(NodeInfoResp),
-    #[serde(rename = "IDENTIFY")]
+    #[serde(rename = "PRE_IDENTIFY", alias = "preIdentify")]
+    PreIdentify(IdentifyData),
+    #[serde(rename = "IDENTIFY", alias = "identify")]
     Identify(IdentifyResp),
+    #[serde(rename = "KEYS_EXISTS", alias = "keysExists")]
+    KeysExists(KeysExistsResp),
+    #[serde(rename = "UNSUBSCRIBE_KEYS", alias = "unsubscribeKeys")]
+    UnsubscribeKeys(UnsubscribeKeysResp),
+    #[serde(rename = "LIST_CONNECTED_SERVERS", alias = "listConnectedServers")]
+    ListConnectedServers(ListConnectedServersResp),
+    #[serde(rename = "PONG", alias = "pong")]
+    Pong(PongResp),
+    /// The node is about to close the connection. Is sent unsolicited, or in answer to a
+    /// goodbye of the endpoint.
+    #[serde(rename = "GOODBYE", alias = "goodbye")]
+    Goodbye(Goodbye),
+    /// The request failed.
+    #[serde(rename = "ERROR", alias = "error")]
+    Error(ErrorResp),
+    /// The request succeeded, and has nothing to answer with.
+    #[serde(rename = "ACK", alias = "ack")]
+    Ack(AckResp),
+    #[serde(rename = "BROADCAST", alias = "broadcast")]
+    Broadcast(BroadcastResp),
+    #[serde(rename = "RELAY", alias = "relay")]
+    Relay(RelayResp),
+    #[serde(rename = "RECONCILE", alias = "reconcile")]
+    Reconcile(ReconcileResp),
+    #[serde(rename = "FETCH_MAIL", alias = "fetchMail")]
+    FetchMail(FetchMailResp),
+    #[serde(rename = "RECEIPT", alias = "receipt")]
+    Receipt(ReceiptResp),
+    #[serde(rename = "CROSS_SIGN", alias = "crossSign")]
+    CrossSign(CrossSignResp),
+    #[serde(rename = "JOURNAL", alias = "journal")]
+    Journal(JournalResp),
 }
 
 impl ObjectType for RespMessage {
     fn object_type(&self) -> &'static str {
         match self {
             Self::Connect(v) => v.object_type(),
+            Self::PreIdentify(v) => v.object_type(),
             Self::Identify(v) => v.object_type(),
+            Self::KeysExists(v) => v.object_type(),
+            Self::UnsubscribeKeys(v) => v.object_type(),
+            Self::ListConnectedServers(v) => v.object_type(),
+            Self::Pong(v) => v.object_type(),
+            Self::Goodbye(v) => v.object_type(),
+            Self::Error(v) => v.object_type(),
+            Self::Ack(v) => v.object_type(),
+            Self::Broadcast(v) => v.object_type(),
+            Self::Relay(v) => v.object_type(),
+            Self::Reconcile(v) => v.object_type(),
+            Self::FetchMail(v) => v.object_type(),
+            Self::Receipt(v) => v.object_type(),
+            Self::CrossSign(v) => v.object_type(),
+            Self::Journal(v) => v.object_type(),
         }
     }
 }
 convert_impl!(NodeInfoResp, "NODE_INFO", RespMessage, Connect);
+convert_impl!(IdentifyData, "PRE_IDENTIFY", RespMessage, PreIdentify);
 convert_impl!(IdentifyResp, "IDENTIFY", RespMessage, Identify);
+convert_impl!(KeysExistsResp, "KEYS_EXISTS", RespMessage, KeysExists);
+convert_impl!(
+    UnsubscribeKeysResp,
+    "UNSUBSCRIBE_KEYS",
+    RespMessage,
+    UnsubscribeKeys
+);
+convert_impl!(
+    ListConnectedServersResp,
+    "LIST_CONNECTED_SERVERS",
+    RespMessage,
+    ListConnectedServers
+);
+convert_impl!(PongResp, "PONG", RespMessage, Pong);
+convert_impl!(Goodbye, "GOODBYE", RespMessage, Goodbye, no_obj_impl);
+convert_impl!(ErrorResp, "ERROR", RespMessage, Error);
+convert_impl!(AckResp, "ACK", RespMessage, Ack);
+convert_impl!(BroadcastResp, "BROADCAST", RespMessage, Broadcast);
+convert_impl!(RelayResp, "RELAY", RespMessage, Relay);
+convert_impl!(ReconcileResp, "RECONCILE", RespMessage, Reconcile);
+convert_impl!(FetchMailResp, "FETCH_MAIL", RespMessage, FetchMail);
+convert_impl!(ReceiptResp, "RECEIPT", RespMessage, Receipt);
+convert_impl!(CrossSignResp, "CROSS_SIGN", RespMessage, CrossSign);
+convert_impl!(JournalResp, "JOURNAL", RespMessage, Journal);
+
+/// Answers the requests whose services respond with nothing.
+impl From<()> for RespMessage {
+    fn from(_: ()) -> Self {
+        Self::Ack(AckResp {})
+    }
+}
+
+/// A message tagged with the id its sender chose for it. A response carries the id of the request
+/// it answers.
+///
+/// The envelope carries the version of the protocol the sender encoded the body with, so that a
+/// receiver can tell which shape of a message it was sent. Fields unknown to the receiver are
+/// ignored and fields missing from the message take their default, so that messages of older and
+/// newer peers of the same major version decode alike.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Tagged<T> {
+    pub id: u64,
+    /// The version of the protocol of the sender, or [`None`] if the sender predates versioned
+    /// envelopes.
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    pub body: T,
+}
+
+impl<T> Tagged<T> {
+    /// Tags `body` with `id`, in an envelope of the current version.
+    pub fn new(id: u64, body: T) -> Self {
+        Self {
+            id,
+            version: Some(CURRENT_VERSION),
+            body,
+        }
+    }
+    /// Returns whether the body was encoded with the [`CURRENT_VERSION`] of the protocol. An
+    /// envelope without a version predates versioned envelopes, and is of version 0.
+    pub fn is_compatible(&self) -> bool {
+        self.version.unwrap_or(0) == CURRENT_VERSION
+    }
+}
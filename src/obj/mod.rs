@@ -1,33 +1,244 @@
+mod canonical;
+mod grant;
+mod journal;
+mod keep_down;
 mod message;
+mod positional;
+mod presence;
+mod profile;
+#[cfg(feature = "protobuf")]
+pub mod proto;
+mod receipt;
 mod signables;
 
-use core::net::{IpAddr, SocketAddr};
+use core::net::SocketAddr;
+use std::collections::BTreeSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 use arcstr::ArcStr;
+pub use canonical::*;
+pub use grant::*;
+pub use journal::*;
+pub use keep_down::*;
 pub use message::*;
-use serde::{Deserialize, Serialize};
+pub(crate) use positional::{
+    from_postcard, to_postcard, PositionalTaggedReq, PositionalTaggedResp,
+};
+pub use presence::*;
+pub use profile::*;
+pub use receipt::*;
+use serde::de::{Error as _, SeqAccess, Unexpected, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 pub use signables::*;
+use subtle::{Choice, ConstantTimeEq};
+use thiserror::Error;
 
-use crate::crypto::{KeyTriad, PublicKey};
+use crate::crypto::{
+    keyset::{KeyProof, KeysRoot, Reconciliation},
+    log::{LogHead, LogProof},
+    merkle::hash_leaf,
+    multi::MultiKeyTriad,
+    recover::RecoverableTriad,
+    schnorr::SignatureScheme,
+    token::Token,
+    HashAlgorithm, HashMsg, KeyTriad, PublicKey,
+};
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct IdentifyReq {
     pub keys: Vec<KeyTriad<SignedData>>,
+    /// Triads whose public keys are recovered from their signatures, to save space.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compact: Vec<RecoverableTriad<SignedData>>,
+    /// Triads signed by the threshold of a group of keys, that identify as the key that signed
+    /// the group.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub multi: Vec<MultiKeyTriad<SignedData>>,
+    /// Triads of keys that identify as the root of their delegation chains.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delegated: Vec<DelegatedTriad>,
 }
 
+/// A triad signed by a delegated key, and the chain of delegations that authorizes the key to
+/// identify as the root of the chain.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
-pub struct IdentifyResp {}
+pub struct DelegatedTriad {
+    pub triad: KeyTriad<SignedData>,
+    /// Triads of [`Delegation`](crate::crypto::delegation::Delegation)s, from the root key to the
+    /// key of the triad.
+    pub chain: Vec<KeyTriad<SignedData>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct IdentifyResp {
+    /// The token that lets a new connection of this client take over this connection.
+    #[serde(rename = "resumptionToken")]
+    pub resumption_token: ResumptionToken,
+}
+
+/// A secret token issued to an identified endpoint, that a new connection of the same client can
+/// present to take over the identities and subscriptions of the old connection.
+///
+/// Tokens are compared in constant time.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialOrd, Ord, Debug)]
+#[serde(transparent)]
+pub struct ResumptionToken(pub Token);
+
+impl ConstantTimeEq for ResumptionToken {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+impl PartialEq for ResumptionToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl Eq for ResumptionToken {}
+impl std::hash::Hash for ResumptionToken {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+/// A request that transfers the identities and subscriptions of the endpoint the token was issued
+/// to, to the endpoint sending this request.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct ResumeReq {
+    pub token: ResumptionToken,
+}
+
+/// A response to a [`ResumeReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct ResumeResp {
+    /// The public keys that were transferred to this endpoint.
+    pub keys: Vec<PublicKey>,
+    /// The resumption token of this endpoint. The presented token is no longer valid.
+    #[serde(rename = "resumptionToken")]
+    pub resumption_token: ResumptionToken,
+}
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct PreIdentifyReq {}
 
+/// A request that checks that the connection is alive, and measures its round trip time.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct PingReq {
+    /// Matches the pong to the ping.
+    pub nonce: u64,
+    /// The time the ping was sent, by the clock of the sender.
+    #[serde(rename = "sentTime")]
+    pub sent_time: u64,
+}
+
+/// Why a side of a connection closes it. Codes are stable across versions of the protocol.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, Hash,
+)]
+#[serde(transparent)]
+pub struct GoodbyeCode(pub u16);
+
+impl GoodbyeCode {
+    /// The side is done with the connection.
+    pub const NORMAL: Self = Self(0);
+    /// The side is shutting down.
+    pub const SHUTDOWN: Self = Self(1);
+    /// The other side was banned, and should not reconnect.
+    pub const BANNED: Self = Self(2);
+    /// The other side violated the protocol.
+    pub const PROTOCOL_ERROR: Self = Self(3);
+    /// The connection was idle for too long.
+    pub const IDLE: Self = Self(4);
+    /// The other side sent more requests than this side allows.
+    pub const RATE_LIMITED: Self = Self(5);
+}
+
+/// A message either side of a connection can send before closing it, so that the other side
+/// learns why. The node answers a goodbye with its own.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Goodbye {
+    pub code: GoodbyeCode,
+    /// A description of why the connection is closed, for humans.
+    pub reason: String,
+    /// How long the other side should wait before reconnecting, signed by the node that closes
+    /// the connection.
+    #[serde(rename = "keepDown", default, skip_serializing_if = "Option::is_none")]
+    pub keep_down: Option<KeyTriad<KeepDown>>,
+}
+
+impl Goodbye {
+    pub fn new(code: GoodbyeCode, reason: impl ToString) -> Self {
+        Self {
+            code,
+            reason: reason.to_string(),
+            keep_down: None,
+        }
+    }
+    /// Attaches a directive not to reconnect before some delay passed.
+    pub fn with_keep_down(mut self, keep_down: KeyTriad<KeepDown>) -> Self {
+        self.keep_down = Some(keep_down);
+        self
+    }
+}
+
+/// A response to a request that has nothing to answer with, such as a [`SendReceiptReq`] or a
+/// [`PublishPresenceReq`], sent once the request succeeded.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct AckResp {}
+
+/// A response to a [`PingReq`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct PongResp {
+    /// The nonce of the ping.
+    pub nonce: u64,
+    /// The time the ping was sent, echoed from the ping.
+    #[serde(rename = "sentTime")]
+    pub sent_time: u64,
+    /// The time the ping was received, by the clock of the receiver.
+    #[serde(rename = "receivedTime")]
+    pub received_time: u64,
+}
+
+impl PongResp {
+    /// Returns the round trip time of the ping, given that this pong was received at `now` by
+    /// the clock of the sender of the ping.
+    pub fn rtt(&self, now: u64) -> Duration {
+        Duration::from_millis(now.saturating_sub(self.sent_time))
+    }
+    /// Estimates how many milliseconds the clock of the receiver of the ping is ahead of the clock
+    /// of its sender, assuming the ping took as long as the pong.
+    pub fn clock_offset(&self, now: u64) -> i64 {
+        let midpoint = self.sent_time as i64 + (self.rtt(now).as_millis() as i64) / 2;
+        self.received_time as i64 - midpoint
+    }
+}
+
+/// A request that revokes a public key. The revocation must be a [`Revocation`] of the key that
+/// signed it, with the [`SignMessageType::Revoke`] message type.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct RevokeReq {
+    pub revocation: KeyTriad<SignedData>,
+}
+
+/// A request a federated server sends to tell the node that public keys disconnected from it, so
+/// that the node stops routing them to the server. Routes the node learned after `time` are kept,
+/// so that a notice that arrives late or more than once does not undo a newer report.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct DisconnectNoticeReq {
+    /// The public keys that disconnected.
+    pub keys: Vec<PublicKey>,
+    /// When the keys disconnected, in milliseconds since the unix epoch.
+    pub time: u64,
+}
+
 /// A request that asks if the specified public keys have connected to the node.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct KeysExistsReq {
     /// The public keys.
     pub keys: Vec<PublicKey>,
     /// If a public key in `keys` has not connected to the node, notify the client when it connects.
+    #[serde(default)]
     pub notify: bool,
 }
 
@@ -36,14 +247,41 @@ pub struct KeysExistsReq {
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct KeysExistsResp {
     pub triads: Vec<KeyTriad<SignedData>>,
+    /// The latest presence published by the public keys of `triads`, for those that published
+    /// any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub presence: Vec<KeyTriad<PresenceUpdate>>,
+}
+
+/// A request that publishes the presence of a public key the endpoint identified as, to be
+/// returned to endpoints that look the key up.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct PublishPresenceReq {
+    pub presence: KeyTriad<PresenceUpdate>,
+}
+
+/// A request that cancels the notifications a [`KeysExistsReq`] asked for.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct UnsubscribeKeysReq {
+    /// The public keys the endpoint should no longer be notified about.
+    pub keys: Vec<PublicKey>,
+}
+
+/// A response to an [`UnsubscribeKeysReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct UnsubscribeKeysResp {
+    /// The public keys of the request the endpoint was subscribed to.
+    pub keys: Vec<PublicKey>,
 }
 
 /// A request that asks if the specified public keys have connected to the node.
 /// If any of the public keys have not connected to the node, sends this request
-/// to other nodes at a depth of `depth - 1`.
+/// to other nodes at a depth of `depth - 1`. The depth bounds how far the request travels, and a
+/// request deeper than a node allows is rejected.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct KeysExistsRReq {
     pub keys: Arc<[PublicKey]>,
+    #[serde(default)]
     pub depth: u32,
 }
 
@@ -71,52 +309,315 @@ pub struct CommunicationReq {
     pub from: PublicKey,
     /// The public key the initiator wants to communicate with.
     pub to: PublicKey,
+    /// The permission of `to` for the initiator to reach it, if `to` is unlisted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant: Option<KeyTriad<ContactGrant>>,
+    /// The amount of servers that relayed the request so far. Is 0 if the initiator sent it to
+    /// the node itself. A request relayed through more servers than the node allows is rejected.
+    #[serde(default)]
+    pub hops: u32,
+}
+
+/// An opaque token issued by a node that introduces two public keys to each other.
+///
+/// Tokens are compared in constant time.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialOrd, Ord, Debug)]
+#[serde(transparent)]
+pub struct DialToken(pub Token);
+
+impl ConstantTimeEq for DialToken {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+impl PartialEq for DialToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl Eq for DialToken {}
+impl std::hash::Hash for DialToken {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
 }
 
-/// A request to list the IP addresses and domain names of the servers that are connected to this node.
+/// A request that asks the node to introduce the initiator to another public key. The node pushes
+/// the resulting [`Introduction`] to the endpoint identified as `to`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct IntroductionReq {
+    /// The public key of the initiator.
+    pub from: PublicKey,
+    /// The public key the initiator wants to be introduced to.
+    pub to: PublicKey,
+}
+
+/// An introduction between two public keys, issued by a node.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Introduction {
+    /// The token either side can present in a [`DialReq`].
+    pub token: DialToken,
+    /// The public key of the initiator.
+    pub from: PublicKey,
+    /// The public key the initiator was introduced to.
+    pub to: PublicKey,
+    /// The expiration timestamp of the token.
+    #[serde(rename = "expireTime")]
+    pub expire_time: u64,
+}
+
+/// A request that opens a stream to the other side of an [`Introduction`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct DialReq {
+    /// The token of the introduction.
+    pub token: DialToken,
+}
+
+/// A request to list the servers that are connected to this node.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct ListConnectedServersReq {
     /// The maximum amount of connected servers to list. Is [`None`] if there is no limit.
     pub max: Option<u32>,
 }
 
-/// A response to a [`ListConnectedServersReq`]. Contains the IP addresses and domain names of the connected servers.
+/// A response to a [`ListConnectedServersReq`]. Contains the domain names of the connected
+/// servers, along with their IP addresses and ports if the node reveals them.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct ListConnectedServersResp {
     pub servers: Vec<ConnectedServer>,
 }
 
+impl ListConnectedServersResp {
+    /// Returns the least loaded server. Servers that did not advertise their load come last.
+    pub fn least_loaded(&self) -> Option<&ConnectedServer> {
+        self.servers
+            .iter()
+            .min_by_key(|server| (server.load.is_none(), server.load))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct ConnectedServer {
-    /// The IP address of the connected server.
-    pub ip: IpAddr,
-    /// The domain name of the connected server.
-    pub domain: ArcStr,
+    /// The address the connected server can be dialed at, at the first port it advertised. Is
+    /// [`None`] if the node does not reveal the address or the ports of the server, or if the
+    /// server advertised no port.
+    #[serde(default, alias = "ip", skip_serializing_if = "Option::is_none")]
+    pub addr: Option<SocketAddr>,
+    /// The domain name of the connected server, and how to connect to it, including the public
+    /// key it advertised.
+    #[serde(flatten)]
+    pub server_info: ServerInfo,
+    /// The load the server advertised the last time it connected, if it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<Load>,
+    /// Whether the server proved that it holds the public key of its [`ServerInfo`], by
+    /// identifying as it on its connection to the node.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub verified: bool,
 }
 
+/// How loaded a node is, so that clients and federated servers can prefer less loaded nodes.
+///
+/// Loads compare by the saturation of the relayed streams first, then by the amount of
+/// connections, then by the amount of identities, so that a lesser load is a less loaded node.
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, Hash,
 )]
+pub struct Load {
+    /// How many of the streams relayed to other servers are in use, in percent.
+    #[serde(rename = "relaySaturation")]
+    pub relay_saturation: u8,
+    /// The amount of open connections.
+    pub connections: u32,
+    /// The amount of identified public keys.
+    pub identities: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, Hash)]
 pub struct NodeInfo {
     /// API version
     #[serde(rename = "apiVersion")]
     pub api_version: u32,
+    /// The optional features this node supports.
+    #[serde(default, skip_serializing_if = "Features::is_empty")]
+    pub features: Features,
+    /// The load of the node when it sent this info, if it advertises it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<Load>,
 }
 
-#[derive(
-    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, Hash,
-)]
+impl NodeInfo {
+    /// Returns the features supported by both this node and `other`, which the connection
+    /// between them may use.
+    pub fn negotiate(&self, other: &NodeInfo) -> Features {
+        self.features.intersection(&other.features)
+    }
+}
+
+/// A set of named optional features of a node. Names the node does not know are kept, so that
+/// they can be negotiated by the nodes that do.
+///
+/// A set received from another node holds at most [`Features::MAX_FEATURES`] names of at most
+/// [`Features::MAX_NAME_LEN`] bytes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Hash)]
+#[serde(transparent)]
+pub struct Features(pub BTreeSet<ArcStr>);
+
+impl Features {
+    /// Frames may be compressed.
+    pub const COMPRESSION: &str = "compression";
+    /// Frames may be encoded with postcard, rather than CBOR.
+    pub const COMPACT: &str = "compact";
+    /// Frames may be encoded with protobuf, as described by `proto/cacophoney.proto`.
+    pub const PROTOBUF: &str = "protobuf";
+    /// The node accepts BIP340 Schnorr signatures.
+    pub const SCHNORR: &str = "schnorr";
+    /// The node accepts signables hashed with SHA-256.
+    pub const SHA256: &str = "sha256";
+    /// The node looks keys up on the servers it is federated with.
+    pub const FEDERATED_LOOKUP: &str = "federated-lookup";
+    /// The node holds messages for public keys until they fetch them.
+    pub const MAILBOX: &str = "mailbox";
+    /// The node routes receipts of messages back to their senders.
+    pub const RECEIPTS: &str = "receipts";
+
+    /// The amount of features a set received from another node can hold.
+    pub const MAX_FEATURES: usize = 64;
+    /// The length of the longest feature name a set received from another node can hold.
+    pub const MAX_NAME_LEN: usize = 64;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn contains(&self, feature: &str) -> bool {
+        self.0.contains(feature)
+    }
+    pub fn insert(&mut self, feature: impl Into<ArcStr>) -> bool {
+        self.0.insert(feature.into())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(ArcStr::as_str)
+    }
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self(self.0.intersection(&other.0).cloned().collect())
+    }
+    /// Returns the signature scheme to sign with when these features were negotiated.
+    pub fn signature_scheme(&self) -> SignatureScheme {
+        match self.contains(Self::SCHNORR) {
+            true => SignatureScheme::Schnorr,
+            false => SignatureScheme::Ecdsa,
+        }
+    }
+    /// Returns the algorithm to hash signables with when these features were negotiated.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        match self.contains(Self::SHA256) {
+            true => HashAlgorithm::Sha256,
+            false => HashAlgorithm::Blake3,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Features {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FeaturesVisitor;
+
+        impl<'de> Visitor<'de> for FeaturesVisitor {
+            type Value = Features;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(
+                    f,
+                    "at most {} feature names of at most {} bytes",
+                    Features::MAX_FEATURES,
+                    Features::MAX_NAME_LEN
+                )
+            }
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut features = Features::new();
+                while let Some(name) = seq.next_element::<ArcStr>()? {
+                    if name.len() > Features::MAX_NAME_LEN
+                        || features.0.len() == Features::MAX_FEATURES
+                    {
+                        return Err(A::Error::invalid_value(Unexpected::Str(&name), &self));
+                    }
+                    features.insert(name);
+                }
+                Ok(features)
+            }
+        }
+
+        deserializer.deserialize_seq(FeaturesVisitor)
+    }
+}
+
+impl<F: Into<ArcStr>> FromIterator<F> for Features {
+    fn from_iter<T: IntoIterator<Item = F>>(iter: T) -> Self {
+        Self(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, Hash)]
 pub struct NodeInfoResp {
     /// If the versions are compatible with each other.
     pub compatible: bool,
     /// The node info sent in response.
     pub info: NodeInfo,
+    /// The features both sides support, which the connection may use. Identify triads sent over
+    /// the connection must be signed with their [`Features::signature_scheme`].
+    #[serde(default, skip_serializing_if = "Features::is_empty")]
+    pub features: Features,
+}
+
+/// A transport a server accepts connections over.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum Transport {
+    #[serde(rename = "TCP", alias = "tcp")]
+    Tcp,
+    #[serde(rename = "QUIC", alias = "quic")]
+    Quic,
+    #[serde(rename = "WS", alias = "ws")]
+    Ws,
+    /// A transport added by a newer version of the protocol, that this node cannot use.
+    #[serde(rename = "UNKNOWN", other)]
+    Unknown,
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
 pub struct ServerInfo {
     /// The domain name of this server.
     pub domain: ArcStr,
+    /// The ports this server listens on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<u16>,
+    /// The transports this server accepts connections over.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transports: Vec<Transport>,
+    /// The API version of this server, if it is known.
+    #[serde(
+        rename = "apiVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub api_version: Option<u32>,
+    /// The public key this server signs with, if it is known.
+    #[serde(rename = "publicKey", default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<PublicKey>,
+}
+
+impl ServerInfo {
+    /// Creates the info of the server of `domain`, with nothing else known about it.
+    pub fn new(domain: ArcStr) -> Self {
+        Self {
+            domain,
+            ports: Vec::new(),
+            transports: Vec::new(),
+            api_version: None,
+            public_key: None,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -134,3 +635,413 @@ impl EndpointInfo {
         }
     }
 }
+
+/// An attestation recorded in the transparency log of a node.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[serde(tag = "type")]
+#[non_exhaustive]
+pub enum LogEntry {
+    /// A public key identified to the node.
+    #[serde(rename = "IDENTIFIED", alias = "identified")]
+    Identified {
+        #[serde(rename = "publicKey")]
+        public_key: PublicKey,
+        time: u64,
+    },
+    /// A public key was revoked.
+    #[serde(rename = "REVOKED", alias = "revoked")]
+    Revoked {
+        #[serde(rename = "publicKey")]
+        public_key: PublicKey,
+        time: u64,
+    },
+}
+
+impl LogEntry {
+    /// Returns the hash of this entry as a leaf of the log.
+    pub fn leaf(&self) -> Result<HashMsg, serde_cbor::Error> {
+        Ok(hash_leaf(serde_cbor::to_vec(self)?))
+    }
+}
+
+/// A request for the signed head of the transparency log of a node, and the proof that the log
+/// grew from `from` entries to the head by appending only.
+///
+/// The first entry of the proof is the entry at index `from`, so the request also proves that the
+/// entry is included in the log. Proofs cover at most
+/// [`SizeLimits::max_proof`](crate::node::SizeLimits::max_proof) entries: if the log grew by more,
+/// the response proves and signs the head the log had that many entries after `from`, and the
+/// rest is requested from there.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct GetLogProofReq {
+    pub from: u64,
+}
+
+/// A response to a [`GetLogProofReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct LogProofResp {
+    /// The head of the log the proof ends at, signed by the node. Is the current head, unless the
+    /// log grew by more entries than a proof may cover.
+    pub head: KeyTriad<LogHead>,
+    pub proof: LogProof,
+    /// The latest heads of the log cosigned by federated servers, that the proof covers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub witnesses: Vec<KeyTriad<KeyTriad<LogHead>>>,
+}
+
+/// A request for the signed root of the set of public keys identified to a node, and proofs that
+/// each of `keys` is or is not in the set.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct KeysRootReq {
+    pub keys: Vec<PublicKey>,
+}
+
+/// A response to a [`KeysRootReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct KeysRootResp {
+    /// The current root of the set, signed by the node.
+    pub root: KeyTriad<KeysRoot>,
+    /// The proofs of the requested keys, in the order they were requested.
+    pub proofs: Vec<KeyProof>,
+}
+
+/// A message of a reconciliation of the public keys two federated servers know of, sent by the
+/// server that started it.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct ReconcileReq {
+    pub round: Reconciliation,
+}
+
+/// A response to a [`ReconcileReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct ReconcileResp {
+    pub round: Reconciliation,
+}
+
+/// A point in the sequence of changes to the set of public keys identified to a node. The epoch
+/// is drawn at random when the sequence starts, and changes if the node loses its sequence, so
+/// that positions of different sequences are never compared.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Hash,
+)]
+pub struct GossipPosition {
+    pub epoch: u64,
+    /// The sequence number of the latest change, or 0 if there was none.
+    pub seq: u64,
+}
+
+/// How the set of public keys identified to a node changed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum KeyChangeKind {
+    /// The key identified to the node.
+    #[serde(rename = "JOINED", alias = "joined")]
+    Joined,
+    /// The key was revoked, and was removed from the set.
+    #[serde(rename = "REVOKED", alias = "revoked")]
+    Revoked,
+}
+
+/// A change to the set of public keys identified to a node.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct KeyChange {
+    /// The sequence number of the change.
+    pub seq: u64,
+    pub key: PublicKey,
+    pub kind: KeyChangeKind,
+}
+
+/// A request for the changes to the set of public keys identified to a node since `since`, so
+/// that a federated server that reconnects does not have to compare the whole set again.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct KeyChangesReq {
+    pub since: GossipPosition,
+}
+
+/// A response to a [`KeyChangesReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct KeyChangesResp {
+    /// The position of the last change of `changes`, or of the latest change if there are none.
+    /// Is behind the latest change if there were more changes than a response may list, in
+    /// which case the requester asks for the rest from it.
+    pub position: GossipPosition,
+    /// The changes after the requested position, oldest first. Is [`None`] if the node no longer
+    /// has them, or the position is of another epoch, in which case the requester must fetch the
+    /// whole set with a [`KeysRootReq`].
+    pub changes: Option<Vec<KeyChange>>,
+}
+
+/// A numeric code identifying the kind of an [`ErrorResp`]. Codes are stable across versions of
+/// the protocol, while the messages that accompany them are not.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, Hash,
+)]
+#[serde(transparent)]
+pub struct ErrorCode(pub u16);
+
+impl ErrorCode {
+    /// The error has no more specific code.
+    pub const UNKNOWN: Self = Self(0);
+    /// The request can only be fulfilled by a node.
+    pub const NOT_SERVER: Self = Self(1);
+    /// The node is shutting down.
+    pub const UNAVAILABLE: Self = Self(2);
+    /// The request cannot be answered on the wire.
+    pub const UNSUPPORTED: Self = Self(3);
+    /// The request or a payload in it exceeds a limit of the node.
+    pub const TOO_LARGE: Self = Self(4);
+    /// The endpoint sent more requests than the node allows. The error carries a hint of when
+    /// to retry.
+    pub const RATE_LIMITED: Self = Self(5);
+    /// The node holds too much in memory to take on the request. The request can be retried
+    /// later.
+    pub const OVERLOADED: Self = Self(6);
+
+    pub const INVALID_SIGNATURE: Self = Self(10);
+    pub const INVALID_IDENTIFY_DATA: Self = Self(11);
+    pub const EXTENSIONS_MISMATCH: Self = Self(12);
+    pub const INSUFFICIENT_WORK: Self = Self(13);
+    /// The identify data, a delegation or a token expired.
+    pub const EXPIRED: Self = Self(14);
+    pub const ALREADY_IDENTIFIED: Self = Self(15);
+    pub const REVOKED: Self = Self(16);
+    pub const INVALID_DELEGATION: Self = Self(17);
+    /// The signed payload could not be decoded.
+    pub const INVALID_PAYLOAD: Self = Self(18);
+    /// The signed payload is not in canonical form, which the node requires.
+    pub const NON_CANONICAL: Self = Self(19);
+
+    /// The endpoint did not identify as the public key it acted as.
+    pub const INVALID_PUBLIC_KEY: Self = Self(20);
+    pub const KEY_NOT_FOUND: Self = Self(21);
+    pub const ENDPOINT_DECLINED: Self = Self(22);
+    /// A stream could not be opened to the endpoint.
+    pub const STREAM_FAILED: Self = Self(23);
+    pub const NOTIFY_FAILED: Self = Self(24);
+    /// The node holds no record of the message.
+    pub const UNKNOWN_MESSAGE: Self = Self(25);
+    /// A key declared that it cannot be used this way.
+    pub const USAGE_DENIED: Self = Self(26);
+    /// A signed payload is of a type other than the operation it is used for.
+    pub const WRONG_MESSAGE_TYPE: Self = Self(27);
+    /// The endpoint identified as as many public keys as the node allows on one connection.
+    pub const TOO_MANY_IDENTITIES: Self = Self(28);
+    /// The endpoint, or a public key it acted as, is banned from the node.
+    pub const BANNED: Self = Self(29);
+
+    pub const INVALID_TOKEN: Self = Self(30);
+    pub const INVALID_REVOCATION: Self = Self(31);
+    pub const INVALID_RANGE: Self = Self(32);
+    /// The request can only be sent by a federated server.
+    pub const NOT_PEER: Self = Self(33);
+    pub const INVALID_PROOF: Self = Self(34);
+    /// A head of a transparency log is inconsistent with a head cosigned before.
+    pub const INCONSISTENT: Self = Self(35);
+    /// A receipt is not signed by the recipient of its message, or does not match the message.
+    pub const INVALID_RECEIPT: Self = Self(36);
+    /// A signed object is valid for longer than the node allows, or was signed in the future.
+    pub const IMPLAUSIBLE_TIME: Self = Self(37);
+    /// A forwarded request would travel further than the node allows.
+    pub const TTL_EXCEEDED: Self = Self(38);
+    /// The message was encoded with a version of the protocol the receiver does not speak.
+    pub const INCOMPATIBLE_VERSION: Self = Self(39);
+    /// The signers of a multi-key triad do not claim the identity of their group.
+    pub const INVALID_GROUP: Self = Self(40);
+}
+
+/// A response to a request that failed.
+#[derive(Error, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[error("error {}: {}", .code.0, .message)]
+pub struct ErrorResp {
+    pub code: ErrorCode,
+    /// A description of the error, for humans.
+    pub message: String,
+    /// How many milliseconds to wait before retrying the request, if the node rejected it for now
+    /// rather than for good.
+    #[serde(
+        rename = "retryAfter",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub retry_after: Option<u64>,
+}
+
+impl ErrorResp {
+    pub fn new(code: ErrorCode, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            retry_after: None,
+        }
+    }
+    /// Hints that the request can be retried after `delay`.
+    pub fn with_retry_after(self, delay: Duration) -> Self {
+        Self {
+            retry_after: Some(delay.as_millis() as u64),
+            ..self
+        }
+    }
+}
+
+/// A request sent between federated servers, that asks the receiver to cosign the head of the
+/// transparency log of the sender.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct CrossSignReq {
+    pub head: KeyTriad<LogHead>,
+    /// The proof that `head` extends the last head of the sender that the receiver cosigned.
+    pub proof: LogProof,
+}
+
+/// A response to a [`CrossSignReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[serde(tag = "type")]
+pub enum CrossSignResp {
+    #[serde(rename = "COSIGNED", alias = "cosigned")]
+    Cosigned {
+        cosignature: Box<KeyTriad<KeyTriad<LogHead>>>,
+    },
+    /// The receiver last cosigned a head of the size `from`, and needs a proof starting there.
+    #[serde(rename = "PROOF_REQUIRED", alias = "proofRequired")]
+    ProofRequired { from: u64 },
+}
+
+/// Prefixes the hash of the public keys and the payload a [`MessageId`] is derived from.
+const MESSAGE_ID_PREFIX: u8 = 9;
+
+/// The id of a message or a stream, derived from the public keys of its sender and its recipient
+/// and the hash of the payload the sender signed. Retries of a message resend the same signed
+/// payload, so that nodes recognize them as the same message, and the id cannot be changed by
+/// anyone who relays the message without signing another payload.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[serde(transparent)]
+pub struct MessageId(pub HashMsg);
+
+impl MessageId {
+    pub fn new(sender: &PublicKey, recipient: &PublicKey, message: &HashMsg) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[MESSAGE_ID_PREFIX]);
+        hasher.update(&sender.0);
+        hasher.update(&recipient.0);
+        hasher.update(&message.0);
+        Self(HashMsg(hasher.finalize().into()))
+    }
+}
+
+/// A message a node holds for a public key until the key fetches it. Is signed by the sender.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Mail {
+    /// The id of the message, derived from the public key of the payload, the recipient and the
+    /// hash of the signed payload.
+    pub id: MessageId,
+    /// The time the node stored the message.
+    pub time: u64,
+    /// The time after which the message is no longer delivered, if the sender set one.
+    #[serde(
+        rename = "expireTime",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expire_time: Option<u64>,
+    pub payload: KeyTriad<SignedData>,
+}
+
+/// A request that asks the node to hold a message for `to` until it identifies, and deliver it
+/// then. The message is pushed right away if `to` is identified already.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct RelayMessage {
+    pub to: PublicKey,
+    /// The message, signed by the sender, which the endpoint must have identified as.
+    pub payload: KeyTriad<SignedData>,
+    /// How long the message is held for, in milliseconds. Is [`None`] if it is held for as long
+    /// as the mailbox of `to` allows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
+}
+
+/// A request from a federated server that pushes a signed payload, such as an announcement of
+/// scheduled maintenance, to every endpoint identified to the node.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct BroadcastReq {
+    pub payload: KeyTriad<SignedData>,
+    /// If set, only endpoints that are servers of this domain receive the payload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<ArcStr>,
+}
+
+/// A response to a [`BroadcastReq`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct BroadcastResp {
+    /// The amount of endpoints the payload was pushed to.
+    pub recipients: u32,
+}
+
+/// A response to a [`RelayMessage`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct RelayResp {
+    /// The id of the message, that its receipts refer to.
+    pub id: MessageId,
+}
+
+/// Why a message was removed from a mailbox before its owner fetched it.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum EvictionReason {
+    /// The message was held longer than the retention policy allows.
+    #[serde(rename = "EXPIRED", alias = "expired")]
+    Expired,
+    /// The mailbox held more messages than the retention policy allows.
+    #[serde(rename = "MAX_MESSAGES", alias = "maxMessages")]
+    MaxMessages,
+    /// The mailbox held more bytes than the retention policy allows.
+    #[serde(rename = "MAX_BYTES", alias = "maxBytes")]
+    MaxBytes,
+}
+
+/// A record of a message that was evicted from a mailbox, handed to the owner of the mailbox on
+/// the next fetch.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct EvictionNotice {
+    /// The id of the evicted message.
+    pub id: MessageId,
+    /// The public key that sent the evicted message.
+    pub from: PublicKey,
+    pub reason: EvictionReason,
+    /// The time the message was evicted.
+    pub time: u64,
+}
+
+/// A request for the messages held for `key`, which the endpoint must have identified as. The
+/// messages are removed from the node once fetched.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct FetchMailReq {
+    pub key: PublicKey,
+}
+
+/// A response to a [`FetchMailReq`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct FetchMailResp {
+    /// The messages held for the key, oldest first.
+    pub mail: Vec<Mail>,
+    /// The messages evicted since the last fetch, in the order they were evicted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evicted: Vec<EvictionNotice>,
+}
+
+/// A request that routes a receipt of a message back to the sender of the message. The endpoint
+/// must have identified as the recipient that signed the receipt.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct SendReceiptReq {
+    pub receipt: KeyTriad<Receipt>,
+}
+
+/// A request for the latest receipt of the message `id`, which the endpoint must have identified
+/// as the sender of.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct ReceiptReq {
+    pub id: MessageId,
+}
+
+/// A response to a [`ReceiptReq`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct ReceiptResp {
+    /// The latest receipt the recipient signed, if any.
+    pub receipt: Option<KeyTriad<Receipt>>,
+}
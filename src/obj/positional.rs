@@ -0,0 +1,525 @@
+//! The shapes of the wire messages in positional encodings, such as postcard, which tell the
+//! fields of a message apart by their position rather than by their name.
+//!
+//! Such encodings cannot leave fields out, and cannot decode the shapes that are only told apart
+//! by their content, such as flattened or internally tagged structures. The messages are encoded
+//! positionally through the shapes of this module, in which every field is kept and every enum
+//! is tagged by the index of its variant, so that the named shapes of the messages are never
+//! altered.
+
+use std::sync::Arc;
+
+use arcstr::ArcStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{
+    de::DeserializeAsWrap, ser::SerializeAsWrap, serde_as, DeserializeAs, SerializeAs,
+};
+
+use super::*;
+use crate::crypto::{
+    keyset::{KeyRange, RangeItem, Reconciliation},
+    multi::KeySignature,
+    recover::RecoverableSignature,
+    Signature,
+};
+
+/// Encodes `value` with postcard, in the positional shape `P`.
+pub(crate) fn to_postcard<T, P: SerializeAs<T>>(value: &T) -> Result<Vec<u8>, postcard::Error> {
+    postcard::to_stdvec(&SerializeAsWrap::<T, P>::new(value))
+}
+
+/// Decodes a value encoded with postcard in the positional shape `P`.
+pub(crate) fn from_postcard<'de, T, P: DeserializeAs<'de, T>>(
+    bytes: &'de [u8],
+) -> Result<T, postcard::Error> {
+    Ok(postcard::from_bytes::<DeserializeAsWrap<T, P>>(bytes)?.into_inner())
+}
+
+/// Lets the shapes of this module be nested with `serde_as`, such as in vectors and options.
+macro_rules! positional_as {
+    ($($positional:ident => $ty:ty),* $(,)?) => {$(
+        impl SerializeAs<$ty> for $positional {
+            fn serialize_as<S: Serializer>(source: &$ty, serializer: S) -> Result<S::Ok, S::Error> {
+                $positional::serialize(source, serializer)
+            }
+        }
+        impl<'de> DeserializeAs<'de, $ty> for $positional {
+            fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<$ty, D::Error> {
+                $positional::deserialize(deserializer)
+            }
+        }
+    )*};
+}
+
+positional_as!(
+    PositionalTaggedReq => Tagged<ReqMessage>,
+    PositionalTaggedResp => Tagged<RespMessage>,
+    PositionalReqMessage => ReqMessage,
+    PositionalRespMessage => RespMessage,
+    PositionalSignedData => SignedData,
+    PositionalTriad => KeyTriad<SignedData>,
+    PositionalRecoverableTriad => RecoverableTriad<SignedData>,
+    PositionalMultiKeyTriad => MultiKeyTriad<SignedData>,
+    PositionalDelegatedTriad => DelegatedTriad,
+    PositionalPresenceTriad => KeyTriad<PresenceUpdate>,
+    PositionalPresenceUpdate => PresenceUpdate,
+    PositionalNodeInfo => NodeInfo,
+    PositionalNodeInfoResp => NodeInfoResp,
+    PositionalIdentifyReq => IdentifyReq,
+    PositionalIdentifyData => IdentifyData,
+    PositionalIdentifyExtensions => IdentifyExtensions,
+    PositionalRevokeReq => RevokeReq,
+    PositionalCommunicationReq => CommunicationReq,
+    PositionalGoodbye => Goodbye,
+    PositionalBroadcastReq => BroadcastReq,
+    PositionalRelayMessage => RelayMessage,
+    PositionalReconcileReq => ReconcileReq,
+    PositionalReconcileResp => ReconcileResp,
+    PositionalReconciliation => Reconciliation,
+    PositionalRangeItem => RangeItem,
+    PositionalKeyRange => KeyRange,
+    PositionalPublishPresenceReq => PublishPresenceReq,
+    PositionalJournalReq => JournalReq,
+    PositionalJournalResp => JournalResp,
+    PositionalJournalRecord => JournalRecord,
+    PositionalJournalEntry => JournalEntry,
+    PositionalKeysExistsResp => KeysExistsResp,
+    PositionalListConnectedServersResp => ListConnectedServersResp,
+    PositionalConnectedServer => ConnectedServer,
+    PositionalServerInfo => ServerInfo,
+    PositionalErrorResp => ErrorResp,
+    PositionalMail => Mail,
+    PositionalFetchMailResp => FetchMailResp,
+    PositionalCrossSignResp => CrossSignResp,
+);
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Tagged<ReqMessage>")]
+pub(crate) struct PositionalTaggedReq {
+    id: u64,
+    version: Option<u32>,
+    #[serde_as(as = "PositionalReqMessage")]
+    body: ReqMessage,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Tagged<RespMessage>")]
+pub(crate) struct PositionalTaggedResp {
+    id: u64,
+    version: Option<u32>,
+    #[serde_as(as = "PositionalRespMessage")]
+    body: RespMessage,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ReqMessage")]
+enum PositionalReqMessage {
+    Connect(#[serde_as(as = "PositionalNodeInfo")] NodeInfo),
+    PreIdentify(PreIdentifyReq),
+    Identify(#[serde_as(as = "PositionalIdentifyReq")] IdentifyReq),
+    Revoke(#[serde_as(as = "PositionalRevokeReq")] RevokeReq),
+    KeysExists(KeysExistsReq),
+    UnsubscribeKeys(UnsubscribeKeysReq),
+    Communication(#[serde_as(as = "PositionalCommunicationReq")] CommunicationReq),
+    ListConnectedServers(ListConnectedServersReq),
+    Ping(PingReq),
+    Goodbye(#[serde_as(as = "PositionalGoodbye")] Goodbye),
+    Broadcast(#[serde_as(as = "PositionalBroadcastReq")] BroadcastReq),
+    Relay(#[serde_as(as = "PositionalRelayMessage")] RelayMessage),
+    Reconcile(#[serde_as(as = "PositionalReconcileReq")] ReconcileReq),
+    FetchMail(FetchMailReq),
+    SendReceipt(SendReceiptReq),
+    Receipt(ReceiptReq),
+    PublishPresence(#[serde_as(as = "PositionalPublishPresenceReq")] PublishPresenceReq),
+    CrossSign(CrossSignReq),
+    Journal(#[serde_as(as = "PositionalJournalReq")] JournalReq),
+    DisconnectNotice(DisconnectNoticeReq),
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "RespMessage")]
+enum PositionalRespMessage {
+    Connect(#[serde_as(as = "PositionalNodeInfoResp")] NodeInfoResp),
+    PreIdentify(#[serde_as(as = "PositionalIdentifyData")] IdentifyData),
+    Identify(IdentifyResp),
+    KeysExists(#[serde_as(as = "PositionalKeysExistsResp")] KeysExistsResp),
+    UnsubscribeKeys(UnsubscribeKeysResp),
+    ListConnectedServers(
+        #[serde_as(as = "PositionalListConnectedServersResp")] ListConnectedServersResp,
+    ),
+    Pong(PongResp),
+    Goodbye(#[serde_as(as = "PositionalGoodbye")] Goodbye),
+    Error(#[serde_as(as = "PositionalErrorResp")] ErrorResp),
+    Ack(AckResp),
+    Broadcast(BroadcastResp),
+    Relay(RelayResp),
+    Reconcile(#[serde_as(as = "PositionalReconcileResp")] ReconcileResp),
+    FetchMail(#[serde_as(as = "PositionalFetchMailResp")] FetchMailResp),
+    Receipt(ReceiptResp),
+    CrossSign(#[serde_as(as = "PositionalCrossSignResp")] CrossSignResp),
+    Journal(#[serde_as(as = "PositionalJournalResp")] JournalResp),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SignedData")]
+enum PositionalSignedData {
+    Json(ArcStr),
+    Cbor(Arc<[u8]>),
+    Detached(HashMsg),
+    Encrypted(Arc<[u8]>),
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "KeyTriad<SignedData>")]
+struct PositionalTriad {
+    public_key: PublicKey,
+    signature: Signature,
+    #[serde_as(as = "PositionalSignedData")]
+    signed: SignedData,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "RecoverableTriad<SignedData>")]
+struct PositionalRecoverableTriad {
+    signature: RecoverableSignature,
+    #[serde_as(as = "PositionalSignedData")]
+    signed: SignedData,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "MultiKeyTriad<SignedData>")]
+struct PositionalMultiKeyTriad {
+    #[serde_as(as = "PositionalTriad")]
+    group: KeyTriad<SignedData>,
+    signers: Vec<KeySignature>,
+    #[serde_as(as = "PositionalSignedData")]
+    signed: SignedData,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "DelegatedTriad")]
+struct PositionalDelegatedTriad {
+    #[serde_as(as = "PositionalTriad")]
+    triad: KeyTriad<SignedData>,
+    #[serde_as(as = "Vec<PositionalTriad>")]
+    chain: Vec<KeyTriad<SignedData>>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "KeyTriad<PresenceUpdate>")]
+struct PositionalPresenceTriad {
+    public_key: PublicKey,
+    signature: Signature,
+    #[serde_as(as = "PositionalPresenceUpdate")]
+    signed: PresenceUpdate,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "PresenceUpdate")]
+struct PositionalPresenceUpdate {
+    status: PresenceStatus,
+    message: Option<String>,
+    time: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "NodeInfo")]
+struct PositionalNodeInfo {
+    api_version: u32,
+    features: Features,
+    load: Option<Load>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "NodeInfoResp")]
+struct PositionalNodeInfoResp {
+    compatible: bool,
+    #[serde_as(as = "PositionalNodeInfo")]
+    info: NodeInfo,
+    features: Features,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "IdentifyReq")]
+struct PositionalIdentifyReq {
+    #[serde_as(as = "Vec<PositionalTriad>")]
+    keys: Vec<KeyTriad<SignedData>>,
+    #[serde_as(as = "Vec<PositionalRecoverableTriad>")]
+    compact: Vec<RecoverableTriad<SignedData>>,
+    #[serde_as(as = "Vec<PositionalMultiKeyTriad>")]
+    multi: Vec<MultiKeyTriad<SignedData>>,
+    #[serde_as(as = "Vec<PositionalDelegatedTriad>")]
+    delegated: Vec<DelegatedTriad>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "IdentifyData")]
+struct PositionalIdentifyData {
+    salt: [u8; SALT_SIZE],
+    start_time: u64,
+    expire_time: u64,
+    #[serde_as(as = "PositionalIdentifyExtensions")]
+    extensions: IdentifyExtensions,
+    difficulty: Option<u8>,
+    work: Option<u64>,
+    usage: KeyUsage,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "IdentifyExtensions")]
+struct PositionalIdentifyExtensions {
+    tos_hash: Option<HashMsg>,
+    node_key: Option<PublicKey>,
+    required_features: Features,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "RevokeReq")]
+struct PositionalRevokeReq {
+    #[serde_as(as = "PositionalTriad")]
+    revocation: KeyTriad<SignedData>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "CommunicationReq")]
+struct PositionalCommunicationReq {
+    from: PublicKey,
+    to: PublicKey,
+    grant: Option<KeyTriad<ContactGrant>>,
+    hops: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Goodbye")]
+struct PositionalGoodbye {
+    code: GoodbyeCode,
+    reason: String,
+    keep_down: Option<KeyTriad<KeepDown>>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "BroadcastReq")]
+struct PositionalBroadcastReq {
+    #[serde_as(as = "PositionalTriad")]
+    payload: KeyTriad<SignedData>,
+    domain: Option<ArcStr>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "RelayMessage")]
+struct PositionalRelayMessage {
+    to: PublicKey,
+    #[serde_as(as = "PositionalTriad")]
+    payload: KeyTriad<SignedData>,
+    ttl: Option<u64>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ReconcileReq")]
+struct PositionalReconcileReq {
+    #[serde_as(as = "PositionalReconciliation")]
+    round: Reconciliation,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ReconcileResp")]
+struct PositionalReconcileResp {
+    #[serde_as(as = "PositionalReconciliation")]
+    round: Reconciliation,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Reconciliation")]
+struct PositionalReconciliation {
+    #[serde_as(as = "Vec<PositionalRangeItem>")]
+    items: Vec<RangeItem>,
+    keys: Vec<PublicKey>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "RangeItem")]
+enum PositionalRangeItem {
+    Fingerprint {
+        #[serde_as(as = "PositionalKeyRange")]
+        range: KeyRange,
+        count: u64,
+        fingerprint: HashMsg,
+    },
+    Keys {
+        #[serde_as(as = "PositionalKeyRange")]
+        range: KeyRange,
+        keys: Vec<PublicKey>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "KeyRange")]
+struct PositionalKeyRange {
+    lower: Option<PublicKey>,
+    upper: Option<PublicKey>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "PublishPresenceReq")]
+struct PositionalPublishPresenceReq {
+    #[serde_as(as = "PositionalPresenceTriad")]
+    presence: KeyTriad<PresenceUpdate>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "JournalReq")]
+struct PositionalJournalReq {
+    since: JournalPosition,
+    max: Option<u32>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "JournalResp")]
+struct PositionalJournalResp {
+    position: JournalPosition,
+    #[serde_as(as = "Vec<PositionalJournalRecord>")]
+    records: Vec<JournalRecord>,
+    snapshot: bool,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "JournalRecord")]
+struct PositionalJournalRecord {
+    seq: u64,
+    #[serde_as(as = "PositionalJournalEntry")]
+    entry: JournalEntry,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "JournalEntry")]
+enum PositionalJournalEntry {
+    Identified {
+        public_key: PublicKey,
+        usage: KeyUsage,
+        time: u64,
+    },
+    Revoked {
+        public_key: PublicKey,
+    },
+    Subscribed {
+        subscriber: PublicKey,
+        keys: Vec<PublicKey>,
+    },
+    Unsubscribed {
+        subscriber: PublicKey,
+        keys: Vec<PublicKey>,
+    },
+    Deposited {
+        to: PublicKey,
+        #[serde_as(as = "PositionalMail")]
+        mail: Mail,
+    },
+    Delivered {
+        to: PublicKey,
+        ids: Vec<MessageId>,
+    },
+    Fetched {
+        to: PublicKey,
+    },
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "KeysExistsResp")]
+struct PositionalKeysExistsResp {
+    #[serde_as(as = "Vec<PositionalTriad>")]
+    triads: Vec<KeyTriad<SignedData>>,
+    #[serde_as(as = "Vec<PositionalPresenceTriad>")]
+    presence: Vec<KeyTriad<PresenceUpdate>>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ListConnectedServersResp")]
+struct PositionalListConnectedServersResp {
+    #[serde_as(as = "Vec<PositionalConnectedServer>")]
+    servers: Vec<ConnectedServer>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ConnectedServer")]
+struct PositionalConnectedServer {
+    addr: Option<SocketAddr>,
+    #[serde_as(as = "PositionalServerInfo")]
+    server_info: ServerInfo,
+    load: Option<Load>,
+    verified: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ServerInfo")]
+struct PositionalServerInfo {
+    domain: ArcStr,
+    ports: Vec<u16>,
+    transports: Vec<Transport>,
+    api_version: Option<u32>,
+    public_key: Option<PublicKey>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ErrorResp")]
+struct PositionalErrorResp {
+    code: ErrorCode,
+    message: String,
+    retry_after: Option<u64>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Mail")]
+struct PositionalMail {
+    id: MessageId,
+    time: u64,
+    expire_time: Option<u64>,
+    #[serde_as(as = "PositionalTriad")]
+    payload: KeyTriad<SignedData>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "FetchMailResp")]
+struct PositionalFetchMailResp {
+    #[serde_as(as = "Vec<PositionalMail>")]
+    mail: Vec<Mail>,
+    evicted: Vec<EvictionNotice>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "CrossSignResp")]
+enum PositionalCrossSignResp {
+    Cosigned {
+        cosignature: Box<KeyTriad<KeyTriad<LogHead>>>,
+    },
+    ProofRequired {
+        from: u64,
+    },
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{HashMsg, KeyTriad, PrivateKey, ToHashMsg};
+
+/// Prefixes the hash of a [`PresenceUpdate`] before it is signed.
+const PRESENCE_PREFIX: u8 = 10;
+
+/// The longest status message of a [`PresenceUpdate`], in bytes.
+pub const MAX_PRESENCE_MESSAGE: usize = 256;
+
+/// Whether the owner of a public key wants to be reached.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum PresenceStatus {
+    #[serde(rename = "ONLINE", alias = "online")]
+    Online,
+    #[serde(rename = "AWAY", alias = "away")]
+    Away,
+    #[serde(rename = "DO_NOT_DISTURB", alias = "doNotDisturb")]
+    DoNotDisturb,
+}
+
+/// The presence a public key published to the node it identified to. Is signed by the key.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct PresenceUpdate {
+    pub status: PresenceStatus,
+    /// A status message, for humans.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The time the update was signed. A node keeps the latest update of each key.
+    pub time: u64,
+}
+
+impl ToHashMsg for &PresenceUpdate {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        let status = match self.status {
+            PresenceStatus::Online => 0u8,
+            PresenceStatus::Away => 1,
+            PresenceStatus::DoNotDisturb => 2,
+        };
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[PRESENCE_PREFIX]);
+        hasher.update(&[status]);
+        match &self.message {
+            Some(message) => {
+                hasher.update(&[1]);
+                hasher.update(&(message.len() as u64).to_be_bytes());
+                hasher.update(message.as_bytes());
+            }
+            None => {
+                hasher.update(&[0]);
+            }
+        }
+        hasher.update(&self.time.to_be_bytes());
+        HashMsg(hasher.finalize().into())
+    }
+}
+
+impl KeyTriad<PresenceUpdate> {
+    /// Signs an update of the presence of `key` at the time `time`.
+    pub fn presence(
+        key: &PrivateKey,
+        status: PresenceStatus,
+        message: Option<String>,
+        time: u64,
+    ) -> Self {
+        let presence = PresenceUpdate {
+            status,
+            message,
+            time,
+        };
+        Self::signed_by(key, presence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn presence() {
+        let key = KeyPair::generate();
+        let presence = KeyTriad::presence(
+            &key.private,
+            PresenceStatus::Away,
+            Some("lunch".to_string()),
+            0,
+        );
+        assert!(presence.valid());
+
+        // the message is signed over
+        let mut forged = presence.clone();
+        forged.signed.message = None;
+        assert!(!forged.valid());
+        let mut forged = presence;
+        forged.signed.status = PresenceStatus::Online;
+        assert!(!forged.valid());
+    }
+}
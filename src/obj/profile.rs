@@ -0,0 +1,226 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_cbor::Value as CborValue;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// A naming scheme for the keys of serialized protocol objects.
+///
+/// Fields of protocol objects are named in camelCase, while the tags of enums such as
+/// [`ReqMessage`](super::ReqMessage) are named in SCREAMING_SNAKE_CASE. Every enum tag also
+/// accepts its camelCase spelling when deserializing, so objects encoded with any profile can be
+/// decoded by the canonical types.
+///
+/// A [`MessageCodec`](crate::codec::MessageCodec) names the CBOR frames it encodes, and checks
+/// the frames it decodes, according to its profile. Positional formats such as postcard leave
+/// the names of fields out, and are not affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum NamingProfile {
+    /// The naming used by version 0 of the protocol.
+    #[default]
+    CanonicalV0,
+    /// Every key is named in camelCase, and keys named otherwise are rejected.
+    StrictCamel,
+}
+
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("{}", .0)]
+    JsonError(#[from] serde_json::Error),
+    #[error("{}", .0)]
+    CborError(#[from] serde_cbor::Error),
+    /// A key does not follow the naming of the profile.
+    #[error("key {} does not follow the naming profile", .0)]
+    InvalidKey(String),
+}
+
+impl NamingProfile {
+    /// Returns the profile used by a version of the protocol.
+    pub const fn for_version(_api_version: u32) -> Self {
+        // every version so far uses the canonical naming
+        Self::CanonicalV0
+    }
+    /// Serializes an object to JSON, named according to this profile.
+    pub fn to_value<T: Serialize>(self, value: &T) -> Result<Value, ProfileError> {
+        let value = serde_json::to_value(value)?;
+
+        Ok(match self {
+            Self::CanonicalV0 => value,
+            Self::StrictCamel => map_keys(value, &to_camel_case),
+        })
+    }
+    /// Deserializes an object from JSON named according to this profile.
+    pub fn from_value<T: DeserializeOwned>(self, value: Value) -> Result<T, ProfileError> {
+        if self == Self::StrictCamel {
+            check_keys(&value, &|key| key == to_camel_case(key))?;
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+    /// Serializes an object to CBOR, named according to this profile.
+    pub fn to_cbor<T: Serialize>(self, value: &T) -> Result<Vec<u8>, ProfileError> {
+        Ok(match self {
+            Self::CanonicalV0 => serde_cbor::to_vec(value)?,
+            Self::StrictCamel => serde_cbor::to_vec(&map_cbor_keys(
+                serde_cbor::value::to_value(value)?,
+                &to_camel_case,
+            ))?,
+        })
+    }
+    /// Deserializes an object from CBOR named according to this profile.
+    pub fn from_cbor<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, ProfileError> {
+        Ok(match self {
+            Self::CanonicalV0 => serde_cbor::from_slice(bytes)?,
+            Self::StrictCamel => {
+                let value: CborValue = serde_cbor::from_slice(bytes)?;
+                check_cbor_keys(&value, &|key| key == to_camel_case(key))?;
+                serde_cbor::value::from_value(value)?
+            }
+        })
+    }
+}
+
+/// Converts a snake_case or SCREAMING_SNAKE_CASE key to camelCase. Keys already in camelCase are
+/// returned unchanged.
+fn to_camel_case(key: &str) -> String {
+    if !key.contains('_') && key.chars().any(|c| c.is_ascii_lowercase()) {
+        return key.to_owned();
+    }
+
+    let mut camel = String::with_capacity(key.len());
+    for (index, part) in key.split('_').filter(|part| !part.is_empty()).enumerate() {
+        let part = part.to_ascii_lowercase();
+        let mut chars = part.chars();
+
+        match (index, chars.next()) {
+            (0, Some(first)) => camel.push(first),
+            (_, Some(first)) => camel.push(first.to_ascii_uppercase()),
+            (_, None) => {}
+        }
+        camel.extend(chars);
+    }
+
+    camel
+}
+
+fn map_keys(value: Value, f: &impl Fn(&str) -> String) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (f(&key), map_keys(value, f)))
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(array) => Value::Array(array.into_iter().map(|v| map_keys(v, f)).collect()),
+        value => value,
+    }
+}
+
+fn map_cbor_keys(value: CborValue, f: &impl Fn(&str) -> String) -> CborValue {
+    match value {
+        CborValue::Map(map) => CborValue::Map(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        CborValue::Text(key) => CborValue::Text(f(&key)),
+                        key => key,
+                    };
+                    (key, map_cbor_keys(value, f))
+                })
+                .collect(),
+        ),
+        CborValue::Array(array) => {
+            CborValue::Array(array.into_iter().map(|v| map_cbor_keys(v, f)).collect())
+        }
+        value => value,
+    }
+}
+
+fn check_cbor_keys(value: &CborValue, f: &impl Fn(&str) -> bool) -> Result<(), ProfileError> {
+    match value {
+        CborValue::Map(map) => {
+            for (key, value) in map {
+                if let CborValue::Text(key) = key {
+                    if !f(key) {
+                        return Err(ProfileError::InvalidKey(key.clone()));
+                    }
+                }
+                check_cbor_keys(value, f)?;
+            }
+            Ok(())
+        }
+        CborValue::Array(array) => array.iter().try_for_each(|v| check_cbor_keys(v, f)),
+        _ => Ok(()),
+    }
+}
+
+fn check_keys(value: &Value, f: &impl Fn(&str) -> bool) -> Result<(), ProfileError> {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                if !f(key) {
+                    return Err(ProfileError::InvalidKey(key.clone()));
+                }
+                check_keys(value, f)?;
+            }
+            Ok(())
+        }
+        Value::Array(array) => array.iter().try_for_each(|v| check_keys(v, f)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::obj::{ListConnectedServersReq, NodeInfo, ReqMessage};
+
+    #[test]
+    fn strict_camel() {
+        let msg = ReqMessage::Connect(NodeInfo::default());
+        let value = NamingProfile::StrictCamel.to_value(&msg).unwrap();
+
+        assert_eq!(value, json!({ "nodeInfo": { "apiVersion": 0 } }));
+        assert_eq!(
+            NamingProfile::StrictCamel
+                .from_value::<ReqMessage>(value.clone())
+                .unwrap(),
+            msg
+        );
+        // the canonical types accept both spellings
+        assert_eq!(
+            NamingProfile::CanonicalV0
+                .from_value::<ReqMessage>(value)
+                .unwrap(),
+            msg
+        );
+
+        let canonical = NamingProfile::CanonicalV0.to_value(&msg).unwrap();
+        assert!(NamingProfile::StrictCamel
+            .from_value::<ReqMessage>(canonical)
+            .is_err());
+    }
+
+    #[test]
+    fn multi_word_tags() {
+        let msg = ReqMessage::from(ListConnectedServersReq { max: Some(2) });
+        assert_eq!(
+            NamingProfile::CanonicalV0.to_value(&msg).unwrap(),
+            json!({ "LIST_CONNECTED_SERVERS": { "max": 2 } })
+        );
+
+        let value = NamingProfile::StrictCamel.to_value(&msg).unwrap();
+        assert_eq!(value, json!({ "listConnectedServers": { "max": 2 } }));
+        assert_eq!(
+            NamingProfile::CanonicalV0
+                .from_value::<ReqMessage>(value)
+                .unwrap(),
+            msg
+        );
+        assert_eq!(
+            ListConnectedServersReq::try_from(msg).unwrap(),
+            ListConnectedServersReq { max: Some(2) }
+        );
+    }
+}
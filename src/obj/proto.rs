@@ -0,0 +1,2708 @@
+//! Protobuf forms of the messages sent on the wire, for peers that only speak protobuf.
+//!
+//! Every type mirrors a message of the [`obj`](super) module, as described by
+//! `proto/cacophoney.proto`, and converts to and from it without loss. Fixed-size values, such as
+//! public keys and signatures, are sent as bytes and checked for their size when converted back.
+
+use std::net::SocketAddr;
+
+use arcstr::ArcStr;
+use thiserror::Error;
+
+use crate::crypto::recover::{self, RecoverableSignature};
+use crate::crypto::token::{self, TokenScope as NativeTokenScope};
+use crate::crypto::{self, HashMsg, Mac, PublicKey, Signature};
+use crate::crypto::{keyset, log, multi};
+use crate::obj;
+
+/// An error that can occur when converting a protobuf message to its native form.
+#[derive(Error, Debug)]
+pub enum ProtoError {
+    #[error("{}", .0)]
+    Decode(#[from] prost::DecodeError),
+    /// A field the native message requires was not set.
+    #[error("missing field {}", .0)]
+    Missing(&'static str),
+    /// A field of a fixed size has another size.
+    #[error("field {field} is {received} bytes long, expected {expected} bytes")]
+    InvalidLength {
+        field: &'static str,
+        expected: usize,
+        received: usize,
+    },
+    /// A field holds a value the native message cannot, such as a number that does not fit or an
+    /// unknown enum value.
+    #[error("field {} is out of range", .0)]
+    OutOfRange(&'static str),
+    /// The message has no protobuf form.
+    #[error("message has no protobuf form")]
+    Unsupported,
+}
+
+fn array<const N: usize>(field: &'static str, bytes: Vec<u8>) -> Result<[u8; N], ProtoError> {
+    let received = bytes.len();
+    bytes.try_into().map_err(|_| ProtoError::InvalidLength {
+        field,
+        expected: N,
+        received,
+    })
+}
+
+fn required<T>(field: &'static str, value: Option<T>) -> Result<T, ProtoError> {
+    value.ok_or(ProtoError::Missing(field))
+}
+
+fn narrow<T: TryFrom<u32>>(field: &'static str, value: u32) -> Result<T, ProtoError> {
+    T::try_from(value).map_err(|_| ProtoError::OutOfRange(field))
+}
+
+fn collect<P, T: TryFrom<P, Error = ProtoError>>(values: Vec<P>) -> Result<Vec<T>, ProtoError> {
+    values.into_iter().map(T::try_from).collect()
+}
+
+fn public_key(field: &'static str, bytes: Vec<u8>) -> Result<PublicKey, ProtoError> {
+    Ok(PublicKey(array(field, bytes)?))
+}
+
+fn hash(field: &'static str, bytes: Vec<u8>) -> Result<HashMsg, ProtoError> {
+    Ok(HashMsg(array(field, bytes)?))
+}
+
+fn message_id(field: &'static str, bytes: Vec<u8>) -> Result<obj::MessageId, ProtoError> {
+    Ok(obj::MessageId(hash(field, bytes)?))
+}
+
+fn public_keys(field: &'static str, keys: Vec<Vec<u8>>) -> Result<Vec<PublicKey>, ProtoError> {
+    keys.into_iter().map(|key| public_key(field, key)).collect()
+}
+
+fn features(features: obj::Features) -> Vec<String> {
+    features
+        .0
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn native_features(field: &'static str, names: Vec<String>) -> Result<obj::Features, ProtoError> {
+    if names.len() > obj::Features::MAX_FEATURES
+        || names
+            .iter()
+            .any(|name| name.len() > obj::Features::MAX_NAME_LEN)
+    {
+        return Err(ProtoError::OutOfRange(field));
+    }
+    Ok(obj::Features(names.into_iter().map(ArcStr::from).collect()))
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SignedData {
+    #[prost(oneof = "signed_data::Data", tags = "1, 2, 3, 4")]
+    pub data: Option<signed_data::Data>,
+}
+
+pub mod signed_data {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Data {
+        #[prost(string, tag = "1")]
+        Json(String),
+        #[prost(bytes = "vec", tag = "2")]
+        Cbor(Vec<u8>),
+        #[prost(bytes = "vec", tag = "3")]
+        Detached(Vec<u8>),
+        #[prost(bytes = "vec", tag = "4")]
+        Encrypted(Vec<u8>),
+    }
+}
+
+impl From<obj::SignedData> for SignedData {
+    fn from(value: obj::SignedData) -> Self {
+        let data = match value {
+            obj::SignedData::Json(json) => signed_data::Data::Json(json.to_string()),
+            obj::SignedData::Cbor(cbor) => signed_data::Data::Cbor(cbor.to_vec()),
+            obj::SignedData::Detached(hash) => signed_data::Data::Detached(hash.0.to_vec()),
+            obj::SignedData::Encrypted(sealed) => signed_data::Data::Encrypted(sealed.to_vec()),
+        };
+        Self { data: Some(data) }
+    }
+}
+impl TryFrom<SignedData> for obj::SignedData {
+    type Error = ProtoError;
+
+    fn try_from(value: SignedData) -> Result<Self, Self::Error> {
+        Ok(match required("SignedData.data", value.data)? {
+            signed_data::Data::Json(json) => Self::Json(json.into()),
+            signed_data::Data::Cbor(cbor) => Self::Cbor(cbor.into()),
+            signed_data::Data::Detached(hash) => {
+                Self::Detached(HashMsg(array("SignedData.detached", hash)?))
+            }
+            signed_data::Data::Encrypted(sealed) => Self::Encrypted(sealed.into()),
+        })
+    }
+}
+
+/// Defines the protobuf form of a [`KeyTriad`](crypto::KeyTriad) of `$native`, whose protobuf
+/// form is `$signed`.
+macro_rules! triad {
+    ($(#[$meta:meta])* $name:ident, $native:ty, $signed:ty) => {
+        $(#[$meta])*
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct $name {
+            #[prost(bytes = "vec", tag = "1")]
+            pub public_key: Vec<u8>,
+            #[prost(bytes = "vec", tag = "2")]
+            pub signature: Vec<u8>,
+            #[prost(message, optional, tag = "3")]
+            pub signed: Option<$signed>,
+        }
+
+        impl From<crypto::KeyTriad<$native>> for $name {
+            fn from(value: crypto::KeyTriad<$native>) -> Self {
+                Self {
+                    public_key: value.public_key.0.to_vec(),
+                    signature: value.signature.0.to_vec(),
+                    signed: Some(value.signed.into()),
+                }
+            }
+        }
+        impl TryFrom<$name> for crypto::KeyTriad<$native> {
+            type Error = ProtoError;
+
+            fn try_from(value: $name) -> Result<Self, Self::Error> {
+                Ok(Self {
+                    public_key: public_key(
+                        concat!(stringify!($name), ".public_key"),
+                        value.public_key,
+                    )?,
+                    signature: Signature(array(
+                        concat!(stringify!($name), ".signature"),
+                        value.signature,
+                    )?),
+                    signed: required(concat!(stringify!($name), ".signed"), value.signed)?
+                        .try_into()?,
+                })
+            }
+        }
+    };
+}
+
+triad!(
+    /// A [`KeyTriad`](crypto::KeyTriad) over [`SignedData`].
+    KeyTriad,
+    obj::SignedData,
+    SignedData
+);
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RecoverableTriad {
+    #[prost(bytes = "vec", tag = "1")]
+    pub signature: Vec<u8>,
+    #[prost(message, optional, tag = "2")]
+    pub signed: Option<SignedData>,
+}
+
+impl From<recover::RecoverableTriad<obj::SignedData>> for RecoverableTriad {
+    fn from(value: recover::RecoverableTriad<obj::SignedData>) -> Self {
+        Self {
+            signature: value.signature.0.to_vec(),
+            signed: Some(value.signed.into()),
+        }
+    }
+}
+impl TryFrom<RecoverableTriad> for recover::RecoverableTriad<obj::SignedData> {
+    type Error = ProtoError;
+
+    fn try_from(value: RecoverableTriad) -> Result<Self, Self::Error> {
+        Ok(Self {
+            signature: RecoverableSignature(array("RecoverableTriad.signature", value.signature)?),
+            signed: required("RecoverableTriad.signed", value.signed)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KeySignature {
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub signature: Vec<u8>,
+}
+
+impl From<multi::KeySignature> for KeySignature {
+    fn from(value: multi::KeySignature) -> Self {
+        Self {
+            public_key: value.public_key.0.to_vec(),
+            signature: value.signature.0.to_vec(),
+        }
+    }
+}
+impl TryFrom<KeySignature> for multi::KeySignature {
+    type Error = ProtoError;
+
+    fn try_from(value: KeySignature) -> Result<Self, Self::Error> {
+        Ok(Self {
+            public_key: public_key("KeySignature.public_key", value.public_key)?,
+            signature: Signature(array("KeySignature.signature", value.signature)?),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MultiKeyTriad {
+    #[prost(message, repeated, tag = "1")]
+    pub signers: Vec<KeySignature>,
+    #[prost(message, optional, tag = "2")]
+    pub signed: Option<SignedData>,
+    #[prost(message, optional, tag = "3")]
+    pub group: Option<KeyTriad>,
+}
+
+impl From<multi::MultiKeyTriad<obj::SignedData>> for MultiKeyTriad {
+    fn from(value: multi::MultiKeyTriad<obj::SignedData>) -> Self {
+        Self {
+            signers: value.signers.into_iter().map(Into::into).collect(),
+            signed: Some(value.signed.into()),
+            group: Some(value.group.into()),
+        }
+    }
+}
+impl TryFrom<MultiKeyTriad> for multi::MultiKeyTriad<obj::SignedData> {
+    type Error = ProtoError;
+
+    fn try_from(value: MultiKeyTriad) -> Result<Self, Self::Error> {
+        Ok(Self {
+            group: required("MultiKeyTriad.group", value.group)?.try_into()?,
+            signers: collect(value.signers)?,
+            signed: required("MultiKeyTriad.signed", value.signed)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DelegatedTriad {
+    #[prost(message, optional, tag = "1")]
+    pub triad: Option<KeyTriad>,
+    #[prost(message, repeated, tag = "2")]
+    pub chain: Vec<KeyTriad>,
+}
+
+impl From<obj::DelegatedTriad> for DelegatedTriad {
+    fn from(value: obj::DelegatedTriad) -> Self {
+        Self {
+            triad: Some(value.triad.into()),
+            chain: value.chain.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+impl TryFrom<DelegatedTriad> for obj::DelegatedTriad {
+    type Error = ProtoError;
+
+    fn try_from(value: DelegatedTriad) -> Result<Self, Self::Error> {
+        Ok(Self {
+            triad: required("DelegatedTriad.triad", value.triad)?.try_into()?,
+            chain: collect(value.chain)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Load {
+    #[prost(uint32, tag = "1")]
+    pub relay_saturation: u32,
+    #[prost(uint32, tag = "2")]
+    pub connections: u32,
+    #[prost(uint32, tag = "3")]
+    pub identities: u32,
+}
+
+impl From<obj::Load> for Load {
+    fn from(value: obj::Load) -> Self {
+        Self {
+            relay_saturation: value.relay_saturation as u32,
+            connections: value.connections,
+            identities: value.identities,
+        }
+    }
+}
+impl TryFrom<Load> for obj::Load {
+    type Error = ProtoError;
+
+    fn try_from(value: Load) -> Result<Self, Self::Error> {
+        Ok(Self {
+            relay_saturation: narrow("Load.relay_saturation", value.relay_saturation)?,
+            connections: value.connections,
+            identities: value.identities,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NodeInfo {
+    #[prost(uint32, tag = "1")]
+    pub api_version: u32,
+    #[prost(string, repeated, tag = "3")]
+    pub features: Vec<String>,
+    #[prost(message, optional, tag = "4")]
+    pub load: Option<Load>,
+}
+
+impl From<obj::NodeInfo> for NodeInfo {
+    fn from(value: obj::NodeInfo) -> Self {
+        Self {
+            api_version: value.api_version,
+            features: features(value.features),
+            load: value.load.map(Into::into),
+        }
+    }
+}
+impl TryFrom<NodeInfo> for obj::NodeInfo {
+    type Error = ProtoError;
+
+    fn try_from(value: NodeInfo) -> Result<Self, Self::Error> {
+        Ok(Self {
+            api_version: value.api_version,
+            features: native_features("NodeInfo.features", value.features)?,
+            load: value.load.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NodeInfoResp {
+    #[prost(bool, tag = "1")]
+    pub compatible: bool,
+    #[prost(message, optional, tag = "2")]
+    pub info: Option<NodeInfo>,
+    #[prost(string, repeated, tag = "3")]
+    pub features: Vec<String>,
+}
+
+impl From<obj::NodeInfoResp> for NodeInfoResp {
+    fn from(value: obj::NodeInfoResp) -> Self {
+        Self {
+            compatible: value.compatible,
+            info: Some(value.info.into()),
+            features: features(value.features),
+        }
+    }
+}
+impl TryFrom<NodeInfoResp> for obj::NodeInfoResp {
+    type Error = ProtoError;
+
+    fn try_from(value: NodeInfoResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            compatible: value.compatible,
+            info: required("NodeInfoResp.info", value.info)?.try_into()?,
+            features: native_features("NodeInfoResp.features", value.features)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PreIdentifyReq {}
+
+impl From<obj::PreIdentifyReq> for PreIdentifyReq {
+    fn from(_: obj::PreIdentifyReq) -> Self {
+        Self {}
+    }
+}
+impl TryFrom<PreIdentifyReq> for obj::PreIdentifyReq {
+    type Error = ProtoError;
+
+    fn try_from(_: PreIdentifyReq) -> Result<Self, Self::Error> {
+        Ok(Self {})
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IdentifyReq {
+    #[prost(message, repeated, tag = "1")]
+    pub keys: Vec<KeyTriad>,
+    #[prost(message, repeated, tag = "2")]
+    pub compact: Vec<RecoverableTriad>,
+    #[prost(message, repeated, tag = "3")]
+    pub multi: Vec<MultiKeyTriad>,
+    #[prost(message, repeated, tag = "4")]
+    pub delegated: Vec<DelegatedTriad>,
+}
+
+impl From<obj::IdentifyReq> for IdentifyReq {
+    fn from(value: obj::IdentifyReq) -> Self {
+        Self {
+            keys: value.keys.into_iter().map(Into::into).collect(),
+            compact: value.compact.into_iter().map(Into::into).collect(),
+            multi: value.multi.into_iter().map(Into::into).collect(),
+            delegated: value.delegated.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+impl TryFrom<IdentifyReq> for obj::IdentifyReq {
+    type Error = ProtoError;
+
+    fn try_from(value: IdentifyReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            keys: collect(value.keys)?,
+            compact: collect(value.compact)?,
+            multi: collect(value.multi)?,
+            delegated: collect(value.delegated)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RevokeReq {
+    #[prost(message, optional, tag = "1")]
+    pub revocation: Option<KeyTriad>,
+}
+
+impl From<obj::RevokeReq> for RevokeReq {
+    fn from(value: obj::RevokeReq) -> Self {
+        Self {
+            revocation: Some(value.revocation.into()),
+        }
+    }
+}
+impl TryFrom<RevokeReq> for obj::RevokeReq {
+    type Error = ProtoError;
+
+    fn try_from(value: RevokeReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            revocation: required("RevokeReq.revocation", value.revocation)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KeysExistsReq {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub keys: Vec<Vec<u8>>,
+    #[prost(bool, tag = "2")]
+    pub notify: bool,
+}
+
+impl From<obj::KeysExistsReq> for KeysExistsReq {
+    fn from(value: obj::KeysExistsReq) -> Self {
+        Self {
+            keys: value.keys.into_iter().map(|key| key.0.to_vec()).collect(),
+            notify: value.notify,
+        }
+    }
+}
+impl TryFrom<KeysExistsReq> for obj::KeysExistsReq {
+    type Error = ProtoError;
+
+    fn try_from(value: KeysExistsReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            keys: public_keys("KeysExistsReq.keys", value.keys)?,
+            notify: value.notify,
+        })
+    }
+}
+
+/// The public keys of an [`UnsubscribeKeysReq`](obj::UnsubscribeKeysReq) or an
+/// [`UnsubscribeKeysResp`](obj::UnsubscribeKeysResp).
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct UnsubscribeKeys {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub keys: Vec<Vec<u8>>,
+}
+
+impl From<obj::UnsubscribeKeysReq> for UnsubscribeKeys {
+    fn from(value: obj::UnsubscribeKeysReq) -> Self {
+        Self {
+            keys: value.keys.into_iter().map(|key| key.0.to_vec()).collect(),
+        }
+    }
+}
+impl TryFrom<UnsubscribeKeys> for obj::UnsubscribeKeysReq {
+    type Error = ProtoError;
+
+    fn try_from(value: UnsubscribeKeys) -> Result<Self, Self::Error> {
+        Ok(Self {
+            keys: public_keys("UnsubscribeKeys.keys", value.keys)?,
+        })
+    }
+}
+impl From<obj::UnsubscribeKeysResp> for UnsubscribeKeys {
+    fn from(value: obj::UnsubscribeKeysResp) -> Self {
+        Self {
+            keys: value.keys.into_iter().map(|key| key.0.to_vec()).collect(),
+        }
+    }
+}
+impl TryFrom<UnsubscribeKeys> for obj::UnsubscribeKeysResp {
+    type Error = ProtoError;
+
+    fn try_from(value: UnsubscribeKeys) -> Result<Self, Self::Error> {
+        Ok(Self {
+            keys: public_keys("UnsubscribeKeys.keys", value.keys)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ContactGrant {
+    #[prost(bytes = "vec", tag = "1")]
+    pub grantee: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub expire_time: u64,
+}
+
+impl From<obj::ContactGrant> for ContactGrant {
+    fn from(value: obj::ContactGrant) -> Self {
+        Self {
+            grantee: value.grantee.0.to_vec(),
+            expire_time: value.expire_time,
+        }
+    }
+}
+impl TryFrom<ContactGrant> for obj::ContactGrant {
+    type Error = ProtoError;
+
+    fn try_from(value: ContactGrant) -> Result<Self, Self::Error> {
+        Ok(Self {
+            grantee: public_key("ContactGrant.grantee", value.grantee)?,
+            expire_time: value.expire_time,
+        })
+    }
+}
+
+triad!(
+    /// A [`KeyTriad`](crypto::KeyTriad) over a [`ContactGrant`].
+    ContactGrantTriad,
+    obj::ContactGrant,
+    ContactGrant
+);
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CommunicationReq {
+    #[prost(bytes = "vec", tag = "1")]
+    pub from: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub to: Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub grant: Option<ContactGrantTriad>,
+    #[prost(uint32, tag = "4")]
+    pub hops: u32,
+}
+
+impl From<obj::CommunicationReq> for CommunicationReq {
+    fn from(value: obj::CommunicationReq) -> Self {
+        Self {
+            from: value.from.0.to_vec(),
+            to: value.to.0.to_vec(),
+            grant: value.grant.map(Into::into),
+            hops: value.hops,
+        }
+    }
+}
+impl TryFrom<CommunicationReq> for obj::CommunicationReq {
+    type Error = ProtoError;
+
+    fn try_from(value: CommunicationReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            from: public_key("CommunicationReq.from", value.from)?,
+            to: public_key("CommunicationReq.to", value.to)?,
+            grant: value.grant.map(TryInto::try_into).transpose()?,
+            hops: value.hops,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListConnectedServersReq {
+    #[prost(uint32, optional, tag = "1")]
+    pub max: Option<u32>,
+}
+
+impl From<obj::ListConnectedServersReq> for ListConnectedServersReq {
+    fn from(value: obj::ListConnectedServersReq) -> Self {
+        Self { max: value.max }
+    }
+}
+impl TryFrom<ListConnectedServersReq> for obj::ListConnectedServersReq {
+    type Error = ProtoError;
+
+    fn try_from(value: ListConnectedServersReq) -> Result<Self, Self::Error> {
+        Ok(Self { max: value.max })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PingReq {
+    #[prost(uint64, tag = "1")]
+    pub nonce: u64,
+    #[prost(uint64, tag = "2")]
+    pub sent_time: u64,
+}
+
+impl From<obj::PingReq> for PingReq {
+    fn from(value: obj::PingReq) -> Self {
+        Self {
+            nonce: value.nonce,
+            sent_time: value.sent_time,
+        }
+    }
+}
+impl TryFrom<PingReq> for obj::PingReq {
+    type Error = ProtoError;
+
+    fn try_from(value: PingReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            nonce: value.nonce,
+            sent_time: value.sent_time,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PongResp {
+    #[prost(uint64, tag = "1")]
+    pub nonce: u64,
+    #[prost(uint64, tag = "2")]
+    pub sent_time: u64,
+    #[prost(uint64, tag = "3")]
+    pub received_time: u64,
+}
+
+impl From<obj::PongResp> for PongResp {
+    fn from(value: obj::PongResp) -> Self {
+        Self {
+            nonce: value.nonce,
+            sent_time: value.sent_time,
+            received_time: value.received_time,
+        }
+    }
+}
+impl TryFrom<PongResp> for obj::PongResp {
+    type Error = ProtoError;
+
+    fn try_from(value: PongResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            nonce: value.nonce,
+            sent_time: value.sent_time,
+            received_time: value.received_time,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KeepDown {
+    #[prost(uint64, tag = "1")]
+    pub min_delay: u64,
+    #[prost(uint64, tag = "2")]
+    pub time: u64,
+}
+
+impl From<obj::KeepDown> for KeepDown {
+    fn from(value: obj::KeepDown) -> Self {
+        Self {
+            min_delay: value.min_delay,
+            time: value.time,
+        }
+    }
+}
+impl TryFrom<KeepDown> for obj::KeepDown {
+    type Error = ProtoError;
+
+    fn try_from(value: KeepDown) -> Result<Self, Self::Error> {
+        Ok(Self {
+            min_delay: value.min_delay,
+            time: value.time,
+        })
+    }
+}
+
+triad!(
+    /// A [`KeyTriad`](crypto::KeyTriad) over a [`KeepDown`].
+    KeepDownTriad,
+    obj::KeepDown,
+    KeepDown
+);
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Goodbye {
+    #[prost(uint32, tag = "1")]
+    pub code: u32,
+    #[prost(string, tag = "2")]
+    pub reason: String,
+    #[prost(message, optional, tag = "3")]
+    pub keep_down: Option<KeepDownTriad>,
+}
+
+impl From<obj::Goodbye> for Goodbye {
+    fn from(value: obj::Goodbye) -> Self {
+        Self {
+            code: value.code.0 as u32,
+            reason: value.reason,
+            keep_down: value.keep_down.map(Into::into),
+        }
+    }
+}
+impl TryFrom<Goodbye> for obj::Goodbye {
+    type Error = ProtoError;
+
+    fn try_from(value: Goodbye) -> Result<Self, Self::Error> {
+        Ok(Self {
+            code: obj::GoodbyeCode(narrow("Goodbye.code", value.code)?),
+            reason: value.reason,
+            keep_down: value.keep_down.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IdentifyExtensions {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub tos_hash: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub node_key: Option<Vec<u8>>,
+    #[prost(string, repeated, tag = "3")]
+    pub required_features: Vec<String>,
+}
+
+impl From<obj::IdentifyExtensions> for IdentifyExtensions {
+    fn from(value: obj::IdentifyExtensions) -> Self {
+        Self {
+            tos_hash: value.tos_hash.map(|hash| hash.0.to_vec()),
+            node_key: value.node_key.map(|key| key.0.to_vec()),
+            required_features: features(value.required_features),
+        }
+    }
+}
+impl TryFrom<IdentifyExtensions> for obj::IdentifyExtensions {
+    type Error = ProtoError;
+
+    fn try_from(value: IdentifyExtensions) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tos_hash: value
+                .tos_hash
+                .map(|hash| array("IdentifyExtensions.tos_hash", hash).map(HashMsg))
+                .transpose()?,
+            node_key: value
+                .node_key
+                .map(|key| public_key("IdentifyExtensions.node_key", key))
+                .transpose()?,
+            required_features: native_features(
+                "IdentifyExtensions.required_features",
+                value.required_features,
+            )?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IdentifyData {
+    #[prost(bytes = "vec", tag = "1")]
+    pub salt: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub start_time: u64,
+    #[prost(uint64, tag = "3")]
+    pub expire_time: u64,
+    #[prost(message, optional, tag = "4")]
+    pub extensions: Option<IdentifyExtensions>,
+    #[prost(uint32, optional, tag = "5")]
+    pub difficulty: Option<u32>,
+    #[prost(uint64, optional, tag = "6")]
+    pub work: Option<u64>,
+    #[prost(uint32, tag = "7")]
+    pub usage: u32,
+}
+
+impl From<obj::IdentifyData> for IdentifyData {
+    fn from(value: obj::IdentifyData) -> Self {
+        Self {
+            salt: value.salt.to_vec(),
+            start_time: value.start_time,
+            expire_time: value.expire_time,
+            extensions: Some(value.extensions.into()),
+            difficulty: value.difficulty.map(Into::into),
+            work: value.work,
+            usage: value.usage.0,
+        }
+    }
+}
+impl TryFrom<IdentifyData> for obj::IdentifyData {
+    type Error = ProtoError;
+
+    fn try_from(value: IdentifyData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            salt: array("IdentifyData.salt", value.salt)?,
+            start_time: value.start_time,
+            expire_time: value.expire_time,
+            extensions: value
+                .extensions
+                .map(TryInto::try_into)
+                .transpose()?
+                .unwrap_or_default(),
+            difficulty: value
+                .difficulty
+                .map(|difficulty| narrow("IdentifyData.difficulty", difficulty))
+                .transpose()?,
+            work: value.work,
+            usage: obj::KeyUsage(value.usage),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TokenScope {
+    Resumption = 0,
+    Dial = 1,
+    Invite = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Token {
+    #[prost(enumeration = "TokenScope", tag = "1")]
+    pub scope: i32,
+    #[prost(uint64, tag = "2")]
+    pub expire_time: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub nonce: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub mac: Vec<u8>,
+}
+
+impl From<token::Token> for Token {
+    fn from(value: token::Token) -> Self {
+        let scope = match value.scope {
+            NativeTokenScope::Resumption => TokenScope::Resumption,
+            NativeTokenScope::Dial => TokenScope::Dial,
+            NativeTokenScope::Invite => TokenScope::Invite,
+        };
+        Self {
+            scope: scope as i32,
+            expire_time: value.expire_time,
+            nonce: value.nonce.to_vec(),
+            mac: value.mac.0.to_vec(),
+        }
+    }
+}
+impl TryFrom<Token> for token::Token {
+    type Error = ProtoError;
+
+    fn try_from(value: Token) -> Result<Self, Self::Error> {
+        let scope = match TokenScope::try_from(value.scope) {
+            Ok(TokenScope::Resumption) => NativeTokenScope::Resumption,
+            Ok(TokenScope::Dial) => NativeTokenScope::Dial,
+            Ok(TokenScope::Invite) => NativeTokenScope::Invite,
+            Err(_) => return Err(ProtoError::OutOfRange("Token.scope")),
+        };
+        Ok(Self {
+            scope,
+            expire_time: value.expire_time,
+            nonce: array("Token.nonce", value.nonce)?,
+            mac: Mac(array("Token.mac", value.mac)?),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IdentifyResp {
+    #[prost(message, optional, tag = "1")]
+    pub resumption_token: Option<Token>,
+}
+
+impl From<obj::IdentifyResp> for IdentifyResp {
+    fn from(value: obj::IdentifyResp) -> Self {
+        Self {
+            resumption_token: Some(value.resumption_token.0.into()),
+        }
+    }
+}
+impl TryFrom<IdentifyResp> for obj::IdentifyResp {
+    type Error = ProtoError;
+
+    fn try_from(value: IdentifyResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            resumption_token: obj::ResumptionToken(
+                required("IdentifyResp.resumption_token", value.resumption_token)?.try_into()?,
+            ),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum PresenceStatus {
+    Online = 0,
+    Away = 1,
+    DoNotDisturb = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PresenceUpdate {
+    #[prost(enumeration = "PresenceStatus", tag = "1")]
+    pub status: i32,
+    #[prost(string, optional, tag = "2")]
+    pub message: Option<String>,
+    #[prost(uint64, tag = "3")]
+    pub time: u64,
+}
+
+impl From<obj::PresenceUpdate> for PresenceUpdate {
+    fn from(value: obj::PresenceUpdate) -> Self {
+        let status = match value.status {
+            obj::PresenceStatus::Online => PresenceStatus::Online,
+            obj::PresenceStatus::Away => PresenceStatus::Away,
+            obj::PresenceStatus::DoNotDisturb => PresenceStatus::DoNotDisturb,
+        };
+        Self {
+            status: status as i32,
+            message: value.message,
+            time: value.time,
+        }
+    }
+}
+impl TryFrom<PresenceUpdate> for obj::PresenceUpdate {
+    type Error = ProtoError;
+
+    fn try_from(value: PresenceUpdate) -> Result<Self, Self::Error> {
+        let status = match PresenceStatus::try_from(value.status) {
+            Ok(PresenceStatus::Online) => obj::PresenceStatus::Online,
+            Ok(PresenceStatus::Away) => obj::PresenceStatus::Away,
+            Ok(PresenceStatus::DoNotDisturb) => obj::PresenceStatus::DoNotDisturb,
+            Err(_) => return Err(ProtoError::OutOfRange("PresenceUpdate.status")),
+        };
+        Ok(Self {
+            status,
+            message: value.message,
+            time: value.time,
+        })
+    }
+}
+
+triad!(
+    /// A [`KeyTriad`](crypto::KeyTriad) over a [`PresenceUpdate`].
+    PresenceTriad,
+    obj::PresenceUpdate,
+    PresenceUpdate
+);
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KeysExistsResp {
+    #[prost(message, repeated, tag = "1")]
+    pub triads: Vec<KeyTriad>,
+    #[prost(message, repeated, tag = "2")]
+    pub presence: Vec<PresenceTriad>,
+}
+
+impl From<obj::KeysExistsResp> for KeysExistsResp {
+    fn from(value: obj::KeysExistsResp) -> Self {
+        Self {
+            triads: value.triads.into_iter().map(Into::into).collect(),
+            presence: value.presence.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+impl TryFrom<KeysExistsResp> for obj::KeysExistsResp {
+    type Error = ProtoError;
+
+    fn try_from(value: KeysExistsResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            triads: collect(value.triads)?,
+            presence: collect(value.presence)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Transport {
+    Tcp = 0,
+    Quic = 1,
+    Ws = 2,
+    Unknown = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ServerInfo {
+    #[prost(string, tag = "1")]
+    pub domain: String,
+    #[prost(uint32, repeated, tag = "2")]
+    pub ports: Vec<u32>,
+    #[prost(enumeration = "Transport", repeated, tag = "3")]
+    pub transports: Vec<i32>,
+    #[prost(uint32, optional, tag = "4")]
+    pub api_version: Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl From<obj::ServerInfo> for ServerInfo {
+    fn from(value: obj::ServerInfo) -> Self {
+        let transports = value
+            .transports
+            .into_iter()
+            .map(|transport| match transport {
+                obj::Transport::Tcp => Transport::Tcp as i32,
+                obj::Transport::Quic => Transport::Quic as i32,
+                obj::Transport::Ws => Transport::Ws as i32,
+                obj::Transport::Unknown => Transport::Unknown as i32,
+            });
+        Self {
+            domain: value.domain.to_string(),
+            ports: value.ports.into_iter().map(Into::into).collect(),
+            transports: transports.collect(),
+            api_version: value.api_version,
+            public_key: value.public_key.map(|key| key.0.to_vec()),
+        }
+    }
+}
+impl TryFrom<ServerInfo> for obj::ServerInfo {
+    type Error = ProtoError;
+
+    fn try_from(value: ServerInfo) -> Result<Self, Self::Error> {
+        let transports =
+            value
+                .transports
+                .into_iter()
+                .map(|transport| match Transport::try_from(transport) {
+                    Ok(Transport::Tcp) => obj::Transport::Tcp,
+                    Ok(Transport::Quic) => obj::Transport::Quic,
+                    Ok(Transport::Ws) => obj::Transport::Ws,
+                    // transports of newer versions
+                    Ok(Transport::Unknown) | Err(_) => obj::Transport::Unknown,
+                });
+        Ok(Self {
+            domain: value.domain.into(),
+            ports: value
+                .ports
+                .into_iter()
+                .map(|port| narrow("ServerInfo.ports", port))
+                .collect::<Result<_, _>>()?,
+            transports: transports.collect(),
+            api_version: value.api_version,
+            public_key: value
+                .public_key
+                .map(|key| public_key("ServerInfo.public_key", key))
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ConnectedServer {
+    /// The address, as an IP address and a port separated by a colon.
+    #[prost(string, optional, tag = "1")]
+    pub addr: Option<String>,
+    #[prost(message, optional, tag = "2")]
+    pub server_info: Option<ServerInfo>,
+    #[prost(message, optional, tag = "3")]
+    pub load: Option<Load>,
+    #[prost(bool, tag = "4")]
+    pub verified: bool,
+}
+
+impl From<obj::ConnectedServer> for ConnectedServer {
+    fn from(value: obj::ConnectedServer) -> Self {
+        Self {
+            addr: value.addr.map(|addr| addr.to_string()),
+            server_info: Some(value.server_info.into()),
+            load: value.load.map(Into::into),
+            verified: value.verified,
+        }
+    }
+}
+impl TryFrom<ConnectedServer> for obj::ConnectedServer {
+    type Error = ProtoError;
+
+    fn try_from(value: ConnectedServer) -> Result<Self, Self::Error> {
+        Ok(Self {
+            addr: value
+                .addr
+                .map(|addr| addr.parse::<SocketAddr>())
+                .transpose()
+                .map_err(|_| ProtoError::OutOfRange("ConnectedServer.addr"))?,
+            server_info: required("ConnectedServer.server_info", value.server_info)?.try_into()?,
+            load: value.load.map(TryInto::try_into).transpose()?,
+            verified: value.verified,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListConnectedServersResp {
+    #[prost(message, repeated, tag = "1")]
+    pub servers: Vec<ConnectedServer>,
+}
+
+impl From<obj::ListConnectedServersResp> for ListConnectedServersResp {
+    fn from(value: obj::ListConnectedServersResp) -> Self {
+        Self {
+            servers: value.servers.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+impl TryFrom<ListConnectedServersResp> for obj::ListConnectedServersResp {
+    type Error = ProtoError;
+
+    fn try_from(value: ListConnectedServersResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            servers: collect(value.servers)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ErrorResp {
+    #[prost(uint32, tag = "1")]
+    pub code: u32,
+    #[prost(string, tag = "2")]
+    pub message: String,
+    #[prost(uint64, optional, tag = "3")]
+    pub retry_after: Option<u64>,
+}
+
+impl From<obj::ErrorResp> for ErrorResp {
+    fn from(value: obj::ErrorResp) -> Self {
+        Self {
+            code: value.code.0 as u32,
+            message: value.message,
+            retry_after: value.retry_after,
+        }
+    }
+}
+impl TryFrom<ErrorResp> for obj::ErrorResp {
+    type Error = ProtoError;
+
+    fn try_from(value: ErrorResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            code: obj::ErrorCode(narrow("ErrorResp.code", value.code)?),
+            message: value.message,
+            retry_after: value.retry_after,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AckResp {}
+
+impl From<obj::AckResp> for AckResp {
+    fn from(_: obj::AckResp) -> Self {
+        Self {}
+    }
+}
+impl TryFrom<AckResp> for obj::AckResp {
+    type Error = ProtoError;
+
+    fn try_from(_: AckResp) -> Result<Self, Self::Error> {
+        Ok(Self {})
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BroadcastReq {
+    #[prost(message, optional, tag = "1")]
+    pub payload: Option<KeyTriad>,
+    #[prost(string, optional, tag = "2")]
+    pub domain: Option<String>,
+}
+
+impl From<obj::BroadcastReq> for BroadcastReq {
+    fn from(value: obj::BroadcastReq) -> Self {
+        Self {
+            payload: Some(value.payload.into()),
+            domain: value.domain.map(|domain| domain.to_string()),
+        }
+    }
+}
+impl TryFrom<BroadcastReq> for obj::BroadcastReq {
+    type Error = ProtoError;
+
+    fn try_from(value: BroadcastReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            payload: required("BroadcastReq.payload", value.payload)?.try_into()?,
+            domain: value.domain.map(Into::into),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BroadcastResp {
+    #[prost(uint32, tag = "1")]
+    pub recipients: u32,
+}
+
+impl From<obj::BroadcastResp> for BroadcastResp {
+    fn from(value: obj::BroadcastResp) -> Self {
+        Self {
+            recipients: value.recipients,
+        }
+    }
+}
+impl TryFrom<BroadcastResp> for obj::BroadcastResp {
+    type Error = ProtoError;
+
+    fn try_from(value: BroadcastResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            recipients: value.recipients,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RelayMessage {
+    #[prost(bytes = "vec", tag = "1")]
+    pub to: Vec<u8>,
+    #[prost(message, optional, tag = "2")]
+    pub payload: Option<KeyTriad>,
+    #[prost(uint64, optional, tag = "3")]
+    pub ttl: Option<u64>,
+}
+
+impl From<obj::RelayMessage> for RelayMessage {
+    fn from(value: obj::RelayMessage) -> Self {
+        Self {
+            to: value.to.0.to_vec(),
+            payload: Some(value.payload.into()),
+            ttl: value.ttl,
+        }
+    }
+}
+impl TryFrom<RelayMessage> for obj::RelayMessage {
+    type Error = ProtoError;
+
+    fn try_from(value: RelayMessage) -> Result<Self, Self::Error> {
+        Ok(Self {
+            to: public_key("RelayMessage.to", value.to)?,
+            payload: required("RelayMessage.payload", value.payload)?.try_into()?,
+            ttl: value.ttl,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RelayResp {
+    #[prost(bytes = "vec", tag = "1")]
+    pub id: Vec<u8>,
+}
+
+impl From<obj::RelayResp> for RelayResp {
+    fn from(value: obj::RelayResp) -> Self {
+        Self {
+            id: value.id.0 .0.to_vec(),
+        }
+    }
+}
+impl TryFrom<RelayResp> for obj::RelayResp {
+    type Error = ProtoError;
+
+    fn try_from(value: RelayResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: message_id("RelayResp.id", value.id)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KeyRange {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub lower: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub upper: Option<Vec<u8>>,
+}
+
+impl From<keyset::KeyRange> for KeyRange {
+    fn from(value: keyset::KeyRange) -> Self {
+        Self {
+            lower: value.lower.map(|key| key.0.to_vec()),
+            upper: value.upper.map(|key| key.0.to_vec()),
+        }
+    }
+}
+impl TryFrom<KeyRange> for keyset::KeyRange {
+    type Error = ProtoError;
+
+    fn try_from(value: KeyRange) -> Result<Self, Self::Error> {
+        Ok(Self {
+            lower: value
+                .lower
+                .map(|key| public_key("KeyRange.lower", key))
+                .transpose()?,
+            upper: value
+                .upper
+                .map(|key| public_key("KeyRange.upper", key))
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RangeItem {
+    #[prost(oneof = "range_item::Item", tags = "1, 2")]
+    pub item: Option<range_item::Item>,
+}
+
+pub mod range_item {
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Item {
+        #[prost(message, tag = "1")]
+        Fingerprint(Fingerprint),
+        #[prost(message, tag = "2")]
+        Keys(Keys),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Fingerprint {
+        #[prost(message, optional, tag = "1")]
+        pub range: Option<KeyRange>,
+        #[prost(uint64, tag = "2")]
+        pub count: u64,
+        #[prost(bytes = "vec", tag = "3")]
+        pub fingerprint: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Keys {
+        #[prost(message, optional, tag = "1")]
+        pub range: Option<KeyRange>,
+        #[prost(bytes = "vec", repeated, tag = "2")]
+        pub keys: Vec<Vec<u8>>,
+    }
+}
+
+impl From<keyset::RangeItem> for RangeItem {
+    fn from(value: keyset::RangeItem) -> Self {
+        use range_item::{Fingerprint, Item, Keys};
+
+        let item = match value {
+            keyset::RangeItem::Fingerprint {
+                range,
+                count,
+                fingerprint,
+            } => Item::Fingerprint(Fingerprint {
+                range: Some(range.into()),
+                count,
+                fingerprint: fingerprint.0.to_vec(),
+            }),
+            keyset::RangeItem::Keys { range, keys } => Item::Keys(Keys {
+                range: Some(range.into()),
+                keys: keys.into_iter().map(|key| key.0.to_vec()).collect(),
+            }),
+        };
+        Self { item: Some(item) }
+    }
+}
+impl TryFrom<RangeItem> for keyset::RangeItem {
+    type Error = ProtoError;
+
+    fn try_from(value: RangeItem) -> Result<Self, Self::Error> {
+        use range_item::Item;
+
+        Ok(match required("RangeItem.item", value.item)? {
+            Item::Fingerprint(item) => Self::Fingerprint {
+                range: required("RangeItem.fingerprint.range", item.range)?.try_into()?,
+                count: item.count,
+                fingerprint: hash("RangeItem.fingerprint.fingerprint", item.fingerprint)?,
+            },
+            Item::Keys(item) => Self::Keys {
+                range: required("RangeItem.keys.range", item.range)?.try_into()?,
+                keys: public_keys("RangeItem.keys.keys", item.keys)?,
+            },
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Reconciliation {
+    #[prost(message, repeated, tag = "1")]
+    pub items: Vec<RangeItem>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub keys: Vec<Vec<u8>>,
+}
+
+impl From<keyset::Reconciliation> for Reconciliation {
+    fn from(value: keyset::Reconciliation) -> Self {
+        Self {
+            items: value.items.into_iter().map(Into::into).collect(),
+            keys: value.keys.into_iter().map(|key| key.0.to_vec()).collect(),
+        }
+    }
+}
+impl TryFrom<Reconciliation> for keyset::Reconciliation {
+    type Error = ProtoError;
+
+    fn try_from(value: Reconciliation) -> Result<Self, Self::Error> {
+        Ok(Self {
+            items: collect(value.items)?,
+            keys: public_keys("Reconciliation.keys", value.keys)?,
+        })
+    }
+}
+
+/// The round of a [`ReconcileReq`](obj::ReconcileReq) or a
+/// [`ReconcileResp`](obj::ReconcileResp).
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Reconcile {
+    #[prost(message, optional, tag = "1")]
+    pub round: Option<Reconciliation>,
+}
+
+impl From<obj::ReconcileReq> for Reconcile {
+    fn from(value: obj::ReconcileReq) -> Self {
+        Self {
+            round: Some(value.round.into()),
+        }
+    }
+}
+impl TryFrom<Reconcile> for obj::ReconcileReq {
+    type Error = ProtoError;
+
+    fn try_from(value: Reconcile) -> Result<Self, Self::Error> {
+        Ok(Self {
+            round: required("Reconcile.round", value.round)?.try_into()?,
+        })
+    }
+}
+impl From<obj::ReconcileResp> for Reconcile {
+    fn from(value: obj::ReconcileResp) -> Self {
+        Self {
+            round: Some(value.round.into()),
+        }
+    }
+}
+impl TryFrom<Reconcile> for obj::ReconcileResp {
+    type Error = ProtoError;
+
+    fn try_from(value: Reconcile) -> Result<Self, Self::Error> {
+        Ok(Self {
+            round: required("Reconcile.round", value.round)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FetchMailReq {
+    #[prost(bytes = "vec", tag = "1")]
+    pub key: Vec<u8>,
+}
+
+impl From<obj::FetchMailReq> for FetchMailReq {
+    fn from(value: obj::FetchMailReq) -> Self {
+        Self {
+            key: value.key.0.to_vec(),
+        }
+    }
+}
+impl TryFrom<FetchMailReq> for obj::FetchMailReq {
+    type Error = ProtoError;
+
+    fn try_from(value: FetchMailReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            key: public_key("FetchMailReq.key", value.key)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Mail {
+    #[prost(bytes = "vec", tag = "1")]
+    pub id: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub time: u64,
+    #[prost(uint64, optional, tag = "3")]
+    pub expire_time: Option<u64>,
+    #[prost(message, optional, tag = "4")]
+    pub payload: Option<KeyTriad>,
+}
+
+impl From<obj::Mail> for Mail {
+    fn from(value: obj::Mail) -> Self {
+        Self {
+            id: value.id.0 .0.to_vec(),
+            time: value.time,
+            expire_time: value.expire_time,
+            payload: Some(value.payload.into()),
+        }
+    }
+}
+impl TryFrom<Mail> for obj::Mail {
+    type Error = ProtoError;
+
+    fn try_from(value: Mail) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: message_id("Mail.id", value.id)?,
+            time: value.time,
+            expire_time: value.expire_time,
+            payload: required("Mail.payload", value.payload)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum EvictionReason {
+    Expired = 0,
+    MaxMessages = 1,
+    MaxBytes = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EvictionNotice {
+    #[prost(bytes = "vec", tag = "1")]
+    pub id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub from: Vec<u8>,
+    #[prost(enumeration = "EvictionReason", tag = "3")]
+    pub reason: i32,
+    #[prost(uint64, tag = "4")]
+    pub time: u64,
+}
+
+impl From<obj::EvictionNotice> for EvictionNotice {
+    fn from(value: obj::EvictionNotice) -> Self {
+        let reason = match value.reason {
+            obj::EvictionReason::Expired => EvictionReason::Expired,
+            obj::EvictionReason::MaxMessages => EvictionReason::MaxMessages,
+            obj::EvictionReason::MaxBytes => EvictionReason::MaxBytes,
+        };
+        Self {
+            id: value.id.0 .0.to_vec(),
+            from: value.from.0.to_vec(),
+            reason: reason as i32,
+            time: value.time,
+        }
+    }
+}
+impl TryFrom<EvictionNotice> for obj::EvictionNotice {
+    type Error = ProtoError;
+
+    fn try_from(value: EvictionNotice) -> Result<Self, Self::Error> {
+        let reason = match EvictionReason::try_from(value.reason) {
+            Ok(EvictionReason::Expired) => obj::EvictionReason::Expired,
+            Ok(EvictionReason::MaxMessages) => obj::EvictionReason::MaxMessages,
+            Ok(EvictionReason::MaxBytes) => obj::EvictionReason::MaxBytes,
+            Err(_) => return Err(ProtoError::OutOfRange("EvictionNotice.reason")),
+        };
+        Ok(Self {
+            id: message_id("EvictionNotice.id", value.id)?,
+            from: public_key("EvictionNotice.from", value.from)?,
+            reason,
+            time: value.time,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FetchMailResp {
+    #[prost(message, repeated, tag = "1")]
+    pub mail: Vec<Mail>,
+    #[prost(message, repeated, tag = "2")]
+    pub evicted: Vec<EvictionNotice>,
+}
+
+impl From<obj::FetchMailResp> for FetchMailResp {
+    fn from(value: obj::FetchMailResp) -> Self {
+        Self {
+            mail: value.mail.into_iter().map(Into::into).collect(),
+            evicted: value.evicted.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+impl TryFrom<FetchMailResp> for obj::FetchMailResp {
+    type Error = ProtoError;
+
+    fn try_from(value: FetchMailResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            mail: collect(value.mail)?,
+            evicted: collect(value.evicted)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ReceiptStatus {
+    Delivered = 0,
+    Read = 1,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Receipt {
+    #[prost(bytes = "vec", tag = "1")]
+    pub id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub message: Vec<u8>,
+    #[prost(enumeration = "ReceiptStatus", tag = "3")]
+    pub status: i32,
+    #[prost(uint64, tag = "4")]
+    pub time: u64,
+}
+
+impl From<obj::Receipt> for Receipt {
+    fn from(value: obj::Receipt) -> Self {
+        let status = match value.status {
+            obj::ReceiptStatus::Delivered => ReceiptStatus::Delivered,
+            obj::ReceiptStatus::Read => ReceiptStatus::Read,
+        };
+        Self {
+            id: value.id.0 .0.to_vec(),
+            message: value.message.0.to_vec(),
+            status: status as i32,
+            time: value.time,
+        }
+    }
+}
+impl TryFrom<Receipt> for obj::Receipt {
+    type Error = ProtoError;
+
+    fn try_from(value: Receipt) -> Result<Self, Self::Error> {
+        let status = match ReceiptStatus::try_from(value.status) {
+            Ok(ReceiptStatus::Delivered) => obj::ReceiptStatus::Delivered,
+            Ok(ReceiptStatus::Read) => obj::ReceiptStatus::Read,
+            Err(_) => return Err(ProtoError::OutOfRange("Receipt.status")),
+        };
+        Ok(Self {
+            id: message_id("Receipt.id", value.id)?,
+            message: hash("Receipt.message", value.message)?,
+            status,
+            time: value.time,
+        })
+    }
+}
+
+triad!(
+    /// A [`KeyTriad`](crypto::KeyTriad) over a [`Receipt`].
+    ReceiptTriad,
+    obj::Receipt,
+    Receipt
+);
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SendReceiptReq {
+    #[prost(message, optional, tag = "1")]
+    pub receipt: Option<ReceiptTriad>,
+}
+
+impl From<obj::SendReceiptReq> for SendReceiptReq {
+    fn from(value: obj::SendReceiptReq) -> Self {
+        Self {
+            receipt: Some(value.receipt.into()),
+        }
+    }
+}
+impl TryFrom<SendReceiptReq> for obj::SendReceiptReq {
+    type Error = ProtoError;
+
+    fn try_from(value: SendReceiptReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            receipt: required("SendReceiptReq.receipt", value.receipt)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReceiptReq {
+    #[prost(bytes = "vec", tag = "1")]
+    pub id: Vec<u8>,
+}
+
+impl From<obj::ReceiptReq> for ReceiptReq {
+    fn from(value: obj::ReceiptReq) -> Self {
+        Self {
+            id: value.id.0 .0.to_vec(),
+        }
+    }
+}
+impl TryFrom<ReceiptReq> for obj::ReceiptReq {
+    type Error = ProtoError;
+
+    fn try_from(value: ReceiptReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: message_id("ReceiptReq.id", value.id)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReceiptResp {
+    #[prost(message, optional, tag = "1")]
+    pub receipt: Option<ReceiptTriad>,
+}
+
+impl From<obj::ReceiptResp> for ReceiptResp {
+    fn from(value: obj::ReceiptResp) -> Self {
+        Self {
+            receipt: value.receipt.map(Into::into),
+        }
+    }
+}
+impl TryFrom<ReceiptResp> for obj::ReceiptResp {
+    type Error = ProtoError;
+
+    fn try_from(value: ReceiptResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            receipt: value.receipt.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PublishPresenceReq {
+    #[prost(message, optional, tag = "1")]
+    pub presence: Option<PresenceTriad>,
+}
+
+impl From<obj::PublishPresenceReq> for PublishPresenceReq {
+    fn from(value: obj::PublishPresenceReq) -> Self {
+        Self {
+            presence: Some(value.presence.into()),
+        }
+    }
+}
+impl TryFrom<PublishPresenceReq> for obj::PublishPresenceReq {
+    type Error = ProtoError;
+
+    fn try_from(value: PublishPresenceReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            presence: required("PublishPresenceReq.presence", value.presence)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LogHead {
+    #[prost(uint64, tag = "1")]
+    pub size: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub head: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub time: u64,
+}
+
+impl From<log::LogHead> for LogHead {
+    fn from(value: log::LogHead) -> Self {
+        Self {
+            size: value.size,
+            head: value.head.0.to_vec(),
+            time: value.time,
+        }
+    }
+}
+impl TryFrom<LogHead> for log::LogHead {
+    type Error = ProtoError;
+
+    fn try_from(value: LogHead) -> Result<Self, Self::Error> {
+        Ok(Self {
+            size: value.size,
+            head: hash("LogHead.head", value.head)?,
+            time: value.time,
+        })
+    }
+}
+
+triad!(
+    /// A [`KeyTriad`](crypto::KeyTriad) over a [`LogHead`].
+    LogHeadTriad,
+    log::LogHead,
+    LogHead
+);
+triad!(
+    /// A [`KeyTriad`](crypto::KeyTriad) over a [`LogHeadTriad`], that cosigns the head.
+    CosignatureTriad,
+    crypto::KeyTriad<log::LogHead>,
+    LogHeadTriad
+);
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LogProof {
+    #[prost(uint64, tag = "1")]
+    pub from: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub start: Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub leaves: Vec<Vec<u8>>,
+}
+
+impl From<log::LogProof> for LogProof {
+    fn from(value: log::LogProof) -> Self {
+        Self {
+            from: value.from,
+            start: value.start.0.to_vec(),
+            leaves: value
+                .leaves
+                .into_iter()
+                .map(|leaf| leaf.0.to_vec())
+                .collect(),
+        }
+    }
+}
+impl TryFrom<LogProof> for log::LogProof {
+    type Error = ProtoError;
+
+    fn try_from(value: LogProof) -> Result<Self, Self::Error> {
+        Ok(Self {
+            from: value.from,
+            start: hash("LogProof.start", value.start)?,
+            leaves: value
+                .leaves
+                .into_iter()
+                .map(|leaf| hash("LogProof.leaves", leaf))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CrossSignReq {
+    #[prost(message, optional, tag = "1")]
+    pub head: Option<LogHeadTriad>,
+    #[prost(message, optional, tag = "2")]
+    pub proof: Option<LogProof>,
+}
+
+impl From<obj::CrossSignReq> for CrossSignReq {
+    fn from(value: obj::CrossSignReq) -> Self {
+        Self {
+            head: Some(value.head.into()),
+            proof: Some(value.proof.into()),
+        }
+    }
+}
+impl TryFrom<CrossSignReq> for obj::CrossSignReq {
+    type Error = ProtoError;
+
+    fn try_from(value: CrossSignReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            head: required("CrossSignReq.head", value.head)?.try_into()?,
+            proof: required("CrossSignReq.proof", value.proof)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CrossSignResp {
+    #[prost(oneof = "cross_sign_resp::Result", tags = "1, 2")]
+    pub result: Option<cross_sign_resp::Result>,
+}
+
+pub mod cross_sign_resp {
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Result {
+        #[prost(message, tag = "1")]
+        Cosigned(CosignatureTriad),
+        /// The size of the head the receiver last cosigned, that the proof must start at.
+        #[prost(uint64, tag = "2")]
+        ProofRequired(u64),
+    }
+}
+
+impl From<obj::CrossSignResp> for CrossSignResp {
+    fn from(value: obj::CrossSignResp) -> Self {
+        use cross_sign_resp::Result;
+
+        let result = match value {
+            obj::CrossSignResp::Cosigned { cosignature } => Result::Cosigned((*cosignature).into()),
+            obj::CrossSignResp::ProofRequired { from } => Result::ProofRequired(from),
+        };
+        Self {
+            result: Some(result),
+        }
+    }
+}
+impl TryFrom<CrossSignResp> for obj::CrossSignResp {
+    type Error = ProtoError;
+
+    fn try_from(value: CrossSignResp) -> Result<Self, Self::Error> {
+        use cross_sign_resp::Result;
+
+        Ok(match required("CrossSignResp.result", value.result)? {
+            Result::Cosigned(cosignature) => Self::Cosigned {
+                cosignature: Box::new(cosignature.try_into()?),
+            },
+            Result::ProofRequired(from) => Self::ProofRequired { from },
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct JournalPosition {
+    #[prost(uint64, tag = "1")]
+    pub epoch: u64,
+    #[prost(uint64, tag = "2")]
+    pub seq: u64,
+}
+
+impl From<obj::JournalPosition> for JournalPosition {
+    fn from(value: obj::JournalPosition) -> Self {
+        Self {
+            epoch: value.epoch,
+            seq: value.seq,
+        }
+    }
+}
+impl TryFrom<JournalPosition> for obj::JournalPosition {
+    type Error = ProtoError;
+
+    fn try_from(value: JournalPosition) -> Result<Self, Self::Error> {
+        Ok(Self {
+            epoch: value.epoch,
+            seq: value.seq,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct JournalReq {
+    #[prost(message, optional, tag = "1")]
+    pub since: Option<JournalPosition>,
+    #[prost(uint32, optional, tag = "2")]
+    pub max: Option<u32>,
+}
+
+impl From<obj::JournalReq> for JournalReq {
+    fn from(value: obj::JournalReq) -> Self {
+        Self {
+            since: Some(value.since.into()),
+            max: value.max,
+        }
+    }
+}
+impl TryFrom<JournalReq> for obj::JournalReq {
+    type Error = ProtoError;
+
+    fn try_from(value: JournalReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            since: required("JournalReq.since", value.since)?.try_into()?,
+            max: value.max,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct JournalEntry {
+    #[prost(oneof = "journal_entry::Entry", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub entry: Option<journal_entry::Entry>,
+}
+
+pub mod journal_entry {
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Entry {
+        #[prost(message, tag = "1")]
+        Identified(Identified),
+        #[prost(message, tag = "2")]
+        Revoked(Revoked),
+        #[prost(message, tag = "3")]
+        Subscribed(Subscription),
+        #[prost(message, tag = "4")]
+        Unsubscribed(Subscription),
+        #[prost(message, tag = "5")]
+        Deposited(Deposited),
+        #[prost(message, tag = "6")]
+        Delivered(Delivered),
+        #[prost(message, tag = "7")]
+        Fetched(Fetched),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Identified {
+        #[prost(bytes = "vec", tag = "1")]
+        pub public_key: Vec<u8>,
+        #[prost(uint32, tag = "2")]
+        pub usage: u32,
+        #[prost(uint64, tag = "3")]
+        pub time: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Revoked {
+        #[prost(bytes = "vec", tag = "1")]
+        pub public_key: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Subscription {
+        #[prost(bytes = "vec", tag = "1")]
+        pub subscriber: Vec<u8>,
+        #[prost(bytes = "vec", repeated, tag = "2")]
+        pub keys: Vec<Vec<u8>>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Deposited {
+        #[prost(bytes = "vec", tag = "1")]
+        pub to: Vec<u8>,
+        #[prost(message, optional, tag = "2")]
+        pub mail: Option<Mail>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Delivered {
+        #[prost(bytes = "vec", tag = "1")]
+        pub to: Vec<u8>,
+        #[prost(bytes = "vec", repeated, tag = "2")]
+        pub ids: Vec<Vec<u8>>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Fetched {
+        #[prost(bytes = "vec", tag = "1")]
+        pub to: Vec<u8>,
+    }
+}
+
+impl From<obj::JournalEntry> for JournalEntry {
+    fn from(value: obj::JournalEntry) -> Self {
+        use journal_entry::*;
+
+        let keys = |keys: Vec<PublicKey>| keys.into_iter().map(|key| key.0.to_vec()).collect();
+        let entry = match value {
+            obj::JournalEntry::Identified {
+                public_key,
+                usage,
+                time,
+            } => Entry::Identified(Identified {
+                public_key: public_key.0.to_vec(),
+                usage: usage.0,
+                time,
+            }),
+            obj::JournalEntry::Revoked { public_key } => Entry::Revoked(Revoked {
+                public_key: public_key.0.to_vec(),
+            }),
+            obj::JournalEntry::Subscribed {
+                subscriber,
+                keys: subscribed,
+            } => Entry::Subscribed(Subscription {
+                subscriber: subscriber.0.to_vec(),
+                keys: keys(subscribed),
+            }),
+            obj::JournalEntry::Unsubscribed {
+                subscriber,
+                keys: unsubscribed,
+            } => Entry::Unsubscribed(Subscription {
+                subscriber: subscriber.0.to_vec(),
+                keys: keys(unsubscribed),
+            }),
+            obj::JournalEntry::Deposited { to, mail } => Entry::Deposited(Deposited {
+                to: to.0.to_vec(),
+                mail: Some(mail.into()),
+            }),
+            obj::JournalEntry::Delivered { to, ids } => Entry::Delivered(Delivered {
+                to: to.0.to_vec(),
+                ids: ids.into_iter().map(|id| id.0 .0.to_vec()).collect(),
+            }),
+            obj::JournalEntry::Fetched { to } => Entry::Fetched(Fetched { to: to.0.to_vec() }),
+        };
+        Self { entry: Some(entry) }
+    }
+}
+impl TryFrom<JournalEntry> for obj::JournalEntry {
+    type Error = ProtoError;
+
+    fn try_from(value: JournalEntry) -> Result<Self, Self::Error> {
+        use journal_entry::Entry;
+
+        Ok(match required("JournalEntry.entry", value.entry)? {
+            Entry::Identified(entry) => Self::Identified {
+                public_key: public_key("JournalEntry.identified.public_key", entry.public_key)?,
+                usage: obj::KeyUsage(entry.usage),
+                time: entry.time,
+            },
+            Entry::Revoked(entry) => Self::Revoked {
+                public_key: public_key("JournalEntry.revoked.public_key", entry.public_key)?,
+            },
+            Entry::Subscribed(entry) => Self::Subscribed {
+                subscriber: public_key("JournalEntry.subscribed.subscriber", entry.subscriber)?,
+                keys: public_keys("JournalEntry.subscribed.keys", entry.keys)?,
+            },
+            Entry::Unsubscribed(entry) => Self::Unsubscribed {
+                subscriber: public_key("JournalEntry.unsubscribed.subscriber", entry.subscriber)?,
+                keys: public_keys("JournalEntry.unsubscribed.keys", entry.keys)?,
+            },
+            Entry::Deposited(entry) => Self::Deposited {
+                to: public_key("JournalEntry.deposited.to", entry.to)?,
+                mail: required("JournalEntry.deposited.mail", entry.mail)?.try_into()?,
+            },
+            Entry::Delivered(entry) => Self::Delivered {
+                to: public_key("JournalEntry.delivered.to", entry.to)?,
+                ids: entry
+                    .ids
+                    .into_iter()
+                    .map(|id| message_id("JournalEntry.delivered.ids", id))
+                    .collect::<Result<_, _>>()?,
+            },
+            Entry::Fetched(entry) => Self::Fetched {
+                to: public_key("JournalEntry.fetched.to", entry.to)?,
+            },
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct JournalRecord {
+    #[prost(uint64, tag = "1")]
+    pub seq: u64,
+    #[prost(message, optional, tag = "2")]
+    pub entry: Option<JournalEntry>,
+}
+
+impl From<obj::JournalRecord> for JournalRecord {
+    fn from(value: obj::JournalRecord) -> Self {
+        Self {
+            seq: value.seq,
+            entry: Some(value.entry.into()),
+        }
+    }
+}
+impl TryFrom<JournalRecord> for obj::JournalRecord {
+    type Error = ProtoError;
+
+    fn try_from(value: JournalRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            seq: value.seq,
+            entry: required("JournalRecord.entry", value.entry)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct JournalResp {
+    #[prost(message, optional, tag = "1")]
+    pub position: Option<JournalPosition>,
+    #[prost(message, repeated, tag = "2")]
+    pub records: Vec<JournalRecord>,
+    #[prost(bool, tag = "3")]
+    pub snapshot: bool,
+}
+
+impl From<obj::JournalResp> for JournalResp {
+    fn from(value: obj::JournalResp) -> Self {
+        Self {
+            position: Some(value.position.into()),
+            records: value.records.into_iter().map(Into::into).collect(),
+            snapshot: value.snapshot,
+        }
+    }
+}
+impl TryFrom<JournalResp> for obj::JournalResp {
+    type Error = ProtoError;
+
+    fn try_from(value: JournalResp) -> Result<Self, Self::Error> {
+        Ok(Self {
+            position: required("JournalResp.position", value.position)?.try_into()?,
+            records: collect(value.records)?,
+            snapshot: value.snapshot,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DisconnectNoticeReq {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub keys: Vec<Vec<u8>>,
+    #[prost(uint64, tag = "2")]
+    pub time: u64,
+}
+
+impl From<obj::DisconnectNoticeReq> for DisconnectNoticeReq {
+    fn from(value: obj::DisconnectNoticeReq) -> Self {
+        Self {
+            keys: value.keys.into_iter().map(|key| key.0.to_vec()).collect(),
+            time: value.time,
+        }
+    }
+}
+impl TryFrom<DisconnectNoticeReq> for obj::DisconnectNoticeReq {
+    type Error = ProtoError;
+
+    fn try_from(value: DisconnectNoticeReq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            keys: public_keys("DisconnectNoticeReq.keys", value.keys)?,
+            time: value.time,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReqMessage {
+    #[prost(
+        oneof = "req_message::Body",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20"
+    )]
+    pub body: Option<req_message::Body>,
+}
+
+pub mod req_message {
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Body {
+        #[prost(message, tag = "1")]
+        Connect(NodeInfo),
+        #[prost(message, tag = "2")]
+        PreIdentify(PreIdentifyReq),
+        #[prost(message, tag = "3")]
+        Identify(IdentifyReq),
+        #[prost(message, tag = "4")]
+        Revoke(RevokeReq),
+        #[prost(message, tag = "5")]
+        KeysExists(KeysExistsReq),
+        #[prost(message, tag = "6")]
+        UnsubscribeKeys(UnsubscribeKeys),
+        #[prost(message, tag = "7")]
+        Communication(CommunicationReq),
+        #[prost(message, tag = "8")]
+        ListConnectedServers(ListConnectedServersReq),
+        #[prost(message, tag = "9")]
+        Ping(PingReq),
+        #[prost(message, tag = "10")]
+        Goodbye(Goodbye),
+        #[prost(message, tag = "11")]
+        Broadcast(BroadcastReq),
+        #[prost(message, tag = "12")]
+        Relay(RelayMessage),
+        #[prost(message, tag = "13")]
+        Reconcile(Reconcile),
+        #[prost(message, tag = "14")]
+        FetchMail(FetchMailReq),
+        #[prost(message, tag = "15")]
+        SendReceipt(SendReceiptReq),
+        #[prost(message, tag = "16")]
+        Receipt(ReceiptReq),
+        #[prost(message, tag = "17")]
+        PublishPresence(PublishPresenceReq),
+        #[prost(message, tag = "18")]
+        CrossSign(CrossSignReq),
+        #[prost(message, tag = "19")]
+        Journal(JournalReq),
+        #[prost(message, tag = "20")]
+        DisconnectNotice(DisconnectNoticeReq),
+    }
+}
+
+impl From<obj::ReqMessage> for ReqMessage {
+    fn from(value: obj::ReqMessage) -> Self {
+        use req_message::Body;
+
+        let body = match value {
+            obj::ReqMessage::Connect(v) => Body::Connect(v.into()),
+            obj::ReqMessage::PreIdentify(v) => Body::PreIdentify(v.into()),
+            obj::ReqMessage::Identify(v) => Body::Identify(v.into()),
+            obj::ReqMessage::Revoke(v) => Body::Revoke(v.into()),
+            obj::ReqMessage::KeysExists(v) => Body::KeysExists(v.into()),
+            obj::ReqMessage::UnsubscribeKeys(v) => Body::UnsubscribeKeys(v.into()),
+            obj::ReqMessage::Communication(v) => Body::Communication(v.into()),
+            obj::ReqMessage::ListConnectedServers(v) => Body::ListConnectedServers(v.into()),
+            obj::ReqMessage::Ping(v) => Body::Ping(v.into()),
+            obj::ReqMessage::Goodbye(v) => Body::Goodbye(v.into()),
+            obj::ReqMessage::Broadcast(v) => Body::Broadcast(v.into()),
+            obj::ReqMessage::Relay(v) => Body::Relay(v.into()),
+            obj::ReqMessage::Reconcile(v) => Body::Reconcile(v.into()),
+            obj::ReqMessage::FetchMail(v) => Body::FetchMail(v.into()),
+            obj::ReqMessage::SendReceipt(v) => Body::SendReceipt(v.into()),
+            obj::ReqMessage::Receipt(v) => Body::Receipt(v.into()),
+            obj::ReqMessage::PublishPresence(v) => Body::PublishPresence(v.into()),
+            obj::ReqMessage::CrossSign(v) => Body::CrossSign(v.into()),
+            obj::ReqMessage::Journal(v) => Body::Journal(v.into()),
+            obj::ReqMessage::DisconnectNotice(v) => Body::DisconnectNotice(v.into()),
+        };
+        Self { body: Some(body) }
+    }
+}
+impl TryFrom<ReqMessage> for obj::ReqMessage {
+    type Error = ProtoError;
+
+    fn try_from(value: ReqMessage) -> Result<Self, Self::Error> {
+        use req_message::Body;
+
+        Ok(match required("ReqMessage.body", value.body)? {
+            Body::Connect(v) => Self::Connect(v.try_into()?),
+            Body::PreIdentify(v) => Self::PreIdentify(v.try_into()?),
+            Body::Identify(v) => Self::Identify(v.try_into()?),
+            Body::Revoke(v) => Self::Revoke(v.try_into()?),
+            Body::KeysExists(v) => Self::KeysExists(v.try_into()?),
+            Body::UnsubscribeKeys(v) => Self::UnsubscribeKeys(v.try_into()?),
+            Body::Communication(v) => Self::Communication(v.try_into()?),
+            Body::ListConnectedServers(v) => Self::ListConnectedServers(v.try_into()?),
+            Body::Ping(v) => Self::Ping(v.try_into()?),
+            Body::Goodbye(v) => Self::Goodbye(v.try_into()?),
+            Body::Broadcast(v) => Self::Broadcast(v.try_into()?),
+            Body::Relay(v) => Self::Relay(v.try_into()?),
+            Body::Reconcile(v) => Self::Reconcile(v.try_into()?),
+            Body::FetchMail(v) => Self::FetchMail(v.try_into()?),
+            Body::SendReceipt(v) => Self::SendReceipt(v.try_into()?),
+            Body::Receipt(v) => Self::Receipt(v.try_into()?),
+            Body::PublishPresence(v) => Self::PublishPresence(v.try_into()?),
+            Body::CrossSign(v) => Self::CrossSign(v.try_into()?),
+            Body::Journal(v) => Self::Journal(v.try_into()?),
+            Body::DisconnectNotice(v) => Self::DisconnectNotice(v.try_into()?),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RespMessage {
+    #[prost(
+        oneof = "resp_message::Body",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17"
+    )]
+    pub body: Option<resp_message::Body>,
+}
+
+pub mod resp_message {
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Body {
+        #[prost(message, tag = "1")]
+        Connect(NodeInfoResp),
+        #[prost(message, tag = "2")]
+        PreIdentify(IdentifyData),
+        #[prost(message, tag = "3")]
+        Identify(IdentifyResp),
+        #[prost(message, tag = "4")]
+        KeysExists(KeysExistsResp),
+        #[prost(message, tag = "5")]
+        UnsubscribeKeys(UnsubscribeKeys),
+        #[prost(message, tag = "6")]
+        ListConnectedServers(ListConnectedServersResp),
+        #[prost(message, tag = "7")]
+        Pong(PongResp),
+        #[prost(message, tag = "8")]
+        Goodbye(Goodbye),
+        #[prost(message, tag = "9")]
+        Error(ErrorResp),
+        #[prost(message, tag = "10")]
+        Ack(AckResp),
+        #[prost(message, tag = "11")]
+        Broadcast(BroadcastResp),
+        #[prost(message, tag = "12")]
+        Relay(RelayResp),
+        #[prost(message, tag = "13")]
+        Reconcile(Reconcile),
+        #[prost(message, tag = "14")]
+        FetchMail(FetchMailResp),
+        #[prost(message, tag = "15")]
+        Receipt(ReceiptResp),
+        #[prost(message, tag = "16")]
+        CrossSign(CrossSignResp),
+        #[prost(message, tag = "17")]
+        Journal(JournalResp),
+    }
+}
+
+impl From<obj::RespMessage> for RespMessage {
+    fn from(value: obj::RespMessage) -> Self {
+        use resp_message::Body;
+
+        let body = match value {
+            obj::RespMessage::Connect(v) => Body::Connect(v.into()),
+            obj::RespMessage::PreIdentify(v) => Body::PreIdentify(v.into()),
+            obj::RespMessage::Identify(v) => Body::Identify(v.into()),
+            obj::RespMessage::KeysExists(v) => Body::KeysExists(v.into()),
+            obj::RespMessage::UnsubscribeKeys(v) => Body::UnsubscribeKeys(v.into()),
+            obj::RespMessage::ListConnectedServers(v) => Body::ListConnectedServers(v.into()),
+            obj::RespMessage::Pong(v) => Body::Pong(v.into()),
+            obj::RespMessage::Goodbye(v) => Body::Goodbye(v.into()),
+            obj::RespMessage::Error(v) => Body::Error(v.into()),
+            obj::RespMessage::Ack(v) => Body::Ack(v.into()),
+            obj::RespMessage::Broadcast(v) => Body::Broadcast(v.into()),
+            obj::RespMessage::Relay(v) => Body::Relay(v.into()),
+            obj::RespMessage::Reconcile(v) => Body::Reconcile(v.into()),
+            obj::RespMessage::FetchMail(v) => Body::FetchMail(v.into()),
+            obj::RespMessage::Receipt(v) => Body::Receipt(v.into()),
+            obj::RespMessage::CrossSign(v) => Body::CrossSign(v.into()),
+            obj::RespMessage::Journal(v) => Body::Journal(v.into()),
+        };
+        Self { body: Some(body) }
+    }
+}
+impl TryFrom<RespMessage> for obj::RespMessage {
+    type Error = ProtoError;
+
+    fn try_from(value: RespMessage) -> Result<Self, ProtoError> {
+        use resp_message::Body;
+
+        Ok(match required("RespMessage.body", value.body)? {
+            Body::Connect(v) => Self::Connect(v.try_into()?),
+            Body::PreIdentify(v) => Self::PreIdentify(v.try_into()?),
+            Body::Identify(v) => Self::Identify(v.try_into()?),
+            Body::KeysExists(v) => Self::KeysExists(v.try_into()?),
+            Body::UnsubscribeKeys(v) => Self::UnsubscribeKeys(v.try_into()?),
+            Body::ListConnectedServers(v) => Self::ListConnectedServers(v.try_into()?),
+            Body::Pong(v) => Self::Pong(v.try_into()?),
+            Body::Goodbye(v) => Self::Goodbye(v.try_into()?),
+            Body::Error(v) => Self::Error(v.try_into()?),
+            Body::Ack(v) => Self::Ack(v.try_into()?),
+            Body::Broadcast(v) => Self::Broadcast(v.try_into()?),
+            Body::Relay(v) => Self::Relay(v.try_into()?),
+            Body::Reconcile(v) => Self::Reconcile(v.try_into()?),
+            Body::FetchMail(v) => Self::FetchMail(v.try_into()?),
+            Body::Receipt(v) => Self::Receipt(v.try_into()?),
+            Body::CrossSign(v) => Self::CrossSign(v.try_into()?),
+            Body::Journal(v) => Self::Journal(v.try_into()?),
+        })
+    }
+}
+
+/// Defines the protobuf form of a [`Tagged`](obj::Tagged) `$native`, whose protobuf form is
+/// `$body`.
+macro_rules! tagged {
+    ($(#[$meta:meta])* $name:ident, $native:ty, $body:ty) => {
+        $(#[$meta])*
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct $name {
+            #[prost(uint64, tag = "1")]
+            pub id: u64,
+            #[prost(uint32, optional, tag = "2")]
+            pub version: Option<u32>,
+            #[prost(message, optional, tag = "3")]
+            pub body: Option<$body>,
+        }
+
+        impl From<obj::Tagged<$native>> for $name {
+            fn from(value: obj::Tagged<$native>) -> Self {
+                Self {
+                    id: value.id,
+                    version: value.version,
+                    body: Some(value.body.into()),
+                }
+            }
+        }
+        impl TryFrom<$name> for obj::Tagged<$native> {
+            type Error = ProtoError;
+
+            fn try_from(value: $name) -> Result<Self, Self::Error> {
+                Ok(Self {
+                    id: value.id,
+                    version: value.version,
+                    body: required(concat!(stringify!($name), ".body"), value.body)?.try_into()?,
+                })
+            }
+        }
+    };
+}
+
+tagged!(
+    /// A [`ReqMessage`] tagged with its id, as sent in a frame.
+    TaggedReq,
+    obj::ReqMessage,
+    ReqMessage
+);
+tagged!(
+    /// A [`RespMessage`] tagged with the id of its request, as sent in a frame.
+    TaggedResp,
+    obj::RespMessage,
+    RespMessage
+);
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::*;
+    use crate::crypto::KeyPair;
+    use crate::obj::{SignMessageType, SignedFormat};
+
+    fn round_trip_req(req: obj::ReqMessage) {
+        let tagged = obj::Tagged::new(7, req);
+        let bytes = TaggedReq::from(tagged.clone()).encode_to_vec();
+        let decoded = TaggedReq::decode(bytes.as_slice()).unwrap();
+        assert_eq!(obj::Tagged::try_from(decoded).unwrap(), tagged);
+    }
+
+    fn round_trip_resp(resp: obj::RespMessage) {
+        let tagged = obj::Tagged::new(7, resp);
+        let bytes = TaggedResp::from(tagged.clone()).encode_to_vec();
+        let decoded = TaggedResp::decode(bytes.as_slice()).unwrap();
+        assert_eq!(obj::Tagged::try_from(decoded).unwrap(), tagged);
+    }
+
+    #[test]
+    fn lossless() {
+        let key = KeyPair::generate();
+        let triad = crypto::KeyTriad::gen_signed(
+            &key.private,
+            &obj::Revocation {
+                key: key.public,
+                time: 1,
+            },
+            SignMessageType::Revoke,
+            SignedFormat::Json,
+        )
+        .unwrap();
+
+        round_trip_req(obj::ReqMessage::Revoke(obj::RevokeReq {
+            revocation: triad.clone(),
+        }));
+        round_trip_req(obj::ReqMessage::Communication(obj::CommunicationReq {
+            from: key.public,
+            to: key.public,
+            grant: Some(crypto::KeyTriad::grant(&key.private, key.public, 5)),
+            hops: 2,
+        }));
+        round_trip_req(obj::ReqMessage::Goodbye(
+            obj::Goodbye::new(obj::GoodbyeCode::RATE_LIMITED, "slow down").with_keep_down(
+                crypto::KeyTriad::keep_down(&key.private, std::time::Duration::from_secs(1), 2),
+            ),
+        ));
+        round_trip_req(obj::ReqMessage::ListConnectedServers(
+            obj::ListConnectedServersReq { max: None },
+        ));
+        round_trip_req(obj::ReqMessage::Relay(obj::RelayMessage {
+            to: key.public,
+            payload: crypto::KeyTriad::sign_detached(&key.private, b"hello"),
+            ttl: Some(60),
+        }));
+        round_trip_req(obj::ReqMessage::Reconcile(obj::ReconcileReq {
+            round: keyset::Reconciliation {
+                items: vec![
+                    keyset::RangeItem::Fingerprint {
+                        range: keyset::KeyRange {
+                            lower: None,
+                            upper: Some(key.public),
+                        },
+                        count: 2,
+                        fingerprint: HashMsg([1; 32]),
+                    },
+                    keyset::RangeItem::Keys {
+                        range: keyset::KeyRange {
+                            lower: Some(key.public),
+                            upper: None,
+                        },
+                        keys: vec![key.public],
+                    },
+                ],
+                keys: vec![key.public],
+            },
+        }));
+        let mut log = log::TransparencyLog::default();
+        log.append(HashMsg([2; 32]));
+        round_trip_req(obj::ReqMessage::CrossSign(obj::CrossSignReq {
+            head: log.sign(&key.private, 4),
+            proof: log.proof(0, 1).unwrap(),
+        }));
+        round_trip_req(obj::ReqMessage::Journal(obj::JournalReq {
+            since: obj::JournalPosition { epoch: 5, seq: 6 },
+            max: Some(10),
+        }));
+        round_trip_req(obj::ReqMessage::DisconnectNotice(
+            obj::DisconnectNoticeReq {
+                keys: vec![key.public],
+                time: 7,
+            },
+        ));
+
+        round_trip_resp(obj::RespMessage::KeysExists(obj::KeysExistsResp {
+            triads: vec![triad],
+            presence: vec![crypto::KeyTriad::presence(
+                &key.private,
+                obj::PresenceStatus::Away,
+                Some("lunch".into()),
+                3,
+            )],
+        }));
+        round_trip_resp(obj::RespMessage::ListConnectedServers(
+            obj::ListConnectedServersResp {
+                servers: vec![obj::ConnectedServer {
+                    addr: Some("[::1]:4000".parse().unwrap()),
+                    server_info: obj::ServerInfo {
+                        ports: vec![4000],
+                        transports: vec![obj::Transport::Quic],
+                        public_key: Some(key.public),
+                        ..obj::ServerInfo::new(arcstr::literal!("example.com"))
+                    },
+                    load: None,
+                    verified: true,
+                }],
+            },
+        ));
+        let mail = obj::Mail {
+            id: obj::MessageId(HashMsg([3; 32])),
+            time: 7,
+            expire_time: None,
+            payload: crypto::KeyTriad::sign_detached(&key.private, b"hello"),
+        };
+        round_trip_resp(obj::RespMessage::FetchMail(obj::FetchMailResp {
+            mail: vec![mail.clone()],
+            evicted: vec![obj::EvictionNotice {
+                id: mail.id,
+                from: key.public,
+                reason: obj::EvictionReason::MaxBytes,
+                time: 8,
+            }],
+        }));
+        round_trip_resp(obj::RespMessage::Receipt(obj::ReceiptResp {
+            receipt: Some(crypto::KeyTriad::receipt(
+                &key.private,
+                mail.id,
+                HashMsg([4; 32]),
+                obj::ReceiptStatus::Read,
+                9,
+            )),
+        }));
+        round_trip_resp(obj::RespMessage::CrossSign(obj::CrossSignResp::Cosigned {
+            cosignature: Box::new(log.sign(&key.private, 4).cosign(&key.private)),
+        }));
+        round_trip_resp(obj::RespMessage::Journal(obj::JournalResp {
+            position: obj::JournalPosition { epoch: 5, seq: 7 },
+            records: vec![obj::JournalRecord {
+                seq: 7,
+                entry: obj::JournalEntry::Deposited {
+                    to: key.public,
+                    mail,
+                },
+            }],
+            snapshot: false,
+        }));
+        round_trip_resp(obj::RespMessage::Ack(obj::AckResp {}));
+        round_trip_resp(obj::RespMessage::Error(
+            obj::ErrorResp::new(obj::ErrorCode::RATE_LIMITED, "wait")
+                .with_retry_after(std::time::Duration::from_millis(30)),
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        let triad = KeyTriad {
+            public_key: vec![0; 3],
+            signature: vec![0; 64],
+            signed: Some(obj::SignedData::Cbor(vec![].into()).into()),
+        };
+        assert!(matches!(
+            crypto::KeyTriad::<obj::SignedData>::try_from(triad),
+            Err(ProtoError::InvalidLength { received: 3, .. })
+        ));
+        assert!(matches!(
+            obj::ReqMessage::try_from(ReqMessage { body: None }),
+            Err(ProtoError::Missing(_))
+        ));
+        let load = Load {
+            relay_saturation: 256,
+            ..Default::default()
+        };
+        assert!(matches!(
+            obj::Load::try_from(load),
+            Err(ProtoError::OutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_transport() {
+        let info = ServerInfo {
+            domain: "example.com".into(),
+            transports: vec![Transport::Tcp as i32, 9],
+            ..Default::default()
+        };
+        let info = obj::ServerInfo::try_from(info).unwrap();
+        assert_eq!(
+            info.transports,
+            vec![obj::Transport::Tcp, obj::Transport::Unknown]
+        );
+
+        let transports: Vec<obj::Transport> = serde_json::from_str(r#"["QUIC", "SCTP"]"#).unwrap();
+        assert_eq!(
+            transports,
+            vec![obj::Transport::Quic, obj::Transport::Unknown]
+        );
+    }
+}
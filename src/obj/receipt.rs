@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use super::MessageId;
+use crate::crypto::{HashMsg, KeyTriad, PrivateKey, ToHashMsg};
+
+/// Prefixes the hash of a [`Receipt`] before it is signed.
+const RECEIPT_PREFIX: u8 = 8;
+
+/// How far a message got once it reached its recipient. Later statuses imply the earlier ones.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum ReceiptStatus {
+    /// The message was delivered to a client of the recipient.
+    #[serde(rename = "DELIVERED", alias = "delivered")]
+    Delivered,
+    /// The message was shown to the recipient.
+    #[serde(rename = "READ", alias = "read")]
+    Read,
+}
+
+/// A statement by the recipient of a message that the message reached it. Is signed by the
+/// recipient.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Receipt {
+    pub id: MessageId,
+    /// The hash of the signed payload of the message.
+    pub message: HashMsg,
+    pub status: ReceiptStatus,
+    /// The time the receipt was signed.
+    pub time: u64,
+}
+
+impl ToHashMsg for &Receipt {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        let status = match self.status {
+            ReceiptStatus::Delivered => 0u8,
+            ReceiptStatus::Read => 1,
+        };
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[RECEIPT_PREFIX]);
+        hasher.update(&self.id.0 .0);
+        hasher.update(&self.message.0);
+        hasher.update(&[status]);
+        hasher.update(&self.time.to_be_bytes());
+        HashMsg(hasher.finalize().into())
+    }
+}
+
+impl KeyTriad<Receipt> {
+    /// Signs a receipt of the message `id` whose payload hashes to `message`.
+    pub fn receipt(
+        key: &PrivateKey,
+        id: MessageId,
+        message: HashMsg,
+        status: ReceiptStatus,
+        time: u64,
+    ) -> Self {
+        let receipt = Receipt {
+            id,
+            message,
+            status,
+            time,
+        };
+        Self::signed_by(key, receipt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn receipt() {
+        let key = KeyPair::generate();
+        let message = crate::crypto::hash(b"message");
+        let id = MessageId::new(&key.public, &key.public, &message);
+        let receipt = KeyTriad::receipt(&key.private, id, message, ReceiptStatus::Read, 0);
+        assert!(receipt.valid());
+
+        // the status is signed over
+        let mut forged = receipt;
+        forged.signed.status = ReceiptStatus::Delivered;
+        assert!(!forged.valid());
+    }
+}
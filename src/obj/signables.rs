@@ -4,7 +4,11 @@ use arcstr::ArcStr;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::crypto::{hash, HashMsg, ToHashMsg};
+use super::{is_canonical_cbor, Features};
+use crate::crypto::{
+    error::SealError, schnorr::SignatureScheme, seal, unseal, HashAlgorithm, HashMsg, PrivateKey,
+    PublicKey, ToHashMsg,
+};
 
 /// The size (in bytes) of the nonce.
 pub const SALT_SIZE: usize = 16;
@@ -15,8 +19,19 @@ pub enum SignedConvertError {
     JsonError(#[from] serde_json::Error),
     #[error("{}", .0)]
     CborError(#[from] serde_cbor::Error),
+    /// The data only references the hash of a payload that travels separately.
+    #[error("the signed payload is detached")]
+    Detached,
+    /// The payload is sealed to a public key, and must be opened first.
+    #[error("the signed payload is encrypted")]
+    Encrypted,
 }
 
+/// This error happens when attaching a payload whose hash differs from the detached hash.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[error("the payload does not match the detached hash")]
+pub struct PayloadMismatchError;
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct CachedSigned<T> {
     pub signable: Signable<T>,
@@ -30,7 +45,15 @@ pub enum SignedData {
     Json(ArcStr),
     #[serde(rename = "CBOR")]
     Cbor(Arc<[u8]>),
+    /// The hash of a payload that is sent or stored separately, such as a large file.
+    #[serde(rename = "DETACHED")]
+    Detached(HashMsg),
+    /// Signed data sealed to a public key with [`seal`](crate::crypto::seal), so that servers
+    /// relaying it cannot read it. The signature covers the sealed bytes.
+    #[serde(rename = "ENCRYPTED")]
+    Encrypted(Arc<[u8]>),
 }
+
 impl SignedData {
     pub fn to_signable<'a, T: Deserialize<'a>>(
         &'a self,
@@ -38,8 +61,117 @@ impl SignedData {
         Ok(match self {
             SignedData::Json(json) => serde_json::from_str(json.as_str())?,
             SignedData::Cbor(cbor) => serde_cbor::from_slice(cbor)?,
+            SignedData::Detached(_) => return Err(SignedConvertError::Detached),
+            SignedData::Encrypted(_) => return Err(SignedConvertError::Encrypted),
         })
     }
+    /// Returns the size of the payload in bytes. A detached payload is the size of its hash.
+    pub fn size(&self) -> usize {
+        match self {
+            SignedData::Json(json) => json.len(),
+            SignedData::Cbor(bytes) | SignedData::Encrypted(bytes) => bytes.len(),
+            SignedData::Detached(hash) => hash.0.len(),
+        }
+    }
+    /// Returns whether the payload is in canonical form. Only CBOR payloads have a canonical form,
+    /// so other payloads are always canonical. Payloads signed before signables were serialized
+    /// canonically are not, and still verify.
+    pub fn is_canonical(&self) -> bool {
+        match self {
+            SignedData::Cbor(cbor) => is_canonical_cbor(cbor),
+            _ => true,
+        }
+    }
+    /// Returns whether the payload of this data is detached.
+    pub fn is_detached(&self) -> bool {
+        matches!(self, SignedData::Detached(_))
+    }
+    /// Replaces the payload with its hash. Signatures over the data remain valid.
+    pub fn detach(&self) -> SignedData {
+        SignedData::Detached(self.to_hash_msg())
+    }
+    /// Replaces a detached hash with the payload it references. If this data is not detached,
+    /// the payload must be the same as this data.
+    pub fn attach(&self, payload: SignedData) -> Result<SignedData, PayloadMismatchError> {
+        match payload.to_hash_msg() == self.to_hash_msg() && !payload.is_detached() {
+            true => Ok(payload),
+            false => Err(PayloadMismatchError),
+        }
+    }
+    /// Returns the algorithm the signable in this data asks to be hashed with. Data that cannot
+    /// be parsed is hashed with the default algorithm, and fails to verify later.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        /// The part of a [`Signable`] that is needed to hash it.
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(default)]
+            hash: HashAlgorithm,
+        }
+
+        let header: Option<Header> = match self {
+            SignedData::Json(json) => serde_json::from_str(json.as_str()).ok(),
+            SignedData::Cbor(cbor) => serde_cbor::from_slice(cbor).ok(),
+            SignedData::Detached(_) | SignedData::Encrypted(_) => None,
+        };
+        header.map(|header| header.hash).unwrap_or_default()
+    }
+    /// Returns the scheme the signable in this data declares it is signed with. Data that cannot
+    /// be parsed is signed with the default scheme.
+    pub fn signature_scheme(&self) -> SignatureScheme {
+        /// The part of a [`Signable`] that names its signature scheme.
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(default)]
+            scheme: SignatureScheme,
+        }
+
+        let header: Option<Header> = match self {
+            SignedData::Json(json) => serde_json::from_str(json.as_str()).ok(),
+            SignedData::Cbor(cbor) => serde_cbor::from_slice(cbor).ok(),
+            SignedData::Detached(_) | SignedData::Encrypted(_) => None,
+        };
+        header.map(|header| header.scheme).unwrap_or_default()
+    }
+    /// Returns the type of the signable in this data, or [`None`] if the data cannot be parsed,
+    /// such as when it is detached or encrypted, or if its type is unknown.
+    pub fn msg_type(&self) -> Option<SignMessageType> {
+        /// The part of a [`Signable`] that names its type.
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(rename = "msgType")]
+            msg_type: SignMessageType,
+        }
+
+        let header: Option<Header> = match self {
+            SignedData::Json(json) => serde_json::from_str(json.as_str()).ok(),
+            SignedData::Cbor(cbor) => serde_cbor::from_slice(cbor).ok(),
+            SignedData::Detached(_) | SignedData::Encrypted(_) => None,
+        };
+        header.map(|header| header.msg_type)
+    }
+    /// Hashes the payload of this data with `algorithm`.
+    pub fn hash_with(&self, algorithm: HashAlgorithm) -> HashMsg {
+        match self {
+            SignedData::Json(value) => algorithm.hash(value),
+            SignedData::Cbor(value) => algorithm.hash(value),
+            SignedData::Detached(value) => *value,
+            SignedData::Encrypted(value) => algorithm.hash(value),
+        }
+    }
+    /// Seals this data to `to`, so that only the holder of its private key can read it.
+    pub fn seal(&self, to: &PublicKey) -> Result<SignedData, SealError> {
+        let bytes = serde_cbor::to_vec(self).map_err(|_| SealError::InvalidPayload)?;
+        Ok(SignedData::Encrypted(seal(to, &bytes)?.into()))
+    }
+    /// Opens data sealed to the public key of `key` with [`SignedData::seal`].
+    pub fn unseal(&self, key: &PrivateKey) -> Result<SignedData, SealError> {
+        let sealed = match self {
+            SignedData::Encrypted(value) => value,
+            _ => return Err(SealError::NotEncrypted),
+        };
+
+        serde_cbor::from_slice(&unseal(key, sealed)?).map_err(|_| SealError::InvalidPayload)
+    }
     pub fn to_cached<T>(self) -> Result<CachedSigned<T>, SignedConvertError>
     where
         for<'a> T: Deserialize<'a>,
@@ -54,10 +186,20 @@ impl ToHashMsg for &SignedData {
     type Output = HashMsg;
 
     fn to_hash_msg(self) -> Self::Output {
-        match self {
-            SignedData::Json(value) => hash(value),
-            SignedData::Cbor(value) => hash(value),
-        }
+        self.hash_with(self.hash_algorithm())
+    }
+    fn signature_scheme(&self) -> SignatureScheme {
+        SignedData::signature_scheme(self)
+    }
+}
+impl<T> ToHashMsg for &CachedSigned<T> {
+    type Output = HashMsg;
+
+    fn to_hash_msg(self) -> Self::Output {
+        self.value.hash_with(self.signable.hash)
+    }
+    fn signature_scheme(&self) -> SignatureScheme {
+        self.signable.scheme
     }
 }
 /// A message that when converted to JSON/CBOR/another format, can be signed.
@@ -66,16 +208,46 @@ pub struct Signable<T> {
     #[serde(rename = "msgType")]
     pub msg_type: SignMessageType,
     pub obj: T,
+    /// The algorithm the serialized signable is hashed with before it is signed.
+    #[serde(default, skip_serializing_if = "HashAlgorithm::is_default")]
+    pub hash: HashAlgorithm,
+    /// The scheme the serialized signable is signed with.
+    #[serde(default, skip_serializing_if = "SignatureScheme::is_default")]
+    pub scheme: SignatureScheme,
+}
+/// The format a [`Signable`] is serialized to before it is signed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SignedFormat {
+    Json,
+    #[default]
+    Cbor,
 }
+/// What a [`Signable`] is signed for. A node only accepts a signable for the operation of its type,
+/// so that a signature made for one purpose cannot be replayed for another.
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 #[non_exhaustive]
 pub enum SignMessageType {
+    /// [`IdentifyData`](super::IdentifyData) a key identifies with.
     #[serde(rename = "IDENTIFY")]
     Identify,
+    /// A [`Revocation`](super::Revocation) of the key that signed it.
+    #[serde(rename = "REVOKE")]
+    Revoke,
+    /// A [`Delegation`](crate::crypto::delegation::Delegation) to another key.
+    #[serde(rename = "DELEGATE")]
+    Delegate,
+    /// A [`KeyGroup`](crate::crypto::multi::KeyGroup) that may claim the identity of the key
+    /// that signed it.
+    #[serde(rename = "KEY_GROUP")]
+    KeyGroup,
+    /// A message of an application, such as the payload of mail or of a broadcast, that nodes
+    /// do not interpret.
+    #[serde(rename = "APP_MESSAGE")]
+    AppMessage,
 }
 
 /// Identify data sent from a node to the signer.
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct IdentifyData {
     /// Nonce.
     pub salt: [u8; SALT_SIZE],
@@ -86,4 +258,145 @@ pub struct IdentifyData {
     #[serde(rename = "expireTime")]
     /// The expiration timestamp.
     pub expire_time: u64,
+    /// The policy of the node the signer accepts by signing.
+    #[serde(default, skip_serializing_if = "IdentifyExtensions::is_empty")]
+    pub extensions: IdentifyExtensions,
+    /// The amount of leading zero bits the proof of work of the signer must have, if the node
+    /// requires one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u8>,
+    /// The proof of work of the signer, filled in by [`IdentifyData::solve`] before signing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub work: Option<u64>,
+    /// How the signer allows its key to be used, filled in by the signer before signing. The
+    /// node enforces it until the key identifies again.
+    #[serde(default, skip_serializing_if = "KeyUsage::is_empty")]
+    pub usage: KeyUsage,
+}
+
+impl IdentifyData {
+    /// Hashes the salt, the public key of the signer and `work`.
+    fn work_hash(&self, public_key: &PublicKey, work: u64) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.salt);
+        hasher.update(&public_key.0);
+        hasher.update(&work.to_be_bytes());
+        hasher.finalize().into()
+    }
+    /// Returns whether the proof of work meets the difficulty for `public_key`. Data without a
+    /// difficulty needs no proof.
+    pub fn work_valid(&self, public_key: &PublicKey) -> bool {
+        let difficulty = match self.difficulty {
+            Some(value) => value as u32,
+            None => return true,
+        };
+        let work = match self.work {
+            Some(value) => value,
+            None => return false,
+        };
+
+        leading_zeros(&self.work_hash(public_key, work)) >= difficulty
+    }
+    /// Finds a proof of work that meets the difficulty for `public_key`, returning the data to
+    /// sign. Each additional bit of difficulty doubles the expected work.
+    pub fn solve(&self, public_key: &PublicKey) -> IdentifyData {
+        let mut solved = self.clone();
+        if self.difficulty.is_none() {
+            return solved;
+        }
+
+        for work in 0.. {
+            solved.work = Some(work);
+            if solved.work_valid(public_key) {
+                break;
+            }
+        }
+        solved
+    }
+}
+
+/// Counts the leading zero bits of `bytes`.
+fn leading_zeros(bytes: &[u8]) -> u32 {
+    let mut zeros = 0;
+    for byte in bytes {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    zeros
+}
+
+/// A statement that a public key must no longer be trusted, signed by the revoked key itself.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Revocation {
+    /// The revoked public key.
+    pub key: PublicKey,
+    /// The time the revocation was signed.
+    pub time: u64,
+}
+
+/// Fields a node attaches to [`IdentifyData`], that the signer signs over along with the nonce.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Default)]
+pub struct IdentifyExtensions {
+    /// The hash of the terms of service of the node.
+    #[serde(rename = "tosHash", default, skip_serializing_if = "Option::is_none")]
+    pub tos_hash: Option<HashMsg>,
+    /// The public key of the node.
+    #[serde(rename = "nodeKey", default, skip_serializing_if = "Option::is_none")]
+    pub node_key: Option<PublicKey>,
+    /// The features the signer must support.
+    #[serde(
+        rename = "requiredFeatures",
+        default,
+        skip_serializing_if = "Features::is_empty"
+    )]
+    pub required_features: Features,
+}
+
+impl IdentifyExtensions {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A set of constraints a key declares on its own use, when it identifies.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, Hash,
+)]
+#[serde(transparent)]
+pub struct KeyUsage(pub u32);
+
+impl KeyUsage {
+    /// The key can only identify on a connection from a federated server.
+    pub const SERVER_ONLY: Self = Self(1 << 0);
+    /// Other keys cannot open communications to the key, be introduced to it or leave it mail.
+    pub const NO_INCOMING: Self = Self(1 << 1);
+    /// The key is only used to be notified. It can neither open nor receive communications.
+    pub const NOTIFY_ONLY: Self = Self(1 << 2);
+    /// The key is left out of the answers to lookups and of the changes shared with federated
+    /// servers, and can only be reached by keys presenting a
+    /// [`ContactGrant`](super::ContactGrant) signed by it.
+    pub const UNLISTED: Self = Self(1 << 3);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+    /// Returns whether other keys can reach the key.
+    pub const fn accepts_incoming(self) -> bool {
+        !self.contains(Self::NO_INCOMING) && !self.contains(Self::NOTIFY_ONLY)
+    }
+    /// Returns whether the key can reach other keys.
+    pub const fn can_initiate(self) -> bool {
+        !self.contains(Self::NOTIFY_ONLY)
+    }
 }
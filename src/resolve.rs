@@ -0,0 +1,184 @@
+//! Resolution of the domains of federated servers to the addresses they can be dialed at.
+//!
+//! The crate does not resolve names on its own. Dialing and discovery take a [`Resolver`], so
+//! that deployments that do not want to leak which servers they federate with through plaintext
+//! DNS can resolve over DNS-over-HTTPS or DNS-over-TLS by implementing the trait on the resolver
+//! of their choice.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::error::Error as StdError;
+use std::hash::Hash;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+
+use arcstr::ArcStr;
+use futures::Future;
+
+use crate::obj::{ConnectedServer, ServerInfo};
+use crate::utils::LockExt;
+
+/// Resolves domain names to IP addresses.
+pub trait Resolver {
+    type Err: StdError;
+
+    /// Resolves `domain` to the IP addresses of its hosts.
+    fn resolve(&self, domain: &str) -> impl Future<Output = Result<Vec<IpAddr>, Self::Err>> + Send;
+}
+
+/// A [`Resolver`] that asks the resolver of the operating system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    type Err = io::Error;
+
+    async fn resolve(&self, domain: &str) -> Result<Vec<IpAddr>, Self::Err> {
+        let mut addrs: Vec<_> = tokio::net::lookup_host((domain, 0))
+            .await?
+            .map(|addr| addr.ip())
+            .collect();
+        dedup(&mut addrs);
+        Ok(addrs)
+    }
+}
+
+/// A [`Resolver`] that only knows the addresses it was given, such as the pinned addresses of
+/// known servers. Unknown domains resolve to no address.
+#[derive(Debug, Default)]
+pub struct StaticResolver {
+    hosts: Mutex<HashMap<ArcStr, Vec<IpAddr>>>,
+}
+
+impl StaticResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the addresses `domain` resolves to, replacing those it resolved to before.
+    pub fn insert(&self, domain: ArcStr, addrs: Vec<IpAddr>) {
+        self.hosts.locked().insert(domain, addrs);
+    }
+}
+
+impl Resolver for StaticResolver {
+    type Err = Infallible;
+
+    async fn resolve(&self, domain: &str) -> Result<Vec<IpAddr>, Self::Err> {
+        Ok(self.hosts.locked().get(domain).cloned().unwrap_or_default())
+    }
+}
+
+/// Returns the addresses `server` can be dialed at, which are every address its domain resolves
+/// to with every port it listens on. Servers that did not advertise their ports are dialed at
+/// `default_port`.
+pub async fn resolve_server<R: Resolver>(
+    resolver: &R,
+    server: &ServerInfo,
+    default_port: u16,
+) -> Result<Vec<SocketAddr>, R::Err> {
+    let ips = resolver.resolve(&server.domain).await?;
+    let ports = if server.ports.is_empty() {
+        &[default_port][..]
+    } else {
+        &server.ports
+    };
+
+    let mut addrs = ips
+        .iter()
+        .flat_map(|ip| ports.iter().map(|port| SocketAddr::new(*ip, *port)))
+        .collect();
+    dedup(&mut addrs);
+    Ok(addrs)
+}
+
+/// Returns the addresses a server listed in a
+/// [`ListConnectedServersResp`](crate::obj::ListConnectedServersResp) can be dialed at, which are
+/// the address the node revealed, if any, followed by those of [`resolve_server`]. Fails only if
+/// the domain of the server could not be resolved and the node revealed no address.
+pub async fn dial_addrs<R: Resolver>(
+    resolver: &R,
+    server: &ConnectedServer,
+    default_port: u16,
+) -> Result<Vec<SocketAddr>, R::Err> {
+    let mut addrs: Vec<_> = server.addr.into_iter().collect();
+    match resolve_server(resolver, &server.server_info, default_port).await {
+        Ok(resolved) => addrs.extend(resolved),
+        Err(err) if addrs.is_empty() => return Err(err),
+        Err(_) => {}
+    }
+    dedup(&mut addrs);
+    Ok(addrs)
+}
+
+/// Removes the values that appeared earlier in `values`, keeping the order of the rest, since
+/// resolvers order addresses by preference.
+fn dedup<T: Copy + Eq + Hash>(values: &mut Vec<T>) {
+    let mut seen = HashSet::new();
+    values.retain(|value| seen.insert(*value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolve_servers() {
+        let resolver = StaticResolver::new();
+        let ips: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+        resolver.insert(arcstr::literal!("peer.example"), ips.clone());
+
+        // every address is paired with every port
+        let server = ServerInfo {
+            ports: vec![443, 8443],
+            ..ServerInfo::new(arcstr::literal!("peer.example"))
+        };
+        let addrs = resolve_server(&resolver, &server, 1).await.unwrap();
+        assert_eq!(
+            addrs,
+            vec![
+                SocketAddr::new(ips[0], 443),
+                SocketAddr::new(ips[0], 8443),
+                SocketAddr::new(ips[1], 443),
+                SocketAddr::new(ips[1], 8443),
+            ]
+        );
+
+        // servers that did not advertise their ports are dialed at the default port
+        let server = ServerInfo::new(arcstr::literal!("peer.example"));
+        let addrs = resolve_server(&resolver, &server, 7000).await.unwrap();
+        assert_eq!(addrs[0], SocketAddr::new(ips[0], 7000));
+
+        let server = ServerInfo::new(arcstr::literal!("unknown.example"));
+        assert!(resolve_server(&resolver, &server, 7000)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn dial_addrs() {
+        let resolver = StaticResolver::new();
+        let ips: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()];
+        // a resolver can repeat an address further down its answer
+        resolver.insert(
+            arcstr::literal!("peer.example"),
+            vec![ips[0], ips[1], ips[0]],
+        );
+
+        // the address the node revealed comes first, and is not repeated
+        let server = ConnectedServer {
+            addr: Some(SocketAddr::new(ips[1], 443)),
+            server_info: ServerInfo {
+                ports: vec![443],
+                ..ServerInfo::new(arcstr::literal!("peer.example"))
+            },
+            load: None,
+            verified: false,
+        };
+        assert_eq!(
+            super::dial_addrs(&resolver, &server, 1).await.unwrap(),
+            vec![SocketAddr::new(ips[1], 443), SocketAddr::new(ips[0], 443)]
+        );
+    }
+}
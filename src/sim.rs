@@ -0,0 +1,169 @@
+//! Deterministic simulation of nodes, for debugging distributed features.
+//!
+//! Within [`Sim::run`], the randomness and the clock of this crate are derived from a seed, and
+//! tasks are interleaved by a single-threaded runtime whose clock only advances when every task
+//! is idle. Running the same simulation with the same seed makes the same decisions in the same
+//! order, so a failure found with one seed can be replayed.
+//!
+//! The keys generated within a simulation are predictable from its seed, so this module must not
+//! be used outside of tests.
+
+use std::cell::{Cell, RefCell};
+
+use futures::Future;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use tokio::time::Instant;
+
+/// The time simulations start at by default, in milliseconds since January 1 1970.
+pub const DEFAULT_EPOCH: u64 = 1_700_000_000_000;
+
+thread_local! {
+    /// The RNG of the simulation running on this thread.
+    static RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+    /// The instant the simulation running on this thread started, and its epoch.
+    static CLOCK: Cell<Option<(Instant, u64)>> = const { Cell::new(None) };
+}
+
+/// A seeded simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sim {
+    seed: u64,
+    epoch: u64,
+}
+
+impl Sim {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            epoch: DEFAULT_EPOCH,
+        }
+    }
+    /// Sets the time the simulation starts at, in milliseconds since January 1 1970.
+    pub fn epoch(self, epoch: u64) -> Self {
+        Self { epoch, ..self }
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Runs `fut` to completion in the simulation, on the current thread.
+    ///
+    /// # Panics
+    /// Panics if called within a simulation or a tokio runtime.
+    pub fn run<F: Future>(&self, fut: F) -> F::Output {
+        assert!(CLOCK.get().is_none(), "simulations cannot be nested");
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .expect("failed to build the simulation runtime");
+
+        RNG.set(Some(StdRng::seed_from_u64(self.seed)));
+        // resets the simulation even if `fut` panics
+        let _reset = Reset;
+
+        runtime.block_on(async {
+            CLOCK.set(Some((Instant::now(), self.epoch)));
+            fut.await
+        })
+    }
+}
+
+struct Reset;
+
+impl Drop for Reset {
+    fn drop(&mut self) {
+        RNG.set(None);
+        CLOCK.set(None);
+    }
+}
+
+/// Returns the time in the simulation running on this thread, if any.
+pub(crate) fn now() -> Option<u64> {
+    CLOCK
+        .get()
+        .map(|(start, epoch)| epoch + start.elapsed().as_millis() as u64)
+}
+
+/// Returns whether a simulation is running on this thread.
+pub(crate) fn running() -> bool {
+    RNG.with_borrow(Option::is_some)
+}
+
+/// The RNG of the simulation running on the current thread. Outside of a simulation, is the
+/// thread-local RNG.
+///
+/// Is not a [`CryptoRng`](rand::CryptoRng): its output is predictable from the seed of the
+/// simulation.
+#[derive(Debug, Clone, Default)]
+pub struct SimRng;
+
+impl SimRng {
+    fn with<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        RNG.with_borrow_mut(|rng| match rng {
+            Some(rng) => f(rng),
+            None => f(&mut rand::thread_rng()),
+        })
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        Self::with(|rng| rng.next_u32())
+    }
+    fn next_u64(&mut self) -> u64 {
+        Self::with(|rng| rng.next_u64())
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        Self::with(|rng| rng.fill_bytes(dest))
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        Self::with(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::BuildHasher;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::crypto::{KeyPair, PublicKey};
+    use crate::node::ServerHandle;
+    use crate::utils;
+
+    fn simulate(seed: u64) -> (PublicKey, PublicKey, u64, u64) {
+        Sim::new(seed).run(async {
+            let server_hdl: Arc<ServerHandle<()>> = ServerHandle::new_hdl();
+            let pair = KeyPair::generate();
+            let start = utils::now();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+
+            (server_hdl.public_key(), pair.public, start, utils::now())
+        })
+    }
+
+    #[test]
+    fn deterministic() {
+        let first = simulate(1);
+        assert_eq!(first, simulate(1));
+        assert_ne!(first.0, simulate(2).0);
+
+        // the clock starts at the epoch, and sleeping advances it without waiting
+        assert_eq!(first.2, DEFAULT_EPOCH);
+        assert_eq!(first.3, DEFAULT_EPOCH + 60_000);
+
+        // the collections of a simulation iterate in the same order in every run
+        let hash = || utils::RandomState::default().hash_one(1);
+        assert_eq!(
+            Sim::new(1).run(async { hash() }),
+            Sim::new(2).run(async { hash() })
+        );
+
+        // outside of a simulation, the crate is random again
+        assert_ne!(KeyPair::generate().public, KeyPair::generate().public);
+        assert_ne!(hash(), hash());
+    }
+}
@@ -0,0 +1,487 @@
+//! Helpers to test integrations against the nodes of this crate, from a single endpoint to
+//! topologies of several federated servers with their clients.
+//!
+//! A [`Scenario`] describes the servers, the links between them, the clients connected to them and
+//! the steps to run, and builds a [`Topology`] that the test inspects or drives further. Endpoints
+//! are backed by a [`TestConn`], which records what the node pushes to them.
+//!
+//! The keys generated by this module are not meant to be used outside of tests.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use arcstr::ArcStr;
+use thiserror::Error;
+use tower_async::Service;
+
+use crate::crypto::{KeyPair, KeyTriad, PrivateKey, PublicKey};
+use crate::mock::MockPush;
+use crate::node::error::{StreamOpenError, StreamOpenErrorType};
+use crate::node::{InboundEndpoint, InboundHdl, NodeConfig, Notify, OpenStream, ServerHandle};
+use crate::obj::{
+    EndpointInfo, ErrorResp, IdentifyResp, Introduction, KeysExistsReq, Mail, PreIdentifyReq,
+    Receipt, RevokeReq, ServerInfo, SignMessageType, SignedData, SignedFormat,
+};
+use crate::utils::{self, LockExt};
+
+/// The endpoint info of the clients of a [`Topology`].
+pub const ENDPOINT_INFO: EndpointInfo = EndpointInfo::non_server(SocketAddr::new(
+    IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+    51763,
+));
+
+/// Returns the endpoint info of a server reachable at `domain`.
+pub fn server_info(domain: impl Into<ArcStr>) -> EndpointInfo {
+    EndpointInfo {
+        server_info: Some(ServerInfo::new(domain.into())),
+        ..ENDPOINT_INFO
+    }
+}
+
+/// Signs the identify data handed out to `hdl` with `key`.
+pub async fn identify_triad<C>(hdl: &InboundEndpoint<C>, key: &PrivateKey) -> KeyTriad<SignedData> {
+    let identify = hdl
+        .pre_identify(PreIdentifyReq {})
+        .await
+        .expect("the node limited the rate of pre-identify requests");
+    KeyTriad::gen_signed(
+        key,
+        &identify,
+        SignMessageType::Identify,
+        SignedFormat::Cbor,
+    )
+    .expect("identify data is serializable")
+}
+
+/// Identifies `hdl` as the public key of `key`.
+///
+/// # Panics
+/// Panics if the node rejects the key.
+pub async fn identify<C: Notify + Send + Sync + 'static>(
+    hdl: &InboundHdl<C>,
+    key: &PrivateKey,
+) -> IdentifyResp {
+    let triad = identify_triad(hdl, key).await;
+    hdl.identify(triad)
+        .await
+        .expect("the node rejected the key")
+}
+
+/// The error of a [`TestConn`] asked to open a stream it declined.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[error("declined")]
+pub struct DeclinedError;
+
+impl StreamOpenError for DeclinedError {
+    fn error_type(&self) -> Option<StreamOpenErrorType> {
+        Some(StreamOpenErrorType::EndpointDeclined)
+    }
+}
+
+/// A connection that records the messages the node pushes to it. Clones share the record.
+/// Opening a stream to it yields the public key of the initiator.
+#[derive(Debug, Clone, Default)]
+pub struct TestConn {
+    pushes: Arc<Mutex<Vec<MockPush>>>,
+}
+
+impl TestConn {
+    /// Returns the messages pushed so far, oldest first.
+    pub fn pushes(&self) -> Vec<MockPush> {
+        self.pushes.locked().clone()
+    }
+    /// Returns the messages pushed so far, and forgets them.
+    pub fn take_pushes(&self) -> Vec<MockPush> {
+        std::mem::take(&mut *self.pushes.locked())
+    }
+    fn push(&self, push: MockPush) -> Result<(), Infallible> {
+        self.pushes.locked().push(push);
+        Ok(())
+    }
+}
+
+impl Notify for TestConn {
+    type Err = Infallible;
+
+    async fn notify_connected(&self, triad: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        self.push(MockPush::Connected(triad.clone()))
+    }
+    async fn notify_introduced(&self, intro: &Introduction) -> Result<(), Self::Err> {
+        self.push(MockPush::Introduced(*intro))
+    }
+    async fn notify_revoked(&self, revocation: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        self.push(MockPush::Revoked(revocation.clone()))
+    }
+    async fn notify_receipt(&self, receipt: &KeyTriad<Receipt>) -> Result<(), Self::Err> {
+        self.push(MockPush::Receipt(*receipt))
+    }
+    async fn notify_mail(&self, mail: &Mail) -> Result<(), Self::Err> {
+        self.push(MockPush::Mail(mail.clone()))
+    }
+    async fn notify_broadcast(&self, payload: &KeyTriad<SignedData>) -> Result<(), Self::Err> {
+        self.push(MockPush::Broadcast(payload.clone()))
+    }
+}
+impl Service<PublicKey> for TestConn {
+    type Response = PublicKey;
+    type Error = DeclinedError;
+
+    async fn call(&self, key: PublicKey) -> Result<Self::Response, Self::Error> {
+        Ok(key)
+    }
+}
+impl OpenStream for TestConn {
+    type Err = DeclinedError;
+}
+
+/// An error of a [`Scenario`].
+#[derive(Error, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ScenarioError {
+    #[error("there is no server named {0}")]
+    UnknownServer(String),
+    #[error("there is no client named {0}")]
+    UnknownClient(String),
+    #[error("the name {0} is taken")]
+    NameTaken(String),
+    /// The server could not be started.
+    #[error("failed to start the server: {0}")]
+    Spawn(std::io::ErrorKind),
+    /// The node rejected a step.
+    #[error("step {step} was rejected: {err}")]
+    Rejected { step: usize, err: ErrorResp },
+}
+
+/// A step of a [`Scenario`]. Clients are referred to by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Step {
+    /// The client identifies as its key.
+    Identify { client: String },
+    /// The client asks to be notified when the keys of other clients identify.
+    Subscribe { client: String, keys: Vec<String> },
+    /// The client no longer waits for the keys of other clients.
+    Unsubscribe { client: String, keys: Vec<String> },
+    /// The client revokes its key.
+    Revoke { client: String },
+    /// The connection of the client drops.
+    Disconnect { client: String },
+}
+
+impl Step {
+    pub fn identify(client: impl Into<String>) -> Self {
+        Self::Identify {
+            client: client.into(),
+        }
+    }
+    pub fn subscribe<S: Into<String>>(
+        client: impl Into<String>,
+        keys: impl IntoIterator<Item = S>,
+    ) -> Self {
+        Self::Subscribe {
+            client: client.into(),
+            keys: keys.into_iter().map(Into::into).collect(),
+        }
+    }
+    pub fn unsubscribe<S: Into<String>>(
+        client: impl Into<String>,
+        keys: impl IntoIterator<Item = S>,
+    ) -> Self {
+        Self::Unsubscribe {
+            client: client.into(),
+            keys: keys.into_iter().map(Into::into).collect(),
+        }
+    }
+    pub fn revoke(client: impl Into<String>) -> Self {
+        Self::Revoke {
+            client: client.into(),
+        }
+    }
+    pub fn disconnect(client: impl Into<String>) -> Self {
+        Self::Disconnect {
+            client: client.into(),
+        }
+    }
+}
+
+/// Describes a topology of servers and clients, and the steps to run on it.
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    servers: Vec<(String, NodeConfig)>,
+    links: Vec<(String, String)>,
+    clients: Vec<(String, String)>,
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a server with the default configuration.
+    pub fn server(self, name: impl Into<String>) -> Self {
+        self.server_with(name, NodeConfig::default())
+    }
+    /// Adds a server with `config`.
+    pub fn server_with(mut self, name: impl Into<String>, config: NodeConfig) -> Self {
+        self.servers.push((name.into(), config));
+        self
+    }
+    /// Connects the servers `a` and `b` to each other, each identified as the key of its node.
+    pub fn link(mut self, a: impl Into<String>, b: impl Into<String>) -> Self {
+        self.links.push((a.into(), b.into()));
+        self
+    }
+    /// Connects a client with a new key to `server`. The client does not identify until a
+    /// [`Step::Identify`].
+    pub fn client(mut self, name: impl Into<String>, server: impl Into<String>) -> Self {
+        self.clients.push((name.into(), server.into()));
+        self
+    }
+    /// Adds a step, run after the steps added before it.
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+    /// Builds the topology and runs the steps on it, stopping at the first step that fails.
+    pub async fn run(self) -> Result<Topology, ScenarioError> {
+        let mut topology = Topology::default();
+        for (name, config) in self.servers {
+            topology.add_server(name, config)?;
+        }
+        for (a, b) in self.links {
+            topology.link(&a, &b).await?;
+        }
+        for (name, server) in self.clients {
+            topology.connect(name, &server)?;
+        }
+        for (step, action) in self.steps.into_iter().enumerate() {
+            topology.apply(action).await.map_err(|err| match err {
+                ScenarioError::Rejected { err, .. } => ScenarioError::Rejected { step, err },
+                err => err,
+            })?;
+        }
+
+        Ok(topology)
+    }
+}
+
+/// A server of a [`Topology`].
+#[derive(Debug)]
+pub struct TestServer {
+    keys: KeyPair,
+    hdl: Arc<ServerHandle<TestConn>>,
+    /// The endpoints of the linked servers on this server, by name.
+    peers: BTreeMap<String, InboundHdl<TestConn>>,
+}
+
+impl TestServer {
+    pub fn hdl(&self) -> &Arc<ServerHandle<TestConn>> {
+        &self.hdl
+    }
+    /// Returns the key the node signs its attestations with.
+    pub fn keys(&self) -> &KeyPair {
+        &self.keys
+    }
+    /// Returns the endpoint of the linked server `name` on this server.
+    pub fn peer(&self, name: &str) -> Option<&InboundHdl<TestConn>> {
+        self.peers.get(name)
+    }
+}
+
+/// A client of a [`Topology`].
+#[derive(Debug)]
+pub struct TestClient {
+    keys: KeyPair,
+    server: String,
+    endpoint: InboundHdl<TestConn>,
+    conn: TestConn,
+}
+
+impl TestClient {
+    pub fn keys(&self) -> &KeyPair {
+        &self.keys
+    }
+    pub fn public_key(&self) -> PublicKey {
+        self.keys.public
+    }
+    /// Returns the name of the server the client is connected to.
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+    /// Returns the endpoint of the client on its server.
+    pub fn endpoint(&self) -> &InboundHdl<TestConn> {
+        &self.endpoint
+    }
+    /// Returns the connection of the client, which records what its server pushed to it.
+    pub fn conn(&self) -> &TestConn {
+        &self.conn
+    }
+}
+
+/// The servers and clients built by a [`Scenario`].
+#[derive(Debug, Default)]
+pub struct Topology {
+    servers: BTreeMap<String, TestServer>,
+    clients: BTreeMap<String, TestClient>,
+    /// The id of the next endpoint.
+    next_id: u64,
+}
+
+impl Topology {
+    /// Returns the server `name`.
+    ///
+    /// # Panics
+    /// Panics if there is no such server.
+    pub fn server(&self, name: &str) -> &TestServer {
+        self.servers
+            .get(name)
+            .unwrap_or_else(|| panic!("there is no server named {name}"))
+    }
+    /// Returns the client `name`.
+    ///
+    /// # Panics
+    /// Panics if there is no such client.
+    pub fn client(&self, name: &str) -> &TestClient {
+        self.clients
+            .get(name)
+            .unwrap_or_else(|| panic!("there is no client named {name}"))
+    }
+    pub fn servers(&self) -> impl Iterator<Item = (&str, &TestServer)> {
+        self.servers.iter().map(|(name, server)| (&**name, server))
+    }
+    pub fn clients(&self) -> impl Iterator<Item = (&str, &TestClient)> {
+        self.clients.iter().map(|(name, client)| (&**name, client))
+    }
+    /// Adds a server with `config`.
+    pub fn add_server(
+        &mut self,
+        name: impl Into<String>,
+        config: NodeConfig,
+    ) -> Result<&TestServer, ScenarioError> {
+        let name = name.into();
+        if self.servers.contains_key(&name) {
+            return Err(ScenarioError::NameTaken(name));
+        }
+
+        let keys = KeyPair::generate();
+        let server = TestServer {
+            hdl: Arc::new(
+                ServerHandle::with_key(config, keys.private.clone())
+                    .map_err(|err| ScenarioError::Spawn(err.kind()))?,
+            ),
+            keys,
+            peers: BTreeMap::new(),
+        };
+        Ok(self.servers.entry(name).or_insert(server))
+    }
+    /// Connects the servers `a` and `b` to each other. Refer to [`Scenario::link`].
+    pub async fn link(&mut self, a: &str, b: &str) -> Result<(), ScenarioError> {
+        for (from, to) in [(a, b), (b, a)] {
+            let id = self.next_id();
+            let remote = self.get_server(from)?.keys.private.clone();
+            let server = self
+                .servers
+                .get_mut(to)
+                .ok_or_else(|| ScenarioError::UnknownServer(to.into()))?;
+
+            let peer = InboundEndpoint::server_hdl(
+                id,
+                server_info(format!("{from}.test")),
+                server.hdl.clone(),
+                TestConn::default(),
+            );
+            identify(&peer, &remote).await;
+            let _ = server.hdl.connect_server(peer.clone()).await;
+            server.peers.insert(from.into(), peer);
+        }
+
+        Ok(())
+    }
+    /// Connects a client with a new key to `server`. Refer to [`Scenario::client`].
+    pub fn connect(
+        &mut self,
+        name: impl Into<String>,
+        server: &str,
+    ) -> Result<&TestClient, ScenarioError> {
+        let name = name.into();
+        if self.clients.contains_key(&name) {
+            return Err(ScenarioError::NameTaken(name));
+        }
+        let id = self.next_id();
+        let hdl = self.get_server(server)?.hdl.clone();
+
+        let conn = TestConn::default();
+        let client = TestClient {
+            keys: KeyPair::generate(),
+            server: server.into(),
+            endpoint: InboundEndpoint::server_hdl(id, ENDPOINT_INFO, hdl, conn.clone()),
+            conn,
+        };
+        Ok(self.clients.entry(name).or_insert(client))
+    }
+    /// Runs `step`. The step of a [`ScenarioError::Rejected`] is always 0.
+    pub async fn apply(&mut self, step: Step) -> Result<(), ScenarioError> {
+        let rejected = |err| ScenarioError::Rejected { step: 0, err };
+
+        match step {
+            Step::Identify { client } => {
+                let client = self.get_client(&client)?;
+                let triad = identify_triad(&client.endpoint, &client.keys.private).await;
+                client
+                    .endpoint
+                    .identify(triad)
+                    .await
+                    .map_err(|err| rejected(err.into()))?;
+            }
+            Step::Subscribe { client, keys } => {
+                let keys = self.public_keys(&keys)?;
+                self.get_client(&client)?
+                    .endpoint
+                    .keys_exists(KeysExistsReq { keys, notify: true })
+                    .await
+                    .map_err(|err| rejected(err.into()))?;
+            }
+            Step::Unsubscribe { client, keys } => {
+                let keys = self.public_keys(&keys)?;
+                let client = self.get_client(&client)?;
+                let server = self.get_server(&client.server)?;
+                server.hdl.unsubscribe(&client.endpoint, &keys).await;
+            }
+            Step::Revoke { client } => {
+                let client = self.get_client(&client)?;
+                let revocation = KeyTriad::revoke(&client.keys.private, utils::now());
+                client
+                    .endpoint
+                    .revoke(RevokeReq { revocation })
+                    .await
+                    .map_err(|err| rejected(err.into()))?;
+            }
+            Step::Disconnect { client } => {
+                self.get_client(&client)?.endpoint.disconnect().await;
+            }
+        }
+
+        Ok(())
+    }
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+    fn get_server(&self, name: &str) -> Result<&TestServer, ScenarioError> {
+        self.servers
+            .get(name)
+            .ok_or_else(|| ScenarioError::UnknownServer(name.into()))
+    }
+    fn get_client(&self, name: &str) -> Result<&TestClient, ScenarioError> {
+        self.clients
+            .get(name)
+            .ok_or_else(|| ScenarioError::UnknownClient(name.into()))
+    }
+    fn public_keys(&self, clients: &[String]) -> Result<Vec<PublicKey>, ScenarioError> {
+        clients
+            .iter()
+            .map(|name| Ok(self.get_client(name)?.keys.public))
+            .collect()
+    }
+}
@@ -0,0 +1,149 @@
+//! Round trips messages between the shapes of older and newer versions of the protocol, to check
+//! that peers of either version can still talk to each other.
+//!
+//! The old shapes are written out by hand the way the types were before fields were added to
+//! them, and the newer shapes carry fields that the current types do not know of.
+
+use std::fmt::Debug;
+
+use arcstr::ArcStr;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::crypto::KeyPair;
+use crate::obj::*;
+use crate::CURRENT_VERSION;
+
+/// Encodes `value` in every encoding of the protocol, and decodes it as the shape `T` of another
+/// version. Panics if the encodings disagree.
+fn convert<F: Serialize, T: DeserializeOwned + PartialEq + Debug>(value: &F) -> T {
+    let cbor: T = serde_cbor::from_slice(&serde_cbor::to_vec(value).unwrap()).unwrap();
+    let json: T = serde_json::from_slice(&serde_json::to_vec(value).unwrap()).unwrap();
+    assert_eq!(cbor, json);
+    cbor
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct OldServerInfo {
+    domain: ArcStr,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct NewerServerInfo {
+    #[serde(flatten)]
+    server_info: ServerInfo,
+    region: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct OldIdentifyData {
+    salt: [u8; SALT_SIZE],
+    #[serde(rename = "startTime")]
+    start_time: u64,
+    #[serde(rename = "expireTime")]
+    expire_time: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct OldTagged<T> {
+    id: u64,
+    body: T,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum OldReqMessage {
+    #[serde(rename = "KEYS_EXISTS")]
+    KeysExists(OldKeysExistsReq),
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct OldKeysExistsReq {
+    keys: Vec<crate::crypto::PublicKey>,
+}
+
+#[test]
+fn server_info() {
+    let old = OldServerInfo {
+        domain: arcstr::literal!("old.example"),
+    };
+    let new: ServerInfo = convert(&old);
+    assert_eq!(new, ServerInfo::new(arcstr::literal!("old.example")));
+
+    let new = ServerInfo {
+        ports: vec![443],
+        transports: vec![Transport::Quic],
+        api_version: Some(CURRENT_VERSION),
+        public_key: Some(KeyPair::generate().public),
+        ..ServerInfo::new(arcstr::literal!("new.example"))
+    };
+    assert_eq!(convert::<_, OldServerInfo>(&new).domain, new.domain);
+
+    // fields added by newer versions are ignored
+    let newer = NewerServerInfo {
+        server_info: new.clone(),
+        region: "eu".into(),
+    };
+    assert_eq!(convert::<_, ServerInfo>(&newer), new);
+}
+
+#[test]
+fn identify_data() {
+    let old = OldIdentifyData {
+        salt: [7; SALT_SIZE],
+        start_time: 1,
+        expire_time: 2,
+    };
+    let new: IdentifyData = convert(&old);
+    assert_eq!(
+        (new.salt, new.start_time, new.expire_time),
+        (old.salt, 1, 2)
+    );
+    assert!(new.extensions.is_empty());
+    assert_eq!((new.difficulty, new.work), (None, None));
+    assert!(new.usage.is_empty());
+
+    let new = IdentifyData {
+        difficulty: Some(8),
+        work: Some(3),
+        ..new
+    };
+    assert_eq!(convert::<_, OldIdentifyData>(&new), old);
+}
+
+#[test]
+fn envelopes() {
+    let key = KeyPair::generate().public;
+
+    // messages of peers that predate versioned envelopes and newer fields
+    let old = OldTagged {
+        id: 3,
+        body: OldReqMessage::KeysExists(OldKeysExistsReq { keys: vec![key] }),
+    };
+    let new: Tagged<ReqMessage> = convert(&old);
+    assert_eq!(new.id, 3);
+    assert_eq!(new.version, None);
+    assert_eq!(
+        new.body,
+        ReqMessage::KeysExists(KeysExistsReq {
+            keys: vec![key],
+            notify: false,
+        })
+    );
+
+    // and the other way around
+    let new = Tagged::new(
+        4,
+        ReqMessage::KeysExists(KeysExistsReq {
+            keys: vec![key],
+            notify: true,
+        }),
+    );
+    assert_eq!(new.version, Some(CURRENT_VERSION));
+    let old: OldTagged<OldReqMessage> = convert(&new);
+    assert_eq!(
+        old,
+        OldTagged {
+            id: 4,
+            body: OldReqMessage::KeysExists(OldKeysExistsReq { keys: vec![key] }),
+        }
+    );
+}
@@ -1 +1 @@
-
+mod compat;
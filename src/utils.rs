@@ -1,9 +1,101 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::BuildHasher;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Gets the current time as millseconds since January 1 1970.
+/// The hasher of the collections the node iterates over. Is random, except for the collections
+/// created within a simulation of the `sim` feature, so that the iteration order is the same in
+/// every run of the simulation.
+#[derive(Debug, Clone)]
+pub(crate) struct RandomState(Option<std::collections::hash_map::RandomState>);
+
+impl Default for RandomState {
+    fn default() -> Self {
+        #[cfg(feature = "sim")]
+        if crate::sim::running() {
+            return Self(None);
+        }
+
+        Self(Some(Default::default()))
+    }
+}
+
+impl BuildHasher for RandomState {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        match &self.0 {
+            Some(state) => state.build_hasher(),
+            None => DefaultHasher::new(),
+        }
+    }
+}
+
+/// Gets the current time as millseconds since January 1 1970. A clock set before then reads as
+/// 0, which [`ServerHandle::diagnose`](crate::node::ServerHandle::diagnose) reports.
 pub fn now() -> u64 {
+    #[cfg(feature = "sim")]
+    if let Some(now) = crate::sim::now() {
+        return now;
+    }
+
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64
+        .map_or(0, |time| time.as_millis() as u64)
+}
+
+/// Locks a [`Mutex`], recovering it if a thread panicked while holding it, so that a panic in a
+/// single task does not spread to every task that shares the lock.
+pub(crate) trait LockExt<T: ?Sized> {
+    fn locked(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T: ?Sized> LockExt<T> for Mutex<T> {
+    fn locked(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Returns the RNG keys and nonces are generated with by default. Is the thread-local RNG, or the
+/// RNG of the running simulation in the `sim` feature.
+#[cfg(not(feature = "sim"))]
+pub(crate) fn rng() -> rand::rngs::ThreadRng {
+    rand::thread_rng()
+}
+#[cfg(feature = "sim")]
+pub(crate) fn rng() -> SimCryptoRng {
+    SimCryptoRng(crate::sim::SimRng)
+}
+
+/// The RNG of [`rng`] in the `sim` feature. Within a simulation, the keys it generates are as
+/// predictable as the seed of the simulation, which is why [`SimRng`](crate::sim::SimRng) itself
+/// is not a [`CryptoRng`](rand::CryptoRng), and only the crate generates keys with it.
+#[cfg(feature = "sim")]
+pub(crate) struct SimCryptoRng(crate::sim::SimRng);
+
+#[cfg(feature = "sim")]
+impl rand::RngCore for SimCryptoRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+// outside of a simulation, the RNG is the thread-local RNG
+#[cfg(feature = "sim")]
+impl rand::CryptoRng for SimCryptoRng {}
+
+/// Generates random bytes using [`rng`].
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::RngCore::fill_bytes(&mut rng(), &mut bytes);
+    bytes
 }